@@ -0,0 +1,108 @@
+//! Compare direct convolution against overlap-save across filter lengths,
+//! to place `FAST_CONVOLUTION_TAPS`.
+//!
+//! Run with `cargo bench --bench conv --features fft`. Prints the throughput
+//! of both paths for each tap count and chunk size, and the shortest filter
+//! from which overlap-save is faster at every longer length. Exits with an
+//! error if `FAST_CONVOLUTION_TAPS` is more than a factor of two from the
+//! latest of those crossovers.
+
+use std::hint::black_box;
+use std::process;
+use std::time::{Duration, Instant};
+
+use radion::dsp::fir::Filter;
+use radion::dsp::{Complex, OverlapSave, FAST_CONVOLUTION_TAPS};
+
+/// Filter lengths compared.
+const TAPS: [usize; 12] = [8, 12, 16, 24, 32, 48, 64, 96, 128, 192, 256, 512];
+
+/// Samples per call. Overlap-save pads a partial block with zeros, so short
+/// chunks are its worst case; whole USB transfers are its best.
+const CHUNKS: [(usize, &str); 2] = [(256, "256-sample chunks"), (8 * 1024, "16 KiB transfers")];
+
+/// Tap-samples filtered per timed run, so every length takes a similar
+/// time on the direct path.
+const WORK: usize = 200_000_000;
+
+/// Timed runs per length, of which the fastest is kept. The two paths take
+/// turns so that a slow spell on a busy machine hits both.
+const RUNS: u32 = 5;
+
+/// Time one call of `f`, over `rounds` calls.
+fn time(rounds: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    start.elapsed() / rounds
+}
+
+/// Find the shortest filter from which overlap-save wins at every length.
+fn crossover(chunk: usize) -> Option<usize> {
+    let input: Vec<Complex<f32>> = (0..chunk)
+        .map(|i| Complex::new((i as f32 * 0.1).sin(), (i as f32 * 0.1).cos()))
+        .collect();
+    let mut buf = input.clone();
+    let mut crossover = None;
+
+    for &len in &TAPS {
+        let taps: Vec<f32> = (0..len).map(|i| 1.0 / (i + 1) as f32).collect();
+        let mut direct = Filter::<Complex<f32>>::new(&taps);
+        let mut fast = OverlapSave::new(&taps);
+        let rounds = (WORK / chunk / len).max(1) as u32;
+
+        let mut base = Duration::MAX;
+        let mut ols = Duration::MAX;
+        for _ in 0..RUNS {
+            base = base.min(time(rounds, || {
+                buf.copy_from_slice(&input);
+                direct.filter_in_place(black_box(&mut buf));
+            }));
+            ols = ols.min(time(rounds, || {
+                buf.copy_from_slice(&input);
+                fast.filter_in_place(black_box(&mut buf));
+            }));
+        }
+
+        let rate = |d: Duration| chunk as f64 / d.as_secs_f64() / 1e6;
+        println!(
+            "{:>4} taps  direct {:>8.1} MS/s, overlap-save {:>8.1} MS/s",
+            len,
+            rate(base),
+            rate(ols)
+        );
+        match crossover {
+            None if ols < base => crossover = Some(len),
+            Some(_) if ols >= base => crossover = None,
+            _ => {}
+        }
+    }
+    crossover
+}
+
+fn main() {
+    let mut latest = 0;
+    for &(chunk, label) in &CHUNKS {
+        println!("{}:", label);
+        match crossover(chunk) {
+            Some(taps) => {
+                println!("crossover at {} taps\n", taps);
+                latest = latest.max(taps);
+            }
+            None => {
+                eprintln!("overlap-save never overtakes direct convolution");
+                process::exit(1);
+            }
+        }
+    }
+
+    println!(
+        "FAST_CONVOLUTION_TAPS is {}, latest crossover {} taps",
+        FAST_CONVOLUTION_TAPS, latest
+    );
+    if FAST_CONVOLUTION_TAPS * 2 < latest || FAST_CONVOLUTION_TAPS > latest * 2 {
+        eprintln!("FAST_CONVOLUTION_TAPS is more than a factor of two from the crossover");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,90 @@
+//! Compare the naive cu8 to complex conversion against `to_complex_f32_into`.
+//!
+//! Run with `cargo bench --bench convert`. Exits with an error if the
+//! speedup on a cache-resident block is below 4x.
+
+use std::hint::black_box;
+use std::process;
+use std::time::{Duration, Instant};
+
+use radion::dsp::Complex;
+use radion::samples::{conversion_path, to_complex_f32_into, Scaling};
+
+/// The speedup the conversion must reach on a block that stays in cache.
+const TARGET: f64 = 4.0;
+
+/// Buffer sizes in samples. Only the first is checked against `TARGET`: an
+/// 8 KiB block, whose 32 KiB of output stays in L1 as it does when a
+/// pipeline converts a block and filters it straight away. The others
+/// convert whole buffers at once and are limited by how fast the output
+/// can be written to memory, not by the conversion.
+const SIZES: [(usize, &str); 3] = [
+    (4 * 1024, "8 KiB block"),
+    (8 * 1024, "16 KiB transfer"),
+    (512 * 1024, "1 MiB capture"),
+];
+
+/// Samples converted per timed run, so every size takes a similar time.
+const WORK: usize = 100_000_000;
+
+/// Timed runs per size, of which the fastest is kept. The two conversions
+/// take turns so that a slow spell on a busy machine hits both.
+const RUNS: u32 = 9;
+
+fn naive(buf: &[u8], out: &mut [Complex<f32>]) {
+    for (o, pair) in out.iter_mut().zip(buf.chunks_exact(2)) {
+        *o = Complex::new(
+            (pair[0] as f32 - 127.5) / 127.5,
+            (pair[1] as f32 - 127.5) / 127.5,
+        );
+    }
+}
+
+/// Time one call of `f`, over `rounds` calls.
+fn time(rounds: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    start.elapsed() / rounds
+}
+
+fn main() {
+    println!("path: {}", conversion_path());
+    let mut checked = 0.0;
+    for (i, &(samples, label)) in SIZES.iter().enumerate() {
+        let buf: Vec<u8> = (0..samples * 2).map(|i| (i * 31 % 256) as u8).collect();
+        let mut out = vec![Complex::new(0.0, 0.0); samples];
+        let rounds = (WORK / samples) as u32;
+
+        let mut base = Duration::MAX;
+        let mut fast = Duration::MAX;
+        for _ in 0..RUNS {
+            base = base.min(time(rounds, || naive(black_box(&buf), black_box(&mut out))));
+            fast = fast.min(time(rounds, || {
+                to_complex_f32_into(black_box(&buf), black_box(&mut out), Scaling::Normalized);
+            }));
+        }
+
+        let rate = |d: Duration| samples as f64 / d.as_secs_f64() / 1e6;
+        let speedup = base.as_secs_f64() / fast.as_secs_f64();
+        println!(
+            "{:<16} naive {:>8.1} MS/s, fast {:>8.1} MS/s, speedup {:>5.2}x",
+            label,
+            rate(base),
+            rate(fast),
+            speedup
+        );
+        if i == 0 {
+            checked = speedup;
+        }
+    }
+
+    if checked < TARGET {
+        eprintln!(
+            "speedup on the {} is {:.2}x, below the {:.1}x target",
+            SIZES[0].1, checked, TARGET
+        );
+        process::exit(1);
+    }
+}
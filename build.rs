@@ -1,3 +1,23 @@
+use std::env;
+
 fn main() {
-    println!("cargo:rustc-link-lib=rtlsdr");
+    println!("cargo:rerun-if-env-changed=RTLSDR_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=RTLSDR_STATIC");
+
+    // A librtlsdr bundled with the application instead of a system one,
+    // e.g. the osmocom Windows release.
+    if let Ok(dir) = env::var("RTLSDR_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+    if env::var("RTLSDR_STATIC").is_ok_and(|v| v != "0") {
+        println!("cargo:rustc-link-lib=static=rtlsdr");
+        // A static librtlsdr leaves libusb to the application.
+        let libusb = match env::var("CARGO_CFG_TARGET_ENV").as_deref() {
+            Ok("msvc") => "libusb-1.0",
+            _ => "usb-1.0",
+        };
+        println!("cargo:rustc-link-lib={}", libusb);
+    } else {
+        println!("cargo:rustc-link-lib=rtlsdr");
+    }
 }
@@ -1,4 +1,4 @@
-use radion::Device;
+use radion::{Device, Frequency, SampleRate};
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
@@ -19,6 +19,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         device.set_tuner_gain(max_gain)?;
     }
 
+    println!(
+        "Receiving at {} with {}",
+        Frequency::from(device.get_center_freq()?),
+        SampleRate::from(device.get_sample_rate()?)
+    );
+
     // Reset the buffer before reading
     device.reset_buffer()?;
 
@@ -0,0 +1,34 @@
+//! Audio output through cpal.
+//!
+//! `AudioSink` plays demodulated mono or stereo audio on a sound card,
+//! resampling it to the device's rate and queueing it in an adjustable
+//! buffer, and counts the underruns when the queue runs dry. With the
+//! `pipeline` feature it is also a `Block`, passing the audio on after
+//! playing it.
+//!
+//! ```no_run
+//! use radion::audio::AudioSink;
+//! use radion::demod::FmDemod;
+//! use radion::dsp::fir::ChannelDecimator;
+//! use radion::pipeline::{Pipeline, ToComplex};
+//! use radion::Device;
+//!
+//! let device = Device::new(0)?;
+//! device.set_center_freq(98_500_000)?;
+//! device.set_sample_rate(1_536_000)?;
+//!
+//! // Mono broadcast FM, down to 192 kHz and then 48 kHz audio.
+//! Pipeline::from_sdr(device, 16 * 16384)
+//!     .then(ToComplex::default())
+//!     .then(ChannelDecimator::new(8, 63))
+//!     .then(FmDemod::new(192_000.0, 75_000.0))
+//!     .then(AudioSink::<f32>::open(192_000.0)?)
+//!     .sink(|_| Ok(()))
+//!     .run()?;
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod resample;
+mod sink;
+
+pub use sink::{output_devices, AudioFrame, AudioSink};
@@ -0,0 +1,96 @@
+use std::f64::consts::PI;
+
+/// Taps of the interpolation filter.
+const TAPS: usize = 32;
+
+/// Fractional positions the filter is tabulated at.
+const PHASES: usize = 256;
+
+/// Windowed-sinc resampler for interleaved audio, with a ratio that can be
+/// trimmed while running.
+pub(crate) struct Resampler {
+    channels: usize,
+    /// Input frames per output frame.
+    step: f64,
+    /// Trim applied to `step`.
+    trim: f64,
+    /// `PHASES + 1` rows of `TAPS` taps.
+    table: Vec<f32>,
+    /// Interleaved input not yet fully used.
+    history: Vec<f32>,
+    /// The next output's position in `history`, in frames.
+    position: f64,
+}
+
+impl Resampler {
+    pub(crate) fn new(input_rate: f64, output_rate: f64, channels: usize) -> Self {
+        let step = input_rate / output_rate;
+        // Cut off below the lower of the two Nyquist frequencies.
+        let cutoff = 0.95 * step.recip().min(1.0);
+        let centre = (TAPS / 2 - 1) as f64;
+        let mut table = Vec::with_capacity((PHASES + 1) * TAPS);
+        for phase in 0..=PHASES {
+            let frac = phase as f64 / PHASES as f64;
+            let row: Vec<f64> = (0..TAPS)
+                .map(|k| {
+                    let x = k as f64 - centre - frac;
+                    let sinc = if x == 0.0 {
+                        1.0
+                    } else {
+                        (PI * cutoff * x).sin() / (PI * cutoff * x)
+                    };
+                    let w = (x + TAPS as f64 / 2.0) / TAPS as f64;
+                    let blackman = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                    sinc * blackman.max(0.0)
+                })
+                .collect();
+            let sum: f64 = row.iter().sum();
+            table.extend(row.iter().map(|&h| (h / sum) as f32));
+        }
+        Resampler {
+            channels,
+            step,
+            trim: 1.0,
+            table,
+            history: vec![0.0; TAPS * channels],
+            position: centre,
+        }
+    }
+
+    /// Speed the output up (above 1) or slow it down, e.g. to follow a
+    /// sound card's clock.
+    pub(crate) fn set_trim(&mut self, trim: f64) {
+        self.trim = trim;
+    }
+
+    pub(crate) fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        let channels = self.channels;
+        self.history.extend_from_slice(input);
+        let frames = self.history.len() / channels;
+        let step = self.step * self.trim;
+        loop {
+            let base = self.position.floor();
+            let first = base as usize + 1 - TAPS / 2;
+            if first + TAPS > frames {
+                break;
+            }
+            let phase = ((self.position - base) * PHASES as f64).round() as usize;
+            let taps = &self.table[phase * TAPS..(phase + 1) * TAPS];
+            for c in 0..channels {
+                out.push(
+                    taps.iter()
+                        .enumerate()
+                        .map(|(k, &h)| h * self.history[(first + k) * channels + c])
+                        .sum(),
+                );
+            }
+            self.position += step;
+        }
+        // Drop the frames no later output reaches.
+        let used = (self.position.floor() as usize + 1)
+            .saturating_sub(TAPS / 2)
+            .min(frames);
+        self.history.drain(..used * channels);
+        self.position -= used as f64;
+    }
+}
@@ -0,0 +1,632 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+
+use super::resample::Resampler;
+use crate::error::{Error, Result};
+
+/// How much the resampling ratio may be trimmed to follow the sound card's
+/// clock.
+const MAX_TRIM: f64 = 0.002;
+
+/// Weight of each `play` in the averaged buffer fill.
+const FILL_ALPHA: f64 = 0.01;
+
+/// A frame of audio `AudioSink` can play: `f32` for mono, `[f32; 2]` for
+/// stereo.
+pub trait AudioFrame: Copy + Send + 'static {
+    /// The channels in a frame.
+    const CHANNELS: usize;
+
+    /// Get the frame's samples, one per channel.
+    fn samples(&self) -> &[f32];
+}
+
+impl AudioFrame for f32 {
+    const CHANNELS: usize = 1;
+
+    fn samples(&self) -> &[f32] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl AudioFrame for [f32; 2] {
+    const CHANNELS: usize = 2;
+
+    fn samples(&self) -> &[f32] {
+        self
+    }
+}
+
+/// State shared with the audio callback.
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when the callback takes samples or the stream fails.
+    drained: Condvar,
+    underruns: AtomicU64,
+    /// Device frames of silence played for want of samples.
+    silence: AtomicU64,
+    stop: AtomicBool,
+}
+
+struct State {
+    /// Interleaved samples at the device rate and channel count.
+    queue: VecDeque<f32>,
+    /// The most samples to queue.
+    capacity: usize,
+    /// Whether the callback is playing from the queue rather than waiting
+    /// for it to fill.
+    playing: bool,
+    /// Whether the queue has run dry and is refilling.
+    starved: bool,
+    error: Option<String>,
+}
+
+/// What the audio thread found when opening the device.
+struct Opened {
+    name: String,
+    rate: u32,
+    channels: u16,
+}
+
+/// Plays audio on a sound card through cpal.
+///
+/// The device is opened at the audio's own rate when it supports it, and
+/// otherwise at its default rate with the audio resampled to match, so the
+/// demodulator can run at whatever rate suits it. Audio is queued for the
+/// device's callback, on a thread of its own that owns the stream. `play`
+/// blocks while the queue holds more than the buffer length, which paces
+/// file playback; for live sources, the resampling ratio is trimmed by up
+/// to 0.2% to keep the queue half full, so the dongle's and the sound
+/// card's clocks can differ without the queue running dry or overflowing.
+/// When it does run dry, the gap is filled with silence, counted as an
+/// underrun, and playback waits until the queue is half full again.
+///
+/// Mono frames go to every device channel; stereo frames to the first two,
+/// or mixed down on a mono device. `with_routing` sends each frame channel
+/// to a chosen device channel instead, e.g. to put two receivers on the
+/// left and right of a virtual audio cable for an external decoder; for
+/// separate devices, open one sink on each with `open_device`.
+///
+/// ```no_run
+/// use radion::audio::AudioSink;
+/// use radion::demod::{Deemphasis, FmDemod, FmStereo};
+/// use radion::dsp::fir::ChannelDecimator;
+/// use radion::samples::{to_complex_f32, Scaling};
+/// use radion::Device;
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(98_500_000)?;
+/// device.set_sample_rate(1_200_000)?;
+/// device.reset_buffer()?;
+///
+/// let mut channel = ChannelDecimator::new(5, 63);
+/// let mut fm = FmDemod::new(240_000.0, 75_000.0);
+/// let mut stereo = FmStereo::new(240_000.0, 48_000.0, Deemphasis::Us50);
+/// let mut speaker = AudioSink::<[f32; 2]>::open(48_000.0)?;
+/// loop {
+///     let iq = to_complex_f32(&device.read_sync(262_144)?, Scaling::Normalized);
+///     speaker.play(&stereo.process(&fm.process(&channel.decimate(&iq))))?;
+///     if speaker.underruns() > 0 {
+///         eprintln!("{} underruns", speaker.underruns());
+///     }
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct AudioSink<F: AudioFrame = f32> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    resampler: Resampler,
+    name: String,
+    rate: u32,
+    channels: u16,
+    /// The queue fill averaged over recent calls, from 0 to 1.
+    fill: f64,
+    /// The device channel for each frame channel, if routed.
+    routing: Option<Vec<usize>>,
+    /// Device-channel samples being queued.
+    mapped: Vec<f32>,
+    resampled: Vec<f32>,
+    frame: PhantomData<F>,
+}
+
+impl<F: AudioFrame> AudioSink<F> {
+    /// Open the default output device with a 200 ms buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_rate` - The rate of the audio to be played in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AudioSink` if the device opened, otherwise
+    /// `Error::Audio`.
+    pub fn open(input_rate: f64) -> Result<Self> {
+        Self::open_with(None, input_rate)
+    }
+
+    /// Open an output device by name, with a 200 ms buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Part of the device's name, as listed by
+    ///   `output_devices`.
+    /// * `input_rate` - The rate of the audio to be played in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AudioSink` if the device opened, `Error::NotFound` if no
+    /// device matches, otherwise `Error::Audio`.
+    pub fn open_device(name: &str, input_rate: f64) -> Result<Self> {
+        Self::open_with(Some(name.to_string()), input_rate)
+    }
+
+    fn open_with(name: Option<String>, input_rate: f64) -> Result<Self> {
+        assert!(input_rate > 0.0, "input rate must be positive");
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                capacity: 0,
+                playing: false,
+                starved: false,
+                error: None,
+            }),
+            drained: Condvar::new(),
+            underruns: AtomicU64::new(0),
+            silence: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        });
+
+        // Streams aren't `Send` on every platform, so one thread builds the
+        // stream and keeps it until the sink is dropped.
+        let (tx, rx) = mpsc::channel();
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("radion-audio".to_string())
+            .spawn(
+                move || match build_stream(name.as_deref(), input_rate, &thread_shared) {
+                    Ok((stream, opened)) => {
+                        let _ = tx.send(Ok(opened));
+                        while !thread_shared.stop.load(Ordering::Acquire) {
+                            thread::park();
+                        }
+                        drop(stream);
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                    }
+                },
+            )
+            .map_err(Error::System)?;
+        let opened = rx
+            .recv()
+            .map_err(|_| Error::Audio("audio thread exited".to_string()))??;
+
+        let mut sink = AudioSink {
+            shared,
+            thread: Some(thread),
+            resampler: Resampler::new(input_rate, opened.rate as f64, opened.channels as usize),
+            name: opened.name,
+            rate: opened.rate,
+            channels: opened.channels,
+            fill: 0.5,
+            routing: None,
+            mapped: Vec::new(),
+            resampled: Vec::new(),
+            frame: PhantomData,
+        };
+        sink.set_buffer(Duration::from_millis(200));
+        Ok(sink)
+    }
+
+    /// Send each frame channel to a chosen device channel, leaving the
+    /// others silent.
+    ///
+    /// ```no_run
+    /// use radion::audio::AudioSink;
+    ///
+    /// // Two receivers' audio on the left and right of a virtual cable.
+    /// let mut cable = AudioSink::<[f32; 2]>::open_device("CABLE Input", 48_000.0)?;
+    /// # let (first, second) = (vec![0.0f32; 480], vec![0.0f32; 480]);
+    /// let frames: Vec<[f32; 2]> = first.iter().zip(&second).map(|(&l, &r)| [l, r]).collect();
+    /// cable.play(&frames)?;
+    ///
+    /// // One receiver on the right channel only.
+    /// let mut right = AudioSink::<f32>::open(48_000.0)?.with_routing(&[1])?;
+    /// right.play(&second)?;
+    /// # Ok::<(), radion::Error>(())
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - The device channel for each frame channel, counted
+    ///   from 0: one for mono frames, two for stereo.
+    ///
+    /// # Returns
+    ///
+    /// The routed `AudioSink`, or `Error::InvalidParam` if `channels` does
+    /// not have one entry per frame channel or names a channel the device
+    /// does not have.
+    pub fn with_routing(mut self, channels: &[usize]) -> Result<Self> {
+        if channels.len() != F::CHANNELS || channels.iter().any(|&c| c >= self.channels as usize) {
+            return Err(Error::InvalidParam);
+        }
+        self.routing = Some(channels.to_vec());
+        Ok(self)
+    }
+
+    /// Set the buffer length.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The most audio to queue. Longer survives more
+    ///   scheduling hiccups at the cost of latency.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioSink` with the new buffer length.
+    pub fn with_buffer(mut self, buffer: Duration) -> Self {
+        self.set_buffer(buffer);
+        self
+    }
+
+    /// Change the buffer length while playing.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The most audio to queue.
+    pub fn set_buffer(&mut self, buffer: Duration) {
+        let frames = (buffer.as_secs_f64() * self.rate as f64).ceil().max(1.0) as usize;
+        self.lock().capacity = frames * self.channels as usize;
+        self.shared.drained.notify_all();
+    }
+
+    /// Get the buffer length.
+    pub fn buffer(&self) -> Duration {
+        self.frames_to_duration(self.lock().capacity)
+    }
+
+    /// Get how much audio is queued.
+    pub fn buffered(&self) -> Duration {
+        self.frames_to_duration(self.lock().queue.len())
+    }
+
+    /// Get how many times the queue has run dry while playing.
+    pub fn underruns(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Get how much silence has been played from underruns until the queue
+    /// refilled.
+    pub fn silence(&self) -> Duration {
+        let frames = self.shared.silence.load(Ordering::Relaxed);
+        Duration::from_secs_f64(frames as f64 / self.rate as f64)
+    }
+
+    /// Get the output device's name.
+    pub fn device_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the output device's sample rate in Hz.
+    pub fn device_rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Get the output device's channel count.
+    pub fn device_channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Queue audio for playback.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The next chunk of audio, of any length.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result once the audio is queued, blocking while the buffer
+    /// is full, or `Error::Audio` if the stream has failed.
+    pub fn play(&mut self, frames: &[F]) -> Result<()> {
+        let channels = self.channels as usize;
+        self.mapped.clear();
+        route(frames, channels, self.routing.as_deref(), &mut self.mapped);
+        self.resampled.clear();
+        self.resampler.process(&self.mapped, &mut self.resampled);
+
+        let mut state = self.lock();
+        let mut pending = &self.resampled[..];
+        loop {
+            if let Some(error) = &state.error {
+                return Err(Error::Audio(error.clone()));
+            }
+            let room = state.capacity.saturating_sub(state.queue.len());
+            let take = (room - room % channels).min(pending.len());
+            state.queue.extend(&pending[..take]);
+            pending = &pending[take..];
+            if pending.is_empty() {
+                break;
+            }
+            state = self
+                .shared
+                .drained
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+
+        // Follow the sound card's clock: a queue filling up means it plays
+        // slower than the input arrives.
+        let fill = state.queue.len() as f64 / state.capacity.max(1) as f64;
+        drop(state);
+        self.fill += FILL_ALPHA * (fill - self.fill);
+        let trim = (2.0 * MAX_TRIM * (self.fill - 0.5)).clamp(-MAX_TRIM, MAX_TRIM);
+        self.resampler.set_trim(1.0 + trim);
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn frames_to_duration(&self, samples: usize) -> Duration {
+        let frames = samples / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / self.rate as f64)
+    }
+}
+
+impl<F: AudioFrame> Drop for AudioSink<F> {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Interleave frames onto a device's channels.
+///
+/// # Arguments
+///
+/// * `frames` - The frames.
+/// * `channels` - The device's channel count.
+/// * `routing` - The device channel for each frame channel, or `None` for
+///   mono on every channel and stereo on the first two or mixed down.
+/// * `out` - Receives the interleaved samples, appended.
+fn route<F: AudioFrame>(
+    frames: &[F],
+    channels: usize,
+    routing: Option<&[usize]>,
+    out: &mut Vec<f32>,
+) {
+    for frame in frames {
+        let samples = frame.samples();
+        match (routing, samples.len(), channels) {
+            (Some(routing), _, _) => {
+                let start = out.len();
+                out.resize(start + channels, 0.0);
+                for (&sample, &channel) in samples.iter().zip(routing) {
+                    out[start + channel] += sample;
+                }
+            }
+            (None, 1, _) => out.extend(std::iter::repeat_n(samples[0], channels)),
+            (None, _, 1) => out.push(samples.iter().sum::<f32>() / samples.len() as f32),
+            (None, _, _) => {
+                let copied = samples.len().min(channels);
+                out.extend_from_slice(&samples[..copied]);
+                out.extend(std::iter::repeat_n(0.0, channels - copied));
+            }
+        }
+    }
+}
+
+/// List the output devices.
+///
+/// # Returns
+///
+/// The names of the devices `AudioSink::open_device` can open, otherwise
+/// `Error::Audio`.
+pub fn output_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    let devices = host.output_devices().map_err(audio_error)?;
+    Ok(devices
+        .filter_map(|d| d.description().ok().map(|d| d.name().to_string()))
+        .collect())
+}
+
+fn audio_error(e: impl std::fmt::Display) -> Error {
+    Error::Audio(e.to_string())
+}
+
+/// Pick the rate to open a device at.
+///
+/// # Arguments
+///
+/// * `input_rate` - The rate of the audio to be played in Hz.
+/// * `default_rate` - The device's default rate in Hz.
+/// * `supported` - The ranges of rates the device supports in its default
+///   format, inclusive.
+///
+/// # Returns
+///
+/// The input rate if the device supports it, so no resampling is needed,
+/// otherwise the default rate.
+fn negotiate_rate(input_rate: f64, default_rate: u32, supported: &[(u32, u32)]) -> u32 {
+    let rate = input_rate.round();
+    let whole = (input_rate - rate).abs() < 1e-6 && rate <= u32::MAX as f64;
+    if whole
+        && supported
+            .iter()
+            .any(|&(min, max)| (min as f64..=max as f64).contains(&rate))
+    {
+        rate as u32
+    } else {
+        default_rate
+    }
+}
+
+fn build_stream(
+    name: Option<&str>,
+    input_rate: f64,
+    shared: &Arc<Shared>,
+) -> Result<(cpal::Stream, Opened)> {
+    let host = cpal::default_host();
+    let device = match name {
+        None => host
+            .default_output_device()
+            .ok_or_else(|| Error::Audio("no default output device".to_string()))?,
+        Some(name) => host
+            .output_devices()
+            .map_err(audio_error)?
+            .find(|d| {
+                d.description()
+                    .map(|d| d.name().contains(name))
+                    .unwrap_or(false)
+            })
+            .ok_or(Error::NotFound)?,
+    };
+    let supported = device.default_output_config().map_err(audio_error)?;
+    let mut config = supported.config();
+    let ranges: Vec<(u32, u32)> = device
+        .supported_output_configs()
+        .map_err(audio_error)?
+        .filter(|c| {
+            c.channels() == config.channels && c.sample_format() == supported.sample_format()
+        })
+        .map(|c| (c.min_sample_rate(), c.max_sample_rate()))
+        .collect();
+    config.sample_rate = negotiate_rate(input_rate, config.sample_rate, &ranges);
+    let opened = Opened {
+        name: device
+            .description()
+            .map(|d| d.name().to_string())
+            .unwrap_or_default(),
+        rate: config.sample_rate,
+        channels: config.channels,
+    };
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_typed::<f32>(&device, config, shared),
+        SampleFormat::I16 => build_typed::<i16>(&device, config, shared),
+        SampleFormat::U16 => build_typed::<u16>(&device, config, shared),
+        SampleFormat::I32 => build_typed::<i32>(&device, config, shared),
+        format => Err(Error::Audio(format!(
+            "unsupported sample format {}",
+            format
+        ))),
+    }?;
+    stream.play().map_err(audio_error)?;
+    Ok((stream, opened))
+}
+
+fn build_typed<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    shared: &Arc<Shared>,
+) -> Result<cpal::Stream> {
+    let channels = config.channels as usize;
+    let data_shared = Arc::clone(shared);
+    let error_shared = Arc::clone(shared);
+    device
+        .build_output_stream::<T, _, _>(
+            config,
+            move |data: &mut [T], _| fill(&data_shared, data, channels),
+            move |e| {
+                let mut state = error_shared.state.lock().unwrap_or_else(|e| e.into_inner());
+                state.error = Some(e.to_string());
+                error_shared.drained.notify_all();
+            },
+            None,
+        )
+        .map_err(audio_error)
+}
+
+/// The stream callback: play from the queue, or silence while it refills.
+fn fill<T: SizedSample + FromSample<f32>>(shared: &Shared, data: &mut [T], channels: usize) {
+    let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+    if !state.playing && state.queue.len() >= state.capacity / 2 && !state.queue.is_empty() {
+        state.playing = true;
+        state.starved = false;
+    }
+    let available = if state.playing {
+        state.queue.len().min(data.len())
+    } else {
+        0
+    };
+    for (out, sample) in data.iter_mut().zip(state.queue.drain(..available)) {
+        *out = T::from_sample(sample);
+    }
+    if available < data.len() {
+        for out in &mut data[available..] {
+            *out = T::from_sample(0.0);
+        }
+        if state.playing {
+            state.playing = false;
+            state.starved = true;
+            shared.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        if state.starved {
+            let missing = (data.len() - available) / channels.max(1);
+            shared.silence.fetch_add(missing as u64, Ordering::Relaxed);
+        }
+    }
+    drop(state);
+    shared.drained.notify_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routed<F: AudioFrame>(frames: &[F], channels: usize, routing: Option<&[usize]>) -> Vec<f32> {
+        let mut out = Vec::new();
+        route(frames, channels, routing, &mut out);
+        out
+    }
+
+    #[test]
+    fn routes_by_default() {
+        assert_eq!(routed(&[0.5f32, -0.5], 2, None), [0.5, 0.5, -0.5, -0.5]);
+        assert_eq!(routed(&[[0.2f32, 0.4]], 1, None), [0.3]);
+        assert_eq!(routed(&[[0.2f32, 0.4]], 4, None), [0.2, 0.4, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn routes_to_chosen_channels() {
+        assert_eq!(
+            routed(&[0.5f32, -0.5], 2, Some(&[1])),
+            [0.0, 0.5, 0.0, -0.5]
+        );
+        assert_eq!(routed(&[[0.1f32, 0.2]], 2, Some(&[1, 0])), [0.2, 0.1]);
+        // Both channels of a frame may share a device channel.
+        assert_eq!(routed(&[[0.25f32, 0.5]], 2, Some(&[0, 0])), [0.75, 0.0]);
+    }
+
+    #[test]
+    fn opens_at_the_input_rate_when_supported() {
+        let ranges = [(8_000, 48_000), (96_000, 96_000)];
+        assert_eq!(negotiate_rate(24_000.0, 48_000, &ranges), 24_000);
+        assert_eq!(negotiate_rate(96_000.0, 48_000, &ranges), 96_000);
+        assert_eq!(
+            negotiate_rate(44_100.0, 48_000, &[(44_100, 44_100)]),
+            44_100
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_default_rate() {
+        let ranges = [(44_100, 48_000)];
+        assert_eq!(negotiate_rate(192_000.0, 48_000, &ranges), 48_000);
+        assert_eq!(negotiate_rate(31_250.0, 44_100, &ranges), 44_100);
+        // Fractional rates always need resampling.
+        assert_eq!(negotiate_rate(46_875.5, 48_000, &ranges), 48_000);
+        assert_eq!(negotiate_rate(48_000.0, 44_100, &[]), 44_100);
+    }
+}
@@ -0,0 +1,120 @@
+//! An ADS-B station: receives 1090 MHz, tracks the aircraft heard and feeds
+//! them to dump1090-style tools.
+//!
+//! Serves SBS BaseStation lines on port 30003 and Beast records on 30005,
+//! and counters in the Prometheus text format on http://localhost:9105/.
+//! Stops cleanly on Ctrl-C.
+//!
+//! Install with `cargo install radion --features adsb,ctrlc` and run as
+//! `radion-adsb [device index] [latitude longitude]`. The receiver's
+//! position is only needed to place aircraft on the ground.
+
+use radion::decoders::adsb::{
+    Change, FeedFormat, FeedServer, MetricsServer, ModeSDemod, Position, StationMetrics, Tracker,
+    TrackerEvent, SAMPLE_RATE,
+};
+use radion::{Device, Frequency, OverrunPolicy, SampleRate};
+use std::error::Error;
+use std::time::Duration;
+
+const FREQ: u32 = 1_090_000_000;
+
+/// Where the metrics are served.
+const METRICS_ADDR: &str = "0.0.0.0:9105";
+
+/// Buffers of 256 KiB, 64 ms each at 2 MS/s.
+const RING_CAPACITY: usize = 32;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let index = args.first().map_or(Ok(0), |a| a.parse())?;
+    let reference = match (args.get(1), args.get(2)) {
+        (Some(lat), Some(lon)) => Some(Position {
+            lat: lat.parse()?,
+            lon: lon.parse()?,
+        }),
+        _ => None,
+    };
+
+    let device = Device::new(index)?;
+    device.set_center_freq(FREQ)?;
+    device.set_sample_rate(SAMPLE_RATE)?;
+    device.set_tuner_gain_mode(false)?;
+    println!(
+        "Receiving at {} with {}",
+        Frequency::from(device.get_center_freq()?),
+        SampleRate::from(device.get_sample_rate()?)
+    );
+
+    let mut sbs = FeedServer::bind(("0.0.0.0", FeedFormat::Sbs.default_port()), FeedFormat::Sbs)?;
+    let mut beast = FeedServer::bind(
+        ("0.0.0.0", FeedFormat::Beast.default_port()),
+        FeedFormat::Beast,
+    )?;
+    let mut tracker = Tracker::new();
+    if let Some(reference) = reference {
+        sbs = sbs.with_reference(reference);
+        tracker = tracker.with_reference(reference);
+    }
+    let metrics = MetricsServer::bind(METRICS_ADDR)?;
+
+    radion::stop_requested()?;
+    let mut demod = ModeSDemod::new();
+    let mut counters = StationMetrics::default();
+    let mut reader = device.start_ring(RING_CAPACITY, OverrunPolicy::DropOldest, 0, 0)?;
+    while !radion::stop_requested()? {
+        let frames = match reader.recv_timeout(Duration::from_millis(500)) {
+            Ok(Some(buf)) => demod.process(&buf),
+            Ok(None) => break,
+            Err(radion::Error::Timeout) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        sbs.send(&frames);
+        beast.send(&frames);
+        for event in tracker.update(&frames) {
+            report(&tracker, event);
+        }
+
+        counters.frames += frames.len() as u64;
+        counters.aircraft = tracker.len();
+        counters.sbs_clients = sbs.client_count();
+        counters.beast_clients = beast.client_count();
+        counters.feed_dropped = sbs.dropped() + beast.dropped();
+        counters.usb = reader.stats();
+        metrics.update(&counters);
+    }
+
+    let stats = reader.stats();
+    reader.stop()?;
+    println!(
+        "{} frames, {} aircraft tracked, {} USB transfers dropped",
+        counters.frames,
+        tracker.len(),
+        stats.dropped_frames
+    );
+    Ok(())
+}
+
+/// Print a tracker event as one line.
+fn report(tracker: &Tracker, event: TrackerEvent) {
+    match event {
+        TrackerEvent::Appeared(icao) => println!("{:06x} appeared", icao),
+        TrackerEvent::Expired(icao) => println!("{:06x} expired", icao),
+        TrackerEvent::Changed { icao, change } => {
+            let callsign = tracker
+                .get(icao)
+                .and_then(|a| a.callsign.clone())
+                .unwrap_or_default();
+            match change {
+                Change::Callsign(callsign) => println!("{:06x} is {}", icao, callsign),
+                Change::Squawk(squawk) => {
+                    println!("{:06x} {:8} squawk {:04}", icao, callsign, squawk)
+                }
+                Change::Position(p) => {
+                    println!("{:06x} {:8} at {:.4}, {:.4}", icao, callsign, p.lat, p.lon)
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::stats::{StreamCounters, StreamStats};
+use crate::stream::StreamHandle;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How a subscriber of a `SampleBroadcaster` copes with falling behind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Queue at most this many buffers and drop new ones while the queue is
+    /// full. Suits displays, which only care about recent data.
+    Lossy(usize),
+    /// Queue every buffer, however far the subscriber falls behind. Suits
+    /// recorders; memory grows for as long as the subscriber is slower than
+    /// the stream.
+    Lossless,
+}
+
+enum Sender {
+    Lossy(SyncSender<Arc<[u8]>>),
+    Lossless(mpsc::Sender<Arc<[u8]>>),
+}
+
+struct Subscriber {
+    tx: Sender,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    /// Queue a buffer.
+    ///
+    /// Returns `false` once the subscription has been dropped.
+    fn send(&self, buf: &Arc<[u8]>) -> bool {
+        match &self.tx {
+            Sender::Lossy(tx) => match tx.try_send(buf.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+            Sender::Lossless(tx) => tx.send(buf.clone()).is_ok(),
+        }
+    }
+}
+
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+fn lock(subscribers: &Subscribers) -> MutexGuard<'_, Vec<Subscriber>> {
+    subscribers.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Share a buffer with every subscriber, forgetting those that have gone.
+fn broadcast(subscribers: &Subscribers, buf: &[u8]) {
+    let buf: Arc<[u8]> = Arc::from(buf);
+    lock(subscribers).retain(|s| s.send(&buf));
+}
+
+/// Ends every subscription when the read ends, however it ends.
+struct Closer(Subscribers);
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        lock(&self.0).clear();
+    }
+}
+
+/// Fans one device's sample stream out to any number of subscribers.
+///
+/// Every buffer read is shared between the subscribers without copying,
+/// each of which queues it according to its own `Backpressure`, so a slow
+/// display cannot hold up a recorder or the other way round. Subscribers may
+/// join before or while the device is read and leave by dropping their
+/// `Subscription`. Dropping the broadcaster stops the read and closes the
+/// device.
+///
+/// ```no_run
+/// use radion::{Backpressure, Device, SampleBroadcaster};
+///
+/// let mut broadcaster = SampleBroadcaster::new();
+/// let recording = broadcaster.subscribe(Backpressure::Lossless);
+/// let display = broadcaster.subscribe(Backpressure::Lossy(4));
+/// broadcaster.start(Device::new(0)?, 0, 0)?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct SampleBroadcaster {
+    subscribers: Subscribers,
+    handle: Option<StreamHandle>,
+    stats: Option<Arc<StreamCounters>>,
+}
+
+impl SampleBroadcaster {
+    /// Create a broadcaster with no subscribers and no device.
+    pub fn new() -> Self {
+        SampleBroadcaster {
+            subscribers: Arc::default(),
+            handle: None,
+            stats: None,
+        }
+    }
+
+    /// Add a subscriber.
+    ///
+    /// # Arguments
+    ///
+    /// * `backpressure` - How the subscriber copes with falling behind.
+    ///
+    /// # Returns
+    ///
+    /// A `Subscription` receiving every buffer read from now on.
+    pub fn subscribe(&self, backpressure: Backpressure) -> Subscription {
+        let (tx, rx) = match backpressure {
+            Backpressure::Lossy(capacity) => {
+                let (tx, rx) = mpsc::sync_channel(capacity.max(1));
+                (Sender::Lossy(tx), rx)
+            }
+            Backpressure::Lossless => {
+                let (tx, rx) = mpsc::channel();
+                (Sender::Lossless(tx), rx)
+            }
+        };
+        let dropped = Arc::new(AtomicU64::new(0));
+        lock(&self.subscribers).push(Subscriber {
+            tx,
+            dropped: dropped.clone(),
+        });
+        Subscription {
+            rx,
+            backpressure,
+            dropped,
+        }
+    }
+
+    /// Get the number of live subscriptions.
+    pub fn subscriber_count(&self) -> usize {
+        let mut subscribers = lock(&self.subscribers);
+        // The subscription holds the other reference to its counter.
+        subscribers.retain(|s| Arc::strong_count(&s.dropped) > 1);
+        subscribers.len()
+    }
+
+    /// Start reading a device on a dedicated thread and broadcasting its
+    /// samples.
+    ///
+    /// The device buffer is reset before reading starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device to read, closed when the broadcaster is
+    ///   dropped.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if the read started, `Error::Busy` if the broadcaster
+    /// is already reading a device, otherwise an `Error`.
+    pub fn start(&mut self, device: Device, buf_num: u32, buf_len: u32) -> Result<()> {
+        if self.handle.is_some() {
+            return Err(Error::Busy);
+        }
+        let stats = device.stream_counters();
+        let subscribers = Closer(self.subscribers.clone());
+        let handle =
+            device.start_async(move |buf| broadcast(&subscribers.0, buf), buf_num, buf_len)?;
+        self.handle = Some(handle);
+        self.stats = Some(stats);
+        Ok(())
+    }
+
+    /// Check whether a device is being read.
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(StreamHandle::is_running)
+    }
+
+    /// Get the counters of the device's asynchronous read path, see
+    /// `Device::stream_stats`. Buffers dropped by lossy subscribers are
+    /// counted by each `Subscription` instead.
+    ///
+    /// # Returns
+    ///
+    /// The counters, or `None` if no device was started.
+    pub fn stats(&self) -> Option<StreamStats> {
+        self.stats.as_ref().map(|s| s.snapshot())
+    }
+
+    /// Stop reading and end every subscription once its queue is drained.
+    ///
+    /// # Returns
+    ///
+    /// The device if the read ended cleanly, otherwise the `Error` it failed
+    /// with.
+    pub fn stop(&mut self) -> Result<Device> {
+        let handle = self.handle.take().ok_or(Error::NotFound);
+        lock(&self.subscribers).clear();
+        handle?.stop()
+    }
+}
+
+impl Default for SampleBroadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SampleBroadcaster {
+    fn drop(&mut self) {
+        self.handle.take();
+        lock(&self.subscribers).clear();
+    }
+}
+
+/// One subscriber's view of a `SampleBroadcaster`.
+///
+/// Created by `SampleBroadcaster::subscribe`. Iterating yields buffers until
+/// the broadcaster stops. Dropping the subscription unsubscribes.
+pub struct Subscription {
+    rx: Receiver<Arc<[u8]>>,
+    backpressure: Backpressure,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// Wait for the next buffer.
+    ///
+    /// # Returns
+    ///
+    /// The next buffer, or `None` once the broadcaster has stopped and
+    /// every queued buffer has been received.
+    pub fn recv(&self) -> Option<Arc<[u8]>> {
+        self.rx.recv().ok()
+    }
+
+    /// Wait for the next buffer for at most `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait.
+    ///
+    /// # Returns
+    ///
+    /// Like `recv`, or `Error::Timeout` if no buffer arrived in time.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Arc<[u8]>>> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(buf) => Ok(Some(buf)),
+            Err(RecvTimeoutError::Timeout) => Err(Error::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Ok(None),
+        }
+    }
+
+    /// Get the backpressure policy the subscription was created with.
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+
+    /// Get the number of buffers dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Arc<[u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossy_subscribers_drop_while_lossless_ones_queue() {
+        let broadcaster = SampleBroadcaster::new();
+        let display = broadcaster.subscribe(Backpressure::Lossy(2));
+        let recording = broadcaster.subscribe(Backpressure::Lossless);
+        for i in 0..5 {
+            broadcast(&broadcaster.subscribers, &[i]);
+        }
+
+        assert_eq!(display.dropped(), 3);
+        assert_eq!(&*display.recv().unwrap(), [0]);
+        assert_eq!(&*display.recv().unwrap(), [1]);
+        assert!(matches!(
+            display.recv_timeout(Duration::ZERO),
+            Err(Error::Timeout)
+        ));
+
+        assert_eq!(recording.dropped(), 0);
+        let received: Vec<u8> = (0..5).map(|_| recording.recv().unwrap()[0]).collect();
+        assert_eq!(received, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn subscribers_share_one_copy() {
+        let broadcaster = SampleBroadcaster::new();
+        let a = broadcaster.subscribe(Backpressure::Lossless);
+        let b = broadcaster.subscribe(Backpressure::Lossy(1));
+        broadcast(&broadcaster.subscribers, &[1, 2, 3]);
+        assert!(Arc::ptr_eq(&a.recv().unwrap(), &b.recv().unwrap()));
+    }
+
+    #[test]
+    fn dropped_subscriptions_are_forgotten() {
+        let broadcaster = SampleBroadcaster::new();
+        let kept = broadcaster.subscribe(Backpressure::Lossless);
+        let gone = broadcaster.subscribe(Backpressure::Lossy(1));
+        assert_eq!(broadcaster.subscriber_count(), 2);
+        drop(gone);
+        assert_eq!(broadcaster.subscriber_count(), 1);
+
+        let late = broadcaster.subscribe(Backpressure::Lossless);
+        broadcast(&broadcaster.subscribers, &[9]);
+        assert_eq!(lock(&broadcaster.subscribers).len(), 2);
+        assert_eq!(&*kept.recv().unwrap(), [9]);
+        assert_eq!(&*late.recv().unwrap(), [9]);
+    }
+
+    #[test]
+    fn subscriptions_drain_then_end_when_the_broadcaster_goes() {
+        let broadcaster = SampleBroadcaster::new();
+        let recording = broadcaster.subscribe(Backpressure::Lossless);
+        broadcast(&broadcaster.subscribers, &[1]);
+        broadcast(&broadcaster.subscribers, &[2]);
+        drop(broadcaster);
+        let received: Vec<_> = recording.map(|buf| buf[0]).collect();
+        assert_eq!(received, [1, 2]);
+    }
+
+    #[test]
+    fn stopping_without_a_device_fails() {
+        let mut broadcaster = SampleBroadcaster::new();
+        assert!(matches!(broadcaster.stop(), Err(Error::NotFound)));
+        assert!(!broadcaster.is_running());
+        assert!(broadcaster.stats().is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::tuner::SamplingMode;
+
+/// Declarative configuration for opening a device.
+///
+/// Every setting is optional; settings that are left out keep the driver's
+/// defaults. `build` applies them in an order that avoids retuning more than
+/// necessary and reports which setting failed.
+///
+/// ```no_run
+/// use radion::DeviceBuilder;
+///
+/// let device = DeviceBuilder::new(0)
+///     .center_freq(100_000_000)
+///     .sample_rate(2_048_000)
+///     .tuner_gain(496)
+///     .build()?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DeviceBuilder {
+    index: u32,
+    xtal_freq: Option<(u32, u32)>,
+    direct_sampling: Option<SamplingMode>,
+    offset_tuning: Option<bool>,
+    sample_rate: Option<u32>,
+    freq_correction: Option<i32>,
+    center_freq: Option<u32>,
+    tuner_bandwidth: Option<u32>,
+    tuner_gain_mode: Option<bool>,
+    tuner_gain: Option<i32>,
+    agc_mode: Option<bool>,
+    test_mode: Option<bool>,
+    bias_tee: Option<bool>,
+}
+
+impl DeviceBuilder {
+    /// Create a builder for the device at `index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the device to open.
+    pub fn new(index: u32) -> Self {
+        DeviceBuilder {
+            index,
+            ..Default::default()
+        }
+    }
+
+    /// Set the crystal frequencies, see `Device::set_xtal_freq`.
+    pub fn xtal_freq(mut self, rtl_freq_hz: u32, tuner_freq_hz: u32) -> Self {
+        self.xtal_freq = Some((rtl_freq_hz, tuner_freq_hz));
+        self
+    }
+
+    /// Set the direct sampling mode, see `Device::set_direct_sampling`.
+    pub fn direct_sampling(mut self, mode: SamplingMode) -> Self {
+        self.direct_sampling = Some(mode);
+        self
+    }
+
+    /// Set the offset tuning mode, see `Device::set_offset_tuning`.
+    pub fn offset_tuning(mut self, on: bool) -> Self {
+        self.offset_tuning = Some(on);
+        self
+    }
+
+    /// Set the sample rate in Hz, see `Device::set_sample_rate`.
+    pub fn sample_rate(mut self, rate_hz: u32) -> Self {
+        self.sample_rate = Some(rate_hz);
+        self
+    }
+
+    /// Set the frequency correction in ppm, see `Device::set_freq_correction`.
+    pub fn freq_correction(mut self, ppm: i32) -> Self {
+        self.freq_correction = Some(ppm);
+        self
+    }
+
+    /// Set the center frequency in Hz, see `Device::set_center_freq`.
+    pub fn center_freq(mut self, freq_hz: u32) -> Self {
+        self.center_freq = Some(freq_hz);
+        self
+    }
+
+    /// Set the tuner bandwidth in Hz, see `Device::set_tuner_bandwidth`.
+    pub fn tuner_bandwidth(mut self, bw_hz: u32) -> Self {
+        self.tuner_bandwidth = Some(bw_hz);
+        self
+    }
+
+    /// Set the tuner gain mode, see `Device::set_tuner_gain_mode`.
+    pub fn tuner_gain_mode(mut self, manual_mode: bool) -> Self {
+        self.tuner_gain_mode = Some(manual_mode);
+        self
+    }
+
+    /// Set the tuner gain in tenths of a dB, see `Device::set_tuner_gain`.
+    ///
+    /// Implies manual gain mode unless `tuner_gain_mode` says otherwise.
+    pub fn tuner_gain(mut self, gain: i32) -> Self {
+        self.tuner_gain = Some(gain);
+        self
+    }
+
+    /// Set the RTL2832 AGC mode, see `Device::set_agc_mode`.
+    pub fn agc_mode(mut self, on: bool) -> Self {
+        self.agc_mode = Some(on);
+        self
+    }
+
+    /// Set the test mode, see `Device::set_test_mode`.
+    pub fn test_mode(mut self, on: bool) -> Self {
+        self.test_mode = Some(on);
+        self
+    }
+
+    /// Enable or disable the bias tee, see `Device::set_bias_tee`.
+    pub fn bias_tee(mut self, on: bool) -> Self {
+        self.bias_tee = Some(on);
+        self
+    }
+
+    /// Open the device and apply the configured settings.
+    ///
+    /// # Returns
+    ///
+    /// The configured `Device` if successful, otherwise an `Error`. Failures
+    /// while applying a setting are reported as `Error::Config` naming the
+    /// setting.
+    pub fn build(&self) -> Result<Device> {
+        let device = Device::new(self.index)?;
+        self.apply(&device)?;
+        Ok(device)
+    }
+
+    /// Apply the configured settings to an already open device.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device to configure.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error::Config` naming the
+    /// setting that failed.
+    pub fn apply(&self, device: &Device) -> Result<()> {
+        if let Some((rtl, tuner)) = self.xtal_freq {
+            config("xtal_freq", device.set_xtal_freq(rtl, tuner))?;
+        }
+        if let Some(mode) = self.direct_sampling {
+            config("direct_sampling", device.set_direct_sampling(mode))?;
+        }
+        if let Some(on) = self.offset_tuning {
+            config("offset_tuning", device.set_offset_tuning(on))?;
+        }
+        if let Some(rate) = self.sample_rate {
+            config("sample_rate", device.set_sample_rate(rate))?;
+        }
+        if let Some(ppm) = self.freq_correction {
+            // librtlsdr rejects setting the correction it already has.
+            if config("freq_correction", device.get_freq_correction())? != ppm {
+                config("freq_correction", device.set_freq_correction(ppm))?;
+            }
+        }
+        if let Some(freq) = self.center_freq {
+            config("center_freq", device.set_center_freq(freq))?;
+        }
+        if let Some(bw) = self.tuner_bandwidth {
+            config("tuner_bandwidth", device.set_tuner_bandwidth(bw))?;
+        }
+        let manual = self.tuner_gain_mode.or(self.tuner_gain.map(|_| true));
+        if let Some(manual) = manual {
+            config("tuner_gain_mode", device.set_tuner_gain_mode(manual))?;
+        }
+        if let Some(gain) = self.tuner_gain {
+            config("tuner_gain", device.set_tuner_gain(gain))?;
+        }
+        if let Some(on) = self.agc_mode {
+            config("agc_mode", device.set_agc_mode(on))?;
+        }
+        if let Some(on) = self.test_mode {
+            config("test_mode", device.set_test_mode(on))?;
+        }
+        if let Some(on) = self.bias_tee {
+            config("bias_tee", device.set_bias_tee(on))?;
+        }
+        Ok(())
+    }
+}
+
+fn config<T>(setting: &'static str, result: Result<T>) -> Result<T> {
+    result.map_err(|e| Error::Config {
+        setting,
+        source: Box::new(e),
+    })
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use crate::device::Device;
+use crate::sym;
+
+/// Cargo features of this crate, with whether each was compiled in.
+const FEATURES: [(&str, bool); 33] = [
+    ("adsb", cfg!(feature = "adsb")),
+    ("ais", cfg!(feature = "ais")),
+    ("aprs", cfg!(feature = "aprs")),
+    ("apt", cfg!(feature = "apt")),
+    ("audio", cfg!(feature = "audio")),
+    ("ctrlc", cfg!(feature = "ctrlc")),
+    ("demod", cfg!(feature = "demod")),
+    ("dsp", cfg!(feature = "dsp")),
+    ("events", cfg!(feature = "events")),
+    ("fft", cfg!(feature = "fft")),
+    ("ism", cfg!(feature = "ism")),
+    ("mmap", cfg!(feature = "mmap")),
+    ("mock", cfg!(feature = "mock")),
+    ("pipeline", cfg!(feature = "pipeline")),
+    ("plugins", cfg!(feature = "plugins")),
+    ("png", cfg!(feature = "png")),
+    ("presets", cfg!(feature = "presets")),
+    ("pulse", cfg!(feature = "pulse")),
+    ("rds", cfg!(feature = "rds")),
+    ("record", cfg!(feature = "record")),
+    ("remote", cfg!(feature = "remote")),
+    ("rigctl", cfg!(feature = "rigctl")),
+    ("sat", cfg!(feature = "sat")),
+    ("scan", cfg!(feature = "scan")),
+    ("schema", cfg!(feature = "schema")),
+    ("script", cfg!(feature = "script")),
+    ("sigmf", cfg!(feature = "sigmf")),
+    ("soak", cfg!(feature = "soak")),
+    ("squelch", cfg!(feature = "squelch")),
+    ("timecode", cfg!(feature = "timecode")),
+    ("tokio", cfg!(feature = "tokio")),
+    ("udp", cfg!(feature = "udp")),
+    ("zmq", cfg!(feature = "zmq")),
+];
+
+/// What this build of the crate can do on this machine.
+///
+/// Meant to be logged at startup and attached to bug reports. The `Display`
+/// output is one `key: value` pair per line.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// The crate version.
+    pub version: &'static str,
+    /// The CPU architecture the crate was compiled for.
+    pub arch: &'static str,
+    /// The operating system the crate was compiled for.
+    pub os: &'static str,
+    /// The Cargo features that were compiled in, in alphabetical order.
+    pub features: Vec<&'static str>,
+    /// Number of RTL-SDR devices librtlsdr can see. Zero usually means no
+    /// dongle is plugged in or, on Windows, no WinUSB driver is bound.
+    pub devices: u32,
+    /// Whether the loaded librtlsdr exports `rtlsdr_set_bias_tee`.
+    pub bias_tee: bool,
+    /// Whether the loaded librtlsdr exports `rtlsdr_set_bias_tee_gpio`.
+    pub bias_tee_gpio: bool,
+    /// The sample conversion path picked on this CPU, see
+    /// `samples::conversion_path`, or `None` without the `dsp` feature.
+    pub conversion_path: Option<&'static str>,
+}
+
+impl Capabilities {
+    /// Check whether a feature was compiled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `feature` - The name of the Cargo feature.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the feature is enabled.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "radion: {}", self.version)?;
+        writeln!(f, "target: {}-{}", self.arch, self.os)?;
+        writeln!(f, "features: {}", self.features.join(", "))?;
+        writeln!(f, "devices: {}", self.devices)?;
+        writeln!(f, "bias tee: {}", self.bias_tee)?;
+        writeln!(f, "bias tee gpio: {}", self.bias_tee_gpio)?;
+        write!(
+            f,
+            "conversion: {}",
+            self.conversion_path.unwrap_or("unavailable")
+        )
+    }
+}
+
+/// Report what this build of the crate can do on this machine.
+///
+/// Lists the compiled features, the librtlsdr functions that are only in
+/// some builds, how many devices are visible and the sample conversion path
+/// in use. No device is opened.
+///
+/// # Returns
+///
+/// The `Capabilities` of the crate.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        arch: std::env::consts::ARCH,
+        os: std::env::consts::OS,
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        devices: Device::get_device_count(),
+        bias_tee: sym::lookup(c"rtlsdr_set_bias_tee").is_some(),
+        bias_tee_gpio: sym::lookup(c"rtlsdr_set_bias_tee_gpio").is_some(),
+        #[cfg(feature = "dsp")]
+        conversion_path: Some(crate::samples::conversion_path()),
+        #[cfg(not(feature = "dsp"))]
+        conversion_path: None,
+    }
+}
@@ -0,0 +1,310 @@
+use std::f64::consts::PI;
+
+use super::demod::SAMPLE_RATE;
+
+/// 2^17, the CPR coordinate scale.
+const SCALE: f64 = 131_072.0;
+
+/// How far apart an even/odd pair may be for global decoding, in samples:
+/// 10 seconds airborne.
+const PAIR_WINDOW: u64 = 10 * SAMPLE_RATE as u64;
+
+/// The same for surface positions: 25 seconds.
+const SURFACE_PAIR_WINDOW: u64 = 25 * SAMPLE_RATE as u64;
+
+/// How long an aircraft's last position is used for local decoding, in
+/// samples: 60 seconds.
+const LOCAL_WINDOW: u64 = 60 * SAMPLE_RATE as u64;
+
+/// A position in degrees, WGS84.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Position {
+    /// Latitude, positive north.
+    pub lat: f64,
+    /// Longitude, positive east.
+    pub lon: f64,
+}
+
+/// A position in Compact Position Reporting form, as sent in airborne and
+/// surface position messages.
+///
+/// One message only locates the aircraft within a zone, so it needs either
+/// a second message of the other parity (global decoding) or a nearby
+/// reference position such as the receiver or the aircraft's last known
+/// position (local decoding).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cpr {
+    /// Encoded latitude, 17 bits.
+    pub lat: u32,
+    /// Encoded longitude, 17 bits.
+    pub lon: u32,
+    /// Whether this is an odd-format message.
+    pub odd: bool,
+    /// Whether this came from a surface position message, which packs the
+    /// zones four times tighter.
+    pub surface: bool,
+}
+
+impl Cpr {
+    /// Decode from a pair of messages of opposite parity.
+    ///
+    /// The two must be sent within about 10 seconds of each other (25 on
+    /// the surface) for the result to be right; this is not checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `even` - The even-format message.
+    /// * `odd` - The odd-format message.
+    /// * `latest_odd` - Whether the odd message is the newer one, whose
+    ///   position is returned.
+    /// * `reference` - Any position within 45° of the aircraft, such as the
+    ///   receiver's, needed for surface messages since their decoding is
+    ///   ambiguous by quadrant. Ignored for airborne ones.
+    ///
+    /// # Returns
+    ///
+    /// The position, or `None` if the messages straddle a longitude zone
+    /// boundary, are of the same parity, or are surface messages without a
+    /// reference.
+    pub fn decode_global(
+        even: &Cpr,
+        odd: &Cpr,
+        latest_odd: bool,
+        reference: Option<Position>,
+    ) -> Option<Position> {
+        if even.odd || !odd.odd || even.surface != odd.surface {
+            return None;
+        }
+        let span = if even.surface { 90.0 } else { 360.0 };
+        let (lat_e, lon_e) = (even.lat as f64 / SCALE, even.lon as f64 / SCALE);
+        let (lat_o, lon_o) = (odd.lat as f64 / SCALE, odd.lon as f64 / SCALE);
+
+        let j = (59.0 * lat_e - 60.0 * lat_o + 0.5).floor();
+        let mut rlat_e = span / 60.0 * (j.rem_euclid(60.0) + lat_e);
+        let mut rlat_o = span / 59.0 * (j.rem_euclid(59.0) + lat_o);
+        if even.surface {
+            // Both hemispheres fit the messages; take the reference's.
+            let reference = reference?;
+            if reference.lat < 0.0 {
+                rlat_e -= 90.0;
+                rlat_o -= 90.0;
+            }
+        } else {
+            if rlat_e >= 270.0 {
+                rlat_e -= 360.0;
+            }
+            if rlat_o >= 270.0 {
+                rlat_o -= 360.0;
+            }
+        }
+        if nl(rlat_e) != nl(rlat_o) {
+            return None;
+        }
+
+        let (lat, lon_cpr, nl_lat) = if latest_odd {
+            (rlat_o, lon_o, nl(rlat_o))
+        } else {
+            (rlat_e, lon_e, nl(rlat_e))
+        };
+        let ni = (nl_lat - latest_odd as i32).max(1) as f64;
+        let m = (lon_e * (nl_lat - 1) as f64 - lon_o * nl_lat as f64 + 0.5).floor();
+        let mut lon = span / ni * (m.rem_euclid(ni) + lon_cpr);
+        if even.surface {
+            // Four longitudes 90° apart fit; take the reference's.
+            let reference = reference?;
+            lon = nearest(lon, reference.lon, 90.0);
+        } else if lon >= 180.0 {
+            lon -= 360.0;
+        }
+        Some(Position {
+            lat,
+            lon: normalize_lon(lon),
+        })
+    }
+
+    /// Decode with a reference position.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - A position within 180 NM of the aircraft (45 NM on
+    ///   the surface), such as the receiver's or the aircraft's last.
+    ///
+    /// # Returns
+    ///
+    /// The position.
+    pub fn decode_local(&self, reference: Position) -> Position {
+        let span = if self.surface { 90.0 } else { 360.0 };
+        let (lat_cpr, lon_cpr) = (self.lat as f64 / SCALE, self.lon as f64 / SCALE);
+
+        let dlat = span / if self.odd { 59.0 } else { 60.0 };
+        let j = (reference.lat / dlat).floor()
+            + (0.5 + reference.lat.rem_euclid(dlat) / dlat - lat_cpr).floor();
+        let lat = dlat * (j + lat_cpr);
+
+        let dlon = span / (nl(lat) - self.odd as i32).max(1) as f64;
+        let m = (reference.lon / dlon).floor()
+            + (0.5 + reference.lon.rem_euclid(dlon) / dlon - lon_cpr).floor();
+        Position {
+            lat,
+            lon: normalize_lon(dlon * (m + lon_cpr)),
+        }
+    }
+}
+
+/// Resolves one aircraft's position messages as they arrive.
+///
+/// Uses global decoding when the latest message pairs with a recent one of
+/// the other parity, otherwise local decoding near the last position.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Locator {
+    even: Option<(Cpr, u64)>,
+    odd: Option<(Cpr, u64)>,
+    position: Option<(Position, u64)>,
+}
+
+impl Locator {
+    /// Resolve a position message, remembering it for the next one.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpr` - The encoded position.
+    /// * `sample` - When it was received, in samples at 2 MS/s.
+    /// * `reference` - The receiver's position, needed for surface
+    ///   messages.
+    ///
+    /// # Returns
+    ///
+    /// The position, or `None` until enough messages have arrived.
+    pub(crate) fn locate(
+        &mut self,
+        cpr: Cpr,
+        sample: u64,
+        reference: Option<Position>,
+    ) -> Option<Position> {
+        if cpr.odd {
+            self.odd = Some((cpr, sample));
+        } else {
+            self.even = Some((cpr, sample));
+        }
+        let window = if cpr.surface {
+            SURFACE_PAIR_WINDOW
+        } else {
+            PAIR_WINDOW
+        };
+        let global = match (self.even, self.odd) {
+            (Some((even, te)), Some((odd, to)))
+                if even.surface == odd.surface && te.abs_diff(to) <= window =>
+            {
+                Cpr::decode_global(&even, &odd, cpr.odd, reference)
+            }
+            _ => None,
+        };
+        let position = global.or_else(|| {
+            let (last, at) = self.position?;
+            (sample.saturating_sub(at) <= LOCAL_WINDOW).then(|| cpr.decode_local(last))
+        })?;
+        self.position = Some((position, sample));
+        Some(position)
+    }
+}
+
+/// The number of longitude zones at a latitude.
+fn nl(lat: f64) -> i32 {
+    let lat = lat.abs();
+    if lat == 0.0 {
+        return 59;
+    }
+    if lat == 87.0 {
+        return 2;
+    }
+    if lat > 87.0 {
+        return 1;
+    }
+    let a = 1.0 - (PI / 30.0).cos();
+    let b = (PI / 180.0 * lat).cos().powi(2);
+    (2.0 * PI / (1.0 - a / b).acos()).floor() as i32
+}
+
+/// Shift `value` by multiples of `step` to be as close as possible to
+/// `target`.
+fn nearest(value: f64, target: f64, step: f64) -> f64 {
+    value + ((target - value) / step).round() * step
+}
+
+fn normalize_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The pair from 8D40621D58C382D690C8AC2863A7 (even) and
+    // 8D40621D58C386435CC412692AD6 (odd).
+    const EVEN: Cpr = Cpr {
+        lat: 93000,
+        lon: 51372,
+        odd: false,
+        surface: false,
+    };
+    const ODD: Cpr = Cpr {
+        lat: 74158,
+        lon: 50194,
+        odd: true,
+        surface: false,
+    };
+
+    fn assert_near(p: Position, lat: f64, lon: f64) {
+        assert!((p.lat - lat).abs() < 1e-9, "lat {} != {}", p.lat, lat);
+        assert!((p.lon - lon).abs() < 1e-9, "lon {} != {}", p.lon, lon);
+    }
+
+    #[test]
+    fn global_decode() {
+        let even_latest = Cpr::decode_global(&EVEN, &ODD, false, None).unwrap();
+        assert_near(even_latest, 52.2572021484375, 3.91937255859375);
+        let odd_latest = Cpr::decode_global(&EVEN, &ODD, true, None).unwrap();
+        assert_near(odd_latest, 52.26578017412606, 3.938912527901786);
+    }
+
+    #[test]
+    fn global_decode_needs_both_parities() {
+        assert!(Cpr::decode_global(&EVEN, &EVEN, false, None).is_none());
+        assert!(Cpr::decode_global(&ODD, &EVEN, false, None).is_none());
+    }
+
+    #[test]
+    fn local_decode() {
+        let reference = Position {
+            lat: 52.258,
+            lon: 3.918,
+        };
+        assert_near(
+            EVEN.decode_local(reference),
+            52.2572021484375,
+            3.91937255859375,
+        );
+    }
+
+    #[test]
+    fn zones() {
+        assert_eq!(nl(0.0), 59);
+        assert_eq!(nl(52.2572021484375), 36);
+        assert_eq!(nl(-52.2572021484375), 36);
+        assert_eq!(nl(87.0), 2);
+        assert_eq!(nl(89.9), 1);
+    }
+
+    #[test]
+    fn locator_pairs_then_goes_local() {
+        let mut locator = Locator::default();
+        assert_eq!(locator.locate(ODD, 0, None), None);
+        let first = locator.locate(EVEN, SAMPLE_RATE as u64, None).unwrap();
+        assert_near(first, 52.2572021484375, 3.91937255859375);
+        // Long after the pair, positions come from the last one.
+        let later = locator.locate(EVEN, 30 * SAMPLE_RATE as u64, None).unwrap();
+        assert_near(later, 52.2572021484375, 3.91937255859375);
+        // And stop once that is too old.
+        assert_eq!(locator.locate(EVEN, 200 * SAMPLE_RATE as u64, None), None);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The Mode S generator polynomial, without its x^24 term.
+const POLY: u32 = 0xfff409;
+
+/// Bits at the start of a message the correction never touches, so a
+/// damaged downlink format is not "corrected" into another one.
+const PROTECTED_BITS: usize = 5;
+
+fn table() -> &'static [u32; 256] {
+    static TABLE: OnceLock<[u32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut crc = (i as u32) << 16;
+            for _ in 0..8 {
+                crc = if crc & 0x80_0000 != 0 {
+                    (crc << 1) ^ POLY
+                } else {
+                    crc << 1
+                };
+            }
+            *entry = crc & 0xff_ffff;
+        }
+        table
+    })
+}
+
+/// Compute the Mode S CRC-24 of some bytes.
+///
+/// # Arguments
+///
+/// * `data` - The bytes to checksum, usually a message without its last
+///   three parity bytes.
+///
+/// # Returns
+///
+/// The 24-bit CRC.
+pub fn crc24(data: &[u8]) -> u32 {
+    let table = table();
+    data.iter().fold(0, |crc, &b| {
+        ((crc << 8) ^ table[(((crc >> 16) ^ b as u32) & 0xff) as usize]) & 0xff_ffff
+    })
+}
+
+/// The CRC of a message XORed with its parity field: zero for an intact
+/// DF11/17/18 message, the aircraft address for address/parity formats.
+pub(crate) fn residual(msg: &[u8]) -> u32 {
+    let n = msg.len();
+    let parity = (msg[n - 3] as u32) << 16 | (msg[n - 2] as u32) << 8 | msg[n - 1] as u32;
+    crc24(&msg[..n - 3]) ^ parity
+}
+
+/// Residuals caused by every one- and two-bit error in a 112-bit message,
+/// mapped to the bits to flip.
+fn syndromes() -> &'static HashMap<u32, (u8, Option<u8>)> {
+    static SYNDROMES: OnceLock<HashMap<u32, (u8, Option<u8>)>> = OnceLock::new();
+    SYNDROMES.get_or_init(|| {
+        // The CRC is linear, so each bit's error pattern is the residual
+        // of a message with only that bit set.
+        let single: Vec<u32> = (0..112)
+            .map(|bit| {
+                let mut msg = [0u8; 14];
+                msg[bit / 8] = 0x80 >> (bit % 8);
+                residual(&msg)
+            })
+            .collect();
+        let mut map = HashMap::new();
+        for a in PROTECTED_BITS..112 {
+            for b in a + 1..112 {
+                map.entry(single[a] ^ single[b])
+                    .or_insert((a as u8, Some(b as u8)));
+            }
+        }
+        // A single error wins over a double one with the same syndrome.
+        for (a, &s) in single.iter().enumerate().skip(PROTECTED_BITS) {
+            map.insert(s, (a as u8, None));
+        }
+        map
+    })
+}
+
+/// Try to repair a 112-bit message with a non-zero residual.
+///
+/// # Returns
+///
+/// The number of bits flipped, or `None` if no error pattern within
+/// `max_errors` bits explains the residual.
+pub(crate) fn correct(msg: &mut [u8], max_errors: u8) -> Option<u8> {
+    if msg.len() != 14 || max_errors == 0 {
+        return None;
+    }
+    let &(a, b) = syndromes().get(&residual(msg))?;
+    let flips = 1 + b.is_some() as u8;
+    if flips > max_errors {
+        return None;
+    }
+    for bit in std::iter::once(a).chain(b) {
+        msg[bit as usize / 8] ^= 0x80 >> (bit % 8);
+    }
+    Some(flips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DF17 identification message from KLM1023.
+    const KLM: [u8; 14] = [
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ];
+
+    #[test]
+    fn crc_of_known_message() {
+        assert_eq!(crc24(&KLM[..11]), 0x576098);
+        assert_eq!(residual(&KLM), 0);
+    }
+
+    #[test]
+    fn corrects_one_and_two_bit_errors() {
+        let mut msg = KLM;
+        msg[6] ^= 0x10;
+        assert_eq!(correct(&mut msg, 1), Some(1));
+        assert_eq!(msg, KLM);
+
+        msg[3] ^= 0x01;
+        msg[12] ^= 0x80;
+        assert_eq!(correct(&mut msg, 1), None);
+        assert_eq!(correct(&mut msg, 2), Some(2));
+        assert_eq!(msg, KLM);
+    }
+
+    #[test]
+    fn leaves_downlink_format_alone() {
+        let mut msg = KLM;
+        msg[0] ^= 0x80;
+        assert_eq!(correct(&mut msg, 2), None);
+    }
+}
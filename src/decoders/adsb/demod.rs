@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use super::crc::{correct, residual};
+use super::message::{bits, Message};
+use crate::decoders::quality::{hard_bits, soft_bit};
+use crate::decoders::{Failure, FailureDump, Quality};
+
+/// Samples in the 8 µs preamble at 2 MS/s.
+const PREAMBLE: usize = 16;
+
+/// Samples in the longest message at 2 MS/s.
+const LONG_SAMPLES: usize = 224;
+
+/// How long an address heard in a checked message is trusted for decoding
+/// address/parity replies, in samples: 60 seconds at 2 MS/s.
+const KNOWN_TTL: u64 = 120_000_000;
+
+/// Sample rate the demodulator expects.
+pub const SAMPLE_RATE: u32 = 2_000_000;
+
+/// Magnitude of every cu8 I/Q pair, scaled to 0..65535.
+fn magnitudes() -> &'static [u16] {
+    static TABLE: OnceLock<Vec<u16>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let full = 127.5 * std::f64::consts::SQRT_2;
+        (0..=0xffffu32)
+            .map(|iq| {
+                let i = (iq >> 8) as f64 - 127.5;
+                let q = (iq & 0xff) as f64 - 127.5;
+                ((i * i + q * q).sqrt() / full * 65535.0).round() as u16
+            })
+            .collect()
+    })
+}
+
+/// A Mode S message that passed its parity check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    data: [u8; 14],
+    len: usize,
+    icao: u32,
+    /// The index of the preamble's first sample since the demodulator was
+    /// created or reset.
+    pub sample: u64,
+    /// The signal level, the mean magnitude of the preamble pulses from 0
+    /// to 65535 at full scale.
+    pub signal: u16,
+    /// The number of bits repaired by error correction.
+    pub corrected: u8,
+    /// Whether the checksum was checked, rather than overlaid with an
+    /// address recognised from an earlier message.
+    checked: bool,
+    soft: [i8; 112],
+}
+
+impl Frame {
+    /// Check a message received some other way, such as from another
+    /// receiver.
+    ///
+    /// Only DF11, DF17 and DF18 messages carry a plain checksum, so other
+    /// formats are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The 7 or 14 byte message.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or `None` if the length or checksum is wrong.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Frame> {
+        if bytes.is_empty() || bytes.len() != message_len(bytes[0] >> 3) {
+            return None;
+        }
+        let mut data = [0; 14];
+        data[..bytes.len()].copy_from_slice(bytes);
+        let mut soft = [0; 112];
+        soft[..8 * bytes.len()].copy_from_slice(&hard_bits(bytes));
+        let frame = Frame {
+            data,
+            len: bytes.len(),
+            icao: 0,
+            sample: 0,
+            signal: 0,
+            corrected: 0,
+            checked: true,
+            soft,
+        };
+        let crc = residual(frame.bytes());
+        match frame.df() {
+            11 if crc < 0x80 => Some(frame.with_announced_icao()),
+            17 | 18 if crc == 0 => Some(frame.with_announced_icao()),
+            _ => None,
+        }
+    }
+
+    /// Get the message bytes, 7 or 14 of them.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// Get the soft decision of each message bit, as received before any
+    /// error correction.
+    ///
+    /// # Returns
+    ///
+    /// One value per bit of `bytes`, most significant bit first: positive
+    /// for a one, negative for a zero, with 127 for a full-strength
+    /// decision. Frames checked with `from_bytes` have full-strength
+    /// decisions.
+    pub fn soft_bits(&self) -> &[i8] {
+        &self.soft[..8 * self.len]
+    }
+
+    /// Get how confidently the message was received.
+    pub fn quality(&self) -> Quality {
+        Quality::from_soft(self.soft_bits(), self.checked, self.corrected as u32)
+    }
+
+    /// Get the downlink format.
+    pub fn df(&self) -> u8 {
+        self.data[0] >> 3
+    }
+
+    /// Get the address of the aircraft the message is from, either sent in
+    /// the message or recovered from its parity field.
+    pub fn icao(&self) -> u32 {
+        self.icao
+    }
+
+    /// Get the reception time on the 12 MHz clock used by the Beast
+    /// protocol and multilateration.
+    pub fn timestamp(&self) -> u64 {
+        self.sample * 6
+    }
+
+    /// Get the signal level in dB relative to full scale.
+    pub fn signal_db(&self) -> f32 {
+        20.0 * (self.signal.max(1) as f32 / 65535.0).log10()
+    }
+
+    /// Decode the message's content.
+    pub fn decode(&self) -> Message {
+        Message::decode(self.bytes(), self.icao)
+    }
+
+    /// Encode the frame in the Beast binary format.
+    ///
+    /// # Returns
+    ///
+    /// The escaped record: `0x1a`, the type (`'2'` for short messages, `'3'`
+    /// for long ones), the 48-bit 12 MHz timestamp, the signal level and
+    /// the message, with every `0x1a` after the first byte doubled.
+    pub fn to_beast(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 + 2 * (7 + self.len));
+        out.push(0x1a);
+        out.push(if self.len == 14 { b'3' } else { b'2' });
+        let timestamp = self.timestamp().to_be_bytes();
+        let level = [(self.signal >> 8) as u8];
+        for &b in timestamp[2..].iter().chain(&level).chain(self.bytes()) {
+            out.push(b);
+            if b == 0x1a {
+                out.push(b);
+            }
+        }
+        out
+    }
+
+    fn with_announced_icao(mut self) -> Self {
+        self.icao = bits(&self.data, 8, 24);
+        self
+    }
+}
+
+/// Formats the frame in the AVR text format, e.g. `*8d4840d6202cc371c32ce0576098;`.
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "*")?;
+        for b in self.bytes() {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, ";")
+    }
+}
+
+/// Mode S demodulator for 1090 MHz.
+///
+/// Takes raw cu8 samples at 2 MS/s, looks for the four-pulse preamble,
+/// slices the 1 µs pulse-position bits that follow, and checks each
+/// message's CRC-24, repairing one or two flipped bits in long messages
+/// when allowed. DF11, DF17 and DF18 messages are checked directly. Other
+/// formats overlay their parity with the aircraft address, so they are
+/// only accepted when that address was heard in a checked message in the
+/// last minute.
+pub struct ModeSDemod {
+    max_errors: u8,
+    /// Where DF11, DF17 and DF18 messages failing their checksum go.
+    failures: Option<FailureDump>,
+    /// Magnitudes not yet searched, the tail of the previous chunk first.
+    mags: Vec<u16>,
+    /// The index of `mags[0]` in the stream.
+    position: u64,
+    /// The I byte of a pair split across chunks.
+    split: Option<u8>,
+    /// When each address was last heard in a checked message.
+    known: HashMap<u32, u64>,
+}
+
+impl Default for ModeSDemod {
+    fn default() -> Self {
+        ModeSDemod::new()
+    }
+}
+
+impl ModeSDemod {
+    /// Create a demodulator repairing single-bit errors.
+    pub fn new() -> Self {
+        ModeSDemod {
+            max_errors: 1,
+            failures: None,
+            mags: Vec::new(),
+            position: 0,
+            split: None,
+            known: HashMap::new(),
+        }
+    }
+
+    /// Set how many flipped bits error correction may repair.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_errors` - 0 to disable correction, 1, or 2. Two-bit
+    ///   correction recovers more weak messages at the cost of the
+    ///   occasional false decode.
+    ///
+    /// # Returns
+    ///
+    /// The `ModeSDemod` with the new limit.
+    pub fn with_max_errors(mut self, max_errors: u8) -> Self {
+        assert!(max_errors <= 2, "at most two bits can be corrected");
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Write the DF11, DF17 and DF18 messages that fail their checksum,
+    /// even after error correction, to a debug directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dump` - Where the failures go.
+    ///
+    /// # Returns
+    ///
+    /// The `ModeSDemod` writing failures to `dump`.
+    pub fn with_failure_dump(mut self, dump: FailureDump) -> Self {
+        self.failures = Some(dump);
+        self
+    }
+
+    /// Get where failures are written, if anywhere.
+    pub fn failure_dump(&self) -> Option<&FailureDump> {
+        self.failures.as_ref()
+    }
+
+    /// Demodulate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - Interleaved cu8 I/Q at 2 MS/s, as read from the device.
+    ///
+    /// # Returns
+    ///
+    /// Every message found, in order. Messages crossing into the next
+    /// chunk are returned by a later call.
+    pub fn process(&mut self, raw: &[u8]) -> Vec<Frame> {
+        let table = magnitudes();
+        let mag = |i: u8, q: u8| table[(i as usize) << 8 | q as usize];
+        let mut raw = raw;
+        if let (Some(i), Some((&q, rest))) = (self.split, raw.split_first()) {
+            self.mags.push(mag(i, q));
+            self.split = None;
+            raw = rest;
+        }
+        let pairs = raw.chunks_exact(2);
+        self.split = pairs.remainder().first().copied();
+        self.mags.extend(pairs.map(|iq| mag(iq[0], iq[1])));
+
+        let mut frames = Vec::new();
+        let mut j = 0;
+        while j + PREAMBLE + LONG_SAMPLES <= self.mags.len() {
+            match self.try_frame(j) {
+                Some(frame) => {
+                    j += PREAMBLE + frame.len * 16;
+                    frames.push(frame);
+                }
+                None => j += 1,
+            }
+        }
+        self.mags.drain(..j);
+        self.position += j as u64;
+        frames
+    }
+
+    /// Get the number of samples taken in since the demodulator was created
+    /// or reset, on the same clock as `Frame::sample`.
+    pub fn samples(&self) -> u64 {
+        self.position + self.mags.len() as u64
+    }
+
+    /// Forget buffered samples, the sample count and the known addresses.
+    pub fn reset(&mut self) {
+        self.mags.clear();
+        self.position = 0;
+        self.split = None;
+        self.known.clear();
+    }
+
+    fn dump(&mut self, frame: &Frame) {
+        if let Some(dump) = &mut self.failures {
+            dump.write(&Failure {
+                decoder: "adsb".into(),
+                reason: "crc".into(),
+                position: frame.sample,
+                context: vec![("signal".into(), frame.signal.to_string())],
+                bytes: frame.bytes().to_vec(),
+                soft: frame.soft_bits().to_vec(),
+            });
+        }
+    }
+
+    fn try_frame(&mut self, j: usize) -> Option<Frame> {
+        let m = &self.mags[j..];
+        let signal = preamble(m)?;
+
+        let mut data = [0u8; 14];
+        let mut soft = [0i8; 112];
+        for (i, byte) in data.iter_mut().enumerate() {
+            for bit in 0..8 {
+                let k = PREAMBLE + 2 * (8 * i + bit);
+                *byte = (*byte << 1) | (m[k] > m[k + 1]) as u8;
+                // The difference between the two halves of the bit,
+                // relative to their sum.
+                let (a, b) = (m[k] as f32, m[k + 1] as f32);
+                soft[8 * i + bit] = soft_bit((a - b) / (a + b).max(1.0));
+            }
+        }
+        let df = data[0] >> 3;
+        let len = message_len(df);
+        data[len..].fill(0);
+        let mut frame = Frame {
+            data,
+            len,
+            icao: 0,
+            sample: self.position + j as u64,
+            signal,
+            corrected: 0,
+            checked: true,
+            soft,
+        };
+
+        let crc = residual(frame.bytes());
+        let checked = match df {
+            11 if crc < 0x80 => true,
+            17 | 18 if crc == 0 => true,
+            17 | 18 => match correct(&mut frame.data, self.max_errors) {
+                Some(fixed) => {
+                    frame.corrected = fixed;
+                    true
+                }
+                None => {
+                    self.dump(&frame);
+                    return None;
+                }
+            },
+            11 => {
+                self.dump(&frame);
+                return None;
+            }
+            0 | 4 | 5 | 16 | 20 | 21 => {
+                let seen = self.known.get(&crc)?;
+                if frame.sample.saturating_sub(*seen) > KNOWN_TTL {
+                    self.known.remove(&crc);
+                    return None;
+                }
+                frame.icao = crc;
+                false
+            }
+            _ => return None,
+        };
+        frame.checked = checked;
+        if checked {
+            frame = frame.with_announced_icao();
+            // DF18 with CF 0 uses an ICAO address too, others do not.
+            if df != 18 || frame.data[0] & 0x07 == 0 {
+                self.known.insert(frame.icao, frame.sample);
+            }
+        }
+        Some(frame)
+    }
+}
+
+/// Check for a preamble at the start of `m`.
+///
+/// # Returns
+///
+/// The mean level of the four pulses, or `None`.
+fn preamble(m: &[u16]) -> Option<u16> {
+    // Pulses at 0, 1, 3.5 and 4.5 µs land on samples 0, 2, 7 and 9, with
+    // quiet samples between them and until the data starts at 8 µs.
+    let shaped = m[0] > m[1]
+        && m[1] < m[2]
+        && m[2] > m[3]
+        && m[3] < m[0]
+        && m[4] < m[0]
+        && m[5] < m[0]
+        && m[6] < m[0]
+        && m[7] > m[8]
+        && m[8] < m[9]
+        && m[9] > m[6];
+    if !shaped {
+        return None;
+    }
+    let sum = m[0] as u32 + m[2] as u32 + m[7] as u32 + m[9] as u32;
+    // The gaps must stay below two thirds of the mean pulse.
+    let high = (sum / 6) as u16;
+    if m[4] >= high || m[5] >= high || m[11..PREAMBLE].iter().any(|&x| x >= high) {
+        return None;
+    }
+    Some((sum / 4) as u16)
+}
+
+/// The length in bytes of a message of some downlink format.
+fn message_len(df: u8) -> usize {
+    if df >= 16 {
+        14
+    } else {
+        7
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: [u8; 14] = [
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ];
+
+    /// cu8 samples at 2 MS/s of a message with pulses `high` above the
+    /// middle and noise jittering `noise` counts either way.
+    fn transmit(message: &[u8], high: u8, noise: u8) -> Vec<u8> {
+        let mut levels = vec![false; 32 + PREAMBLE];
+        for i in [0, 2, 7, 9] {
+            levels[32 + i] = true;
+        }
+        for i in 0..8 * message.len() {
+            let bit = message[i / 8] >> (7 - i % 8) & 1 == 1;
+            levels.extend([bit, !bit]);
+        }
+        levels.resize(levels.len() + 32, false);
+        let mut state = 0x2545_f491u32;
+        let mut out = Vec::new();
+        for level in levels {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let jitter = (state % (2 * noise as u32 + 1)) as i32 - noise as i32;
+            let i = 128 + if level { high as i32 } else { 0 } + jitter;
+            out.extend([i.clamp(0, 255) as u8, 128]);
+        }
+        out
+    }
+
+    #[test]
+    fn grades_clean_and_noisy_frames() {
+        let clean = ModeSDemod::new().process(&transmit(&MESSAGE, 100, 0));
+        assert_eq!(clean.len(), 1);
+        let quality = clean[0].quality();
+        assert!(quality.crc_ok);
+        assert_eq!(quality.corrected_bits, 0);
+        assert!(quality.evm < 0.05, "{} evm", quality.evm);
+        let hard: Vec<u8> = clean[0]
+            .soft_bits()
+            .chunks(8)
+            .map(|c| c.iter().fold(0, |acc, &s| (acc << 1) | (s > 0) as u8))
+            .collect();
+        assert_eq!(hard, MESSAGE);
+
+        let noisy = ModeSDemod::new().process(&transmit(&MESSAGE, 100, 40));
+        assert_eq!(noisy.len(), 1);
+        assert_eq!(noisy[0].bytes(), MESSAGE);
+        let marginal = noisy[0].quality();
+        assert!(marginal.evm > quality.evm + 0.1, "{} evm", marginal.evm);
+        assert!(marginal.bit_error_rate > quality.bit_error_rate);
+    }
+
+    #[test]
+    fn checked_frames_have_full_strength_bits() {
+        let frame = Frame::from_bytes(&MESSAGE).unwrap();
+        assert_eq!(frame.soft_bits().len(), 112);
+        assert_eq!(frame.soft_bits()[..2], [127, -127]);
+        let quality = frame.quality();
+        assert!(quality.crc_ok);
+        assert_eq!(quality.bit_error_rate, 0.0);
+    }
+
+    #[test]
+    fn dumps_messages_failing_their_checksum() {
+        let dir = std::env::temp_dir().join(format!("radion-adsb-dump-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut damaged = MESSAGE;
+        damaged[5] ^= 0x07;
+        let mut demod = ModeSDemod::new().with_failure_dump(FailureDump::new(&dir).unwrap());
+        let mut raw = transmit(&damaged, 100, 0);
+        raw.extend(transmit(&MESSAGE, 100, 0));
+        assert_eq!(demod.process(&raw).len(), 1);
+        assert_eq!(demod.failure_dump().unwrap().written(), 1);
+
+        let path = std::fs::read_dir(&dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let failure = Failure::read(&path).unwrap();
+        assert_eq!(failure.decoder, "adsb");
+        assert_eq!(failure.reason, "crc");
+        assert_eq!(failure.position, 32);
+        assert_eq!(failure.bytes, damaged);
+        assert_eq!(failure.soft.len(), 112);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,559 @@
+use super::cpr::Cpr;
+
+const CALLSIGN_CHARS: &[u8; 64] =
+    b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+
+/// How a speed in an airborne velocity message was measured.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpeedKind {
+    /// Speed over the ground, with `heading` the track angle.
+    Ground,
+    /// Indicated airspeed, with `heading` the magnetic heading.
+    Indicated,
+    /// True airspeed, with `heading` the magnetic heading.
+    True,
+}
+
+/// A decoded Mode S message.
+///
+/// Altitudes are in feet, speeds in knots, angles in degrees clockwise
+/// from north and vertical rates in feet per minute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// DF11 all-call reply, sent in answer to radar interrogations.
+    AllCall {
+        /// The aircraft address.
+        icao: u32,
+        /// The transponder capability field.
+        capability: u8,
+    },
+    /// DF0, DF4, DF16 or DF20 reply carrying the pressure altitude.
+    SurveillanceAltitude {
+        /// The aircraft address.
+        icao: u32,
+        /// The altitude, or `None` if not available.
+        altitude: Option<i32>,
+    },
+    /// DF5 or DF21 reply carrying the squawk code.
+    SurveillanceIdentity {
+        /// The aircraft address.
+        icao: u32,
+        /// The squawk as its four octal digits read in decimal, so 7700
+        /// is 7700.
+        squawk: u16,
+    },
+    /// ADS-B aircraft identification.
+    Identification {
+        /// The aircraft address.
+        icao: u32,
+        /// The emitter category as set A to D in the high nibble and the
+        /// number in the low one, e.g. 0xa3 for A3, a large aircraft.
+        category: u8,
+        /// The callsign or registration, without trailing spaces.
+        callsign: String,
+    },
+    /// ADS-B airborne position.
+    AirbornePosition {
+        /// The aircraft address.
+        icao: u32,
+        /// The altitude, or `None` if not available.
+        altitude: Option<i32>,
+        /// Whether the altitude is GNSS height rather than barometric.
+        gnss: bool,
+        /// The encoded position.
+        cpr: Cpr,
+    },
+    /// ADS-B surface position.
+    SurfacePosition {
+        /// The aircraft address.
+        icao: u32,
+        /// The ground speed, or `None` if not available.
+        speed: Option<f32>,
+        /// The ground track, or `None` if not available.
+        track: Option<f32>,
+        /// The encoded position.
+        cpr: Cpr,
+    },
+    /// ADS-B airborne velocity.
+    AirborneVelocity {
+        /// The aircraft address.
+        icao: u32,
+        /// The speed, or `None` if not available.
+        speed: Option<f32>,
+        /// What `speed` and `heading` measure.
+        speed_kind: SpeedKind,
+        /// The track or heading, or `None` if not available.
+        heading: Option<f32>,
+        /// The vertical rate, or `None` if not available.
+        vertical_rate: Option<i32>,
+    },
+    /// Anything else, such as other ADS-B message types or Comm-B data.
+    Other {
+        /// The downlink format.
+        df: u8,
+        /// The aircraft address.
+        icao: u32,
+    },
+}
+
+impl Message {
+    /// Get the address of the aircraft the message is from.
+    pub fn icao(&self) -> u32 {
+        match *self {
+            Message::AllCall { icao, .. }
+            | Message::SurveillanceAltitude { icao, .. }
+            | Message::SurveillanceIdentity { icao, .. }
+            | Message::Identification { icao, .. }
+            | Message::AirbornePosition { icao, .. }
+            | Message::SurfacePosition { icao, .. }
+            | Message::AirborneVelocity { icao, .. }
+            | Message::Other { icao, .. } => icao,
+        }
+    }
+
+    /// Decode a checked message.
+    pub(crate) fn decode(msg: &[u8], icao: u32) -> Message {
+        let df = msg[0] >> 3;
+        match df {
+            11 => Message::AllCall {
+                icao,
+                capability: msg[0] & 0x07,
+            },
+            0 | 4 | 16 | 20 => Message::SurveillanceAltitude {
+                icao,
+                altitude: ac13(bits(msg, 19, 13)),
+            },
+            5 | 21 => Message::SurveillanceIdentity {
+                icao,
+                squawk: squawk(bits(msg, 19, 13)),
+            },
+            // DF18 with CF 0 is ADS-B from a non-transponder device with an
+            // ICAO address.
+            17 => extended_squitter(&msg[4..11], icao).unwrap_or(Message::Other { df, icao }),
+            18 if msg[0] & 0x07 == 0 => {
+                extended_squitter(&msg[4..11], icao).unwrap_or(Message::Other { df, icao })
+            }
+            _ => Message::Other { df, icao },
+        }
+    }
+}
+
+/// Decode the 56-bit ME field of an extended squitter.
+fn extended_squitter(me: &[u8], icao: u32) -> Option<Message> {
+    // Field positions below are 1-based and inclusive, as in DO-260B.
+    let field = |from: usize, to: usize| bits(me, from - 1, to - from + 1);
+    let tc = field(1, 5);
+    match tc {
+        1..=4 => {
+            let ca = field(6, 8) as u8;
+            let category = if ca == 0 {
+                0
+            } else {
+                ((0xe - tc as u8) << 4) | ca
+            };
+            let callsign: String = (0..8)
+                .map(|i| CALLSIGN_CHARS[field(9 + 6 * i, 14 + 6 * i) as usize] as char)
+                .collect();
+            Some(Message::Identification {
+                icao,
+                category,
+                callsign: callsign.trim_end_matches([' ', '#']).to_string(),
+            })
+        }
+        5..=8 => {
+            let movement = field(6, 12);
+            let track = (field(13, 13) == 1).then(|| field(14, 20) as f32 * 360.0 / 128.0);
+            Some(Message::SurfacePosition {
+                icao,
+                speed: surface_speed(movement),
+                track,
+                cpr: cpr(&field, true),
+            })
+        }
+        9..=18 | 20..=22 => {
+            let code = field(9, 20);
+            let gnss = tc >= 20;
+            let altitude = if gnss {
+                // GNSS height comes in metres.
+                (code != 0).then(|| (code as f64 * 3.28084).round() as i32)
+            } else {
+                ac12(code)
+            };
+            Some(Message::AirbornePosition {
+                icao,
+                altitude,
+                gnss,
+                cpr: cpr(&field, false),
+            })
+        }
+        19 => velocity(&field, icao),
+        _ => None,
+    }
+}
+
+fn cpr(field: &impl Fn(usize, usize) -> u32, surface: bool) -> Cpr {
+    Cpr {
+        odd: field(22, 22) == 1,
+        lat: field(23, 39),
+        lon: field(40, 56),
+        surface,
+    }
+}
+
+fn velocity(field: &impl Fn(usize, usize) -> u32, icao: u32) -> Option<Message> {
+    let subtype = field(6, 8);
+    let supersonic = subtype == 2 || subtype == 4;
+    let scale = if supersonic { 4.0 } else { 1.0 };
+    let (speed, speed_kind, heading) = match subtype {
+        1 | 2 => {
+            let (ew, ns) = (field(15, 24), field(26, 35));
+            if ew == 0 || ns == 0 {
+                (None, SpeedKind::Ground, None)
+            } else {
+                let sign = |negative: u32| if negative == 1 { -1.0 } else { 1.0 };
+                let vx = sign(field(14, 14)) * (ew - 1) as f32 * scale;
+                let vy = sign(field(25, 25)) * (ns - 1) as f32 * scale;
+                let track = vx.atan2(vy).to_degrees().rem_euclid(360.0);
+                (Some(vx.hypot(vy)), SpeedKind::Ground, Some(track))
+            }
+        }
+        3 | 4 => {
+            let heading = (field(14, 14) == 1).then(|| field(15, 24) as f32 * 360.0 / 1024.0);
+            let kind = if field(25, 25) == 1 {
+                SpeedKind::True
+            } else {
+                SpeedKind::Indicated
+            };
+            let raw = field(26, 35);
+            let speed = (raw != 0).then(|| (raw - 1) as f32 * scale);
+            (speed, kind, heading)
+        }
+        _ => return None,
+    };
+    let rate = field(38, 46);
+    let vertical_rate = (rate != 0).then(|| {
+        let rate = (rate as i32 - 1) * 64;
+        if field(37, 37) == 1 {
+            -rate
+        } else {
+            rate
+        }
+    });
+    Some(Message::AirborneVelocity {
+        icao,
+        speed,
+        speed_kind,
+        heading,
+        vertical_rate,
+    })
+}
+
+/// Read `len` bits starting `start` bits into `data`, most significant
+/// first.
+pub(crate) fn bits(data: &[u8], start: usize, len: usize) -> u32 {
+    (start..start + len).fold(0, |acc, i| {
+        (acc << 1) | ((data[i / 8] >> (7 - i % 8)) & 1) as u32
+    })
+}
+
+/// Decode the 12-bit altitude of an airborne position message.
+fn ac12(code: u32) -> Option<i32> {
+    // Put back the M bit the 12-bit form leaves out.
+    ac13(((code & 0xfc0) << 1) | (code & 0x03f))
+}
+
+/// Decode a 13-bit altitude code.
+fn ac13(code: u32) -> Option<i32> {
+    if code == 0 || code & 0x040 != 0 {
+        // Not available, or in metres, which nothing uses.
+        return None;
+    }
+    if code & 0x010 != 0 {
+        // 25 ft steps: the 11 bits around M and Q.
+        let n = ((code & 0x1f80) >> 2) | ((code & 0x0020) >> 1) | (code & 0x000f);
+        return Some(n as i32 * 25 - 1000);
+    }
+    gillham(code)
+}
+
+/// Decode a Gillham-coded altitude in 100 ft steps, the code older
+/// transponders send.
+fn gillham(code: u32) -> Option<i32> {
+    let [c1, a1, c2, a2, c4, a4, _, b1, _, b2, d2, b4, d4] = gillham_bits(code);
+    let gray_to_binary = |bits: &[u32]| {
+        let mut value = 0u32;
+        let mut prev = 0;
+        for &b in bits {
+            prev ^= b;
+            value = (value << 1) | prev;
+        }
+        value
+    };
+    let fives = gray_to_binary(&[d2, d4, a1, a2, a4, b1, b2, b4]) as i32;
+    let mut ones = gray_to_binary(&[c1, c2, c4]) as i32;
+    // The 100 ft code counts 1, 2, 3, 4 then 7 for the fifth step, and 5 is
+    // not a valid step, so swap the two before rejecting anything past 5.
+    if ones & 5 == 5 {
+        ones ^= 2;
+    }
+    if ones == 0 || ones > 5 {
+        return None;
+    }
+    if fives % 2 == 1 {
+        ones = 6 - ones;
+    }
+    Some((fives * 5 + ones - 13) * 100)
+}
+
+/// Split a 13-bit code into its bits, in transmission order: C1 A1 C2 A2
+/// C4 A4 X B1 D1 B2 D2 B4 D4.
+fn gillham_bits(code: u32) -> [u32; 13] {
+    std::array::from_fn(|i| (code >> (12 - i)) & 1)
+}
+
+/// Decode the squawk of an identity reply.
+fn squawk(code: u32) -> u16 {
+    let [c1, a1, c2, a2, c4, a4, _, b1, d1, b2, d2, b4, d4] = gillham_bits(code);
+    let digit = |x4: u32, x2: u32, x1: u32| (x4 << 2 | x2 << 1 | x1) as u16;
+    digit(a4, a2, a1) * 1000 + digit(b4, b2, b1) * 100 + digit(c4, c2, c1) * 10 + digit(d4, d2, d1)
+}
+
+/// Decode the movement field of a surface position message.
+fn surface_speed(movement: u32) -> Option<f32> {
+    // Each band starts at a code and speed and has its own step.
+    const BANDS: [(u32, f32, f32); 6] = [
+        (2, 0.125, 0.125),
+        (9, 1.0, 0.25),
+        (13, 2.0, 0.5),
+        (39, 15.0, 1.0),
+        (94, 70.0, 2.0),
+        (109, 100.0, 5.0),
+    ];
+    match movement {
+        0 | 125.. => None,
+        1 => Some(0.0),
+        124 => Some(175.0),
+        _ => BANDS
+            .iter()
+            .rev()
+            .find(|&&(start, _, _)| movement >= start)
+            .map(|&(start, speed, step)| speed + (movement - start) as f32 * step),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::adsb::Frame;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode(s: &str) -> Message {
+        Frame::from_bytes(&hex(s)).expect("checksum").decode()
+    }
+
+    #[test]
+    fn identification() {
+        let frame = Frame::from_bytes(&hex("8D4840D6202CC371C32CE0576098")).unwrap();
+        assert_eq!(frame.icao(), 0x4840d6);
+        assert_eq!(
+            frame.decode(),
+            Message::Identification {
+                icao: 0x4840d6,
+                category: 0,
+                callsign: "KLM1023".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        assert!(Frame::from_bytes(&hex("8D4840D6202CC371C32CE0576099")).is_none());
+    }
+
+    #[test]
+    fn airborne_position() {
+        let Message::AirbornePosition {
+            icao,
+            altitude,
+            gnss,
+            cpr,
+        } = decode("8D40621D58C382D690C8AC2863A7")
+        else {
+            panic!("not a position");
+        };
+        assert_eq!(icao, 0x40621d);
+        assert_eq!(altitude, Some(38000));
+        assert!(!gnss);
+        assert_eq!(
+            cpr,
+            Cpr {
+                odd: false,
+                lat: 93000,
+                lon: 51372,
+                surface: false,
+            }
+        );
+    }
+
+    #[test]
+    fn ground_speed() {
+        let Message::AirborneVelocity {
+            speed,
+            speed_kind,
+            heading,
+            vertical_rate,
+            ..
+        } = decode("8D485020994409940838175B284F")
+        else {
+            panic!("not a velocity");
+        };
+        assert_eq!(speed_kind, SpeedKind::Ground);
+        assert!((speed.unwrap() - 159.20).abs() < 0.01);
+        assert!((heading.unwrap() - 182.88).abs() < 0.01);
+        assert_eq!(vertical_rate, Some(-832));
+    }
+
+    #[test]
+    fn airspeed() {
+        let Message::AirborneVelocity {
+            speed,
+            speed_kind,
+            heading,
+            vertical_rate,
+            ..
+        } = decode("8DA05F219B06B6AF189400CBC33F")
+        else {
+            panic!("not a velocity");
+        };
+        assert_eq!(speed_kind, SpeedKind::True);
+        assert_eq!(speed, Some(375.0));
+        assert!((heading.unwrap() - 243.98).abs() < 0.01);
+        assert_eq!(vertical_rate, Some(-2304));
+    }
+
+    #[test]
+    fn surveillance_replies() {
+        let msg = hex("A02014B400000000000000F9D514");
+        assert!(matches!(
+            Message::decode(&msg, 0),
+            Message::SurveillanceAltitude {
+                altitude: Some(32300),
+                ..
+            }
+        ));
+        let msg = hex("2A00516D492B80");
+        assert!(matches!(
+            Message::decode(&msg, 0),
+            Message::SurveillanceIdentity { squawk: 356, .. }
+        ));
+    }
+
+    /// Build a 13-bit code from named bits, in `gillham_bits` order.
+    fn code(names: &[&str]) -> u32 {
+        const ORDER: [&str; 13] = [
+            "C1", "A1", "C2", "A2", "C4", "A4", "X", "B1", "D1", "B2", "D2", "B4", "D4",
+        ];
+        names.iter().fold(0, |code, name| {
+            let i = ORDER.iter().position(|o| o == name).unwrap();
+            code | 1 << (12 - i)
+        })
+    }
+
+    #[test]
+    fn gillham_table() {
+        let table: [(&[&str], i32); 13] = [
+            (&["C4"], -1200),
+            (&["C2", "C4"], -1100),
+            (&["C2"], -1000),
+            (&["C1", "C2"], -900),
+            (&["C1"], -800),
+            (&["B4", "C1"], -700),
+            (&["B4", "C1", "C2"], -600),
+            (&["B4", "C2"], -500),
+            (&["B4", "C2", "C4"], -400),
+            (&["B4", "C4"], -300),
+            (&["B2", "B4", "C4"], -200),
+            // D4 is first needed above 30700 ft.
+            (&["A1", "C4"], 30_700),
+            (&["D4", "A1", "C4"], 30_800),
+        ];
+        for (names, feet) in table {
+            assert_eq!(ac13(code(names)), Some(feet), "{:?}", names);
+        }
+    }
+
+    /// Encode an altitude the way a Gillham encoder does: a reflected Gray
+    /// code of the 500 ft band, and within it a five step cycle of C bits
+    /// that runs backwards in odd bands.
+    fn gillham_encode(feet: i32) -> u32 {
+        const HUNDREDS: [&[&str]; 5] = [&["C4"], &["C2", "C4"], &["C2"], &["C1", "C2"], &["C1"]];
+        const FIVES: [&str; 8] = ["D2", "D4", "A1", "A2", "A4", "B1", "B2", "B4"];
+        let steps = (feet / 100 + 12) as u32;
+        let (fives, mut ones) = (steps / 5, steps % 5);
+        if fives % 2 == 1 {
+            ones = 4 - ones;
+        }
+        let gray = fives ^ (fives >> 1);
+        let mut names: Vec<&str> = HUNDREDS[ones as usize].to_vec();
+        names.extend(
+            (0..8)
+                .filter(|i| gray >> (7 - i) & 1 == 1)
+                .map(|i| FIVES[i]),
+        );
+        code(&names)
+    }
+
+    #[test]
+    fn gillham_round_trip() {
+        for feet in (-1200..=126_700).step_by(100) {
+            assert_eq!(ac13(gillham_encode(feet)), Some(feet));
+        }
+    }
+
+    #[test]
+    fn gillham_rejects_invalid_hundreds() {
+        // C1 C2 C4 all set, or none, or C1 and C4 alone, is no 100 ft step.
+        for names in [&["C1", "C2", "C4"][..], &["B4"], &["C1", "C4"]] {
+            assert_eq!(ac13(code(names)), None, "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn q_bit_altitude() {
+        // 25 ft steps: N = 1560 gives 38000 ft.
+        let n: u32 = 1560;
+        let code = ((n & 0x7e0) << 2) | ((n & 0x010) << 1) | 0x010 | (n & 0x00f);
+        assert_eq!(ac13(code), Some(38000));
+    }
+
+    #[test]
+    fn squawk_table() {
+        let table: [(&[&str], u16); 5] = [
+            (&[], 0),
+            (&["A1", "B2"], 1200),
+            (&["A1", "A2", "A4", "B1", "B2", "B4"], 7700),
+            (&["C1", "C2", "C4", "D1", "D2", "D4"], 77),
+            (&["A4", "A2", "B1", "C4", "D2"], 6142),
+        ];
+        for (names, expected) in table {
+            assert_eq!(squawk(code(names)), expected, "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn surface_speed_bands() {
+        assert_eq!(surface_speed(0), None);
+        assert_eq!(surface_speed(1), Some(0.0));
+        assert_eq!(surface_speed(2), Some(0.125));
+        assert_eq!(surface_speed(9), Some(1.0));
+        assert_eq!(surface_speed(38), Some(14.5));
+        assert_eq!(surface_speed(124), Some(175.0));
+        assert_eq!(surface_speed(125), None);
+    }
+}
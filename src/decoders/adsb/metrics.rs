@@ -0,0 +1,282 @@
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::stats::StreamStats;
+
+/// How often the server thread checks for connections and for being
+/// dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a scrape may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Counters of an ADS-B station, served by `MetricsServer`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StationMetrics {
+    /// Mode S frames received with a valid CRC.
+    pub frames: u64,
+    /// Aircraft currently in the `Tracker`.
+    pub aircraft: usize,
+    /// Clients connected to the SBS feed.
+    pub sbs_clients: usize,
+    /// Clients connected to the Beast feed.
+    pub beast_clients: usize,
+    /// Messages dropped for feed clients whose queue was full.
+    pub feed_dropped: u64,
+    /// The device's read path, from `RingReader::stats` or
+    /// `Device::stream_stats`.
+    pub usb: StreamStats,
+}
+
+impl StationMetrics {
+    /// Render the counters in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, u64)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        metric(
+            "radion_adsb_frames_total",
+            "counter",
+            "Mode S frames received with a valid CRC.",
+            &[("", self.frames)],
+        );
+        metric(
+            "radion_adsb_aircraft",
+            "gauge",
+            "Aircraft currently tracked.",
+            &[("", self.aircraft as u64)],
+        );
+        metric(
+            "radion_adsb_feed_clients",
+            "gauge",
+            "Clients connected to each feed.",
+            &[
+                ("{format=\"sbs\"}", self.sbs_clients as u64),
+                ("{format=\"beast\"}", self.beast_clients as u64),
+            ],
+        );
+        metric(
+            "radion_adsb_feed_dropped_total",
+            "counter",
+            "Messages dropped for slow feed clients.",
+            &[("", self.feed_dropped)],
+        );
+        metric(
+            "radion_usb_transfers_total",
+            "counter",
+            "USB transfers delivered.",
+            &[("", self.usb.frames)],
+        );
+        metric(
+            "radion_usb_dropped_total",
+            "counter",
+            "USB transfers dropped because processing fell behind.",
+            &[("", self.usb.dropped_frames)],
+        );
+        metric(
+            "radion_usb_discontinuities_total",
+            "counter",
+            "Gaps where the dongle dropped samples.",
+            &[("", self.usb.discontinuities)],
+        );
+        out
+    }
+}
+
+/// HTTP server answering Prometheus scrapes with `StationMetrics`.
+///
+/// Scrapes are answered on a thread of its own from the last counters
+/// passed to `update`, so a slow or stuck scraper never holds up the loop
+/// reading samples, and `update` only copies the counters. Every request
+/// gets the metrics, whatever its path. The thread stops when the server
+/// is dropped.
+///
+/// ```no_run
+/// use radion::decoders::adsb::{MetricsServer, ModeSDemod, StationMetrics, SAMPLE_RATE};
+/// use radion::Device;
+///
+/// let metrics = MetricsServer::bind("0.0.0.0:9105")?;
+/// let device = Device::new(0)?;
+/// device.set_center_freq(1_090_000_000)?;
+/// device.set_sample_rate(SAMPLE_RATE)?;
+/// device.reset_buffer()?;
+///
+/// let mut demod = ModeSDemod::new();
+/// let mut counters = StationMetrics::default();
+/// loop {
+///     counters.frames += demod.process(&device.read_sync(262_144)?).len() as u64;
+///     metrics.update(&counters);
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct MetricsServer {
+    metrics: Arc<Mutex<StationMetrics>>,
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Start listening.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `"0.0.0.0:9105"`.
+    ///
+    /// # Returns
+    ///
+    /// A new `MetricsServer` serving all-zero counters until the first
+    /// `update` if successful, otherwise an `Error`.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let metrics = Arc::new(Mutex::new(StationMetrics::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let metrics = metrics.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("radion-adsb-metrics".into())
+                .spawn(move || {
+                    while !stop.load(Ordering::Acquire) {
+                        match listener.accept() {
+                            Ok((client, _)) => {
+                                let body = metrics
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .to_prometheus();
+                                serve(client, &body);
+                            }
+                            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                                thread::sleep(POLL_INTERVAL)
+                            }
+                            Err(_) => thread::sleep(POLL_INTERVAL),
+                        }
+                    }
+                })?
+        };
+        Ok(MetricsServer {
+            metrics,
+            addr,
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    /// Get the address the server listens on.
+    ///
+    /// # Returns
+    ///
+    /// The address, useful after binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replace the counters served.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The station's current counters.
+    pub fn update(&self, metrics: &StationMetrics) {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone_from(metrics);
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Answer one HTTP request, ignoring clients that go away.
+fn serve(mut client: TcpStream, body: &str) {
+    let _ = client.set_nonblocking(false);
+    let _ = client.set_read_timeout(Some(REQUEST_TIMEOUT));
+    let _ = client.set_write_timeout(Some(REQUEST_TIMEOUT));
+    let mut request = [0u8; 1024];
+    let _ = client.read(&mut request);
+    let _ = write!(
+        client,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters() -> StationMetrics {
+        StationMetrics {
+            frames: 1234,
+            aircraft: 7,
+            sbs_clients: 2,
+            beast_clients: 1,
+            feed_dropped: 3,
+            usb: StreamStats {
+                frames: 500,
+                dropped_frames: 4,
+                discontinuities: 1,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let text = counters().to_prometheus();
+        for line in [
+            "# TYPE radion_adsb_frames_total counter",
+            "radion_adsb_frames_total 1234",
+            "radion_adsb_aircraft 7",
+            "radion_adsb_feed_clients{format=\"sbs\"} 2",
+            "radion_adsb_feed_clients{format=\"beast\"} 1",
+            "radion_adsb_feed_dropped_total 3",
+            "radion_usb_transfers_total 500",
+            "radion_usb_dropped_total 4",
+            "radion_usb_discontinuities_total 1",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?}", line);
+        }
+    }
+
+    #[test]
+    fn serves_the_latest_counters() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        server.update(&counters());
+
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\n\r\n# HELP "));
+        assert!(response.contains("\nradion_adsb_frames_total 1234\n"));
+        drop(server);
+    }
+}
@@ -0,0 +1,54 @@
+//! ADS-B and Mode S reception on 1090 MHz.
+//!
+//! `ModeSDemod` finds messages in raw samples at 2 MS/s and checks them;
+//! `Frame::decode` turns them into typed `Message`s, and `Frame::quality`
+//! and `Frame::soft_bits` tell how cleanly they were received. Positions arrive in
+//! CPR form and are resolved with `Cpr::decode_global` from a pair of
+//! messages or `Cpr::decode_local` near a known position. `FeedServer`
+//! serves frames over TCP in the SBS and Beast formats for tools built
+//! around dump1090, `Tracker` keeps a table of the aircraft heard, and
+//! `MetricsServer` serves a station's counters to Prometheus. The
+//! `radion-adsb` binary puts them together into a station.
+//!
+//! ```no_run
+//! use radion::decoders::adsb::{Message, ModeSDemod, SAMPLE_RATE};
+//! use radion::Device;
+//!
+//! let device = Device::new(0)?;
+//! device.set_center_freq(1_090_000_000)?;
+//! device.set_sample_rate(SAMPLE_RATE)?;
+//! device.set_tuner_gain_mode(false)?;
+//! device.reset_buffer()?;
+//!
+//! let mut demod = ModeSDemod::new();
+//! loop {
+//!     for frame in demod.process(&device.read_sync(262_144)?) {
+//!         if let Message::Identification { icao, callsign, .. } = frame.decode() {
+//!             println!("{:06x} {}", icao, callsign);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod cpr;
+mod crc;
+mod demod;
+mod message;
+mod metrics;
+mod sbs;
+mod server;
+mod tracker;
+
+pub use cpr::{Cpr, Position};
+pub use crc::crc24;
+pub use demod::{Frame, ModeSDemod, SAMPLE_RATE};
+pub use message::{Message, SpeedKind};
+pub use metrics::{MetricsServer, StationMetrics};
+pub use sbs::SbsEncoder;
+pub use server::{FeedFormat, FeedServer};
+pub use tracker::{Aircraft, Change, TrackPoint, Tracker, TrackerEvent};
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::SystemTime;
+
+use super::cpr::{Locator, Position};
+use super::demod::{Frame, SAMPLE_RATE};
+use super::message::{Message, SpeedKind};
+use crate::utils::UtcTime;
+
+/// How long a silent aircraft is remembered, in samples: 60 seconds.
+const STALE: u64 = 60 * SAMPLE_RATE as u64;
+
+/// How often silent aircraft are forgotten, in samples: 10 seconds.
+const PRUNE_INTERVAL: u64 = 10 * SAMPLE_RATE as u64;
+
+#[derive(Default)]
+struct Aircraft {
+    locator: Locator,
+    seen: u64,
+}
+
+/// Encoder for the SBS-1 BaseStation text format.
+///
+/// Turns frames into the comma-separated `MSG` lines served on port 30003
+/// by dump1090 and read by Virtual Radar Server, PlanePlotter and many
+/// feeders. Positions are resolved from even/odd pairs per aircraft, or
+/// near the aircraft's last position, so the encoder keeps a little state
+/// per aircraft and must see every frame.
+pub struct SbsEncoder {
+    reference: Option<Position>,
+    aircraft: HashMap<u32, Aircraft>,
+    pruned: u64,
+}
+
+impl Default for SbsEncoder {
+    fn default() -> Self {
+        SbsEncoder::new()
+    }
+}
+
+impl SbsEncoder {
+    /// Create an encoder without a receiver position.
+    pub fn new() -> Self {
+        SbsEncoder {
+            reference: None,
+            aircraft: HashMap::new(),
+            pruned: 0,
+        }
+    }
+
+    /// Set the receiver position.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The receiver's position. Surface positions can only
+    ///   be resolved with it.
+    ///
+    /// # Returns
+    ///
+    /// The `SbsEncoder` with the position set.
+    pub fn with_reference(mut self, reference: Position) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Encode a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame, stamped with the current time.
+    ///
+    /// # Returns
+    ///
+    /// The line, ending in CRLF, or `None` for messages the format has no
+    /// place for, such as Comm-B replies.
+    pub fn encode(&mut self, frame: &Frame) -> Option<String> {
+        self.prune(frame.sample);
+        let message = frame.decode();
+        let icao = message.icao();
+        let aircraft = self.aircraft.entry(icao).or_default();
+        aircraft.seen = frame.sample;
+
+        let mut fields = Fields::default();
+        let kind = match message {
+            Message::Identification { callsign, .. } => {
+                fields.callsign = callsign;
+                1
+            }
+            Message::SurfacePosition {
+                speed, track, cpr, ..
+            } => {
+                fields.speed = speed;
+                fields.track = track;
+                fields.position = aircraft.locator.locate(cpr, frame.sample, self.reference);
+                fields.on_ground = Some(true);
+                2
+            }
+            Message::AirbornePosition { altitude, cpr, .. } => {
+                fields.altitude = altitude;
+                fields.position = aircraft.locator.locate(cpr, frame.sample, self.reference);
+                fields.on_ground = Some(false);
+                3
+            }
+            Message::AirborneVelocity {
+                speed,
+                speed_kind,
+                heading,
+                vertical_rate,
+                ..
+            } => {
+                // The format only has ground speed and track.
+                if speed_kind == SpeedKind::Ground {
+                    fields.speed = speed;
+                    fields.track = heading;
+                }
+                fields.vertical_rate = vertical_rate;
+                fields.on_ground = Some(false);
+                4
+            }
+            Message::SurveillanceAltitude { altitude, .. } => {
+                fields.altitude = altitude;
+                if matches!(frame.df(), 0 | 16) {
+                    // Air-air replies carry only a vertical status bit.
+                    fields.on_ground = Some(frame.bytes()[0] & 0x04 != 0);
+                    7
+                } else {
+                    fields.status(frame.bytes()[0] & 0x07);
+                    5
+                }
+            }
+            Message::SurveillanceIdentity { squawk, .. } => {
+                fields.squawk = Some(squawk);
+                fields.emergency = Some(matches!(squawk, 7500 | 7600 | 7700));
+                fields.status(frame.bytes()[0] & 0x07);
+                6
+            }
+            Message::AllCall { capability, .. } => {
+                fields.on_ground = match capability {
+                    4 => Some(true),
+                    5 => Some(false),
+                    _ => None,
+                };
+                8
+            }
+            Message::Other { .. } => return None,
+        };
+        Some(fields.line(kind, icao, SystemTime::now()))
+    }
+
+    /// Forget every aircraft.
+    pub fn reset(&mut self) {
+        self.aircraft.clear();
+        self.pruned = 0;
+    }
+
+    /// Forget aircraft not heard for a while, at most every 10 seconds.
+    fn prune(&mut self, now: u64) {
+        // A sample count going backwards means the demodulator was reset.
+        if now >= self.pruned && now - self.pruned < PRUNE_INTERVAL {
+            return;
+        }
+        self.pruned = now;
+        self.aircraft
+            .retain(|_, a| a.seen <= now && now - a.seen <= STALE);
+    }
+}
+
+/// The variable fields of a line.
+#[derive(Default)]
+struct Fields {
+    callsign: String,
+    altitude: Option<i32>,
+    speed: Option<f32>,
+    track: Option<f32>,
+    position: Option<Position>,
+    vertical_rate: Option<i32>,
+    squawk: Option<u16>,
+    alert: Option<bool>,
+    emergency: Option<bool>,
+    spi: Option<bool>,
+    on_ground: Option<bool>,
+}
+
+impl Fields {
+    /// Fill in the flags carried by a surveillance reply's flight status.
+    fn status(&mut self, fs: u8) {
+        self.alert = Some(matches!(fs, 2..=4));
+        self.spi = Some(matches!(fs, 4 | 5));
+        self.on_ground = match fs {
+            1 | 3 => Some(true),
+            0 | 2 => Some(false),
+            _ => None,
+        };
+    }
+
+    fn line(&self, kind: u8, icao: u32, now: SystemTime) -> String {
+        let t = UtcTime::from_system_time(now);
+        let date = format!("{:04}/{:02}/{:02}", t.year, t.month, t.day);
+        let time = format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            t.hour,
+            t.minute,
+            t.second,
+            t.nanos / 1_000_000
+        );
+        let flag = |f: Option<bool>| match f {
+            Some(true) => "-1",
+            Some(false) => "0",
+            None => "",
+        };
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        let round = |v: Option<f32>| opt(v.map(|v| format!("{:.0}", v)));
+
+        let mut line = format!(
+            "MSG,{},1,1,{:06X},1,{date},{time},{date},{time}",
+            kind, icao
+        );
+        let (lat, lon) = match self.position {
+            Some(p) => (format!("{:.5}", p.lat), format!("{:.5}", p.lon)),
+            None => (String::new(), String::new()),
+        };
+        let _ = write!(
+            line,
+            ",{},{},{},{},{},{},{},{},{},{},{},{}\r\n",
+            self.callsign,
+            opt(self.altitude.map(|a| a.to_string())),
+            round(self.speed),
+            round(self.track),
+            lat,
+            lon,
+            opt(self.vertical_rate.map(|v| v.to_string())),
+            opt(self.squawk.map(|s| format!("{:04}", s))),
+            flag(self.alert),
+            flag(self.emergency),
+            flag(self.spi),
+            flag(self.on_ground),
+        );
+        line
+    }
+}
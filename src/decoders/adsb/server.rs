@@ -0,0 +1,184 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use super::cpr::Position;
+use super::demod::Frame;
+use super::sbs::SbsEncoder;
+use crate::decoders::feed::Feed;
+use crate::error::Result;
+
+/// The wire format a `FeedServer` speaks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FeedFormat {
+    /// SBS-1 BaseStation CSV lines, see `SbsEncoder`.
+    Sbs,
+    /// Beast binary records, see `Frame::to_beast`.
+    Beast,
+}
+
+impl FeedFormat {
+    /// Get the port dump1090 serves the format on, which tools connect to
+    /// by default: 30003 for SBS and 30005 for Beast.
+    pub fn default_port(self) -> u16 {
+        match self {
+            FeedFormat::Sbs => 30003,
+            FeedFormat::Beast => 30005,
+        }
+    }
+}
+
+/// TCP server feeding decoded traffic to dump1090-compatible tools.
+///
+/// Serves either SBS-1 BaseStation lines, read by Virtual Radar Server and
+/// similar, or Beast binary records, read by tar1090/readsb, FlightAware
+/// and other aggregator feeders and mlat clients. Bind one server per
+/// format and pass every frame to each.
+///
+/// The server never blocks the caller. Connections are accepted and queued
+/// bytes written whenever `send` or `flush` is called. Each client has its
+/// own queue: while a client is slower than the traffic, whole messages
+/// are dropped for that client alone once its queue is full, and a client
+/// that takes nothing for the stall timeout is disconnected.
+///
+/// ```no_run
+/// use radion::decoders::adsb::{FeedFormat, FeedServer, ModeSDemod, SAMPLE_RATE};
+/// use radion::Device;
+///
+/// let mut sbs = FeedServer::bind("0.0.0.0:30003", FeedFormat::Sbs)?;
+/// let mut beast = FeedServer::bind("0.0.0.0:30005", FeedFormat::Beast)?;
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(1_090_000_000)?;
+/// device.set_sample_rate(SAMPLE_RATE)?;
+/// device.set_tuner_gain_mode(false)?;
+/// device.reset_buffer()?;
+///
+/// let mut demod = ModeSDemod::new();
+/// loop {
+///     let frames = demod.process(&device.read_sync(262_144)?);
+///     sbs.send(&frames);
+///     beast.send(&frames);
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct FeedServer {
+    feed: Feed,
+    format: FeedFormat,
+    sbs: SbsEncoder,
+}
+
+impl FeedServer {
+    /// Start listening.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `"0.0.0.0:30005"`.
+    /// * `format` - The format to serve.
+    ///
+    /// # Returns
+    ///
+    /// A new `FeedServer` with a 64 KiB queue per client and a 10 second
+    /// stall timeout if successful, otherwise an `Error`.
+    pub fn bind(addr: impl ToSocketAddrs, format: FeedFormat) -> Result<Self> {
+        Ok(FeedServer {
+            feed: Feed::bind(addr)?,
+            format,
+            sbs: SbsEncoder::new(),
+        })
+    }
+
+    /// Set the size of each client's queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - How many bytes may wait for a client before messages
+    ///   are dropped for it.
+    ///
+    /// # Returns
+    ///
+    /// The `FeedServer` with the new queue size.
+    pub fn with_max_pending(mut self, bytes: usize) -> Self {
+        self.feed.max_pending = bytes;
+        self
+    }
+
+    /// Set how long a client may take nothing before it is disconnected.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The stall timeout.
+    ///
+    /// # Returns
+    ///
+    /// The `FeedServer` with the new timeout.
+    pub fn with_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.feed.stall_timeout = timeout;
+        self
+    }
+
+    /// Set the receiver position, used to resolve surface positions in SBS
+    /// output.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The receiver's position.
+    ///
+    /// # Returns
+    ///
+    /// The `FeedServer` with the position set.
+    pub fn with_reference(mut self, reference: Position) -> Self {
+        self.sbs = SbsEncoder::new().with_reference(reference);
+        self
+    }
+
+    /// Get the format served.
+    pub fn format(&self) -> FeedFormat {
+        self.format
+    }
+
+    /// Get the address the server listens on.
+    ///
+    /// # Returns
+    ///
+    /// The address, useful after binding to port 0, otherwise an `Error`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.feed.local_addr()
+    }
+
+    /// Get the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.feed.client_count()
+    }
+
+    /// Get the number of messages dropped for clients whose queue was full,
+    /// summed over all clients.
+    pub fn dropped(&self) -> u64 {
+        self.feed.dropped()
+    }
+
+    /// Send frames to every client.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames, in order. SBS output needs every frame the
+    ///   demodulator returns to resolve positions.
+    pub fn send(&mut self, frames: &[Frame]) {
+        for frame in frames {
+            match self.format {
+                FeedFormat::Sbs => {
+                    if let Some(line) = self.sbs.encode(frame) {
+                        self.feed.queue(line.as_bytes());
+                    }
+                }
+                FeedFormat::Beast => self.feed.queue(&frame.to_beast()),
+            }
+        }
+        self.feed.flush();
+    }
+
+    /// Accept new clients and write queued bytes without sending anything
+    /// new. Call it while no frames arrive to keep clients served.
+    pub fn flush(&mut self) {
+        self.feed.flush();
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::cpr::{Locator, Position};
+use super::demod::{Frame, SAMPLE_RATE};
+use super::message::{Message, SpeedKind};
+
+/// A point in an aircraft's position history.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrackPoint {
+    /// Where the aircraft was.
+    pub position: Position,
+    /// Its barometric altitude in feet, if known at the time.
+    pub altitude: Option<i32>,
+    /// When, in samples at 2 MS/s, see `Frame::sample`.
+    pub sample: u64,
+}
+
+/// Everything known about one aircraft.
+///
+/// Altitudes are in feet, speeds in knots, angles in degrees clockwise
+/// from north and vertical rates in feet per minute, as in `Message`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aircraft {
+    /// The aircraft address.
+    pub icao: u32,
+    /// The callsign, once identified.
+    pub callsign: Option<String>,
+    /// The emitter category, see `Message::Identification`.
+    pub category: Option<u8>,
+    /// The squawk, once a surveillance identity reply has been heard.
+    pub squawk: Option<u16>,
+    /// The barometric altitude.
+    pub altitude: Option<i32>,
+    /// The GNSS height, from position messages that carry it instead.
+    pub gnss_altitude: Option<i32>,
+    /// The last resolved position.
+    pub position: Option<Position>,
+    /// Recent positions, oldest first, the last being `position`.
+    pub history: VecDeque<TrackPoint>,
+    /// The speed.
+    pub speed: Option<f32>,
+    /// What `speed` and `heading` measure.
+    pub speed_kind: SpeedKind,
+    /// The track or heading.
+    pub heading: Option<f32>,
+    /// The vertical rate.
+    pub vertical_rate: Option<i32>,
+    /// Whether the aircraft is on the ground, if it has said.
+    pub on_ground: Option<bool>,
+    /// The signal level of the last message, in dB relative to full scale.
+    pub signal_db: f32,
+    /// The number of messages received.
+    pub messages: u64,
+    /// When the first message arrived, in samples.
+    pub first_seen: u64,
+    /// When the last message arrived, in samples.
+    pub last_seen: u64,
+}
+
+impl Aircraft {
+    fn new(icao: u32, sample: u64) -> Self {
+        Aircraft {
+            icao,
+            callsign: None,
+            category: None,
+            squawk: None,
+            altitude: None,
+            gnss_altitude: None,
+            position: None,
+            history: VecDeque::new(),
+            speed: None,
+            speed_kind: SpeedKind::Ground,
+            heading: None,
+            vertical_rate: None,
+            on_ground: None,
+            signal_db: 0.0,
+            messages: 0,
+            first_seen: sample,
+            last_seen: sample,
+        }
+    }
+}
+
+/// A change in a `Tracker`'s table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrackerEvent {
+    /// An aircraft was heard for the first time, or again after expiring.
+    Appeared(u32),
+    /// Something known about an aircraft changed.
+    Changed {
+        /// The aircraft address.
+        icao: u32,
+        /// What changed.
+        change: Change,
+    },
+    /// An aircraft was not heard for the expiry time and was removed.
+    Expired(u32),
+}
+
+/// What changed about an aircraft, with the new value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// The callsign.
+    Callsign(String),
+    /// The squawk.
+    Squawk(u16),
+    /// The barometric altitude.
+    Altitude(i32),
+    /// The position.
+    Position(Position),
+    /// The speed, track or heading, or vertical rate.
+    Velocity {
+        /// The speed.
+        speed: Option<f32>,
+        /// The track or heading.
+        heading: Option<f32>,
+        /// The vertical rate.
+        vertical_rate: Option<i32>,
+    },
+    /// Whether the aircraft is on the ground.
+    OnGround(bool),
+}
+
+/// Aggregates decoded messages into a table of aircraft.
+///
+/// Each frame updates its aircraft's entry: callsign, squawk, altitude,
+/// velocity and a position resolved from the CPR messages, with a capped
+/// history of positions. Entries expire when an aircraft is not heard for
+/// a while. `update` returns what changed, for pushing to a map, and
+/// `snapshot` the whole table, for drawing one.
+///
+/// Time is counted in samples, as in `Frame::sample`, so recordings are
+/// tracked as they were received however fast they are processed.
+///
+/// ```no_run
+/// use radion::decoders::adsb::{Change, ModeSDemod, Tracker, TrackerEvent, SAMPLE_RATE};
+/// use radion::Device;
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(1_090_000_000)?;
+/// device.set_sample_rate(SAMPLE_RATE)?;
+/// device.set_tuner_gain_mode(false)?;
+/// device.reset_buffer()?;
+///
+/// let mut demod = ModeSDemod::new();
+/// let mut tracker = Tracker::new();
+/// loop {
+///     let frames = demod.process(&device.read_sync(262_144)?);
+///     for event in tracker.update(&frames) {
+///         if let TrackerEvent::Changed { icao, change: Change::Position(p) } = event {
+///             println!("{:06x} {:.5} {:.5}", icao, p.lat, p.lon);
+///         }
+///     }
+///     tracker.expire(demod.samples());
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct Tracker {
+    aircraft: HashMap<u32, (Aircraft, Locator)>,
+    reference: Option<Position>,
+    expire_after: u64,
+    history_len: usize,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Tracker::new()
+    }
+}
+
+impl Tracker {
+    /// Create an empty tracker that expires aircraft after 60 seconds and
+    /// keeps 100 positions of history.
+    pub fn new() -> Self {
+        Tracker {
+            aircraft: HashMap::new(),
+            reference: None,
+            expire_after: 60 * SAMPLE_RATE as u64,
+            history_len: 100,
+        }
+    }
+
+    /// Set the receiver position.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The receiver's position. Surface positions can only
+    ///   be resolved with it.
+    ///
+    /// # Returns
+    ///
+    /// The `Tracker` with the position set.
+    pub fn with_reference(mut self, reference: Position) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Set how long an aircraft is kept after its last message.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The expiry time.
+    ///
+    /// # Returns
+    ///
+    /// The `Tracker` with the new expiry time.
+    pub fn with_expiry(mut self, seconds: f64) -> Self {
+        assert!(seconds > 0.0, "expiry time must be positive");
+        self.expire_after = (seconds * SAMPLE_RATE as f64) as u64;
+        self
+    }
+
+    /// Set how many positions each aircraft's history keeps.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The history length, 0 to keep none.
+    ///
+    /// # Returns
+    ///
+    /// The `Tracker` with the new history length.
+    pub fn with_history(mut self, len: usize) -> Self {
+        self.history_len = len;
+        self
+    }
+
+    /// Apply frames to the table.
+    ///
+    /// Aircraft not heard for the expiry time before the last frame are
+    /// removed as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames, in order, as returned by
+    ///   `ModeSDemod::process`.
+    ///
+    /// # Returns
+    ///
+    /// The changes, in order.
+    pub fn update(&mut self, frames: &[Frame]) -> Vec<TrackerEvent> {
+        let mut events = Vec::new();
+        for frame in frames {
+            self.apply(frame, &mut events);
+        }
+        if let Some(last) = frames.last() {
+            events.extend(self.expire(last.sample));
+        }
+        events
+    }
+
+    /// Remove aircraft not heard for the expiry time.
+    ///
+    /// `update` does this too, but only when frames arrive; call this
+    /// regularly so aircraft also expire when all goes quiet.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time in samples, e.g. `ModeSDemod::samples`.
+    ///
+    /// # Returns
+    ///
+    /// An `Expired` event for each aircraft removed.
+    pub fn expire(&mut self, now: u64) -> Vec<TrackerEvent> {
+        let expire_after = self.expire_after;
+        let mut events = Vec::new();
+        self.aircraft.retain(|&icao, (a, _)| {
+            let keep = now.saturating_sub(a.last_seen) <= expire_after;
+            if !keep {
+                events.push(TrackerEvent::Expired(icao));
+            }
+            keep
+        });
+        events
+    }
+
+    /// Get one aircraft.
+    ///
+    /// # Arguments
+    ///
+    /// * `icao` - The aircraft address.
+    ///
+    /// # Returns
+    ///
+    /// The aircraft, or `None` if it is not in the table.
+    pub fn get(&self, icao: u32) -> Option<&Aircraft> {
+        self.aircraft.get(&icao).map(|(a, _)| a)
+    }
+
+    /// Iterate over the aircraft, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Aircraft> {
+        self.aircraft.values().map(|(a, _)| a)
+    }
+
+    /// Copy the table, for handing to another thread.
+    ///
+    /// # Returns
+    ///
+    /// Every aircraft, ordered by address.
+    pub fn snapshot(&self) -> Vec<Aircraft> {
+        let mut all: Vec<Aircraft> = self.iter().cloned().collect();
+        all.sort_by_key(|a| a.icao);
+        all
+    }
+
+    /// Get the number of aircraft in the table.
+    pub fn len(&self) -> usize {
+        self.aircraft.len()
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.aircraft.is_empty()
+    }
+
+    /// Empty the table.
+    pub fn clear(&mut self) {
+        self.aircraft.clear();
+    }
+
+    fn apply(&mut self, frame: &Frame, events: &mut Vec<TrackerEvent>) {
+        let message = frame.decode();
+        let icao = message.icao();
+        let (a, locator) = self.aircraft.entry(icao).or_insert_with(|| {
+            events.push(TrackerEvent::Appeared(icao));
+            (Aircraft::new(icao, frame.sample), Locator::default())
+        });
+        a.messages += 1;
+        a.last_seen = frame.sample;
+        a.signal_db = frame.signal_db();
+
+        let mut changes = Vec::new();
+        match message {
+            Message::Identification {
+                category, callsign, ..
+            } => {
+                a.category = Some(category);
+                if a.callsign.as_deref() != Some(callsign.as_str()) {
+                    a.callsign = Some(callsign.clone());
+                    changes.push(Change::Callsign(callsign));
+                }
+            }
+            Message::SurveillanceIdentity { squawk, .. } => {
+                if a.squawk != Some(squawk) {
+                    a.squawk = Some(squawk);
+                    changes.push(Change::Squawk(squawk));
+                }
+            }
+            Message::SurveillanceAltitude { altitude, .. } => {
+                set_altitude(a, altitude, &mut changes);
+            }
+            Message::AirbornePosition {
+                altitude,
+                gnss,
+                cpr,
+                ..
+            } => {
+                if gnss {
+                    a.gnss_altitude = altitude.or(a.gnss_altitude);
+                } else {
+                    set_altitude(a, altitude, &mut changes);
+                }
+                set_on_ground(a, false, &mut changes);
+                let position = locator.locate(cpr, frame.sample, self.reference);
+                set_position(a, position, frame.sample, self.history_len, &mut changes);
+            }
+            Message::SurfacePosition {
+                speed, track, cpr, ..
+            } => {
+                set_on_ground(a, true, &mut changes);
+                if speed.is_some() || track.is_some() {
+                    let velocity = (speed.or(a.speed), SpeedKind::Ground, track.or(a.heading));
+                    set_velocity(a, velocity, Some(0), &mut changes);
+                }
+                let position = locator.locate(cpr, frame.sample, self.reference);
+                set_position(a, position, frame.sample, self.history_len, &mut changes);
+            }
+            Message::AirborneVelocity {
+                speed,
+                speed_kind,
+                heading,
+                vertical_rate,
+                ..
+            } => {
+                set_on_ground(a, false, &mut changes);
+                set_velocity(a, (speed, speed_kind, heading), vertical_rate, &mut changes);
+            }
+            Message::AllCall { .. } | Message::Other { .. } => {}
+        }
+        events.extend(
+            changes
+                .into_iter()
+                .map(|change| TrackerEvent::Changed { icao, change }),
+        );
+    }
+}
+
+fn set_altitude(a: &mut Aircraft, altitude: Option<i32>, changes: &mut Vec<Change>) {
+    if let Some(altitude) = altitude {
+        if a.altitude != Some(altitude) {
+            a.altitude = Some(altitude);
+            changes.push(Change::Altitude(altitude));
+        }
+    }
+}
+
+fn set_on_ground(a: &mut Aircraft, on_ground: bool, changes: &mut Vec<Change>) {
+    if a.on_ground != Some(on_ground) {
+        a.on_ground = Some(on_ground);
+        changes.push(Change::OnGround(on_ground));
+    }
+}
+
+fn set_velocity(
+    a: &mut Aircraft,
+    (speed, speed_kind, heading): (Option<f32>, SpeedKind, Option<f32>),
+    vertical_rate: Option<i32>,
+    changes: &mut Vec<Change>,
+) {
+    let old = (a.speed, a.speed_kind, a.heading, a.vertical_rate);
+    a.speed = speed;
+    a.speed_kind = speed_kind;
+    a.heading = heading;
+    a.vertical_rate = vertical_rate;
+    if old != (a.speed, a.speed_kind, a.heading, a.vertical_rate) {
+        changes.push(Change::Velocity {
+            speed,
+            heading,
+            vertical_rate,
+        });
+    }
+}
+
+fn set_position(
+    a: &mut Aircraft,
+    position: Option<Position>,
+    sample: u64,
+    history_len: usize,
+    changes: &mut Vec<Change>,
+) {
+    let Some(position) = position else {
+        return;
+    };
+    if a.position == Some(position) {
+        return;
+    }
+    a.position = Some(position);
+    if history_len > 0 {
+        while a.history.len() >= history_len {
+            a.history.pop_front();
+        }
+        a.history.push_back(TrackPoint {
+            position,
+            altitude: a.altitude,
+            sample,
+        });
+    }
+    changes.push(Change::Position(position));
+}
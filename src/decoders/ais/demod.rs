@@ -0,0 +1,419 @@
+use std::fmt;
+
+use super::message::{bits, Message};
+use crate::decoders::hdlc::Deframer;
+use crate::decoders::quality::{hard_bits, soft_bit};
+use crate::decoders::{Failure, FailureDump, Quality};
+use crate::demod::FmDemod;
+use crate::dsp::fir::{low_pass, Window};
+use crate::dsp::{Complex, FreqXlator};
+use crate::error::Result;
+
+/// The frequency to tune to for both channels, halfway between them.
+pub const CENTER_FREQ: u32 = 162_000_000;
+
+/// The sample rate `AisReceiver::new` is meant for, which holds both
+/// channels with room for the filters.
+pub const SAMPLE_RATE: u32 = 250_000;
+
+/// The AIS bit rate.
+const BAUD: f64 = 9600.0;
+
+/// The GMSK peak deviation, a modulation index of 0.5.
+const DEVIATION: f64 = 2400.0;
+
+/// One of the two AIS channels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Channel 87B, 161.975 MHz.
+    A,
+    /// Channel 88B, 162.025 MHz.
+    B,
+}
+
+impl Channel {
+    /// Get the channel frequency in Hz.
+    pub fn frequency(self) -> u32 {
+        match self {
+            Channel::A => 161_975_000,
+            Channel::B => 162_025_000,
+        }
+    }
+}
+
+/// Formats the channel as its NMEA letter, `A` or `B`.
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::A => write!(f, "A"),
+            Channel::B => write!(f, "B"),
+        }
+    }
+}
+
+/// An AIS message that passed its checksum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packet {
+    /// The channel it was received on.
+    pub channel: Channel,
+    data: Vec<u8>,
+    soft: Vec<i8>,
+}
+
+impl Packet {
+    /// Wrap a message received some other way.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel it was received on.
+    /// * `data` - The message bytes, most significant bit first.
+    ///
+    /// # Returns
+    ///
+    /// A new `Packet`.
+    pub fn new(channel: Channel, data: Vec<u8>) -> Self {
+        let soft = hard_bits(&data);
+        Packet {
+            channel,
+            data,
+            soft,
+        }
+    }
+
+    /// Get the message bytes, without the checksum.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the soft decision of each message bit.
+    ///
+    /// # Returns
+    ///
+    /// One value per bit of `data`, most significant bit first: positive
+    /// for a one, negative for a zero, with 127 for a full-strength
+    /// decision. Messages wrapped with `new` have full-strength decisions.
+    pub fn soft_bits(&self) -> &[i8] {
+        &self.soft
+    }
+
+    /// Get how confidently the message was received.
+    pub fn quality(&self) -> Quality {
+        Quality::from_soft(&self.soft, true, 0)
+    }
+
+    /// Get the message type, 1 to 27.
+    pub fn msg_type(&self) -> u8 {
+        bits(&self.data, 0, 6) as u8
+    }
+
+    /// Get the MMSI of the station that sent the message.
+    pub fn mmsi(&self) -> u32 {
+        bits(&self.data, 8, 30)
+    }
+
+    /// Decode the message's content.
+    pub fn decode(&self) -> Message {
+        Message::decode(&self.data)
+    }
+}
+
+/// How many symbol phases are sliced side by side.
+const PHASES: usize = 4;
+
+/// How close in symbols two copies of a frame from different phases end.
+const DUPLICATE_WINDOW: u64 = 8;
+
+/// The shortest frame, in bytes with the checksum: a 56-bit message, the
+/// shortest AIS has, and two checksum bytes.
+const MIN_BYTES: usize = 9;
+
+/// The longest frame, in bytes with the checksum: five slots' worth.
+const MAX_BYTES: usize = 128;
+
+/// Slices one symbol phase and deframes the bits.
+struct PhaseSlicer {
+    /// The carrier offset, in units of the deviation.
+    offset: f32,
+    /// The previous symbol and its level, for NRZI decoding.
+    prev: f32,
+    level: bool,
+    deframer: Deframer,
+}
+
+impl PhaseSlicer {
+    fn new() -> Self {
+        PhaseSlicer {
+            offset: 0.0,
+            prev: 0.0,
+            level: false,
+            deframer: Deframer::new(MIN_BYTES, MAX_BYTES),
+        }
+    }
+
+    fn push(&mut self, symbol: f32) -> Option<(Vec<u8>, Vec<i8>)> {
+        let symbol = symbol.clamp(-2.0, 2.0) - self.offset;
+        let level = symbol > 0.0;
+        // The midpoint of a transition is the remaining carrier offset
+        // whatever the run lengths around it.
+        if level != self.level {
+            self.offset += (symbol + self.prev) / 16.0;
+        }
+        // NRZI: a change is a zero, no change a one, as sure as the weaker
+        // of the two symbols.
+        let bit = level == self.level;
+        let margin = symbol.abs().min(self.prev.abs());
+        self.prev = symbol;
+        self.level = level;
+        self.deframer
+            .push(bit, soft_bit(if bit { margin } else { -margin }))
+    }
+}
+
+/// Demodulator for one AIS channel.
+///
+/// Selects the channel from wider IQ, recovers the 9600 bit/s GMSK with an
+/// FM discriminator, undoes the NRZI coding and hands the bits to an HDLC
+/// deframer, which finds the flags, removes stuffed bits and checks each
+/// frame's CRC. A packet is too short for a timing loop to lock reliably
+/// within its training sequence, so the discriminator is sliced at four
+/// symbol phases side by side and each frame is taken from whichever
+/// phases deframe it. Carrier offsets of a kHz or so are followed, but a
+/// dongle far off frequency should be corrected with
+/// `Device::set_freq_correction` first.
+pub struct AisDemod {
+    channel: Channel,
+    xlator: FreqXlator,
+    fm: FmDemod,
+    samples_per_symbol: f64,
+    /// Discriminator output not yet sliced at every phase.
+    freq: Vec<f32>,
+    /// Position of the next symbol's first phase in `freq`.
+    clock: f64,
+    slicers: Vec<PhaseSlicer>,
+    /// Symbols sliced so far.
+    symbols: u64,
+    /// Frames recently returned and the symbol they ended on.
+    recent: Vec<(u64, Vec<u8>)>,
+    /// Where frames failing their checksum go.
+    failures: Option<FailureDump>,
+}
+
+impl AisDemod {
+    /// Create a demodulator for one channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel, only used to label packets.
+    /// * `sample_rate` - The input sample rate in Hz, at least 48 kHz.
+    /// * `offset` - The channel frequency relative to the tuned frequency
+    ///   in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AisDemod`.
+    pub fn new(channel: Channel, sample_rate: f64, offset: f64) -> Self {
+        assert!(
+            sample_rate >= 48_000.0,
+            "AIS needs a sample rate of at least 48 kHz"
+        );
+        let factor = (sample_rate / 48_000.0).floor() as usize;
+        // Pass the signal and stop by 20 kHz out.
+        let taps = (5.5 * sample_rate / 15_000.0) as usize | 1;
+        let filter = low_pass(7_000.0 / sample_rate, taps, Window::Blackman);
+        let xlator = FreqXlator::new(sample_rate, offset, factor, &filter);
+        let rate = xlator.output_rate();
+        AisDemod {
+            channel,
+            xlator,
+            fm: FmDemod::new(rate, DEVIATION),
+            samples_per_symbol: rate / BAUD,
+            freq: Vec::new(),
+            clock: 0.0,
+            slicers: (0..PHASES).map(|_| PhaseSlicer::new()).collect(),
+            symbols: 0,
+            recent: Vec::new(),
+            failures: None,
+        }
+    }
+
+    /// Write the frames that fail their checksum to a debug directory.
+    ///
+    /// Each symbol phase deframes on its own, so a damaged frame may be
+    /// written once for each phase that found it.
+    ///
+    /// # Arguments
+    ///
+    /// * `dump` - Where the failures go.
+    ///
+    /// # Returns
+    ///
+    /// The `AisDemod` writing failures to `dump`.
+    pub fn with_failure_dump(mut self, dump: FailureDump) -> Self {
+        for slicer in &mut self.slicers {
+            slicer.deframer.keep_rejected();
+        }
+        self.failures = Some(dump);
+        self
+    }
+
+    /// Get where failures are written, if anywhere.
+    pub fn failure_dump(&self) -> Option<&FailureDump> {
+        self.failures.as_ref()
+    }
+
+    /// Get the channel.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Demodulate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// Every message completed in the chunk, in order.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<Packet> {
+        let baseband = self.xlator.process(iq);
+        self.freq.extend(self.fm.process(&baseband));
+
+        let sps = self.samples_per_symbol;
+        let step = sps / PHASES as f64;
+        let mut packets = Vec::new();
+        while self.clock + sps + 1.0 < self.freq.len() as f64 {
+            for (p, slicer) in self.slicers.iter_mut().enumerate() {
+                let t = self.clock + p as f64 * step;
+                let i = t as usize;
+                let mu = (t - i as f64) as f32;
+                let symbol = self.freq[i] + (self.freq[i + 1] - self.freq[i]) * mu;
+                let frame = slicer.push(symbol);
+                if let (Some(dump), Some((bytes, soft))) =
+                    (&mut self.failures, slicer.deframer.take_rejected())
+                {
+                    dump.write(&Failure {
+                        decoder: "ais".into(),
+                        reason: "crc".into(),
+                        position: self.symbols,
+                        context: vec![
+                            ("channel".into(), self.channel.to_string()),
+                            ("phase".into(), p.to_string()),
+                        ],
+                        bytes,
+                        soft,
+                    });
+                }
+                let Some((data, soft)) = frame else {
+                    continue;
+                };
+                let symbols = self.symbols;
+                self.recent
+                    .retain(|(at, _)| symbols - at <= DUPLICATE_WINDOW);
+                if self.recent.iter().all(|(_, d)| *d != data) {
+                    self.recent.push((symbols, data.clone()));
+                    packets.push(Packet {
+                        channel: self.channel,
+                        data,
+                        soft,
+                    });
+                }
+            }
+            self.clock += sps;
+            self.symbols += 1;
+        }
+        let used = self.clock as usize;
+        self.freq.drain(..used);
+        self.clock -= used as f64;
+        packets
+    }
+
+    /// Clear the filter, timing and deframer state.
+    pub fn reset(&mut self) {
+        self.xlator.reset();
+        self.fm.reset();
+        self.freq.clear();
+        self.clock = 0.0;
+        let keep_rejected = self.failures.is_some();
+        for slicer in &mut self.slicers {
+            *slicer = PhaseSlicer::new();
+            if keep_rejected {
+                slicer.deframer.keep_rejected();
+            }
+        }
+        self.symbols = 0;
+        self.recent.clear();
+    }
+}
+
+/// Receiver for both AIS channels from one capture.
+///
+/// Tune to `CENTER_FREQ`, where the channels sit 25 kHz either side, at
+/// `SAMPLE_RATE` or any rate wide enough to hold them.
+pub struct AisReceiver {
+    a: AisDemod,
+    b: AisDemod,
+}
+
+impl AisReceiver {
+    /// Create a receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz, at least 100 kHz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AisReceiver`.
+    pub fn new(sample_rate: f64) -> Self {
+        assert!(
+            sample_rate >= 100_000.0,
+            "both AIS channels need a sample rate of at least 100 kHz"
+        );
+        let offset = |c: Channel| c.frequency() as f64 - CENTER_FREQ as f64;
+        AisReceiver {
+            a: AisDemod::new(Channel::A, sample_rate, offset(Channel::A)),
+            b: AisDemod::new(Channel::B, sample_rate, offset(Channel::B)),
+        }
+    }
+
+    /// Write the frames that fail their checksum to a debug directory,
+    /// with a subdirectory for each channel named by its letter.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory for AIS failures.
+    ///
+    /// # Returns
+    ///
+    /// The `AisReceiver` writing failures if the directories could be
+    /// created, otherwise an `Error`.
+    pub fn with_failure_dump(self, dir: impl AsRef<std::path::Path>) -> Result<Self> {
+        let dump = |c: Channel| FailureDump::new(dir.as_ref().join(c.to_string()));
+        Ok(AisReceiver {
+            a: self.a.with_failure_dump(dump(Channel::A)?),
+            b: self.b.with_failure_dump(dump(Channel::B)?),
+        })
+    }
+
+    /// Demodulate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, tuned to `CENTER_FREQ`.
+    ///
+    /// # Returns
+    ///
+    /// Every message completed in the chunk, channel A's first.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<Packet> {
+        let mut packets = self.a.process(iq);
+        packets.extend(self.b.process(iq));
+        packets
+    }
+
+    /// Clear both channels' state.
+    pub fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+}
@@ -0,0 +1,357 @@
+/// A ship's dimensions from its position reference point, in metres.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Dimensions {
+    /// Distance to the bow.
+    pub to_bow: u16,
+    /// Distance to the stern.
+    pub to_stern: u16,
+    /// Distance to port.
+    pub to_port: u8,
+    /// Distance to starboard.
+    pub to_starboard: u8,
+}
+
+impl Dimensions {
+    /// Get the length overall in metres.
+    pub fn length(&self) -> u16 {
+        self.to_bow + self.to_stern
+    }
+
+    /// Get the beam in metres.
+    pub fn beam(&self) -> u16 {
+        self.to_port as u16 + self.to_starboard as u16
+    }
+}
+
+/// A position report from a class A (types 1 to 3) or class B (types 18
+/// and 19) transponder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionReport {
+    /// The message type.
+    pub msg_type: u8,
+    /// The vessel's MMSI.
+    pub mmsi: u32,
+    /// The navigational status, 0 for under way using engine, 1 at anchor,
+    /// 5 moored and so on; `None` if not defined or from class B.
+    pub status: Option<u8>,
+    /// The rate of turn in degrees per minute, positive to starboard. ±720
+    /// means turning faster than 5° per 30 seconds with no rate indicator.
+    pub turn: Option<f32>,
+    /// The speed over ground in knots.
+    pub speed: Option<f32>,
+    /// Whether the position is accurate to better than 10 m.
+    pub accurate: bool,
+    /// The latitude in degrees, positive north.
+    pub lat: Option<f64>,
+    /// The longitude in degrees, positive east.
+    pub lon: Option<f64>,
+    /// The course over ground in degrees.
+    pub course: Option<f32>,
+    /// The true heading in degrees.
+    pub heading: Option<u16>,
+    /// The UTC second the report was made, or 60 and up if not available.
+    pub second: u8,
+}
+
+/// A base station report (type 4) or UTC response (type 11).
+#[derive(Clone, Debug, PartialEq)]
+pub struct BaseStationReport {
+    /// The station's MMSI.
+    pub mmsi: u32,
+    /// The UTC year, month, day, hour, minute and second, or `None` if
+    /// not available.
+    pub time: Option<(u16, u8, u8, u8, u8, u8)>,
+    /// Whether the position is accurate to better than 10 m.
+    pub accurate: bool,
+    /// The latitude in degrees, positive north.
+    pub lat: Option<f64>,
+    /// The longitude in degrees, positive east.
+    pub lon: Option<f64>,
+}
+
+/// Static and voyage related data from a class A transponder (type 5).
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaticVoyage {
+    /// The vessel's MMSI.
+    pub mmsi: u32,
+    /// The IMO number.
+    pub imo: Option<u32>,
+    /// The radio callsign.
+    pub callsign: String,
+    /// The vessel name.
+    pub name: String,
+    /// The ship and cargo type, e.g. 70 for cargo or 60 for passenger.
+    pub ship_type: u8,
+    /// The dimensions.
+    pub dimensions: Dimensions,
+    /// The estimated time of arrival as month, day, hour and minute in
+    /// UTC, or `None` if not available.
+    pub eta: Option<(u8, u8, u8, u8)>,
+    /// The draught in metres.
+    pub draught: f32,
+    /// The destination.
+    pub destination: String,
+}
+
+/// Static data from a class B transponder (type 24), which comes in two
+/// parts: part A with the name and part B with the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaticData {
+    /// The vessel's MMSI.
+    pub mmsi: u32,
+    /// The vessel name, from part A.
+    pub name: Option<String>,
+    /// The ship and cargo type, from part B.
+    pub ship_type: Option<u8>,
+    /// The radio callsign, from part B.
+    pub callsign: Option<String>,
+    /// The dimensions, from part B.
+    pub dimensions: Option<Dimensions>,
+}
+
+/// An aid-to-navigation report (type 21), from a buoy, light or virtual
+/// mark.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AidToNavigation {
+    /// The aid's MMSI.
+    pub mmsi: u32,
+    /// The kind of aid, e.g. 1 for a reference point or 20 to 28 for
+    /// cardinal and lateral marks.
+    pub aid_type: u8,
+    /// The aid's name.
+    pub name: String,
+    /// Whether the position is accurate to better than 10 m.
+    pub accurate: bool,
+    /// The latitude in degrees, positive north.
+    pub lat: Option<f64>,
+    /// The longitude in degrees, positive east.
+    pub lon: Option<f64>,
+    /// Whether a floating aid is off its charted position.
+    pub off_position: bool,
+    /// Whether the aid only exists as this broadcast.
+    pub virtual_aid: bool,
+}
+
+/// A decoded AIS message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// Types 1, 2, 3, 18 and 19. The static part of type 19 is not kept.
+    Position(PositionReport),
+    /// Types 4 and 11.
+    BaseStation(BaseStationReport),
+    /// Type 5.
+    StaticVoyage(StaticVoyage),
+    /// Type 24.
+    StaticData(StaticData),
+    /// Type 21.
+    AidToNavigation(AidToNavigation),
+    /// Any other type, or a message too short for its type.
+    Other {
+        /// The message type.
+        msg_type: u8,
+        /// The sender's MMSI.
+        mmsi: u32,
+    },
+}
+
+impl Message {
+    /// Get the MMSI of the station that sent the message.
+    pub fn mmsi(&self) -> u32 {
+        match self {
+            Message::Position(m) => m.mmsi,
+            Message::BaseStation(m) => m.mmsi,
+            Message::StaticVoyage(m) => m.mmsi,
+            Message::StaticData(m) => m.mmsi,
+            Message::AidToNavigation(m) => m.mmsi,
+            Message::Other { mmsi, .. } => *mmsi,
+        }
+    }
+
+    /// Decode a checked message.
+    pub(crate) fn decode(data: &[u8]) -> Message {
+        let msg_type = bits(data, 0, 6) as u8;
+        let mmsi = bits(data, 8, 30);
+        let len = data.len() * 8;
+        let needs = match msg_type {
+            1..=4 | 11 | 18 => 168,
+            5 => 420,
+            19 => 312,
+            21 => 272,
+            24 => 160,
+            _ => 0,
+        };
+        if needs == 0 || len < needs {
+            return Message::Other { msg_type, mmsi };
+        }
+        match msg_type {
+            1..=3 => Message::Position(PositionReport {
+                msg_type,
+                mmsi,
+                status: Some(bits(data, 38, 4) as u8).filter(|&s| s != 15),
+                turn: turn(signed(data, 42, 8)),
+                speed: speed(bits(data, 50, 10)),
+                accurate: bits(data, 60, 1) == 1,
+                lon: lon(signed(data, 61, 28)),
+                lat: lat(signed(data, 89, 27)),
+                course: course(bits(data, 116, 12)),
+                heading: heading(bits(data, 128, 9)),
+                second: bits(data, 137, 6) as u8,
+            }),
+            18 | 19 => Message::Position(PositionReport {
+                msg_type,
+                mmsi,
+                status: None,
+                turn: None,
+                speed: speed(bits(data, 46, 10)),
+                accurate: bits(data, 56, 1) == 1,
+                lon: lon(signed(data, 57, 28)),
+                lat: lat(signed(data, 85, 27)),
+                course: course(bits(data, 112, 12)),
+                heading: heading(bits(data, 124, 9)),
+                second: bits(data, 133, 6) as u8,
+            }),
+            4 | 11 => {
+                let field = |start, len| bits(data, start, len) as u8;
+                let year = bits(data, 38, 14) as u16;
+                let time = (year != 0).then(|| {
+                    (
+                        year,
+                        field(52, 4),
+                        field(56, 5),
+                        field(61, 5),
+                        field(66, 6),
+                        field(72, 6),
+                    )
+                });
+                Message::BaseStation(BaseStationReport {
+                    mmsi,
+                    time,
+                    accurate: bits(data, 78, 1) == 1,
+                    lon: lon(signed(data, 79, 28)),
+                    lat: lat(signed(data, 107, 27)),
+                })
+            }
+            5 => {
+                let imo = bits(data, 40, 30);
+                let eta = (
+                    bits(data, 274, 4) as u8,
+                    bits(data, 278, 5) as u8,
+                    bits(data, 283, 5) as u8,
+                    bits(data, 288, 6) as u8,
+                );
+                Message::StaticVoyage(StaticVoyage {
+                    mmsi,
+                    imo: (imo != 0).then_some(imo),
+                    callsign: text(data, 70, 7),
+                    name: text(data, 112, 20),
+                    ship_type: bits(data, 232, 8) as u8,
+                    dimensions: dimensions(data, 240),
+                    eta: (eta.0 != 0 && eta.1 != 0).then_some(eta),
+                    draught: bits(data, 294, 8) as f32 / 10.0,
+                    destination: text(data, 302, 20),
+                })
+            }
+            21 => Message::AidToNavigation(AidToNavigation {
+                mmsi,
+                aid_type: bits(data, 38, 5) as u8,
+                name: text(data, 43, 20),
+                accurate: bits(data, 163, 1) == 1,
+                lon: lon(signed(data, 164, 28)),
+                lat: lat(signed(data, 192, 27)),
+                off_position: bits(data, 259, 1) == 1,
+                virtual_aid: bits(data, 269, 1) == 1,
+            }),
+            24 => {
+                let mut report = StaticData {
+                    mmsi,
+                    name: None,
+                    ship_type: None,
+                    callsign: None,
+                    dimensions: None,
+                };
+                match bits(data, 38, 2) {
+                    0 => report.name = Some(text(data, 40, 20)),
+                    1 if len >= 162 => {
+                        report.ship_type = Some(bits(data, 40, 8) as u8);
+                        report.callsign = Some(text(data, 90, 7));
+                        report.dimensions = Some(dimensions(data, 132));
+                    }
+                    _ => return Message::Other { msg_type, mmsi },
+                }
+                Message::StaticData(report)
+            }
+            _ => unreachable!("lengths are only listed for decoded types"),
+        }
+    }
+}
+
+/// Read `len` bits starting `start` bits into `data`, most significant
+/// first. Bits past the end read as zero.
+pub(crate) fn bits(data: &[u8], start: usize, len: usize) -> u32 {
+    (start..start + len).fold(0, |acc, i| {
+        let bit = data.get(i / 8).map_or(0, |b| (b >> (7 - i % 8)) & 1);
+        (acc << 1) | bit as u32
+    })
+}
+
+/// Read a two's complement field.
+fn signed(data: &[u8], start: usize, len: usize) -> i32 {
+    let raw = bits(data, start, len) as i32;
+    (raw << (32 - len)) >> (32 - len)
+}
+
+/// Read six-bit text, dropping the `@` padding and trailing spaces.
+fn text(data: &[u8], start: usize, chars: usize) -> String {
+    let text: String = (0..chars)
+        .map(|i| {
+            let c = bits(data, start + 6 * i, 6) as u8;
+            (if c < 32 { c + 64 } else { c }) as char
+        })
+        .take_while(|&c| c != '@')
+        .collect();
+    text.trim_end().to_string()
+}
+
+fn dimensions(data: &[u8], start: usize) -> Dimensions {
+    Dimensions {
+        to_bow: bits(data, start, 9) as u16,
+        to_stern: bits(data, start + 9, 9) as u16,
+        to_port: bits(data, start + 18, 6) as u8,
+        to_starboard: bits(data, start + 24, 6) as u8,
+    }
+}
+
+/// Positions are in 1/10000 minute, with 181° and 91° meaning not
+/// available.
+fn lon(raw: i32) -> Option<f64> {
+    let lon = raw as f64 / 600_000.0;
+    (lon.abs() <= 180.0).then_some(lon)
+}
+
+fn lat(raw: i32) -> Option<f64> {
+    let lat = raw as f64 / 600_000.0;
+    (lat.abs() <= 90.0).then_some(lat)
+}
+
+fn speed(raw: u32) -> Option<f32> {
+    (raw != 1023).then(|| raw as f32 / 10.0)
+}
+
+fn course(raw: u32) -> Option<f32> {
+    (raw < 3600).then(|| raw as f32 / 10.0)
+}
+
+fn heading(raw: u32) -> Option<u16> {
+    (raw < 360).then_some(raw as u16)
+}
+
+/// Decode the rate of turn, sent as 4.733 times the square root of the
+/// rate in degrees per minute.
+fn turn(raw: i32) -> Option<f32> {
+    match raw {
+        -128 => None,
+        127 => Some(720.0),
+        -127 => Some(-720.0),
+        _ => Some((raw as f32 / 4.733).powi(2).copysign(raw as f32)),
+    }
+}
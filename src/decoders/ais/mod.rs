@@ -0,0 +1,45 @@
+//! AIS reception on the two marine VHF channels.
+//!
+//! `AisReceiver` demodulates both channels from one capture tuned between
+//! them, returning each message that passes its checksum as a `Packet`.
+//! `Packet::decode` turns it into a typed `Message`, and `NmeaEncoder`
+//! into the AIVDM sentences other AIS software reads. `Packet::quality`
+//! grades the reception and `Packet::soft_bits` gives the soft decisions.
+//!
+//! ```no_run
+//! use radion::decoders::ais::{AisReceiver, NmeaEncoder, CENTER_FREQ, SAMPLE_RATE};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::Device;
+//!
+//! let device = Device::new(0)?;
+//! device.set_center_freq(CENTER_FREQ)?;
+//! device.set_sample_rate(SAMPLE_RATE)?;
+//! device.reset_buffer()?;
+//!
+//! let mut receiver = AisReceiver::new(SAMPLE_RATE as f64);
+//! let mut nmea = NmeaEncoder::new();
+//! loop {
+//!     let iq = to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized);
+//!     for packet in receiver.process(&iq) {
+//!         for sentence in nmea.encode(&packet) {
+//!             println!("{}", sentence);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod demod;
+mod message;
+mod nmea;
+
+pub use demod::{AisDemod, AisReceiver, Channel, Packet, CENTER_FREQ, SAMPLE_RATE};
+pub use message::{
+    AidToNavigation, BaseStationReport, Dimensions, Message, PositionReport, StaticData,
+    StaticVoyage,
+};
+pub use nmea::NmeaEncoder;
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
@@ -0,0 +1,169 @@
+use super::demod::Packet;
+
+/// Payload characters per sentence, keeping sentences within the 82
+/// characters NMEA 0183 allows.
+const MAX_CHARS: usize = 60;
+
+/// Encoder for AIVDM NMEA 0183 sentences, the format AIS receivers give
+/// to chart plotters, OpenCPN, gpsd and AIS aggregators.
+///
+/// Messages too long for one sentence are split over several sharing a
+/// sequential message ID, which the encoder cycles through 0 to 9.
+#[derive(Default)]
+pub struct NmeaEncoder {
+    next_id: u8,
+}
+
+impl NmeaEncoder {
+    /// Create an encoder.
+    pub fn new() -> Self {
+        NmeaEncoder { next_id: 0 }
+    }
+
+    /// Encode a packet.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet.
+    ///
+    /// # Returns
+    ///
+    /// The sentences, such as
+    /// `!AIVDM,1,1,,A,15RTgt0PAso;90TKcjM8h6g208CQ,0*4A`, without line
+    /// endings.
+    pub fn encode(&mut self, packet: &Packet) -> Vec<String> {
+        let (payload, fill) = armor(packet.data());
+        let chunks: Vec<&[u8]> = payload.as_bytes().chunks(MAX_CHARS).collect();
+        let total = chunks.len();
+        let id = if total > 1 {
+            let id = self.next_id;
+            self.next_id = (self.next_id + 1) % 10;
+            id.to_string()
+        } else {
+            String::new()
+        };
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let last = i + 1 == total;
+                let body = format!(
+                    "AIVDM,{},{},{},{},{},{}",
+                    total,
+                    i + 1,
+                    id,
+                    packet.channel,
+                    String::from_utf8_lossy(chunk),
+                    if last { fill } else { 0 },
+                );
+                format!("!{}*{:02X}", body, checksum(&body))
+            })
+            .collect()
+    }
+}
+
+/// Pack message bytes into the six-bit payload characters.
+///
+/// # Returns
+///
+/// The payload and the number of fill bits padding its last character.
+fn armor(data: &[u8]) -> (String, usize) {
+    let bits = data.len() * 8;
+    let chars = bits.div_ceil(6);
+    let payload = (0..chars)
+        .map(|i| {
+            let v = super::message::bits(data, 6 * i, 6) as u8;
+            (if v < 40 { v + 48 } else { v + 56 }) as char
+        })
+        .collect();
+    (payload, chars * 6 - bits)
+}
+
+/// The NMEA checksum: every byte between `!` and `*` XORed together.
+fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::demod::Channel;
+    use super::*;
+
+    /// From the gpsd AIVDM documentation: a type 1 report from MMSI
+    /// 371798000.
+    const SENTENCE: &str = "!AIVDM,1,1,,A,15RTgt0PAso;90TKcjM8h6g208CQ,0*4A";
+
+    /// Unpack payload characters, dropping `fill` bits from the end.
+    fn dearmor(payload: &str, fill: usize) -> Vec<u8> {
+        let mut bits = Vec::new();
+        for c in payload.bytes() {
+            let v = if c < 96 { c - 48 } else { c - 56 };
+            bits.extend((0..6).rev().map(|i| v >> i & 1));
+        }
+        bits.truncate(bits.len() - fill);
+        bits.chunks(8)
+            .map(|c| c.iter().fold(0, |acc, &b| acc << 1 | b))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_a_known_sentence() {
+        let data = dearmor("15RTgt0PAso;90TKcjM8h6g208CQ", 0);
+        let packet = Packet::new(Channel::A, data);
+        assert_eq!(packet.msg_type(), 1);
+        assert_eq!(packet.mmsi(), 371_798_000);
+        assert_eq!(NmeaEncoder::new().encode(&packet), [SENTENCE]);
+    }
+
+    #[test]
+    fn checksum_covers_the_body() {
+        let body = &SENTENCE[1..SENTENCE.len() - 3];
+        assert_eq!(checksum(body), 0x4a);
+    }
+
+    #[test]
+    fn armor_maps_every_six_bit_value() {
+        // 0 to 63 in six-bit fields, 48 bytes.
+        let data = dearmor(
+            "0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVW`abcdefghijklmnopqrstuvw",
+            0,
+        );
+        assert_eq!(data.len(), 48);
+        let (payload, fill) = armor(&data);
+        assert_eq!(
+            payload,
+            "0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVW`abcdefghijklmnopqrstuvw"
+        );
+        assert_eq!(fill, 0);
+    }
+
+    #[test]
+    fn fill_bits_pad_the_last_character() {
+        assert_eq!(armor(&[0xff]), ("wh".to_string(), 4));
+        assert_eq!(armor(&[0xff, 0xff]), ("wwt".to_string(), 2));
+        assert_eq!(armor(&[0xff; 3]), ("wwww".to_string(), 0));
+    }
+
+    #[test]
+    fn splits_long_messages() {
+        // 46 bytes, 62 characters with 4 fill bits.
+        let packet = Packet::new(Channel::B, vec![0x55; 46]);
+        let mut encoder = NmeaEncoder::new();
+        let sentences = encoder.encode(&packet);
+        assert_eq!(sentences.len(), 2);
+        assert!(sentences[0].starts_with("!AIVDM,2,1,0,B,"));
+        assert!(sentences[0].contains(",0*"));
+        assert!(sentences[1].starts_with("!AIVDM,2,2,0,B,"));
+        assert!(sentences[1].contains(",4*"));
+        assert!(sentences.iter().all(|s| s.len() <= 82));
+
+        let payload: String = sentences
+            .iter()
+            .map(|s| s.split(',').nth(5).unwrap())
+            .collect();
+        assert_eq!(dearmor(&payload, 4), packet.data());
+
+        // The next split message takes the next ID.
+        assert!(encoder.encode(&packet)[0].starts_with("!AIVDM,2,1,1,B,"));
+    }
+}
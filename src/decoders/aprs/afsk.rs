@@ -0,0 +1,393 @@
+use std::f32::consts::TAU;
+
+use super::ax25::Ax25Frame;
+use crate::decoders::hdlc::Deframer;
+use crate::decoders::quality::soft_bit;
+use crate::decoders::{Failure, FailureDump, Quality};
+use crate::demod::FmDemod;
+use crate::dsp::fir::{low_pass, Window};
+use crate::dsp::{Complex, FreqXlator};
+
+/// The North American APRS frequency, 144.39 MHz.
+pub const NA_FREQ: u32 = 144_390_000;
+
+/// The European APRS frequency, 144.8 MHz.
+pub const EU_FREQ: u32 = 144_800_000;
+
+/// The Bell 202 bit rate.
+const BAUD: f32 = 1200.0;
+
+/// The Bell 202 tones: mark is a one, space a zero.
+const MARK: f32 = 1200.0;
+const SPACE: f32 = 2200.0;
+
+/// The usual peak deviation of an APRS transmitter.
+const DEVIATION: f64 = 3000.0;
+
+/// The shortest frame, in bytes with the checksum: two addresses, the
+/// control and PID bytes and the checksum.
+const MIN_BYTES: usize = 18;
+
+/// The longest frame, in bytes with the checksum: ten addresses, the
+/// control and PID bytes, 256 bytes of information and the checksum.
+const MAX_BYTES: usize = 330;
+
+/// How far the bit clock moves towards each transition.
+const PLL_GAIN: f32 = 0.3;
+
+/// Measures one tone's strength over the last bit.
+struct Correlator {
+    step: f32,
+    phase: f32,
+    /// The last bit's worth of mixed samples and their running sum.
+    history: Vec<Complex<f32>>,
+    pos: usize,
+    sum: Complex<f32>,
+}
+
+impl Correlator {
+    fn new(freq: f32, sample_rate: f32, len: usize) -> Self {
+        Correlator {
+            step: TAU * freq / sample_rate,
+            phase: 0.0,
+            history: vec![Complex::new(0.0, 0.0); len],
+            pos: 0,
+            sum: Complex::new(0.0, 0.0),
+        }
+    }
+
+    fn push(&mut self, sample: f32) -> f32 {
+        let mixed = Complex::new(self.phase.cos(), -self.phase.sin()) * sample;
+        self.phase = (self.phase + self.step) % TAU;
+        self.sum += mixed - self.history[self.pos];
+        self.history[self.pos] = mixed;
+        self.pos = (self.pos + 1) % self.history.len();
+        self.sum.norm()
+    }
+}
+
+/// Normalises one tone's strength between its recent peak and valley, so
+/// that pre-emphasis and receiver audio response don't favour a tone.
+struct Agc {
+    attack: f32,
+    decay: f32,
+    peak: f32,
+    valley: f32,
+}
+
+impl Agc {
+    fn new(sample_rate: f32) -> Self {
+        Agc {
+            attack: 0.5,
+            // Forget over about a quarter of a second.
+            decay: 4.0 / sample_rate,
+            peak: 0.0,
+            valley: 0.0,
+        }
+    }
+
+    fn push(&mut self, x: f32) -> f32 {
+        let rate = |up: bool| if up { self.attack } else { self.decay };
+        self.peak += (x - self.peak) * rate(x > self.peak);
+        self.valley += (x - self.valley) * rate(x < self.valley);
+        if self.peak > self.valley {
+            (x - self.valley) / (self.peak - self.valley) - 0.5
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A frame with the soft decisions it was sliced from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SoftFrame {
+    /// The frame.
+    pub frame: Ax25Frame,
+    /// One value per bit of the frame's `to_bytes`, most significant bit
+    /// of each byte first: positive for a one, negative for a zero, with
+    /// 127 for a full-strength decision.
+    pub soft: Vec<i8>,
+}
+
+impl SoftFrame {
+    /// Get how confidently the frame was received.
+    pub fn quality(&self) -> Quality {
+        Quality::from_soft(&self.soft, true, 0)
+    }
+}
+
+/// Demodulator for 1200 bit/s Bell 202 AFSK, the modulation of VHF APRS.
+///
+/// Takes FM-demodulated audio, compares the strength of the mark and space
+/// tones over each bit, recovers the bit clock with a digital PLL, undoes
+/// the NRZI coding and hands the bits to an HDLC deframer, which finds the
+/// flags, removes stuffed bits and checks each frame's CRC. Use it
+/// directly on audio from a scanner or sound card, or through
+/// `AprsReceiver` on IQ.
+pub struct AfskDemod {
+    mark: Correlator,
+    space: Correlator,
+    mark_agc: Agc,
+    space_agc: Agc,
+    /// Bit clock phase, a bit is sampled each time it wraps.
+    clock: f32,
+    clock_step: f32,
+    /// The previous tone decision, for finding transitions.
+    tone: bool,
+    /// The previous bit's level and how far mark and space were apart,
+    /// for NRZI decoding.
+    level: bool,
+    margin: f32,
+    /// Bits sliced since creation or reset.
+    bits: u64,
+    deframer: Deframer,
+    /// Where frames failing their checksum or AX.25 parsing go.
+    failures: Option<FailureDump>,
+}
+
+impl AfskDemod {
+    /// Create a demodulator.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The audio sample rate in Hz, at least 9600 Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AfskDemod`.
+    pub fn new(sample_rate: f64) -> Self {
+        assert!(
+            sample_rate >= 9_600.0,
+            "AFSK needs a sample rate of at least 9600 Hz"
+        );
+        let rate = sample_rate as f32;
+        let len = (rate / BAUD).round() as usize;
+        AfskDemod {
+            mark: Correlator::new(MARK, rate, len),
+            space: Correlator::new(SPACE, rate, len),
+            mark_agc: Agc::new(rate),
+            space_agc: Agc::new(rate),
+            clock: 0.0,
+            clock_step: BAUD / rate,
+            tone: false,
+            level: false,
+            margin: 0.0,
+            bits: 0,
+            deframer: Deframer::new(MIN_BYTES, MAX_BYTES),
+            failures: None,
+        }
+    }
+
+    /// Write the frames that fail their checksum or don't parse as AX.25
+    /// to a debug directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dump` - Where the failures go.
+    ///
+    /// # Returns
+    ///
+    /// The `AfskDemod` writing failures to `dump`.
+    pub fn with_failure_dump(mut self, dump: FailureDump) -> Self {
+        self.deframer.keep_rejected();
+        self.failures = Some(dump);
+        self
+    }
+
+    /// Get where failures are written, if anywhere.
+    pub fn failure_dump(&self) -> Option<&FailureDump> {
+        self.failures.as_ref()
+    }
+
+    /// Demodulate a chunk of audio.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of the stream, of any length and level.
+    ///
+    /// # Returns
+    ///
+    /// Every frame completed in the chunk that parses as AX.25, in order.
+    pub fn process(&mut self, audio: &[f32]) -> Vec<Ax25Frame> {
+        self.process_soft(audio)
+            .into_iter()
+            .map(|soft| soft.frame)
+            .collect()
+    }
+
+    /// Demodulate a chunk of audio, keeping the soft decisions.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of the stream, of any length and level.
+    ///
+    /// # Returns
+    ///
+    /// Every frame completed in the chunk that parses as AX.25 with its
+    /// soft decisions, in order.
+    pub fn process_soft(&mut self, audio: &[f32]) -> Vec<SoftFrame> {
+        let mut frames = Vec::new();
+        for &sample in audio {
+            let mark = self.mark_agc.push(self.mark.push(sample));
+            let space = self.space_agc.push(self.space.push(sample));
+            let tone = mark > space;
+            // Transitions should fall halfway between samples.
+            if tone != self.tone {
+                self.clock += (0.5 - self.clock) * PLL_GAIN;
+                self.tone = tone;
+            }
+            self.clock += self.clock_step;
+            if self.clock < 1.0 {
+                continue;
+            }
+            self.clock -= 1.0;
+            // NRZI: a change is a zero, no change a one, as sure as the
+            // weaker of the two tone decisions.
+            let bit = tone == self.level;
+            let margin = (mark - space).abs().min(self.margin);
+            self.level = tone;
+            self.margin = (mark - space).abs();
+            self.bits += 1;
+            let soft = soft_bit(if bit { margin } else { -margin });
+            let deframed = self.deframer.push(bit, soft);
+            if let Some((bytes, soft)) = self.deframer.take_rejected() {
+                self.dump("crc", bytes, soft);
+            }
+            let Some((data, soft)) = deframed else {
+                continue;
+            };
+            match Ax25Frame::from_bytes(&data) {
+                Some(frame) => frames.push(SoftFrame { frame, soft }),
+                None => self.dump("parse", data, soft),
+            }
+        }
+        frames
+    }
+
+    /// Clear the filter, clock and deframer state.
+    pub fn reset(&mut self) {
+        let rate = BAUD / self.clock_step;
+        let len = self.mark.history.len();
+        self.mark = Correlator::new(MARK, rate, len);
+        self.space = Correlator::new(SPACE, rate, len);
+        self.mark_agc = Agc::new(rate);
+        self.space_agc = Agc::new(rate);
+        self.clock = 0.0;
+        self.tone = false;
+        self.level = false;
+        self.margin = 0.0;
+        self.bits = 0;
+        self.deframer = Deframer::new(MIN_BYTES, MAX_BYTES);
+        if self.failures.is_some() {
+            self.deframer.keep_rejected();
+        }
+    }
+
+    fn dump(&mut self, reason: &str, bytes: Vec<u8>, soft: Vec<i8>) {
+        if let Some(dump) = &mut self.failures {
+            dump.write(&Failure {
+                decoder: "aprs".into(),
+                reason: reason.into(),
+                position: self.bits,
+                context: Vec::new(),
+                bytes,
+                soft,
+            });
+        }
+    }
+}
+
+/// Receiver for an APRS channel from IQ.
+///
+/// Selects the channel, FM-demodulates it and hands the audio to an
+/// `AfskDemod`. Tune a little off `NA_FREQ` or `EU_FREQ` and pass the
+/// offset to keep the channel clear of the dongle's DC spike.
+pub struct AprsReceiver {
+    xlator: FreqXlator,
+    fm: FmDemod,
+    afsk: AfskDemod,
+}
+
+impl AprsReceiver {
+    /// Create a receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz, at least 24 kHz.
+    /// * `offset` - The channel frequency relative to the tuned frequency
+    ///   in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AprsReceiver`.
+    pub fn new(sample_rate: f64, offset: f64) -> Self {
+        assert!(
+            sample_rate >= 24_000.0,
+            "APRS needs a sample rate of at least 24 kHz"
+        );
+        let factor = (sample_rate / 24_000.0).floor() as usize;
+        // Pass the 3 kHz deviation and the tones, and stop by 12 kHz out.
+        let taps = (5.5 * sample_rate / 5_500.0) as usize | 1;
+        let filter = low_pass(6_500.0 / sample_rate, taps, Window::Blackman);
+        let xlator = FreqXlator::new(sample_rate, offset, factor, &filter);
+        let rate = xlator.output_rate();
+        AprsReceiver {
+            xlator,
+            fm: FmDemod::new(rate, DEVIATION),
+            afsk: AfskDemod::new(rate),
+        }
+    }
+
+    /// Demodulate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// Every frame completed in the chunk, in order.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<Ax25Frame> {
+        let baseband = self.xlator.process(iq);
+        let audio = self.fm.process(&baseband);
+        self.afsk.process(&audio)
+    }
+
+    /// Write the frames that fail their checksum or don't parse as AX.25
+    /// to a debug directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dump` - Where the failures go.
+    ///
+    /// # Returns
+    ///
+    /// The `AprsReceiver` writing failures to `dump`.
+    pub fn with_failure_dump(mut self, dump: FailureDump) -> Self {
+        self.afsk = self.afsk.with_failure_dump(dump);
+        self
+    }
+
+    /// Demodulate a chunk of samples, keeping the soft decisions.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// Every frame completed in the chunk with its soft decisions, in
+    /// order.
+    pub fn process_soft(&mut self, iq: &[Complex<f32>]) -> Vec<SoftFrame> {
+        let baseband = self.xlator.process(iq);
+        let audio = self.fm.process(&baseband);
+        self.afsk.process_soft(&audio)
+    }
+
+    /// Clear the filter, demodulator and deframer state.
+    pub fn reset(&mut self) {
+        self.xlator.reset();
+        self.fm.reset();
+        self.afsk.reset();
+    }
+}
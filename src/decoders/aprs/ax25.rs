@@ -0,0 +1,264 @@
+use std::fmt;
+
+use super::payload::Aprs;
+
+/// The control byte of a UI frame, which APRS is sent in.
+pub const CONTROL_UI: u8 = 0x03;
+
+/// The PID of a frame with no layer 3 protocol, which APRS uses.
+pub const PID_NO_LAYER3: u8 = 0xf0;
+
+/// The most digipeaters a frame can carry.
+const MAX_DIGIPEATERS: usize = 8;
+
+/// KISS framing bytes.
+const FEND: u8 = 0xc0;
+const FESC: u8 = 0xdb;
+const TFEND: u8 = 0xdc;
+const TFESC: u8 = 0xdd;
+
+/// An AX.25 station address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Address {
+    /// The callsign, up to six characters.
+    pub callsign: String,
+    /// The secondary station identifier, 0 to 15.
+    pub ssid: u8,
+    /// For a digipeater, whether it has repeated the frame. For the
+    /// destination and source, the command/response bit.
+    pub repeated: bool,
+}
+
+impl Address {
+    /// Create an address.
+    ///
+    /// # Arguments
+    ///
+    /// * `callsign` - The callsign, up to six characters.
+    /// * `ssid` - The secondary station identifier, 0 to 15.
+    ///
+    /// # Returns
+    ///
+    /// A new `Address` with `repeated` clear.
+    pub fn new(callsign: &str, ssid: u8) -> Self {
+        assert!(callsign.len() <= 6, "callsigns are at most six characters");
+        assert!(ssid <= 15, "SSIDs run from 0 to 15");
+        Address {
+            callsign: callsign.to_ascii_uppercase(),
+            ssid,
+            repeated: false,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let callsign: String = bytes[..6]
+            .iter()
+            .map(|&b| (b >> 1) as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+        if callsign.is_empty() || !callsign.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(Address {
+            callsign,
+            ssid: (bytes[6] >> 1) & 0x0f,
+            repeated: bytes[6] & 0x80 != 0,
+        })
+    }
+
+    fn to_bytes(&self, last: bool) -> [u8; 7] {
+        let mut bytes = [b' ' << 1; 7];
+        for (b, c) in bytes.iter_mut().zip(self.callsign.bytes()) {
+            *b = c << 1;
+        }
+        // The two reserved bits are sent as ones.
+        bytes[6] = 0x60 | (self.ssid << 1) | (self.repeated as u8) << 7 | last as u8;
+        bytes
+    }
+}
+
+/// Formats the address as `CALL-SSID`, leaving out an SSID of 0.
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ssid == 0 {
+            write!(f, "{}", self.callsign)
+        } else {
+            write!(f, "{}-{}", self.callsign, self.ssid)
+        }
+    }
+}
+
+/// An AX.25 frame that passed its checksum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ax25Frame {
+    /// Where the frame is addressed. APRS uses it for a software version
+    /// or, in Mic-E, the latitude.
+    pub destination: Address,
+    /// The station that sent the frame.
+    pub source: Address,
+    /// The digipeater path, in order.
+    pub digipeaters: Vec<Address>,
+    /// The control byte, `CONTROL_UI` for APRS.
+    pub control: u8,
+    /// The protocol identifier, present in I and UI frames.
+    pub pid: Option<u8>,
+    /// The information field.
+    pub info: Vec<u8>,
+}
+
+impl Ax25Frame {
+    /// Parse a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The frame from the first address byte to the end of the
+    ///   information field, without flags or checksum.
+    ///
+    /// # Returns
+    ///
+    /// The frame if the addresses are well formed, otherwise `None`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut addresses = Vec::new();
+        let mut pos = 0;
+        loop {
+            let field = bytes.get(pos..pos + 7)?;
+            addresses.push(Address::from_bytes(field)?);
+            pos += 7;
+            // The low bit marks the last address.
+            if field[6] & 1 != 0 {
+                break;
+            }
+            if addresses.len() == 2 + MAX_DIGIPEATERS {
+                return None;
+            }
+        }
+        if addresses.len() < 2 {
+            return None;
+        }
+        let control = *bytes.get(pos)?;
+        pos += 1;
+        // I frames and UI frames carry a PID.
+        let pid = if control & 1 == 0 || control & 0xef == CONTROL_UI {
+            let pid = *bytes.get(pos)?;
+            pos += 1;
+            Some(pid)
+        } else {
+            None
+        };
+        let mut addresses = addresses.into_iter();
+        Some(Ax25Frame {
+            destination: addresses.next()?,
+            source: addresses.next()?,
+            digipeaters: addresses.collect(),
+            control,
+            pid,
+            info: bytes[pos..].to_vec(),
+        })
+    }
+
+    /// Create an APRS frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The sending station.
+    /// * `destination` - The destination, e.g. `APRS`.
+    /// * `digipeaters` - The digipeater path, e.g. `WIDE1-1,WIDE2-1`.
+    /// * `info` - The APRS information field.
+    ///
+    /// # Returns
+    ///
+    /// A new UI `Ax25Frame`.
+    pub fn ui(
+        source: Address,
+        destination: Address,
+        digipeaters: Vec<Address>,
+        info: &[u8],
+    ) -> Self {
+        assert!(
+            digipeaters.len() <= MAX_DIGIPEATERS,
+            "a frame carries at most eight digipeaters"
+        );
+        Ax25Frame {
+            destination,
+            source,
+            digipeaters,
+            control: CONTROL_UI,
+            pid: Some(PID_NO_LAYER3),
+            info: info.to_vec(),
+        }
+    }
+
+    /// Encode the frame, without flags or checksum.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + 7 * self.digipeaters.len() + self.info.len());
+        bytes.extend(self.destination.to_bytes(false));
+        bytes.extend(self.source.to_bytes(self.digipeaters.is_empty()));
+        for (i, digi) in self.digipeaters.iter().enumerate() {
+            bytes.extend(digi.to_bytes(i + 1 == self.digipeaters.len()));
+        }
+        bytes.push(self.control);
+        bytes.extend(self.pid);
+        bytes.extend_from_slice(&self.info);
+        bytes
+    }
+
+    /// Encode the frame as a KISS data frame, as TNCs send to the host and
+    /// Direwolf serves on its KISS TCP port.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The TNC port, 0 to 15.
+    ///
+    /// # Returns
+    ///
+    /// The frame between `FEND` bytes, with `FEND` and `FESC` escaped.
+    pub fn to_kiss(&self, port: u8) -> Vec<u8> {
+        assert!(port <= 15, "KISS ports run from 0 to 15");
+        let bytes = self.to_bytes();
+        let mut kiss = Vec::with_capacity(bytes.len() + 4);
+        kiss.push(FEND);
+        kiss.push(port << 4);
+        for b in bytes {
+            match b {
+                FEND => kiss.extend([FESC, TFEND]),
+                FESC => kiss.extend([FESC, TFESC]),
+                _ => kiss.push(b),
+            }
+        }
+        kiss.push(FEND);
+        kiss
+    }
+
+    /// Whether the frame is a UI frame with no layer 3 protocol, as APRS
+    /// packets are.
+    pub fn is_aprs(&self) -> bool {
+        self.control & 0xef == CONTROL_UI && self.pid == Some(PID_NO_LAYER3)
+    }
+
+    /// Decode the information field as APRS.
+    ///
+    /// # Returns
+    ///
+    /// The decoded packet if the frame is an APRS packet, otherwise `None`.
+    pub fn aprs(&self) -> Option<Aprs> {
+        self.is_aprs().then(|| Aprs::decode(self))
+    }
+}
+
+/// Formats the frame the way TNC2 monitors and APRS-IS show it:
+/// `SOURCE>DEST,DIGI*,DIGI:info`, with `*` after the last digipeater that
+/// repeated it.
+impl fmt::Display for Ax25Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}>{}", self.source, self.destination)?;
+        let last = self.digipeaters.iter().rposition(|d| d.repeated);
+        for (i, digi) in self.digipeaters.iter().enumerate() {
+            write!(f, ",{}", digi)?;
+            if Some(i) == last {
+                write!(f, "*")?;
+            }
+        }
+        write!(f, ":{}", String::from_utf8_lossy(&self.info))
+    }
+}
@@ -0,0 +1,149 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use super::ax25::Ax25Frame;
+use crate::decoders::feed::Feed;
+use crate::error::Result;
+
+/// The port Direwolf serves KISS on, which APRS clients connect to by
+/// default.
+pub const KISS_PORT: u16 = 8001;
+
+/// TCP server feeding received frames as KISS to APRS software.
+///
+/// Speaks the KISS-over-TCP protocol Direwolf serves, read by Xastir,
+/// YAAC, APRSIS32, PinPoint and igate software. Anything clients send,
+/// such as frames to transmit, is ignored.
+///
+/// The server never blocks the caller. Connections are accepted and queued
+/// bytes written whenever `send` or `flush` is called. Each client has its
+/// own queue: while a client is slower than the traffic, whole frames are
+/// dropped for that client alone once its queue is full, and a client that
+/// takes nothing for the stall timeout is disconnected.
+///
+/// ```no_run
+/// use radion::decoders::aprs::{AprsReceiver, KissServer, KISS_PORT, NA_FREQ};
+/// use radion::samples::{to_complex_f32, Scaling};
+/// use radion::Device;
+///
+/// let mut kiss = KissServer::bind(("0.0.0.0", KISS_PORT))?;
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(NA_FREQ - 50_000)?;
+/// device.set_sample_rate(240_000)?;
+/// device.reset_buffer()?;
+///
+/// let mut receiver = AprsReceiver::new(240_000.0, 50_000.0);
+/// loop {
+///     let iq = to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized);
+///     kiss.send(&receiver.process(&iq));
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct KissServer {
+    feed: Feed,
+    port: u8,
+}
+
+impl KissServer {
+    /// Start listening.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `"0.0.0.0:8001"`.
+    ///
+    /// # Returns
+    ///
+    /// A new `KissServer` sending on TNC port 0, with a 64 KiB queue per
+    /// client and a 10 second stall timeout if successful, otherwise an
+    /// `Error`.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(KissServer {
+            feed: Feed::bind(addr)?,
+            port: 0,
+        })
+    }
+
+    /// Set the TNC port frames are labelled with, for clients that tell
+    /// several radios apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The port, 0 to 15.
+    ///
+    /// # Returns
+    ///
+    /// The `KissServer` with the new port.
+    pub fn with_port(mut self, port: u8) -> Self {
+        assert!(port <= 15, "KISS ports run from 0 to 15");
+        self.port = port;
+        self
+    }
+
+    /// Set the size of each client's queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - How many bytes may wait for a client before frames are
+    ///   dropped for it.
+    ///
+    /// # Returns
+    ///
+    /// The `KissServer` with the new queue size.
+    pub fn with_max_pending(mut self, bytes: usize) -> Self {
+        self.feed.max_pending = bytes;
+        self
+    }
+
+    /// Set how long a client may take nothing before it is disconnected.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The stall timeout.
+    ///
+    /// # Returns
+    ///
+    /// The `KissServer` with the new timeout.
+    pub fn with_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.feed.stall_timeout = timeout;
+        self
+    }
+
+    /// Get the address the server listens on.
+    ///
+    /// # Returns
+    ///
+    /// The address, useful after binding to port 0, otherwise an `Error`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.feed.local_addr()
+    }
+
+    /// Get the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.feed.client_count()
+    }
+
+    /// Get the number of frames dropped for clients whose queue was full,
+    /// summed over all clients.
+    pub fn dropped(&self) -> u64 {
+        self.feed.dropped()
+    }
+
+    /// Send frames to every client.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames, in order.
+    pub fn send(&mut self, frames: &[Ax25Frame]) {
+        for frame in frames {
+            self.feed.queue(&frame.to_kiss(self.port));
+        }
+        self.feed.flush();
+    }
+
+    /// Accept new clients and write queued bytes without sending anything
+    /// new. Call it while no frames arrive to keep clients served.
+    pub fn flush(&mut self) {
+        self.feed.flush();
+    }
+}
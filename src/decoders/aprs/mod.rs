@@ -0,0 +1,47 @@
+//! APRS reception on VHF.
+//!
+//! `AprsReceiver` demodulates the 1200 bit/s AFSK of an APRS channel from
+//! IQ, returning each AX.25 frame that passes its checksum as an
+//! `Ax25Frame`; `AfskDemod` does the same from audio, and both keep each
+//! frame's soft decisions as a `SoftFrame` through `process_soft`.
+//! `Ax25Frame::aprs` decodes positions, weather, telemetry, messages and
+//! status reports, its `Display` gives the TNC2 monitor form, and
+//! `KissServer` feeds frames to APRS software over TCP.
+//!
+//! ```no_run
+//! use radion::decoders::aprs::{Aprs, AprsReceiver, NA_FREQ};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::Device;
+//!
+//! // Tune off the channel to keep it clear of the DC spike.
+//! let device = Device::new(0)?;
+//! device.set_center_freq(NA_FREQ - 50_000)?;
+//! device.set_sample_rate(240_000)?;
+//! device.reset_buffer()?;
+//!
+//! let mut receiver = AprsReceiver::new(240_000.0, 50_000.0);
+//! loop {
+//!     let iq = to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized);
+//!     for frame in receiver.process(&iq) {
+//!         println!("{}", frame);
+//!         if let Some(Aprs::Position(pos)) = frame.aprs() {
+//!             println!("  {} at {:.5}, {:.5}", frame.source, pos.lat, pos.lon);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod afsk;
+mod ax25;
+mod kiss;
+mod payload;
+
+pub use afsk::{AfskDemod, AprsReceiver, SoftFrame, EU_FREQ, NA_FREQ};
+pub use ax25::{Address, Ax25Frame, CONTROL_UI, PID_NO_LAYER3};
+pub use kiss::{KissServer, KISS_PORT};
+pub use payload::{Aprs, Message, Position, Telemetry, Weather};
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
@@ -0,0 +1,422 @@
+use super::ax25::Ax25Frame;
+
+/// A decoded APRS packet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Aprs {
+    /// A position report, uncompressed, compressed or Mic-E, possibly from
+    /// a weather station.
+    Position(Position),
+    /// A weather report without a position.
+    Weather(Weather),
+    /// A telemetry report.
+    Telemetry(Telemetry),
+    /// A message to another station.
+    Message(Message),
+    /// A status report.
+    Status(String),
+    /// Any other kind of packet, with its data type identifier and
+    /// information field.
+    Other(char, String),
+}
+
+/// An APRS position report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    /// Latitude in degrees, positive north.
+    pub lat: f64,
+    /// Longitude in degrees, positive east.
+    pub lon: f64,
+    /// The symbol table, `/` or `\` or an overlay character.
+    pub symbol_table: char,
+    /// The symbol within the table, e.g. `>` for a car or `_` for a
+    /// weather station.
+    pub symbol: char,
+    /// Course in degrees clockwise from north.
+    pub course: Option<u16>,
+    /// Speed in knots.
+    pub speed: Option<f32>,
+    /// Altitude in feet.
+    pub altitude: Option<f32>,
+    /// The timestamp as sent, e.g. `092345z`.
+    pub timestamp: Option<String>,
+    /// Whether the station can receive messages.
+    pub messaging: bool,
+    /// The weather report of a weather station.
+    pub weather: Option<Weather>,
+    /// The free text after the position and any data decoded from it.
+    pub comment: String,
+}
+
+/// An APRS weather report, converted to metric units.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Weather {
+    /// Wind direction in degrees the wind blows from.
+    pub wind_direction: Option<u16>,
+    /// Sustained wind speed in km/h.
+    pub wind_speed: Option<f32>,
+    /// Peak wind gust in the last five minutes in km/h.
+    pub wind_gust: Option<f32>,
+    /// Temperature in °C.
+    pub temperature: Option<f32>,
+    /// Rain in the last hour in mm.
+    pub rain_1h: Option<f32>,
+    /// Rain in the last 24 hours in mm.
+    pub rain_24h: Option<f32>,
+    /// Rain since midnight in mm.
+    pub rain_today: Option<f32>,
+    /// Relative humidity in %.
+    pub humidity: Option<u8>,
+    /// Barometric pressure in hPa.
+    pub pressure: Option<f32>,
+    /// Luminosity in W/m².
+    pub luminosity: Option<u16>,
+    /// The timestamp of a positionless report as sent, `MMDDhhmm`.
+    pub timestamp: Option<String>,
+}
+
+/// An APRS telemetry report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Telemetry {
+    /// The sequence number as sent, usually three digits.
+    pub sequence: String,
+    /// Up to five analog values.
+    pub analog: Vec<f32>,
+    /// The eight digital bits, first bit first, if sent.
+    pub digital: Vec<bool>,
+}
+
+/// An APRS message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Message {
+    /// The station it is addressed to.
+    pub addressee: String,
+    /// The message text.
+    pub text: String,
+    /// The message number the receiver acknowledges, if any.
+    pub id: Option<String>,
+}
+
+impl Aprs {
+    /// Decode an APRS packet.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame. Mic-E packets carry part of their position
+    ///   in the destination address.
+    ///
+    /// # Returns
+    ///
+    /// The decoded packet, `Other` for kinds not decoded or malformed.
+    pub fn decode(frame: &Ax25Frame) -> Self {
+        let info = &frame.info[..];
+        let Some(&kind) = info.first() else {
+            return Aprs::Other(' ', String::new());
+        };
+        let body = &info[1..];
+        let decoded = match kind {
+            b'!' | b'=' => position(body, None, kind == b'=').map(Aprs::Position),
+            b'/' | b'@' if body.len() > 7 => {
+                position(&body[7..], Some(text(&body[..7])), kind == b'@').map(Aprs::Position)
+            }
+            b'`' | b'\'' => mic_e(&frame.destination.callsign, body).map(Aprs::Position),
+            b'_' if body.len() > 8 => {
+                let (mut weather, _) = weather(&body[8..], Weather::default());
+                weather.timestamp = Some(text(&body[..8]));
+                Some(Aprs::Weather(weather))
+            }
+            b'T' => telemetry(body).map(Aprs::Telemetry),
+            b':' => message(body).map(Aprs::Message),
+            b'>' => Some(Aprs::Status(text(body))),
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| Aprs::Other(kind as char, text(body)))
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn digits(bytes: &[u8]) -> Option<f64> {
+    std::str::from_utf8(bytes).ok()?.trim().parse().ok()
+}
+
+fn base91(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0u32, |acc, &b| {
+        (33..=123).contains(&b).then(|| acc * 91 + (b - 33) as u32)
+    })
+}
+
+/// Decode a position report's body after the data type and any timestamp.
+fn position(body: &[u8], timestamp: Option<String>, messaging: bool) -> Option<Position> {
+    let mut pos = if body.first()?.is_ascii_digit() {
+        uncompressed(body)?
+    } else {
+        compressed(body)?
+    };
+    pos.timestamp = timestamp;
+    pos.messaging = messaging;
+    let used = if body[0].is_ascii_digit() { 19 } else { 13 };
+    let mut rest = &body[used..];
+
+    if pos.symbol == '_' {
+        let mut weather = Weather::default();
+        // A weather station's course and speed are the wind.
+        if let Some(dir) = pos.course.take() {
+            weather.wind_direction = Some(dir);
+        }
+        if let Some(speed) = pos.speed.take() {
+            weather.wind_speed = Some(speed * 1.852);
+        }
+        if let Some((dir, speed)) = course_speed(rest) {
+            weather.wind_direction = Some(dir);
+            weather.wind_speed = Some(speed * 1.609_344);
+            rest = &rest[7..];
+        }
+        let (weather, remaining) = self::weather(rest, weather);
+        pos.weather = Some(weather);
+        rest = remaining;
+    } else if let Some((course, speed)) = course_speed(rest) {
+        pos.course = Some(course);
+        pos.speed = Some(speed);
+        rest = &rest[7..];
+    }
+    pos.comment = text(rest);
+    if let Some(at) = pos.comment.find("/A=") {
+        if let Some(feet) = pos
+            .comment
+            .get(at + 3..at + 9)
+            .and_then(|s| s.parse::<f32>().ok())
+        {
+            pos.altitude = Some(feet);
+            pos.comment.replace_range(at..at + 9, "");
+        }
+    }
+    Some(pos)
+}
+
+/// Decode `ddmm.hhN/dddmm.hhW>`, with spaces for position ambiguity.
+fn uncompressed(body: &[u8]) -> Option<Position> {
+    let field = body.get(..19)?;
+    let clear = |b: &[u8]| -> Vec<u8> {
+        b.iter()
+            .map(|&c| if c == b' ' { b'0' } else { c })
+            .collect()
+    };
+    let lat_field = clear(&field[..7]);
+    let lon_field = clear(&field[9..17]);
+    let mut lat = digits(&lat_field[..2])? + digits(&lat_field[2..7])? / 60.0;
+    let mut lon = digits(&lon_field[..3])? + digits(&lon_field[3..8])? / 60.0;
+    match field[7] {
+        b'N' => {}
+        b'S' => lat = -lat,
+        _ => return None,
+    }
+    match field[17] {
+        b'E' => {}
+        b'W' => lon = -lon,
+        _ => return None,
+    }
+    Some(Position {
+        lat,
+        lon,
+        symbol_table: field[8] as char,
+        symbol: field[18] as char,
+        ..Position::default()
+    })
+}
+
+/// Decode the base-91 compressed form `/YYYYXXXX>cst`.
+fn compressed(body: &[u8]) -> Option<Position> {
+    let field = body.get(..13)?;
+    let lat = 90.0 - base91(&field[1..5])? as f64 / 380_926.0;
+    let lon = -180.0 + base91(&field[5..9])? as f64 / 190_463.0;
+    let mut pos = Position {
+        lat,
+        lon,
+        // Overlays are sent as a-j for 0-9.
+        symbol_table: match field[0] {
+            c @ b'a'..=b'j' => (c - b'a' + b'0') as char,
+            c => c as char,
+        },
+        symbol: field[9] as char,
+        ..Position::default()
+    };
+    let (c, s, t) = (field[10], field[11], field[12]);
+    if c != b' ' && (33..=123).contains(&t) {
+        if (t - 33) & 0x18 == 0x10 {
+            // An altitude from a GGA sentence.
+            let exp = base91(&[c, s])? as i32;
+            pos.altitude = Some(1.002f32.powi(exp));
+        } else if (b'!'..=b'z').contains(&c) {
+            pos.course = Some((c - 33) as u16 * 4);
+            pos.speed = Some(1.08f32.powi((s as i32) - 33) - 1.0);
+        }
+    }
+    Some(pos)
+}
+
+/// Decode a `ccc/sss` course and speed extension.
+fn course_speed(rest: &[u8]) -> Option<(u16, f32)> {
+    let field = rest.get(..7)?;
+    if field[3] != b'/' {
+        return None;
+    }
+    let course = digits(&field[..3])?;
+    let speed = digits(&field[4..])?;
+    Some((course as u16, speed as f32))
+}
+
+/// Decode weather fields, each a letter and a fixed number of digits, dots
+/// or spaces when not measured. Stops at the first unknown letter, where
+/// the station's software or a comment follows.
+fn weather(mut rest: &[u8], mut weather: Weather) -> (Weather, &[u8]) {
+    while let Some(&key) = rest.first() {
+        let width = match key {
+            b'c' | b's' | b'g' | b't' | b'r' | b'p' | b'P' | b'L' | b'l' => 3,
+            b'h' => 2,
+            b'b' => 5,
+            _ => break,
+        };
+        let Some(field) = rest.get(1..1 + width) else {
+            break;
+        };
+        let value = digits(field);
+        rest = &rest[1 + width..];
+        let Some(v) = value else {
+            continue;
+        };
+        let mph = |v: f64| (v * 1.609_344) as f32;
+        let inches = |v: f64| (v * 0.254) as f32;
+        match key {
+            b'c' => weather.wind_direction = Some(v as u16),
+            b's' => weather.wind_speed = Some(mph(v)),
+            b'g' => weather.wind_gust = Some(mph(v)),
+            b't' => weather.temperature = Some(((v - 32.0) * 5.0 / 9.0) as f32),
+            b'r' => weather.rain_1h = Some(inches(v)),
+            b'p' => weather.rain_24h = Some(inches(v)),
+            b'P' => weather.rain_today = Some(inches(v)),
+            b'h' => weather.humidity = Some(if v == 0.0 { 100 } else { v as u8 }),
+            b'b' => weather.pressure = Some((v / 10.0) as f32),
+            b'L' => weather.luminosity = Some(v as u16),
+            _ => weather.luminosity = Some(v as u16 + 1000),
+        }
+    }
+    (weather, rest)
+}
+
+/// Decode a Mic-E position, whose latitude, hemispheres and longitude
+/// offset are encoded in the destination address.
+fn mic_e(destination: &str, body: &[u8]) -> Option<Position> {
+    let dest = destination.as_bytes();
+    if dest.len() != 6 || body.len() < 8 {
+        return None;
+    }
+    let mut lat_digits = [0u8; 6];
+    for (d, &c) in lat_digits.iter_mut().zip(dest) {
+        *d = match c {
+            b'0'..=b'9' => c - b'0',
+            b'A'..=b'J' => c - b'A',
+            b'P'..=b'Y' => c - b'P',
+            // Position ambiguity.
+            b'K' | b'L' | b'Z' => 0,
+            _ => return None,
+        };
+    }
+    let high = |c: u8| (b'P'..=b'Z').contains(&c);
+    let [d0, d1, m0, m1, h0, h1] = lat_digits.map(|d| d as f64);
+    let mut lat = d0 * 10.0 + d1 + (m0 * 10.0 + m1 + (h0 * 10.0 + h1) / 100.0) / 60.0;
+    if !high(dest[3]) {
+        lat = -lat;
+    }
+
+    let b = |i: usize| body[i] as i32 - 28;
+    let mut deg = b(0);
+    if high(dest[4]) {
+        deg += 100;
+    }
+    if (180..=189).contains(&deg) {
+        deg -= 80;
+    } else if (190..=199).contains(&deg) {
+        deg -= 190;
+    }
+    let mut min = b(1);
+    if min >= 60 {
+        min -= 60;
+    }
+    let mut lon = deg as f64 + (min as f64 + b(2) as f64 / 100.0) / 60.0;
+    if high(dest[5]) {
+        lon = -lon;
+    }
+
+    let mut speed = b(3) * 10 + b(4) / 10;
+    if speed >= 800 {
+        speed -= 800;
+    }
+    let mut course = (b(4) % 10) * 100 + b(5);
+    if course >= 400 {
+        course -= 400;
+    }
+
+    let mut pos = Position {
+        lat,
+        lon,
+        symbol: body[6] as char,
+        symbol_table: body[7] as char,
+        course: Some(course as u16),
+        speed: Some(speed as f32),
+        ..Position::default()
+    };
+    let mut rest = &body[8..];
+    // The altitude, in metres above -10 km, may follow a one-byte radio
+    // type.
+    for skip in 0..2 {
+        if rest.get(skip + 3) == Some(&b'}') {
+            if let Some(metres) = base91(&rest[skip..skip + 3]) {
+                pos.altitude = Some((metres as f32 - 10_000.0) / 0.3048);
+                rest = &rest[skip + 4..];
+                break;
+            }
+        }
+    }
+    pos.comment = text(rest);
+    Some(pos)
+}
+
+/// Decode `#sss,aaa,aaa,aaa,aaa,aaa,bbbbbbbb`.
+fn telemetry(body: &[u8]) -> Option<Telemetry> {
+    let body = std::str::from_utf8(body.strip_prefix(b"#")?).ok()?;
+    let mut fields = body.split(',');
+    let sequence = fields.next()?.to_string();
+    let mut telemetry = Telemetry {
+        sequence,
+        ..Telemetry::default()
+    };
+    for field in fields {
+        if telemetry.analog.len() < 5 {
+            telemetry.analog.push(field.trim().parse().ok()?);
+        } else {
+            telemetry.digital = field.bytes().take(8).map(|b| b == b'1').collect();
+            break;
+        }
+    }
+    Some(telemetry)
+}
+
+/// Decode `:ADDRESSEE:text{id`, the addressee padded to nine characters.
+fn message(body: &[u8]) -> Option<Message> {
+    if body.get(9) != Some(&b':') {
+        return None;
+    }
+    let addressee = text(&body[..9]).trim_end().to_string();
+    let content = text(&body[10..]);
+    let (text, id) = match content.rsplit_once('{') {
+        Some((text, id)) => (text.to_string(), Some(id.trim_end().to_string())),
+        None => (content, None),
+    };
+    Some(Message {
+        addressee,
+        text,
+        id,
+    })
+}
@@ -0,0 +1,423 @@
+use super::image::{AptLine, LINE_PIXELS};
+use crate::demod::FmDemod;
+use crate::dsp::fir::{low_pass, Window};
+use crate::dsp::{Complex, FreqXlator};
+
+/// The word rate: two lines a second.
+const PIXEL_RATE: f64 = 4160.0;
+
+/// The AM subcarrier carrying the image.
+const SUBCARRIER: f64 = 2400.0;
+
+/// The peak deviation of the FM carrier.
+const DEVIATION: f64 = 17_000.0;
+
+/// Sync A, seven cycles of a 1040 Hz square wave between black words.
+const SYNC_A: [f32; 39] = {
+    let mut sync = [-1.0; 39];
+    let mut i = 0;
+    while i < 7 {
+        sync[4 + 4 * i] = 1.0;
+        sync[5 + 4 * i] = 1.0;
+        i += 1;
+    }
+    sync
+};
+
+/// How far either side of where it is expected a line's sync is looked
+/// for once locked, in pixels.
+const TRACK: usize = 8;
+
+/// The weakest sync match taken as a line start.
+const MIN_SYNC: f32 = 0.4;
+
+/// How many lines in a row may miss their sync before the lock is given
+/// up, ten seconds.
+const MAX_MISSES: u32 = 20;
+
+/// A NOAA polar orbiter transmitting APT.
+///
+/// The three satellites ended APT transmissions in 2025, so these mainly
+/// label recordings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Satellite {
+    /// NOAA 15, 137.62 MHz.
+    Noaa15,
+    /// NOAA 18, 137.9125 MHz.
+    Noaa18,
+    /// NOAA 19, 137.1 MHz.
+    Noaa19,
+}
+
+impl Satellite {
+    /// Get the downlink frequency in Hz.
+    pub fn frequency(self) -> u32 {
+        match self {
+            Satellite::Noaa15 => 137_620_000,
+            Satellite::Noaa18 => 137_912_500,
+            Satellite::Noaa19 => 137_100_000,
+        }
+    }
+}
+
+/// Where to pick up a long recording again without losing or repeating
+/// lines.
+///
+/// Save it alongside the lines decoded so far. To resume, read the
+/// recording from `sample` and pass the checkpoint to the decoder's
+/// `with_checkpoint`: the lines before `line_sample`, decoded again while
+/// the filters settle and the sync locks, are dropped, and numbering
+/// carries on from `line`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Checkpoint {
+    /// The input sample to restart reading at.
+    pub sample: u64,
+    /// The number of the next line.
+    pub line: u64,
+    /// The input sample the next line starts at, or where searching for
+    /// it starts.
+    pub line_sample: u64,
+}
+
+/// Decoder for APT lines from FM-demodulated audio.
+///
+/// Recovers the 2400 Hz AM subcarrier's envelope at the 4160 word/s rate,
+/// finds each line's sync A by correlation and returns the lines as
+/// `AptLine`s. Once locked the sync is followed from line to line, so
+/// Doppler and sample clock drift don't skew the image; lines whose sync
+/// is lost are kept at the expected spacing until it returns. Memory use
+/// doesn't grow with the recording, and `checkpoint` allows an interrupted
+/// run to be resumed. Use it directly on audio from a receiver or WAV
+/// file, or through `AptReceiver` on IQ.
+pub struct AptDecoder {
+    sample_rate: f64,
+    subcarrier: FreqXlator,
+    /// Envelope not yet resampled, and the position of the next pixel in
+    /// it.
+    envelope: Vec<f32>,
+    clock: f64,
+    /// Pixels not yet taken into a line, and the index of the first.
+    pixels: Vec<f32>,
+    base: u64,
+    /// Where the next line is expected to start, once locked.
+    next: Option<u64>,
+    misses: u32,
+    line: u64,
+    /// The input sample pixel 0 was taken at.
+    offset: u64,
+    /// Lines starting before this input sample were decoded before a
+    /// resume.
+    skip_before: u64,
+}
+
+impl AptDecoder {
+    /// Create a decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The audio sample rate in Hz, at least 9600 Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AptDecoder`.
+    pub fn new(sample_rate: f64) -> Self {
+        assert!(
+            sample_rate >= 9_600.0,
+            "APT needs a sample rate of at least 9600 Hz"
+        );
+        // Pass the 2080 Hz a pixel pair spans either side of the subcarrier.
+        let taps = (4.0 * sample_rate / 1_000.0) as usize | 1;
+        let filter = low_pass(2_080.0 / sample_rate, taps, Window::Blackman);
+        AptDecoder {
+            sample_rate,
+            subcarrier: FreqXlator::new(sample_rate, SUBCARRIER, 1, &filter),
+            envelope: Vec::new(),
+            clock: 0.0,
+            pixels: Vec::new(),
+            base: 0,
+            next: None,
+            misses: 0,
+            line: 0,
+            offset: 0,
+            skip_before: 0,
+        }
+    }
+
+    /// Resume from a checkpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `checkpoint` - A checkpoint taken by a decoder with the same
+    ///   sample rate. Input must continue from `checkpoint.sample`.
+    ///
+    /// # Returns
+    ///
+    /// The `AptDecoder` picking up where the checkpoint was taken.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.offset = checkpoint.sample;
+        self.line = checkpoint.line;
+        // A resumed line may start a little off where it was expected.
+        self.skip_before = checkpoint
+            .line_sample
+            .saturating_sub(self.input_samples(LINE_PIXELS as u64 / 2));
+        self
+    }
+
+    /// Get a checkpoint to resume from.
+    ///
+    /// # Returns
+    ///
+    /// A `Checkpoint` after every line returned so far, restarting a
+    /// second before the next line.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let line_sample = self.input_sample(self.next.unwrap_or(self.base));
+        Checkpoint {
+            sample: line_sample.saturating_sub(self.sample_rate as u64),
+            line: self.line,
+            line_sample,
+        }
+    }
+
+    /// Decode a chunk of audio.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of the stream, of any length and level.
+    ///
+    /// # Returns
+    ///
+    /// Every line completed in the chunk, in order.
+    pub fn process(&mut self, audio: &[f32]) -> Vec<AptLine> {
+        let iq: Vec<Complex<f32>> = audio.iter().map(|&s| Complex::new(s, 0.0)).collect();
+        self.envelope
+            .extend(self.subcarrier.process(&iq).iter().map(|s| s.norm()));
+
+        let step = self.sample_rate / PIXEL_RATE;
+        while self.clock + 1.0 < self.envelope.len() as f64 {
+            let i = self.clock as usize;
+            let mu = (self.clock - i as f64) as f32;
+            let pixel = self.envelope[i] + (self.envelope[i + 1] - self.envelope[i]) * mu;
+            self.pixels.push(pixel);
+            self.clock += step;
+        }
+        let used = (self.clock as usize).min(self.envelope.len());
+        self.envelope.drain(..used);
+        self.clock -= used as f64;
+
+        self.lines()
+    }
+
+    /// Clear the filter, sync and line state, and restart counting input
+    /// samples and lines.
+    pub fn reset(&mut self) {
+        self.subcarrier.reset();
+        self.envelope.clear();
+        self.clock = 0.0;
+        self.pixels.clear();
+        self.base = 0;
+        self.next = None;
+        self.misses = 0;
+        self.line = 0;
+        self.offset = 0;
+        self.skip_before = 0;
+    }
+
+    fn lines(&mut self) -> Vec<AptLine> {
+        let mut lines = Vec::new();
+        loop {
+            let end = self.base + self.pixels.len() as u64;
+            let Some(expected) = self.next else {
+                // Search a whole line's worth for a sync to lock to.
+                if end < self.base + (LINE_PIXELS + SYNC_A.len()) as u64 {
+                    break;
+                }
+                let (start, sync) = self.find_sync(self.base, LINE_PIXELS);
+                if sync >= MIN_SYNC {
+                    self.next = Some(start);
+                    self.misses = 0;
+                } else {
+                    self.drain_to(self.base + LINE_PIXELS as u64);
+                }
+                continue;
+            };
+            if end < expected + (TRACK + LINE_PIXELS) as u64 {
+                break;
+            }
+            let from = expected.saturating_sub(TRACK as u64).max(self.base);
+            let (mut start, sync) =
+                self.find_sync(from, (expected + TRACK as u64 + 1 - from) as usize);
+            if sync < MIN_SYNC {
+                start = expected;
+                self.misses += 1;
+                if self.misses > MAX_MISSES {
+                    self.next = None;
+                    continue;
+                }
+            } else {
+                self.misses = 0;
+            }
+
+            let sample = self.input_sample(start);
+            if sample >= self.skip_before {
+                let at = (start - self.base) as usize;
+                let pixels = self.pixels[at..at + LINE_PIXELS].to_vec();
+                lines.push(AptLine::new(self.line, sample, sync.max(0.0), pixels));
+                self.line += 1;
+            }
+            self.next = Some(start + LINE_PIXELS as u64);
+            self.drain_to(start + (LINE_PIXELS - TRACK) as u64);
+        }
+        lines
+    }
+
+    /// Find the best match for sync A starting within `count` pixels of
+    /// `from`, and its normalised correlation.
+    fn find_sync(&self, from: u64, count: usize) -> (u64, f32) {
+        let n = SYNC_A.len() as f32;
+        let mean = SYNC_A.iter().sum::<f32>() / n;
+        let norm = SYNC_A
+            .iter()
+            .map(|k| (k - mean).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        let first = (from - self.base) as usize;
+        let mut best = (from, f32::MIN);
+        for at in first..first + count {
+            let window = &self.pixels[at..at + SYNC_A.len()];
+            let level = window.iter().sum::<f32>() / n;
+            let spread = window
+                .iter()
+                .map(|x| (x - level).powi(2))
+                .sum::<f32>()
+                .sqrt();
+            let dot: f32 = window.iter().zip(SYNC_A).map(|(x, k)| x * (k - mean)).sum();
+            let sync = dot / (norm * spread).max(f32::EPSILON);
+            if sync > best.1 {
+                best = (self.base + at as u64, sync);
+            }
+        }
+        best
+    }
+
+    fn drain_to(&mut self, pixel: u64) {
+        let count = pixel
+            .saturating_sub(self.base)
+            .min(self.pixels.len() as u64);
+        self.pixels.drain(..count as usize);
+        self.base += count;
+    }
+
+    fn input_samples(&self, pixels: u64) -> u64 {
+        (pixels as f64 * self.sample_rate / PIXEL_RATE).round() as u64
+    }
+
+    fn input_sample(&self, pixel: u64) -> u64 {
+        self.offset + self.input_samples(pixel)
+    }
+}
+
+/// Receiver for APT from IQ.
+///
+/// Selects the 34 kHz wide downlink, FM-demodulates it and hands the audio
+/// to an `AptDecoder`. Doppler shifts the downlink by up to 3.5 kHz over a
+/// pass, which the channel filter leaves room for.
+pub struct AptReceiver {
+    xlator: FreqXlator,
+    fm: FmDemod,
+    decoder: AptDecoder,
+    factor: u64,
+}
+
+impl AptReceiver {
+    /// Create a receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz, at least 48 kHz.
+    /// * `offset` - The downlink frequency relative to the tuned frequency
+    ///   in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AptReceiver`.
+    pub fn new(sample_rate: f64, offset: f64) -> Self {
+        assert!(
+            sample_rate >= 48_000.0,
+            "APT needs a sample rate of at least 48 kHz"
+        );
+        let factor = (sample_rate / 48_000.0).floor() as usize;
+        // Pass the deviation, subcarrier and Doppler, and stop by 30 kHz
+        // out.
+        let taps = (5.5 * sample_rate / 9_000.0) as usize | 1;
+        let filter = low_pass(21_000.0 / sample_rate, taps, Window::Blackman);
+        let xlator = FreqXlator::new(sample_rate, offset, factor, &filter);
+        let rate = xlator.output_rate();
+        AptReceiver {
+            xlator,
+            fm: FmDemod::new(rate, DEVIATION),
+            decoder: AptDecoder::new(rate),
+            factor: factor as u64,
+        }
+    }
+
+    /// Resume from a checkpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `checkpoint` - A checkpoint taken by a receiver with the same
+    ///   sample rate. Input must continue from `checkpoint.sample`.
+    ///
+    /// # Returns
+    ///
+    /// The `AptReceiver` picking up where the checkpoint was taken.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.decoder = self.decoder.with_checkpoint(Checkpoint {
+            sample: checkpoint.sample / self.factor,
+            line: checkpoint.line,
+            line_sample: checkpoint.line_sample / self.factor,
+        });
+        self
+    }
+
+    /// Get a checkpoint to resume from.
+    ///
+    /// # Returns
+    ///
+    /// A `Checkpoint` after every line returned so far, restarting a
+    /// second before the next line.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let checkpoint = self.decoder.checkpoint();
+        Checkpoint {
+            sample: checkpoint.sample * self.factor,
+            line: checkpoint.line,
+            line_sample: checkpoint.line_sample * self.factor,
+        }
+    }
+
+    /// Decode a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// Every line completed in the chunk, in order.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<AptLine> {
+        let baseband = self.xlator.process(iq);
+        let audio = self.fm.process(&baseband);
+        let mut lines = self.decoder.process(&audio);
+        for line in &mut lines {
+            line.sample *= self.factor;
+        }
+        lines
+    }
+
+    /// Clear the filter, demodulator and decoder state, and restart
+    /// counting input samples and lines.
+    pub fn reset(&mut self) {
+        self.xlator.reset();
+        self.fm.reset();
+        self.decoder.reset();
+    }
+}
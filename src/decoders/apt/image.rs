@@ -0,0 +1,243 @@
+use std::ops::Range;
+
+#[cfg(feature = "png")]
+use crate::error::{Error, Result};
+
+/// Pixels in a line: sync, space, image and telemetry for both channels.
+pub const LINE_PIXELS: usize = 2080;
+
+/// Pixels in one channel's image.
+pub const CHANNEL_PIXELS: usize = 909;
+
+/// One of the two image channels in each line.
+///
+/// The satellite picks the sensor each channel carries, usually visible or
+/// near infrared in A during the day and thermal infrared in B.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// The channel after sync A, on the left.
+    A,
+    /// The channel after sync B, on the right.
+    B,
+}
+
+impl Channel {
+    fn image(self) -> Range<usize> {
+        match self {
+            Channel::A => 86..86 + CHANNEL_PIXELS,
+            Channel::B => 1126..1126 + CHANNEL_PIXELS,
+        }
+    }
+
+    fn telemetry(self) -> Range<usize> {
+        let end = self.image().end;
+        end..end + 45
+    }
+}
+
+/// One decoded APT line, half a second of transmission.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AptLine {
+    /// The line's number, counted from the first line decoded.
+    pub number: u64,
+    /// The input sample the line's sync starts at.
+    pub sample: u64,
+    /// How well the line's sync matched, from 0 to 1.
+    pub sync: f32,
+    pixels: Vec<f32>,
+}
+
+impl AptLine {
+    pub(crate) fn new(number: u64, sample: u64, sync: f32, pixels: Vec<f32>) -> Self {
+        AptLine {
+            number,
+            sample,
+            sync,
+            pixels,
+        }
+    }
+
+    /// Get the line's `LINE_PIXELS` subcarrier amplitudes, starting with
+    /// sync A.
+    pub fn pixels(&self) -> &[f32] {
+        &self.pixels
+    }
+
+    /// Get one channel's image pixels.
+    pub fn channel(&self, channel: Channel) -> &[f32] {
+        &self.pixels[channel.image()]
+    }
+
+    /// Get one channel's telemetry pixels, part of a wedge of the frame's
+    /// calibration strip.
+    pub fn telemetry(&self, channel: Channel) -> &[f32] {
+        &self.pixels[channel.telemetry()]
+    }
+}
+
+/// An APT image built up from decoded lines.
+///
+/// ```no_run
+/// use radion::decoders::apt::{AptImage, AptReceiver, Channel};
+/// use radion::samples::{to_complex_f32, Scaling};
+/// use radion::{FileSource, IqFormat, Pacing, SdrSource};
+///
+/// let mut source = FileSource::open("pass.cu8", IqFormat::Cu8, 240_000)?
+///     .with_pacing(Pacing::Unpaced);
+/// let mut receiver = AptReceiver::new(240_000.0, 0.0);
+/// let mut image = AptImage::new();
+/// for chunk in source.chunks(262_144) {
+///     let iq = to_complex_f32(&chunk?, Scaling::Normalized);
+///     image.extend(receiver.process(&iq));
+/// }
+/// image.save_png("pass-b.png", Some(Channel::B), true)?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AptImage {
+    lines: Vec<AptLine>,
+}
+
+impl AptImage {
+    /// Create an empty image.
+    pub fn new() -> Self {
+        AptImage::default()
+    }
+
+    /// Add a line at the bottom.
+    pub fn push(&mut self, line: AptLine) {
+        self.lines.push(line);
+    }
+
+    /// Get the lines, top first.
+    pub fn lines(&self) -> &[AptLine] {
+        &self.lines
+    }
+
+    /// Get the number of lines.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether the image has no lines.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Get the width of the image or of one channel in pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel, or `None` for whole lines.
+    pub fn width(channel: Option<Channel>) -> usize {
+        match channel {
+            Some(_) => CHANNEL_PIXELS,
+            None => LINE_PIXELS,
+        }
+    }
+
+    /// Render the image as 8-bit grey levels.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to render, or `None` for whole lines with
+    ///   sync and telemetry.
+    /// * `normalize` - Whether to equalise the histogram, spreading the
+    ///   levels present evenly over the grey scale. Otherwise levels are
+    ///   stretched linearly between the darkest and brightest 0.5%.
+    ///
+    /// # Returns
+    ///
+    /// `width(channel) * len()` bytes, row-major with the first line at the
+    /// top.
+    pub fn to_gray(&self, channel: Option<Channel>, normalize: bool) -> Vec<u8> {
+        let range = channel.map_or(0..LINE_PIXELS, Channel::image);
+        let values: Vec<f32> = self
+            .lines
+            .iter()
+            .flat_map(|line| line.pixels[range.clone()].iter().copied())
+            .collect();
+        let mut sorted = values.clone();
+        sorted.sort_unstable_by(f32::total_cmp);
+        if sorted.is_empty() {
+            return Vec::new();
+        }
+        if normalize {
+            let scale = 255.0 / sorted.len().max(2).saturating_sub(1) as f32;
+            values
+                .iter()
+                .map(|&v| (sorted.partition_point(|&s| s < v) as f32 * scale) as u8)
+                .collect()
+        } else {
+            let clip = sorted.len() / 200;
+            let low = sorted[clip];
+            let high = sorted[sorted.len() - 1 - clip].max(low);
+            let span = (high - low).max(f32::EPSILON);
+            values
+                .iter()
+                .map(|&v| ((v - low) / span * 255.0).clamp(0.0, 255.0) as u8)
+                .collect()
+        }
+    }
+
+    /// Write the image as a greyscale PNG, first line at the top.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the image.
+    /// * `channel` - The channel to write, or `None` for whole lines.
+    /// * `normalize` - Whether to equalise the histogram, see `to_gray`.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`. An empty image is
+    /// written as a single black line.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: std::io::Write>(
+        &self,
+        writer: W,
+        channel: Option<Channel>,
+        normalize: bool,
+    ) -> Result<()> {
+        let width = AptImage::width(channel);
+        let height = self.len().max(1);
+        let mut pixels = self.to_gray(channel, normalize);
+        pixels.resize(width * height, 0);
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let to_error = |e: png::EncodingError| Error::System(std::io::Error::other(e));
+        let mut png = encoder.write_header().map_err(to_error)?;
+        png.write_image_data(&pixels).map_err(to_error)?;
+        png.finish().map_err(to_error)
+    }
+
+    /// Save the image as a greyscale PNG file, first line at the top.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    /// * `channel` - The channel to write, or `None` for whole lines.
+    /// * `normalize` - Whether to equalise the histogram, see `to_gray`.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        channel: Option<Channel>,
+        normalize: bool,
+    ) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file), channel, normalize)
+    }
+}
+
+impl Extend<AptLine> for AptImage {
+    fn extend<I: IntoIterator<Item = AptLine>>(&mut self, lines: I) {
+        self.lines.extend(lines);
+    }
+}
@@ -0,0 +1,47 @@
+//! NOAA APT weather image reception.
+//!
+//! `AptReceiver` demodulates the 137 MHz FM downlink from IQ and decodes
+//! its 2400 Hz AM subcarrier into lines of two image channels, returning
+//! each as an `AptLine`; `AptDecoder` does the same from audio. Collect
+//! the lines in an `AptImage` to render or save them as PNG, optionally
+//! with histogram equalisation. Long recordings are decoded in chunks with
+//! bounded memory, and a `Checkpoint` lets an interrupted run resume
+//! without losing or repeating lines.
+//!
+//! ```no_run
+//! use std::io::{Seek, SeekFrom};
+//!
+//! use radion::decoders::apt::{AptImage, AptReceiver, Checkpoint, Channel};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::{FileSource, IqFormat, Pacing, SdrSource};
+//!
+//! // A checkpoint saved by an earlier, interrupted run.
+//! let checkpoint = Checkpoint { sample: 4_800_000, line: 38, line_sample: 5_040_000 };
+//!
+//! let mut file = std::fs::File::open("pass.cu8")?;
+//! file.seek(SeekFrom::Start(checkpoint.sample * 2))?;
+//! let mut source = FileSource::from_reader(file, IqFormat::Cu8, 240_000)
+//!     .with_pacing(Pacing::Unpaced);
+//! let mut receiver = AptReceiver::new(240_000.0, 0.0).with_checkpoint(checkpoint);
+//! let mut image = AptImage::new();
+//! for chunk in source.chunks(262_144) {
+//!     let iq = to_complex_f32(&chunk?, Scaling::Normalized);
+//!     for line in receiver.process(&iq) {
+//!         println!("line {} sync {:.2}", line.number, line.sync);
+//!         image.push(line);
+//!     }
+//!     // Save receiver.checkpoint() with the lines to resume again later.
+//! }
+//! image.save_png("pass-a.png", Some(Channel::A), false)?;
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod demod;
+mod image;
+
+pub use demod::{AptDecoder, AptReceiver, Checkpoint, Satellite};
+pub use image::{AptImage, AptLine, Channel, CHANNEL_PIXELS, LINE_PIXELS};
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
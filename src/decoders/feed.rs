@@ -0,0 +1,131 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+struct Client {
+    stream: TcpStream,
+    /// Bytes queued but not yet taken by the socket.
+    pending: Vec<u8>,
+    /// When the client last took bytes or had nothing queued.
+    progress: Instant,
+}
+
+impl Client {
+    /// Write as much of the queue as the socket takes without blocking.
+    ///
+    /// Returns `false` once the client has gone or stalled for too long.
+    fn flush(&mut self, now: Instant, stall_timeout: Duration) -> bool {
+        let mut scratch = [0u8; 512];
+        // Anything clients send is ignored, but reading it is how a closed
+        // connection shows up.
+        loop {
+            match self.stream.read(&mut scratch) {
+                Ok(0) => return false,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        let mut written = 0;
+        while written < self.pending.len() {
+            match self.stream.write(&self.pending[written..]) {
+                Ok(0) => return false,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        self.pending.drain(..written);
+        if written > 0 || self.pending.is_empty() {
+            self.progress = now;
+        }
+        now.duration_since(self.progress) <= stall_timeout
+    }
+}
+
+/// Non-blocking TCP fan-out of messages to any number of clients.
+///
+/// Connections are accepted and queued bytes written whenever `flush` is
+/// called. Each client has its own queue: whole messages are dropped for a
+/// client whose queue is full, and a client that takes nothing for the
+/// stall timeout is disconnected.
+pub(crate) struct Feed {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    pub(crate) max_pending: usize,
+    pub(crate) stall_timeout: Duration,
+    dropped: u64,
+}
+
+impl Feed {
+    /// Start listening, with a 64 KiB queue per client and a 10 second
+    /// stall timeout.
+    pub(crate) fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Feed {
+            listener,
+            clients: Vec::new(),
+            max_pending: 64 * 1024,
+            stall_timeout: Duration::from_secs(10),
+            dropped: 0,
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub(crate) fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Queue one message for every client, without writing it yet.
+    pub(crate) fn queue(&mut self, message: &[u8]) {
+        for client in &mut self.clients {
+            if client.pending.len() + message.len() > self.max_pending {
+                self.dropped += 1;
+            } else {
+                client.pending.extend_from_slice(message);
+            }
+        }
+    }
+
+    /// Accept new clients and write what the sockets take.
+    pub(crate) fn flush(&mut self) {
+        self.accept();
+        let now = Instant::now();
+        let stall_timeout = self.stall_timeout;
+        self.clients.retain_mut(|c| c.flush(now, stall_timeout));
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+                    // Messages are small and latency matters.
+                    let _ = stream.set_nodelay(true);
+                    self.clients.push(Client {
+                        stream,
+                        pending: Vec::new(),
+                        progress: Instant::now(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                // Nothing waiting, or out of descriptors: try again later.
+                Err(_) => break,
+            }
+        }
+    }
+}
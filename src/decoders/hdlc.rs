@@ -0,0 +1,291 @@
+/// CRC-16/X.25, the HDLC frame check sequence, over bytes assembled least
+/// significant bit first.
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for &b in data {
+        crc ^= b as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// HDLC deframer: finds flags, removes stuffed bits and checks the frame
+/// check sequence.
+pub(crate) struct Deframer {
+    /// The shortest and longest frames accepted, in bytes with the
+    /// checksum.
+    min_bytes: usize,
+    max_bytes: usize,
+    /// Consecutive ones received.
+    ones: u32,
+    /// Whether a flag has been seen and bits are being collected.
+    in_frame: bool,
+    bits: Vec<bool>,
+    /// The soft decision of each collected bit.
+    soft: Vec<i8>,
+    /// Whether frames failing their checksum are kept, and the last one.
+    keep_rejected: bool,
+    rejected: Option<(Vec<u8>, Vec<i8>)>,
+}
+
+impl Deframer {
+    /// Create a deframer.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_bytes` - The shortest frame accepted, in bytes with the
+    ///   checksum.
+    /// * `max_bytes` - The longest frame accepted, likewise.
+    ///
+    /// # Returns
+    ///
+    /// A new `Deframer`, waiting for a flag.
+    pub(crate) fn new(min_bytes: usize, max_bytes: usize) -> Self {
+        Deframer {
+            min_bytes,
+            max_bytes,
+            ones: 0,
+            in_frame: false,
+            bits: Vec::new(),
+            soft: Vec::new(),
+            keep_rejected: false,
+            rejected: None,
+        }
+    }
+
+    /// Keep whole-byte frames that fail their checksum for `take_rejected`.
+    pub(crate) fn keep_rejected(&mut self) {
+        self.keep_rejected = true;
+    }
+
+    /// Take the last frame that failed its checksum, if kept.
+    ///
+    /// # Returns
+    ///
+    /// The bytes with the checksum and the soft decisions of their bits,
+    /// most significant first.
+    pub(crate) fn take_rejected(&mut self) -> Option<(Vec<u8>, Vec<i8>)> {
+        self.rejected.take()
+    }
+
+    /// Take in one NRZI-decoded bit.
+    ///
+    /// # Arguments
+    ///
+    /// * `bit` - The bit.
+    /// * `soft` - Its soft decision, positive for a one.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of a frame with a good checksum, without the checksum,
+    /// and the soft decisions of their bits, most significant first, when
+    /// this bit completed one.
+    pub(crate) fn push(&mut self, bit: bool, soft: i8) -> Option<(Vec<u8>, Vec<i8>)> {
+        if bit {
+            self.ones += 1;
+            if self.ones > 6 {
+                // Seven ones abort the frame.
+                self.clear();
+            } else if self.in_frame {
+                self.bits.push(true);
+                self.soft.push(soft);
+            }
+            return None;
+        }
+        let ones = std::mem::replace(&mut self.ones, 0);
+        match ones {
+            6 => {
+                // A flag ends one frame and may start the next. Its first
+                // seven bits were collected as data.
+                let frame = if self.in_frame {
+                    let len = self.bits.len().saturating_sub(7);
+                    self.bits.truncate(len);
+                    self.soft.truncate(len);
+                    self.check()
+                } else {
+                    None
+                };
+                self.in_frame = true;
+                self.bits.clear();
+                self.soft.clear();
+                frame
+            }
+            // A zero stuffed after five ones.
+            5 => None,
+            _ => {
+                if self.in_frame {
+                    self.bits.push(false);
+                    self.soft.push(soft);
+                    // Allow for the seven bits of the closing flag
+                    // collected before it is recognised.
+                    if self.bits.len() > self.max_bytes * 8 + 7 {
+                        self.clear();
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.in_frame = false;
+        self.bits.clear();
+        self.soft.clear();
+    }
+
+    fn check(&mut self) -> Option<(Vec<u8>, Vec<i8>)> {
+        if !self.bits.len().is_multiple_of(8) || self.bits.len() < self.min_bytes * 8 {
+            return None;
+        }
+        // Bytes are sent least significant bit first.
+        let mut bytes: Vec<u8> = self
+            .bits
+            .chunks_exact(8)
+            .map(|c| c.iter().rev().fold(0, |acc, &b| (acc << 1) | b as u8))
+            .collect();
+        let fcs = u16::from_le_bytes([bytes[bytes.len() - 2], bytes[bytes.len() - 1]]);
+        let mut soft: Vec<i8> = self
+            .soft
+            .chunks_exact(8)
+            .flat_map(|c| c.iter().rev().copied())
+            .collect();
+        if crc16(&bytes[..bytes.len() - 2]) != fcs {
+            if self.keep_rejected {
+                self.rejected = Some((bytes, soft));
+            }
+            return None;
+        }
+        bytes.truncate(bytes.len() - 2);
+        soft.truncate(bytes.len() * 8);
+        Some((bytes, soft))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAG: [bool; 8] = [false, true, true, true, true, true, true, false];
+
+    /// The bits of a frame with its checksum, least significant bit first
+    /// and with a zero stuffed after every five ones, between flags.
+    fn frame(data: &[u8]) -> Vec<bool> {
+        let mut bytes = data.to_vec();
+        bytes.extend_from_slice(&crc16(data).to_le_bytes());
+        let mut bits = FLAG.to_vec();
+        let mut ones = 0;
+        for byte in bytes {
+            for i in 0..8 {
+                let bit = byte >> i & 1 == 1;
+                bits.push(bit);
+                ones = if bit { ones + 1 } else { 0 };
+                if ones == 5 {
+                    bits.push(false);
+                    ones = 0;
+                }
+            }
+        }
+        bits.extend_from_slice(&FLAG);
+        bits
+    }
+
+    fn deframe(bits: &[bool]) -> Vec<Vec<u8>> {
+        let mut deframer = Deframer::new(3, 64);
+        bits.iter()
+            .filter_map(|&b| deframer.push(b, if b { 127 } else { -127 }))
+            .map(|(data, _)| data)
+            .collect()
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x906e);
+        assert_eq!(crc16(&[]), 0x0000);
+    }
+
+    #[test]
+    fn destuffs_frames() {
+        // Runs of ones long enough to need stuffing, and one that ends
+        // a byte and carries into the next.
+        let data = [0xff, 0x7e, 0x1f, 0xf8, 0x00, 0xfe];
+        let bits = frame(&data);
+        assert!(bits.len() > (data.len() + 2) * 8 + 16);
+        assert_eq!(deframe(&bits), [data.to_vec()]);
+    }
+
+    #[test]
+    fn shares_flags_between_frames() {
+        let mut bits = frame(b"first");
+        bits.extend_from_slice(&frame(b"second")[8..]);
+        assert_eq!(deframe(&bits), [b"first".to_vec(), b"second".to_vec()]);
+    }
+
+    #[test]
+    fn rejects_bad_checksums_and_lengths() {
+        let mut bits = frame(b"frame");
+        bits[12] = !bits[12];
+        assert!(deframe(&bits).is_empty());
+        // The limits count the two checksum bytes.
+        assert_eq!(deframe(&frame(&[0x42])), [vec![0x42]]);
+        assert!(deframe(&frame(&[])).is_empty());
+        assert_eq!(deframe(&frame(&[0x55; 62])), [vec![0x55; 62]]);
+        assert!(deframe(&frame(&[0x55; 63])).is_empty());
+    }
+
+    #[test]
+    fn keeps_rejected_frames_when_asked() {
+        let mut bits = frame(b"frame");
+        bits[12] = !bits[12];
+        let mut deframer = Deframer::new(3, 64);
+        deframer.keep_rejected();
+        for &b in &bits {
+            assert!(deframer.push(b, 0).is_none());
+        }
+        let (bytes, soft) = deframer.take_rejected().unwrap();
+        assert_eq!(bytes.len(), 7);
+        assert_eq!(bytes[0], b'f' ^ 0x10);
+        assert_eq!(soft.len(), 56);
+        assert!(deframer.take_rejected().is_none());
+    }
+
+    #[test]
+    fn seven_ones_abort() {
+        let lost = frame(b"lost");
+        let mut bits = lost[..lost.len() - 20].to_vec();
+        bits.extend_from_slice(&[true; 7]);
+        bits.extend_from_slice(&frame(b"kept"));
+        assert_eq!(deframe(&bits), [b"kept".to_vec()]);
+    }
+
+    #[test]
+    fn keeps_soft_decisions_in_data_bit_order() {
+        let data = [0xf8, 0x01, 0x3c];
+        let mut deframer = Deframer::new(3, 64);
+        // Make each bit's margin its position in the frame, so the order
+        // they come back in shows.
+        let mut sent = 0i8;
+        let mut out = Vec::new();
+        for b in frame(&data) {
+            let soft = if b { 10 + sent % 100 } else { -10 - sent % 100 };
+            sent = sent.wrapping_add(1);
+            out.extend(deframer.push(b, soft));
+        }
+        let (bytes, soft) = out.pop().unwrap();
+        assert_eq!(bytes, data);
+        assert_eq!(soft.len(), 24);
+        let hard: Vec<u8> = soft
+            .chunks(8)
+            .map(|c| c.iter().fold(0, |acc, &s| (acc << 1) | (s > 0) as u8))
+            .collect();
+        assert_eq!(hard, data);
+        // The first byte's least significant bit follows the flag.
+        assert_eq!(soft[7], -18);
+        assert_eq!(soft[0], 25);
+    }
+}
@@ -0,0 +1,91 @@
+use super::{IsmDecoder, Measurement, Reading};
+use crate::pulse::{Bits, Modulation, Pulse, PulseTrain, Slicer};
+
+/// Remotes, doorbells and alarm sensors built on the EV1527 encoder and
+/// its many clones.
+///
+/// Each message is 24 bits: a 20-bit id burnt in at the factory and a
+/// 4-bit button code. A 1 is a pulse of three time units and a gap of one,
+/// a 0 the other way round, and every message ends with a one-unit sync
+/// pulse and a 31-unit gap. The time unit is set by a resistor and is
+/// usually 250 to 500 µs. Messages are repeated for as long as the button
+/// is held, and each repeat is decoded separately.
+pub struct Ev1527 {
+    unit: f64,
+}
+
+impl Ev1527 {
+    /// Create a decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The nominal time unit in microseconds. Units from about
+    ///   two thirds to twice this decode.
+    ///
+    /// # Returns
+    ///
+    /// A new `Ev1527`.
+    pub fn new(unit: f64) -> Self {
+        Ev1527 { unit }
+    }
+
+    /// Gaps longer than this end a message.
+    fn row_gap(&self) -> f64 {
+        9.0 * self.unit
+    }
+}
+
+impl Default for Ev1527 {
+    /// A decoder for the common 350 µs time unit.
+    fn default() -> Self {
+        Ev1527::new(350.0)
+    }
+}
+
+impl IsmDecoder for Ev1527 {
+    fn name(&self) -> &str {
+        "EV1527"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Ook
+    }
+
+    fn slicer(&self) -> Slicer {
+        Slicer::pwm(self.unit, 3.0 * self.unit).with_row_gap(self.row_gap())
+    }
+
+    fn decode(&self, rows: &[Bits], train: &PulseTrain) -> Vec<Reading> {
+        // Rows are split at the same gaps as the pulses here, so each row
+        // lines up with its pulses. The sync pulse slices as a trailing 1,
+        // if it was caught.
+        let groups = train.pulses.split_inclusive(|p| p.gap > self.row_gap());
+        let Some(row) = rows
+            .iter()
+            .zip(groups)
+            .find(|(row, pulses)| (row.len() == 24 || row.len() == 25) && regular(pulses))
+            .map(|(row, _)| row)
+        else {
+            return Vec::new();
+        };
+        // The slicer reads short pulses as 1.
+        let bits = row.inverted();
+        let id = bits.read(0, 20).unwrap_or(0);
+        let button = bits.read(20, 4).unwrap_or(0) as u8;
+        if id == 0 || id == 0xfffff {
+            return Vec::new();
+        }
+
+        let mut reading = Reading::new("EV1527", id);
+        reading.measurements.push(Measurement::Button(button));
+        vec![reading]
+    }
+}
+
+/// Check that every bit takes the same time, which random noise rarely
+/// does.
+fn regular(pulses: &[Pulse]) -> bool {
+    let periods: Vec<f64> = pulses[..23].iter().map(|p| p.width + p.gap).collect();
+    let mean = periods.iter().sum::<f64>() / periods.len() as f64;
+    periods.iter().all(|&p| (p - mean).abs() < 0.25 * mean)
+}
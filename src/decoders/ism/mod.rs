@@ -0,0 +1,330 @@
+//! Decoders for 315/433/868 MHz ISM-band sensors and remotes.
+//!
+//! An `IsmRegistry` holds protocol decoders and runs every burst from a
+//! `PulseDetector` past the ones expecting its modulation, each slicing the
+//! pulses its own way. Decoded messages come out as `Reading`s, which
+//! convert to JSON with the field names rtl_433 uses, so existing tooling
+//! can consume them.
+//!
+//! The crate ships a few reference decoders; implement `IsmDecoder` to
+//! add others.
+//!
+//! ```no_run
+//! use radion::decoders::ism::IsmRegistry;
+//! use radion::pulse::{Modulation, PulseDetector};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::Device;
+//!
+//! let device = Device::new(0)?;
+//! device.set_center_freq(433_920_000)?;
+//! device.set_sample_rate(250_000)?;
+//! device.reset_buffer()?;
+//!
+//! let registry = IsmRegistry::with_defaults();
+//! let mut detector = PulseDetector::new(250_000.0, Modulation::Ook);
+//! loop {
+//!     let raw = device.read_sync(131_072)?;
+//!     for train in detector.process(&to_complex_f32(&raw, Scaling::Normalized)) {
+//!         for reading in registry.decode(&train) {
+//!             println!("{}", reading.to_json());
+//!         }
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod ev1527;
+mod nexus;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+mod prologue;
+
+pub use ev1527::Ev1527;
+pub use nexus::NexusTh;
+pub use prologue::PrologueTh;
+
+use serde_json::{Map, Value};
+
+use super::quality::soft_bit;
+use super::Quality;
+use crate::pulse::{Bits, Modulation, PulseTrain, Slicer};
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
+
+/// One quantity reported by a device.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Measurement {
+    /// Temperature in °C.
+    Temperature(f32),
+    /// Relative humidity in percent.
+    Humidity(u8),
+    /// Pressure in kPa, e.g. from a tyre pressure sensor.
+    Pressure(f32),
+    /// Average wind speed in km/h.
+    WindSpeed(f32),
+    /// Wind direction in degrees from north.
+    WindDirection(u16),
+    /// Accumulated rain in mm.
+    Rain(f32),
+    /// The button or command code of a remote.
+    Button(u8),
+    /// Anything else, under a name of the decoder's choosing.
+    Other(String, f64),
+}
+
+impl Measurement {
+    /// Get the JSON field name, as rtl_433 names it.
+    pub fn key(&self) -> &str {
+        match self {
+            Measurement::Temperature(_) => "temperature_C",
+            Measurement::Humidity(_) => "humidity",
+            Measurement::Pressure(_) => "pressure_kPa",
+            Measurement::WindSpeed(_) => "wind_avg_km_h",
+            Measurement::WindDirection(_) => "wind_dir_deg",
+            Measurement::Rain(_) => "rain_mm",
+            Measurement::Button(_) => "button",
+            Measurement::Other(name, _) => name,
+        }
+    }
+
+    /// Get the quantity as a number, with a button code as its value.
+    pub fn value(&self) -> f64 {
+        match *self {
+            Measurement::Temperature(v)
+            | Measurement::Pressure(v)
+            | Measurement::WindSpeed(v)
+            | Measurement::Rain(v) => v as f64,
+            Measurement::Humidity(v) | Measurement::Button(v) => v as f64,
+            Measurement::WindDirection(v) => v as f64,
+            Measurement::Other(_, v) => v,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match *self {
+            Measurement::Temperature(v)
+            | Measurement::Pressure(v)
+            | Measurement::WindSpeed(v)
+            | Measurement::Rain(v) => {
+                // Round away the float noise of fixed-point conversions.
+                Value::from((v as f64 * 100.0).round() / 100.0)
+            }
+            Measurement::Humidity(v) | Measurement::Button(v) => Value::from(v),
+            Measurement::WindDirection(v) => Value::from(v),
+            Measurement::Other(_, v) => Value::from(v),
+        }
+    }
+}
+
+/// A decoded message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reading {
+    /// The device model, e.g. `Nexus-TH`.
+    pub model: String,
+    /// The device's identifier. Many sensors pick a new random one when
+    /// their batteries are changed.
+    pub id: u64,
+    /// The channel selected on the device, if it has a switch for one.
+    pub channel: Option<u8>,
+    /// Whether the battery is fine, if the device reports it.
+    pub battery_ok: Option<bool>,
+    /// The reported quantities.
+    pub measurements: Vec<Measurement>,
+    /// The SNR of the burst in dB, filled in by the registry.
+    pub snr: f32,
+    /// The index of the burst's first sample, filled in by the registry.
+    pub start: u64,
+    /// How cleanly the burst's timings were sliced, filled in by the
+    /// registry. Few devices send a checksum, so `crc_ok` is left unset.
+    pub quality: Option<Quality>,
+}
+
+impl Reading {
+    /// Create a reading without channel, battery state or measurements.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The device model.
+    /// * `id` - The device's identifier.
+    ///
+    /// # Returns
+    ///
+    /// A new `Reading`.
+    pub fn new(model: &str, id: u64) -> Self {
+        Reading {
+            model: model.to_string(),
+            id,
+            channel: None,
+            battery_ok: None,
+            measurements: Vec::new(),
+            snr: 0.0,
+            start: 0,
+            quality: None,
+        }
+    }
+
+    /// Get a measurement by its JSON field name.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The field name, e.g. `temperature_C`.
+    ///
+    /// # Returns
+    ///
+    /// The first measurement with that name, or `None`.
+    pub fn get(&self, key: &str) -> Option<&Measurement> {
+        self.measurements.iter().find(|m| m.key() == key)
+    }
+
+    /// Convert the reading to a JSON object with rtl_433's field names.
+    pub fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        map.insert("model".into(), Value::from(self.model.as_str()));
+        map.insert("id".into(), Value::from(self.id));
+        if let Some(channel) = self.channel {
+            map.insert("channel".into(), Value::from(channel));
+        }
+        if let Some(ok) = self.battery_ok {
+            map.insert("battery_ok".into(), Value::from(ok as u8));
+        }
+        for m in &self.measurements {
+            map.insert(m.key().into(), m.to_json());
+        }
+        map.insert(
+            "snr".into(),
+            Value::from((self.snr as f64 * 10.0).round() / 10.0),
+        );
+        Value::Object(map)
+    }
+}
+
+/// A protocol decoder.
+pub trait IsmDecoder: Send + Sync {
+    /// Get the decoder's name, for listing and filtering.
+    fn name(&self) -> &str;
+
+    /// Get the modulation the device uses.
+    fn modulation(&self) -> Modulation;
+
+    /// Get the slicer matching the device's timing and line coding.
+    fn slicer(&self) -> Slicer;
+
+    /// Decode the rows sliced from one burst.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The rows of bits.
+    /// * `train` - The burst they came from, for decoders that need the
+    ///   raw pulses.
+    ///
+    /// # Returns
+    ///
+    /// The messages found, usually none or one. Decoders should check the
+    /// message thoroughly, since every burst of the right modulation is
+    /// offered to every decoder.
+    fn decode(&self, rows: &[Bits], train: &PulseTrain) -> Vec<Reading>;
+}
+
+/// The set of decoders to try on each burst.
+#[derive(Default)]
+pub struct IsmRegistry {
+    decoders: Vec<Box<dyn IsmDecoder>>,
+}
+
+impl IsmRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        IsmRegistry::default()
+    }
+
+    /// Create a registry with every decoder the crate ships.
+    pub fn with_defaults() -> Self {
+        let mut registry = IsmRegistry::new();
+        registry.register(NexusTh);
+        registry.register(PrologueTh);
+        registry.register(Ev1527::default());
+        registry
+    }
+
+    /// Add a decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder` - The decoder, tried after those already registered.
+    pub fn register<D: IsmDecoder + 'static>(&mut self, decoder: D) {
+        self.decoders.push(Box::new(decoder));
+    }
+
+    /// Remove decoders by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the decoders to remove.
+    ///
+    /// # Returns
+    ///
+    /// The number of decoders removed.
+    pub fn unregister(&mut self, name: &str) -> usize {
+        let before = self.decoders.len();
+        self.decoders.retain(|d| d.name() != name);
+        before - self.decoders.len()
+    }
+
+    /// Get the names of the registered decoders, in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.decoders.iter().map(|d| d.name()).collect()
+    }
+
+    /// Decode a burst with every decoder for its modulation.
+    ///
+    /// # Arguments
+    ///
+    /// * `train` - A burst from `PulseDetector`.
+    ///
+    /// # Returns
+    ///
+    /// The messages found by all decoders, in registration order.
+    pub fn decode(&self, train: &PulseTrain) -> Vec<Reading> {
+        let mut readings = Vec::new();
+        for decoder in &self.decoders {
+            if decoder.modulation() != train.modulation {
+                continue;
+            }
+            let slicer = decoder.slicer();
+            let rows = slicer.slice(train);
+            let decoded = decoder.decode(&rows, train);
+            if decoded.is_empty() {
+                continue;
+            }
+            let soft: Vec<i8> = slicer.margins(train).into_iter().map(soft_bit).collect();
+            let quality = Quality::from_soft(&soft, false, 0);
+            for mut reading in decoded {
+                reading.snr = train.snr();
+                reading.start = train.start;
+                reading.quality = Some(quality);
+                readings.push(reading);
+            }
+        }
+        readings
+    }
+}
+
+/// Find a row sent several times, as most sensors repeat their message
+/// within a burst to get through interference.
+///
+/// # Arguments
+///
+/// * `rows` - The rows of a burst.
+/// * `min_repeats` - How many identical rows are needed.
+/// * `len` - The message length in bits.
+///
+/// # Returns
+///
+/// The first row of that length repeated often enough, or `None`.
+pub fn find_repeated(rows: &[Bits], min_repeats: usize, len: usize) -> Option<&Bits> {
+    rows.iter()
+        .filter(|row| row.len() == len)
+        .find(|row| rows.iter().filter(|other| other == row).count() >= min_repeats)
+}
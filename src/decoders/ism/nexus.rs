@@ -0,0 +1,54 @@
+use super::{find_repeated, IsmDecoder, Measurement, Reading};
+use crate::pulse::{Bits, Modulation, PulseTrain, Slicer};
+
+/// Nexus temperature and humidity sensors, also sold under many other
+/// brands with weather stations.
+///
+/// The sensor sends 36 bits 12 times, each as a 500 µs pulse followed by a
+/// 1000 µs gap for a 0 or a 2000 µs gap for a 1, with a 4000 µs gap between
+/// repeats. The layout is `IIIIIIII BxCCTTTT TTTTTTTT 1111HHHH HHHH`: an
+/// 8-bit id, the battery flag, the channel, a 12-bit signed temperature in
+/// 0.1 °C, a constant nibble and the humidity. Temperature-only models send
+/// a humidity of 0.
+pub struct NexusTh;
+
+impl IsmDecoder for NexusTh {
+    fn name(&self) -> &str {
+        "Nexus-TH"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Ook
+    }
+
+    fn slicer(&self) -> Slicer {
+        Slicer::ppm(1000.0, 2000.0).with_row_gap(3000.0)
+    }
+
+    fn decode(&self, rows: &[Bits], _train: &PulseTrain) -> Vec<Reading> {
+        let Some(row) = find_repeated(rows, 3, 36) else {
+            return Vec::new();
+        };
+        let b = row.to_bytes();
+        if b[3] & 0xf0 != 0xf0 {
+            return Vec::new();
+        }
+        let temp = ((((b[1] & 0x0f) as i16) << 12) | ((b[2] as i16) << 4)) >> 4;
+        let humidity = ((b[3] & 0x0f) << 4) | (b[4] >> 4);
+        if humidity > 100 {
+            return Vec::new();
+        }
+
+        let model = if humidity == 0 { "Nexus-T" } else { "Nexus-TH" };
+        let mut reading = Reading::new(model, b[0] as u64);
+        reading.channel = Some(((b[1] >> 4) & 0x03) + 1);
+        reading.battery_ok = Some(b[1] & 0x80 != 0);
+        reading
+            .measurements
+            .push(Measurement::Temperature(temp as f32 * 0.1));
+        if humidity != 0 {
+            reading.measurements.push(Measurement::Humidity(humidity));
+        }
+        vec![reading]
+    }
+}
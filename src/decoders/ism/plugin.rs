@@ -0,0 +1,593 @@
+//! Decoders loaded at runtime from shared libraries.
+//!
+//! A plugin is a shared library exporting one C function, named by
+//! `ENTRY_POINT`, that describes the decoders it contains. The interface
+//! uses only C types, so plugins can be written in any language and built
+//! with any compiler version, and it is versioned by `ABI_VERSION`: a
+//! plugin built for another version is refused rather than misread. In C:
+//!
+//! ```c
+//! struct radion_pulse { double width, gap; };              /* µs */
+//! struct radion_burst {
+//!     uint32_t modulation;                                  /* 0 OOK, 1 FSK */
+//!     const struct radion_pulse *pulses; size_t pulse_count;
+//!     double sample_rate; float level, noise; uint64_t start;
+//! };
+//! struct radion_row { const uint8_t *bits; size_t len; };   /* one 0/1 byte per bit */
+//! struct radion_measurement { const char *key; double value; };
+//! struct radion_reading {
+//!     const char *model; uint64_t id;
+//!     int32_t channel;                                      /* -1 if none */
+//!     int32_t battery_ok;                                   /* -1 unknown, 0 low, 1 ok */
+//!     const struct radion_measurement *measurements; size_t measurement_count;
+//! };
+//! typedef void (*radion_emit)(void *ctx, const struct radion_reading *reading);
+//! struct radion_decoder {
+//!     uint32_t abi_version;                                 /* 1 */
+//!     const char *name;
+//!     uint32_t modulation;
+//!     uint32_t coding;                                      /* 0 PCM, 1 PWM, 2 PPM, 3 Manchester */
+//!     double short_us, long_us, row_gap_us;                 /* row_gap_us <= 0 for the default */
+//!     const void *state;
+//!     void (*decode)(const void *state, const struct radion_row *rows, size_t row_count,
+//!                    const struct radion_burst *burst, radion_emit emit, void *ctx);
+//! };
+//! const struct radion_decoder *radion_ism_plugin_v1(size_t *count);
+//! ```
+//!
+//! The entry point returns an array of `*count` decoders that stays valid
+//! for as long as the library is loaded. `decode` is called with the rows
+//! sliced by the decoder's own slicer settings and calls `emit` once per
+//! message found; everything it passes is copied before `emit` returns.
+//! `decode` may be called from several threads at once. Measurement keys
+//! use rtl_433's names, so `temperature_C` becomes
+//! `Measurement::Temperature`; unknown keys become `Measurement::Other`.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+use std::sync::Arc;
+
+use libloading::Library;
+
+use super::{IsmDecoder, IsmRegistry, Measurement, Reading};
+use crate::error::{Error, Result};
+use crate::pulse::{Bits, Modulation, PulseTrain, Slicer};
+
+/// Version of the plugin interface described in the module documentation.
+pub const ABI_VERSION: u32 = 1;
+
+/// Name of the function every plugin exports.
+pub const ENTRY_POINT: &str = "radion_ism_plugin_v1";
+
+/// `RawBurst::modulation` and `RawDecoder::modulation` for OOK.
+pub const MODULATION_OOK: u32 = 0;
+/// `RawBurst::modulation` and `RawDecoder::modulation` for FSK.
+pub const MODULATION_FSK: u32 = 1;
+
+/// `RawDecoder::coding` for PCM.
+pub const CODING_PCM: u32 = 0;
+/// `RawDecoder::coding` for PWM.
+pub const CODING_PWM: u32 = 1;
+/// `RawDecoder::coding` for PPM.
+pub const CODING_PPM: u32 = 2;
+/// `RawDecoder::coding` for Manchester.
+pub const CODING_MANCHESTER: u32 = 3;
+
+/// One pulse and the gap after it, as `struct radion_pulse`.
+#[repr(C)]
+pub struct RawPulse {
+    /// The pulse width in microseconds.
+    pub width: f64,
+    /// The gap after the pulse in microseconds.
+    pub gap: f64,
+}
+
+/// A burst, as `struct radion_burst`.
+#[repr(C)]
+pub struct RawBurst {
+    /// `MODULATION_OOK` or `MODULATION_FSK`.
+    pub modulation: u32,
+    /// The pulses, in order.
+    pub pulses: *const RawPulse,
+    /// The number of pulses.
+    pub pulse_count: usize,
+    /// The sample rate the burst was detected at, in Hz.
+    pub sample_rate: f64,
+    /// The carrier level in dBFS.
+    pub level: f32,
+    /// The noise floor in dBFS.
+    pub noise: f32,
+    /// The index of the burst's first sample.
+    pub start: u64,
+}
+
+/// A row of bits, as `struct radion_row`.
+#[repr(C)]
+pub struct RawRow {
+    /// One byte per bit, 0 or 1.
+    pub bits: *const u8,
+    /// The number of bits.
+    pub len: usize,
+}
+
+/// One quantity, as `struct radion_measurement`.
+#[repr(C)]
+pub struct RawMeasurement {
+    /// The rtl_433 field name, NUL-terminated.
+    pub key: *const c_char,
+    /// The value.
+    pub value: f64,
+}
+
+/// A decoded message, as `struct radion_reading`.
+#[repr(C)]
+pub struct RawReading {
+    /// The device model, NUL-terminated.
+    pub model: *const c_char,
+    /// The device's identifier.
+    pub id: u64,
+    /// The channel, or -1 if the device has none.
+    pub channel: i32,
+    /// 1 if the battery is fine, 0 if low, -1 if not reported.
+    pub battery_ok: i32,
+    /// The measurements.
+    pub measurements: *const RawMeasurement,
+    /// The number of measurements.
+    pub measurement_count: usize,
+}
+
+/// Callback a plugin calls with each message, as `radion_emit`.
+pub type EmitFn = unsafe extern "C" fn(ctx: *mut c_void, reading: *const RawReading);
+
+/// A plugin's decode function.
+pub type DecodeFn = unsafe extern "C" fn(
+    state: *const c_void,
+    rows: *const RawRow,
+    row_count: usize,
+    burst: *const RawBurst,
+    emit: EmitFn,
+    ctx: *mut c_void,
+);
+
+/// A decoder description, as `struct radion_decoder`.
+#[repr(C)]
+pub struct RawDecoder {
+    /// Must be `ABI_VERSION`.
+    pub abi_version: u32,
+    /// The decoder's name, NUL-terminated.
+    pub name: *const c_char,
+    /// `MODULATION_OOK` or `MODULATION_FSK`.
+    pub modulation: u32,
+    /// One of the `CODING_*` constants.
+    pub coding: u32,
+    /// The short timing in microseconds, see `Slicer`. For PCM the bit
+    /// period, for Manchester the half-bit period.
+    pub short: f64,
+    /// The long timing in microseconds, for PWM and PPM.
+    pub long: f64,
+    /// The row gap in microseconds, or 0 for the slicer's default.
+    pub row_gap: f64,
+    /// Passed back to `decode` untouched.
+    pub state: *const c_void,
+    /// The decode function.
+    pub decode: Option<DecodeFn>,
+}
+
+/// Signature of the function named by `ENTRY_POINT`.
+pub type EntryFn = unsafe extern "C" fn(count: *mut usize) -> *const RawDecoder;
+
+/// An `IsmDecoder` implemented by a plugin.
+pub struct PluginDecoder {
+    name: String,
+    modulation: Modulation,
+    slicer: Slicer,
+    state: *const c_void,
+    decode: DecodeFn,
+    /// Keeps the code behind `decode` loaded.
+    _library: Option<Arc<Library>>,
+}
+
+// Plugins promise that `decode` is thread-safe and `state` is shared
+// read-only.
+unsafe impl Send for PluginDecoder {}
+unsafe impl Sync for PluginDecoder {}
+
+impl PluginDecoder {
+    /// Wrap a decoder description, e.g. one linked in statically.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The description.
+    ///
+    /// # Returns
+    ///
+    /// The decoder, or `Error::Plugin` if the description is for another
+    /// ABI version or malformed.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must point to a valid `RawDecoder` whose name, state and
+    /// decode function stay valid for as long as the decoder is used.
+    pub unsafe fn from_raw(raw: *const RawDecoder) -> Result<Self> {
+        Self::wrap(raw, None)
+    }
+
+    unsafe fn wrap(raw: *const RawDecoder, library: Option<Arc<Library>>) -> Result<Self> {
+        let raw = raw
+            .as_ref()
+            .ok_or_else(|| Error::Plugin("null decoder".into()))?;
+        if raw.abi_version != ABI_VERSION {
+            return Err(Error::Plugin(format!(
+                "ABI version {}, expected {}",
+                raw.abi_version, ABI_VERSION
+            )));
+        }
+        if raw.name.is_null() {
+            return Err(Error::Plugin("decoder without a name".into()));
+        }
+        let name = CStr::from_ptr(raw.name).to_string_lossy().into_owned();
+        let invalid = |what: &str| Error::Plugin(format!("{}: invalid {}", name, what));
+        let modulation = match raw.modulation {
+            MODULATION_OOK => Modulation::Ook,
+            MODULATION_FSK => Modulation::Fsk,
+            _ => return Err(invalid("modulation")),
+        };
+        // Only PWM and PPM have a long timing.
+        let long_used = matches!(raw.coding, CODING_PWM | CODING_PPM);
+        let timing =
+            raw.short > 0.0 && (!long_used || raw.long >= raw.short) && !raw.row_gap.is_nan();
+        if !timing {
+            return Err(invalid("timing"));
+        }
+        let slicer = match raw.coding {
+            CODING_PCM => Slicer::pcm(raw.short),
+            CODING_PWM => Slicer::pwm(raw.short, raw.long),
+            CODING_PPM => Slicer::ppm(raw.short, raw.long),
+            CODING_MANCHESTER => Slicer::manchester(raw.short),
+            _ => return Err(invalid("coding")),
+        };
+        let slicer = if raw.row_gap > 0.0 {
+            slicer.with_row_gap(raw.row_gap)
+        } else {
+            slicer
+        };
+        let decode = raw.decode.ok_or_else(|| invalid("decode function"))?;
+        Ok(PluginDecoder {
+            name,
+            modulation,
+            slicer,
+            state: raw.state,
+            decode,
+            _library: library,
+        })
+    }
+}
+
+impl IsmDecoder for PluginDecoder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn modulation(&self) -> Modulation {
+        self.modulation
+    }
+
+    fn slicer(&self) -> Slicer {
+        self.slicer
+    }
+
+    fn decode(&self, rows: &[Bits], train: &PulseTrain) -> Vec<Reading> {
+        let bits: Vec<Vec<u8>> = rows
+            .iter()
+            .map(|row| row.as_slice().iter().map(|&b| b as u8).collect())
+            .collect();
+        let raw_rows: Vec<RawRow> = bits
+            .iter()
+            .map(|row| RawRow {
+                bits: row.as_ptr(),
+                len: row.len(),
+            })
+            .collect();
+        let pulses: Vec<RawPulse> = train
+            .pulses
+            .iter()
+            .map(|p| RawPulse {
+                width: p.width,
+                gap: p.gap,
+            })
+            .collect();
+        let burst = RawBurst {
+            modulation: match train.modulation {
+                Modulation::Ook => MODULATION_OOK,
+                Modulation::Fsk => MODULATION_FSK,
+            },
+            pulses: pulses.as_ptr(),
+            pulse_count: pulses.len(),
+            sample_rate: train.sample_rate,
+            level: train.level,
+            noise: train.noise,
+            start: train.start,
+        };
+        let mut readings: Vec<Reading> = Vec::new();
+        // SAFETY: everything passed lives until the call returns, and
+        // `collect` only runs while it does.
+        unsafe {
+            (self.decode)(
+                self.state,
+                raw_rows.as_ptr(),
+                raw_rows.len(),
+                &burst,
+                collect,
+                &mut readings as *mut Vec<Reading> as *mut c_void,
+            );
+        }
+        readings
+    }
+}
+
+/// The `EmitFn` handed to plugins, copying each message into the
+/// `Vec<Reading>` behind `ctx`.
+unsafe extern "C" fn collect(ctx: *mut c_void, reading: *const RawReading) {
+    let (Some(readings), Some(raw)) = ((ctx as *mut Vec<Reading>).as_mut(), reading.as_ref())
+    else {
+        return;
+    };
+    if raw.model.is_null() {
+        return;
+    }
+    let model = CStr::from_ptr(raw.model).to_string_lossy();
+    let mut reading = Reading::new(&model, raw.id);
+    reading.channel = u8::try_from(raw.channel).ok();
+    reading.battery_ok = match raw.battery_ok {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    };
+    if !raw.measurements.is_null() {
+        let measurements = std::slice::from_raw_parts(raw.measurements, raw.measurement_count);
+        reading.measurements = measurements
+            .iter()
+            .filter(|m| !m.key.is_null())
+            .map(|m| measurement(&CStr::from_ptr(m.key).to_string_lossy(), m.value))
+            .collect();
+    }
+    readings.push(reading);
+}
+
+/// Turn an rtl_433 field name and value into a `Measurement`.
+fn measurement(key: &str, value: f64) -> Measurement {
+    match key {
+        "temperature_C" => Measurement::Temperature(value as f32),
+        "humidity" => Measurement::Humidity(value as u8),
+        "pressure_kPa" => Measurement::Pressure(value as f32),
+        "wind_avg_km_h" => Measurement::WindSpeed(value as f32),
+        "wind_dir_deg" => Measurement::WindDirection(value as u16),
+        "rain_mm" => Measurement::Rain(value as f32),
+        "button" => Measurement::Button(value as u8),
+        _ => Measurement::Other(key.to_string(), value),
+    }
+}
+
+/// Load the decoders of a plugin.
+///
+/// # Arguments
+///
+/// * `path` - The shared library.
+///
+/// # Returns
+///
+/// The plugin's decoders, or `Error::Plugin` if the library could not be
+/// loaded, has no entry point or describes a decoder this version cannot
+/// use.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<PluginDecoder>> {
+    let path = path.as_ref();
+    let failed = |e: libloading::Error| Error::Plugin(format!("{}: {}", path.display(), e));
+    // SAFETY: loading runs the library's initialisers, which a plugin is
+    // trusted to have written sensibly, like any other dependency.
+    let library = Arc::new(unsafe { Library::new(path) }.map_err(failed)?);
+    let mut count = 0;
+    let raw = unsafe {
+        let entry = library
+            .get::<EntryFn>(ENTRY_POINT.as_bytes())
+            .map_err(failed)?;
+        entry(&mut count)
+    };
+    if raw.is_null() {
+        return Ok(Vec::new());
+    }
+    (0..count)
+        // SAFETY: the entry point returned `count` descriptions that stay
+        // valid while the library is loaded, which each decoder ensures.
+        .map(|i| unsafe { PluginDecoder::wrap(raw.add(i), Some(library.clone())) })
+        .collect()
+}
+
+impl IsmRegistry {
+    /// Load a plugin and register its decoders.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The shared library.
+    ///
+    /// # Returns
+    ///
+    /// The names of the decoders registered, or `Error::Plugin` if the
+    /// plugin could not be used, in which case none are.
+    pub fn load_plugin(&mut self, path: impl AsRef<Path>) -> Result<Vec<String>> {
+        let decoders = load(path)?;
+        let names = decoders.iter().map(|d| d.name.clone()).collect();
+        for decoder in decoders {
+            self.register(decoder);
+        }
+        Ok(names)
+    }
+
+    /// Load every plugin in a directory, in file name order.
+    ///
+    /// Files without the platform's shared library extension (`so`,
+    /// `dylib` or `dll`) are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The plugin directory.
+    ///
+    /// # Returns
+    ///
+    /// The names of the decoders registered, or the `Error` of the first
+    /// plugin that could not be used; the plugins before it stay
+    /// registered.
+    pub fn load_plugins(&mut self, dir: impl AsRef<Path>) -> Result<Vec<String>> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|e| e == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        let mut names = Vec::new();
+        for path in paths {
+            names.extend(self.load_plugin(path)?);
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulse::Pulse;
+
+    /// A plugin decoder reporting how many rows and pulses it was given.
+    unsafe extern "C" fn count_rows(
+        state: *const c_void,
+        rows: *const RawRow,
+        row_count: usize,
+        burst: *const RawBurst,
+        emit: EmitFn,
+        ctx: *mut c_void,
+    ) {
+        let rows = std::slice::from_raw_parts(rows, row_count);
+        let burst = &*burst;
+        let first = std::slice::from_raw_parts(rows[0].bits, rows[0].len);
+        let measurements = [
+            RawMeasurement {
+                key: c"temperature_C".as_ptr(),
+                value: burst.pulse_count as f64,
+            },
+            RawMeasurement {
+                key: c"ones".as_ptr(),
+                value: first.iter().map(|&b| b as f64).sum(),
+            },
+        ];
+        let reading = RawReading {
+            model: state as *const c_char,
+            id: row_count as u64,
+            channel: 2,
+            battery_ok: 1,
+            measurements: measurements.as_ptr(),
+            measurement_count: measurements.len(),
+        };
+        emit(ctx, &reading);
+    }
+
+    fn raw(coding: u32) -> RawDecoder {
+        RawDecoder {
+            abi_version: ABI_VERSION,
+            name: c"Counter".as_ptr(),
+            modulation: MODULATION_OOK,
+            coding,
+            short: 100.0,
+            long: 300.0,
+            row_gap: 0.0,
+            state: c"Test-Plugin".as_ptr() as *const c_void,
+            decode: Some(count_rows),
+        }
+    }
+
+    #[test]
+    fn decodes_through_the_c_interface() {
+        let raw = raw(CODING_PWM);
+        let mut registry = IsmRegistry::new();
+        registry.register(unsafe { PluginDecoder::from_raw(&raw) }.unwrap());
+        assert_eq!(registry.names(), ["Counter"]);
+
+        let pulse = |width| Pulse { width, gap: 200.0 };
+        let mut pulses = vec![pulse(100.0), pulse(300.0), pulse(100.0)];
+        pulses.last_mut().unwrap().gap = 5_000.0;
+        pulses.extend([pulse(100.0), pulse(100.0)]);
+        let train = PulseTrain {
+            modulation: Modulation::Ook,
+            pulses,
+            start: 42,
+            sample_rate: 250_000.0,
+            level: -10.0,
+            noise: -30.0,
+        };
+        let readings = registry.decode(&train);
+        assert_eq!(readings.len(), 1);
+        let reading = &readings[0];
+        assert_eq!((reading.model.as_str(), reading.id), ("Test-Plugin", 2));
+        assert_eq!((reading.channel, reading.battery_ok), (Some(2), Some(true)));
+        assert_eq!(
+            reading.get("temperature_C"),
+            Some(&Measurement::Temperature(5.0))
+        );
+        // PWM reads short pulses as 1.
+        assert_eq!(reading.get("ones").map(Measurement::value), Some(2.0));
+        assert_eq!((reading.start, reading.snr), (42, 20.0));
+        // Every width sat on its nominal timing.
+        let quality = reading.quality.unwrap();
+        assert!(!quality.crc_ok);
+        assert_eq!((quality.evm, quality.bit_error_rate), (0.0, 0.0));
+    }
+
+    #[test]
+    fn accepts_pcm_and_manchester_without_a_long_timing() {
+        for coding in [CODING_PCM, CODING_MANCHESTER] {
+            let raw = RawDecoder {
+                long: 0.0,
+                ..raw(coding)
+            };
+            assert!(unsafe { PluginDecoder::from_raw(&raw) }.is_ok());
+        }
+    }
+
+    #[test]
+    fn refuses_other_versions_and_bad_descriptions() {
+        let mut other = raw(CODING_PCM);
+        other.abi_version = 2;
+        assert!(matches!(
+            unsafe { PluginDecoder::from_raw(&other) },
+            Err(Error::Plugin(_))
+        ));
+        for bad in [
+            RawDecoder {
+                coding: 9,
+                ..raw(CODING_PCM)
+            },
+            RawDecoder {
+                modulation: 7,
+                ..raw(CODING_PCM)
+            },
+            RawDecoder {
+                short: 0.0,
+                ..raw(CODING_PCM)
+            },
+            RawDecoder {
+                long: 0.0,
+                ..raw(CODING_PPM)
+            },
+            RawDecoder {
+                decode: None,
+                ..raw(CODING_PCM)
+            },
+        ] {
+            assert!(unsafe { PluginDecoder::from_raw(&bad) }.is_err());
+        }
+        assert!(matches!(
+            IsmRegistry::new().load_plugin("/nonexistent/libplugin.so"),
+            Err(Error::Plugin(_))
+        ));
+    }
+}
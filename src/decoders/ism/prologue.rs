@@ -0,0 +1,61 @@
+use super::{find_repeated, IsmDecoder, Measurement, Reading};
+use crate::pulse::{Bits, Modulation, PulseTrain, Slicer};
+
+/// Prologue temperature and humidity sensors.
+///
+/// The sensor sends 36 bits 7 times, each as a 500 µs pulse followed by a
+/// 2000 µs gap for a 0 or a 4000 µs gap for a 1, with a 9000 µs gap between
+/// repeats. The layout is `1001IIII IIIIBKCC TTTTTTTT TTTTHHHH HHHH`: the
+/// type nibble, an 8-bit id, the battery flag, whether the transmit button
+/// was pressed, the channel, a 12-bit signed temperature in 0.1 °C and the
+/// humidity, which temperature-only models send as 0xcc.
+pub struct PrologueTh;
+
+impl IsmDecoder for PrologueTh {
+    fn name(&self) -> &str {
+        "Prologue-TH"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Ook
+    }
+
+    fn slicer(&self) -> Slicer {
+        Slicer::ppm(2000.0, 4000.0).with_row_gap(6500.0)
+    }
+
+    fn decode(&self, rows: &[Bits], _train: &PulseTrain) -> Vec<Reading> {
+        let Some(row) = find_repeated(rows, 3, 36) else {
+            return Vec::new();
+        };
+        let b = row.to_bytes();
+        if b[0] >> 4 != 0x9 {
+            return Vec::new();
+        }
+        let id = ((b[0] & 0x0f) << 4) | (b[1] >> 4);
+        let temp = (((b[2] as i16) << 8) | (b[3] & 0xf0) as i16) >> 4;
+        let humidity = ((b[3] & 0x0f) << 4) | (b[4] >> 4);
+        if humidity != 0xcc && humidity > 100 {
+            return Vec::new();
+        }
+
+        let model = if humidity == 0xcc {
+            "Prologue-T"
+        } else {
+            "Prologue-TH"
+        };
+        let mut reading = Reading::new(model, id as u64);
+        reading.channel = Some((b[1] & 0x03) + 1);
+        reading.battery_ok = Some(b[1] & 0x08 != 0);
+        reading
+            .measurements
+            .push(Measurement::Temperature(temp as f32 * 0.1));
+        if humidity != 0xcc {
+            reading.measurements.push(Measurement::Humidity(humidity));
+        }
+        reading
+            .measurements
+            .push(Measurement::Button((b[1] >> 2) & 1));
+        vec![reading]
+    }
+}
@@ -0,0 +1,50 @@
+//! Protocol decoders built on the crate's demodulators.
+//!
+//! The packet decoders grade each message they return with a `Quality`,
+//! from its checksum status and the soft decisions it was sliced from,
+//! so marginal decodes can be filtered out. ISM readings, time code frames
+//! and RDS groups are graded the same way, from their pulse timings, tick
+//! confidences and symbol margins. Given a `FailureDump`, the packet
+//! decoders also write each frame they find but reject to a debug
+//! directory, from where `Failure::read` loads it back for offline
+//! debugging.
+
+#[cfg(feature = "adsb")]
+pub mod adsb;
+#[cfg(feature = "ais")]
+pub mod ais;
+#[cfg(feature = "aprs")]
+pub mod aprs;
+#[cfg(feature = "apt")]
+pub mod apt;
+#[cfg(any(feature = "adsb", feature = "aprs"))]
+mod feed;
+#[cfg(any(feature = "ais", feature = "aprs"))]
+mod hdlc;
+#[cfg(feature = "ism")]
+pub mod ism;
+#[cfg(any(
+    feature = "adsb",
+    feature = "ais",
+    feature = "aprs",
+    feature = "ism",
+    feature = "rds",
+    feature = "timecode"
+))]
+pub(crate) mod quality;
+#[cfg(any(feature = "adsb", feature = "ais", feature = "aprs"))]
+mod replay;
+#[cfg(feature = "timecode")]
+pub mod timecode;
+
+#[cfg(any(
+    feature = "adsb",
+    feature = "ais",
+    feature = "aprs",
+    feature = "ism",
+    feature = "rds",
+    feature = "timecode"
+))]
+pub use quality::Quality;
+#[cfg(any(feature = "adsb", feature = "ais", feature = "aprs"))]
+pub use replay::{Failure, FailureDump};
@@ -0,0 +1,155 @@
+/// How confidently a message was received, for telling clean decodes from
+/// marginal ones.
+///
+/// The error estimates come from the message's soft decisions: a clean
+/// signal slices every bit with about the same margin, while noise spreads
+/// the margins and pushes some towards zero, where bits flip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quality {
+    /// Whether a checksum confirmed the bits. Messages whose checksum is
+    /// overlaid with an address that was recognised rather than checked
+    /// have this unset.
+    pub crc_ok: bool,
+    /// The number of bits repaired by error correction.
+    pub corrected_bits: u32,
+    /// The spread of the decision margins relative to their mean, from 0
+    /// for a clean signal upwards.
+    pub evm: f32,
+    /// The estimated probability of any one bit being wrong.
+    pub bit_error_rate: f32,
+}
+
+impl Quality {
+    /// Estimate the quality of a message from its soft decisions.
+    ///
+    /// # Arguments
+    ///
+    /// * `soft` - One value per bit, positive for a one and negative for a
+    ///   zero, with 127 for a full-strength decision.
+    /// * `crc_ok` - Whether a checksum confirmed the bits.
+    /// * `corrected_bits` - The number of bits repaired.
+    ///
+    /// # Returns
+    ///
+    /// A new `Quality`.
+    pub fn from_soft(soft: &[i8], crc_ok: bool, corrected_bits: u32) -> Self {
+        let (evm, bit_error_rate) = if soft.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let n = soft.len() as f32;
+            let mean = soft.iter().map(|&s| s.unsigned_abs() as f32).sum::<f32>() / n;
+            let var = soft
+                .iter()
+                .map(|&s| (s.unsigned_abs() as f32 - mean).powi(2))
+                .sum::<f32>()
+                / n;
+            let sigma = var.sqrt();
+            if mean == 0.0 {
+                (1.0, 0.5)
+            } else if sigma == 0.0 {
+                (0.0, 0.0)
+            } else {
+                // The margins as a Gaussian around their mean: a bit flips
+                // when its margin falls below zero.
+                (
+                    sigma / mean,
+                    0.5 * erfc(mean / (sigma * std::f32::consts::SQRT_2)),
+                )
+            }
+        };
+        Quality {
+            crc_ok,
+            corrected_bits,
+            evm,
+            bit_error_rate,
+        }
+    }
+}
+
+/// Soft decisions for bytes that were received as hard ones.
+#[cfg(any(feature = "adsb", feature = "ais", test))]
+pub(crate) fn hard_bits(bytes: &[u8]) -> Vec<i8> {
+    bytes
+        .iter()
+        .flat_map(|&b| {
+            (0..8)
+                .rev()
+                .map(move |i| if b >> i & 1 == 1 { 127 } else { -127 })
+        })
+        .collect()
+}
+
+/// Scale a decision margin to a soft bit.
+///
+/// # Arguments
+///
+/// * `margin` - The margin, positive for a one, where 1 is full strength.
+pub(crate) fn soft_bit(margin: f32) -> i8 {
+    (margin.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+/// The complementary error function, to within 1.5e-7 (Abramowitz and
+/// Stegun 7.1.26).
+fn erfc(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_7 + t * (-1.453_152_1 + t * 1.061_405_4))));
+    let y = poly * (-x * x).exp();
+    if x >= 0.0 {
+        y
+    } else {
+        2.0 - y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_decisions_are_perfect() {
+        let q = Quality::from_soft(&hard_bits(&[0xa5, 0x0f]), true, 0);
+        assert_eq!(
+            q,
+            Quality {
+                crc_ok: true,
+                corrected_bits: 0,
+                evm: 0.0,
+                bit_error_rate: 0.0
+            }
+        );
+        assert_eq!(hard_bits(&[0x81])[..2], [127, -127]);
+    }
+
+    #[test]
+    fn spread_margins_raise_the_estimates() {
+        let tight: Vec<i8> = (0..64)
+            .map(|i| if i % 2 == 0 { 100 } else { -110 })
+            .collect();
+        let loose: Vec<i8> = (0..64)
+            .map(|i| if i % 2 == 0 { 20 } else { -120 })
+            .collect();
+        let tight = Quality::from_soft(&tight, true, 0);
+        let loose = Quality::from_soft(&loose, true, 1);
+        assert!(tight.evm < 0.1, "{} evm", tight.evm);
+        assert!(tight.bit_error_rate < 1e-6, "{} ber", tight.bit_error_rate);
+        assert!(loose.evm > 0.5, "{} evm", loose.evm);
+        assert!(loose.bit_error_rate > 0.01, "{} ber", loose.bit_error_rate);
+        assert_eq!(loose.corrected_bits, 1);
+        assert_eq!(Quality::from_soft(&[0; 8], false, 0).bit_error_rate, 0.5);
+    }
+
+    #[test]
+    fn erfc_matches_known_values() {
+        for (x, want) in [
+            (0.0, 1.0),
+            (0.5, 0.479_500),
+            (1.0, 0.157_299),
+            (2.0, 0.004_678),
+            (-1.0, 1.842_701),
+        ] {
+            assert!((erfc(x) - want).abs() < 1e-5, "{} erfc", x);
+        }
+    }
+}
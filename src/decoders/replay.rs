@@ -0,0 +1,258 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// The most failures a `FailureDump` writes unless told otherwise.
+const DEFAULT_LIMIT: usize = 1000;
+
+/// A frame a decoder found but could not accept, as written by
+/// `FailureDump`.
+///
+/// The window is kept as the bytes the bits assembled into and the soft
+/// decision of every bit, most significant bit of each byte first, so a
+/// failure can be read back and fed to a decoder's checks offline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// The decoder, e.g. `adsb`.
+    pub decoder: String,
+    /// Why the frame was rejected, e.g. `crc` or `parse`.
+    pub reason: String,
+    /// Where in the stream the frame was found, counted since the decoder
+    /// was created or reset: the first sample of the preamble for Mode S,
+    /// the bit that completed the frame for AIS and APRS.
+    pub position: u64,
+    /// Anything else the decoder knew, such as the channel or signal
+    /// level, as names and values.
+    pub context: Vec<(String, String)>,
+    /// The bytes, with any checksum.
+    pub bytes: Vec<u8>,
+    /// The soft decision of each bit of `bytes`: positive for a one,
+    /// negative for a zero, with 127 for a full-strength decision.
+    pub soft: Vec<i8>,
+}
+
+impl Failure {
+    /// Read a failure written by `FailureDump`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file.
+    ///
+    /// # Returns
+    ///
+    /// The `Failure` if successful, otherwise an `Error`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let invalid = |line: usize, reason: &str| {
+            Error::InvalidMetadata(format!("line {}: {}", line + 1, reason))
+        };
+        let mut failure = Failure {
+            decoder: String::new(),
+            reason: String::new(),
+            position: 0,
+            context: Vec::new(),
+            bytes: Vec::new(),
+            soft: Vec::new(),
+        };
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid(n, "expected `name: value`"))?;
+            let value = value.trim();
+            match key {
+                "decoder" => failure.decoder = value.to_string(),
+                "reason" => failure.reason = value.to_string(),
+                "position" => {
+                    failure.position = value.parse().map_err(|_| invalid(n, "bad position"))?
+                }
+                "bytes" => {
+                    failure.bytes = (0..value.len())
+                        .step_by(2)
+                        .map(|i| {
+                            value
+                                .get(i..i + 2)
+                                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid(n, "bad hex"))?
+                }
+                "soft" => {
+                    failure.soft = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| invalid(n, "bad soft decision"))?
+                }
+                _ => failure.context.push((key.to_string(), value.to_string())),
+            }
+        }
+        if failure.decoder.is_empty() {
+            return Err(invalid(0, "no decoder"));
+        }
+        if failure.soft.len() != failure.bytes.len() * 8 {
+            return Err(Error::InvalidMetadata(format!(
+                "{} soft decisions for {} bytes",
+                failure.soft.len(),
+                failure.bytes.len()
+            )));
+        }
+        Ok(failure)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "decoder: {}", self.decoder);
+        let _ = writeln!(text, "reason: {}", self.reason);
+        let _ = writeln!(text, "position: {}", self.position);
+        for (name, value) in &self.context {
+            let _ = writeln!(text, "{}: {}", name, value);
+        }
+        text.push_str("bytes: ");
+        for b in &self.bytes {
+            let _ = write!(text, "{:02x}", b);
+        }
+        text.push_str("\nsoft:");
+        for s in &self.soft {
+            let _ = write!(text, " {}", s);
+        }
+        text.push('\n');
+        text
+    }
+}
+
+/// Writes the frames a decoder rejects to a debug directory, one file per
+/// failure, for reproducing and debugging them offline.
+///
+/// Noise passes for the start of a frame often, so only the first
+/// `limit` failures are kept. Write errors are counted rather than
+/// returned, so a full disk never stops a decoder.
+pub struct FailureDump {
+    dir: PathBuf,
+    limit: usize,
+    written: usize,
+    errors: usize,
+}
+
+impl FailureDump {
+    /// Create a dump, creating the directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory for this decoder's failures.
+    ///
+    /// # Returns
+    ///
+    /// A new `FailureDump` keeping up to 1000 failures if successful,
+    /// otherwise an `Error`.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(FailureDump {
+            dir,
+            limit: DEFAULT_LIMIT,
+            written: 0,
+            errors: 0,
+        })
+    }
+
+    /// Set how many failures are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The most files written.
+    ///
+    /// # Returns
+    ///
+    /// The `FailureDump` with the new limit.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Get the directory failures are written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the number of failures written.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Get the number of failures that could not be written.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Write a failure, named by its sequence number, position and reason,
+    /// e.g. `000003-1024-crc.txt`, unless the limit has been reached.
+    pub(crate) fn write(&mut self, failure: &Failure) {
+        if self.written >= self.limit {
+            return;
+        }
+        let name = format!(
+            "{:06}-{}-{}.txt",
+            self.written + self.errors,
+            failure.position,
+            failure.reason
+        );
+        match fs::write(self.dir.join(name), failure.to_text()) {
+            Ok(()) => self.written += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> Failure {
+        Failure {
+            decoder: "ais".into(),
+            reason: "crc".into(),
+            position: 1024,
+            context: vec![("channel".into(), "A".into())],
+            bytes: vec![0x8d, 0x01],
+            soft: vec![
+                90, -80, -70, -60, 50, 40, -30, 20, -1, -2, -3, -4, -5, -6, -7, 8,
+            ],
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_back_failures() {
+        let dir = std::env::temp_dir().join(format!("radion-failures-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut dump = FailureDump::new(dir.join("ais")).unwrap().with_limit(2);
+        for _ in 0..3 {
+            dump.write(&failure());
+        }
+        assert_eq!(dump.written(), 2);
+        assert_eq!(dump.errors(), 0);
+        let path = dump.dir().join("000001-1024-crc.txt");
+        assert_eq!(Failure::read(&path).unwrap(), failure());
+        assert_eq!(fs::read_dir(dump.dir()).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let text = failure().to_text();
+        assert!(Failure::parse(&text.replace("8d01", "8d0")).is_err());
+        assert!(Failure::parse(&text.replace(" 8\n", "\n")).is_err());
+        assert!(Failure::parse(&text.replace("decoder: ais\n", "")).is_err());
+        match Failure::parse("decoder: ais\nposition: x\n") {
+            Err(Error::InvalidMetadata(reason)) => assert_eq!(reason, "line 2: bad position"),
+            other => panic!("{:?}", other),
+        }
+    }
+}
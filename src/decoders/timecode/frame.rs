@@ -0,0 +1,387 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::decoders::quality::soft_bit;
+use crate::decoders::Quality;
+
+/// A long-wave time signal station.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Station {
+    /// DCF77, Mainflingen, Germany. Sends CET/CEST.
+    Dcf77,
+    /// MSF, Anthorn, UK. Sends UK civil time, GMT or BST.
+    Msf,
+    /// WWVB, Fort Collins, USA. Sends UTC.
+    Wwvb,
+}
+
+impl Station {
+    /// Get the carrier frequency in Hz.
+    pub fn frequency(&self) -> u32 {
+        match self {
+            Station::Dcf77 => 77_500,
+            Station::Msf | Station::Wwvb => 60_000,
+        }
+    }
+}
+
+/// What one second of the time code carries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    /// A data bit.
+    Bit(bool),
+    /// MSF's A and B bits.
+    Bits(bool, bool),
+    /// A marker: DCF77's missing 59th second, MSF's minute marker or one
+    /// of WWVB's position markers.
+    Marker,
+}
+
+/// One decoded second.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tick {
+    /// The input sample the second starts at.
+    pub sample: u64,
+    /// The second within the minute, once a minute marker has been seen.
+    pub second: Option<u8>,
+    /// What the second carries.
+    pub symbol: Symbol,
+    /// How clearly the carrier levels that decided the symbol sat either
+    /// side of the threshold, from 0 (a guess) to 1.
+    pub confidence: f32,
+}
+
+/// A decoded minute.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeFrame {
+    /// The station that sent it.
+    pub station: Station,
+    /// The start of the minute the frame labels, in UTC.
+    pub time: SystemTime,
+    /// The input sample that minute starts at.
+    pub sample: u64,
+    /// Whether summer time is in effect. For WWVB, whether DST is in
+    /// effect for the whole UTC day.
+    pub summer_time: bool,
+    /// Whether a leap second is announced. MSF doesn't announce them.
+    pub leap_second: bool,
+    /// The lowest confidence of the frame's ticks.
+    pub confidence: f32,
+    /// The spread of the ticks' confidences, graded like the packet
+    /// decoders' messages. `crc_ok` is set when parity bits confirmed the
+    /// frame, which WWVB doesn't send.
+    pub quality: Quality,
+}
+
+/// Numbers the seconds of a station's time code and decodes each complete
+/// minute.
+pub(crate) struct Assembler {
+    station: Station,
+    sample_rate: f64,
+    ticks: Vec<Tick>,
+    synced: bool,
+}
+
+impl Assembler {
+    pub(crate) fn new(station: Station, sample_rate: f64) -> Self {
+        Assembler {
+            station,
+            sample_rate,
+            ticks: Vec::with_capacity(61),
+            synced: false,
+        }
+    }
+
+    /// Number a tick and add it to the minute, returning the minute it
+    /// completes if it decodes.
+    pub(crate) fn push(&mut self, tick: &mut Tick) -> Option<TimeFrame> {
+        let marker = tick.symbol == Symbol::Marker;
+        let mut frame = None;
+        match self.station {
+            // The minute's time is sent during the minute before, and its
+            // 59th second has no marker, so the frame ends there.
+            Station::Dcf77 => {
+                if marker {
+                    if self.synced && self.ticks.len() == 59 {
+                        let start = tick.sample + self.sample_rate.round() as u64;
+                        frame = decode_dcf77(&self.ticks, start);
+                    }
+                    tick.second = Some(59);
+                    self.ticks.clear();
+                    self.synced = true;
+                } else {
+                    tick.second = self.number(self.ticks.len());
+                    self.ticks.push(*tick);
+                }
+            }
+            // The same, but with a marker in second 0 of the minute.
+            Station::Msf => {
+                if marker {
+                    if self.synced && self.ticks.len() == 59 {
+                        frame = decode_msf(&self.ticks, tick.sample);
+                    }
+                    tick.second = Some(0);
+                    self.ticks.clear();
+                    self.synced = true;
+                } else {
+                    tick.second = self.number(self.ticks.len() + 1);
+                    self.ticks.push(*tick);
+                }
+            }
+            // The time of the minute being sent, which starts with the
+            // second of two markers in a row.
+            Station::Wwvb => {
+                let last_marker = self.ticks.last().map(|t| t.symbol) == Some(Symbol::Marker);
+                if marker && last_marker {
+                    if self.synced && self.ticks.len() == 60 {
+                        frame = decode_wwvb(&self.ticks);
+                    }
+                    self.ticks.clear();
+                    self.synced = true;
+                }
+                tick.second = self.number(self.ticks.len());
+                self.ticks.push(*tick);
+            }
+        }
+        if self.ticks.len() > 60 {
+            self.ticks.clear();
+            self.synced = false;
+        }
+        frame
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.ticks.clear();
+        self.synced = false;
+    }
+
+    fn number(&self, second: usize) -> Option<u8> {
+        (self.synced && second < 60).then_some(second as u8)
+    }
+}
+
+fn decode_dcf77(ticks: &[Tick], sample: u64) -> Option<TimeFrame> {
+    let bits = bits(ticks)?;
+    // Second 0 is always 0, second 20 always 1, and exactly one of CEST
+    // and CET is set.
+    if bits[0] || !bits[20] || bits[17] == bits[18] {
+        return None;
+    }
+    let even = |range: std::ops::RangeInclusive<usize>| {
+        bits[range].iter().filter(|&&b| b).count() % 2 == 0
+    };
+    if !even(21..=28) || !even(29..=35) || !even(36..=58) {
+        return None;
+    }
+    let minute = bcd(&bits[21..28], &[1, 2, 4, 8, 10, 20, 40])?;
+    let hour = bcd(&bits[29..35], &[1, 2, 4, 8, 10, 20])?;
+    let day = bcd(&bits[36..42], &[1, 2, 4, 8, 10, 20])?;
+    let weekday = bcd(&bits[42..45], &[1, 2, 4])?;
+    let month = bcd(&bits[45..50], &[1, 2, 4, 8, 10])?;
+    let year = bcd(&bits[50..58], &[1, 2, 4, 8, 10, 20, 40, 80])?;
+    if !(1..=7).contains(&weekday) {
+        return None;
+    }
+    let local = civil(2000 + year, month, day, hour, minute)?;
+    let offset = if bits[17] { 7200 } else { 3600 };
+    Some(TimeFrame {
+        station: Station::Dcf77,
+        time: local.checked_sub(Duration::from_secs(offset))?,
+        sample,
+        summer_time: bits[17],
+        leap_second: bits[19],
+        confidence: confidence(ticks),
+        quality: quality(ticks, true),
+    })
+}
+
+fn decode_msf(ticks: &[Tick], sample: u64) -> Option<TimeFrame> {
+    let mut a = [false; 60];
+    let mut b = [false; 60];
+    for (i, tick) in ticks.iter().enumerate() {
+        let Symbol::Bits(bit_a, bit_b) = tick.symbol else {
+            return None;
+        };
+        a[i + 1] = bit_a;
+        b[i + 1] = bit_b;
+    }
+    // The minute identifier 01111110 in A52 to A59.
+    if a[52..60] != [false, true, true, true, true, true, true, false] {
+        return None;
+    }
+    let odd = |range: std::ops::RangeInclusive<usize>, parity: bool| {
+        (a[range].iter().filter(|&&b| b).count() + parity as usize) % 2 == 1
+    };
+    if !odd(17..=24, b[54]) || !odd(25..=35, b[55]) || !odd(36..=38, b[56]) || !odd(39..=51, b[57])
+    {
+        return None;
+    }
+    let year = bcd(&a[17..25], &[80, 40, 20, 10, 8, 4, 2, 1])?;
+    let month = bcd(&a[25..30], &[10, 8, 4, 2, 1])?;
+    let day = bcd(&a[30..36], &[20, 10, 8, 4, 2, 1])?;
+    let weekday = bcd(&a[36..39], &[4, 2, 1])?;
+    let hour = bcd(&a[39..45], &[20, 10, 8, 4, 2, 1])?;
+    let minute = bcd(&a[45..52], &[40, 20, 10, 8, 4, 2, 1])?;
+    if weekday > 6 {
+        return None;
+    }
+    let local = civil(2000 + year, month, day, hour, minute)?;
+    let offset = if b[58] { 3600 } else { 0 };
+    Some(TimeFrame {
+        station: Station::Msf,
+        time: local.checked_sub(Duration::from_secs(offset))?,
+        sample,
+        summer_time: b[58],
+        leap_second: false,
+        confidence: confidence(ticks),
+        quality: quality(ticks, true),
+    })
+}
+
+fn decode_wwvb(ticks: &[Tick]) -> Option<TimeFrame> {
+    const MARKERS: [usize; 7] = [0, 9, 19, 29, 39, 49, 59];
+    const ZEROS: [usize; 11] = [4, 10, 11, 14, 20, 21, 24, 34, 35, 44, 54];
+    let mut bits = [false; 60];
+    for (i, tick) in ticks.iter().enumerate() {
+        match tick.symbol {
+            Symbol::Marker if MARKERS.contains(&i) => {}
+            Symbol::Bit(bit) if !MARKERS.contains(&i) => bits[i] = bit,
+            _ => return None,
+        }
+    }
+    if ZEROS.iter().any(|&i| bits[i]) {
+        return None;
+    }
+    let minute = bcd(&bits[1..9], &[40, 20, 10, 0, 8, 4, 2, 1])?;
+    let hour = bcd(&bits[12..19], &[20, 10, 0, 8, 4, 2, 1])?;
+    let hundreds = bcd(&bits[22..24], &[200, 100])?;
+    let tens = bcd(&bits[25..29], &[80, 40, 20, 10])?;
+    let units = bcd(&bits[30..34], &[8, 4, 2, 1])?;
+    let year = 2000 + bcd(&bits[45..54], &[80, 40, 20, 10, 0, 8, 4, 2, 1])?;
+    let day = hundreds + tens + units;
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    if bits[55] != leap_year || day == 0 || day > 365 + leap_year as u32 {
+        return None;
+    }
+    let start = civil(year, 1, 1, hour, minute)?;
+    Some(TimeFrame {
+        station: Station::Wwvb,
+        time: start + Duration::from_secs((day as u64 - 1) * 86_400),
+        sample: ticks[0].sample,
+        summer_time: bits[57] && bits[58],
+        leap_second: bits[56],
+        confidence: confidence(ticks),
+        quality: quality(ticks, false),
+    })
+}
+
+/// The data bits of a frame with no markers in it.
+fn bits(ticks: &[Tick]) -> Option<Vec<bool>> {
+    ticks
+        .iter()
+        .map(|tick| match tick.symbol {
+            Symbol::Bit(bit) => Some(bit),
+            _ => None,
+        })
+        .collect()
+}
+
+fn confidence(ticks: &[Tick]) -> f32 {
+    ticks.iter().map(|t| t.confidence).fold(1.0, f32::min)
+}
+
+fn quality(ticks: &[Tick], parity_ok: bool) -> Quality {
+    let soft: Vec<i8> = ticks.iter().map(|t| soft_bit(t.confidence)).collect();
+    Quality::from_soft(&soft, parity_ok, 0)
+}
+
+/// Add up a BCD field, rejecting a units digit above 9.
+fn bcd(bits: &[bool], weights: &[u32]) -> Option<u32> {
+    let (mut units, mut tens) = (0, 0);
+    for (&bit, &weight) in bits.iter().zip(weights) {
+        match (bit, weight) {
+            (false, _) => {}
+            (true, 0) => return None,
+            (true, w) if w < 10 => units += w,
+            (true, w) => tens += w,
+        }
+    }
+    (units <= 9).then_some(tens + units)
+}
+
+/// Convert a date and time to a `SystemTime` if it is valid.
+fn civil(year: u32, month: u32, day: u32, hour: u32, minute: u32) -> Option<SystemTime> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // Days from 1970-01-01, counting years from March.
+    let (year, month) = (year as i64, month as i64);
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let seconds = days * 86_400 + hour as i64 * 3600 + minute as i64 * 60;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The ticks of a DCF77 minute, each received with `confidence`.
+    fn dcf77(fields: &[(usize, u32, &[u32])], confidence: f32) -> Vec<Tick> {
+        let mut bits = [false; 59];
+        bits[17] = true;
+        bits[20] = true;
+        for &(at, value, weights) in fields {
+            let (mut tens, mut units) = (value / 10 * 10, value % 10);
+            for (i, &w) in weights.iter().enumerate().rev() {
+                let left = if w < 10 { &mut units } else { &mut tens };
+                if *left >= w {
+                    *left -= w;
+                    bits[at + i] = true;
+                }
+            }
+        }
+        for (from, parity) in [(21, 28), (29, 35), (36, 58)] {
+            bits[parity] = bits[from..parity].iter().filter(|&&b| b).count() % 2 == 1;
+        }
+        bits.iter()
+            .enumerate()
+            .map(|(i, &bit)| Tick {
+                sample: i as u64,
+                second: Some(i as u8),
+                symbol: Symbol::Bit(bit),
+                confidence,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_and_grades_a_dcf77_minute() {
+        // Friday 2026-10-16 12:34 CEST.
+        let fields: [(usize, u32, &[u32]); 6] = [
+            (21, 34, &[1, 2, 4, 8, 10, 20, 40]),
+            (29, 12, &[1, 2, 4, 8, 10, 20]),
+            (36, 16, &[1, 2, 4, 8, 10, 20]),
+            (42, 5, &[1, 2, 4]),
+            (45, 10, &[1, 2, 4, 8, 10]),
+            (50, 26, &[1, 2, 4, 8, 10, 20, 40, 80]),
+        ];
+        let frame = decode_dcf77(&dcf77(&fields, 1.0), 7).unwrap();
+        assert_eq!(frame.time, civil(2026, 10, 16, 10, 34).unwrap());
+        assert!(frame.summer_time);
+        assert!(frame.quality.crc_ok);
+        assert_eq!(frame.quality.evm, 0.0);
+
+        let mut ticks = dcf77(&fields, 0.9);
+        ticks[30].confidence = 0.1;
+        let marginal = decode_dcf77(&ticks, 7).unwrap();
+        assert_eq!(marginal.confidence, 0.1);
+        assert!(marginal.quality.evm > 0.05, "{} evm", marginal.quality.evm);
+
+        ticks[22].symbol = Symbol::Bit(ticks[22].symbol != Symbol::Bit(true));
+        assert_eq!(decode_dcf77(&ticks, 7), None);
+    }
+}
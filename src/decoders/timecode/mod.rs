@@ -0,0 +1,49 @@
+//! Long-wave time signal decoding: DCF77, MSF and WWVB.
+//!
+//! `TimecodeReceiver` reads the stations' amplitude-keyed time codes from
+//! IQ, returning each second as a `Tick` with its symbol and a confidence,
+//! and each minute that decodes as a `TimeFrame` with the UTC time and the
+//! input sample it starts at. The carriers sit at 60 kHz and 77.5 kHz,
+//! below the tuners' range, so receive them with direct sampling and an
+//! antenna on the ADC input the dongle samples.
+//!
+//! ```no_run
+//! use radion::decoders::timecode::{Event, Station, TimecodeReceiver};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::{Device, SamplingMode};
+//!
+//! const SAMPLE_RATE: u32 = 250_000;
+//! const CENTER_FREQ: u32 = 100_000;
+//!
+//! let device = Device::new(0)?;
+//! device.set_direct_sampling(SamplingMode::QADC)?;
+//! device.set_center_freq(CENTER_FREQ)?;
+//! device.set_sample_rate(SAMPLE_RATE)?;
+//! device.reset_buffer()?;
+//!
+//! let station = Station::Dcf77;
+//! let offset = station.frequency() as f64 - CENTER_FREQ as f64;
+//! let mut receiver = TimecodeReceiver::new(station, SAMPLE_RATE as f64, offset);
+//! loop {
+//!     let iq = to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized);
+//!     for event in receiver.process(&iq) {
+//!         match event {
+//!             Event::Tick(tick) => {
+//!                 println!("{:?} {:?} {:.2}", tick.second, tick.symbol, tick.confidence)
+//!             }
+//!             Event::Frame(frame) => println!("{:?}", frame.time),
+//!         }
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod frame;
+mod receiver;
+
+pub use frame::{Station, Symbol, Tick, TimeFrame};
+pub use receiver::{Event, TimecodeReceiver};
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+
+use super::frame::{Assembler, Station, Symbol, Tick, TimeFrame};
+use crate::dsp::fir::{low_pass, Window};
+use crate::dsp::{Complex, FreqXlator};
+
+/// The rate the carrier level is measured at: 10 ms bins.
+const BIN_RATE: f64 = 100.0;
+
+/// Bins in a second.
+const BINS: usize = 100;
+
+/// Bins in the 100 ms slots the symbols are read from.
+const SLOT: usize = 10;
+
+/// The rate the carrier is mixed down and decimated to.
+const ENVELOPE_RATE: f64 = 8_000.0;
+
+/// Seconds of carrier to average before the first tick.
+const WARMUP: u64 = 3;
+
+/// How much each second moves the averaged second, about a five second
+/// time constant.
+const PROFILE_GAIN: f32 = 0.2;
+
+/// What `TimecodeReceiver::process` found.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A second was decoded.
+    Tick(Tick),
+    /// A minute was decoded. Follows the tick that completed it.
+    Frame(TimeFrame),
+}
+
+/// Decodes a long-wave time signal from IQ.
+///
+/// The carrier is mixed to baseband and its level measured every 10 ms.
+/// Averaging the level over the last few seconds, folded onto one second,
+/// shows where the carrier drops at the start of each second; each second
+/// is then read from the level in 100 ms slots, relative to the averaged
+/// level of the slots that are always reduced and always full. Each
+/// returned `Tick` carries the symbol and a confidence, and every complete
+/// minute that passes its parity and range checks returns a `TimeFrame`.
+/// Second starts are found to within a 10 ms bin.
+///
+/// Leap seconds break the minute they are inserted in, which is dropped.
+pub struct TimecodeReceiver {
+    station: Station,
+    sample_rate: f64,
+    xlator: FreqXlator,
+    /// The channel filter's delay in input samples.
+    delay: u64,
+    /// Envelope samples per bin.
+    bin_length: f64,
+    /// Envelope samples in the bin being filled.
+    count: usize,
+    /// Sum of their magnitudes.
+    sum: f32,
+    /// Where the bin being filled ends, in envelope samples since the
+    /// start.
+    bin_end: f64,
+    envelope_samples: u64,
+    /// Recent bins, `bins[0]` being bin number `first_bin`.
+    bins: VecDeque<f32>,
+    first_bin: u64,
+    /// The carrier level averaged over recent seconds, by bin within the
+    /// second.
+    profile: [f32; BINS],
+    /// Bins folded into the profile.
+    profiled: u64,
+    /// The bin the last tick started at.
+    last_start: Option<u64>,
+    assembler: Assembler,
+}
+
+impl TimecodeReceiver {
+    /// Create a receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `station` - The station to decode.
+    /// * `sample_rate` - The input sample rate in Hz, at least 8 kHz.
+    /// * `offset` - The station's carrier relative to the tuned frequency
+    ///   in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `TimecodeReceiver`.
+    pub fn new(station: Station, sample_rate: f64, offset: f64) -> Self {
+        assert!(
+            sample_rate >= ENVELOPE_RATE,
+            "time signals need a sample rate of at least 8 kHz"
+        );
+        let factor = (sample_rate / ENVELOPE_RATE).floor() as usize;
+        // Pass 500 Hz around the carrier and stop by 4 kHz out.
+        let taps = (5.5 * sample_rate / 3_500.0) as usize | 1;
+        let filter = low_pass(500.0 / sample_rate, taps, Window::Blackman);
+        let xlator = FreqXlator::new(sample_rate, offset, factor, &filter);
+        let bin_length = xlator.output_rate() / BIN_RATE;
+        TimecodeReceiver {
+            station,
+            sample_rate,
+            xlator,
+            delay: taps as u64 / 2,
+            bin_length,
+            count: 0,
+            sum: 0.0,
+            bin_end: bin_length,
+            envelope_samples: 0,
+            bins: VecDeque::new(),
+            first_bin: 0,
+            profile: [0.0; BINS],
+            profiled: 0,
+            last_start: None,
+            assembler: Assembler::new(station, sample_rate),
+        }
+    }
+
+    /// Get the station being decoded.
+    pub fn station(&self) -> Station {
+        self.station
+    }
+
+    /// Decode a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The seconds and minutes completed by the chunk, in order.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<Event> {
+        for sample in self.xlator.process(iq) {
+            self.sum += sample.norm();
+            self.count += 1;
+            self.envelope_samples += 1;
+            if self.envelope_samples as f64 >= self.bin_end {
+                self.push_bin(self.sum / self.count as f32);
+                self.bin_end += self.bin_length;
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+
+        let mut events = Vec::new();
+        while let Some(start) = self.next_start() {
+            let Some(mut tick) = self.read_second(start) else {
+                break;
+            };
+            self.last_start = Some(start);
+            let frame = self.assembler.push(&mut tick);
+            events.push(Event::Tick(tick));
+            events.extend(frame.map(Event::Frame));
+            // Keep from halfway to the next tick, the earliest it can start.
+            while self.first_bin < start + BINS as u64 / 2 && !self.bins.is_empty() {
+                self.bins.pop_front();
+                self.first_bin += 1;
+            }
+        }
+        events
+    }
+
+    /// Forget the stream so far, e.g. after retuning.
+    pub fn reset(&mut self) {
+        self.xlator.reset();
+        self.count = 0;
+        self.sum = 0.0;
+        self.bin_end = self.bin_length;
+        self.envelope_samples = 0;
+        self.bins.clear();
+        self.first_bin = 0;
+        self.profile = [0.0; BINS];
+        self.profiled = 0;
+        self.last_start = None;
+        self.assembler.reset();
+    }
+
+    fn push_bin(&mut self, level: f32) {
+        let index = (self.profiled % BINS as u64) as usize;
+        if self.profiled < BINS as u64 {
+            self.profile[index] = level;
+        } else {
+            self.profile[index] += PROFILE_GAIN * (level - self.profile[index]);
+        }
+        self.profiled += 1;
+        self.bins.push_back(level);
+    }
+
+    /// The bin within the second where the averaged carrier falls the most.
+    fn phase(&self) -> usize {
+        let at = |i: usize| self.profile[i % BINS];
+        (0..BINS)
+            .max_by(|&a, &b| {
+                let fall = |i: usize| at(i + BINS - 2) + at(i + BINS - 1) - at(i) - at(i + 1);
+                fall(a).total_cmp(&fall(b))
+            })
+            .unwrap_or(0)
+    }
+
+    /// The bin the next second starts at.
+    fn next_start(&self) -> Option<u64> {
+        if self.profiled < WARMUP * BINS as u64 {
+            return None;
+        }
+        let earliest = match self.last_start {
+            Some(last) => last + BINS as u64 / 2,
+            None => self.first_bin,
+        };
+        let phase = self.phase() as u64;
+        let start = earliest + (phase + BINS as u64 - earliest % BINS as u64) % BINS as u64;
+        Some(start.max(self.first_bin))
+    }
+
+    /// Read the second starting at a bin, if all of it has arrived.
+    fn read_second(&self, start: u64) -> Option<Tick> {
+        let offset = (start - self.first_bin) as usize;
+        if offset + BINS > self.bins.len() {
+            return None;
+        }
+        let phase = (start % BINS as u64) as usize;
+        // The first 100 ms is reduced in every second bar DCF77's 59th,
+        // and the last 100 ms is always full.
+        let average = |from: usize| {
+            (from + 2..from + SLOT - 2)
+                .map(|i| self.profile[(phase + i) % BINS])
+                .sum::<f32>()
+                / (SLOT - 4) as f32
+        };
+        let low = average(0);
+        let high = average(BINS - SLOT);
+        let span = (high - low).max(f32::EPSILON);
+        // Each slot's level from 0 (reduced) to 1 (full), skipping the
+        // edges.
+        let slot = |k: usize| {
+            let level = (offset + k * SLOT + 2..offset + (k + 1) * SLOT - 2)
+                .map(|i| self.bins[i])
+                .sum::<f32>()
+                / (SLOT - 4) as f32;
+            ((level - low) / span).clamp(0.0, 1.0)
+        };
+        let reduced = |k: usize| slot(k) < 0.5;
+        let margin = |slots: &[usize]| {
+            slots
+                .iter()
+                .map(|&k| (slot(k) - 0.5).abs() * 2.0)
+                .fold(1.0, f32::min)
+        };
+
+        let (symbol, confidence) = match self.station {
+            // 100 ms reduced for 0, 200 ms for 1, none for the marker.
+            Station::Dcf77 => {
+                let symbol = if !reduced(0) {
+                    Symbol::Marker
+                } else {
+                    Symbol::Bit(reduced(1))
+                };
+                (symbol, margin(&[0, 1]))
+            }
+            // Off for 100 ms, then A and B for 100 ms each, or 500 ms for
+            // the minute marker.
+            Station::Msf => {
+                let symbol = if reduced(3) && reduced(4) {
+                    Symbol::Marker
+                } else {
+                    Symbol::Bits(reduced(1), reduced(2))
+                };
+                (symbol, margin(&[1, 2, 3]))
+            }
+            // 200 ms reduced for 0, 500 ms for 1, 800 ms for a marker.
+            Station::Wwvb => {
+                let symbol = if reduced(6) {
+                    Symbol::Marker
+                } else {
+                    Symbol::Bit(reduced(3))
+                };
+                (symbol, margin(&[3, 6]))
+            }
+        };
+        Some(Tick {
+            sample: ((start as f64 * self.sample_rate / BIN_RATE).round() as u64)
+                .saturating_sub(self.delay),
+            second: None,
+            symbol,
+            confidence,
+        })
+    }
+}
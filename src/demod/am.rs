@@ -0,0 +1,135 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::dsp::fir::{kaiser_params, low_pass, Decimator, Window};
+use crate::dsp::Complex;
+
+/// Envelope AM demodulator for airband and broadcast AM.
+///
+/// The envelope is low-pass filtered and decimated to the audio rate, and the
+/// carrier is tracked with a slow average and removed. Audio is normalised to
+/// the carrier, so a fully modulated signal swings between -1.0 and 1.0
+/// regardless of signal strength.
+pub struct AmDemod {
+    audio: Decimator<f32>,
+    /// Carrier smoothing factor per audio sample.
+    alpha: f32,
+    carrier: f32,
+    envelope: Vec<f32>,
+}
+
+impl AmDemod {
+    /// Create a demodulator with an audio bandwidth of 40% of the audio rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `audio_rate` - The output rate in Hz. `sample_rate` should be an
+    ///   integer multiple of it.
+    ///
+    /// # Returns
+    ///
+    /// A new `AmDemod`.
+    pub fn new(sample_rate: f64, audio_rate: f64) -> Self {
+        Self::with_bandwidth(sample_rate, audio_rate, 0.4 * audio_rate)
+    }
+
+    /// Create a demodulator with an explicit audio bandwidth.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `audio_rate` - The output rate in Hz. `sample_rate` should be an
+    ///   integer multiple of it.
+    /// * `bandwidth` - Audio low-pass cutoff in Hz, e.g. 3000 for airband.
+    ///
+    /// # Returns
+    ///
+    /// A new `AmDemod`.
+    pub fn with_bandwidth(sample_rate: f64, audio_rate: f64, bandwidth: f64) -> Self {
+        let factor = (sample_rate / audio_rate).round().max(1.0) as usize;
+        let audio_rate = sample_rate / factor as f64;
+        let cutoff = bandwidth.min(0.45 * audio_rate);
+        let transition = (0.5 * audio_rate - cutoff).max(0.05 * audio_rate) / sample_rate;
+        let (taps, beta) = kaiser_params(transition, 50.0);
+        AmDemod {
+            audio: Decimator::new(
+                factor,
+                &low_pass(cutoff / sample_rate, taps, Window::Kaiser(beta)),
+            ),
+            // Carrier time constant of about 100 ms.
+            alpha: (1.0 / (0.1 * audio_rate)) as f32,
+            carrier: 0.0,
+            envelope: Vec::new(),
+        }
+    }
+
+    /// Get the tracked carrier level.
+    ///
+    /// # Returns
+    ///
+    /// The carrier amplitude in input units, for signal meters and squelch.
+    pub fn carrier_level(&self) -> f32 {
+        self.carrier
+    }
+
+    /// Demodulate quadrature samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - Baseband samples with the carrier at DC.
+    ///
+    /// # Returns
+    ///
+    /// Audio samples at the audio rate.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<f32> {
+        let mut envelope = std::mem::take(&mut self.envelope);
+        envelope.clear();
+        envelope.extend(iq.iter().map(|s| s.norm()));
+        let audio = self.finish(&envelope);
+        self.envelope = envelope;
+        audio
+    }
+
+    /// Demodulate real samples, as produced by direct sampling.
+    ///
+    /// The signal is full-wave rectified and scaled by pi/2, so after
+    /// low-pass filtering the envelope matches what `process` would give for
+    /// the same carrier.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Real samples with the carrier well above the audio
+    ///   bandwidth and not at a simple fraction of the sample rate, where
+    ///   the rectifier harmonics alias onto DC.
+    ///
+    /// # Returns
+    ///
+    /// Audio samples at the audio rate.
+    pub fn process_real(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut envelope = std::mem::take(&mut self.envelope);
+        envelope.clear();
+        envelope.extend(samples.iter().map(|s| s.abs() * FRAC_PI_2));
+        let audio = self.finish(&envelope);
+        self.envelope = envelope;
+        audio
+    }
+
+    fn finish(&mut self, envelope: &[f32]) -> Vec<f32> {
+        let mut audio = self.audio.decimate(envelope);
+        for a in audio.iter_mut() {
+            self.carrier += self.alpha * (*a - self.carrier);
+            *a = if self.carrier > 0.0 {
+                (*a - self.carrier) / self.carrier
+            } else {
+                0.0
+            };
+        }
+        audio
+    }
+
+    /// Clear the filter and carrier state.
+    pub fn reset(&mut self) {
+        self.audio.reset();
+        self.carrier = 0.0;
+    }
+}
@@ -0,0 +1,730 @@
+use std::f64::consts::{PI, TAU};
+use std::time::Duration;
+
+#[cfg(feature = "fft")]
+use {
+    crate::dsp::Complex,
+    rustfft::{Fft, FftPlanner},
+    std::sync::Arc,
+};
+
+/// Corner of the high-pass that removes sub-audible signalling, above the
+/// highest CTCSS tone (254.1 Hz).
+const TONE_CUTOFF_HZ: f64 = 300.0;
+
+/// Edges of the communications voice band.
+const VOICE_LOW_HZ: f64 = 300.0;
+const VOICE_HIGH_HZ: f64 = 3_000.0;
+
+/// Second-order IIR section with coefficients from the Audio EQ Cookbook,
+/// in transposed direct form II.
+#[derive(Copy, Clone, Debug)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    z: [f32; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: [
+                (b[0] / a[0]) as f32,
+                (b[1] / a[0]) as f32,
+                (b[2] / a[0]) as f32,
+            ],
+            a: [(a[1] / a[0]) as f32, (a[2] / a[0]) as f32],
+            z: [0.0; 2],
+        }
+    }
+
+    fn high_pass(rate: f64, freq: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::params(rate, freq, q);
+        Biquad::new(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn low_pass(rate: f64, freq: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::params(rate, freq, q);
+        Biquad::new(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// A band-pass with unity gain at the centre.
+    fn band_pass(rate: f64, freq: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::params(rate, freq, q);
+        Biquad::new([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    fn params(rate: f64, freq: f64, q: f64) -> (f64, f64) {
+        let w = TAU * freq.min(0.49 * rate) / rate;
+        (w.cos(), w.sin() / (2.0 * q))
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The Q of each section of an even-order Butterworth filter.
+fn butterworth(order: usize) -> impl Iterator<Item = f64> {
+    (0..order / 2).map(move |k| 1.0 / (2.0 * ((2 * k + 1) as f64 * PI / (2 * order) as f64).cos()))
+}
+
+/// A cascade of biquads.
+#[derive(Clone, Debug)]
+struct Cascade(Vec<Biquad>);
+
+impl Cascade {
+    fn process(&mut self, audio: &mut [f32]) {
+        for s in audio {
+            *s = self.0.iter_mut().fold(*s, |x, f| f.process(x));
+        }
+    }
+
+    fn reset(&mut self) {
+        for f in &mut self.0 {
+            f.z = [0.0; 2];
+        }
+    }
+}
+
+/// Removes CTCSS tones and DCS codes from received audio.
+///
+/// A sixth-order Butterworth high-pass at 300 Hz takes tones below 200 Hz
+/// down by over 20 dB while leaving speech alone. Apply it after any
+/// `ToneSquelch`, which needs the tone.
+#[derive(Clone, Debug)]
+pub struct ToneFilter(Cascade);
+
+impl ToneFilter {
+    /// Create a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `ToneFilter`.
+    pub fn new(audio_rate: f64) -> Self {
+        ToneFilter(Cascade(
+            butterworth(6)
+                .map(|q| Biquad::high_pass(audio_rate, TONE_CUTOFF_HZ, q))
+                .collect(),
+        ))
+    }
+
+    /// Filter a chunk of audio in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of audio.
+    pub fn process(&mut self, audio: &mut [f32]) {
+        self.0.process(audio);
+    }
+
+    /// Clear the filter's history.
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+/// The audio band a `BandFilter` passes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AudioBand {
+    /// 300 Hz to 3 kHz, for speech on AM, FM and SSB.
+    Voice,
+    /// A narrow band around the beat note of a Morse signal.
+    Cw {
+        /// The centre of the band in Hz, usually 600 to 800.
+        pitch_hz: f32,
+        /// The width of the band in Hz, e.g. 500, or 200 for a crowded band.
+        width_hz: f32,
+    },
+}
+
+/// Restricts received audio to a band, cutting the hiss and rumble outside
+/// it.
+#[derive(Clone, Debug)]
+pub struct BandFilter(Cascade);
+
+impl BandFilter {
+    /// Create a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz.
+    /// * `band` - The band to pass.
+    ///
+    /// # Returns
+    ///
+    /// A new `BandFilter`: a second-order high-pass and fourth-order
+    /// low-pass for voice, or two band-pass sections for CW.
+    pub fn new(audio_rate: f64, band: AudioBand) -> Self {
+        let sections = match band {
+            AudioBand::Voice => std::iter::once(Biquad::high_pass(
+                audio_rate,
+                VOICE_LOW_HZ,
+                std::f64::consts::FRAC_1_SQRT_2,
+            ))
+            .chain(butterworth(4).map(|q| Biquad::low_pass(audio_rate, VOICE_HIGH_HZ, q)))
+            .collect(),
+            AudioBand::Cw { pitch_hz, width_hz } => {
+                // Two sections narrow the band by about 1.55 each, so widen
+                // each to keep the requested -3 dB width.
+                let q = pitch_hz as f64 / (width_hz.max(1.0) as f64 * 1.554);
+                vec![Biquad::band_pass(audio_rate, pitch_hz as f64, q); 2]
+            }
+        };
+        BandFilter(Cascade(sections))
+    }
+
+    /// Filter a chunk of audio in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of audio.
+    pub fn process(&mut self, audio: &mut [f32]) {
+        self.0.process(audio);
+    }
+
+    /// Clear the filter's history.
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+/// Mutes audio while its level stays below a threshold, silencing the hiss
+/// between words without the clicks of a hard squelch.
+///
+/// The level is a peak envelope decaying over 20 ms. The gate opens within
+/// a millisecond of it crossing the threshold, stays open for the hold time
+/// after it falls back, then fades out over 20 ms.
+#[derive(Clone, Debug)]
+pub struct NoiseGate {
+    threshold: f32,
+    hold: usize,
+    held: usize,
+    envelope: f32,
+    gain: f32,
+    decay: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl NoiseGate {
+    /// Create a gate with a 100 ms hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz.
+    /// * `threshold_db` - The peak level that opens the gate in dBFS,
+    ///   e.g. -40.
+    ///
+    /// # Returns
+    ///
+    /// A new, closed `NoiseGate`.
+    pub fn new(audio_rate: f64, threshold_db: f32) -> Self {
+        let coef = |seconds: f64| (-1.0 / (seconds * audio_rate)).exp() as f32;
+        NoiseGate {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            hold: (0.1 * audio_rate) as usize,
+            held: 0,
+            envelope: 0.0,
+            gain: 0.0,
+            decay: coef(0.02),
+            attack: coef(0.001),
+            release: coef(0.02),
+        }
+    }
+
+    /// Set how long the gate stays open after the level falls.
+    ///
+    /// # Arguments
+    ///
+    /// * `hold` - The hold time.
+    /// * `audio_rate` - The audio sample rate in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The `NoiseGate` with the new hold time.
+    pub fn with_hold(mut self, hold: Duration, audio_rate: f64) -> Self {
+        self.hold = (hold.as_secs_f64() * audio_rate) as usize;
+        self
+    }
+
+    /// Get whether the gate is open.
+    pub fn is_open(&self) -> bool {
+        self.held > 0
+    }
+
+    /// Gate a chunk of audio in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of audio.
+    pub fn process(&mut self, audio: &mut [f32]) {
+        for s in audio {
+            self.envelope = s.abs().max(self.envelope * self.decay);
+            if self.envelope >= self.threshold {
+                self.held = self.hold.max(1);
+            } else {
+                self.held = self.held.saturating_sub(1);
+            }
+            let (target, coef) = if self.held > 0 {
+                (1.0, self.attack)
+            } else {
+                (0.0, self.release)
+            };
+            self.gain = target + coef * (self.gain - target);
+            *s *= self.gain;
+        }
+    }
+
+    /// Close the gate.
+    pub fn reset(&mut self) {
+        self.held = 0;
+        self.envelope = 0.0;
+        self.gain = 0.0;
+    }
+}
+
+/// Over-subtraction of the noise estimate at full strength. The minimum
+/// of the smoothed power sits well below its mean, so this also makes up
+/// for that bias.
+#[cfg(feature = "fft")]
+const OVERSUBTRACTION: f32 = 4.0;
+
+/// The least gain of a bin, so noise is lowered rather than removed, which
+/// keeps the residue from warbling.
+#[cfg(feature = "fft")]
+const GAIN_FLOOR: f32 = 0.1;
+
+/// How fast the noise estimate may rise, in dB per second.
+#[cfg(feature = "fft")]
+const NOISE_RISE_DB: f64 = 3.0;
+
+/// Spectral noise reduction for steady hiss.
+///
+/// Audio is cut into 50% overlapping frames of about 16 ms under a
+/// square-root Hann window. Each bin's noise level follows the minimum of
+/// its smoothed power, rising at most 3 dB a second, so speech doesn't
+/// count as noise but a changing background is learned within seconds; a
+/// tone held for many seconds is learned as noise too.
+/// Each bin is then scaled down by the share of its power the noise
+/// accounts for, and the frames are overlapped back together. The output
+/// lags the input by one frame, `latency` samples.
+#[cfg(feature = "fft")]
+pub struct NoiseReducer {
+    strength: f32,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    hop: usize,
+    input: Vec<f32>,
+    overlap: Vec<f32>,
+    output: Vec<f32>,
+    pos: usize,
+    buf: Vec<Complex<f32>>,
+    smoothed: Vec<f32>,
+    noise: Vec<f32>,
+    rise: f32,
+    learned: bool,
+}
+
+#[cfg(feature = "fft")]
+impl NoiseReducer {
+    /// Create a reducer.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz.
+    /// * `strength` - How hard to reduce, from 0 for not at all to 1.
+    ///
+    /// # Returns
+    ///
+    /// A new `NoiseReducer`.
+    pub fn new(audio_rate: f64, strength: f32) -> Self {
+        let len = ((0.016 * audio_rate) as usize).next_power_of_two().max(64);
+        let hop = len / 2;
+        let mut planner = FftPlanner::new();
+        NoiseReducer {
+            strength: strength.clamp(0.0, 1.0),
+            forward: planner.plan_fft_forward(len),
+            inverse: planner.plan_fft_inverse(len),
+            window: (0..len)
+                .map(|n| (PI * n as f64 / len as f64).sin() as f32)
+                .collect(),
+            hop,
+            input: vec![0.0; hop],
+            overlap: vec![0.0; len],
+            output: vec![0.0; hop],
+            pos: 0,
+            buf: vec![Complex::new(0.0, 0.0); len],
+            smoothed: vec![0.0; len],
+            noise: vec![0.0; len],
+            rise: 10f64.powf(NOISE_RISE_DB / 10.0 * hop as f64 / audio_rate) as f32,
+            learned: false,
+        }
+    }
+
+    /// Get the delay of the output behind the input.
+    ///
+    /// # Returns
+    ///
+    /// The delay in samples.
+    pub fn latency(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Reduce the noise in a chunk of audio in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of audio, replaced by the output
+    ///   `latency` samples behind it.
+    pub fn process(&mut self, audio: &mut [f32]) {
+        for s in audio {
+            self.input.push(*s);
+            *s = self.output[self.pos];
+            self.pos += 1;
+            if self.input.len() == self.window.len() {
+                self.frame();
+                self.input.drain(..self.hop);
+                self.pos = 0;
+            }
+        }
+    }
+
+    fn frame(&mut self) {
+        let len = self.window.len();
+        for ((b, &x), &w) in self.buf.iter_mut().zip(&self.input).zip(&self.window) {
+            *b = Complex::new(x * w, 0.0);
+        }
+        self.forward.process(&mut self.buf);
+        for (k, b) in self.buf.iter_mut().enumerate() {
+            let power = b.norm_sqr();
+            let smoothed = &mut self.smoothed[k];
+            *smoothed = if self.learned {
+                0.8 * *smoothed + 0.2 * power
+            } else {
+                power
+            };
+            let noise = &mut self.noise[k];
+            *noise = if self.learned {
+                smoothed.min(*noise * self.rise)
+            } else {
+                *smoothed
+            };
+            let gain = if power > 0.0 {
+                (1.0 - OVERSUBTRACTION * self.strength * *noise / power).max(GAIN_FLOOR)
+            } else {
+                1.0
+            };
+            *b *= gain.max(1.0 - self.strength);
+        }
+        self.learned = true;
+        self.inverse.process(&mut self.buf);
+        let scale = 1.0 / len as f32;
+        for ((o, b), &w) in self.overlap.iter_mut().zip(&self.buf).zip(&self.window) {
+            *o += b.re * w * scale;
+        }
+        self.output.copy_from_slice(&self.overlap[..self.hop]);
+        self.overlap.copy_within(self.hop.., 0);
+        self.overlap[len - self.hop..].fill(0.0);
+    }
+
+    /// Forget the learned noise and buffered audio.
+    pub fn reset(&mut self) {
+        self.input = vec![0.0; self.hop];
+        self.overlap.fill(0.0);
+        self.output.fill(0.0);
+        self.pos = 0;
+        self.learned = false;
+    }
+}
+
+/// Which audio filters to run on a channel, as kept with a `Preset`.
+///
+/// The default runs none.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AudioFilterConfig {
+    /// Remove CTCSS tones and DCS codes with a `ToneFilter`.
+    pub tone_filter: bool,
+    /// Restrict the audio to a band with a `BandFilter`.
+    pub band: Option<AudioBand>,
+    /// Reduce steady hiss with a `NoiseReducer` of this strength. Needs the
+    /// `fft` feature, and is ignored without it.
+    pub noise_reduction: Option<f32>,
+    /// Gate the audio with a `NoiseGate` opening at this level in dBFS.
+    pub gate_db: Option<f32>,
+}
+
+/// The audio filters of a channel, run in turn on demodulated audio: tone
+/// removal, band limiting, noise reduction and the noise gate.
+///
+/// ```no_run
+/// use radion::demod::{AudioBand, AudioFilterConfig, AudioFilters, FmDemod};
+///
+/// let mut fm = FmDemod::new(16_000.0, 2_500.0);
+/// let mut filters = AudioFilters::new(
+///     16_000.0,
+///     AudioFilterConfig {
+///         tone_filter: true,
+///         band: Some(AudioBand::Voice),
+///         gate_db: Some(-40.0),
+///         ..Default::default()
+///     },
+/// );
+/// # let iq: Vec<radion::dsp::Complex<f32>> = Vec::new();
+/// let mut audio = fm.process(&iq);
+/// filters.process(&mut audio);
+/// ```
+pub struct AudioFilters {
+    config: AudioFilterConfig,
+    tone: Option<ToneFilter>,
+    band: Option<BandFilter>,
+    #[cfg(feature = "fft")]
+    reducer: Option<NoiseReducer>,
+    gate: Option<NoiseGate>,
+}
+
+impl AudioFilters {
+    /// Create the filters a configuration asks for.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz.
+    /// * `config` - The filters to run.
+    ///
+    /// # Returns
+    ///
+    /// A new `AudioFilters`.
+    pub fn new(audio_rate: f64, config: AudioFilterConfig) -> Self {
+        AudioFilters {
+            config,
+            tone: config.tone_filter.then(|| ToneFilter::new(audio_rate)),
+            band: config.band.map(|band| BandFilter::new(audio_rate, band)),
+            #[cfg(feature = "fft")]
+            reducer: config
+                .noise_reduction
+                .map(|strength| NoiseReducer::new(audio_rate, strength)),
+            gate: config.gate_db.map(|db| NoiseGate::new(audio_rate, db)),
+        }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &AudioFilterConfig {
+        &self.config
+    }
+
+    /// Filter a chunk of audio in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of demodulated audio.
+    pub fn process(&mut self, audio: &mut [f32]) {
+        if let Some(tone) = &mut self.tone {
+            tone.process(audio);
+        }
+        if let Some(band) = &mut self.band {
+            band.process(audio);
+        }
+        #[cfg(feature = "fft")]
+        if let Some(reducer) = &mut self.reducer {
+            reducer.process(audio);
+        }
+        if let Some(gate) = &mut self.gate {
+            gate.process(audio);
+        }
+    }
+
+    /// Clear every filter's history, e.g. after retuning.
+    pub fn reset(&mut self) {
+        if let Some(tone) = &mut self.tone {
+            tone.reset();
+        }
+        if let Some(band) = &mut self.band {
+            band.reset();
+        }
+        #[cfg(feature = "fft")]
+        if let Some(reducer) = &mut self.reducer {
+            reducer.reset();
+        }
+        if let Some(gate) = &mut self.gate {
+            gate.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 16_000.0;
+
+    fn tone(freq: f64, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| amplitude * (TAU * freq * n as f64 / RATE).sin() as f32)
+            .collect()
+    }
+
+    /// Uniform noise in -amplitude..amplitude from a fixed seed.
+    fn noise(amplitude: f32, len: usize) -> Vec<f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                amplitude * ((state >> 8) as f32 / (1 << 23) as f32 - 1.0)
+            })
+            .collect()
+    }
+
+    /// Gain in dB over the second half of a filtered tone.
+    fn gain_db(process: &mut dyn FnMut(&mut [f32]), freq: f64) -> f32 {
+        let mut audio = tone(freq, 0.5, 16_000);
+        process(&mut audio);
+        let rms = |s: &[f32]| (s.iter().map(|x| x * x).sum::<f32>() / s.len() as f32).sqrt();
+        20.0 * (rms(&audio[8_000..]) / rms(&tone(freq, 0.5, 8_000))).log10()
+    }
+
+    #[test]
+    fn tone_filter_removes_sub_audible_tones() {
+        for (freq, low, high) in [
+            (67.0, f32::MIN, -60.0),
+            (150.0, f32::MIN, -30.0),
+            (200.0, f32::MIN, -20.0),
+            (1_000.0, -0.5, 0.5),
+        ] {
+            let mut filter = ToneFilter::new(RATE);
+            let db = gain_db(&mut |a| filter.process(a), freq);
+            assert!(db > low && db < high, "{} dB at {} Hz", db, freq);
+        }
+    }
+
+    #[test]
+    fn band_filters_pass_their_band() {
+        for (band, freq, low, high) in [
+            (AudioBand::Voice, 1_000.0, -0.5, 0.5),
+            (AudioBand::Voice, 100.0, f32::MIN, -15.0),
+            (AudioBand::Voice, 6_000.0, f32::MIN, -30.0),
+            (
+                AudioBand::Cw {
+                    pitch_hz: 700.0,
+                    width_hz: 500.0,
+                },
+                700.0,
+                -0.5,
+                0.5,
+            ),
+            (
+                AudioBand::Cw {
+                    pitch_hz: 700.0,
+                    width_hz: 500.0,
+                },
+                950.0,
+                -4.0,
+                -2.0,
+            ),
+            (
+                AudioBand::Cw {
+                    pitch_hz: 700.0,
+                    width_hz: 500.0,
+                },
+                2_000.0,
+                f32::MIN,
+                -15.0,
+            ),
+        ] {
+            let mut filter = BandFilter::new(RATE, band);
+            let db = gain_db(&mut |a| filter.process(a), freq);
+            assert!(
+                db > low && db < high,
+                "{} dB at {} Hz for {:?}",
+                db,
+                freq,
+                band
+            );
+        }
+    }
+
+    #[test]
+    fn gate_mutes_quiet_audio() {
+        let mut gate = NoiseGate::new(RATE, -30.0);
+        let mut audio = noise(0.01, 8_000);
+        audio.extend(tone(1_000.0, 0.5, 8_000));
+        audio.extend(noise(0.01, 16_000));
+        gate.process(&mut audio);
+        let peak = |s: &[f32]| s.iter().fold(0f32, |m, x| m.max(x.abs()));
+        assert_eq!(peak(&audio[..8_000]), 0.0);
+        assert!(peak(&audio[8_100..16_000]) > 0.49);
+        // Held open for 100 ms, then faded out.
+        assert!(peak(&audio[16_000..17_500]) > 0.001);
+        assert!(peak(&audio[24_000..]) < 1e-6);
+        assert!(!gate.is_open());
+    }
+
+    #[cfg(feature = "fft")]
+    #[test]
+    fn noise_reducer_lowers_hiss_and_keeps_tones() {
+        let power = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>() / s.len() as f32;
+        let mut reducer = NoiseReducer::new(RATE, 1.0);
+        let mut hiss = noise(0.1, 32_000);
+        let before = power(&hiss[16_000..]);
+        reducer.process(&mut hiss);
+        let after = power(&hiss[16_000..]);
+        let db = 10.0 * (after / before).log10();
+        assert!(db < -8.0, "{} dB", db);
+
+        // A burst of tone after a second of hiss, like speech after a
+        // pause, comes through a frame late with the hiss around it lowered.
+        let mut reducer = NoiseReducer::new(RATE, 1.0);
+        let mut clean = vec![0.0; 16_000];
+        clean.extend(tone(1_000.0, 0.5, 8_000));
+        let mut audio: Vec<f32> = clean
+            .iter()
+            .zip(noise(0.1, 24_000))
+            .map(|(a, b)| a + b)
+            .collect();
+        reducer.process(&mut audio);
+        let lag = reducer.latency();
+        let clean = &clean[17_000 - lag..24_000 - lag];
+        let error: Vec<f32> = audio[17_000..]
+            .iter()
+            .zip(clean)
+            .map(|(a, b)| a - b)
+            .collect();
+        let snr = 10.0 * (power(clean) / power(&error)).log10();
+        let input_snr = 10.0 * (power(clean) / before).log10();
+        assert!(snr > input_snr + 5.0, "{} dB snr, {} dB in", snr, input_snr);
+    }
+
+    #[test]
+    fn runs_the_configured_chain() {
+        let mut filters = AudioFilters::new(
+            RATE,
+            AudioFilterConfig {
+                tone_filter: true,
+                band: Some(AudioBand::Voice),
+                gate_db: Some(-30.0),
+                ..Default::default()
+            },
+        );
+        assert!(filters.config().tone_filter);
+        // A loud CTCSS tone alone is filtered below the gate.
+        let mut audio = tone(100.0, 0.5, 16_000);
+        filters.process(&mut audio);
+        assert!(audio[8_000..].iter().all(|s| s.abs() < 1e-3));
+        filters.reset();
+        let db = gain_db(&mut |a| filters.process(a), 1_000.0);
+        assert!(db.abs() < 1.0, "{} dB", db);
+    }
+}
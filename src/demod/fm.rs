@@ -0,0 +1,250 @@
+use std::f64::consts::TAU;
+
+use crate::dsp::fir::{kaiser_params, low_pass, Decimator, Window};
+use crate::dsp::Complex;
+
+/// Quadrature FM discriminator.
+///
+/// Produces the instantaneous frequency normalised to the peak deviation, so
+/// a fully deviated signal swings between -1.0 and 1.0. For broadcast FM the
+/// output is the stereo multiplex that `FmStereo` decodes.
+pub struct FmDemod {
+    /// Radians of phase step per unit of output.
+    scale: f32,
+    prev: Complex<f32>,
+}
+
+impl FmDemod {
+    /// Create a discriminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `deviation` - The peak deviation in Hz, 75 kHz for broadcast FM and
+    ///   2.5-5 kHz for narrowband voice.
+    ///
+    /// # Returns
+    ///
+    /// A new `FmDemod`.
+    pub fn new(sample_rate: f64, deviation: f64) -> Self {
+        FmDemod {
+            scale: (sample_rate / (TAU * deviation)) as f32,
+            prev: Complex::new(0.0, 0.0),
+        }
+    }
+
+    /// Demodulate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - Baseband samples with the carrier at DC.
+    ///
+    /// # Returns
+    ///
+    /// One output sample per input sample, at the input rate.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<f32> {
+        iq.iter()
+            .map(|&s| {
+                let d = (s * self.prev.conj()).arg() * self.scale;
+                self.prev = s;
+                d
+            })
+            .collect()
+    }
+
+    /// Clear the discriminator state.
+    pub fn reset(&mut self) {
+        self.prev = Complex::new(0.0, 0.0);
+    }
+}
+
+/// Broadcast FM de-emphasis time constant.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Deemphasis {
+    None,
+    /// 50 us, used in Europe, Asia and Australia.
+    Us50,
+    /// 75 us, used in the Americas and South Korea.
+    Us75,
+}
+
+impl Deemphasis {
+    fn tau(self) -> Option<f64> {
+        match self {
+            Deemphasis::None => None,
+            Deemphasis::Us50 => Some(50e-6),
+            Deemphasis::Us75 => Some(75e-6),
+        }
+    }
+}
+
+/// Pilot frequency of the stereo multiplex.
+const PILOT_HZ: f64 = 19_000.0;
+
+/// Loop bandwidth of the pilot PLL in Hz.
+const PLL_BANDWIDTH_HZ: f64 = 20.0;
+
+/// Smallest pilot amplitude, relative to full deviation, that counts as
+/// present. Broadcasters use about 0.09.
+const PILOT_MIN_LEVEL: f32 = 0.02;
+
+/// FM stereo decoder.
+///
+/// A PLL locks to the 19 kHz pilot in the multiplex from `FmDemod`; its
+/// doubled phase coherently demodulates the L-R signal on the 38 kHz
+/// subcarrier. L+R and L-R are low-pass filtered to 15 kHz, decimated to the
+/// audio rate, matrixed into left and right and de-emphasised. Without a
+/// locked pilot both channels carry the mono signal.
+pub struct FmStereo {
+    mpx_rate: f64,
+    /// NCO phase in radians.
+    phase: f64,
+    /// NCO frequency in radians per sample.
+    freq: f64,
+    nominal: f64,
+    kp: f64,
+    ki: f64,
+    /// Pilot mixed to DC and low-pass filtered.
+    pilot: Complex<f32>,
+    pilot_alpha: f32,
+    /// Smoothed cosine of the phase error, near 1.0 when locked.
+    lock: f32,
+    sum: Decimator<f32>,
+    diff: Decimator<f32>,
+    diff_in: Vec<f32>,
+    deemphasis: Option<f32>,
+    state: [f32; 2],
+}
+
+impl FmStereo {
+    /// Create a stereo decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `mpx_rate` - Sample rate of the multiplex in Hz; at least 120 kHz
+    ///   so the 38 kHz subcarrier fits.
+    /// * `audio_rate` - The output rate in Hz. `mpx_rate` should be an
+    ///   integer multiple of it.
+    /// * `deemphasis` - The de-emphasis used in the listening region.
+    ///
+    /// # Returns
+    ///
+    /// A new `FmStereo`.
+    pub fn new(mpx_rate: f64, audio_rate: f64, deemphasis: Deemphasis) -> Self {
+        let factor = (mpx_rate / audio_rate).round().max(1.0) as usize;
+        let audio_rate = mpx_rate / factor as f64;
+        let cutoff = 15_000f64.min(0.45 * audio_rate);
+        let (taps, beta) = kaiser_params((PILOT_HZ - 2000.0 - cutoff).max(1000.0) / mpx_rate, 50.0);
+        let h = low_pass(cutoff / mpx_rate, taps, Window::Kaiser(beta));
+
+        // Critically damped second-order loop.
+        let wn = TAU * PLL_BANDWIDTH_HZ / mpx_rate;
+        let nominal = TAU * PILOT_HZ / mpx_rate;
+        FmStereo {
+            mpx_rate,
+            phase: 0.0,
+            freq: nominal,
+            nominal,
+            kp: 2.0 * 0.707 * wn,
+            ki: wn * wn,
+            pilot: Complex::new(0.0, 0.0),
+            pilot_alpha: (TAU * 4.0 * PLL_BANDWIDTH_HZ / mpx_rate) as f32,
+            lock: 0.0,
+            sum: Decimator::new(factor, &h),
+            diff: Decimator::new(factor, &h),
+            diff_in: Vec::new(),
+            deemphasis: deemphasis
+                .tau()
+                .map(|tau| (1.0 - (-1.0 / (tau * audio_rate)).exp()) as f32),
+            state: [0.0; 2],
+        }
+    }
+
+    /// Check whether the pilot PLL is locked.
+    ///
+    /// # Returns
+    ///
+    /// `true` while a pilot is present and tracked, i.e. while the output is
+    /// stereo.
+    pub fn is_pilot_locked(&self) -> bool {
+        self.lock > 0.9 && self.pilot.norm() > PILOT_MIN_LEVEL / 2.0
+    }
+
+    /// Get the tracked pilot level.
+    ///
+    /// # Returns
+    ///
+    /// The pilot amplitude relative to full deviation.
+    pub fn pilot_level(&self) -> f32 {
+        2.0 * self.pilot.norm()
+    }
+
+    /// Get the pilot frequency the PLL is tracking.
+    ///
+    /// # Returns
+    ///
+    /// The NCO frequency in Hz.
+    pub fn pilot_frequency(&self) -> f64 {
+        self.freq * self.mpx_rate / TAU
+    }
+
+    /// Decode a chunk of the multiplex.
+    ///
+    /// # Arguments
+    ///
+    /// * `mpx` - Output of `FmDemod` for a broadcast station.
+    ///
+    /// # Returns
+    ///
+    /// `[left, right]` frames at the audio rate.
+    pub fn process(&mut self, mpx: &[f32]) -> Vec<[f32; 2]> {
+        self.diff_in.clear();
+        for &x in mpx {
+            let (sin, cos) = self.phase.sin_cos();
+            let z = Complex::new(x * cos as f32, -x * sin as f32);
+            self.pilot += (z - self.pilot) * self.pilot_alpha;
+            let err = self.pilot.im.atan2(self.pilot.re) as f64;
+            self.lock += (err.cos() as f32 - self.lock) * self.pilot_alpha;
+
+            // The pilot is transmitted as sin(wt) and the subcarrier as
+            // sin(2wt). Locking cos(phase) to the pilot puts phase at
+            // wt - pi/2, so the subcarrier is -sin(2 * phase).
+            self.diff_in
+                .push(-2.0 * x * (2.0 * self.phase).sin() as f32);
+
+            self.freq += self.ki * err;
+            self.freq = self.freq.clamp(0.99 * self.nominal, 1.01 * self.nominal);
+            self.phase = (self.phase + self.freq + self.kp * err) % TAU;
+        }
+
+        let sum = self.sum.decimate(mpx);
+        let diff = self.diff.decimate(&self.diff_in);
+        let stereo = self.is_pilot_locked();
+
+        sum.iter()
+            .zip(&diff)
+            .map(|(&m, &s)| {
+                let s = if stereo { s } else { 0.0 };
+                let mut frame = [m + s, m - s];
+                if let Some(a) = self.deemphasis {
+                    for (y, st) in frame.iter_mut().zip(self.state.iter_mut()) {
+                        *st += a * (*y - *st);
+                        *y = *st;
+                    }
+                }
+                frame
+            })
+            .collect()
+    }
+
+    /// Clear the PLL, filter and de-emphasis state.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.freq = self.nominal;
+        self.pilot = Complex::new(0.0, 0.0);
+        self.lock = 0.0;
+        self.sum.reset();
+        self.diff.reset();
+        self.state = [0.0; 2];
+    }
+}
@@ -0,0 +1,14 @@
+//! Demodulators turning baseband IQ into audio, and filters for the
+//! audio they produce.
+
+mod am;
+mod filters;
+mod fm;
+mod ssb;
+
+pub use am::AmDemod;
+#[cfg(feature = "fft")]
+pub use filters::NoiseReducer;
+pub use filters::{AudioBand, AudioFilterConfig, AudioFilters, BandFilter, NoiseGate, ToneFilter};
+pub use fm::{Deemphasis, FmDemod, FmStereo};
+pub use ssb::{Sideband, SsbDemod};
@@ -0,0 +1,189 @@
+use std::f64::consts::TAU;
+
+use crate::dsp::fir::{kaiser_params, low_pass, Decimator, Window};
+use crate::dsp::Complex;
+
+/// The sideband `SsbDemod` recovers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sideband {
+    Upper,
+    Lower,
+}
+
+/// Single-sideband demodulator for HF voice and data.
+///
+/// The passband is selected with the Weaver method, which is equivalent to a
+/// complex band-pass filter: the centre of the wanted sideband is shifted to
+/// DC, low-pass filtered and decimated to the audio rate, then shifted back up
+/// and the real part taken. The opposite sideband is rejected by the low-pass
+/// filter's stopband.
+pub struct SsbDemod {
+    sample_rate: f64,
+    factor: usize,
+    sideband: Sideband,
+    low_cut: f64,
+    bandwidth: f64,
+    bfo: f64,
+    /// Input mixer phase in cycles.
+    in_phase: f64,
+    /// Output mixer phase in cycles.
+    out_phase: f64,
+    filter: Decimator<Complex<f32>>,
+    mixed: Vec<Complex<f32>>,
+    baseband: Vec<Complex<f32>>,
+}
+
+impl SsbDemod {
+    /// Create a demodulator with a 300-2700 Hz voice passband.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `audio_rate` - The output rate in Hz. `sample_rate` should be an
+    ///   integer multiple of it.
+    /// * `sideband` - The sideband to receive.
+    ///
+    /// # Returns
+    ///
+    /// A new `SsbDemod`.
+    pub fn new(sample_rate: f64, audio_rate: f64, sideband: Sideband) -> Self {
+        let factor = (sample_rate / audio_rate).round().max(1.0) as usize;
+        let mut demod = SsbDemod {
+            sample_rate,
+            factor,
+            sideband,
+            low_cut: 300.0,
+            bandwidth: 2400.0,
+            bfo: 0.0,
+            in_phase: 0.0,
+            out_phase: 0.0,
+            filter: Decimator::new(factor, &[1.0]),
+            mixed: Vec::new(),
+            baseband: Vec::new(),
+        };
+        demod.design();
+        demod
+    }
+
+    fn design(&mut self) {
+        let audio_rate = self.audio_rate();
+        let cutoff = self.bandwidth / 2.0;
+        let transition = (0.1 * self.bandwidth).max(50.0) / self.sample_rate;
+        let (taps, beta) = kaiser_params(transition, 60.0);
+        let cutoff = cutoff.min(0.45 * audio_rate) / self.sample_rate;
+        self.filter = Decimator::new(self.factor, &low_pass(cutoff, taps, Window::Kaiser(beta)));
+    }
+
+    /// Get the output sample rate.
+    ///
+    /// # Returns
+    ///
+    /// The audio rate in Hz, rounded to an integer fraction of the input rate.
+    pub fn audio_rate(&self) -> f64 {
+        self.sample_rate / self.factor as f64
+    }
+
+    /// Select the sideband to receive.
+    ///
+    /// # Arguments
+    ///
+    /// * `sideband` - The sideband to receive.
+    pub fn set_sideband(&mut self, sideband: Sideband) {
+        self.sideband = sideband;
+    }
+
+    /// Set the audio passband.
+    ///
+    /// # Arguments
+    ///
+    /// * `low_cut` - Lowest audio frequency passed in Hz, e.g. 300 for voice.
+    /// * `bandwidth` - Width of the passband in Hz, e.g. 2400 for voice or
+    ///   500 for CW.
+    pub fn set_passband(&mut self, low_cut: f64, bandwidth: f64) {
+        self.low_cut = low_cut;
+        self.bandwidth = bandwidth;
+        self.design();
+    }
+
+    /// Set the beat frequency oscillator offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `bfo` - Frequency of the suppressed carrier in the input in Hz. For
+    ///   quadrature input this is the offset from DC used for fine tuning;
+    ///   for real input it is the carrier frequency itself.
+    pub fn set_bfo(&mut self, bfo: f64) {
+        self.bfo = bfo;
+    }
+
+    /// Demodulate quadrature samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - Baseband samples with the suppressed carrier at the BFO
+    ///   offset.
+    ///
+    /// # Returns
+    ///
+    /// Audio samples at the audio rate.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<f32> {
+        let mut mixed = std::mem::take(&mut self.mixed);
+        mixed.clear();
+        mixed.extend_from_slice(iq);
+        let audio = self.finish(&mut mixed);
+        self.mixed = mixed;
+        audio
+    }
+
+    /// Demodulate real samples, as produced by direct sampling.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Real samples with the suppressed carrier at the BFO
+    ///   frequency.
+    ///
+    /// # Returns
+    ///
+    /// Audio samples at the audio rate.
+    pub fn process_real(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut mixed = std::mem::take(&mut self.mixed);
+        mixed.clear();
+        mixed.extend(samples.iter().map(|&s| Complex::new(s, 0.0)));
+        let audio = self.finish(&mut mixed);
+        self.mixed = mixed;
+        audio
+    }
+
+    fn finish(&mut self, mixed: &mut [Complex<f32>]) -> Vec<f32> {
+        let centre = match self.sideband {
+            Sideband::Upper => self.low_cut + self.bandwidth / 2.0,
+            Sideband::Lower => -(self.low_cut + self.bandwidth / 2.0),
+        };
+
+        let step = (self.bfo + centre) / self.sample_rate;
+        for s in mixed.iter_mut() {
+            *s *= Complex::from_polar(1.0, (-TAU * self.in_phase) as f32);
+            self.in_phase = (self.in_phase + step).fract();
+        }
+
+        self.baseband.clear();
+        self.filter.decimate_into(mixed, &mut self.baseband);
+
+        let step = centre / self.audio_rate();
+        self.baseband
+            .iter()
+            .map(|&s| {
+                let out = (s * Complex::from_polar(1.0, (TAU * self.out_phase) as f32)).re;
+                self.out_phase = (self.out_phase + step).fract();
+                out
+            })
+            .collect()
+    }
+
+    /// Clear the filter and oscillator state.
+    pub fn reset(&mut self) {
+        self.filter.reset();
+        self.in_phase = 0.0;
+        self.out_phase = 0.0;
+    }
+}
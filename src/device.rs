@@ -1,18 +1,34 @@
+use crate::device_info::DeviceInfo;
+use crate::eeprom::{BrickRisk, EepromProgress};
 use crate::error::{Error, Result};
 use crate::ffi::*;
 use crate::hw_info::HwInfo;
-use crate::tuner::RTLSDRTuner;
-use crate::utils::{
-    parse_string_descriptors, serialize_string_descriptors, EEPROM_SIZE, STR_OFFSET_START,
-};
+use crate::pool::{BufferPool, PooledBuffer};
+use crate::ring::{OverrunPolicy, RingReader};
+use crate::stats::{GapDetector, StreamCounters, StreamStats, DEFAULT_BUF_LEN};
+#[cfg(feature = "tokio")]
+use crate::stream::SampleStream;
+use crate::stream::{Samples, StreamHandle};
+use crate::sym;
+use crate::timing::{ArrivalClock, TimedSamples};
+use crate::tuner::{RTLSDRTuner, SamplingMode, TunerCapabilities};
+use crate::usb;
+use crate::utils::{parse_hw_info, serialize_hw_info, EEPROM_PAGE_SIZE, EEPROM_SIZE};
+use std::any::Any;
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_int, c_void};
-use std::ptr;
+use std::os::raw::{c_char, c_int, c_uchar, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::{mem, ptr, slice};
 
 pub struct Device {
     dev: *mut RTLSDRDevT,
+    stats: Arc<StreamCounters>,
 }
 
+// librtlsdr handles are not tied to the thread that opened them.
+unsafe impl Send for Device {}
+
 impl Device {
     /// Open a RTL-SDR device by index.
     ///
@@ -22,15 +38,20 @@ impl Device {
     ///
     /// # Returns
     ///
-    /// A new `Device` instance if successful, otherwise an `Error`.
+    /// A new `Device` instance if successful, otherwise an `Error`. On
+    /// Windows, a device without the WinUSB driver gives
+    /// `Error::DriverMissing`.
     pub fn new(index: u32) -> Result<Self> {
         unsafe {
             let mut dev: *mut RTLSDRDevT = ptr::null_mut();
             let err = rtlsdr_open(&mut dev, index);
             if err == 0 {
-                Ok(Device { dev })
+                Ok(Device {
+                    dev,
+                    stats: Arc::default(),
+                })
             } else {
-                Err(Error::from(err))
+                Err(open_error(Error::from(err)))
             }
         }
     }
@@ -64,17 +85,37 @@ impl Device {
         }
     }
 
-    /// Get the USB strings of the device.
+    /// Get the USB strings of the open device.
+    ///
+    /// librtlsdr's `rtlsdr_get_device_usb_strings` takes a device index, not
+    /// a handle, so this is kept only for compatibility and returns
+    /// `get_usb_strings`.
     ///
     /// # Returns
     ///
     /// The manufacturer, product, and serial strings of the device.
+    #[deprecated(
+        note = "use `get_usb_strings` on an open device or `get_device_usb_strings_by_index`"
+    )]
     pub fn get_device_usb_strings(&self) -> Result<(String, String, String)> {
+        self.get_usb_strings()
+    }
+
+    /// Get the USB strings of a device by index, without opening it.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the device.
+    ///
+    /// # Returns
+    ///
+    /// The manufacturer, product, and serial strings of the device.
+    pub fn get_device_usb_strings_by_index(index: u32) -> Result<(String, String, String)> {
         let mut m: [c_char; 256] = [0; 256];
         let mut p: [c_char; 256] = [0; 256];
         let mut s: [c_char; 256] = [0; 256];
         let ret = unsafe {
-            rtlsdr_get_device_usb_strings(self.dev, m.as_mut_ptr(), p.as_mut_ptr(), s.as_mut_ptr())
+            rtlsdr_get_device_usb_strings(index, m.as_mut_ptr(), p.as_mut_ptr(), s.as_mut_ptr())
         };
         if ret == 0 {
             let manufact = unsafe { CStr::from_ptr(m.as_ptr()) }
@@ -92,6 +133,103 @@ impl Device {
         }
     }
 
+    /// List the available devices without opening them.
+    ///
+    /// # Returns
+    ///
+    /// A `DeviceInfo` for every device librtlsdr can see. Devices whose USB
+    /// strings cannot be read (e.g. because of missing permissions) are listed
+    /// with empty strings.
+    pub fn list() -> Vec<DeviceInfo> {
+        let count = Self::get_device_count();
+        // Only trust the paths if libusb saw the same devices.
+        let paths = usb::device_paths().filter(|paths| paths.len() == count as usize);
+        (0..count)
+            .map(|index| {
+                let (manufact, product, serial) =
+                    Self::get_device_usb_strings_by_index(index).unwrap_or_default();
+                DeviceInfo {
+                    index,
+                    name: Self::get_device_name(index).unwrap_or_default(),
+                    manufact,
+                    product,
+                    serial,
+                    usb_path: paths
+                        .as_ref()
+                        .map(|paths| paths[index as usize].clone())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Open the only device with the given serial number.
+    ///
+    /// Many dongles leave the factory with the same serial, commonly
+    /// `00000001`. Give each one a unique serial with `set_hw_info` before
+    /// relying on this, or tell them apart by the port they are plugged into
+    /// with `open_by_usb_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - The serial number of the device.
+    ///
+    /// # Returns
+    ///
+    /// The opened `Device`, `Error::NotFound` if no device has the serial or
+    /// `Error::AmbiguousDevice` if several do.
+    pub fn open_by_serial(serial: &str) -> Result<Self> {
+        Self::open_unique(|info| info.serial == serial)
+    }
+
+    /// Open the only device with the given name, as reported by
+    /// `get_device_name`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the device.
+    ///
+    /// # Returns
+    ///
+    /// The opened `Device`, `Error::NotFound` if no device has the name or
+    /// `Error::AmbiguousDevice` if several do.
+    pub fn open_by_name(name: &str) -> Result<Self> {
+        Self::open_unique(|info| info.name == name)
+    }
+
+    /// Open the device plugged into the given USB port.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The device's `DeviceInfo::usb_path` from `list`, e.g.
+    ///   `1-4.2`.
+    ///
+    /// # Returns
+    ///
+    /// The opened `Device`, or `Error::NotFound` if no device is plugged in
+    /// there or the USB topology could not be read.
+    pub fn open_by_usb_path(path: &str) -> Result<Self> {
+        Self::open_unique(|info| !info.usb_path.is_empty() && info.usb_path == path)
+    }
+
+    fn open_unique<F>(matches: F) -> Result<Self>
+    where
+        F: Fn(&DeviceInfo) -> bool,
+    {
+        let found: Vec<u32> = Self::list()
+            .iter()
+            .filter(|info| matches(info))
+            .map(|info| info.index)
+            .collect();
+        match found[..] {
+            [] => Err(Error::NotFound),
+            [index] => Self::new(index),
+            _ => Err(Error::AmbiguousDevice {
+                matches: found.len(),
+            }),
+        }
+    }
+
     /// Get the index of the device by serial number.
     ///
     /// # Arguments
@@ -228,6 +366,89 @@ impl Device {
         }
     }
 
+    /// Read data from the EEPROM of the device page by page.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset to read the data from.
+    /// * `len` - The length of the data to read.
+    /// * `progress` - Called after every page with the bytes read so far.
+    ///
+    /// # Returns
+    ///
+    /// A vector of data read from the EEPROM.
+    pub fn read_eeprom_chunked<F>(&self, offset: u8, len: u16, mut progress: F) -> Result<Vec<u8>>
+    where
+        F: FnMut(EepromProgress),
+    {
+        check_eeprom_range(offset as usize, len as usize)?;
+        let mut v = Vec::with_capacity(len as usize);
+        for (start, end) in eeprom_pages(offset as usize, len as usize) {
+            v.extend(self.read_eeprom(start as u8, (end - start) as u16)?);
+            progress(EepromProgress {
+                done: v.len(),
+                total: len as usize,
+            });
+        }
+        Ok(v)
+    }
+
+    /// Write data to the EEPROM of the device page by page, verifying every
+    /// page.
+    ///
+    /// Pages that already hold the requested data are skipped. A page that
+    /// fails to write or reads back differently is retried up to `retries`
+    /// times, so an interrupted or flaky write can be resumed by calling this
+    /// again with the same data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to write to the EEPROM.
+    /// * `offset` - The offset to write the data to.
+    /// * `retries` - How many times to retry a failing page.
+    /// * `progress` - Called after every page with the bytes written so far.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`. A page that never
+    /// verifies is reported as `Error::EepromVerify`.
+    pub fn write_eeprom_verified<F>(
+        &self,
+        data: &[u8],
+        offset: u8,
+        retries: u32,
+        mut progress: F,
+    ) -> Result<()>
+    where
+        F: FnMut(EepromProgress),
+    {
+        check_eeprom_range(offset as usize, data.len())?;
+        for (start, end) in eeprom_pages(offset as usize, data.len()) {
+            let page = &data[start - offset as usize..end - offset as usize];
+            let mut attempt = 0;
+            loop {
+                let current = self.read_eeprom(start as u8, page.len() as u16);
+                if matches!(&current, Ok(current) if current == page) {
+                    break;
+                }
+                if attempt > retries {
+                    return Err(match current {
+                        Ok(_) => Error::EepromVerify { offset: start },
+                        Err(e) => e,
+                    });
+                }
+                // A failed write shows up as a mismatch on the next read.
+                let _ = self.write_eeprom(page, start as u8);
+                attempt += 1;
+            }
+            progress(EepromProgress {
+                done: end - offset as usize,
+                total: data.len(),
+            });
+        }
+        Ok(())
+    }
+
     /// Set the sample rate of the device.
     ///
     /// # Arguments
@@ -302,6 +523,16 @@ impl Device {
         RTLSDRTuner::try_from(tuner_type)
     }
 
+    /// Get the static capabilities of the device's tuner.
+    ///
+    /// # Returns
+    ///
+    /// The gain table, tuning range and gain accuracy notes of the tuner if
+    /// successful, otherwise an `Error`.
+    pub fn tuner_capabilities(&self) -> Result<TunerCapabilities> {
+        Ok(self.get_tuner_type()?.capabilities())
+    }
+
     /// Get the tuner gain mode of the device.
     ///
     /// # Returns
@@ -482,13 +713,17 @@ impl Device {
     ///
     /// # Arguments
     ///
-    /// * `on` - The direct sampling mode to set.
+    /// * `mode` - The direct sampling mode to set. HF setups with the antenna
+    ///   on the Q branch (e.g. RTL-SDR Blog V3) need `SamplingMode::QADC`.
     ///
     /// # Returns
     ///
     /// An `Ok` result if successful, otherwise an `Error`.
-    pub fn set_direct_sampling(&self, on: bool) -> Result<()> {
-        let ret = unsafe { rtlsdr_set_direct_sampling(self.dev, on as c_int) };
+    pub fn set_direct_sampling(&self, mode: SamplingMode) -> Result<()> {
+        if mode == SamplingMode::Error {
+            return Err(Error::InvalidParam);
+        }
+        let ret = unsafe { rtlsdr_set_direct_sampling(self.dev, c_int::from(mode)) };
         if ret == 0 {
             Ok(())
         } else {
@@ -496,15 +731,15 @@ impl Device {
         }
     }
 
-    /// Get the direct sampling state of the device.
+    /// Get the direct sampling mode of the device.
     ///
     /// # Returns
     ///
-    /// The device's direct sampling state.
-    pub fn get_direct_sampling(&self) -> Result<bool> {
+    /// The device's direct sampling mode.
+    pub fn get_direct_sampling(&self) -> Result<SamplingMode> {
         let ret = unsafe { rtlsdr_get_direct_sampling(self.dev) };
         if ret >= 0 {
-            Ok(ret != 0)
+            SamplingMode::try_from(ret)
         } else {
             Err(Error::from(ret))
         }
@@ -542,6 +777,57 @@ impl Device {
         }
     }
 
+    /// Enable or disable the bias tee on GPIO 0.
+    ///
+    /// Only librtlsdr builds that export `rtlsdr_set_bias_tee` (e.g. the
+    /// rtl-sdr-blog fork and upstream 0.6.0+) support this; the symbol is
+    /// looked up at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - Whether to power the antenna port.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, `Error::NotSupported` if librtlsdr lacks
+    /// bias tee support, otherwise an `Error`.
+    pub fn set_bias_tee(&self, on: bool) -> Result<()> {
+        let addr = sym::lookup(c"rtlsdr_set_bias_tee").ok_or(Error::NotSupported)?;
+        let ret = unsafe {
+            let f: SetBiasTeeT = mem::transmute(addr);
+            f(self.dev, on as c_int)
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(Error::from(ret))
+        }
+    }
+
+    /// Enable or disable a bias tee driven by an arbitrary GPIO.
+    ///
+    /// # Arguments
+    ///
+    /// * `gpio` - The GPIO pin driving the bias tee.
+    /// * `on` - Whether to power the antenna port.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, `Error::NotSupported` if librtlsdr lacks
+    /// `rtlsdr_set_bias_tee_gpio`, otherwise an `Error`.
+    pub fn set_bias_tee_gpio(&self, gpio: i32, on: bool) -> Result<()> {
+        let addr = sym::lookup(c"rtlsdr_set_bias_tee_gpio").ok_or(Error::NotSupported)?;
+        let ret = unsafe {
+            let f: SetBiasTeeGpioT = mem::transmute(addr);
+            f(self.dev, gpio, on as c_int)
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(Error::from(ret))
+        }
+    }
+
     /// Reset the buffer of the device.
     ///
     /// # Returns
@@ -558,6 +844,9 @@ impl Device {
 
     /// Read data from the device synchronously.
     ///
+    /// A single USB transfer may return fewer bytes than requested, in which
+    /// case the returned vector is shorter than `length`.
+    ///
     /// # Arguments
     ///
     /// * `length` - The length of the data to read.
@@ -567,23 +856,83 @@ impl Device {
     /// A vector of data read from the device.
     pub fn read_sync(&self, length: usize) -> Result<Vec<u8>> {
         let mut buffer = vec![0u8; length];
+        let n_read = self.read_sync_into(&mut buffer)?;
+        buffer.truncate(n_read);
+        Ok(buffer)
+    }
+
+    /// Read exactly `length` bytes from the device synchronously.
+    ///
+    /// Short transfers are retried until the buffer is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - The length of the data to read.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `length` bytes, or `Error::PartialRead` if the device
+    /// stopped delivering data before the buffer was full.
+    pub fn read_sync_exact(&self, length: usize) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; length];
+        let mut read = 0;
+        while read < length {
+            let n_read = self.read_sync_into(&mut buffer[read..])?;
+            if n_read == 0 {
+                return Err(Error::PartialRead {
+                    read,
+                    requested: length,
+                });
+            }
+            read += n_read;
+        }
+        Ok(buffer)
+    }
+
+    pub(crate) fn read_sync_into(&self, buffer: &mut [u8]) -> Result<usize> {
+        let mut n_read: c_int = 0;
         let ret = unsafe {
             rtlsdr_read_sync(
                 self.dev,
                 buffer.as_mut_ptr() as *mut c_void,
-                length as c_int,
-                ptr::null_mut(),
+                buffer.len() as c_int,
+                &mut n_read,
             )
         };
         if ret == 0 {
-            Ok(buffer)
+            Ok((n_read.max(0) as usize).min(buffer.len()))
         } else {
             Err(Error::from(ret))
         }
     }
 
+    /// Iterate over the device's IQ samples in chunks.
+    ///
+    /// The device buffer is reset before the first chunk, so the iterator can
+    /// be used directly after configuring the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_len` - The length in bytes of each chunk. librtlsdr requires a
+    ///   multiple of 512.
+    ///
+    /// # Returns
+    ///
+    /// An iterator yielding one `Result` per chunk read.
+    pub fn samples(&self, chunk_len: usize) -> Samples<'_> {
+        Samples::new(self, chunk_len)
+    }
+
     /// Wait for asynchronous data to be read from the device.
     ///
+    /// `rtlsdr_wait_async` is deprecated in librtlsdr. Use `read_async_with`
+    /// or `start_async`, or `radion::raw::wait_async` if the old semantics are
+    /// really needed.
+    ///
+    /// This is not marked `unsafe` for compatibility, but `ctx` is handed to
+    /// `callback` as-is and must stay valid for whatever `callback` does with
+    /// it until this call returns.
+    ///
     /// # Arguments
     ///
     /// * `callback` - The callback function to call when data is read.
@@ -592,25 +941,33 @@ impl Device {
     /// # Returns
     ///
     /// An `Ok` result if successful, otherwise an `Error`.
+    #[deprecated(
+        note = "deprecated in librtlsdr; use `read_async_with`, `start_async` or `radion::raw::wait_async`"
+    )]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn wait_async(&self, callback: ReadAsyncCbT, ctx: *mut c_void) -> Result<()> {
-        let ret = unsafe { rtlsdr_wait_async(self.dev, callback, ctx) };
-        if ret == 0 {
-            Ok(())
-        } else {
-            Err(Error::from(ret))
-        }
+        unsafe { crate::raw::wait_async(self, callback, ctx) }
     }
 
     /// Read data from the device asynchronously.
     ///
+    /// This is not marked `unsafe` for compatibility, but `ctx` is handed to
+    /// `callback` as-is and must stay valid for whatever `callback` does with
+    /// it until this call returns. Use `read_async_with`, which takes a
+    /// closure, or `read_async_raw`, which is `unsafe` like the contract.
+    ///
     /// # Arguments
     ///
     /// * `callback` - The callback function to call when data is read.
     /// * `ctx` - The context to pass to the callback function.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
     ///
     /// # Returns
     ///
     /// An `Ok` result if successful, otherwise an `Error`.
+    #[deprecated(note = "use `read_async_with`, or `read_async_raw` for a raw callback")]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn read_async(
         &self,
         callback: ReadAsyncCbT,
@@ -618,7 +975,37 @@ impl Device {
         buf_num: u32,
         buf_len: u32,
     ) -> Result<()> {
-        let ret = unsafe { rtlsdr_read_async(self.dev, callback, ctx, buf_num, buf_len) };
+        unsafe { self.read_async_raw(callback, ctx, buf_num, buf_len) }
+    }
+
+    /// Read data from the device asynchronously with a raw callback.
+    ///
+    /// Prefer `read_async_with`, which takes a closure instead of a raw
+    /// callback and context pointer.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The callback function to call when data is read.
+    /// * `ctx` - The context to pass to the callback function.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    ///
+    /// # Safety
+    ///
+    /// `ctx` is handed to `callback` as-is, so it must stay valid for whatever
+    /// `callback` does with it until this call returns.
+    pub unsafe fn read_async_raw(
+        &self,
+        callback: ReadAsyncCbT,
+        ctx: *mut c_void,
+        buf_num: u32,
+        buf_len: u32,
+    ) -> Result<()> {
+        let ret = rtlsdr_read_async(self.dev, callback, ctx, buf_num, buf_len);
         if ret == 0 {
             Ok(())
         } else {
@@ -626,6 +1013,62 @@ impl Device {
         }
     }
 
+    /// Read data from the device asynchronously, passing every buffer to a
+    /// closure.
+    ///
+    /// This call blocks until the read is cancelled with `cancel_async`,
+    /// which the closure itself may do since it can borrow the device. If the
+    /// closure panics, the read is cancelled and the panic is resumed on the
+    /// calling thread once librtlsdr has returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The closure to call with every buffer read.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn read_async_with<F>(&self, mut callback: F, buf_num: u32, buf_len: u32) -> Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        let stats = self.stats.clone();
+        let mut gaps = GapDetector::new(self.get_sample_rate().unwrap_or(0), buf_num, buf_len);
+        let callback = |buf: &[u8]| {
+            stats.record_frame(buf.len(), gaps.expected);
+            if gaps.check(buf.len()) {
+                stats.record_discontinuity();
+            }
+            callback(buf)
+        };
+        self.run_async(callback, buf_num, buf_len)
+    }
+
+    fn run_async<F>(&self, callback: F, buf_num: u32, buf_len: u32) -> Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut ctx = AsyncContext {
+            callback,
+            dev: self.dev,
+            panic: None,
+        };
+        let ret = unsafe {
+            self.read_async_raw(
+                Some(async_trampoline::<F>),
+                &mut ctx as *mut AsyncContext<F> as *mut c_void,
+                buf_num,
+                buf_len,
+            )
+        };
+        if let Some(payload) = ctx.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        ret
+    }
+
     /// Cancel an asynchronous read operation.
     ///
     /// # Returns
@@ -640,39 +1083,242 @@ impl Device {
         }
     }
 
-    /// Get the device's USB vendor, product ID, etc.
+    /// Get the counters of the asynchronous read path.
     ///
     /// # Returns
     ///
-    /// The hardware information of the device as a `HwInfo` struct.
-    pub fn get_hw_info(&self) -> Result<HwInfo> {
-        let data = self.read_eeprom(0, EEPROM_SIZE as u16)?;
-        if data.len() < STR_OFFSET_START {
-            return Err(Error::NoValidEEPROMHeader);
-        }
+    /// The frames and bytes delivered and any data dropped or lost by reads
+    /// since the device was opened.
+    pub fn stream_stats(&self) -> StreamStats {
+        self.stats.snapshot()
+    }
+
+    pub(crate) fn stream_counters(&self) -> Arc<StreamCounters> {
+        self.stats.clone()
+    }
 
-        if data[0] != 0x28 || data[1] != 0x32 {
-            return Err(Error::NoValidEEPROMHeader);
+    /// Start reading asynchronously on a dedicated thread.
+    ///
+    /// The device buffer is reset before reading starts. The device is moved
+    /// onto the reader thread; dropping the returned handle cancels the read,
+    /// joins the thread and closes the device, while `StreamHandle::stop`
+    /// hands the device back instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The closure to call with every buffer read.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// A `StreamHandle` controlling the running read.
+    pub fn start_async<F>(self, callback: F, buf_num: u32, buf_len: u32) -> Result<StreamHandle>
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.reset_buffer()?;
+        Ok(StreamHandle::spawn(self, callback, buf_num, buf_len))
+    }
+
+    /// Start reading asynchronously on a dedicated thread, passing every
+    /// buffer to a closure as a `PooledBuffer`.
+    ///
+    /// Buffers are copied into `pool` instead of freshly allocated, and can
+    /// be kept or sent to other threads cheaply; they return to the pool when
+    /// dropped. Buffers that arrive while the pool is exhausted are dropped
+    /// and counted in both `BufferPool::stats` and `stream_stats`. The device
+    /// buffer is reset before reading starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The pool to take buffers from. Its buffers must be at
+    ///   least `buf_len` bytes long.
+    /// * `callback` - The closure to call with every buffer read.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// A `StreamHandle` controlling the running read, or
+    /// `Error::InvalidParam` if the pool's buffers are too short.
+    pub fn start_async_pooled<F>(
+        self,
+        pool: &BufferPool,
+        mut callback: F,
+        buf_num: u32,
+        buf_len: u32,
+    ) -> Result<StreamHandle>
+    where
+        F: FnMut(PooledBuffer) + Send + 'static,
+    {
+        let needed = if buf_len == 0 {
+            DEFAULT_BUF_LEN
+        } else {
+            buf_len
+        };
+        if pool.buf_len() < needed as usize {
+            return Err(Error::InvalidParam);
         }
+        let pool = pool.clone();
+        let stats = self.stream_counters();
+        self.start_async(
+            move |buf| match pool.copy_from(buf) {
+                Some(buf) => callback(buf),
+                None => stats.record_drop(),
+            },
+            buf_num,
+            buf_len,
+        )
+    }
 
-        let vendor_id = u16::from_le_bytes([data[2], data[3]]);
-        let product_id = u16::from_le_bytes([data[4], data[5]]);
-        let have_serial = data[6] == 0xA5;
-        let remote_wakeup = (data[7] & 0x01) != 0;
-        let enable_ir = (data[7] & 0x02) != 0;
+    /// Start reading asynchronously on a dedicated thread, delivering
+    /// buffers through a pre-allocated lock-free ring.
+    ///
+    /// Unlike `start_async` and `into_stream`, nothing is allocated, locked
+    /// or waited on per buffer on the USB side, which keeps up with the
+    /// highest sample rates. The device buffer is reset before reading
+    /// starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of buffers the ring holds.
+    /// * `policy` - What to do with buffers that arrive while it is full.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// A `RingReader` to take the buffers from.
+    pub fn start_ring(
+        self,
+        capacity: usize,
+        policy: OverrunPolicy,
+        buf_num: u32,
+        buf_len: u32,
+    ) -> Result<RingReader> {
+        RingReader::spawn(self, capacity, policy, buf_num, buf_len)
+    }
 
-        let (manufact, product, serial) = parse_string_descriptors(&data)?;
+    /// Read data from the device asynchronously, passing every buffer to a
+    /// closure together with the estimated time of its first sample.
+    ///
+    /// Like `read_async_with`, but buffers are delivered as `TimedSamples`.
+    /// The clock model starts with the first buffer and assumes the sample
+    /// rate set when the read starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The closure to call with every buffer read.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn read_async_timed<F>(&self, mut callback: F, buf_num: u32, buf_len: u32) -> Result<()>
+    where
+        F: FnMut(TimedSamples<'_>),
+    {
+        let mut clock = ArrivalClock::new(self.get_sample_rate()?);
+        self.read_async_with(|buf| callback(clock.stamp(buf)), buf_num, buf_len)
+    }
 
-        Ok(HwInfo {
-            vendor_id,
-            product_id,
-            manufact,
-            product,
-            serial,
-            have_serial,
-            enable_ir,
-            remote_wakeup,
-        })
+    /// Start reading asynchronously on a dedicated thread, passing every
+    /// buffer to a closure together with the estimated time of its first
+    /// sample.
+    ///
+    /// Like `start_async`, but buffers are delivered as `TimedSamples`.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The closure to call with every buffer read.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// A `StreamHandle` controlling the running read.
+    pub fn start_async_timed<F>(
+        self,
+        mut callback: F,
+        buf_num: u32,
+        buf_len: u32,
+    ) -> Result<StreamHandle>
+    where
+        F: FnMut(TimedSamples<'_>) + Send + 'static,
+    {
+        let mut clock = ArrivalClock::new(self.get_sample_rate()?);
+        self.start_async(move |buf| callback(clock.stamp(buf)), buf_num, buf_len)
+    }
+
+    /// Read samples on a dedicated thread and expose them as a `Stream`.
+    ///
+    /// The device buffer is reset before reading starts. The device is moved
+    /// into the stream and closed when the stream is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of buffers to queue before dropping new ones.
+    /// * `buf_num` - The number of USB buffers to allocate, 0 for the default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for the default.
+    ///
+    /// # Returns
+    ///
+    /// A `SampleStream` yielding one `Result` per buffer read.
+    #[cfg(feature = "tokio")]
+    pub fn into_stream(self, capacity: usize, buf_num: u32, buf_len: u32) -> Result<SampleStream> {
+        SampleStream::spawn(self, capacity, buf_num, buf_len)
+    }
+
+    /// Get the librtlsdr handle of the device, for use with `radion::raw`.
+    ///
+    /// The handle stays owned by the `Device` and is closed when it is
+    /// dropped.
+    ///
+    /// # Returns
+    ///
+    /// The `rtlsdr_dev_t` pointer.
+    pub fn as_raw(&self) -> *mut RTLSDRDevT {
+        self.dev
+    }
+
+    /// Take ownership of a handle opened outside radion.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - A handle returned by `rtlsdr_open`.
+    ///
+    /// # Returns
+    ///
+    /// A `Device` that closes the handle when dropped.
+    ///
+    /// # Safety
+    ///
+    /// `dev` must be a valid, open handle that nothing else closes.
+    pub unsafe fn from_raw(dev: *mut RTLSDRDevT) -> Device {
+        Device {
+            dev,
+            stats: Arc::default(),
+        }
+    }
+
+    pub(crate) fn canceller(&self) -> AsyncCanceller {
+        AsyncCanceller { dev: self.dev }
+    }
+
+    /// Get the device's USB vendor, product ID, etc.
+    ///
+    /// A corrupted header or string descriptors do not fail the call; check
+    /// `HwInfo::warnings` for anything that could not be parsed. Passing the
+    /// result to `set_hw_info` writes a clean image from the recovered fields.
+    ///
+    /// # Returns
+    ///
+    /// The hardware information of the device as a `HwInfo` struct.
+    pub fn get_hw_info(&self) -> Result<HwInfo> {
+        let data = self.read_eeprom(0, EEPROM_SIZE as u16)?;
+        parse_hw_info(&data)
     }
 
     /// Set the hardware information of the device.
@@ -685,24 +1331,109 @@ impl Device {
     ///
     /// An `Ok` result if successful, otherwise an `Error`.
     pub fn set_hw_info(&self, info: &HwInfo) -> Result<()> {
-        let mut data = vec![0u8; EEPROM_SIZE];
+        let data = serialize_hw_info(info)?;
+        self.write_eeprom(&data, 0)
+    }
 
-        data[0] = 0x28;
-        data[1] = 0x32;
-        data[2..4].copy_from_slice(&info.vendor_id.to_le_bytes());
-        data[4..6].copy_from_slice(&info.product_id.to_le_bytes());
-        data[6] = if info.have_serial { 0xA5 } else { 0x00 };
-        data[7] = 0x00;
-        if info.remote_wakeup {
-            data[7] |= 0x01;
+    /// Overwrite the whole EEPROM with an image, without looking at what is
+    /// currently stored.
+    ///
+    /// This is meant for recovering dongles whose EEPROM is too corrupted for
+    /// `get_hw_info`/`set_hw_info`, typically with an image from
+    /// `DongleModel::default_image`. Writing a wrong image can leave the
+    /// dongle enumerating with the wrong IDs or not at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The EEPROM image to write, at most 256 bytes.
+    /// * `_risk` - Acknowledgement that the write may brick the device.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn flash_eeprom_unchecked(&self, image: &[u8], _risk: BrickRisk) -> Result<()> {
+        if image.len() > EEPROM_SIZE {
+            return Err(Error::InvalidParam);
         }
-        if info.enable_ir {
-            data[7] |= 0x02;
+        self.write_eeprom(image, 0)
+    }
+}
+
+fn check_eeprom_range(offset: usize, len: usize) -> Result<()> {
+    if offset + len > EEPROM_SIZE {
+        Err(Error::InvalidParam)
+    } else {
+        Ok(())
+    }
+}
+
+/// Split `offset..offset + len` into ranges that do not cross a page.
+fn eeprom_pages(offset: usize, len: usize) -> impl Iterator<Item = (usize, usize)> {
+    let end = offset + len;
+    let mut start = offset;
+    std::iter::from_fn(move || {
+        if start >= end {
+            return None;
         }
+        let page_end = ((start / EEPROM_PAGE_SIZE + 1) * EEPROM_PAGE_SIZE).min(end);
+        let page = (start, page_end);
+        start = page_end;
+        Some(page)
+    })
+}
 
-        serialize_string_descriptors(&mut data, info)?;
+/// Translate a failed `rtlsdr_open` into the most helpful error.
+///
+/// On Windows libusb reports a device without a WinUSB driver as not
+/// supported, which says nothing about how to fix it. Other errors, such as
+/// not found for an unplugged dongle, are passed on unchanged.
+fn open_error(err: Error) -> Error {
+    match err {
+        Error::NotSupported if cfg!(windows) => Error::DriverMissing,
+        err => err,
+    }
+}
 
-        self.write_eeprom(&data, 0)
+/// Handle used to cancel an asynchronous read from another thread.
+///
+/// Only valid while the `Device` it was taken from is open.
+pub(crate) struct AsyncCanceller {
+    dev: *mut RTLSDRDevT,
+}
+
+// rtlsdr_cancel_async is meant to be called from outside the reading thread.
+unsafe impl Send for AsyncCanceller {}
+
+impl AsyncCanceller {
+    pub(crate) fn cancel(&self) -> Result<()> {
+        let ret = unsafe { rtlsdr_cancel_async(self.dev) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(Error::from(ret))
+        }
+    }
+}
+
+struct AsyncContext<F> {
+    callback: F,
+    dev: *mut RTLSDRDevT,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+unsafe extern "C" fn async_trampoline<F>(buf: *mut c_uchar, len: u32, ctx: *mut c_void)
+where
+    F: FnMut(&[u8]),
+{
+    let ctx = &mut *(ctx as *mut AsyncContext<F>);
+    if ctx.panic.is_some() || buf.is_null() {
+        return;
+    }
+    let data = slice::from_raw_parts(buf, len as usize);
+    let callback = &mut ctx.callback;
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| callback(data))) {
+        ctx.panic = Some(payload);
+        rtlsdr_cancel_async(ctx.dev);
     }
 }
 
@@ -0,0 +1,13 @@
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: u32,
+    pub name: String,
+    pub manufact: String,
+    pub product: String,
+    pub serial: String,
+    /// Where the device is plugged in, as the bus number and the port on
+    /// each hub from the root down, e.g. `1-4.2`. Stays the same while the
+    /// device stays in the same port, so it tells apart dongles that share
+    /// a serial. Empty if the USB topology could not be read.
+    pub usb_path: String,
+}
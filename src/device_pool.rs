@@ -0,0 +1,235 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+
+/// A set of sources used together, such as every dongle attached to a
+/// host.
+///
+/// Work that splits into independent parts, like the segments of a sweep
+/// in `radion::scan::ParallelSweep`, takes a pool and gives each source a
+/// share. Any `SdrSource` can be pooled, so pools of recordings or mocks
+/// stand in for hardware.
+pub struct DevicePool<S = Device> {
+    devices: Vec<S>,
+}
+
+impl DevicePool<Device> {
+    /// Open every attached device that is free.
+    ///
+    /// Devices that cannot be opened, typically because another program
+    /// holds them, are skipped.
+    ///
+    /// # Returns
+    ///
+    /// A pool of the opened devices in index order, or `Error::NotFound` if
+    /// none could be opened.
+    pub fn open_all() -> Result<Self> {
+        let devices: Vec<Device> = (0..Device::get_device_count())
+            .filter_map(|index| Device::new(index).ok())
+            .collect();
+        if devices.is_empty() {
+            return Err(Error::NotFound);
+        }
+        Ok(DevicePool { devices })
+    }
+
+    /// Open the devices with the given indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `indices` - The indices of the devices, as passed to `Device::new`.
+    ///
+    /// # Returns
+    ///
+    /// A pool of the devices in the order given, or the `Error` of the
+    /// first device that could not be opened.
+    pub fn open(indices: &[u32]) -> Result<Self> {
+        let devices = indices
+            .iter()
+            .map(|&index| Device::new(index))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DevicePool { devices })
+    }
+
+    /// Open the devices with the given serial numbers.
+    ///
+    /// # Arguments
+    ///
+    /// * `serials` - The serial numbers, each of which must belong to
+    ///   exactly one device.
+    ///
+    /// # Returns
+    ///
+    /// A pool of the devices in the order given, or the `Error` of the
+    /// first device that could not be opened.
+    pub fn open_by_serials(serials: &[&str]) -> Result<Self> {
+        let devices = serials
+            .iter()
+            .map(|serial| Device::open_by_serial(serial))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DevicePool { devices })
+    }
+}
+
+impl<S> DevicePool<S> {
+    /// Pool sources that are already open.
+    ///
+    /// # Arguments
+    ///
+    /// * `devices` - The sources.
+    ///
+    /// # Returns
+    ///
+    /// A pool of the sources in the order given.
+    pub fn from_devices(devices: Vec<S>) -> Self {
+        DevicePool { devices }
+    }
+
+    /// Add a source to the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The source to add.
+    pub fn push(&mut self, device: S) {
+        self.devices.push(device);
+    }
+
+    /// Get the number of sources in the pool.
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Check whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Get a source by its position in the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the source.
+    ///
+    /// # Returns
+    ///
+    /// The source, or `None` if the pool is shorter.
+    pub fn get(&self, index: usize) -> Option<&S> {
+        self.devices.get(index)
+    }
+
+    /// Get a source by its position in the pool, for changing settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the source.
+    ///
+    /// # Returns
+    ///
+    /// The source, or `None` if the pool is shorter.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut S> {
+        self.devices.get_mut(index)
+    }
+
+    /// Iterate over the sources.
+    pub fn iter(&self) -> std::slice::Iter<'_, S> {
+        self.devices.iter()
+    }
+
+    /// Iterate over the sources, for changing settings.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, S> {
+        self.devices.iter_mut()
+    }
+
+    /// Apply the same settings to every source.
+    ///
+    /// # Arguments
+    ///
+    /// * `configure` - The closure to call with each source in turn.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every call succeeded, otherwise the first `Error`;
+    /// the remaining sources are then left as they were.
+    pub fn configure<F>(&mut self, configure: F) -> Result<()>
+    where
+        F: FnMut(&mut S) -> Result<()>,
+    {
+        self.devices.iter_mut().try_for_each(configure)
+    }
+
+    /// Give back the sources.
+    ///
+    /// # Returns
+    ///
+    /// The sources in pool order.
+    pub fn into_inner(self) -> Vec<S> {
+        self.devices
+    }
+}
+
+impl<S: SdrSource> DevicePool<S> {
+    /// Set the sample rate of every source.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_hz` - The sample rate in Hz.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every source accepted it, otherwise the first
+    /// `Error`.
+    pub fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        self.configure(|device| device.set_sample_rate(rate_hz))
+    }
+
+    /// Set the gain of every source.
+    ///
+    /// # Arguments
+    ///
+    /// * `gain` - The gain in tenths of a dB, or `None` for automatic gain.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every source accepted it, otherwise the first
+    /// `Error`.
+    pub fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.configure(|device| device.set_gain(gain))
+    }
+}
+
+impl<S> FromIterator<S> for DevicePool<S> {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        DevicePool {
+            devices: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<S> IntoIterator for DevicePool<S> {
+    type Item = S;
+    type IntoIter = std::vec::IntoIter<S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.devices.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Call, MockDevice};
+
+    #[test]
+    fn configures_every_device() {
+        let mut pool: DevicePool<MockDevice> = (0..3).map(|_| MockDevice::new()).collect();
+        assert_eq!(pool.len(), 3);
+        pool.set_sample_rate(2_048_000).unwrap();
+        pool.set_gain(Some(297)).unwrap();
+        for device in pool.iter() {
+            assert_eq!(device.get_sample_rate().unwrap(), 2_048_000);
+            assert!(device.calls().contains(&Call::SetTunerGain(297)));
+        }
+        pool.push(MockDevice::new());
+        assert_eq!(pool.into_inner().len(), 4);
+    }
+}
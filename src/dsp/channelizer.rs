@@ -0,0 +1,265 @@
+use num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::sync::Arc;
+
+use super::fir::{kaiser_params, low_pass, Window};
+
+/// Critically sampled polyphase filter bank channelizer.
+///
+/// Splits a wideband stream into `channels` evenly spaced channels at once,
+/// each decimated by `channels`: with 2.4 MS/s in and 96 channels, that is
+/// 96 channels 25 kHz apart, each as a 25 kHz stream centred on its own
+/// frequency. Every input sample costs `taps_per_channel` multiplies plus
+/// a share of one FFT, however many channels are used, which is far less
+/// than mixing and filtering each channel separately.
+///
+/// Channel `k` is centred `k * sample_rate / channels` above the input's
+/// centre, wrapping to negative offsets past the middle like FFT bins.
+/// Neighbouring channels overlap at their edges, where the response is
+/// down 6 dB, so signals should be narrower than the spacing, as NBFM
+/// channels are.
+pub struct Channelizer {
+    /// Prototype low-pass taps.
+    taps: Vec<f32>,
+    ifft: Arc<dyn Fft<f32>>,
+    /// The last `taps - channels` inputs followed by the current chunk.
+    work: Vec<Complex<f32>>,
+    branch: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Channelizer {
+    /// Create a channelizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - The number of channels, which is also the decimation
+    ///   factor. The spacing is `sample_rate / channels`.
+    /// * `taps_per_channel` - The length of each polyphase branch; more
+    ///   gives sharper channel edges. 12 to 24 is typical.
+    ///
+    /// # Returns
+    ///
+    /// A new `Channelizer`.
+    pub fn new(channels: usize, taps_per_channel: usize) -> Self {
+        assert!(channels > 1, "channelizer needs at least two channels");
+        assert!(
+            taps_per_channel > 0,
+            "channelizer needs at least one tap per channel"
+        );
+        let len = channels * taps_per_channel;
+        // Aim for the stopband to start at the next channel's centre.
+        let (_, beta) = kaiser_params(0.5 / channels as f64, 70.0);
+        let taps = low_pass(0.5 / channels as f64, len, Window::Kaiser(beta));
+        let ifft = FftPlanner::new().plan_fft_inverse(channels);
+        let scratch = vec![Complex::new(0.0, 0.0); ifft.get_inplace_scratch_len()];
+        Channelizer {
+            taps,
+            ifft,
+            work: vec![Complex::new(0.0, 0.0); len - channels],
+            branch: vec![Complex::new(0.0, 0.0); channels],
+            scratch,
+        }
+    }
+
+    /// Get the number of channels.
+    pub fn channels(&self) -> usize {
+        self.branch.len()
+    }
+
+    /// Get the centre of a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel index.
+    /// * `sample_rate` - The sample rate of the input in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the channel centre from the input's centre in Hz.
+    pub fn channel_frequency(&self, channel: usize, sample_rate: f64) -> f64 {
+        let n = self.channels();
+        let k = channel % n;
+        let k = if k < n.div_ceil(2) {
+            k as f64
+        } else {
+            k as f64 - n as f64
+        };
+        k * sample_rate / n as f64
+    }
+
+    /// Find the channel containing a frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset from the input's centre in Hz.
+    /// * `sample_rate` - The sample rate of the input in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The index of the channel whose centre is nearest, or `None` if the
+    /// offset is outside the input's bandwidth.
+    pub fn channel_index(&self, offset: f64, sample_rate: f64) -> Option<usize> {
+        if offset.abs() > sample_rate / 2.0 {
+            return None;
+        }
+        let n = self.channels() as f64;
+        let k = (offset * n / sample_rate).round();
+        Some(k.rem_euclid(n) as usize)
+    }
+
+    /// Split samples into channels.
+    ///
+    /// Input may come in chunks of any size; samples that do not fill a
+    /// whole output step are kept for the next call.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Samples at the input rate.
+    /// * `out` - One output per channel, at `1 / channels` of the input
+    ///   rate. New samples are appended.
+    pub fn process_into(&mut self, input: &[Complex<f32>], out: &mut [Vec<Complex<f32>>]) {
+        let n = self.channels();
+        assert_eq!(out.len(), n, "need one output per channel");
+        let len = self.taps.len();
+        self.work.extend_from_slice(input);
+
+        let mut start = 0;
+        while start + len <= self.work.len() {
+            let newest = start + len - 1;
+            for (q, v) in self.branch.iter_mut().enumerate() {
+                *v = self.taps[q..]
+                    .iter()
+                    .step_by(n)
+                    .enumerate()
+                    .map(|(p, &h)| self.work[newest - p * n - q] * h)
+                    .sum();
+            }
+            // The unnormalised inverse DFT over the branches moves channel
+            // k down to DC, keeping the prototype's unity gain.
+            self.ifft
+                .process_with_scratch(&mut self.branch, &mut self.scratch);
+            for (channel, v) in out.iter_mut().zip(&self.branch) {
+                channel.push(*v);
+            }
+            start += n;
+        }
+        self.work.drain(..start);
+    }
+
+    /// Split samples into channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Samples at the input rate.
+    ///
+    /// # Returns
+    ///
+    /// One stream per channel, at `1 / channels` of the input rate.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<Vec<Complex<f32>>> {
+        let n = self.channels();
+        let mut out = vec![Vec::with_capacity(input.len() / n + 1); n];
+        self.process_into(input, &mut out);
+        out
+    }
+
+    /// Clear the filter history.
+    pub fn reset(&mut self) {
+        let len = self.taps.len() - self.channels();
+        self.work.clear();
+        self.work.resize(len, Complex::new(0.0, 0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const RATE: f64 = 96_000.0;
+
+    fn tone(freq: f64, len: usize) -> Vec<Complex<f32>> {
+        (0..len)
+            .map(|n| {
+                let (sin, cos) = (TAU * freq / RATE * n as f64).sin_cos();
+                Complex::new(cos as f32, sin as f32)
+            })
+            .collect()
+    }
+
+    fn level_db(samples: &[Complex<f32>]) -> f64 {
+        let power: f64 =
+            samples.iter().map(|s| s.norm_sqr() as f64).sum::<f64>() / samples.len() as f64;
+        10.0 * power.log10()
+    }
+
+    #[test]
+    fn a_tone_comes_out_of_its_channel_only() {
+        let mut channelizer = Channelizer::new(8, 16);
+        for k in 0..8 {
+            channelizer.reset();
+            let freq = channelizer.channel_frequency(k, RATE);
+            let out = channelizer.process(&tone(freq, 8 * 1000));
+            for (channel, samples) in out.iter().enumerate() {
+                assert_eq!(samples.len(), 1000);
+                let level = level_db(&samples[20..]);
+                if channel == k {
+                    assert!(level.abs() < 0.1, "channel {}: {:.2} dB", k, level);
+                } else {
+                    assert!(
+                        level < -60.0,
+                        "tone in channel {} leaks {:.1} dB into {}",
+                        k,
+                        level,
+                        channel
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_tone_off_centre_stays_at_its_offset() {
+        let mut channelizer = Channelizer::new(8, 16);
+        // 1 kHz above the centre of channel 2, which is 12 kHz wide.
+        let out = channelizer.process(&tone(25_000.0, 8 * 1000));
+        let samples = &out[2][20..];
+        let rotation = samples
+            .windows(2)
+            .map(|w| (w[1] * w[0].conj()).arg() as f64)
+            .sum::<f64>()
+            / (samples.len() - 1) as f64;
+        let freq = rotation * RATE / 8.0 / TAU;
+        assert!((freq - 1_000.0).abs() < 1.0, "{} Hz", freq);
+    }
+
+    #[test]
+    fn chunking_does_not_change_the_output() {
+        let input = tone(-31_000.0, 8 * 300);
+        let whole = Channelizer::new(8, 12).process(&input);
+        let mut channelizer = Channelizer::new(8, 12);
+        let mut chunked = vec![Vec::new(); 8];
+        for chunk in input.chunks(13) {
+            channelizer.process_into(chunk, &mut chunked);
+        }
+        assert_eq!(whole, chunked);
+    }
+
+    #[test]
+    fn maps_frequencies_to_channels() {
+        let channelizer = Channelizer::new(8, 12);
+        assert_eq!(channelizer.channel_frequency(1, RATE), 12_000.0);
+        assert_eq!(channelizer.channel_frequency(4, RATE), -48_000.0);
+        assert_eq!(channelizer.channel_frequency(7, RATE), -12_000.0);
+        for k in 0..8 {
+            let freq = channelizer.channel_frequency(k, RATE);
+            assert_eq!(channelizer.channel_index(freq + 5_000.0, RATE), Some(k));
+        }
+        assert_eq!(channelizer.channel_index(-5_900.0, RATE), Some(0));
+        assert_eq!(channelizer.channel_index(48_001.0, RATE), None);
+
+        let odd = Channelizer::new(5, 12);
+        assert_eq!(odd.channel_frequency(2, 50_000.0), 20_000.0);
+        assert_eq!(odd.channel_frequency(3, 50_000.0), -20_000.0);
+    }
+}
@@ -0,0 +1,248 @@
+use num_complex::Complex;
+
+use super::fir::Filter;
+
+#[cfg(feature = "fft")]
+use rustfft::{Fft, FftPlanner};
+#[cfg(feature = "fft")]
+use std::sync::Arc;
+
+/// Tap count from which `Convolver` switches from direct convolution to
+/// overlap-save. Only used with the `fft` feature.
+///
+/// Set from `benches/conv.rs`. `fir::Filter` is a scalar dot product, so
+/// overlap-save overtakes it at about 12 taps, whether fed 256-sample
+/// chunks or whole 16 KiB transfers, and is ten times as fast by 128. The
+/// 64 to 128 taps often quoted hold for vectorised direct filters, not this
+/// one. The threshold sits a little above the crossover, where direct
+/// convolution is still as fast and keeps no FFT buffers.
+pub const FAST_CONVOLUTION_TAPS: usize = 16;
+
+/// Streaming FIR filter over complex samples with real taps.
+///
+/// Short filters are applied by direct convolution. With the `fft` feature,
+/// filters of `FAST_CONVOLUTION_TAPS` taps or more use `OverlapSave`
+/// instead, which costs O(log N) per sample rather than O(taps). Both paths
+/// keep their history between calls, so a stream can be filtered in chunks of
+/// any size.
+pub struct Convolver {
+    inner: Inner,
+}
+
+enum Inner {
+    Direct(Filter<Complex<f32>>),
+    #[cfg(feature = "fft")]
+    OverlapSave(OverlapSave),
+}
+
+impl Convolver {
+    /// Create a filter, choosing the implementation from the tap count.
+    ///
+    /// # Arguments
+    ///
+    /// * `taps` - The filter impulse response. Must not be empty.
+    ///
+    /// # Returns
+    ///
+    /// A new `Convolver`.
+    pub fn new(taps: &[f32]) -> Self {
+        #[cfg(feature = "fft")]
+        {
+            if taps.len() >= FAST_CONVOLUTION_TAPS {
+                return Convolver {
+                    inner: Inner::OverlapSave(OverlapSave::new(taps)),
+                };
+            }
+        }
+        Convolver {
+            inner: Inner::Direct(Filter::new(taps)),
+        }
+    }
+
+    /// Check whether this filter uses FFT-based convolution.
+    ///
+    /// # Returns
+    ///
+    /// `true` if overlap-save was selected.
+    pub fn is_fast(&self) -> bool {
+        match self.inner {
+            Inner::Direct(_) => false,
+            #[cfg(feature = "fft")]
+            Inner::OverlapSave(_) => true,
+        }
+    }
+
+    /// Get the number of taps.
+    ///
+    /// # Returns
+    ///
+    /// The filter length.
+    pub fn taps(&self) -> usize {
+        match &self.inner {
+            Inner::Direct(d) => d.taps(),
+            #[cfg(feature = "fft")]
+            Inner::OverlapSave(o) => o.taps,
+        }
+    }
+
+    /// Filter samples in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The next chunk of the stream, replaced by the filter
+    ///   output.
+    pub fn filter_in_place(&mut self, samples: &mut [Complex<f32>]) {
+        match &mut self.inner {
+            Inner::Direct(d) => d.filter_in_place(samples),
+            #[cfg(feature = "fft")]
+            Inner::OverlapSave(o) => o.filter_in_place(samples),
+        }
+    }
+
+    /// Filter a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The filter output, one sample per input sample.
+    pub fn filter(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = input.to_vec();
+        self.filter_in_place(&mut out);
+        out
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        match &mut self.inner {
+            Inner::Direct(d) => d.reset(),
+            #[cfg(feature = "fft")]
+            Inner::OverlapSave(o) => o.reset(),
+        }
+    }
+}
+
+/// FFT-based overlap-save convolution.
+///
+/// Each block of `fft_len - taps + 1` new samples is transformed together with
+/// the previous `taps - 1` samples, multiplied by the filter spectrum and
+/// transformed back; the first `taps - 1` outputs are corrupted by circular
+/// wrap-around and discarded. A trailing partial block is zero-padded, which
+/// leaves the outputs that are kept unchanged, so there is no added latency.
+#[cfg(feature = "fft")]
+pub struct OverlapSave {
+    taps: usize,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    /// Filter spectrum, pre-scaled by `1 / fft_len`.
+    spectrum: Vec<Complex<f32>>,
+    /// The last `taps - 1` inputs.
+    history: Vec<Complex<f32>>,
+    block: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+#[cfg(feature = "fft")]
+impl OverlapSave {
+    /// Create an overlap-save filter with an FFT size of about four times the
+    /// filter length.
+    ///
+    /// # Arguments
+    ///
+    /// * `taps` - The filter impulse response. Must not be empty.
+    ///
+    /// # Returns
+    ///
+    /// A new `OverlapSave`.
+    pub fn new(taps: &[f32]) -> Self {
+        Self::with_fft_len(taps, (4 * taps.len()).next_power_of_two())
+    }
+
+    /// Create an overlap-save filter with an explicit FFT size.
+    ///
+    /// # Arguments
+    ///
+    /// * `taps` - The filter impulse response. Must not be empty.
+    /// * `fft_len` - The transform size. Must be greater than `taps.len()`.
+    ///
+    /// # Returns
+    ///
+    /// A new `OverlapSave`.
+    pub fn with_fft_len(taps: &[f32], fft_len: usize) -> Self {
+        assert!(!taps.is_empty(), "filter needs at least one tap");
+        assert!(fft_len > taps.len(), "FFT must be longer than the filter");
+
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_len);
+        let ifft = planner.plan_fft_inverse(fft_len);
+
+        let scale = 1.0 / fft_len as f32;
+        let mut spectrum = vec![Complex::new(0.0, 0.0); fft_len];
+        for (s, &h) in spectrum.iter_mut().zip(taps) {
+            *s = Complex::new(h * scale, 0.0);
+        }
+        fft.process(&mut spectrum);
+
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+        OverlapSave {
+            taps: taps.len(),
+            fft,
+            ifft,
+            spectrum,
+            history: vec![Complex::new(0.0, 0.0); taps.len() - 1],
+            block: vec![Complex::new(0.0, 0.0); fft_len],
+            scratch: vec![Complex::new(0.0, 0.0); scratch_len],
+        }
+    }
+
+    /// Get the number of new samples consumed by each transform.
+    ///
+    /// # Returns
+    ///
+    /// `fft_len - taps + 1`. Chunks that are a multiple of this avoid
+    /// zero-padded partial blocks.
+    pub fn block_len(&self) -> usize {
+        self.block.len() - self.history.len()
+    }
+
+    /// Filter samples in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The next chunk of the stream, replaced by the filter
+    ///   output.
+    pub fn filter_in_place(&mut self, samples: &mut [Complex<f32>]) {
+        let keep = self.history.len();
+        for chunk in samples.chunks_mut(self.block_len()) {
+            let n = chunk.len();
+            self.block[..keep].copy_from_slice(&self.history);
+            self.block[keep..keep + n].copy_from_slice(chunk);
+            self.block[keep + n..].fill(Complex::new(0.0, 0.0));
+
+            // Next history is the last `keep` samples of history + chunk.
+            if n >= keep {
+                self.history.copy_from_slice(&chunk[n - keep..]);
+            } else {
+                self.history.copy_from_slice(&self.block[n..n + keep]);
+            }
+
+            self.fft
+                .process_with_scratch(&mut self.block, &mut self.scratch);
+            for (b, s) in self.block.iter_mut().zip(&self.spectrum) {
+                *b *= s;
+            }
+            self.ifft
+                .process_with_scratch(&mut self.block, &mut self.scratch);
+            chunk.copy_from_slice(&self.block[keep..keep + n]);
+        }
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        self.history.fill(Complex::new(0.0, 0.0));
+    }
+}
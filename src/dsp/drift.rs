@@ -0,0 +1,121 @@
+use num_complex::Complex;
+
+use super::freq_est::estimate_carrier_offset;
+
+/// One relative clock error measurement.
+#[derive(Copy, Clone, Debug)]
+pub struct DriftSample {
+    /// Stream time of the measurement in seconds, counted from the first
+    /// update at the nominal sample rate.
+    pub time_s: f64,
+    /// Clock error of device B relative to device A in parts per million.
+    pub ppm: f64,
+}
+
+/// Measures the relative clock drift between two dongles.
+///
+/// Both devices are tuned to the same frequency near a shared reference
+/// carrier, e.g. a GPSDO-disciplined beacon or a strong broadcast station.
+/// The tuner and the ADC of an RTL-SDR are driven by the same crystal, so a
+/// clock error of e ppm moves the carrier by about `-e * reference_hz` at
+/// baseband, and the difference between the two observed offsets gives the
+/// relative error independently of how accurate the reference itself is.
+pub struct ClockDriftMeter {
+    reference_hz: f64,
+    sample_rate: f64,
+    consumed: u64,
+    history: Vec<DriftSample>,
+}
+
+impl ClockDriftMeter {
+    /// Create a meter.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference_hz` - RF frequency of the reference carrier in Hz.
+    /// * `sample_rate` - Nominal sample rate of both streams in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `ClockDriftMeter`.
+    pub fn new(reference_hz: f64, sample_rate: f64) -> Self {
+        ClockDriftMeter {
+            reference_hz,
+            sample_rate,
+            consumed: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Measure a pair of simultaneous chunks.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - Samples from the reference device, filtered down to the
+    ///   carrier.
+    /// * `b` - Samples from the device being compared, filtered the same
+    ///   way.
+    ///
+    /// # Returns
+    ///
+    /// The relative error of `b` in ppm, or `None` if either chunk is too
+    /// short to measure.
+    pub fn update(&mut self, a: &[Complex<f32>], b: &[Complex<f32>]) -> Option<f64> {
+        let time_s = self.consumed as f64 / self.sample_rate;
+        self.consumed += a.len() as u64;
+
+        let fa = estimate_carrier_offset(a, self.sample_rate)?;
+        let fb = estimate_carrier_offset(b, self.sample_rate)?;
+        let ppm = (fa - fb) / self.reference_hz * 1e6;
+        self.history.push(DriftSample { time_s, ppm });
+        Some(ppm)
+    }
+
+    /// Get every measurement so far.
+    ///
+    /// # Returns
+    ///
+    /// The measurements in time order.
+    pub fn history(&self) -> &[DriftSample] {
+        &self.history
+    }
+
+    /// Get the average relative error.
+    ///
+    /// # Returns
+    ///
+    /// The mean of all measurements in ppm, or `None` before the first.
+    pub fn mean_ppm(&self) -> Option<f64> {
+        if self.history.is_empty() {
+            return None;
+        }
+        Some(self.history.iter().map(|s| s.ppm).sum::<f64>() / self.history.len() as f64)
+    }
+
+    /// Get the rate at which the relative error changes.
+    ///
+    /// # Returns
+    ///
+    /// The least-squares slope in ppm per second, or `None` with fewer than
+    /// two measurements at distinct times.
+    pub fn drift_ppm_per_s(&self) -> Option<f64> {
+        let n = self.history.len() as f64;
+        if n < 2.0 {
+            return None;
+        }
+        let mean_t = self.history.iter().map(|s| s.time_s).sum::<f64>() / n;
+        let mean_p = self.mean_ppm()?;
+        let (mut num, mut den) = (0.0, 0.0);
+        for s in &self.history {
+            num += (s.time_s - mean_t) * (s.ppm - mean_p);
+            den += (s.time_s - mean_t).powi(2);
+        }
+        (den > 0.0).then(|| num / den)
+    }
+
+    /// Discard all measurements and restart the stream clock.
+    pub fn reset(&mut self) {
+        self.consumed = 0;
+        self.history.clear();
+    }
+}
@@ -0,0 +1,387 @@
+//! FIR filter design, filtering and decimation.
+//!
+//! Filters work on real (`f32`) or complex (`Complex<f32>`) samples with real
+//! taps, and keep their history between calls so a stream can be processed
+//! in chunks of any size.
+
+use num_complex::Complex;
+use std::f64::consts::PI;
+use std::ops::{Add, Mul};
+
+use super::halfband::{plan_decimation, HalfBandCascade};
+
+/// Sample types the filters in this module operate on.
+pub trait Sample: Copy + Default + Add<Output = Self> + Mul<f32, Output = Self> {}
+
+impl Sample for f32 {}
+impl Sample for Complex<f32> {}
+
+/// Window applied to a windowed-sinc design.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    /// Kaiser window with the given beta; larger values trade a wider
+    /// transition band for more stopband rejection.
+    Kaiser(f64),
+}
+
+impl Window {
+    /// Evaluate the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Tap index.
+    /// * `len` - Total number of taps.
+    ///
+    /// # Returns
+    ///
+    /// The window value at `n`.
+    pub fn value(&self, n: usize, len: usize) -> f64 {
+        if len < 2 {
+            return 1.0;
+        }
+        let x = n as f64 / (len - 1) as f64;
+        match *self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * (2.0 * PI * x).cos(),
+            Window::Hamming => 0.54 - 0.46 * (2.0 * PI * x).cos(),
+            Window::Blackman => 0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos(),
+            Window::Kaiser(beta) => {
+                let r = 2.0 * x - 1.0;
+                bessel_i0(beta * (1.0 - r * r).sqrt()) / bessel_i0(beta)
+            }
+        }
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= half / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Design a windowed-sinc low-pass filter with unity gain at DC.
+///
+/// # Arguments
+///
+/// * `cutoff` - Cutoff frequency as a fraction of the sample rate, between 0
+///   and 0.5.
+/// * `taps` - Number of taps. Odd lengths give an integer group delay.
+/// * `window` - Window applied to the ideal response.
+///
+/// # Returns
+///
+/// The filter taps.
+pub fn low_pass(cutoff: f64, taps: usize, window: Window) -> Vec<f32> {
+    assert!(taps > 0, "filter needs at least one tap");
+    assert!(cutoff > 0.0 && cutoff <= 0.5, "cutoff must be in (0, 0.5]");
+    let mid = (taps - 1) as f64 / 2.0;
+    let h: Vec<f64> = (0..taps)
+        .map(|n| {
+            let t = n as f64 - mid;
+            let sinc = if t == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * t).sin() / (PI * t)
+            };
+            sinc * window.value(n, taps)
+        })
+        .collect();
+    let sum: f64 = h.iter().sum();
+    h.iter().map(|&v| (v / sum) as f32).collect()
+}
+
+/// Estimate the number of taps a Kaiser-windowed design needs.
+///
+/// # Arguments
+///
+/// * `transition` - Transition bandwidth as a fraction of the sample rate.
+/// * `attenuation_db` - Required stopband attenuation in dB.
+///
+/// # Returns
+///
+/// The tap count (always odd) and the Kaiser beta to pass to
+/// `Window::Kaiser`.
+pub fn kaiser_params(transition: f64, attenuation_db: f64) -> (usize, f64) {
+    let beta = if attenuation_db > 50.0 {
+        0.1102 * (attenuation_db - 8.7)
+    } else if attenuation_db >= 21.0 {
+        0.5842 * (attenuation_db - 21.0).powf(0.4) + 0.07886 * (attenuation_db - 21.0)
+    } else {
+        0.0
+    };
+    let taps = ((attenuation_db - 7.95) / (14.36 * transition))
+        .ceil()
+        .max(1.0) as usize;
+    (taps | 1, beta)
+}
+
+/// Streaming direct-form FIR filter.
+pub struct Filter<T> {
+    /// Taps in reverse order, so each output is a forward dot product.
+    taps: Vec<f32>,
+    /// The last `taps - 1` inputs followed by the current chunk.
+    work: Vec<T>,
+}
+
+impl<T: Sample> Filter<T> {
+    /// Create a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `taps` - The filter impulse response. Must not be empty.
+    ///
+    /// # Returns
+    ///
+    /// A new `Filter`.
+    pub fn new(taps: &[f32]) -> Self {
+        assert!(!taps.is_empty(), "filter needs at least one tap");
+        Filter {
+            taps: taps.iter().rev().copied().collect(),
+            work: vec![T::default(); taps.len() - 1],
+        }
+    }
+
+    /// Get the number of taps.
+    ///
+    /// # Returns
+    ///
+    /// The filter length.
+    pub fn taps(&self) -> usize {
+        self.taps.len()
+    }
+
+    /// Filter samples in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The next chunk of the stream, replaced by the filter
+    ///   output.
+    pub fn filter_in_place(&mut self, samples: &mut [T]) {
+        let keep = self.taps.len() - 1;
+        self.work.extend_from_slice(samples);
+        for (out, window) in samples.iter_mut().zip(self.work.windows(self.taps.len())) {
+            *out = dot(window, &self.taps);
+        }
+        self.work.drain(..self.work.len() - keep);
+    }
+
+    /// Filter a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The filter output, one sample per input sample.
+    pub fn filter(&mut self, input: &[T]) -> Vec<T> {
+        let mut out = input.to_vec();
+        self.filter_in_place(&mut out);
+        out
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        self.work.fill(T::default());
+    }
+}
+
+fn dot<T: Sample>(window: &[T], taps: &[f32]) -> T {
+    window
+        .iter()
+        .zip(taps)
+        .fold(T::default(), |acc, (&x, &h)| acc + x * h)
+}
+
+/// Streaming polyphase FIR decimator.
+///
+/// Only every `factor`-th output is computed, which is the polyphase
+/// decomposition of the filter evaluated one branch per input sample: the cost
+/// is `taps / factor` multiplies per input sample instead of `taps`.
+pub struct Decimator<T> {
+    factor: usize,
+    /// Taps in reverse order.
+    taps: Vec<f32>,
+    /// The last `taps - 1` inputs followed by the current chunk.
+    work: Vec<T>,
+    /// Offset of the next output's newest input past the window start.
+    phase: usize,
+}
+
+impl<T: Sample> Decimator<T> {
+    /// Create a decimator with explicit taps.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - Decimation factor. Must be non-zero.
+    /// * `taps` - Anti-aliasing filter taps at the input rate. Must not be
+    ///   empty.
+    ///
+    /// # Returns
+    ///
+    /// A new `Decimator`.
+    pub fn new(factor: usize, taps: &[f32]) -> Self {
+        assert!(factor > 0, "decimation factor must be non-zero");
+        assert!(!taps.is_empty(), "filter needs at least one tap");
+        Decimator {
+            factor,
+            taps: taps.iter().rev().copied().collect(),
+            work: vec![T::default(); taps.len() - 1],
+            phase: 0,
+        }
+    }
+
+    /// Create a decimator with a Blackman-windowed low-pass cutting off at
+    /// 80% of the output Nyquist frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - Decimation factor. Must be non-zero.
+    /// * `taps` - Number of filter taps.
+    ///
+    /// # Returns
+    ///
+    /// A new `Decimator`.
+    pub fn with_low_pass(factor: usize, taps: usize) -> Self {
+        let cutoff = 0.4 / factor as f64;
+        Self::new(factor, &low_pass(cutoff, taps, Window::Blackman))
+    }
+
+    /// Get the decimation factor.
+    ///
+    /// # Returns
+    ///
+    /// The factor passed at construction.
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Decimate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives the output samples, appended.
+    pub fn decimate_into(&mut self, input: &[T], out: &mut Vec<T>) {
+        let len = self.taps.len();
+        self.work.extend_from_slice(input);
+
+        let mut end = len - 1 + self.phase;
+        while end < self.work.len() {
+            out.push(dot(&self.work[end + 1 - len..=end], &self.taps));
+            end += self.factor;
+        }
+
+        self.phase = end - self.work.len();
+        self.work.drain(..self.work.len() - (len - 1));
+    }
+
+    /// Decimate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The output samples.
+    pub fn decimate(&mut self, input: &[T]) -> Vec<T> {
+        let mut out = Vec::with_capacity(input.len() / self.factor + 1);
+        self.decimate_into(input, &mut out);
+        out
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        self.work.fill(T::default());
+        self.phase = 0;
+    }
+}
+
+/// Complex decimator for bringing the dongle rate down to a channel.
+///
+/// The factor is split by `plan_decimation`: powers of two are taken by a
+/// half-band cascade and the remainder by a final `Decimator`, which also sets
+/// the channel edges at 80% of the output Nyquist frequency.
+pub struct ChannelDecimator {
+    half_bands: HalfBandCascade,
+    last: Decimator<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl ChannelDecimator {
+    /// Create a channel decimator.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - Total decimation factor, e.g. 192 for 2.4 MS/s to
+    ///   12.5 kHz. Must be non-zero.
+    /// * `taps` - Number of taps of the final channel filter.
+    ///
+    /// # Returns
+    ///
+    /// A new `ChannelDecimator`.
+    pub fn new(factor: usize, taps: usize) -> Self {
+        let plan = plan_decimation(factor);
+        ChannelDecimator {
+            half_bands: HalfBandCascade::new(plan.half_band_stages, 8),
+            last: Decimator::with_low_pass(plan.remainder, taps),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Get the total decimation factor.
+    ///
+    /// # Returns
+    ///
+    /// The factor passed at construction.
+    pub fn factor(&self) -> usize {
+        self.half_bands.factor() * self.last.factor()
+    }
+
+    /// Decimate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives the output samples, appended.
+    pub fn decimate_into(&mut self, input: &[Complex<f32>], out: &mut Vec<Complex<f32>>) {
+        self.scratch.clear();
+        self.half_bands.decimate_into(input, &mut self.scratch);
+        self.last.decimate_into(&self.scratch, out);
+    }
+
+    /// Decimate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The output samples.
+    pub fn decimate(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = Vec::with_capacity(input.len() / self.factor() + 1);
+        self.decimate_into(input, &mut out);
+        out
+    }
+
+    /// Clear the history of every stage.
+    pub fn reset(&mut self) {
+        self.half_bands.reset();
+        self.last.reset();
+    }
+}
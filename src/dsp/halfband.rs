@@ -0,0 +1,335 @@
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+/// How a decimation factor is split between half-band stages and a final
+/// stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimationPlan {
+    /// Number of decimate-by-2 half-band stages, run first.
+    pub half_band_stages: u32,
+    /// Factor left for the final FIR stage; 1 if none is needed.
+    pub remainder: usize,
+}
+
+/// Split a decimation factor into a half-band cascade and a remainder.
+///
+/// Every factor of two is taken by a half-band stage, each of which needs
+/// only about a quarter of the multiplies of a general FIR of the same length
+/// and runs at half the rate of the stage before it. Odd factors are left for
+/// a single FIR stage at the lowest rate, where it is cheapest.
+///
+/// # Arguments
+///
+/// * `factor` - The total decimation factor, e.g. 192 for 2.4 MS/s to
+///   12.5 kHz. Must be non-zero.
+///
+/// # Returns
+///
+/// The plan, e.g. six half-band stages and a remainder of 3 for 192.
+pub fn plan_decimation(factor: usize) -> DecimationPlan {
+    assert!(factor > 0, "decimation factor must be non-zero");
+    let half_band_stages = factor.trailing_zeros();
+    DecimationPlan {
+        half_band_stages,
+        remainder: factor >> half_band_stages,
+    }
+}
+
+/// Decimate-by-2 half-band FIR filter.
+///
+/// A half-band low-pass has its cutoff at a quarter of the input rate, which
+/// makes every other tap zero except the centre one (which is 0.5). Together
+/// with the symmetry of a linear-phase filter and computing only the kept
+/// outputs, each output sample costs `order + 1` multiplies.
+pub struct HalfBand {
+    /// Non-zero side taps, innermost first.
+    coeffs: Vec<f32>,
+    /// The last `len - 1` inputs followed by the current chunk.
+    work: Vec<Complex<f32>>,
+    /// Offset of the next output's newest input past the window start.
+    phase: usize,
+}
+
+impl HalfBand {
+    /// Design a Blackman-windowed half-band filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - Number of non-zero taps on each side of the centre. The
+    ///   filter has `4 * order - 1` taps; 8 gives over 70 dB of stopband
+    ///   rejection above 0.35 of the input rate, and less than 0.4 dB of
+    ///   droop up to 0.2.
+    ///
+    /// # Returns
+    ///
+    /// A new `HalfBand`.
+    pub fn new(order: usize) -> Self {
+        assert!(order > 0, "half-band order must be non-zero");
+        let len = 4 * order - 1;
+        let centre = (len - 1) / 2;
+        let mut coeffs: Vec<f64> = (0..order)
+            .map(|j| {
+                let k = 2 * j + 1;
+                let x = k as f64 / 2.0;
+                let sinc = (PI * x).sin() / (PI * x);
+                // Window over two more points than the filter, so that its
+                // zero ends fall outside and the outermost taps count.
+                let n = (centre + k + 1) as f64 / (len + 1) as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos();
+                0.5 * sinc * window
+            })
+            .collect();
+        // Unity gain at DC: 0.5 + 2 * sum(coeffs) == 1.
+        let sum: f64 = coeffs.iter().sum();
+        coeffs.iter_mut().for_each(|c| *c *= 0.25 / sum);
+
+        HalfBand {
+            coeffs: coeffs.into_iter().map(|c| c as f32).collect(),
+            work: vec![Complex::new(0.0, 0.0); len - 1],
+            phase: 0,
+        }
+    }
+
+    /// Get the full filter length.
+    ///
+    /// # Returns
+    ///
+    /// The number of taps, including the zero ones.
+    pub fn taps(&self) -> usize {
+        4 * self.coeffs.len() - 1
+    }
+
+    /// Decimate a chunk of samples by two.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives the output samples, appended.
+    pub fn decimate_into(&mut self, input: &[Complex<f32>], out: &mut Vec<Complex<f32>>) {
+        let len = self.taps();
+        let centre = len / 2;
+        self.work.extend_from_slice(input);
+
+        let mut end = len - 1 + self.phase;
+        while end < self.work.len() {
+            let w = &self.work[end + 1 - len..=end];
+            let mut acc = w[centre] * 0.5;
+            for (j, &c) in self.coeffs.iter().enumerate() {
+                let k = 2 * j + 1;
+                acc += (w[centre + k] + w[centre - k]) * c;
+            }
+            out.push(acc);
+            end += 2;
+        }
+
+        self.phase = end - self.work.len();
+        self.work.drain(..self.work.len() - (len - 1));
+    }
+
+    /// Decimate a chunk of samples by two.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The output samples.
+    pub fn decimate(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = Vec::with_capacity(input.len() / 2 + 1);
+        self.decimate_into(input, &mut out);
+        out
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        self.work.fill(Complex::new(0.0, 0.0));
+        self.phase = 0;
+    }
+}
+
+/// A chain of `HalfBand` stages decimating by a power of two.
+pub struct HalfBandCascade {
+    stages: Vec<HalfBand>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl HalfBandCascade {
+    /// Create a cascade.
+    ///
+    /// # Arguments
+    ///
+    /// * `stages` - Number of decimate-by-2 stages; the total factor is
+    ///   `2^stages`.
+    /// * `order` - Filter order of each stage, see `HalfBand::new`.
+    ///
+    /// # Returns
+    ///
+    /// A new `HalfBandCascade`.
+    pub fn new(stages: u32, order: usize) -> Self {
+        HalfBandCascade {
+            stages: (0..stages).map(|_| HalfBand::new(order)).collect(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Get the total decimation factor.
+    ///
+    /// # Returns
+    ///
+    /// `2^stages`.
+    pub fn factor(&self) -> usize {
+        1 << self.stages.len()
+    }
+
+    /// Decimate a chunk of samples through every stage.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives the output samples, appended.
+    pub fn decimate_into(&mut self, input: &[Complex<f32>], out: &mut Vec<Complex<f32>>) {
+        let Some((last, rest)) = self.stages.split_last_mut() else {
+            out.extend_from_slice(input);
+            return;
+        };
+        let mut buf = input.to_vec();
+        for stage in rest {
+            self.scratch.clear();
+            stage.decimate_into(&buf, &mut self.scratch);
+            std::mem::swap(&mut buf, &mut self.scratch);
+        }
+        last.decimate_into(&buf, out);
+    }
+
+    /// Decimate a chunk of samples through every stage.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The output samples.
+    pub fn decimate(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = Vec::with_capacity(input.len() / self.factor() + 1);
+        self.decimate_into(input, &mut out);
+        out
+    }
+
+    /// Clear the history of every stage.
+    pub fn reset(&mut self) {
+        self.stages.iter_mut().for_each(HalfBand::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Magnitude response in dB at a frequency relative to the input rate.
+    fn response_db(filter: &HalfBand, freq: f64) -> f64 {
+        let w = 2.0 * PI * freq;
+        let side: f64 = filter
+            .coeffs
+            .iter()
+            .enumerate()
+            .map(|(j, &c)| 2.0 * c as f64 * (w * (2 * j + 1) as f64).cos())
+            .sum();
+        20.0 * (0.5 + side).abs().log10()
+    }
+
+    fn worst(filter: &HalfBand, from: f64, to: f64, f: fn(f64, f64) -> f64) -> f64 {
+        (0..=1000)
+            .map(|i| response_db(filter, from + (to - from) * i as f64 / 1000.0))
+            .fold(response_db(filter, from), f)
+    }
+
+    #[test]
+    fn order_8_meets_its_documented_response() {
+        let filter = HalfBand::new(8);
+        assert_eq!(filter.taps(), 31);
+        assert!(response_db(&filter, 0.0).abs() < 1e-4);
+        let stopband = worst(&filter, 0.35, 0.5, f64::max);
+        assert!(stopband < -70.0, "stopband {:.1} dB", stopband);
+        let droop = worst(&filter, 0.0, 0.2, f64::min);
+        assert!(droop > -0.4, "droop {:.2} dB", droop);
+        // Every half-band filter is 6 dB down at a quarter of the rate.
+        assert!((response_db(&filter, 0.25) + 6.02).abs() < 0.01);
+    }
+
+    #[test]
+    fn outermost_taps_are_used() {
+        for order in [2, 4, 8, 16] {
+            let filter = HalfBand::new(order);
+            let outer = *filter.coeffs.last().unwrap();
+            assert!(outer.abs() > 1e-6, "order {} outer tap {}", order, outer);
+        }
+    }
+
+    #[test]
+    fn more_taps_reject_more() {
+        let rejection = |order| worst(&HalfBand::new(order), 0.35, 0.5, f64::max);
+        assert!(rejection(4) > rejection(6));
+        assert!(rejection(6) > rejection(8));
+    }
+
+    fn tone(freq: f64, len: usize) -> Vec<Complex<f32>> {
+        (0..len)
+            .map(|n| {
+                let (sin, cos) = (2.0 * PI * freq * n as f64).sin_cos();
+                Complex::new(cos as f32, sin as f32)
+            })
+            .collect()
+    }
+
+    fn level_db(samples: &[Complex<f32>]) -> f64 {
+        let power: f64 =
+            samples.iter().map(|s| s.norm_sqr() as f64).sum::<f64>() / samples.len() as f64;
+        10.0 * power.log10()
+    }
+
+    #[test]
+    fn decimation_keeps_the_passband_and_removes_aliases() {
+        // Fed in uneven chunks, to cover the carried over history.
+        let decimate = |input: &[Complex<f32>]| {
+            let mut filter = HalfBand::new(8);
+            let mut out = Vec::new();
+            for chunk in input.chunks(37) {
+                filter.decimate_into(chunk, &mut out);
+            }
+            assert_eq!(out.len(), input.len() / 2);
+            out
+        };
+        let kept = decimate(&tone(0.1, 4000));
+        assert!(level_db(&kept[100..]).abs() < 0.1);
+        let alias = decimate(&tone(-0.4, 4000));
+        assert!(level_db(&alias[100..]) < -70.0);
+    }
+
+    #[test]
+    fn cascade_decimates_by_a_power_of_two() {
+        let mut cascade = HalfBandCascade::new(3, 8);
+        assert_eq!(cascade.factor(), 8);
+        let out = cascade.decimate(&tone(0.01, 8000));
+        assert_eq!(out.len(), 1000);
+        assert!(level_db(&out[100..]).abs() < 0.1);
+        assert_eq!(
+            HalfBandCascade::new(0, 8).decimate(&tone(0.1, 10)).len(),
+            10
+        );
+    }
+
+    #[test]
+    fn plans_half_bands_first() {
+        assert_eq!(
+            plan_decimation(192),
+            DecimationPlan {
+                half_band_stages: 6,
+                remainder: 3
+            }
+        );
+        assert_eq!(plan_decimation(8).remainder, 1);
+        assert_eq!(plan_decimation(5).half_band_stages, 0);
+    }
+}
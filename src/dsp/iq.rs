@@ -0,0 +1,364 @@
+use num_complex::Complex;
+
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use crate::tuner::SamplingMode;
+
+/// Swap the I and Q components of interleaved 8-bit samples in place.
+///
+/// Swapping I/Q mirrors the spectrum around DC, so it is equivalent to
+/// `invert_spectrum_u8` followed by a 90° phase rotation.
+///
+/// # Arguments
+///
+/// * `buf` - Interleaved I/Q samples as returned by the device.
+pub fn swap_iq_u8(buf: &mut [u8]) {
+    for pair in buf.chunks_exact_mut(2) {
+        pair.swap(0, 1);
+    }
+}
+
+/// Invert the spectrum of interleaved 8-bit samples in place.
+///
+/// The Q component is mirrored around the 127.5 midpoint, which conjugates
+/// every sample without any loss of precision.
+///
+/// # Arguments
+///
+/// * `buf` - Interleaved I/Q samples as returned by the device.
+pub fn invert_spectrum_u8(buf: &mut [u8]) {
+    for pair in buf.chunks_exact_mut(2) {
+        pair[1] = 255 - pair[1];
+    }
+}
+
+/// Swap the I and Q components of complex samples in place.
+///
+/// # Arguments
+///
+/// * `iq` - The samples to modify.
+pub fn swap_iq(iq: &mut [Complex<f32>]) {
+    for s in iq {
+        *s = Complex::new(s.im, s.re);
+    }
+}
+
+/// Invert the spectrum of complex samples in place.
+///
+/// # Arguments
+///
+/// * `iq` - The samples to modify.
+pub fn invert_spectrum(iq: &mut [Complex<f32>]) {
+    for s in iq {
+        *s = s.conj();
+    }
+}
+
+/// A frequency converter between the antenna and the dongle, such as an HF
+/// upconverter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Upconverter {
+    lo_hz: u32,
+    inverting: bool,
+}
+
+impl Upconverter {
+    /// Describe a converter whose output is its input plus the local
+    /// oscillator, like the Ham It Up (125 MHz) or SpyVerter (120 MHz).
+    ///
+    /// # Arguments
+    ///
+    /// * `lo_hz` - The local oscillator frequency in Hz.
+    pub fn new(lo_hz: u32) -> Self {
+        assert!(lo_hz > 0, "local oscillator frequency must be positive");
+        Upconverter {
+            lo_hz,
+            inverting: false,
+        }
+    }
+
+    /// Describe a converter whose output is the local oscillator minus its
+    /// input, which mirrors the spectrum.
+    ///
+    /// # Arguments
+    ///
+    /// * `lo_hz` - The local oscillator frequency in Hz.
+    pub fn inverting(lo_hz: u32) -> Self {
+        Upconverter {
+            inverting: true,
+            ..Upconverter::new(lo_hz)
+        }
+    }
+
+    /// Get the local oscillator frequency in Hz.
+    pub fn lo_hz(&self) -> u32 {
+        self.lo_hz
+    }
+
+    /// Get whether the converter mirrors the spectrum.
+    pub fn is_inverting(&self) -> bool {
+        self.inverting
+    }
+
+    /// Get the frequency to tune the dongle to for an antenna frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `rf_hz` - The antenna frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The dongle frequency, or `Error::InvalidParam` if the converter
+    /// cannot reach the antenna frequency.
+    pub fn tuner_freq(&self, rf_hz: u32) -> Result<u32> {
+        let freq = if self.inverting {
+            self.lo_hz.checked_sub(rf_hz)
+        } else {
+            self.lo_hz.checked_add(rf_hz)
+        };
+        freq.ok_or(Error::InvalidParam)
+    }
+
+    /// Get the antenna frequency a dongle frequency corresponds to.
+    ///
+    /// # Arguments
+    ///
+    /// * `tuner_hz` - The dongle frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The antenna frequency, or `Error::InvalidParam` if the dongle
+    /// frequency is on the wrong side of the local oscillator.
+    pub fn rf_freq(&self, tuner_hz: u32) -> Result<u32> {
+        let freq = if self.inverting {
+            self.lo_hz.checked_sub(tuner_hz)
+        } else {
+            tuner_hz.checked_sub(self.lo_hz)
+        };
+        freq.ok_or(Error::InvalidParam)
+    }
+}
+
+/// An `SdrSource` that corrects swapped I/Q and mirrored spectra.
+///
+/// Reads have I and Q swapped if asked to, then their spectrum inverted if
+/// asked to, if the source is direct sampling from the Q branch, or if an
+/// inverting upconverter is in front of it; two of these cancel out. The
+/// sampling mode is checked on every read, so switching it on the wrapped
+/// device takes effect straight away. With an upconverter, `tune` and
+/// `center_freq` work in antenna frequencies.
+///
+/// ```no_run
+/// use radion::dsp::{SpectrumCorrected, Upconverter};
+/// use radion::{Device, SamplingMode, SdrSource};
+///
+/// let device = Device::new(0)?;
+/// device.set_direct_sampling(SamplingMode::QADC)?;
+/// device.set_sample_rate(2_048_000)?;
+/// device.reset_buffer()?;
+/// // Q-branch direct sampling is inverted without further setup.
+/// let mut hf = SpectrumCorrected::new(device);
+/// hf.tune(7_100_000)?;
+///
+/// // Behind a Ham It Up, tune to 14.2 MHz at 139.2 MHz.
+/// let mut up = SpectrumCorrected::new(Device::new(1)?).with_upconverter(Upconverter::new(125_000_000));
+/// up.tune(14_200_000)?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct SpectrumCorrected<S> {
+    source: S,
+    upconverter: Option<Upconverter>,
+    swap_iq: bool,
+    invert: bool,
+}
+
+impl<S: SdrSource> SpectrumCorrected<S> {
+    /// Wrap a source, correcting only what is detected automatically.
+    pub fn new(source: S) -> Self {
+        SpectrumCorrected {
+            source,
+            upconverter: None,
+            swap_iq: false,
+            invert: false,
+        }
+    }
+
+    /// Set the converter in front of the dongle.
+    pub fn with_upconverter(mut self, upconverter: Upconverter) -> Self {
+        self.upconverter = Some(upconverter);
+        self
+    }
+
+    /// Set whether to swap I and Q.
+    pub fn with_swap_iq(mut self, swap: bool) -> Self {
+        self.swap_iq = swap;
+        self
+    }
+
+    /// Set whether to invert the spectrum, on top of any automatic
+    /// inversion.
+    pub fn with_inversion(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    /// Get whether reads are being inverted, taking the manual setting, the
+    /// sampling mode and the upconverter into account.
+    pub fn inverts(&self) -> Result<bool> {
+        let q_branch = self.source.sampling_mode()? == SamplingMode::QADC;
+        let upconverter = self.upconverter.is_some_and(|u| u.inverting);
+        Ok(self.invert ^ q_branch ^ upconverter)
+    }
+
+    /// Get the wrapped source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the wrapped source mutably, e.g. to change its settings.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Unwrap the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: SdrSource> SdrSource for SpectrumCorrected<S> {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        let freq_hz = match self.upconverter {
+            Some(u) => u.tuner_freq(freq_hz)?,
+            None => freq_hz,
+        };
+        self.source.tune(freq_hz)
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        let freq_hz = self.source.center_freq()?;
+        match self.upconverter {
+            Some(u) => u.rf_freq(freq_hz),
+            None => Ok(freq_hz),
+        }
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        self.source.set_sample_rate(rate_hz)
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        self.source.sample_rate()
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.source.set_gain(gain)
+    }
+
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        self.source.sampling_mode()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.source.read(buf)?;
+        if self.swap_iq {
+            swap_iq_u8(&mut buf[..n]);
+        }
+        if self.inverts()? {
+            invert_spectrum_u8(&mut buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{MockDevice, MockSignal};
+
+    /// A source repeating the same two samples.
+    fn source(mode: SamplingMode) -> MockDevice {
+        let mock = MockDevice::new().with_signal(MockSignal::Data(vec![10, 20, 200, 255]));
+        mock.set_direct_sampling(mode).unwrap();
+        mock
+    }
+
+    fn read<S: SdrSource>(source: &mut S) -> Vec<u8> {
+        let mut buf = [0u8; 4];
+        let n = source.read(&mut buf).unwrap();
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn u8_helpers() {
+        let mut buf = [10, 20, 200, 255, 7];
+        swap_iq_u8(&mut buf);
+        assert_eq!(buf, [20, 10, 255, 200, 7]);
+        invert_spectrum_u8(&mut buf);
+        assert_eq!(buf, [20, 245, 255, 55, 7]);
+    }
+
+    #[test]
+    fn leaves_normal_sampling_alone() {
+        let mut rx = SpectrumCorrected::new(source(SamplingMode::None));
+        assert!(!rx.inverts().unwrap());
+        assert_eq!(read(&mut rx), [10, 20, 200, 255]);
+    }
+
+    #[test]
+    fn inverts_q_branch() {
+        let mut rx = SpectrumCorrected::new(source(SamplingMode::QADC));
+        assert!(rx.inverts().unwrap());
+        assert_eq!(read(&mut rx), [10, 235, 200, 0]);
+
+        rx.get_ref()
+            .set_direct_sampling(SamplingMode::IADC)
+            .unwrap();
+        assert_eq!(read(&mut rx), [10, 20, 200, 255]);
+    }
+
+    #[test]
+    fn inversions_cancel() {
+        let mut rx = SpectrumCorrected::new(source(SamplingMode::QADC))
+            .with_upconverter(Upconverter::inverting(150_000_000));
+        assert!(!rx.inverts().unwrap());
+        assert_eq!(read(&mut rx), [10, 20, 200, 255]);
+
+        let mut rx = rx.with_inversion(true);
+        assert_eq!(read(&mut rx), [10, 235, 200, 0]);
+    }
+
+    #[test]
+    fn swaps_before_inverting() {
+        let mut rx = SpectrumCorrected::new(source(SamplingMode::None))
+            .with_swap_iq(true)
+            .with_inversion(true);
+        assert_eq!(read(&mut rx), [20, 245, 255, 55]);
+    }
+
+    #[test]
+    fn tunes_through_upconverter() {
+        let mut rx = SpectrumCorrected::new(source(SamplingMode::None))
+            .with_upconverter(Upconverter::new(125_000_000));
+        rx.tune(14_200_000).unwrap();
+        assert_eq!(rx.get_ref().get_center_freq().unwrap(), 139_200_000);
+        assert_eq!(rx.center_freq().unwrap(), 14_200_000);
+        assert!(!rx.inverts().unwrap());
+
+        let mut rx = rx.with_upconverter(Upconverter::inverting(150_000_000));
+        rx.tune(7_100_000).unwrap();
+        assert_eq!(rx.get_ref().get_center_freq().unwrap(), 142_900_000);
+        assert_eq!(rx.center_freq().unwrap(), 7_100_000);
+        assert!(rx.tune(160_000_000).is_err());
+    }
+
+    #[test]
+    fn upconverter_frequencies() {
+        let up = Upconverter::new(125_000_000);
+        assert_eq!(up.tuner_freq(10_000_000).unwrap(), 135_000_000);
+        assert_eq!(up.rf_freq(135_000_000).unwrap(), 10_000_000);
+        assert!(matches!(up.rf_freq(100_000_000), Err(Error::InvalidParam)));
+        assert!(matches!(up.tuner_freq(u32::MAX), Err(Error::InvalidParam)));
+    }
+}
@@ -0,0 +1,37 @@
+#[cfg(feature = "fft")]
+mod channelizer;
+mod conv;
+mod drift;
+pub mod fir;
+mod freq_est;
+mod halfband;
+mod iq;
+mod measure;
+mod pll;
+#[cfg(feature = "fft")]
+pub mod spectrum;
+mod symbol_sync;
+#[cfg(feature = "fft")]
+mod trigger;
+mod xlator;
+
+#[cfg(feature = "fft")]
+pub use channelizer::Channelizer;
+#[cfg(feature = "fft")]
+pub use conv::OverlapSave;
+pub use conv::{Convolver, FAST_CONVOLUTION_TAPS};
+pub use drift::{ClockDriftMeter, DriftSample};
+pub use freq_est::estimate_carrier_offset;
+pub use halfband::{plan_decimation, DecimationPlan, HalfBand, HalfBandCascade};
+pub use iq::{
+    invert_spectrum, invert_spectrum_u8, swap_iq, swap_iq_u8, SpectrumCorrected, Upconverter,
+};
+pub use measure::{am_depth, fm_deviation, occupied_bandwidth, FmDeviation};
+pub use num_complex::Complex;
+pub use pll::{CostasLoop, CostasOrder, Pll};
+#[cfg(feature = "fft")]
+pub use spectrum::Spectrum;
+pub use symbol_sync::{SymbolSync, TimingDetector};
+#[cfg(feature = "fft")]
+pub use trigger::{WidebandEvent, WidebandTrigger};
+pub use xlator::{FreqXlator, OffsetTuned};
@@ -0,0 +1,504 @@
+use num_complex::Complex;
+use std::f64::consts::TAU;
+
+/// Smoothed lock metric above which a loop counts as locked.
+const LOCK_THRESHOLD: f32 = 0.9;
+
+/// Second-order loop filter and NCO shared by `Pll` and `CostasLoop`.
+struct Nco {
+    sample_rate: f64,
+    /// Phase in radians.
+    phase: f64,
+    /// Frequency in radians per sample.
+    freq: f64,
+    nominal: f64,
+    /// Largest distance of `freq` from `nominal`.
+    range: f64,
+    /// Loop natural frequency in Hz.
+    bandwidth: f64,
+    damping: f64,
+    kp: f64,
+    ki: f64,
+    /// Smoothed lock metric, near 1.0 when locked.
+    lock: f32,
+    lock_alpha: f32,
+}
+
+impl Nco {
+    fn new(sample_rate: f64, freq: f64, bandwidth: f64, damping: f64) -> Self {
+        let nominal = TAU * freq / sample_rate;
+        let mut nco = Nco {
+            sample_rate,
+            phase: 0.0,
+            freq: nominal,
+            nominal,
+            range: std::f64::consts::PI,
+            bandwidth,
+            damping,
+            kp: 0.0,
+            ki: 0.0,
+            lock: 0.0,
+            lock_alpha: 0.0,
+        };
+        nco.set_gains();
+        nco
+    }
+
+    fn set_gains(&mut self) {
+        let wn = TAU * self.bandwidth / self.sample_rate;
+        self.kp = 2.0 * self.damping * wn;
+        self.ki = wn * wn;
+        self.lock_alpha = (4.0 * wn).min(1.0) as f32;
+    }
+
+    /// Mix a sample down by the current phase.
+    fn derotate(&self, x: Complex<f32>) -> Complex<f32> {
+        let (sin, cos) = self.phase.sin_cos();
+        x * Complex::new(cos as f32, -sin as f32)
+    }
+
+    /// Feed a phase error in radians and a lock metric, and advance.
+    fn advance(&mut self, err: f64, lock: f32) {
+        self.freq += self.ki * err;
+        self.freq = self
+            .freq
+            .clamp(self.nominal - self.range, self.nominal + self.range);
+        self.phase = (self.phase + self.freq + self.kp * err).rem_euclid(TAU);
+        self.lock += self.lock_alpha * (lock - self.lock);
+    }
+
+    fn frequency(&self) -> f64 {
+        self.freq * self.sample_rate / TAU
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.freq = self.nominal;
+        self.lock = 0.0;
+    }
+}
+
+/// Second-order phase-locked loop for a complex carrier.
+///
+/// The NCO starts at the nominal carrier frequency and follows the input's
+/// phase with a proportional-integral loop filter, so it tracks both phase
+/// and frequency offsets. The phase detector measures the exact angle
+/// between the input and the NCO, which makes the loop independent of the
+/// input level.
+pub struct Pll {
+    nco: Nco,
+}
+
+impl Pll {
+    /// Create a loop with a damping factor of 0.707.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `freq` - The nominal carrier frequency in Hz, relative to DC.
+    /// * `bandwidth` - The loop's natural frequency in Hz. Wider loops pull
+    ///   in and follow changes faster; narrower ones reject more noise.
+    ///
+    /// # Returns
+    ///
+    /// A new `Pll`.
+    pub fn new(sample_rate: f64, freq: f64, bandwidth: f64) -> Self {
+        Pll {
+            nco: Nco::new(sample_rate, freq, bandwidth, 0.707),
+        }
+    }
+
+    /// Set the damping factor; 1.0 is critically damped.
+    ///
+    /// # Arguments
+    ///
+    /// * `damping` - The damping factor.
+    ///
+    /// # Returns
+    ///
+    /// The `Pll` with the new damping.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.nco.damping = damping;
+        self.nco.set_gains();
+        self
+    }
+
+    /// Limit how far the loop may pull away from the nominal frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The largest offset in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The `Pll` with the new range.
+    pub fn with_range(mut self, range: f64) -> Self {
+        self.nco.range = TAU * range.abs() / self.nco.sample_rate;
+        self
+    }
+
+    /// Change the loop bandwidth, keeping the loop's state.
+    ///
+    /// # Arguments
+    ///
+    /// * `bandwidth` - The loop's natural frequency in Hz.
+    pub fn set_bandwidth(&mut self, bandwidth: f64) {
+        self.nco.bandwidth = bandwidth;
+        self.nco.set_gains();
+    }
+
+    /// Track one sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The next input sample.
+    ///
+    /// # Returns
+    ///
+    /// The sample with the tracked carrier removed.
+    pub fn step(&mut self, x: Complex<f32>) -> Complex<f32> {
+        let y = self.nco.derotate(x);
+        let norm = y.norm();
+        if norm > 0.0 {
+            self.nco.advance(y.arg() as f64, y.re / norm);
+        } else {
+            self.nco.advance(0.0, 0.0);
+        }
+        y
+    }
+
+    /// Track a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The samples with the tracked carrier removed.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        input.iter().map(|&x| self.step(x)).collect()
+    }
+
+    /// Get the NCO phase.
+    ///
+    /// # Returns
+    ///
+    /// The phase in radians, from 0 to 2π.
+    pub fn phase(&self) -> f64 {
+        self.nco.phase
+    }
+
+    /// Get the frequency the loop is tracking.
+    ///
+    /// # Returns
+    ///
+    /// The NCO frequency in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.nco.frequency()
+    }
+
+    /// Get the smoothed cosine of the phase error.
+    ///
+    /// # Returns
+    ///
+    /// A value near 1.0 while locked and near 0.0 while not.
+    pub fn lock_metric(&self) -> f32 {
+        self.nco.lock
+    }
+
+    /// Check whether the loop is locked.
+    pub fn is_locked(&self) -> bool {
+        self.nco.lock > LOCK_THRESHOLD
+    }
+
+    /// Return to the nominal frequency and forget the lock.
+    pub fn reset(&mut self) {
+        self.nco.reset();
+    }
+}
+
+/// Modulation a `CostasLoop` recovers the carrier of.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CostasOrder {
+    /// Two phases, locking the symbols onto the real axis.
+    Bpsk,
+    /// Four phases, locking the symbols onto the diagonals.
+    Qpsk,
+}
+
+/// Costas loop for suppressed-carrier BPSK and QPSK.
+///
+/// Takes symbols near DC, for example from `FreqXlator`, and removes the
+/// residual carrier phase and frequency with a second-order loop. The phase
+/// detector ignores the data, so the loop locks with a phase ambiguity of
+/// 180° for BPSK and 90° for QPSK, which the decoder has to resolve, usually
+/// through differential coding.
+pub struct CostasLoop {
+    nco: Nco,
+    order: CostasOrder,
+}
+
+impl CostasLoop {
+    /// Create a loop with a damping factor of 0.707.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `bandwidth` - The loop's natural frequency in Hz, typically a few
+    ///   percent of the symbol rate.
+    /// * `order` - The modulation.
+    ///
+    /// # Returns
+    ///
+    /// A new `CostasLoop`.
+    pub fn new(sample_rate: f64, bandwidth: f64, order: CostasOrder) -> Self {
+        CostasLoop {
+            nco: Nco::new(sample_rate, 0.0, bandwidth, 0.707),
+            order,
+        }
+    }
+
+    /// Set the damping factor; 1.0 is critically damped.
+    ///
+    /// # Arguments
+    ///
+    /// * `damping` - The damping factor.
+    ///
+    /// # Returns
+    ///
+    /// The `CostasLoop` with the new damping.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.nco.damping = damping;
+        self.nco.set_gains();
+        self
+    }
+
+    /// Limit how far the loop may pull away from DC.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The largest carrier offset in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The `CostasLoop` with the new range.
+    pub fn with_range(mut self, range: f64) -> Self {
+        self.nco.range = TAU * range.abs() / self.nco.sample_rate;
+        self
+    }
+
+    /// Change the loop bandwidth, keeping the loop's state.
+    ///
+    /// # Arguments
+    ///
+    /// * `bandwidth` - The loop's natural frequency in Hz.
+    pub fn set_bandwidth(&mut self, bandwidth: f64) {
+        self.nco.bandwidth = bandwidth;
+        self.nco.set_gains();
+    }
+
+    /// Get the modulation the loop was created for.
+    pub fn order(&self) -> CostasOrder {
+        self.order
+    }
+
+    /// Track one symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The next input sample.
+    ///
+    /// # Returns
+    ///
+    /// The sample with the residual carrier removed.
+    pub fn step(&mut self, x: Complex<f32>) -> Complex<f32> {
+        let y = self.nco.derotate(x);
+        let norm = y.norm();
+        if norm == 0.0 {
+            self.nco.advance(0.0, 0.0);
+            return y;
+        }
+        let u = y / norm;
+        let (err, lock) = match self.order {
+            CostasOrder::Bpsk => (u.re.signum() * u.im, u.re * u.re - u.im * u.im),
+            CostasOrder::Qpsk => {
+                let u2 = u * u;
+                (u.re.signum() * u.im - u.im.signum() * u.re, -(u2 * u2).re)
+            }
+        };
+        self.nco.advance(err as f64, lock);
+        y
+    }
+
+    /// Track a chunk of symbols.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The samples with the residual carrier removed.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        input.iter().map(|&x| self.step(x)).collect()
+    }
+
+    /// Get the NCO phase.
+    ///
+    /// # Returns
+    ///
+    /// The phase in radians, from 0 to 2π.
+    pub fn phase(&self) -> f64 {
+        self.nco.phase
+    }
+
+    /// Get the carrier offset the loop is tracking.
+    ///
+    /// # Returns
+    ///
+    /// The NCO frequency in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.nco.frequency()
+    }
+
+    /// Get the smoothed lock metric: the cosine of twice the phase error
+    /// for BPSK, of four times it for QPSK.
+    ///
+    /// # Returns
+    ///
+    /// A value near 1.0 while locked and near 0.0 while not.
+    pub fn lock_metric(&self) -> f32 {
+        self.nco.lock
+    }
+
+    /// Check whether the loop is locked.
+    pub fn is_locked(&self) -> bool {
+        self.nco.lock > LOCK_THRESHOLD
+    }
+
+    /// Return to DC and forget the lock.
+    pub fn reset(&mut self) {
+        self.nco.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 48_000.0;
+
+    fn carrier(freq: f64, phase: f64, len: usize) -> impl Iterator<Item = Complex<f32>> {
+        (0..len).map(move |n| {
+            let (sin, cos) = (TAU * freq / RATE * n as f64 + phase).sin_cos();
+            Complex::new(cos as f32, sin as f32)
+        })
+    }
+
+    /// Random symbols, from the constellation points given, on a carrier.
+    fn symbols(points: &[Complex<f32>], freq: f64, phase: f64, len: usize) -> Vec<Complex<f32>> {
+        let mut seed = 0x2545_f491_u32;
+        carrier(freq, phase, len)
+            .map(|c| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                c * points[(seed >> 24) as usize % points.len()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pll_locks_onto_an_offset_carrier() {
+        let mut pll = Pll::new(RATE, 1_000.0, 50.0);
+        let out = pll.process(&carrier(1_080.0, 2.0, 48_000).collect::<Vec<_>>());
+        assert!(
+            (pll.frequency() - 1_080.0).abs() < 0.1,
+            "{}",
+            pll.frequency()
+        );
+        assert!(pll.is_locked(), "lock metric {}", pll.lock_metric());
+        // Locked, the carrier is mixed down to a constant phase of zero.
+        for y in &out[out.len() - 100..] {
+            assert!(y.arg().abs() < 0.01, "{}", y);
+        }
+        // Amplitude makes no difference to the detector.
+        let mut quiet = Pll::new(RATE, 1_000.0, 50.0);
+        let input: Vec<_> = carrier(1_080.0, 2.0, 48_000).map(|c| c * 0.001).collect();
+        quiet.process(&input);
+        assert!((quiet.frequency() - 1_080.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn pll_stays_within_its_range() {
+        let mut pll = Pll::new(RATE, 1_000.0, 50.0).with_range(40.0);
+        pll.process(&carrier(1_080.0, 0.0, 48_000).collect::<Vec<_>>());
+        assert!((pll.frequency() - 1_040.0).abs() < 1e-6);
+        assert!(!pll.is_locked());
+
+        pll.reset();
+        assert!((pll.frequency() - 1_000.0).abs() < 1e-9);
+        assert_eq!(pll.lock_metric(), 0.0);
+    }
+
+    #[test]
+    fn pll_does_not_lock_on_silence() {
+        let mut pll = Pll::new(RATE, 1_000.0, 50.0);
+        pll.process(&vec![Complex::new(0.0, 0.0); 4_800]);
+        assert!(!pll.is_locked());
+        assert!((pll.frequency() - 1_000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn costas_locks_onto_bpsk() {
+        let points = [Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+        let input = symbols(&points, 60.0, 1.0, 48_000);
+        let mut costas = CostasLoop::new(RATE, 100.0, CostasOrder::Bpsk);
+        let out = costas.process(&input);
+        assert!(
+            (costas.frequency() - 60.0).abs() < 0.1,
+            "{}",
+            costas.frequency()
+        );
+        assert!(costas.is_locked(), "lock metric {}", costas.lock_metric());
+        for y in &out[out.len() - 100..] {
+            assert!(y.im.abs() < 0.01 && y.re.abs() > 0.99, "{}", y);
+        }
+    }
+
+    #[test]
+    fn costas_locks_onto_qpsk() {
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        let points = [
+            Complex::new(h, h),
+            Complex::new(-h, h),
+            Complex::new(-h, -h),
+            Complex::new(h, -h),
+        ];
+        let input = symbols(&points, -45.0, -2.5, 48_000);
+        let mut costas = CostasLoop::new(RATE, 100.0, CostasOrder::Qpsk);
+        let out = costas.process(&input);
+        assert!(
+            (costas.frequency() + 45.0).abs() < 0.1,
+            "{}",
+            costas.frequency()
+        );
+        assert!(costas.is_locked(), "lock metric {}", costas.lock_metric());
+        for y in &out[out.len() - 100..] {
+            assert!((y.re.abs() - h).abs() < 0.01 && (y.im.abs() - h).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn costas_bpsk_does_not_lock_onto_qpsk() {
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        let points = [
+            Complex::new(h, h),
+            Complex::new(-h, h),
+            Complex::new(-h, -h),
+            Complex::new(h, -h),
+        ];
+        let mut costas = CostasLoop::new(RATE, 100.0, CostasOrder::Bpsk);
+        costas.process(&symbols(&points, 0.0, 0.3, 48_000));
+        assert!(!costas.is_locked(), "lock metric {}", costas.lock_metric());
+    }
+}
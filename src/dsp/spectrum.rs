@@ -0,0 +1,190 @@
+//! Power spectra and waterfalls.
+
+use num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::sync::Arc;
+
+use super::fir::Window;
+
+mod waterfall;
+
+pub use waterfall::{Colormap, Waterfall};
+
+/// Welch power spectrum estimator.
+///
+/// Samples are cut into overlapping frames, windowed and transformed, and the
+/// power of each bin is averaged over all frames since the last `take_power`
+/// or `take_dbfs`. Frames may span calls to `push`, so a stream can be fed in
+/// chunks of any size.
+///
+/// Bins are ordered from the most negative frequency to the most positive,
+/// with DC at index `len / 2`. Levels are normalised to the window's coherent
+/// gain, so a complex tone of amplitude 1.0 centred on a bin reads 0 dBFS.
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Samples between the starts of consecutive frames.
+    hop: usize,
+    /// Samples not yet part of a complete frame.
+    pending: Vec<Complex<f32>>,
+    buf: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    power: Vec<f64>,
+    frames: usize,
+    /// Scale from |X|^2 to power relative to full scale.
+    norm: f64,
+}
+
+impl Spectrum {
+    /// Create an estimator with 50% overlap.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The FFT size; the frequency resolution is
+    ///   `sample_rate / len`.
+    /// * `window` - The window applied to each frame.
+    ///
+    /// # Returns
+    ///
+    /// A new `Spectrum`.
+    pub fn new(len: usize, window: Window) -> Self {
+        assert!(len > 1, "FFT needs at least two bins");
+        let fft = FftPlanner::new().plan_fft_forward(len);
+        // Periodic window, which is what spectral analysis wants.
+        let window: Vec<f32> = (0..len).map(|n| window.value(n, len + 1) as f32).collect();
+        let gain: f64 = window.iter().map(|&w| w as f64).sum();
+        let scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+        Spectrum {
+            fft,
+            window,
+            hop: len / 2,
+            pending: Vec::new(),
+            buf: vec![Complex::new(0.0, 0.0); len],
+            scratch,
+            power: vec![0.0; len],
+            frames: 0,
+            norm: 1.0 / (gain * gain),
+        }
+    }
+
+    /// Set the overlap between consecutive frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `overlap` - Fraction of a frame shared with the next one, from 0.0
+    ///   up to but not including 1.0.
+    ///
+    /// # Returns
+    ///
+    /// The `Spectrum` with the new overlap.
+    pub fn with_overlap(mut self, overlap: f64) -> Self {
+        let overlap = overlap.clamp(0.0, 0.99);
+        self.hop = ((self.len() as f64 * (1.0 - overlap)).round() as usize).max(1);
+        self
+    }
+
+    /// Get the FFT size.
+    ///
+    /// # Returns
+    ///
+    /// The number of bins.
+    pub fn len(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Always `false`; a spectrum has at least two bins.
+    ///
+    /// # Returns
+    ///
+    /// `false`.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Get the number of frames averaged so far.
+    ///
+    /// # Returns
+    ///
+    /// The frame count since the last `take_power` or `take_dbfs`.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Get the frequency of a bin.
+    ///
+    /// # Arguments
+    ///
+    /// * `bin` - The bin index.
+    /// * `sample_rate` - The sample rate of the input in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the bin centre from DC in Hz.
+    pub fn bin_frequency(&self, bin: usize, sample_rate: f64) -> f64 {
+        (bin as f64 - (self.len() / 2) as f64) * sample_rate / self.len() as f64
+    }
+
+    /// Add samples to the average.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream.
+    pub fn push(&mut self, iq: &[Complex<f32>]) {
+        let n = self.len();
+        self.pending.extend_from_slice(iq);
+        let mut start = 0;
+        while start + n <= self.pending.len() {
+            let frame = &self.pending[start..start + n];
+            for ((b, &x), &w) in self.buf.iter_mut().zip(frame).zip(&self.window) {
+                *b = x * w;
+            }
+            self.fft
+                .process_with_scratch(&mut self.buf, &mut self.scratch);
+            for (i, b) in self.buf.iter().enumerate() {
+                self.power[(i + n / 2) % n] += b.norm_sqr() as f64;
+            }
+            self.frames += 1;
+            start += self.hop;
+        }
+        self.pending.drain(..start.min(self.pending.len()));
+    }
+
+    /// Take the averaged power spectrum and start a new average.
+    ///
+    /// # Returns
+    ///
+    /// Linear power per bin relative to full scale, or `None` if no complete
+    /// frame has been pushed.
+    pub fn take_power(&mut self) -> Option<Vec<f64>> {
+        if self.frames == 0 {
+            return None;
+        }
+        let scale = self.norm / self.frames as f64;
+        let power = self.power.iter().map(|p| p * scale).collect();
+        self.power.fill(0.0);
+        self.frames = 0;
+        Some(power)
+    }
+
+    /// Take the averaged power spectrum in dBFS and start a new average.
+    ///
+    /// # Returns
+    ///
+    /// Power per bin in dBFS, or `None` if no complete frame has been
+    /// pushed.
+    pub fn take_dbfs(&mut self) -> Option<Vec<f32>> {
+        self.take_power().map(|power| {
+            power
+                .into_iter()
+                .map(|p| (10.0 * p.max(1e-30).log10()) as f32)
+                .collect()
+        })
+    }
+
+    /// Drop the running average and any partial frame.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.power.fill(0.0);
+        self.frames = 0;
+    }
+}
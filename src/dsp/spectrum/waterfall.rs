@@ -0,0 +1,264 @@
+use num_complex::Complex;
+use std::collections::VecDeque;
+
+use super::Spectrum;
+use crate::dsp::fir::Window;
+#[cfg(feature = "png")]
+use crate::error::{Error, Result};
+
+/// Colour scale used when rendering a `Waterfall` or sweep heatmap to RGB.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Grayscale,
+    /// Black through blue, cyan and yellow to white, the classic SDR look.
+    Heat,
+    /// Dark purple through blue and green to yellow, evenly bright to the
+    /// eye and readable in greyscale and by colour-blind viewers.
+    Viridis,
+}
+
+impl Colormap {
+    pub(crate) fn rgb(self, level: u8) -> [u8; 3] {
+        const HEAT: [[f32; 3]; 5] = [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 255.0],
+            [0.0, 255.0, 255.0],
+            [255.0, 255.0, 0.0],
+            [255.0, 255.0, 255.0],
+        ];
+        const VIRIDIS: [[f32; 3]; 5] = [
+            [68.0, 1.0, 84.0],
+            [59.0, 82.0, 139.0],
+            [33.0, 145.0, 140.0],
+            [94.0, 201.0, 98.0],
+            [253.0, 231.0, 37.0],
+        ];
+        let stops = match self {
+            Colormap::Grayscale => return [level; 3],
+            Colormap::Heat => &HEAT,
+            Colormap::Viridis => &VIRIDIS,
+        };
+        let x = level as f32 / 255.0 * (stops.len() - 1) as f32;
+        let i = (x as usize).min(stops.len() - 2);
+        let t = x - i as f32;
+        let mut rgb = [0; 3];
+        for (c, out) in rgb.iter_mut().enumerate() {
+            *out = (stops[i][c] + t * (stops[i + 1][c] - stops[i][c])) as u8;
+        }
+        rgb
+    }
+}
+
+/// Rolling matrix of spectrum lines for waterfall displays.
+///
+/// Every line is the Welch average of a configurable number of frames, in
+/// dBFS with DC in the middle. The newest `history` lines are kept; older
+/// ones are dropped. Lines can be read as dB values, as bytes scaled to a dB
+/// range for texture uploads, or exported as a PNG with the `png` feature.
+pub struct Waterfall {
+    spectrum: Spectrum,
+    frames_per_line: usize,
+    history: usize,
+    lines: VecDeque<Vec<f32>>,
+    min_db: f32,
+    max_db: f32,
+}
+
+impl Waterfall {
+    /// Create a waterfall with one frame per line and a -120 to 0 dBFS
+    /// range.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_len` - Width of the waterfall in bins.
+    /// * `window` - The window applied to each frame.
+    /// * `history` - Number of lines kept.
+    ///
+    /// # Returns
+    ///
+    /// A new `Waterfall`.
+    pub fn new(fft_len: usize, window: Window, history: usize) -> Self {
+        Waterfall {
+            spectrum: Spectrum::new(fft_len, window),
+            frames_per_line: 1,
+            history: history.max(1),
+            lines: VecDeque::with_capacity(history),
+            min_db: -120.0,
+            max_db: 0.0,
+        }
+    }
+
+    /// Set how many frames are averaged into each line.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - Frames per line; more gives a smoother, slower waterfall.
+    ///
+    /// # Returns
+    ///
+    /// The `Waterfall` with the new averaging.
+    pub fn with_averaging(mut self, frames: usize) -> Self {
+        self.frames_per_line = frames.max(1);
+        self
+    }
+
+    /// Set the dB range mapped onto the colour scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_db` - Level shown as the lowest colour.
+    /// * `max_db` - Level shown as the highest colour.
+    ///
+    /// # Returns
+    ///
+    /// The `Waterfall` with the new range.
+    pub fn with_range(mut self, min_db: f32, max_db: f32) -> Self {
+        self.min_db = min_db;
+        self.max_db = max_db;
+        self
+    }
+
+    /// Get the width of each line.
+    ///
+    /// # Returns
+    ///
+    /// The number of bins per line.
+    pub fn width(&self) -> usize {
+        self.spectrum.len()
+    }
+
+    /// Get the number of lines currently held.
+    ///
+    /// # Returns
+    ///
+    /// At most the configured history.
+    pub fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Add samples, producing new lines as enough frames are averaged.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The next chunk of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The number of lines added.
+    pub fn push(&mut self, iq: &[Complex<f32>]) -> usize {
+        let mut added = 0;
+        // Half a frame at a time adds at most one frame to the spectrum, so
+        // lines are cut after exactly `frames_per_line` frames.
+        for piece in iq.chunks((self.width() / 2).max(1)) {
+            self.spectrum.push(piece);
+            if self.spectrum.frames() >= self.frames_per_line {
+                if let Some(line) = self.spectrum.take_dbfs() {
+                    if self.lines.len() == self.history {
+                        self.lines.pop_front();
+                    }
+                    self.lines.push_back(line);
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+
+    /// Iterate over the lines.
+    ///
+    /// # Returns
+    ///
+    /// The lines in dBFS, newest first.
+    pub fn lines(&self) -> impl Iterator<Item = &[f32]> {
+        self.lines.iter().rev().map(Vec::as_slice)
+    }
+
+    /// Get the most recent line.
+    ///
+    /// # Returns
+    ///
+    /// The newest line in dBFS, or `None` before the first.
+    pub fn latest(&self) -> Option<&[f32]> {
+        self.lines.back().map(Vec::as_slice)
+    }
+
+    fn level(&self, db: f32) -> u8 {
+        let span = (self.max_db - self.min_db).max(f32::EPSILON);
+        ((db - self.min_db) / span * 255.0).clamp(0.0, 255.0) as u8
+    }
+
+    /// Render the waterfall as bytes scaled to the dB range.
+    ///
+    /// # Returns
+    ///
+    /// `width() * height()` bytes, row-major with the newest line first.
+    pub fn to_levels(&self) -> Vec<u8> {
+        self.lines()
+            .flat_map(|line| line.iter().map(|&db| self.level(db)))
+            .collect()
+    }
+
+    /// Render the waterfall as RGB pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `colormap` - The colour scale to use.
+    ///
+    /// # Returns
+    ///
+    /// `width() * height() * 3` bytes, row-major with the newest line first.
+    pub fn to_rgb(&self, colormap: Colormap) -> Vec<u8> {
+        self.to_levels()
+            .into_iter()
+            .flat_map(|level| colormap.rgb(level))
+            .collect()
+    }
+
+    /// Write the waterfall as a PNG image, newest line at the top.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the image.
+    /// * `colormap` - The colour scale to use.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`. An empty waterfall
+    /// is written as a single black line.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: std::io::Write>(&self, writer: W, colormap: Colormap) -> Result<()> {
+        let height = self.height().max(1);
+        let mut pixels = self.to_rgb(colormap);
+        pixels.resize(self.width() * height * 3, 0);
+
+        let mut encoder = png::Encoder::new(writer, self.width() as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let to_error = |e: png::EncodingError| Error::System(std::io::Error::other(e));
+        let mut png = encoder.write_header().map_err(to_error)?;
+        png.write_image_data(&pixels).map_err(to_error)?;
+        png.finish().map_err(to_error)
+    }
+
+    /// Save the waterfall as a PNG file, newest line at the top.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    /// * `colormap` - The colour scale to use.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P, colormap: Colormap) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file), colormap)
+    }
+
+    /// Drop all lines and any partially averaged frames.
+    pub fn clear(&mut self) {
+        self.spectrum.clear();
+        self.lines.clear();
+    }
+}
@@ -0,0 +1,314 @@
+use num_complex::Complex;
+
+/// Timing error detector used by a `SymbolSync`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimingDetector {
+    /// Gardner's detector, which compares the slope across each symbol with
+    /// the sample halfway between. Works with any modulation and before
+    /// carrier recovery, but needs at least two samples per symbol.
+    Gardner,
+    /// Mueller and Müller's decision-directed detector, which only looks at
+    /// the symbol samples. Suits binary and QPSK signals after carrier
+    /// recovery.
+    MuellerMuller,
+}
+
+/// Symbol timing recovery.
+///
+/// Interpolates the filtered baseband at the estimated symbol instants and
+/// steers them with a second-order loop driven by a timing error detector,
+/// so the symbol rate may be a non-integer number of samples and drift
+/// with the transmitter's clock. The input should already be matched
+/// filtered; the output is one sample per symbol, taken at the eye's
+/// widest opening.
+pub struct SymbolSync {
+    detector: TimingDetector,
+    nominal: f64,
+    /// Current estimate of the samples per symbol.
+    omega: f64,
+    /// Largest distance of `omega` from `nominal`.
+    max_deviation: f64,
+    /// Proportional and integral gains, per unit of normalised error.
+    kp: f64,
+    ki: f64,
+    /// Inputs still needed for interpolation, followed by the current chunk.
+    work: Vec<Complex<f32>>,
+    /// Position of the next symbol in `work`, in samples.
+    next: f64,
+    prev: Complex<f32>,
+    /// Average symbol power, for normalising the error.
+    power: f32,
+}
+
+impl SymbolSync {
+    /// Create a synchronizer with a Gardner detector, allowing the symbol
+    /// rate to deviate 1% from nominal.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_per_symbol` - The nominal input samples per symbol, at
+    ///   least 2 and not necessarily an integer.
+    /// * `bandwidth` - The loop bandwidth as a fraction of the symbol rate;
+    ///   0.01 is a good start, less for noisy signals.
+    ///
+    /// # Returns
+    ///
+    /// A new `SymbolSync`.
+    pub fn new(samples_per_symbol: f64, bandwidth: f64) -> Self {
+        assert!(
+            samples_per_symbol >= 2.0,
+            "symbol sync needs at least two samples per symbol"
+        );
+        // Standard second-order loop with a damping factor of 0.707.
+        let damping = std::f64::consts::FRAC_1_SQRT_2;
+        let theta = bandwidth / (damping + 0.25 / damping);
+        let d = 1.0 + 2.0 * damping * theta + theta * theta;
+        SymbolSync {
+            detector: TimingDetector::Gardner,
+            nominal: samples_per_symbol,
+            omega: samples_per_symbol,
+            max_deviation: 0.01 * samples_per_symbol,
+            kp: 4.0 * damping * theta / d * samples_per_symbol,
+            ki: 4.0 * theta * theta / d * samples_per_symbol,
+            work: vec![Complex::new(0.0, 0.0); 2],
+            next: 2.0 + samples_per_symbol,
+            prev: Complex::new(0.0, 0.0),
+            power: 0.0,
+        }
+    }
+
+    /// Set the timing error detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - The detector to use.
+    ///
+    /// # Returns
+    ///
+    /// The `SymbolSync` with the new detector.
+    pub fn with_detector(mut self, detector: TimingDetector) -> Self {
+        self.detector = detector;
+        self
+    }
+
+    /// Set how far the symbol rate may deviate from nominal.
+    ///
+    /// # Arguments
+    ///
+    /// * `fraction` - The largest deviation as a fraction of the nominal
+    ///   samples per symbol.
+    ///
+    /// # Returns
+    ///
+    /// The `SymbolSync` with the new limit.
+    pub fn with_max_deviation(mut self, fraction: f64) -> Self {
+        self.max_deviation = fraction.abs() * self.nominal;
+        self
+    }
+
+    /// Get the tracked number of samples per symbol.
+    pub fn samples_per_symbol(&self) -> f64 {
+        self.omega
+    }
+
+    /// Recover symbols from a chunk of complex baseband.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives one sample per symbol, appended.
+    pub fn process_into(&mut self, input: &[Complex<f32>], out: &mut Vec<Complex<f32>>) {
+        self.work.extend_from_slice(input);
+        while self.next.floor() as usize + 2 < self.work.len() {
+            let symbol = interpolate(&self.work, self.next);
+            let err = match self.detector {
+                TimingDetector::Gardner => {
+                    let mid = interpolate(&self.work, self.next - self.omega / 2.0);
+                    ((self.prev - symbol) * mid.conj()).re
+                }
+                TimingDetector::MuellerMuller => {
+                    let decide = |z: Complex<f32>| Complex::new(z.re.signum(), z.im.signum());
+                    (decide(self.prev).conj() * symbol - decide(symbol).conj() * self.prev).re
+                }
+            };
+            self.power += 0.01 * (symbol.norm_sqr() - self.power);
+            let err = (err / self.power.max(f32::MIN_POSITIVE)).clamp(-1.0, 1.0) as f64;
+
+            self.omega = (self.omega + self.ki * err).clamp(
+                self.nominal - self.max_deviation,
+                self.nominal + self.max_deviation,
+            );
+            self.next += self.omega + self.kp * err;
+            self.prev = symbol;
+            out.push(symbol);
+        }
+
+        // Keep what the next half-symbol and interpolation look back on.
+        let keep = (self.omega / 2.0).ceil() as usize + 2;
+        let drop = (self.next.floor() as usize)
+            .saturating_sub(keep)
+            .min(self.work.len());
+        self.work.drain(..drop);
+        self.next -= drop as f64;
+    }
+
+    /// Recover symbols from a chunk of complex baseband.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// One sample per symbol.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = Vec::with_capacity((input.len() as f64 / self.omega) as usize + 1);
+        self.process_into(input, &mut out);
+        out
+    }
+
+    /// Recover symbols from a chunk of real baseband, such as FSK
+    /// discriminator output.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// One sample per symbol.
+    pub fn process_real(&mut self, input: &[f32]) -> Vec<f32> {
+        let input: Vec<Complex<f32>> = input.iter().map(|&x| Complex::new(x, 0.0)).collect();
+        self.process(&input).into_iter().map(|z| z.re).collect()
+    }
+
+    /// Return to the nominal rate and clear the history.
+    pub fn reset(&mut self) {
+        self.omega = self.nominal;
+        self.work.clear();
+        self.work.resize(2, Complex::new(0.0, 0.0));
+        self.next = 2.0 + self.nominal;
+        self.prev = Complex::new(0.0, 0.0);
+        self.power = 0.0;
+    }
+}
+
+/// Cubic Lagrange interpolation of `x` at position `t`, which must have a
+/// sample before it and two after.
+fn interpolate(x: &[Complex<f32>], t: f64) -> Complex<f32> {
+    let i = t.floor() as usize;
+    let mu = (t - i as f64) as f32;
+    let (a, b, c, d) = (x[i - 1], x[i], x[i + 1], x[i + 2]);
+    let w0 = -mu * (mu - 1.0) * (mu - 2.0) / 6.0;
+    let w1 = (mu + 1.0) * (mu - 1.0) * (mu - 2.0) / 2.0;
+    let w2 = -(mu + 1.0) * mu * (mu - 2.0) / 2.0;
+    let w3 = (mu + 1.0) * mu * (mu - 1.0) / 6.0;
+    a * w0 + b * w1 + c * w2 + d * w3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// Raised cosine pulse with a roll-off of 0.5, one symbol long.
+    fn pulse(t: f64) -> f64 {
+        let sinc = if t == 0.0 {
+            1.0
+        } else {
+            (PI * t).sin() / (PI * t)
+        };
+        let d = 1.0 - 4.0 * 0.25 * t * t;
+        let shape = if d.abs() < 1e-9 {
+            PI / 4.0
+        } else {
+            (PI * 0.5 * t).cos() / d
+        };
+        sinc * shape
+    }
+
+    /// Random BPSK symbols and their pulse shaped baseband, `sps` samples
+    /// per symbol, starting `offset` samples into the first symbol.
+    fn bpsk(count: usize, sps: f64, offset: f64) -> (Vec<f32>, Vec<f32>) {
+        let mut seed = 0x1234_5678_u32;
+        let data: Vec<f32> = (0..count)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                if seed >> 31 == 0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            })
+            .collect();
+        let len = (count as f64 * sps) as usize;
+        let baseband = (0..len)
+            .map(|n| {
+                let t = (n as f64 + offset) / sps;
+                let k = t.round() as i64;
+                (k - 6..=k + 6)
+                    .filter(|&k| k >= 0 && (k as usize) < count)
+                    .map(|k| data[k as usize] as f64 * pulse(t - k as f64))
+                    .sum::<f64>() as f32
+            })
+            .collect();
+        (data, baseband)
+    }
+
+    /// Check the last symbols recovered are clean and match the data.
+    fn assert_converged(data: &[f32], symbols: &[f32]) {
+        let tail = &symbols[symbols.len() - 200..symbols.len() - 10];
+        for &s in tail {
+            assert!((s.abs() - 1.0).abs() < 0.05, "eye not open: {}", s);
+        }
+        let decided: Vec<f32> = tail.iter().map(|s| s.signum()).collect();
+        assert!(
+            data.windows(decided.len()).any(|w| w == decided),
+            "symbols do not match the data"
+        );
+    }
+
+    #[test]
+    fn gardner_converges_on_an_off_nominal_rate() {
+        let (data, baseband) = bpsk(3000, 4.1, 1.7);
+        let mut sync = SymbolSync::new(4.0, 0.01).with_max_deviation(0.05);
+        let symbols = sync.process_real(&baseband);
+        assert!((sync.samples_per_symbol() - 4.1).abs() < 0.005);
+        assert_converged(&data, &symbols);
+    }
+
+    #[test]
+    fn mueller_muller_converges_on_an_off_nominal_rate() {
+        let (data, baseband) = bpsk(3000, 7.9, 3.2);
+        let mut sync = SymbolSync::new(8.0, 0.01)
+            .with_detector(TimingDetector::MuellerMuller)
+            .with_max_deviation(0.05);
+        let symbols = sync.process_real(&baseband);
+        assert!((sync.samples_per_symbol() - 7.9).abs() < 0.01);
+        assert_converged(&data, &symbols);
+    }
+
+    #[test]
+    fn deviation_is_limited() {
+        let (_, baseband) = bpsk(2000, 4.1, 0.0);
+        let mut sync = SymbolSync::new(4.0, 0.01);
+        sync.process_real(&baseband);
+        assert!((sync.samples_per_symbol() - 4.04).abs() < 1e-9);
+
+        sync.reset();
+        assert_eq!(sync.samples_per_symbol(), 4.0);
+    }
+
+    #[test]
+    fn chunking_does_not_change_the_output() {
+        let (_, baseband) = bpsk(500, 4.3, 0.5);
+        let baseband: Vec<Complex<f32>> = baseband.iter().map(|&x| Complex::new(x, -x)).collect();
+        let whole = SymbolSync::new(4.3, 0.01).process(&baseband);
+        let mut sync = SymbolSync::new(4.3, 0.01);
+        let mut chunked = Vec::new();
+        for chunk in baseband.chunks(7) {
+            sync.process_into(chunk, &mut chunked);
+        }
+        assert_eq!(whole, chunked);
+    }
+}
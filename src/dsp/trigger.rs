@@ -0,0 +1,153 @@
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+use super::fir::{low_pass, Decimator, Window};
+use super::spectrum::Spectrum;
+
+/// A signal caught by `WidebandTrigger`.
+#[derive(Clone, Debug)]
+pub struct WidebandEvent {
+    /// Lower edge of the signal as an offset from the centre frequency in Hz.
+    pub low_offset_hz: f64,
+    /// Upper edge of the signal as an offset from the centre frequency in Hz.
+    pub high_offset_hz: f64,
+    /// Peak power above the noise floor in dB.
+    pub peak_db: f64,
+    /// The chunk that triggered, shifted so the signal is centred on DC and
+    /// filtered and decimated to its bandwidth.
+    pub iq: Vec<Complex<f32>>,
+    /// The sample rate of `iq` in Hz.
+    pub sample_rate: f64,
+}
+
+impl WidebandEvent {
+    /// Get the occupied bandwidth.
+    ///
+    /// # Returns
+    ///
+    /// The width between the detected edges in Hz.
+    pub fn bandwidth_hz(&self) -> f64 {
+        self.high_offset_hz - self.low_offset_hz
+    }
+
+    /// Get the centre of the signal.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the signal centre from the tuned frequency in Hz.
+    pub fn centre_offset_hz(&self) -> f64 {
+        (self.low_offset_hz + self.high_offset_hz) / 2.0
+    }
+}
+
+/// Trigger that fires when a signal wider than a given bandwidth appears
+/// anywhere in the monitored span.
+///
+/// Each chunk is reduced to an averaged power spectrum, the noise floor is
+/// taken as the median bin, and contiguous runs of bins above the floor plus a
+/// threshold are reported if they are at least `min_bandwidth_hz` wide. Unlike
+/// a channel squelch this needs no prior knowledge of where the signal is,
+/// which makes it suitable for hunting interference.
+pub struct WidebandTrigger {
+    sample_rate: f64,
+    min_bandwidth_hz: f64,
+    threshold_db: f64,
+    spectrum: Spectrum,
+}
+
+impl WidebandTrigger {
+    /// Create a trigger.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate of the input in Hz.
+    /// * `fft_len` - The FFT size; the frequency resolution is
+    ///   `sample_rate / fft_len`.
+    /// * `min_bandwidth_hz` - Narrowest signal that fires the trigger.
+    /// * `threshold_db` - How far above the noise floor a bin must be to
+    ///   count as occupied.
+    ///
+    /// # Returns
+    ///
+    /// A new `WidebandTrigger`.
+    pub fn new(sample_rate: f64, fft_len: usize, min_bandwidth_hz: f64, threshold_db: f64) -> Self {
+        WidebandTrigger {
+            sample_rate,
+            min_bandwidth_hz,
+            threshold_db,
+            spectrum: Spectrum::new(fft_len, Window::Hann),
+        }
+    }
+
+    /// Look for wide signals in a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - Baseband samples. Must hold at least one FFT length; a
+    ///   trailing partial frame is ignored for detection but included in
+    ///   the captured IQ.
+    ///
+    /// # Returns
+    ///
+    /// One event per detected signal, in order of frequency.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<WidebandEvent> {
+        self.spectrum.clear();
+        self.spectrum.push(iq);
+        let Some(power) = self.spectrum.take_power() else {
+            return Vec::new();
+        };
+        let n = power.len();
+        let mut sorted = power.clone();
+        sorted.sort_by(f64::total_cmp);
+        let floor = sorted[n / 2].max(f64::MIN_POSITIVE);
+        let limit = floor * 10f64.powf(self.threshold_db / 10.0);
+
+        let bin_hz = self.sample_rate / n as f64;
+        let mut events = Vec::new();
+        let mut i = 0;
+        while i < n {
+            if power[i] <= limit {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            let mut peak: f64 = 0.0;
+            // Allow single-bin dips so a modulated signal isn't split in two.
+            while i < n && (power[i] > limit || (i + 1 < n && power[i + 1] > limit)) {
+                peak = peak.max(power[i]);
+                i += 1;
+            }
+            let low = (start as f64 - n as f64 / 2.0) * bin_hz;
+            let high = (i as f64 - n as f64 / 2.0) * bin_hz;
+            if high - low >= self.min_bandwidth_hz {
+                events.push(self.capture(iq, low, high, 10.0 * (peak / floor).log10()));
+            }
+        }
+        events
+    }
+
+    /// Shift, filter and decimate the chunk down to the detected signal.
+    fn capture(&self, iq: &[Complex<f32>], low: f64, high: f64, peak_db: f64) -> WidebandEvent {
+        let centre = (low + high) / 2.0;
+        let width = high - low;
+        let factor = ((self.sample_rate / (width * 1.25)).floor() as usize).max(1);
+
+        let step = -2.0 * PI * centre / self.sample_rate;
+        let shifted: Vec<Complex<f32>> = iq
+            .iter()
+            .enumerate()
+            .map(|(k, &x)| x * Complex::from_polar(1.0, (step * k as f64) as f32))
+            .collect();
+
+        let cutoff = (width / 2.0 / self.sample_rate).min(0.5);
+        let mut decimator =
+            Decimator::new(factor, &low_pass(cutoff, 8 * factor + 1, Window::Blackman));
+        WidebandEvent {
+            low_offset_hz: low,
+            high_offset_hz: high,
+            peak_db,
+            iq: decimator.decimate(&shifted),
+            sample_rate: self.sample_rate / factor as f64,
+        }
+    }
+}
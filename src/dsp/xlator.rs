@@ -0,0 +1,417 @@
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+use super::fir::{low_pass, Window};
+use crate::error::{Error, Result};
+use crate::samples::{to_complex_f32_into, Scaling};
+use crate::source::SdrSource;
+use crate::tuner::SamplingMode;
+
+/// Frequency-translating decimating FIR filter.
+///
+/// Selects a channel anywhere inside the captured bandwidth: the channel at
+/// `offset` is mixed down to DC, low-pass filtered and decimated in a single
+/// pass. Rather than mixing every input sample, the filter taps are shifted
+/// up to the channel and only the decimated outputs are mixed down, so the
+/// cost is that of a plain decimating filter.
+///
+/// Tuning the dongle a little away from the wanted signal and selecting it
+/// with an offset keeps it clear of the DC spike.
+pub struct FreqXlator {
+    sample_rate: f64,
+    offset: f64,
+    factor: usize,
+    /// Prototype low-pass taps, in order.
+    prototype: Vec<f32>,
+    /// Band-pass taps shifted to the offset, in reverse order.
+    taps: Vec<Complex<f32>>,
+    /// The last `taps - 1` inputs followed by the current chunk.
+    work: Vec<Complex<f32>>,
+    /// Offset of the next output's newest input past the window start.
+    phase: usize,
+    /// Phase of the mixer at the next output's newest input, in radians.
+    mixer: f64,
+}
+
+impl FreqXlator {
+    /// Create a translating filter with explicit taps.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `offset` - The centre of the wanted channel relative to the input's
+    ///   centre in Hz.
+    /// * `factor` - Decimation factor. Must be non-zero.
+    /// * `taps` - Low-pass channel filter taps at the input rate. Must not
+    ///   be empty.
+    ///
+    /// # Returns
+    ///
+    /// A new `FreqXlator`.
+    pub fn new(sample_rate: f64, offset: f64, factor: usize, taps: &[f32]) -> Self {
+        assert!(factor > 0, "decimation factor must be non-zero");
+        assert!(!taps.is_empty(), "filter needs at least one tap");
+        let mut xlator = FreqXlator {
+            sample_rate,
+            offset,
+            factor,
+            prototype: taps.to_vec(),
+            taps: Vec::new(),
+            work: vec![Complex::new(0.0, 0.0); taps.len() - 1],
+            phase: 0,
+            mixer: 0.0,
+        };
+        xlator.shift_taps();
+        xlator
+    }
+
+    /// Create a translating filter with a Blackman-windowed low-pass
+    /// cutting off at 80% of the output Nyquist frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz.
+    /// * `offset` - The centre of the wanted channel relative to the input's
+    ///   centre in Hz.
+    /// * `factor` - Decimation factor. Must be non-zero.
+    /// * `taps` - Number of filter taps.
+    ///
+    /// # Returns
+    ///
+    /// A new `FreqXlator`.
+    pub fn with_low_pass(sample_rate: f64, offset: f64, factor: usize, taps: usize) -> Self {
+        let cutoff = 0.4 / factor as f64;
+        Self::new(
+            sample_rate,
+            offset,
+            factor,
+            &low_pass(cutoff, taps, Window::Blackman),
+        )
+    }
+
+    /// Get the offset of the selected channel in Hz.
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Select another channel, keeping the filter history so the output
+    /// stays continuous.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The centre of the wanted channel relative to the input's
+    ///   centre in Hz.
+    pub fn set_offset(&mut self, offset: f64) {
+        self.offset = offset;
+        self.shift_taps();
+    }
+
+    /// Get the decimation factor.
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Get the output sample rate in Hz.
+    pub fn output_rate(&self) -> f64 {
+        self.sample_rate / self.factor as f64
+    }
+
+    /// Translate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    /// * `out` - Receives the output samples, appended.
+    pub fn process_into(&mut self, input: &[Complex<f32>], out: &mut Vec<Complex<f32>>) {
+        let len = self.taps.len();
+        let step = self.omega() * self.factor as f64;
+        self.work.extend_from_slice(input);
+
+        let mut end = len - 1 + self.phase;
+        while end < self.work.len() {
+            let filtered = self.work[end + 1 - len..=end]
+                .iter()
+                .zip(&self.taps)
+                .fold(Complex::new(0.0, 0.0), |acc, (&x, &h)| acc + x * h);
+            let (sin, cos) = (-self.mixer).sin_cos();
+            out.push(filtered * Complex::new(cos as f32, sin as f32));
+            self.mixer = (self.mixer + step).rem_euclid(2.0 * PI);
+            end += self.factor;
+        }
+
+        self.phase = end - self.work.len();
+        self.work.drain(..self.work.len() - (len - 1));
+    }
+
+    /// Translate a chunk of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The output samples.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let mut out = Vec::with_capacity(input.len() / self.factor + 1);
+        self.process_into(input, &mut out);
+        out
+    }
+
+    /// Clear the filter history, as if no samples had been seen.
+    pub fn reset(&mut self) {
+        self.work.fill(Complex::new(0.0, 0.0));
+        self.phase = 0;
+        self.mixer = 0.0;
+    }
+
+    /// Offset in radians per input sample.
+    fn omega(&self) -> f64 {
+        2.0 * PI * self.offset / self.sample_rate
+    }
+
+    /// Shift the prototype up to the offset: `h[n] e^(j w n)`, reversed.
+    fn shift_taps(&mut self) {
+        let omega = self.omega();
+        self.taps = self
+            .prototype
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(n, &h)| {
+                let (sin, cos) = (omega * n as f64).sin_cos();
+                Complex::new(h * cos as f32, h * sin as f32)
+            })
+            .collect();
+    }
+}
+
+/// An `SdrSource` that keeps the tuned frequency off the DC spike.
+///
+/// `tune` sets the hardware `margin` Hz away from the requested frequency
+/// and reads are shifted back with a `FreqXlator`, so the requested
+/// frequency still sits at the centre of every read while the dongle's DC
+/// offset and its close-in noise end up `margin` Hz to one side. Anything
+/// built on `SdrSource` gets this without changes. `center_freq` reports
+/// the requested frequency.
+///
+/// The shift wraps the spectrum around, so the outer `margin` Hz on the
+/// other side hold what lies beyond the band edge; keep the margin small
+/// against the sample rate. Samples are shifted in floating point and
+/// rounded back to 8 bits, which adds about half an LSB of noise.
+///
+/// This is done in software and works with every tuner, unlike
+/// `Device::set_offset_tuning`, which only the E4000 supports.
+///
+/// ```no_run
+/// use radion::dsp::OffsetTuned;
+/// use radion::{Device, SdrSource};
+///
+/// let device = Device::new(0)?;
+/// device.set_sample_rate(2_048_000)?;
+/// device.reset_buffer()?;
+/// // The dongle tunes to 145.8 MHz; reads are centred on 145.5 MHz.
+/// let mut source = OffsetTuned::new(device, 300_000)?;
+/// source.tune(145_500_000)?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct OffsetTuned<S> {
+    source: S,
+    margin: i32,
+    xlator: FreqXlator,
+    iq: Vec<Complex<f32>>,
+    shifted: Vec<Complex<f32>>,
+}
+
+impl<S: SdrSource> OffsetTuned<S> {
+    /// Wrap a source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to tune.
+    /// * `margin` - How far above the requested frequency to tune the
+    ///   hardware in Hz, negative for below. Must be within half the sample
+    ///   rate.
+    ///
+    /// # Returns
+    ///
+    /// A new `OffsetTuned`, `Error::InvalidParam` if `margin` is not within
+    /// half the sample rate, otherwise an `Error` if the sample rate could
+    /// not be read.
+    pub fn new(source: S, margin: i32) -> Result<Self> {
+        let xlator = shifter(source.sample_rate()?, margin)?;
+        Ok(OffsetTuned {
+            source,
+            margin,
+            xlator,
+            iq: Vec::new(),
+            shifted: Vec::new(),
+        })
+    }
+
+    /// Get how far the hardware is tuned from the requested frequency in
+    /// Hz.
+    pub fn margin(&self) -> i32 {
+        self.margin
+    }
+
+    /// Get the wrapped source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the wrapped source mutably, e.g. to change its settings.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Unwrap the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+/// A single-tap translator moving the signal at `-margin` to DC, or
+/// `Error::InvalidParam` if the margin is not within half the sample rate.
+fn shifter(sample_rate: u32, margin: i32) -> Result<FreqXlator> {
+    if margin.unsigned_abs() as u64 * 2 >= sample_rate as u64 {
+        return Err(Error::InvalidParam);
+    }
+    Ok(FreqXlator::new(
+        sample_rate as f64,
+        -(margin as f64),
+        1,
+        &[1.0],
+    ))
+}
+
+impl<S: SdrSource> SdrSource for OffsetTuned<S> {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        let hardware =
+            u32::try_from(freq_hz as i64 + self.margin as i64).map_err(|_| Error::InvalidParam)?;
+        self.source.tune(hardware)
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        let hardware = self.source.center_freq()?;
+        u32::try_from(hardware as i64 - self.margin as i64).map_err(|_| Error::InvalidParam)
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        // Check the margin still fits before touching the hardware.
+        let xlator = shifter(rate_hz, self.margin)?;
+        self.source.set_sample_rate(rate_hz)?;
+        self.xlator = xlator;
+        Ok(())
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        self.source.sample_rate()
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.source.set_gain(gain)
+    }
+
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        self.source.sampling_mode()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.source.read(buf)?;
+        self.iq.resize(n / 2, Complex::new(0.0, 0.0));
+        to_complex_f32_into(&buf[..n], &mut self.iq, Scaling::Normalized);
+        self.shifted.clear();
+        self.xlator.process_into(&self.iq, &mut self.shifted);
+        for (pair, z) in buf[..n].chunks_exact_mut(2).zip(&self.shifted) {
+            pair[0] = to_u8(z.re);
+            pair[1] = to_u8(z.im);
+        }
+        Ok(n)
+    }
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockDevice, MockSignal};
+
+    const RATE: u32 = 1_024_000;
+
+    /// A source producing a tone `offset_hz` from whatever it is tuned to.
+    fn tone(offset_hz: f64) -> MockDevice {
+        let mock = MockDevice::new().with_signal(MockSignal::Tone {
+            offset_hz,
+            amplitude: 0.8,
+        });
+        mock.set_sample_rate(RATE).unwrap();
+        mock
+    }
+
+    /// The mean frequency of cu8 samples in Hz, from their phase steps.
+    fn frequency(buf: &[u8]) -> f64 {
+        let iq: Vec<Complex<f32>> = crate::samples::to_complex_f32(buf, Scaling::Normalized);
+        let sum = iq
+            .windows(2)
+            .fold(Complex::new(0.0, 0.0), |acc, w| acc + w[1] * w[0].conj());
+        sum.arg() as f64 * RATE as f64 / (2.0 * PI)
+    }
+
+    #[test]
+    fn tunes_the_hardware_off_the_requested_frequency() {
+        let source = tone(0.0);
+        let mut tuned = OffsetTuned::new(source, 250_000).unwrap();
+        tuned.tune(100_000_000).unwrap();
+        assert_eq!(tuned.get_ref().get_center_freq().unwrap(), 100_250_000);
+        assert_eq!(tuned.center_freq().unwrap(), 100_000_000);
+
+        let mut below = OffsetTuned::new(tuned.into_inner(), -250_000).unwrap();
+        below.tune(100_000_000).unwrap();
+        assert_eq!(below.get_ref().get_center_freq().unwrap(), 99_750_000);
+        assert!(matches!(below.tune(100_000), Err(Error::InvalidParam)));
+    }
+
+    #[test]
+    fn moves_the_requested_frequency_back_to_the_centre() {
+        // With the hardware 250 kHz up, a carrier on the requested
+        // frequency arrives at -250 kHz.
+        let mut raw = tone(-250_000.0);
+        let mut buf = vec![0u8; 8192];
+        raw.read(&mut buf).unwrap();
+        assert!((frequency(&buf) + 250_000.0).abs() < 100.0);
+
+        let mut tuned = OffsetTuned::new(raw, 250_000).unwrap();
+        assert_eq!(tuned.read(&mut buf).unwrap(), 8192);
+        assert!(frequency(&buf).abs() < 100.0, "{}", frequency(&buf));
+        // Still a full-strength carrier, not smeared by rounding.
+        let iq = crate::samples::to_complex_f32(&buf, Scaling::Normalized);
+        assert!(iq.iter().all(|z| (z.norm() - 0.8).abs() < 0.02));
+
+        // A carrier 10 kHz above follows it.
+        let mut tuned = OffsetTuned::new(tone(-240_000.0), 250_000).unwrap();
+        tuned.read(&mut buf).unwrap();
+        assert!((frequency(&buf) - 10_000.0).abs() < 100.0);
+    }
+
+    #[test]
+    fn rejects_margins_beyond_nyquist() {
+        assert!(matches!(
+            OffsetTuned::new(tone(0.0), -512_000),
+            Err(Error::InvalidParam)
+        ));
+
+        // Lowering the rate under the margin leaves the source as it was.
+        let mut tuned = OffsetTuned::new(tone(0.0), 300_000).unwrap();
+        assert!(matches!(
+            tuned.set_sample_rate(500_000),
+            Err(Error::InvalidParam)
+        ));
+        assert_eq!(tuned.sample_rate().unwrap(), RATE);
+        tuned.set_sample_rate(2_048_000).unwrap();
+        assert_eq!(tuned.sample_rate().unwrap(), 2_048_000);
+    }
+}
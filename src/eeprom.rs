@@ -0,0 +1,125 @@
+use crate::error::Result;
+use crate::hw_info::HwInfo;
+use crate::utils::serialize_hw_info;
+
+/// Progress of a chunked EEPROM transfer.
+#[derive(Copy, Clone, Debug)]
+pub struct EepromProgress {
+    /// Bytes transferred so far.
+    pub done: usize,
+    /// Bytes to transfer in total.
+    pub total: usize,
+}
+
+/// Acknowledgement required by `Device::flash_eeprom_unchecked`.
+///
+/// Flashing an EEPROM image bypasses every check that `set_hw_info` relies
+/// on. A wrong image can change the USB IDs the dongle enumerates with, after
+/// which librtlsdr no longer recognises it until the EEPROM is shorted out and
+/// re-flashed.
+pub struct BrickRisk(());
+
+impl BrickRisk {
+    /// Accept that flashing may leave the device unusable.
+    pub fn i_accept_this_may_brick_my_dongle() -> Self {
+        BrickRisk(())
+    }
+}
+
+/// Dongle models with a known factory EEPROM layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DongleModel {
+    /// Generic Realtek OEM dongle (`rtl_eeprom -g realtek`).
+    RealtekOem,
+    /// The values a RTL2832U reports when no EEPROM is present
+    /// (`rtl_eeprom -g realtek_no_eeprom`).
+    RealtekNoEeprom,
+    /// Terratec NOXON DAB/DAB+ stick (`rtl_eeprom -g noxon`).
+    TerratecNoxon,
+    /// RTL-SDR Blog V3.
+    RtlSdrBlogV3,
+    /// RTL-SDR Blog V4.
+    RtlSdrBlogV4,
+}
+
+impl DongleModel {
+    /// All models with a known default image.
+    pub const ALL: [DongleModel; 5] = [
+        DongleModel::RealtekOem,
+        DongleModel::RealtekNoEeprom,
+        DongleModel::TerratecNoxon,
+        DongleModel::RtlSdrBlogV3,
+        DongleModel::RtlSdrBlogV4,
+    ];
+
+    /// Get the factory hardware information of the model.
+    ///
+    /// # Returns
+    ///
+    /// The default `HwInfo` of the model.
+    pub fn default_hw_info(&self) -> HwInfo {
+        let (vendor_id, product_id, manufact, product, serial, enable_ir, remote_wakeup) =
+            match self {
+                DongleModel::RealtekOem => (
+                    0x0bda,
+                    0x2838,
+                    "Realtek",
+                    "RTL2838UHIDIR",
+                    "00000001",
+                    true,
+                    false,
+                ),
+                DongleModel::RealtekNoEeprom => (
+                    0x0bda,
+                    0x2832,
+                    "Generic",
+                    "RTL2832U DVB-T",
+                    "0",
+                    false,
+                    true,
+                ),
+                DongleModel::TerratecNoxon => {
+                    (0x0ccd, 0x00b3, "NOXON", "DAB Stick", "0", false, true)
+                }
+                DongleModel::RtlSdrBlogV3 => (
+                    0x0bda,
+                    0x2838,
+                    "RTLSDRBlog",
+                    "Blog V3",
+                    "00000001",
+                    true,
+                    false,
+                ),
+                DongleModel::RtlSdrBlogV4 => (
+                    0x0bda,
+                    0x2838,
+                    "RTLSDRBlog",
+                    "Blog V4",
+                    "00000001",
+                    true,
+                    false,
+                ),
+            };
+        HwInfo {
+            vendor_id,
+            product_id,
+            manufact: manufact.to_string(),
+            product: product.to_string(),
+            serial: serial.to_string(),
+            have_serial: true,
+            enable_ir,
+            remote_wakeup,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Build the factory EEPROM image of the model.
+    ///
+    /// # Returns
+    ///
+    /// A 256 byte image suitable for
+    /// `Device::flash_eeprom_unchecked`.
+    pub fn default_image(&self) -> Result<Vec<u8>> {
+        serialize_hw_info(&self.default_hw_info())
+    }
+}
@@ -3,6 +3,7 @@ use std::os::raw::c_int;
 
 #[derive(Debug)]
 pub enum Error {
+    /// librtlsdr reported a USB transfer error (libusb -1).
     Io,
     InvalidParam,
     Access,
@@ -19,6 +20,54 @@ pub enum Error {
     StringValueTooLong,
     StringDescriptorInvalid,
     StringDescriptorTooLong,
+    /// A read returned fewer bytes than requested.
+    PartialRead {
+        read: usize,
+        requested: usize,
+    },
+    /// `DeviceBuilder` failed to apply `setting`, with `source` as the cause.
+    Config {
+        setting: &'static str,
+        source: Box<Error>,
+    },
+    /// An EEPROM page at `offset` still read back wrong after every retry.
+    EepromVerify {
+        offset: usize,
+    },
+    /// A device selector matched `matches` devices instead of one.
+    AmbiguousDevice {
+        matches: usize,
+    },
+    /// An operating system call outside librtlsdr failed: reading or
+    /// writing a file, a socket or a sound card, or starting a thread.
+    System(std::io::Error),
+    /// A recording's metadata is malformed or describes something that
+    /// cannot be read.
+    InvalidMetadata(String),
+    /// Samples were dropped because the consumer of a `RingReader` fell
+    /// behind and its overrun policy is `OverrunPolicy::Error`.
+    Overrun,
+    /// The device was found but has no usable USB driver bound. Only
+    /// reported on Windows, where the WinUSB driver has to be installed by
+    /// hand.
+    DriverMissing,
+    /// A two-line element set is malformed or describes an orbit that SGP4
+    /// cannot propagate.
+    InvalidTle(String),
+    /// A satellite's elements no longer describe a valid orbit at the
+    /// requested time, usually because it has decayed.
+    OrbitDecayed,
+    /// The audio output could not be opened or has failed.
+    Audio(String),
+    /// A channel list is malformed or uses a setting that cannot be
+    /// received.
+    InvalidPreset(String),
+    /// A decoder plugin could not be loaded or was built for another
+    /// version of the plugin interface.
+    Plugin(String),
+    /// An event script could not be compiled or one of its handlers
+    /// failed.
+    Script(String),
     Unknown,
 }
 
@@ -48,6 +97,12 @@ impl From<c_int> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::System(e)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -59,9 +114,44 @@ impl fmt::Display for Error {
             Error::Busy => write!(f, "Resource busy"),
             Error::Timeout => write!(f, "Operation timed out"),
             Error::Overflow => write!(f, "Overflow"),
+            Error::NotSupported => write!(f, "Not supported"),
+            Error::PartialRead { read, requested } => {
+                write!(f, "Partial read: {} of {} bytes", read, requested)
+            }
+            Error::Config { setting, source } => {
+                write!(f, "Failed to set {}: {}", setting, source)
+            }
+            Error::EepromVerify { offset } => {
+                write!(f, "EEPROM verification failed at offset {:#04x}", offset)
+            }
+            Error::AmbiguousDevice { matches } => {
+                write!(f, "{} devices match, expected exactly one", matches)
+            }
+            Error::System(e) => write!(f, "System I/O error: {}", e),
+            Error::InvalidMetadata(reason) => write!(f, "Invalid metadata: {}", reason),
+            Error::Overrun => write!(f, "Samples dropped, the consumer fell behind"),
+            Error::DriverMissing => write!(
+                f,
+                "No WinUSB driver is bound to the device; install it with Zadig \
+                 (https://zadig.akeo.ie) for \"Bulk-In, Interface (Interface 0)\""
+            ),
+            Error::InvalidTle(reason) => write!(f, "Invalid TLE: {}", reason),
+            Error::OrbitDecayed => write!(f, "The orbit is no longer valid at that time"),
+            Error::Audio(reason) => write!(f, "Audio output error: {}", reason),
+            Error::InvalidPreset(reason) => write!(f, "Invalid preset: {}", reason),
+            Error::Plugin(reason) => write!(f, "Plugin error: {}", reason),
+            Error::Script(reason) => write!(f, "Script error: {}", reason),
             _ => write!(f, "An unknown error occurred"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config { source, .. } => Some(source.as_ref()),
+            Error::System(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{Event, EventKind};
+use crate::error::Result;
+use crate::Provenance;
+
+/// Writes events as JSON Lines: one JSON object per line, flushed after
+/// each so a reader tailing the file sees every event as it happens.
+///
+/// The first event is preceded by a `provenance` event naming the radion
+/// and decoder versions that wrote the rest, so appending to a file from
+/// a newer build marks where the versions change.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    written: u64,
+    provenance: Option<Provenance>,
+}
+
+impl JsonlSink<BufWriter<File>> {
+    /// Open a file for events, appending to it if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file, conventionally ending in `.jsonl`.
+    ///
+    /// # Returns
+    ///
+    /// A new `JsonlSink` if successful, otherwise an `Error`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonlSink::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> JsonlSink<W> {
+    /// Write events to any writer, such as stdout or a socket.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the lines go.
+    ///
+    /// # Returns
+    ///
+    /// A new `JsonlSink`.
+    pub fn new(writer: W) -> Self {
+        JsonlSink {
+            writer,
+            written: 0,
+            provenance: Some(Provenance::current()),
+        }
+    }
+
+    /// Set the provenance written before the first event, e.g. to add the
+    /// pipeline's configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `provenance` - The provenance, by default `Provenance::current()`.
+    ///
+    /// # Returns
+    ///
+    /// The `JsonlSink` with the new provenance.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// Write no provenance, for consumers that expect only the events
+    /// they're given.
+    pub fn without_provenance(mut self) -> Self {
+        self.provenance = None;
+        self
+    }
+
+    /// Write one event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the line is flushed, otherwise an `Error`.
+    pub fn write(&mut self, event: &Event) -> Result<()> {
+        if let Some(provenance) = self.provenance.take() {
+            self.line(&Event::now(EventKind::Provenance(provenance)))?;
+        }
+        self.line(event)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn line(&mut self, event: &Event) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, event).map_err(std::io::Error::other)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Get the number of events written, not counting the provenance.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Get the writer back.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{ActivityChange, EventKind};
+
+    #[test]
+    fn writes_one_object_per_line() {
+        let mut sink = JsonlSink::new(Vec::new()).without_provenance();
+        for (time, started) in [(1.0, true), (4.5, false)] {
+            let kind = EventKind::Activity(ActivityChange {
+                started,
+                freq_hz: 146_520_000.0,
+                bandwidth_hz: 12_500.0,
+                duration_s: time - 1.0,
+                peak_db: -40.0,
+                floor_db: -90.0,
+            });
+            sink.write(&Event { time, kind }).unwrap();
+        }
+        assert_eq!(sink.written(), 2);
+        let text = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"time\":1.0,\"type\":\"activity\",\"started\":true,"));
+        let last: Event = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(last.time, 4.5);
+        assert!(text.ends_with("}\n"));
+    }
+
+    #[test]
+    fn starts_with_the_provenance() {
+        let provenance = Provenance::current().with_config("jsonl test");
+        let mut sink = JsonlSink::new(Vec::new()).with_provenance(provenance.clone());
+        let kind = EventKind::Activity(ActivityChange {
+            started: true,
+            freq_hz: 446_006_250.0,
+            bandwidth_hz: 12_500.0,
+            duration_s: 0.0,
+            peak_db: -50.0,
+            floor_db: -95.0,
+        });
+        sink.write(&Event { time: 1.0, kind }).unwrap();
+        assert_eq!(sink.written(), 1);
+        let text = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: Event = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first.kind, EventKind::Provenance(provenance));
+        assert!(lines[0].contains("\"type\":\"provenance\",\"radion\":"));
+        assert!(lines[1].contains("\"type\":\"activity\""));
+    }
+}
@@ -0,0 +1,431 @@
+//! Events in a stable JSON form, for logging and for consumers written in
+//! other languages.
+//!
+//! Each decoder's output and each change in channel activity converts to
+//! an `Event`: a timestamp plus a `type` field naming which of the
+//! `EventKind`s the rest of the object is. `JsonlSink` writes events one
+//! JSON object per line. With the `schema` feature, `schema` gives the
+//! JSON Schema of an event, generated from these types, so consumers can
+//! validate events and generate bindings for them; the same schema is kept
+//! in the repository as `schema/events.json`. With the `script` feature,
+//! `script::ScriptHooks` runs Rhai handlers on events.
+//!
+//! ```no_run
+//! use radion::events::{ActivityChange, Event, EventKind, JsonlSink};
+//!
+//! let mut sink = JsonlSink::new(std::io::stdout());
+//! // Decoded frames convert with `Event::now(&frame)`.
+//! let kind = EventKind::Activity(ActivityChange {
+//!     started: true,
+//!     freq_hz: 146_520_000.0,
+//!     bandwidth_hz: 12_500.0,
+//!     duration_s: 0.0,
+//!     peak_db: -41.2,
+//!     floor_db: -92.0,
+//! });
+//! // e.g. {"time":1760000000.5,"type":"activity","started":true,...}
+//! sink.write(&Event::now(kind))?;
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod jsonl;
+#[cfg(feature = "script")]
+pub mod script;
+
+pub use jsonl::JsonlSink;
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Something that happened, with when.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Event {
+    /// When it happened, in seconds since the Unix epoch.
+    pub time: f64,
+    /// What happened.
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl Event {
+    /// Create an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When it happened.
+    /// * `kind` - What happened, or anything that converts to it, such as
+    ///   a decoded frame.
+    ///
+    /// # Returns
+    ///
+    /// A new `Event`.
+    pub fn new(time: SystemTime, kind: impl Into<EventKind>) -> Self {
+        Event {
+            time: unix_seconds(time),
+            kind: kind.into(),
+        }
+    }
+
+    /// Create an event that happened just now.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - What happened.
+    ///
+    /// # Returns
+    ///
+    /// A new `Event` stamped with the current time.
+    pub fn now(kind: impl Into<EventKind>) -> Self {
+        Event::new(SystemTime::now(), kind)
+    }
+}
+
+/// What an event is about, named by its `type` field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// A Mode S message.
+    Adsb(AdsbEvent),
+    /// An AIS message.
+    Ais(AisEvent),
+    /// An APRS or other AX.25 frame.
+    Aprs(AprsEvent),
+    /// A reading from an ISM-band sensor or remote.
+    Ism(IsmEvent),
+    /// A channel becoming active or falling quiet.
+    Activity(ActivityChange),
+    /// What wrote the events that follow, written by `JsonlSink` before the
+    /// first.
+    Provenance(crate::Provenance),
+}
+
+/// How confidently a message was received, as `decoders::Quality` grades
+/// it.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Confidence {
+    /// Whether a checksum confirmed the bits.
+    pub crc_ok: bool,
+    /// The number of bits repaired by error correction.
+    pub corrected_bits: u32,
+    /// The spread of the decision margins relative to their mean.
+    pub evm: f32,
+    /// The estimated probability of any one bit being wrong.
+    pub bit_error_rate: f32,
+}
+
+/// A Mode S message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AdsbEvent {
+    /// The aircraft address, six hex digits.
+    pub icao: String,
+    /// The downlink format.
+    pub df: u8,
+    /// The message in hex, 14 or 28 digits.
+    pub message: String,
+    /// The signal level in dB relative to full scale.
+    pub signal_db: f32,
+    /// How confidently the message was received.
+    pub quality: Confidence,
+}
+
+/// An AIS message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AisEvent {
+    /// The channel, `A` or `B`.
+    pub channel: String,
+    /// The MMSI of the sending station.
+    pub mmsi: u32,
+    /// The message type, 1 to 27.
+    pub msg_type: u8,
+    /// The message in hex.
+    pub message: String,
+    /// How confidently the message was received.
+    pub quality: Confidence,
+}
+
+/// An AX.25 frame, as APRS carries.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AprsEvent {
+    /// The sending station, e.g. `N0CALL-9`.
+    pub source: String,
+    /// The destination, e.g. `APRS`.
+    pub destination: String,
+    /// The digipeater path, with `*` after those that repeated the frame.
+    pub path: Vec<String>,
+    /// The information field, with invalid UTF-8 replaced.
+    pub info: String,
+    /// How confidently the frame was received, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Confidence>,
+}
+
+/// A reading from an ISM-band device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct IsmEvent {
+    /// The device model, e.g. `Nexus-TH`.
+    pub model: String,
+    /// The device's identifier.
+    pub id: u64,
+    /// The channel selected on the device, if it has a switch for one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    /// Whether the battery is fine, if the device reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_ok: Option<bool>,
+    /// The reported quantities by rtl_433's field names, e.g.
+    /// `temperature_C`.
+    pub measurements: BTreeMap<String, f64>,
+    /// The SNR of the burst in dB.
+    pub snr_db: f32,
+    /// How cleanly the burst was sliced, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Confidence>,
+}
+
+/// A channel becoming active or falling quiet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ActivityChange {
+    /// Whether the channel became active, rather than fell quiet.
+    pub started: bool,
+    /// The channel's centre frequency in Hz.
+    pub freq_hz: f64,
+    /// The channel's width in Hz.
+    pub bandwidth_hz: f64,
+    /// How long the channel has been active, in seconds.
+    pub duration_s: f64,
+    /// The highest channel power seen in dB.
+    pub peak_db: f32,
+    /// The channel's noise floor in dB when the activity started.
+    pub floor_db: f32,
+}
+
+/// Get the JSON Schema of an `Event`.
+///
+/// # Returns
+///
+/// The schema as pretty-printed JSON.
+#[cfg(feature = "schema")]
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(Event);
+    serde_json::to_string_pretty(&schema).expect("a schema always serializes")
+}
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+#[cfg(any(feature = "adsb", feature = "ais"))]
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut out = String::with_capacity(2 * bytes.len());
+    for b in bytes {
+        let _ = write!(out, "{:02x}", b);
+    }
+    out
+}
+
+#[cfg(any(feature = "adsb", feature = "ais", feature = "aprs", feature = "ism"))]
+impl From<crate::decoders::Quality> for Confidence {
+    fn from(q: crate::decoders::Quality) -> Self {
+        Confidence {
+            crc_ok: q.crc_ok,
+            corrected_bits: q.corrected_bits,
+            evm: q.evm,
+            bit_error_rate: q.bit_error_rate,
+        }
+    }
+}
+
+#[cfg(feature = "adsb")]
+impl From<&crate::decoders::adsb::Frame> for EventKind {
+    fn from(frame: &crate::decoders::adsb::Frame) -> Self {
+        EventKind::Adsb(AdsbEvent {
+            icao: format!("{:06x}", frame.icao()),
+            df: frame.df(),
+            message: hex(frame.bytes()),
+            signal_db: frame.signal_db(),
+            quality: frame.quality().into(),
+        })
+    }
+}
+
+#[cfg(feature = "ais")]
+impl From<&crate::decoders::ais::Packet> for EventKind {
+    fn from(packet: &crate::decoders::ais::Packet) -> Self {
+        EventKind::Ais(AisEvent {
+            channel: packet.channel.to_string(),
+            mmsi: packet.mmsi(),
+            msg_type: packet.msg_type(),
+            message: hex(packet.data()),
+            quality: packet.quality().into(),
+        })
+    }
+}
+
+#[cfg(feature = "aprs")]
+impl From<&crate::decoders::aprs::Ax25Frame> for EventKind {
+    fn from(frame: &crate::decoders::aprs::Ax25Frame) -> Self {
+        EventKind::Aprs(AprsEvent {
+            source: frame.source.to_string(),
+            destination: frame.destination.to_string(),
+            path: frame
+                .digipeaters
+                .iter()
+                .map(|d| format!("{}{}", d, if d.repeated { "*" } else { "" }))
+                .collect(),
+            info: String::from_utf8_lossy(&frame.info).into_owned(),
+            quality: None,
+        })
+    }
+}
+
+#[cfg(feature = "aprs")]
+impl From<&crate::decoders::aprs::SoftFrame> for EventKind {
+    fn from(soft: &crate::decoders::aprs::SoftFrame) -> Self {
+        let mut kind = EventKind::from(&soft.frame);
+        if let EventKind::Aprs(event) = &mut kind {
+            event.quality = Some(soft.quality().into());
+        }
+        kind
+    }
+}
+
+#[cfg(feature = "ism")]
+impl From<&crate::decoders::ism::Reading> for EventKind {
+    fn from(reading: &crate::decoders::ism::Reading) -> Self {
+        EventKind::Ism(IsmEvent {
+            model: reading.model.clone(),
+            id: reading.id,
+            channel: reading.channel,
+            battery_ok: reading.battery_ok,
+            measurements: reading
+                .measurements
+                .iter()
+                .map(|m| (m.key().to_string(), m.value()))
+                .collect(),
+            snr_db: reading.snr,
+            quality: reading.quality.map(Confidence::from),
+        })
+    }
+}
+
+#[cfg(feature = "scan")]
+impl From<&crate::scan::ActivityEvent> for EventKind {
+    fn from(event: &crate::scan::ActivityEvent) -> Self {
+        let a = event.activity();
+        EventKind::Activity(ActivityChange {
+            started: matches!(event, crate::scan::ActivityEvent::Started(_)),
+            freq_hz: a.channel.centre_hz(),
+            bandwidth_hz: a.channel.bandwidth_hz(),
+            duration_s: a.duration.as_secs_f64(),
+            peak_db: a.peak_db,
+            floor_db: a.floor_db,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn ism() -> Event {
+        Event {
+            time: 1_760_000_000.5,
+            kind: EventKind::Ism(IsmEvent {
+                model: "Nexus-TH".into(),
+                id: 42,
+                channel: Some(1),
+                battery_ok: None,
+                measurements: [("temperature_C".to_string(), 21.5)].into_iter().collect(),
+                snr_db: 18.0,
+                quality: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn serializes_flat_tagged_objects() {
+        let json = serde_json::to_string(&ism()).unwrap();
+        assert_eq!(
+            json,
+            "{\"time\":1760000000.5,\"type\":\"ism\",\"model\":\"Nexus-TH\",\"id\":42,\
+             \"channel\":1,\"measurements\":{\"temperature_C\":21.5},\"snr_db\":18.0}"
+        );
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), ism());
+    }
+
+    #[test]
+    fn stamps_events_in_unix_seconds() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_500);
+        let event = Event::new(time, ism().kind);
+        assert_eq!(event.time, 1.5);
+    }
+
+    #[cfg(feature = "aprs")]
+    #[test]
+    fn converts_aprs_frames() {
+        use crate::decoders::aprs::{Address, Ax25Frame};
+        let mut digi = Address::new("WIDE1", 1);
+        digi.repeated = true;
+        let frame = Ax25Frame::ui(
+            Address::new("N0CALL", 9),
+            Address::new("APRS", 0),
+            vec![digi, Address::new("WIDE2", 1)],
+            b">hello",
+        );
+        match EventKind::from(&frame) {
+            EventKind::Aprs(e) => {
+                assert_eq!(e.source, "N0CALL-9");
+                assert_eq!(e.path, ["WIDE1-1*", "WIDE2-1"]);
+                assert_eq!(e.info, ">hello");
+                assert_eq!(e.quality, None);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[cfg(feature = "adsb")]
+    #[test]
+    fn converts_adsb_frames() {
+        let bytes = [
+            0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+        ];
+        let frame = crate::decoders::adsb::Frame::from_bytes(&bytes).unwrap();
+        let json = serde_json::to_value(Event::now(&frame)).unwrap();
+        assert_eq!(json["type"], "adsb");
+        assert_eq!(json["icao"], "4840d6");
+        assert_eq!(json["df"], 17);
+        assert_eq!(json["message"], "8d4840d6202cc371c32ce0576098");
+        assert_eq!(json["quality"]["crc_ok"], true);
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn published_schema_is_current() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/events.json");
+        if std::env::var_os("RADION_UPDATE_SCHEMA").is_some() {
+            std::fs::write(path, schema() + "\n").unwrap();
+        }
+        let published = std::fs::read_to_string(path).unwrap();
+        assert_eq!(
+            published.trim_end(),
+            schema(),
+            "run the tests with RADION_UPDATE_SCHEMA=1 to regenerate {}",
+            path
+        );
+    }
+}
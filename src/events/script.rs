@@ -0,0 +1,496 @@
+//! Rhai scripts reacting to events.
+//!
+//! A script defines handlers named `on_event`, called with every event,
+//! and `on_<type>`, such as `on_activity` or `on_ism`, called with events
+//! of that type. Each handler gets the event as an object map with the
+//! fields of its JSON form and, as `this`, a map it can keep state in
+//! across calls. Handlers ask for things to be done by calling:
+//!
+//! * `retune(hz)` - tune the receiver to `hz`.
+//! * `record(seconds)` - start recording for `seconds`.
+//! * `stop_recording()` - stop a recording.
+//! * `webhook(url, body)` - post `body`, a string or a map sent as JSON,
+//!   to `url`.
+//! * `log(message)` or `print(message)` - log a message.
+//!
+//! The requests come back from `ScriptHooks::handle` as `Action`s for the
+//! application to carry out; `post_webhook` carries out webhooks.
+//! Statements outside the handlers run once when the script is loaded,
+//! then `init()`, if the script defines it, which can set up the state in
+//! `this` before the first event.
+//!
+//! ```no_run
+//! use radion::events::script::{Action, ScriptHooks};
+//! use radion::events::{ActivityChange, Event, EventKind};
+//!
+//! let mut hooks = ScriptHooks::new(
+//!     r#"
+//!     fn init() {
+//!         this.hits = 0;
+//!     }
+//!
+//!     fn on_activity(event) {
+//!         this.hits += 1;
+//!         if event.started && event.peak_db > -60.0 {
+//!             record(30);
+//!             webhook("http://127.0.0.1:8080/hit", #{ freq: event.freq_hz, hits: this.hits });
+//!         }
+//!     }
+//!     "#,
+//! )?;
+//! let event = Event::now(EventKind::Activity(ActivityChange {
+//!     started: true,
+//!     freq_hz: 146_520_000.0,
+//!     bandwidth_hz: 12_500.0,
+//!     duration_s: 0.0,
+//!     peak_db: -41.2,
+//!     floor_db: -92.0,
+//! }));
+//! let actions = hooks.handle(&event)?;
+//! assert_eq!(actions[0], Action::Record(std::time::Duration::from_secs(30)));
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Map, Scope, AST};
+
+use super::{Event, EventKind};
+use crate::error::{Error, Result};
+
+/// Operations a handler may run per call when not set with
+/// `with_max_operations`, so a script stuck in a loop cannot stall the
+/// receiver.
+pub const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Something a script asked for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Tune the receiver to a frequency in Hz.
+    Retune(u32),
+    /// Start recording for a time.
+    Record(Duration),
+    /// Stop a recording.
+    StopRecording,
+    /// Post a body to a URL.
+    Webhook {
+        /// The URL.
+        url: String,
+        /// The body, JSON if the script passed a map.
+        body: String,
+    },
+    /// Log a message.
+    Log(String),
+}
+
+type Actions = Arc<Mutex<Vec<Action>>>;
+
+/// A loaded script and the state it keeps between events.
+pub struct ScriptHooks {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    /// The handlers the script defines.
+    handlers: Vec<String>,
+    actions: Actions,
+}
+
+impl ScriptHooks {
+    /// Compile a script, run its top-level statements and call its `init`
+    /// handler, if it has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - The Rhai source.
+    ///
+    /// # Returns
+    ///
+    /// The hooks, or `Error::Script` if the script does not compile or its
+    /// top-level statements or `init` fail.
+    pub fn new(script: &str) -> Result<Self> {
+        let actions = Actions::default();
+        let mut engine = Engine::new();
+        engine.set_max_operations(DEFAULT_MAX_OPERATIONS);
+        register(&mut engine, &actions);
+        let ast = engine
+            .compile(script)
+            .map_err(|e| Error::Script(e.to_string()))?;
+        let handlers = ast
+            .iter_functions()
+            .filter(|f| f.name.starts_with("on_") && f.params.len() == 1)
+            .map(|f| f.name.to_string())
+            .collect();
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(script_error)?;
+        let mut state = Dynamic::from_map(Map::new());
+        if ast
+            .iter_functions()
+            .any(|f| f.name == "init" && f.params.is_empty())
+        {
+            let mut options = CallFnOptions::new().bind_this_ptr(&mut state);
+            options.eval_ast = false;
+            engine
+                .call_fn_with_options::<Dynamic>(options, &mut scope, &ast, "init", ())
+                .map(drop)
+                .map_err(script_error)?;
+        }
+        Ok(ScriptHooks {
+            engine,
+            ast,
+            scope,
+            state,
+            handlers,
+            actions,
+        })
+    }
+
+    /// Load a script from a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The script file.
+    ///
+    /// # Returns
+    ///
+    /// The hooks, or an `Error` if the file could not be read or the
+    /// script could not be loaded.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        ScriptHooks::new(&std::fs::read_to_string(path)?)
+    }
+
+    /// Set how many operations a handler may run per call.
+    ///
+    /// # Arguments
+    ///
+    /// * `operations` - The limit, or 0 for none.
+    ///
+    /// # Returns
+    ///
+    /// The `ScriptHooks` with the new limit.
+    pub fn with_max_operations(mut self, operations: u64) -> Self {
+        self.engine.set_max_operations(operations);
+        self
+    }
+
+    /// Check whether the script handles a type of event.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The event type, e.g. `activity`.
+    pub fn handles(&self, kind: &str) -> bool {
+        let name = format!("on_{}", kind);
+        self.handlers.iter().any(|h| *h == name || h == "on_event")
+    }
+
+    /// Run the handlers for an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event.
+    ///
+    /// # Returns
+    ///
+    /// What the handlers asked for, in order, or `Error::Script` if a
+    /// handler failed; what it asked for before failing is dropped.
+    pub fn handle(&mut self, event: &Event) -> Result<Vec<Action>> {
+        let specific = format!("on_{}", kind_name(&event.kind));
+        let value = rhai::serde::to_dynamic(event).map_err(script_error)?;
+        let mut result = Ok(());
+        for name in ["on_event", specific.as_str()] {
+            if !self.handlers.iter().any(|h| h == name) {
+                continue;
+            }
+            let mut options = CallFnOptions::new().bind_this_ptr(&mut self.state);
+            options.eval_ast = false;
+            result = self
+                .engine
+                .call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut self.scope,
+                    &self.ast,
+                    name,
+                    (value.clone(),),
+                )
+                .map(drop)
+                .map_err(script_error);
+            if result.is_err() {
+                break;
+            }
+        }
+        let actions = std::mem::take(&mut *self.actions.lock().unwrap());
+        result.map(|()| actions)
+    }
+}
+
+/// Register the functions scripts ask for actions with.
+fn register(engine: &mut Engine, actions: &Actions) {
+    let push = |actions: &Actions, action| actions.lock().unwrap().push(action);
+
+    let sink = actions.clone();
+    engine.register_fn(
+        "retune",
+        move |hz: i64| -> std::result::Result<(), Box<EvalAltResult>> {
+            let hz = u32::try_from(hz).map_err(|_| format!("invalid frequency {}", hz))?;
+            push(&sink, Action::Retune(hz));
+            Ok(())
+        },
+    );
+    let sink = actions.clone();
+    engine.register_fn(
+        "retune",
+        move |hz: f64| -> std::result::Result<(), Box<EvalAltResult>> {
+            if !(0.0..=u32::MAX as f64).contains(&hz) {
+                return Err(format!("invalid frequency {}", hz).into());
+            }
+            push(&sink, Action::Retune(hz.round() as u32));
+            Ok(())
+        },
+    );
+    let sink = actions.clone();
+    engine.register_fn(
+        "record",
+        move |seconds: f64| -> std::result::Result<(), Box<EvalAltResult>> {
+            let time = Duration::try_from_secs_f64(seconds)
+                .map_err(|_| format!("invalid recording time {}", seconds))?;
+            push(&sink, Action::Record(time));
+            Ok(())
+        },
+    );
+    let sink = actions.clone();
+    engine.register_fn(
+        "record",
+        move |seconds: i64| -> std::result::Result<(), Box<EvalAltResult>> {
+            let seconds = u64::try_from(seconds)
+                .map_err(|_| format!("invalid recording time {}", seconds))?;
+            push(&sink, Action::Record(Duration::from_secs(seconds)));
+            Ok(())
+        },
+    );
+    let sink = actions.clone();
+    engine.register_fn("stop_recording", move || push(&sink, Action::StopRecording));
+    let sink = actions.clone();
+    engine.register_fn(
+        "webhook",
+        move |url: ImmutableString, body: Dynamic| -> std::result::Result<(), Box<EvalAltResult>> {
+            let body = match body.clone().into_immutable_string() {
+                Ok(text) => text.to_string(),
+                Err(_) => serde_json::to_string(&body).map_err(|e| e.to_string())?,
+            };
+            push(
+                &sink,
+                Action::Webhook {
+                    url: url.to_string(),
+                    body,
+                },
+            );
+            Ok(())
+        },
+    );
+    let sink = actions.clone();
+    engine.register_fn("log", move |message: ImmutableString| {
+        push(&sink, Action::Log(message.to_string()))
+    });
+    let sink = actions.clone();
+    engine.on_print(move |message| push(&sink, Action::Log(message.to_string())));
+}
+
+fn script_error(e: Box<EvalAltResult>) -> Error {
+    Error::Script(e.to_string())
+}
+
+/// The `type` field of an event.
+fn kind_name(kind: &EventKind) -> &'static str {
+    match kind {
+        EventKind::Adsb(_) => "adsb",
+        EventKind::Ais(_) => "ais",
+        EventKind::Aprs(_) => "aprs",
+        EventKind::Ism(_) => "ism",
+        EventKind::Activity(_) => "activity",
+        EventKind::Provenance(_) => "provenance",
+    }
+}
+
+/// Post a JSON body to an `http://` URL, as for `Action::Webhook`.
+///
+/// # Arguments
+///
+/// * `url` - The URL, `http://host[:port][/path]`.
+/// * `body` - The body, sent as `application/json`.
+/// * `timeout` - The longest to wait for connecting, sending and the
+///   response each.
+///
+/// # Returns
+///
+/// The HTTP status code of the response, `Error::NotSupported` for URLs
+/// other than plain HTTP, or an `Error` if the request failed.
+pub fn post_webhook(url: &str, body: &str, timeout: Duration) -> Result<u16> {
+    let rest = url.strip_prefix("http://").ok_or(Error::NotSupported)?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let target = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let addr = target
+        .to_socket_addrs()?
+        .next()
+        .ok_or(Error::InvalidParam)?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        body
+    )?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 256];
+    // The status line is all that is needed.
+    while !response.contains(&b'\n') {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    let line = String::from_utf8_lossy(&response);
+    line.split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| {
+            Error::System(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "malformed HTTP response",
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ActivityChange;
+    use std::net::TcpListener;
+
+    fn activity(started: bool, peak_db: f32) -> Event {
+        Event {
+            time: 1_760_000_000.0,
+            kind: EventKind::Activity(ActivityChange {
+                started,
+                freq_hz: 146_520_000.0,
+                bandwidth_hz: 12_500.0,
+                duration_s: 2.5,
+                peak_db,
+                floor_db: -92.0,
+            }),
+        }
+    }
+
+    const SCRIPT: &str = r#"
+        log("loaded");
+
+        fn init() {
+            this.seen = 0;
+        }
+
+        fn on_event(event) {
+            this.seen += 1;
+        }
+
+        fn on_activity(event) {
+            if event.started && event.peak_db > -50.0 {
+                retune(event.freq_hz);
+                record(30);
+            } else if !event.started {
+                stop_recording();
+                webhook("http://127.0.0.1:9/hook", #{ freq: event.freq_hz, seen: this.seen });
+            }
+        }
+    "#;
+
+    #[test]
+    fn handlers_ask_for_actions_and_keep_state() {
+        let mut hooks = ScriptHooks::new(SCRIPT).unwrap();
+        assert!(hooks.handles("activity"));
+        assert_eq!(
+            hooks.handle(&activity(true, -40.0)).unwrap(),
+            [
+                Action::Log("loaded".into()),
+                Action::Retune(146_520_000),
+                Action::Record(Duration::from_secs(30)),
+            ]
+        );
+        assert!(hooks.handle(&activity(true, -70.0)).unwrap().is_empty());
+        let actions = hooks.handle(&activity(false, -40.0)).unwrap();
+        assert_eq!(actions[0], Action::StopRecording);
+        let Action::Webhook { url, body } = &actions[1] else {
+            panic!("{:?}", actions[1]);
+        };
+        assert_eq!(url, "http://127.0.0.1:9/hook");
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["seen"], 3);
+        assert_eq!(body["freq"], 146_520_000.0);
+    }
+
+    #[test]
+    fn reports_script_errors() {
+        assert!(matches!(
+            ScriptHooks::new("fn on_event(e) {"),
+            Err(Error::Script(_))
+        ));
+        let mut hooks = ScriptHooks::new("fn on_activity(e) { retune(-1); }").unwrap();
+        assert!(matches!(
+            hooks.handle(&activity(true, 0.0)),
+            Err(Error::Script(_))
+        ));
+        let mut hooks = ScriptHooks::new("fn on_event(e) { loop {} }")
+            .unwrap()
+            .with_max_operations(1000);
+        assert!(matches!(
+            hooks.handle(&activity(true, 0.0)),
+            Err(Error::Script(_))
+        ));
+    }
+
+    #[test]
+    fn posts_webhooks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 512];
+            while !request.ends_with(b"{\"a\":1}") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        let status = post_webhook(&url, "{\"a\":1}", Duration::from_secs(5)).unwrap();
+        assert_eq!(status, 204);
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 7\r\n"));
+        assert!(matches!(
+            post_webhook("https://example.com/", "", Duration::from_secs(1)),
+            Err(Error::NotSupported)
+        ));
+    }
+}
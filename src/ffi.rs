@@ -3,13 +3,18 @@ pub enum RTLSDRDevT {}
 
 pub type ReadAsyncCbT = Option<unsafe extern "C" fn(buf: *mut c_uchar, len: u32, ctx: *mut c_void)>;
 
+// Only present in some librtlsdr builds, so resolved at runtime.
+pub type SetBiasTeeT = unsafe extern "C" fn(dev: *mut RTLSDRDevT, on: c_int) -> c_int;
+pub type SetBiasTeeGpioT =
+    unsafe extern "C" fn(dev: *mut RTLSDRDevT, gpio: c_int, on: c_int) -> c_int;
+
 #[link(name = "rtlsdr")]
 #[allow(improper_ctypes)]
 extern "C" {
     pub fn rtlsdr_get_device_count() -> u32;
     pub fn rtlsdr_get_device_name(index: u32) -> *const c_char;
     pub fn rtlsdr_get_device_usb_strings(
-        index: *mut RTLSDRDevT,
+        index: u32,
         manufact: *mut c_char,
         product: *mut c_char,
         serial: *mut c_char,
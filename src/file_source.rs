@@ -0,0 +1,597 @@
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sample formats of IQ recordings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IqFormat {
+    /// Unsigned 8-bit I/Q pairs, as produced by the dongle and `rtl_sdr`.
+    Cu8,
+    /// Signed 16-bit little-endian I/Q pairs.
+    Cs16,
+    /// 32-bit little-endian float I/Q pairs in the range -1.0..1.0.
+    Cf32,
+}
+
+impl IqFormat {
+    /// The size of a single I/Q pair in bytes.
+    pub fn sample_size(&self) -> usize {
+        match self {
+            IqFormat::Cu8 => 2,
+            IqFormat::Cs16 => 4,
+            IqFormat::Cf32 => 8,
+        }
+    }
+}
+
+/// How fast a `FileSource` delivers samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    /// Deliver samples no faster than the configured sample rate.
+    RealTime,
+    /// Deliver samples as fast as they can be read.
+    Unpaced,
+}
+
+/// Playback state shared between a `FileSource` and its `PlaybackControl`s.
+struct Playback {
+    paused: bool,
+    speed: f64,
+    seek: Option<u64>,
+    /// Bumped on every change, so the source knows to pick them up.
+    generation: u64,
+}
+
+struct Shared {
+    playback: Mutex<Playback>,
+    resumed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Playback> {
+        self.playback.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Playback)) {
+        let mut playback = self.lock();
+        f(&mut playback);
+        playback.generation += 1;
+        self.resumed.notify_all();
+    }
+
+    fn set_speed(&self, speed: f64) -> Result<()> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(Error::InvalidParam);
+        }
+        self.update(|p| p.speed = speed);
+        Ok(())
+    }
+}
+
+/// Controls a `FileSource`'s playback from another thread, such as a UI's
+/// while a processing thread reads the source.
+///
+/// Obtained from `FileSource::control`. Changes take effect at the
+/// source's next read.
+#[derive(Clone)]
+pub struct PlaybackControl {
+    shared: Arc<Shared>,
+}
+
+impl PlaybackControl {
+    /// Pause playback. Reads block until `resume` is called.
+    pub fn pause(&self) {
+        self.shared.update(|p| p.paused = true);
+    }
+
+    /// Resume paused playback.
+    pub fn resume(&self) {
+        self.shared.update(|p| p.paused = false);
+    }
+
+    /// Check whether playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().paused
+    }
+
+    /// Continue playback from a sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The I/Q pair to play next, counted from the start of
+    ///   the recording. Seeking past the end ends the stream.
+    pub fn seek(&self, sample: u64) {
+        self.shared.update(|p| p.seek = Some(sample));
+    }
+
+    /// Set the playback speed under `Pacing::RealTime`.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - A multiple of the sample rate, e.g. 0.5 for half speed
+    ///   or 10.0 to test decoders faster than real time.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, `Error::InvalidParam` if `speed` is not
+    /// positive and finite.
+    pub fn set_speed(&self, speed: f64) -> Result<()> {
+        self.shared.set_speed(speed)
+    }
+
+    /// Get the playback speed.
+    pub fn speed(&self) -> f64 {
+        self.shared.lock().speed
+    }
+}
+
+/// Replays an IQ recording through the `SdrSource` interface.
+///
+/// Samples are converted to the dongle's native unsigned 8-bit format. Tuning
+/// and gain changes are recorded but cannot affect a recording; changing the
+/// sample rate changes the playback speed under `Pacing::RealTime`.
+///
+/// Seekable recordings can be paused, sought and sped up, directly or from
+/// another thread through a `PlaybackControl`:
+///
+/// ```no_run
+/// use radion::{FileSource, IqFormat, SdrSource};
+///
+/// let mut source = FileSource::open("capture.cu8", IqFormat::Cu8, 2_048_000)?;
+/// let control = source.control();
+/// std::thread::spawn(move || {
+///     // Skip the first minute, then play at four times real time.
+///     control.seek(60 * 2_048_000);
+///     control.set_speed(4.0)
+/// });
+/// let mut buf = vec![0u8; 16_384];
+/// while source.read(&mut buf)? > 0 {
+///     // Process the samples.
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct FileSource<R = BufReader<File>> {
+    reader: R,
+    format: IqFormat,
+    sample_rate: u32,
+    center_freq: u32,
+    gain: Option<i32>,
+    pacing: Pacing,
+    started: Option<Instant>,
+    delivered: u64,
+    scratch: Vec<u8>,
+    shared: Arc<Shared>,
+    generation: u64,
+    speed: f64,
+    position: u64,
+    /// Seeks the reader to a byte offset, set once the reader is known to
+    /// be seekable.
+    seeker: Option<fn(&mut R, u64) -> io::Result<u64>>,
+}
+
+impl FileSource {
+    /// Open a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the recording.
+    /// * `format` - The sample format of the recording.
+    /// * `sample_rate` - The sample rate of the recording in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A real-time paced `FileSource` if successful, otherwise an `Error`.
+    pub fn open<P: AsRef<Path>>(path: P, format: IqFormat, sample_rate: u32) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::from_reader(BufReader::new(file), format, sample_rate))
+    }
+}
+
+impl<R: Read> FileSource<R> {
+    /// Replay samples from any reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to take samples from.
+    /// * `format` - The sample format of the data.
+    /// * `sample_rate` - The sample rate of the data in Hz.
+    pub fn from_reader(reader: R, format: IqFormat, sample_rate: u32) -> Self {
+        FileSource {
+            reader,
+            format,
+            sample_rate,
+            center_freq: 0,
+            gain: None,
+            pacing: Pacing::RealTime,
+            started: None,
+            delivered: 0,
+            scratch: Vec::new(),
+            shared: Arc::new(Shared {
+                playback: Mutex::new(Playback {
+                    paused: false,
+                    speed: 1.0,
+                    seek: None,
+                    generation: 0,
+                }),
+                resumed: Condvar::new(),
+            }),
+            generation: 0,
+            speed: 1.0,
+            position: 0,
+            seeker: None,
+        }
+    }
+
+    /// Set how fast samples are delivered.
+    pub fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Set the center frequency the recording was made at, as reported by
+    /// `SdrSource::center_freq`.
+    pub fn with_center_freq(mut self, freq_hz: u32) -> Self {
+        self.center_freq = freq_hz;
+        self
+    }
+
+    /// Get the gain last set through `SdrSource::set_gain`.
+    pub fn gain(&self) -> Option<i32> {
+        self.gain
+    }
+
+    /// Get the underlying reader back.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Get the playback position.
+    ///
+    /// # Returns
+    ///
+    /// The next I/Q pair to be read, counted from the start of the
+    /// recording like seek targets. A reader that cannot seek counts from
+    /// where it was when it was handed over.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Pause playback, as `PlaybackControl::pause`.
+    ///
+    /// Reads block until playback is resumed, so only pause a source that
+    /// another thread will resume through a `PlaybackControl`, or that is
+    /// not read again until `resume`.
+    pub fn pause(&self) {
+        self.shared.update(|p| p.paused = true);
+    }
+
+    /// Resume paused playback.
+    pub fn resume(&self) {
+        self.shared.update(|p| p.paused = false);
+    }
+
+    /// Set the playback speed under `Pacing::RealTime`, as
+    /// `PlaybackControl::set_speed`.
+    pub fn set_speed(&self, speed: f64) -> Result<()> {
+        self.shared.set_speed(speed)
+    }
+
+    /// Pick up changes made through the controls, waiting while paused.
+    fn sync(&mut self) -> Result<()> {
+        let shared = Arc::clone(&self.shared);
+        let mut playback = shared.lock();
+        while playback.paused {
+            playback = shared
+                .resumed
+                .wait(playback)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        self.apply(playback)
+    }
+
+    /// Pick up changes made through the controls, paused or not.
+    fn apply(&mut self, mut playback: MutexGuard<'_, Playback>) -> Result<()> {
+        if playback.generation == self.generation {
+            return Ok(());
+        }
+        self.generation = playback.generation;
+        self.speed = playback.speed;
+        let seek = playback.seek.take();
+        drop(playback);
+
+        if let Some(sample) = seek {
+            let seeker = self.seeker.ok_or(Error::NotSupported)?;
+            seeker(&mut self.reader, sample * self.format.sample_size() as u64)?;
+            self.position = sample;
+        }
+        // Restart the pacing clock from here at the new speed.
+        self.started = None;
+        self.delivered = 0;
+        Ok(())
+    }
+
+    fn pace(&mut self) {
+        if self.pacing != Pacing::RealTime || self.sample_rate == 0 {
+            return;
+        }
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let rate = self.sample_rate as f64 * self.speed;
+        let due = Duration::from_secs_f64(self.delivered as f64 / rate);
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+impl<R: Read + Seek> FileSource<R> {
+    /// Get a handle for controlling playback from another thread.
+    pub fn control(&mut self) -> PlaybackControl {
+        self.make_seekable();
+        PlaybackControl {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Continue playback from a sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The I/Q pair to play next, counted from the start of
+    ///   the recording. Seeking past the end ends the stream.
+    ///
+    /// Takes effect immediately, also while paused, so a paused source can
+    /// be scrubbed from the thread that reads it.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, otherwise an `Error` from the reader.
+    pub fn seek(&mut self, sample: u64) -> Result<()> {
+        self.make_seekable();
+        self.shared.update(|p| p.seek = Some(sample));
+        let shared = Arc::clone(&self.shared);
+        let playback = shared.lock();
+        self.apply(playback)
+    }
+
+    /// Allow seeking, and count the position from the start of the
+    /// recording rather than from where the reader was handed over.
+    fn make_seekable(&mut self) {
+        if self.seeker.is_some() {
+            return;
+        }
+        self.seeker = Some(|reader, offset| reader.seek(SeekFrom::Start(offset)));
+        if let Ok(offset) = self.reader.stream_position() {
+            self.position = offset / self.format.sample_size() as u64;
+        }
+    }
+
+    /// Continue playback from a time into the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time from the start of the recording.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if successful, otherwise an `Error` from the reader.
+    pub fn seek_time(&mut self, time: Duration) -> Result<()> {
+        self.seek((time.as_secs_f64() * self.sample_rate as f64).round() as u64)
+    }
+}
+
+impl<R: Read> SdrSource for FileSource<R> {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        self.center_freq = freq_hz;
+        Ok(())
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        Ok(self.center_freq)
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        // Restart the pacing clock so the new rate applies from here on.
+        self.started = None;
+        self.delivered = 0;
+        self.sample_rate = rate_hz;
+        Ok(())
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        Ok(self.sample_rate)
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.gain = gain;
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.sync()?;
+        self.pace();
+        let samples = buf.len() / 2;
+        let size = self.format.sample_size();
+        let n = match self.format {
+            IqFormat::Cu8 => fill(&mut self.reader, &mut buf[..samples * 2])? / size,
+            IqFormat::Cs16 | IqFormat::Cf32 => {
+                self.scratch.resize(samples * size, 0);
+                let n = fill(&mut self.reader, &mut self.scratch)? / size;
+                convert(self.format, &self.scratch[..n * size], &mut buf[..n * 2]);
+                n
+            }
+        };
+        self.delivered += n as u64;
+        self.position += n as u64;
+        Ok(n * 2)
+    }
+}
+
+/// Read until `buf` is full or the reader is exhausted.
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Convert wider sample formats to unsigned 8-bit components.
+pub(crate) fn convert(format: IqFormat, src: &[u8], dst: &mut [u8]) {
+    match format {
+        IqFormat::Cu8 => dst.copy_from_slice(src),
+        IqFormat::Cs16 => {
+            for (d, s) in dst.iter_mut().zip(src.chunks_exact(2)) {
+                let v = i16::from_le_bytes([s[0], s[1]]);
+                *d = ((v >> 8) + 128) as u8;
+            }
+        }
+        IqFormat::Cf32 => {
+            for (d, s) in dst.iter_mut().zip(src.chunks_exact(4)) {
+                let v = f32::from_le_bytes([s[0], s[1], s[2], s[3]]);
+                *d = (v * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::mpsc;
+
+    /// A cu8 recording whose I component counts the samples.
+    fn counting(samples: usize) -> FileSource<Cursor<Vec<u8>>> {
+        let data = (0..samples).flat_map(|i| [i as u8, 0]).collect();
+        FileSource::from_reader(Cursor::new(data), IqFormat::Cu8, 1000)
+    }
+
+    fn next(source: &mut impl SdrSource, samples: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; samples * 2];
+        let n = source.read(&mut buf).unwrap();
+        buf.truncate(n);
+        buf.iter().step_by(2).copied().collect()
+    }
+
+    #[test]
+    fn seeks_by_sample_and_time() {
+        let mut source = counting(200).with_pacing(Pacing::Unpaced);
+        assert_eq!(next(&mut source, 3), [0, 1, 2]);
+        assert_eq!(source.position(), 3);
+
+        source.seek(100).unwrap();
+        assert_eq!(source.position(), 100);
+        assert_eq!(next(&mut source, 3), [100, 101, 102]);
+
+        source.seek_time(Duration::from_millis(50)).unwrap();
+        assert_eq!(next(&mut source, 2), [50, 51]);
+
+        source.seek(250).unwrap();
+        assert!(next(&mut source, 2).is_empty());
+    }
+
+    #[test]
+    fn seeks_wider_formats_by_sample() {
+        let data: Vec<u8> = (0..10i16)
+            .flat_map(|i| [(i * 256).to_le_bytes(), 0i16.to_le_bytes()].concat())
+            .collect();
+        let mut source = FileSource::from_reader(Cursor::new(data), IqFormat::Cs16, 1000)
+            .with_pacing(Pacing::Unpaced);
+        source.seek(7).unwrap();
+        assert_eq!(next(&mut source, 5), [135, 136, 137]);
+    }
+
+    #[test]
+    fn control_seeks_from_another_thread() {
+        let mut source = counting(200).with_pacing(Pacing::Unpaced);
+        let control = source.control();
+        thread::spawn(move || control.seek(42)).join().unwrap();
+        assert_eq!(next(&mut source, 2), [42, 43]);
+        assert_eq!(source.position(), 44);
+    }
+
+    #[test]
+    fn pause_blocks_reads_until_resumed() {
+        let mut source = counting(200).with_pacing(Pacing::Unpaced);
+        let control = source.control();
+        control.pause();
+        assert!(control.is_paused());
+
+        let (tx, rx) = mpsc::channel();
+        let reader = thread::spawn(move || {
+            tx.send(next(&mut source, 2)).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        control.resume();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), [0, 1]);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn speed_scales_real_time_pacing() {
+        // 200 samples at 1 kS/s take 200 ms in real time.
+        let mut source = counting(250);
+        next(&mut source, 10);
+        let start = Instant::now();
+        next(&mut source, 200);
+        next(&mut source, 1);
+        assert!(start.elapsed() >= Duration::from_millis(190));
+
+        // And 20 ms at ten times real time.
+        source.set_speed(10.0).unwrap();
+        next(&mut source, 1);
+        let start = Instant::now();
+        next(&mut source, 20);
+        next(&mut source, 1);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(2), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(150), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn rejects_invalid_speeds() {
+        let mut source = counting(1);
+        let control = source.control();
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(source.set_speed(speed), Err(Error::InvalidParam)));
+            assert!(matches!(control.set_speed(speed), Err(Error::InvalidParam)));
+        }
+        assert_eq!(control.speed(), 1.0);
+    }
+
+    #[test]
+    fn seeks_while_paused() {
+        let mut source = counting(200).with_pacing(Pacing::Unpaced);
+        source.pause();
+        source.seek(10).unwrap();
+        source.seek(20).unwrap();
+        assert_eq!(source.position(), 20);
+        source.resume();
+        assert_eq!(next(&mut source, 2), [20, 21]);
+    }
+
+    #[test]
+    fn counts_the_position_from_the_start() {
+        let data: Vec<u8> = (0..100).flat_map(|i| [i, 0]).collect();
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(60);
+        let mut source =
+            FileSource::from_reader(cursor, IqFormat::Cu8, 1000).with_pacing(Pacing::Unpaced);
+        let _control = source.control();
+        assert_eq!(source.position(), 30);
+        assert_eq!(next(&mut source, 2), [30, 31]);
+        source.seek(5).unwrap();
+        assert_eq!(source.position(), 5);
+        assert_eq!(next(&mut source, 1), [5]);
+    }
+}
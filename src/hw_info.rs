@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use std::fmt;
+
+#[derive(Debug, Default, Clone)]
 pub struct HwInfo {
     pub vendor_id: u16,
     pub product_id: u16,
@@ -8,4 +10,29 @@ pub struct HwInfo {
     pub have_serial: bool,
     pub enable_ir: bool,
     pub remote_wakeup: bool,
+    /// Problems found while parsing the EEPROM. Fields affected by a warning
+    /// hold whatever could be recovered, or their default value.
+    pub warnings: Vec<EepromWarning>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EepromWarning {
+    /// The EEPROM does not start with the RTL2832 magic bytes, so the IDs and
+    /// flags were read from an image of unknown layout.
+    InvalidHeader,
+    /// The string descriptor at the given position (0 = manufacturer,
+    /// 1 = product, 2 = serial) is malformed. It and all following
+    /// descriptors are left empty.
+    InvalidStringDescriptor(usize),
+}
+
+impl fmt::Display for EepromWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EepromWarning::InvalidHeader => write!(f, "No valid EEPROM header"),
+            EepromWarning::InvalidStringDescriptor(i) => {
+                write!(f, "String descriptor {} is invalid", i)
+            }
+        }
+    }
 }
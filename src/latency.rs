@@ -0,0 +1,430 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::ffi::{rtlsdr_set_test_mode, RTLSDRDevT};
+use crate::stats::{DEFAULT_BUF_LEN, DEFAULT_BUF_NUM};
+use std::fmt;
+use std::panic;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Consecutive counter bytes that identify test mode output. Noise matches
+/// this by chance with a probability of 256^-64.
+const COUNTER_RUN: usize = 64;
+
+/// How long to wait for a marker to come through before giving up.
+const MARKER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Extra time left between markers on top of the queued buffers.
+const MARKER_SETTLE: Duration = Duration::from_millis(100);
+
+/// Buffer settings of an asynchronous read: the `buf_num` and `buf_len`
+/// passed to `Device::read_async_with`, `Device::start_async` and friends.
+///
+/// Fewer, shorter buffers deliver samples sooner but wake the reader more
+/// often and leave less slack before samples are lost.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StreamParams {
+    /// The number of USB buffers.
+    pub buf_num: u32,
+    /// The length of each buffer in bytes, a multiple of 512.
+    pub buf_len: u32,
+}
+
+impl StreamParams {
+    /// librtlsdr's defaults: 15 buffers of 256 KiB, about 55 ms each at
+    /// 2.4 MS/s.
+    pub const DEFAULT: StreamParams = StreamParams {
+        buf_num: DEFAULT_BUF_NUM,
+        buf_len: DEFAULT_BUF_LEN,
+    };
+
+    /// 8 buffers of 64 KiB, about 14 ms each at 2.4 MS/s.
+    pub const BALANCED: StreamParams = StreamParams {
+        buf_num: 8,
+        buf_len: 128 * 512,
+    };
+
+    /// 4 buffers of 16 KiB, about 3.4 ms each at 2.4 MS/s. Needs a reader
+    /// that never stalls.
+    pub const LOW_LATENCY: StreamParams = StreamParams {
+        buf_num: 4,
+        buf_len: 32 * 512,
+    };
+
+    /// The presets, from the most buffering to the least.
+    pub const PRESETS: [StreamParams; 3] = [Self::DEFAULT, Self::BALANCED, Self::LOW_LATENCY];
+
+    /// Create buffer settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_num` - The number of USB buffers, 0 for librtlsdr's default.
+    /// * `buf_len` - The length of each buffer in bytes, 0 for librtlsdr's
+    ///   default.
+    ///
+    /// # Returns
+    ///
+    /// The `StreamParams` with zeros replaced by the defaults, or
+    /// `Error::InvalidParam` if `buf_len` is not a multiple of 512.
+    pub fn new(buf_num: u32, buf_len: u32) -> Result<Self> {
+        if !buf_len.is_multiple_of(512) {
+            return Err(Error::InvalidParam);
+        }
+        Ok(StreamParams {
+            buf_num: if buf_num == 0 {
+                DEFAULT_BUF_NUM
+            } else {
+                buf_num
+            },
+            buf_len: if buf_len == 0 {
+                DEFAULT_BUF_LEN
+            } else {
+                buf_len
+            },
+        })
+    }
+
+    /// Get the time it takes to fill one buffer.
+    ///
+    /// This is the least latency the settings add: a sample is only
+    /// delivered once the rest of its buffer has been filled.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate in samples per second.
+    ///
+    /// # Returns
+    ///
+    /// The duration of one buffer.
+    pub fn buffer_duration(self, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(self.buf_len as f64 / 2.0 / sample_rate.max(1) as f64)
+    }
+
+    /// Get the time all buffers cover.
+    ///
+    /// This is the most latency the settings can add before samples are
+    /// lost, and how long a reader may stall without losing any.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate in samples per second.
+    ///
+    /// # Returns
+    ///
+    /// The duration of all buffers.
+    pub fn queue_duration(self, sample_rate: u32) -> Duration {
+        self.buffer_duration(sample_rate) * self.buf_num
+    }
+}
+
+impl Default for StreamParams {
+    fn default() -> Self {
+        StreamParams::DEFAULT
+    }
+}
+
+/// Formats the settings as buffer count and length, e.g. `15 x 262144 B`.
+impl fmt::Display for StreamParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} x {} B", self.buf_num, self.buf_len)
+    }
+}
+
+/// Latency measured by `Device::measure_latency` for one set of buffer
+/// settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyReport {
+    /// The buffer settings measured.
+    pub params: StreamParams,
+    /// The sample rate during the measurement.
+    pub sample_rate: u32,
+    /// The latency of every marker that came through, from switching test
+    /// mode on to the callback receiving the buffer it started in.
+    pub markers: Vec<Duration>,
+    /// Markers that did not come through within two seconds.
+    pub missed: usize,
+}
+
+impl LatencyReport {
+    /// Get the shortest latency measured.
+    ///
+    /// # Returns
+    ///
+    /// The minimum, or `None` if no marker came through.
+    pub fn min(&self) -> Option<Duration> {
+        self.markers.iter().min().copied()
+    }
+
+    /// Get the longest latency measured.
+    ///
+    /// # Returns
+    ///
+    /// The maximum, or `None` if no marker came through.
+    pub fn max(&self) -> Option<Duration> {
+        self.markers.iter().max().copied()
+    }
+
+    /// Get the average latency measured.
+    ///
+    /// # Returns
+    ///
+    /// The mean, or `None` if no marker came through.
+    pub fn mean(&self) -> Option<Duration> {
+        let n = u32::try_from(self.markers.len()).ok().filter(|&n| n > 0)?;
+        Some(self.markers.iter().sum::<Duration>() / n)
+    }
+
+    /// Get the part of the latency the buffers account for.
+    ///
+    /// # Returns
+    ///
+    /// The time to fill one buffer at the measured sample rate. A marker
+    /// lands in a random place in its buffer, so on average it waits half
+    /// of this; the rest of the mean is USB, the control transfer that
+    /// switches test mode and the scheduler.
+    pub fn buffer_latency(&self) -> Duration {
+        self.params.buffer_duration(self.sample_rate)
+    }
+}
+
+/// Formats the report on one line, e.g.
+/// `4 x 16384 B: buffer 3.4 ms, latency 2.9/5.1/8.0 ms (10 markers)`.
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        write!(
+            f,
+            "{}: buffer {:.1} ms",
+            self.params,
+            ms(self.buffer_latency())
+        )?;
+        match (self.min(), self.mean(), self.max()) {
+            (Some(min), Some(mean), Some(max)) => write!(
+                f,
+                ", latency {:.1}/{:.1}/{:.1} ms ({} markers)",
+                ms(min),
+                ms(mean),
+                ms(max),
+                self.markers.len()
+            )?,
+            _ => write!(f, ", no markers")?,
+        }
+        if self.missed > 0 {
+            write!(f, ", {} missed", self.missed)?;
+        }
+        Ok(())
+    }
+}
+
+impl Device {
+    /// Measure the latency from the dongle to the read callback.
+    ///
+    /// Streams with the given buffer settings and switches the RTL2832's
+    /// test mode on and off `markers` times. Test mode replaces the samples
+    /// with a byte counter, so the first counter bytes mark a known moment
+    /// in the sample stream; the time from switching it on to the callback
+    /// receiving them is the latency of the whole path. The device buffer
+    /// is reset first and test mode is left off.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The buffer settings to measure.
+    /// * `markers` - The number of markers to time.
+    ///
+    /// # Returns
+    ///
+    /// A `LatencyReport`, or an `Error` if the device could not stream or
+    /// switch test mode. Set a sample rate first.
+    pub fn measure_latency(&self, params: StreamParams, markers: usize) -> Result<LatencyReport> {
+        let sample_rate = self.get_sample_rate()?;
+        if sample_rate == 0 {
+            return Err(Error::InvalidParam);
+        }
+        if markers == 0 {
+            return Ok(LatencyReport {
+                params,
+                sample_rate,
+                markers: Vec::new(),
+                missed: 0,
+            });
+        }
+        self.set_test_mode(false)?;
+        self.reset_buffer()?;
+        let settle = params.queue_duration(sample_rate) * 2 + MARKER_SETTLE;
+
+        let (marked_tx, marked_rx) = mpsc::channel::<Instant>();
+        let (seen_tx, seen_rx) = mpsc::channel::<Duration>();
+        let switch = TestModeSwitch { dev: self.as_raw() };
+        let canceller = self.canceller();
+        let mut pending = None;
+
+        thread::scope(|scope| {
+            let marker = scope.spawn(move || {
+                let mut report = (Vec::new(), 0);
+                let mut result = Ok(());
+                for _ in 0..markers {
+                    thread::sleep(settle);
+                    // Sent first, so the callback is looking before any
+                    // counter bytes can arrive.
+                    let _ = marked_tx.send(Instant::now());
+                    result = switch.set(true);
+                    if result.is_err() {
+                        break;
+                    }
+                    match seen_rx.recv_timeout(MARKER_TIMEOUT) {
+                        Ok(latency) => report.0.push(latency),
+                        Err(_) => report.1 += 1,
+                    }
+                    result = switch.set(false);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                let _ = canceller.cancel();
+                result.map(|_| report)
+            });
+
+            let read = self.read_async_with(
+                |buf| {
+                    while let Ok(at) = marked_rx.try_recv() {
+                        pending = Some(at);
+                    }
+                    if let Some(at) = pending {
+                        if find_counter(buf).is_some() {
+                            let _ = seen_tx.send(at.elapsed());
+                            pending = None;
+                        }
+                    }
+                },
+                params.buf_num,
+                params.buf_len,
+            );
+            let marked = marker
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload));
+            let switched_off = self.set_test_mode(false);
+            read?;
+            switched_off?;
+            let (markers, missed) = marked?;
+            Ok(LatencyReport {
+                params,
+                sample_rate,
+                markers,
+                missed,
+            })
+        })
+    }
+
+    /// Measure the latency of every `StreamParams` preset.
+    ///
+    /// # Arguments
+    ///
+    /// * `markers` - The number of markers to time per preset.
+    ///
+    /// # Returns
+    ///
+    /// One `LatencyReport` per entry of `StreamParams::PRESETS`, in order.
+    pub fn measure_latency_presets(&self, markers: usize) -> Result<Vec<LatencyReport>> {
+        StreamParams::PRESETS
+            .iter()
+            .map(|&params| self.measure_latency(params, markers))
+            .collect()
+    }
+}
+
+/// Handle used to switch test mode from another thread while reading.
+struct TestModeSwitch {
+    dev: *mut RTLSDRDevT,
+}
+
+// Control transfers may be issued while another thread reads, as rtl_tcp
+// does for every command.
+unsafe impl Send for TestModeSwitch {}
+
+impl TestModeSwitch {
+    fn set(&self, on: bool) -> Result<()> {
+        let ret = unsafe { rtlsdr_set_test_mode(self.dev, on as i32) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(Error::from(ret))
+        }
+    }
+}
+
+/// Find where test mode's byte counter starts in a buffer.
+fn find_counter(buf: &[u8]) -> Option<usize> {
+    let mut run = 0;
+    for i in 1..buf.len() {
+        if buf[i] == buf[i - 1].wrapping_add(1) {
+            run += 1;
+            if run == COUNTER_RUN {
+                return Some(i - COUNTER_RUN);
+            }
+        } else {
+            run = 0;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_the_presets() {
+        assert_eq!(StreamParams::new(0, 0).unwrap(), StreamParams::DEFAULT);
+        assert!(StreamParams::new(4, 1000).is_err());
+        let rate = 2_048_000;
+        assert_eq!(
+            StreamParams::LOW_LATENCY.buffer_duration(rate),
+            Duration::from_millis(4)
+        );
+        assert_eq!(
+            StreamParams::LOW_LATENCY.queue_duration(rate),
+            Duration::from_millis(16)
+        );
+        let durations: Vec<Duration> = StreamParams::PRESETS
+            .iter()
+            .map(|p| p.buffer_duration(rate))
+            .collect();
+        assert!(durations.windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(StreamParams::DEFAULT.to_string(), "15 x 262144 B");
+    }
+
+    #[test]
+    fn finds_the_counter() {
+        let mut buf: Vec<u8> = (0..1000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        assert_eq!(find_counter(&buf), None);
+        for (i, b) in buf[600..].iter_mut().enumerate() {
+            *b = (i as u8).wrapping_add(200);
+        }
+        assert_eq!(find_counter(&buf), Some(600));
+    }
+
+    #[test]
+    fn summarises_markers() {
+        let mut report = LatencyReport {
+            params: StreamParams::LOW_LATENCY,
+            sample_rate: 2_048_000,
+            markers: vec![],
+            missed: 1,
+        };
+        assert_eq!(report.mean(), None);
+        assert_eq!(
+            report.to_string(),
+            "4 x 16384 B: buffer 4.0 ms, no markers, 1 missed"
+        );
+        report.markers = [3, 5, 10].map(Duration::from_millis).to_vec();
+        report.missed = 0;
+        assert_eq!(report.min(), Some(Duration::from_millis(3)));
+        assert_eq!(report.mean(), Some(Duration::from_millis(6)));
+        assert_eq!(report.max(), Some(Duration::from_millis(10)));
+        assert_eq!(
+            report.to_string(),
+            "4 x 16384 B: buffer 4.0 ms, latency 3.0/6.0/10.0 ms (3 markers)"
+        );
+    }
+}
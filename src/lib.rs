@@ -1,11 +1,115 @@
+#[cfg(feature = "audio")]
+pub mod audio;
+mod broadcast;
+mod builder;
+mod capabilities;
+#[cfg(any(
+    feature = "adsb",
+    feature = "ais",
+    feature = "aprs",
+    feature = "apt",
+    feature = "ism",
+    feature = "rds",
+    feature = "timecode"
+))]
+pub mod decoders;
+#[cfg(feature = "demod")]
+pub mod demod;
 mod device;
+mod device_info;
+mod device_pool;
+#[cfg(feature = "dsp")]
+pub mod dsp;
+mod eeprom;
 mod error;
+#[cfg(feature = "events")]
+pub mod events;
 mod ffi;
+mod file_source;
 mod hw_info;
+mod latency;
+#[cfg(feature = "mmap")]
+mod mapped;
+#[cfg(any(test, feature = "mock"))]
+#[cfg_attr(not(feature = "mock"), allow(dead_code))]
+mod mock;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+mod pool;
+#[cfg(feature = "presets")]
+pub mod presets;
+mod probe;
+mod provenance;
+#[cfg(feature = "pulse")]
+pub mod pulse;
+pub mod raw;
+#[cfg(feature = "rds")]
+pub mod rds;
+mod reconnect;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "rigctl")]
+pub mod rigctl;
+mod ring;
+#[cfg(feature = "dsp")]
+pub mod samples;
+#[cfg(feature = "sat")]
+pub mod sat;
+#[cfg(feature = "scan")]
+pub mod scan;
+#[cfg(feature = "ctrlc")]
+mod shutdown;
+#[cfg(feature = "soak")]
+pub mod soak;
+mod source;
+#[cfg(feature = "squelch")]
+pub mod squelch;
+mod stats;
+mod stream;
+mod sym;
+mod timing;
 mod tuner;
+#[cfg(feature = "udp")]
+mod udp;
+mod units;
+mod usb;
 mod utils;
+#[cfg(feature = "zmq")]
+mod zeromq;
 
+pub use broadcast::{Backpressure, SampleBroadcaster, Subscription};
+pub use builder::DeviceBuilder;
+pub use capabilities::{capabilities, Capabilities};
 pub use device::Device;
+pub use device_info::DeviceInfo;
+pub use device_pool::DevicePool;
+pub use eeprom::{BrickRisk, DongleModel, EepromProgress};
 pub use error::{Error, Result};
-pub use hw_info::HwInfo;
-pub use tuner::{RTLSDRTuner, SamplingMode};
+pub use file_source::{FileSource, IqFormat, Pacing, PlaybackControl};
+pub use hw_info::{EepromWarning, HwInfo};
+pub use latency::{LatencyReport, StreamParams};
+#[cfg(feature = "mmap")]
+pub use mapped::MappedRecording;
+#[cfg(feature = "mock")]
+pub use mock::{Call, MockDevice, MockSignal, MockSignals};
+pub use pool::{BufferPool, PoolStats, PooledBuffer};
+pub use probe::ProbeReport;
+pub use provenance::Provenance;
+pub use reconnect::{ReconnectEvent, Reconnecting, SleepDetector, DEFAULT_SLEEP_THRESHOLD};
+pub use ring::{OverrunPolicy, RingReader, RingSlot};
+#[cfg(feature = "ctrlc")]
+pub use shutdown::{run_until_ctrlc, stop_requested, Capture};
+pub use source::{Chunks, SdrSource};
+pub use stats::StreamStats;
+#[cfg(feature = "tokio")]
+pub use stream::SampleStream;
+pub use stream::{Samples, StreamHandle};
+pub use timing::{TimedSamples, Timestamp};
+pub use tuner::{GainStep, RTLSDRTuner, SamplingMode, TunerCapabilities};
+#[cfg(feature = "udp")]
+pub use udp::{UdpSink, UdpSource, UDP_HEADER_LEN};
+pub use units::{Frequency, SampleRate};
+#[cfg(feature = "zmq")]
+pub use zeromq::{TagValue, ZmqChunk, ZmqSink, ZmqSource, ZmqTag};
@@ -0,0 +1,95 @@
+use crate::error::Result;
+use crate::file_source::{FileSource, IqFormat};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+
+/// A memory-mapped IQ recording.
+///
+/// The recording is paged in by the OS on demand, so captures far larger than
+/// RAM can be processed window by window without reading them into memory.
+pub struct MappedRecording {
+    map: Mmap,
+    format: IqFormat,
+}
+
+impl MappedRecording {
+    /// Map a recording into memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the recording.
+    /// * `format` - The sample format of the recording.
+    ///
+    /// # Returns
+    ///
+    /// The mapped recording if successful, otherwise an `Error`.
+    pub fn open<P: AsRef<Path>>(path: P, format: IqFormat) -> Result<Self> {
+        let file = File::open(path)?;
+        // The mapping is read-only; like any mmap it assumes the file is not
+        // truncated by another process while mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedRecording { map, format })
+    }
+
+    /// Get the sample format of the recording.
+    pub fn format(&self) -> IqFormat {
+        self.format
+    }
+
+    /// Get the number of whole I/Q samples in the recording.
+    pub fn len(&self) -> usize {
+        self.map.len() / self.format.sample_size()
+    }
+
+    /// Check whether the recording holds no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the raw bytes of a range of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first sample.
+    /// * `count` - The number of samples, clipped to the end of the recording.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the samples in the recording's format.
+    pub fn slice(&self, start: usize, count: usize) -> &[u8] {
+        let size = self.format.sample_size();
+        let start = start.min(self.len());
+        let end = start.saturating_add(count).min(self.len());
+        &self.map[start * size..end * size]
+    }
+
+    /// Iterate over the recording in windows of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of samples per window.
+    /// * `hop` - The number of samples between window starts. Use `len` for
+    ///   back-to-back windows, less for overlapping ones.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(first_sample, bytes)` pairs. The last window may be
+    /// shorter than `len`.
+    pub fn windows(&self, len: usize, hop: usize) -> impl Iterator<Item = (usize, &[u8])> + '_ {
+        let hop = hop.max(1);
+        (0..self.len())
+            .step_by(hop)
+            .map(move |start| (start, self.slice(start, len)))
+    }
+
+    /// Replay the recording through the `SdrSource` interface.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate of the recording in Hz.
+    pub fn into_source(self, sample_rate: u32) -> FileSource<Cursor<Mmap>> {
+        FileSource::from_reader(Cursor::new(self.map), self.format, sample_rate)
+    }
+}
@@ -0,0 +1,505 @@
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use crate::tuner::SamplingMode;
+use std::cell::{Cell, RefCell};
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// A setter call recorded by `MockDevice`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    SetCenterFreq(u32),
+    SetSampleRate(u32),
+    SetFreqCorrection(i32),
+    SetTunerGainMode(bool),
+    SetTunerGain(i32),
+    SetTunerBandwidth(u32),
+    SetAgcMode(bool),
+    SetDirectSampling(SamplingMode),
+    SetOffsetTuning(bool),
+    SetBiasTee(bool),
+    ResetBuffer,
+}
+
+/// A synthetic signal produced by `MockDevice`.
+#[derive(Clone, Debug)]
+pub enum MockSignal {
+    /// A complex tone at `offset_hz` from the center frequency, with an
+    /// amplitude relative to full scale.
+    Tone { offset_hz: f64, amplitude: f64 },
+    /// Uniform white noise with an amplitude relative to full scale, from a
+    /// seeded generator so runs are repeatable.
+    Noise { amplitude: f64, seed: u64 },
+    /// Raw cu8 samples played back in a loop, e.g. loaded from a recording.
+    Data(Vec<u8>),
+    /// An unmodulated carrier at `freq_hz`, which moves with tuning the way
+    /// a real one does. Like a dongle's anti-aliasing filter, the mock
+    /// leaves it out while it lies outside 90 % of the passband.
+    Carrier { freq_hz: u32, amplitude: f64 },
+    /// A carrier at `freq_hz` keyed `deviation_hz` either side by
+    /// alternating bits at `baud`, filtered like `Carrier`.
+    Fsk {
+        freq_hz: u32,
+        deviation_hz: f64,
+        baud: f64,
+        amplitude: f64,
+    },
+}
+
+/// A handle on the signals of a `MockDevice`, for changing them while the
+/// device is owned by something else, e.g. a scanner under test.
+#[derive(Clone, Debug, Default)]
+pub struct MockSignals(Arc<Mutex<Vec<MockSignal>>>);
+
+impl MockSignals {
+    /// Add a signal.
+    pub fn push(&self, signal: MockSignal) {
+        self.lock().push(signal);
+    }
+
+    /// Remove a signal.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The signal's position, in the order they were added.
+    ///
+    /// # Returns
+    ///
+    /// The signal, or `None` if there is none at `index`.
+    pub fn remove(&self, index: usize) -> Option<MockSignal> {
+        let mut signals = self.lock();
+        (index < signals.len()).then(|| signals.remove(index))
+    }
+
+    /// Keep only the signals a function accepts.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - Whether to keep a signal.
+    pub fn retain(&self, keep: impl FnMut(&MockSignal) -> bool) {
+        self.lock().retain(keep);
+    }
+
+    /// Get the signals, in the order they were added.
+    pub fn get(&self) -> Vec<MockSignal> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<MockSignal>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A stand-in for `Device` that needs no hardware.
+///
+/// It offers the same setters and readers as `Device` and implements
+/// `SdrSource`. Every setter call is recorded for inspection with `calls`,
+/// and reads return a deterministic mix of the configured signals.
+pub struct MockDevice {
+    center_freq: Cell<u32>,
+    sample_rate: Cell<u32>,
+    freq_correction: Cell<i32>,
+    tuner_gain: Cell<i32>,
+    direct_sampling: Cell<SamplingMode>,
+    gains: Vec<i32>,
+    calls: RefCell<Vec<Call>>,
+    signals: MockSignals,
+    state: RefCell<GeneratorState>,
+}
+
+#[derive(Default)]
+struct GeneratorState {
+    sample: u64,
+    /// The state of each `Noise` signal's generator, in order.
+    rng: Vec<u64>,
+}
+
+impl Default for MockDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockDevice {
+    /// Create a mock device producing silence.
+    pub fn new() -> Self {
+        MockDevice {
+            center_freq: Cell::new(100_000_000),
+            sample_rate: Cell::new(2_048_000),
+            freq_correction: Cell::new(0),
+            tuner_gain: Cell::new(0),
+            direct_sampling: Cell::new(SamplingMode::None),
+            // The R820T gain table, the most common tuner.
+            gains: vec![
+                0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328,
+                338, 364, 372, 386, 402, 421, 434, 439, 445, 480, 496,
+            ],
+            calls: RefCell::new(Vec::new()),
+            signals: MockSignals::default(),
+            state: RefCell::new(GeneratorState::default()),
+        }
+    }
+
+    /// Add a signal to the generated samples. Signals are summed.
+    pub fn with_signal(self, signal: MockSignal) -> Self {
+        self.signals.push(signal);
+        self
+    }
+
+    /// Get a handle on the signals, to change them later.
+    pub fn signals(&self) -> MockSignals {
+        self.signals.clone()
+    }
+
+    /// Get the setter calls made so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.borrow().clone()
+    }
+
+    /// Forget the recorded setter calls.
+    pub fn clear_calls(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    fn record(&self, call: Call) -> Result<()> {
+        self.calls.borrow_mut().push(call);
+        Ok(())
+    }
+
+    /// Mock of `Device::set_center_freq`.
+    pub fn set_center_freq(&self, freq_hz: u32) -> Result<()> {
+        self.center_freq.set(freq_hz);
+        self.record(Call::SetCenterFreq(freq_hz))
+    }
+
+    /// Mock of `Device::get_center_freq`.
+    pub fn get_center_freq(&self) -> Result<u32> {
+        Ok(self.center_freq.get())
+    }
+
+    /// Mock of `Device::set_sample_rate`.
+    pub fn set_sample_rate(&self, rate_hz: u32) -> Result<()> {
+        if rate_hz == 0 {
+            return Err(Error::InvalidParam);
+        }
+        self.sample_rate.set(rate_hz);
+        self.record(Call::SetSampleRate(rate_hz))
+    }
+
+    /// Mock of `Device::get_sample_rate`.
+    pub fn get_sample_rate(&self) -> Result<u32> {
+        Ok(self.sample_rate.get())
+    }
+
+    /// Mock of `Device::set_freq_correction`.
+    pub fn set_freq_correction(&self, ppm: i32) -> Result<()> {
+        self.freq_correction.set(ppm);
+        self.record(Call::SetFreqCorrection(ppm))
+    }
+
+    /// Mock of `Device::get_freq_correction`.
+    pub fn get_freq_correction(&self) -> Result<i32> {
+        Ok(self.freq_correction.get())
+    }
+
+    /// Mock of `Device::get_tuner_gains`.
+    pub fn get_tuner_gains(&self) -> Result<Vec<i32>> {
+        Ok(self.gains.clone())
+    }
+
+    /// Mock of `Device::set_tuner_gain_mode`.
+    pub fn set_tuner_gain_mode(&self, manual_mode: bool) -> Result<()> {
+        self.record(Call::SetTunerGainMode(manual_mode))
+    }
+
+    /// Mock of `Device::set_tuner_gain`.
+    pub fn set_tuner_gain(&self, gain: i32) -> Result<()> {
+        self.tuner_gain.set(gain);
+        self.record(Call::SetTunerGain(gain))
+    }
+
+    /// Mock of `Device::get_tuner_gain`.
+    pub fn get_tuner_gain(&self) -> Result<i32> {
+        Ok(self.tuner_gain.get())
+    }
+
+    /// Mock of `Device::set_tuner_bandwidth`.
+    pub fn set_tuner_bandwidth(&self, bw_hz: u32) -> Result<()> {
+        self.record(Call::SetTunerBandwidth(bw_hz))
+    }
+
+    /// Mock of `Device::set_agc_mode`.
+    pub fn set_agc_mode(&self, on: bool) -> Result<()> {
+        self.record(Call::SetAgcMode(on))
+    }
+
+    /// Mock of `Device::set_direct_sampling`.
+    pub fn set_direct_sampling(&self, mode: SamplingMode) -> Result<()> {
+        if mode == SamplingMode::Error {
+            return Err(Error::InvalidParam);
+        }
+        self.direct_sampling.set(mode);
+        self.record(Call::SetDirectSampling(mode))
+    }
+
+    /// Mock of `Device::get_direct_sampling`.
+    pub fn get_direct_sampling(&self) -> Result<SamplingMode> {
+        Ok(self.direct_sampling.get())
+    }
+
+    /// Mock of `Device::set_offset_tuning`.
+    pub fn set_offset_tuning(&self, on: bool) -> Result<()> {
+        self.record(Call::SetOffsetTuning(on))
+    }
+
+    /// Mock of `Device::set_bias_tee`.
+    pub fn set_bias_tee(&self, on: bool) -> Result<()> {
+        self.record(Call::SetBiasTee(on))
+    }
+
+    /// Restart the generated signals from their first sample.
+    pub fn reset_buffer(&self) -> Result<()> {
+        self.state.borrow_mut().sample = 0;
+        self.record(Call::ResetBuffer)
+    }
+
+    /// Mock of `Device::read_sync`.
+    pub fn read_sync(&self, length: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; length];
+        self.generate(&mut buf);
+        Ok(buf)
+    }
+
+    /// Mock of `Device::read_sync_exact`.
+    pub fn read_sync_exact(&self, length: usize) -> Result<Vec<u8>> {
+        self.read_sync(length)
+    }
+
+    fn generate(&self, buf: &mut [u8]) {
+        let rate = self.sample_rate.get() as f64;
+        let centre_hz = self.center_freq.get() as f64;
+        // Read in place rather than cloned, so reads allocate nothing once
+        // every noise generator is seeded.
+        let signals = self.signals.lock();
+        let mut state = self.state.borrow_mut();
+        let GeneratorState { sample, rng } = &mut *state;
+        // Seed the generators of noise added since the last read.
+        let seeds = signals.iter().filter_map(|signal| match signal {
+            MockSignal::Noise { seed, .. } => Some(*seed),
+            _ => None,
+        });
+        for seed in seeds.skip(rng.len()) {
+            // xorshift gets stuck on zero.
+            rng.push(seed.max(1));
+        }
+        for pair in buf.chunks_exact_mut(2) {
+            let (mut i, mut q) = (0.0, 0.0);
+            let mut noise = rng.iter_mut();
+            let t = *sample as f64 / rate;
+            for signal in signals.iter() {
+                match signal {
+                    MockSignal::Tone {
+                        offset_hz,
+                        amplitude,
+                    } => {
+                        let phase = 2.0 * PI * offset_hz * (*sample as f64) / rate;
+                        i += amplitude * phase.cos();
+                        q += amplitude * phase.sin();
+                    }
+                    MockSignal::Noise { amplitude, .. } => {
+                        if let Some(state) = noise.next() {
+                            i += amplitude * uniform(state);
+                            q += amplitude * uniform(state);
+                        }
+                    }
+                    MockSignal::Data(data) if data.len() >= 2 => {
+                        let at = (*sample as usize * 2) % (data.len() & !1);
+                        i += (data[at] as f64 - 127.5) / 127.5;
+                        q += (data[at + 1] as f64 - 127.5) / 127.5;
+                    }
+                    MockSignal::Data(_) => {}
+                    MockSignal::Carrier { freq_hz, amplitude } => {
+                        let offset = *freq_hz as f64 - centre_hz;
+                        if offset.abs() < rate * 0.45 {
+                            let phase = 2.0 * PI * offset * t;
+                            i += amplitude * phase.cos();
+                            q += amplitude * phase.sin();
+                        }
+                    }
+                    MockSignal::Fsk {
+                        freq_hz,
+                        deviation_hz,
+                        baud,
+                        amplitude,
+                    } => {
+                        let offset = *freq_hz as f64 - centre_hz;
+                        if offset.abs() < rate * 0.45 {
+                            // Alternating bits make the frequency a square
+                            // wave, so the phase they add is a triangle.
+                            let bit = 1.0 / baud;
+                            let within = t % (2.0 * bit);
+                            let keyed = if within < bit {
+                                within
+                            } else {
+                                2.0 * bit - within
+                            };
+                            let phase = 2.0 * PI * (offset * t + deviation_hz * keyed);
+                            i += amplitude * phase.cos();
+                            q += amplitude * phase.sin();
+                        }
+                    }
+                }
+            }
+            pair[0] = to_u8(i);
+            pair[1] = to_u8(q);
+            *sample += 1;
+        }
+    }
+}
+
+impl SdrSource for MockDevice {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        self.set_center_freq(freq_hz)
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        self.get_center_freq()
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        MockDevice::set_sample_rate(self, rate_hz)
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        self.get_sample_rate()
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.set_tuner_gain_mode(gain.is_some())?;
+        match gain {
+            Some(gain) => self.set_tuner_gain(gain),
+            None => Ok(()),
+        }
+    }
+
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        self.get_direct_sampling()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = buf.len() & !1;
+        self.generate(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// xorshift64 mapped to -1.0..1.0.
+fn uniform(state: &mut u64) -> f64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    (*state >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+fn to_u8(v: f64) -> u8 {
+    (v * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Set up a receiver the way an application would, through the trait.
+    fn configure<S: SdrSource>(source: &mut S) -> Result<()> {
+        source.tune(433_920_000)?;
+        source.set_sample_rate(1_024_000)?;
+        source.set_gain(Some(297))?;
+        source.set_gain(None)
+    }
+
+    #[test]
+    fn records_calls_made_through_sdr_source() {
+        let mut mock = MockDevice::new();
+        configure(&mut mock).unwrap();
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::SetCenterFreq(433_920_000),
+                Call::SetSampleRate(1_024_000),
+                Call::SetTunerGainMode(true),
+                Call::SetTunerGain(297),
+                Call::SetTunerGainMode(false),
+            ]
+        );
+        assert_eq!(mock.center_freq().unwrap(), 433_920_000);
+        assert_eq!(SdrSource::sample_rate(&mock).unwrap(), 1_024_000);
+        assert_eq!(mock.get_tuner_gain().unwrap(), 297);
+
+        mock.clear_calls();
+        assert!(SdrSource::set_sample_rate(&mut mock, 0).is_err());
+        assert!(mock.calls().is_empty());
+    }
+
+    #[test]
+    fn tone_is_deterministic() {
+        let mut mock = MockDevice::new().with_signal(MockSignal::Tone {
+            offset_hz: 100_000.0,
+            amplitude: 0.5,
+        });
+        configure(&mut mock).unwrap();
+        let chunks: Vec<Vec<u8>> = mock.chunks(8).take(2).map(|c| c.unwrap()).collect();
+        // 0.5 * e^(j 2 pi n 100 kHz / 1.024 MS/s), scaled to cu8.
+        assert_eq!(chunks[0], [191, 128, 180, 164, 149, 188, 110, 189]);
+        assert_ne!(chunks[1], chunks[0]);
+
+        // An odd length reads whole samples only.
+        let mut buf = [0u8; 5];
+        assert_eq!(mock.read(&mut buf).unwrap(), 4);
+
+        // Resetting the buffer starts the tone over.
+        mock.reset_buffer().unwrap();
+        let mut again = [0u8; 8];
+        mock.read_exact(&mut again).unwrap();
+        assert_eq!(again, chunks[0][..]);
+        assert_eq!(mock.calls().last(), Some(&Call::ResetBuffer));
+    }
+
+    #[test]
+    fn noise_repeats_for_a_seed() {
+        let noise = MockSignal::Noise {
+            amplitude: 0.3,
+            seed: 7,
+        };
+        let a = MockDevice::new().with_signal(noise.clone());
+        let b = MockDevice::new().with_signal(noise);
+        let (a, b) = (a.read_sync(1024).unwrap(), b.read_sync(1024).unwrap());
+        assert_eq!(a, b);
+        assert!(a.iter().any(|&v| v != a[0]));
+    }
+
+    #[test]
+    fn carriers_move_with_tuning_and_switch_while_owned() {
+        let mut mock = MockDevice::new();
+        let signals = mock.signals();
+        configure(&mut mock).unwrap();
+        let level = |mock: &mut MockDevice| {
+            let mut buf = [0u8; 64];
+            mock.read_exact(&mut buf).unwrap();
+            buf.iter().map(|&v| (v as f64 - 127.5).abs()).sum::<f64>() / 64.0
+        };
+        assert!(level(&mut mock) < 1.0);
+
+        signals.push(MockSignal::Carrier {
+            freq_hz: 434_000_000,
+            amplitude: 0.5,
+        });
+        assert!(level(&mut mock) > 30.0);
+        // Outside the passband once tuned away.
+        mock.tune(435_000_000).unwrap();
+        assert!(level(&mut mock) < 1.0);
+        mock.tune(433_920_000).unwrap();
+        assert!(signals.remove(0).is_some());
+        assert!(signals.remove(0).is_none());
+        assert!(level(&mut mock) < 1.0);
+    }
+}
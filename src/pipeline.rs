@@ -0,0 +1,720 @@
+//! Chaining sources, processing blocks and sinks into a flowgraph.
+//!
+//! A pipeline starts from a source, passes every item through a chain of
+//! `Block`s and ends in a sink. Each stage runs on its own thread and hands
+//! items to the next through a bounded queue, so a slow stage holds back the
+//! ones before it instead of letting memory grow. Stages spend their time
+//! blocked on those queues or on the source, so they get threads of their
+//! own rather than sharing a fixed pool, which would deadlock as soon as
+//! every worker waited on a stage left without one.
+//!
+//! ```no_run
+//! use radion::dsp::Complex;
+//! use radion::pipeline::{self, Pipeline, ToComplex};
+//! use radion::Device;
+//!
+//! let device = Device::new(0)?;
+//! Pipeline::from_sdr(device, 16 * 16384)
+//!     .then(ToComplex::default())
+//!     .then(pipeline::map(|iq: Vec<Complex<f32>>| {
+//!         iq.iter().map(|s| s.norm_sqr()).sum::<f32>() / iq.len() as f32
+//!     }))
+//!     .sink(|power| {
+//!         println!("{:.1} dBFS", 10.0 * power.log10());
+//!         Ok(())
+//!     })
+//!     .run()?;
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use std::marker::PhantomData;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Items queued between two stages when not set with `with_queue`.
+pub const DEFAULT_QUEUE: usize = 4;
+
+/// A processing stage of a pipeline.
+///
+/// Blocks take one item at a time, typically a buffer of samples, and may
+/// produce one item for each. Implemented for the filters, decimators and
+/// demodulators of `radion::dsp` and `radion::demod`; use `map` for
+/// closures.
+pub trait Block: Send + 'static {
+    /// The items the block consumes.
+    type In: Send + 'static;
+    /// The items the block produces.
+    type Out: Send + 'static;
+
+    /// Process one item.
+    ///
+    /// # Returns
+    ///
+    /// The item to pass on, `None` to pass nothing on this time, or an
+    /// `Error` that stops the pipeline.
+    fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>>;
+
+    /// Flush anything held back once the input has ended.
+    ///
+    /// # Returns
+    ///
+    /// A last item to pass on, if any.
+    fn finish(&mut self) -> Result<Option<Self::Out>> {
+        Ok(None)
+    }
+}
+
+/// Block calling a closure on every item, created by `map`.
+pub struct Map<F, I> {
+    f: F,
+    input: PhantomData<fn(I)>,
+}
+
+/// Turn a closure into a `Block`.
+///
+/// # Arguments
+///
+/// * `f` - The closure to call with every item.
+///
+/// # Returns
+///
+/// A block passing on whatever the closure returns.
+pub fn map<F, I, O>(f: F) -> Map<F, I>
+where
+    F: FnMut(I) -> O + Send + 'static,
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    Map {
+        f,
+        input: PhantomData,
+    }
+}
+
+impl<F, I, O> Block for Map<F, I>
+where
+    F: FnMut(I) -> O + Send + 'static,
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    type In = I;
+    type Out = O;
+
+    fn process(&mut self, input: I) -> Result<Option<O>> {
+        Ok(Some((self.f)(input)))
+    }
+}
+
+type Stage = Box<dyn FnOnce() -> Result<()> + Send>;
+
+/// A pipeline under construction whose last stage produces `T`.
+///
+/// Created by `Pipeline::source` or `Pipeline::from_sdr`.
+pub struct PipelineBuilder<T> {
+    stages: Vec<Stage>,
+    output: Receiver<T>,
+    queue: usize,
+    stop: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> PipelineBuilder<T> {
+    /// Set how many items may wait between the stages added after this.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The queue length, at least 1.
+    pub fn with_queue(mut self, queue: usize) -> Self {
+        self.queue = queue.max(1);
+        self
+    }
+
+    /// Append a block.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The block to pass every item through.
+    ///
+    /// # Returns
+    ///
+    /// The builder, now producing the block's output.
+    pub fn then<B: Block<In = T>>(mut self, mut block: B) -> PipelineBuilder<B::Out> {
+        let (tx, output) = mpsc::sync_channel(self.queue);
+        let input = self.output;
+        self.stages.push(Box::new(move || {
+            for item in input {
+                if let Some(out) = block.process(item)? {
+                    if tx.send(out).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            if let Some(out) = block.finish()? {
+                let _ = tx.send(out);
+            }
+            Ok(())
+        }));
+        PipelineBuilder {
+            stages: self.stages,
+            output,
+            queue: self.queue,
+            stop: self.stop,
+        }
+    }
+
+    /// End the pipeline in a closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - The closure to call with every item. An `Error` stops the
+    ///   pipeline.
+    ///
+    /// # Returns
+    ///
+    /// The complete `Pipeline`, ready to run.
+    pub fn sink<F>(mut self, mut sink: F) -> Pipeline
+    where
+        F: FnMut(T) -> Result<()> + Send + 'static,
+    {
+        let input = self.output;
+        self.stages.push(Box::new(move || {
+            for item in input {
+                sink(item)?;
+            }
+            Ok(())
+        }));
+        Pipeline {
+            stages: self.stages,
+            stop: self.stop,
+        }
+    }
+
+    /// Run the pipeline and iterate over what its last stage produces.
+    ///
+    /// # Returns
+    ///
+    /// A `PipelineOutput` yielding the items, which stops the pipeline
+    /// when dropped.
+    pub fn spawn_iter(self) -> Result<PipelineOutput<T>> {
+        let handle = Pipeline {
+            stages: self.stages,
+            stop: self.stop,
+        }
+        .spawn()?;
+        Ok(PipelineOutput {
+            output: Some(self.output),
+            handle: Some(handle),
+        })
+    }
+}
+
+/// A complete pipeline, from source to sink.
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Pipeline {
+    /// Start a pipeline from an iterator.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The items to feed the pipeline. The pipeline stops at
+    ///   the first `Error`.
+    ///
+    /// # Returns
+    ///
+    /// A `PipelineBuilder` to append blocks to.
+    pub fn source<I, T>(source: I) -> PipelineBuilder<T>
+    where
+        I: IntoIterator<Item = Result<T>>,
+        I::IntoIter: Send + 'static,
+        T: Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, output) = mpsc::sync_channel(DEFAULT_QUEUE);
+        let source = source.into_iter();
+        let flag = stop.clone();
+        let stage: Stage = Box::new(move || feed(source, tx, &flag));
+        PipelineBuilder {
+            stages: vec![stage],
+            output,
+            queue: DEFAULT_QUEUE,
+            stop,
+        }
+    }
+
+    /// Start a pipeline from an `SdrSource`, such as a `Device` or
+    /// `FileSource`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to read until it is exhausted or the
+    ///   pipeline is stopped.
+    /// * `chunk_len` - The number of bytes per item.
+    ///
+    /// # Returns
+    ///
+    /// A `PipelineBuilder` producing the raw cu8 chunks.
+    pub fn from_sdr<S>(mut source: S, chunk_len: usize) -> PipelineBuilder<Vec<u8>>
+    where
+        S: SdrSource + Send + 'static,
+    {
+        let mut done = false;
+        Pipeline::source(std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let mut buf = vec![0; chunk_len];
+            let chunk = match source.read(&mut buf) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Ok(buf)
+                }
+                Err(e) => Err(e),
+            };
+            done = chunk.is_err();
+            Some(chunk)
+        }))
+    }
+
+    /// Get the number of stages, each of which runs on its own thread.
+    pub fn stages(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Start every stage on its own thread.
+    ///
+    /// # Returns
+    ///
+    /// A `PipelineHandle` controlling the running pipeline, or an `Error` if
+    /// a thread could not be started.
+    pub fn spawn(self) -> Result<PipelineHandle> {
+        let mut threads = Vec::with_capacity(self.stages.len());
+        for (i, stage) in self.stages.into_iter().enumerate() {
+            let thread = thread::Builder::new()
+                .name(format!("radion-pipeline-{}", i))
+                .spawn(stage);
+            match thread {
+                Ok(thread) => threads.push(thread),
+                Err(e) => {
+                    // Dropping the unstarted stages closes their queues, so
+                    // the started ones wind down by themselves.
+                    self.stop.store(true, Ordering::Release);
+                    return Err(Error::System(e));
+                }
+            }
+        }
+        Ok(PipelineHandle {
+            threads,
+            stop: self.stop,
+        })
+    }
+
+    /// Run the pipeline until the source is exhausted.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every stage finished, otherwise the first `Error`.
+    pub fn run(self) -> Result<()> {
+        self.spawn()?.wait()
+    }
+}
+
+fn feed<I, T>(source: I, tx: SyncSender<T>, stop: &AtomicBool) -> Result<()>
+where
+    I: Iterator<Item = Result<T>>,
+{
+    for item in source {
+        if stop.load(Ordering::Acquire) || tx.send(item?).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Guard for a running pipeline.
+///
+/// Created by `Pipeline::spawn`. Dropping the handle stops the pipeline and
+/// waits for its threads.
+pub struct PipelineHandle {
+    threads: Vec<JoinHandle<Result<()>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PipelineHandle {
+    /// Check whether any stage is still running.
+    pub fn is_running(&self) -> bool {
+        self.threads.iter().any(|t| !t.is_finished())
+    }
+
+    /// Stop the source and wait for the other stages to finish what is
+    /// queued.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every stage finished, otherwise the first `Error`.
+    pub fn stop(self) -> Result<()> {
+        self.stop.store(true, Ordering::Release);
+        self.wait()
+    }
+
+    /// Wait for the pipeline to end by itself.
+    ///
+    /// If a stage panicked, the panic is resumed on the calling thread.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every stage finished, otherwise the first `Error`.
+    pub fn wait(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for thread in self.threads.drain(..) {
+            match thread.join() {
+                Ok(Err(e)) if result.is_ok() => result = Err(e),
+                Ok(_) => {}
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        result
+    }
+}
+
+impl Drop for PipelineHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if !thread::panicking() {
+            let _ = self.join();
+        }
+    }
+}
+
+/// Iterator over the output of a running pipeline.
+///
+/// Created by `PipelineBuilder::spawn_iter`. Dropping it stops the pipeline.
+pub struct PipelineOutput<T> {
+    output: Option<Receiver<T>>,
+    handle: Option<PipelineHandle>,
+}
+
+impl<T> PipelineOutput<T> {
+    /// Stop the pipeline, discarding what is still queued.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every stage finished, otherwise the first `Error`.
+    pub fn stop(mut self) -> Result<()> {
+        // Closing the last queue ends the stages feeding it.
+        self.output.take();
+        self.handle.take().map_or(Ok(()), PipelineHandle::stop)
+    }
+}
+
+impl<T> Iterator for PipelineOutput<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(item) = self.output.as_ref()?.recv() {
+            return Some(Ok(item));
+        }
+        // The last stage has ended; report how.
+        self.output = None;
+        match self.handle.take()?.wait() {
+            Ok(()) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<T> Drop for PipelineOutput<T> {
+    fn drop(&mut self) {
+        self.output.take();
+    }
+}
+
+#[cfg(feature = "dsp")]
+pub use self::dsp_blocks::ToComplex;
+
+#[cfg(feature = "dsp")]
+mod dsp_blocks {
+    use super::Block;
+    use crate::dsp::fir::{ChannelDecimator, Decimator, Filter, Sample};
+    use crate::dsp::{
+        Complex, Convolver, CostasLoop, FreqXlator, HalfBand, HalfBandCascade, Pll, SymbolSync,
+    };
+    use crate::error::Result;
+    use crate::samples::{to_complex_f32, Scaling};
+
+    /// Block converting raw cu8 chunks into complex samples, see
+    /// `radion::samples::to_complex_f32`.
+    #[derive(Copy, Clone, Debug, Default)]
+    pub struct ToComplex {
+        /// The scaling to convert with.
+        pub scaling: Scaling,
+    }
+
+    impl Block for ToComplex {
+        type In = Vec<u8>;
+        type Out = Vec<Complex<f32>>;
+
+        fn process(&mut self, input: Vec<u8>) -> Result<Option<Self::Out>> {
+            Ok(Some(to_complex_f32(&input, self.scaling)))
+        }
+    }
+
+    impl<T: Sample + Send + 'static> Block for Filter<T> {
+        type In = Vec<T>;
+        type Out = Vec<T>;
+
+        fn process(&mut self, mut input: Vec<T>) -> Result<Option<Vec<T>>> {
+            self.filter_in_place(&mut input);
+            Ok(Some(input))
+        }
+    }
+
+    impl Block for Convolver {
+        type In = Vec<Complex<f32>>;
+        type Out = Vec<Complex<f32>>;
+
+        fn process(&mut self, mut input: Self::In) -> Result<Option<Self::Out>> {
+            self.filter_in_place(&mut input);
+            Ok(Some(input))
+        }
+    }
+
+    impl<T: Sample + Send + 'static> Block for Decimator<T> {
+        type In = Vec<T>;
+        type Out = Vec<T>;
+
+        fn process(&mut self, input: Vec<T>) -> Result<Option<Vec<T>>> {
+            Ok(Some(self.decimate(&input)))
+        }
+    }
+
+    macro_rules! complex_decimator_block {
+        ($($ty:ty),*) => {$(
+            impl Block for $ty {
+                type In = Vec<Complex<f32>>;
+                type Out = Vec<Complex<f32>>;
+
+                fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+                    Ok(Some(self.decimate(&input)))
+                }
+            }
+        )*};
+    }
+
+    complex_decimator_block!(ChannelDecimator, HalfBand, HalfBandCascade);
+
+    macro_rules! complex_process_block {
+        ($($ty:ty),*) => {$(
+            impl Block for $ty {
+                type In = Vec<Complex<f32>>;
+                type Out = Vec<Complex<f32>>;
+
+                fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+                    Ok(Some(<$ty>::process(self, &input)))
+                }
+            }
+        )*};
+    }
+
+    complex_process_block!(CostasLoop, FreqXlator, Pll, SymbolSync);
+
+    #[cfg(feature = "fft")]
+    impl Block for crate::dsp::Channelizer {
+        type In = Vec<Complex<f32>>;
+        type Out = Vec<Vec<Complex<f32>>>;
+
+        fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+            Ok(Some(crate::dsp::Channelizer::process(self, &input)))
+        }
+    }
+}
+
+#[cfg(feature = "demod")]
+mod demod_blocks {
+    use super::Block;
+    use crate::demod::{AmDemod, FmDemod, FmStereo, SsbDemod};
+    use crate::dsp::Complex;
+    use crate::error::Result;
+
+    macro_rules! demod_block {
+        ($($ty:ty),*) => {$(
+            impl Block for $ty {
+                type In = Vec<Complex<f32>>;
+                type Out = Vec<f32>;
+
+                fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+                    Ok(Some(<$ty>::process(self, &input)))
+                }
+            }
+        )*};
+    }
+
+    demod_block!(AmDemod, FmDemod, SsbDemod);
+
+    impl Block for FmStereo {
+        type In = Vec<f32>;
+        type Out = Vec<[f32; 2]>;
+
+        fn process(&mut self, input: Vec<f32>) -> Result<Option<Self::Out>> {
+            Ok(Some(FmStereo::process(self, &input)))
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl<F: crate::audio::AudioFrame> Block for crate::audio::AudioSink<F> {
+    type In = Vec<F>;
+    type Out = Vec<F>;
+
+    /// Plays each chunk and passes it on unchanged, e.g. to a recorder.
+    fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+        self.play(&input)?;
+        Ok(Some(input))
+    }
+}
+
+#[cfg(feature = "pulse")]
+impl Block for crate::pulse::PulseDetector {
+    type In = Vec<crate::dsp::Complex<f32>>;
+    type Out = Vec<crate::pulse::PulseTrain>;
+
+    /// Passes on the bursts completed in each chunk, skipping chunks that
+    /// complete none.
+    fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+        let trains = crate::pulse::PulseDetector::process(self, &input);
+        Ok((!trains.is_empty()).then_some(trains))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    /// Block summing its input and passing the total on at the end.
+    struct Sum(u32);
+
+    impl Block for Sum {
+        type In = u32;
+        type Out = u32;
+
+        fn process(&mut self, input: u32) -> Result<Option<u32>> {
+            self.0 += input;
+            Ok(None)
+        }
+
+        fn finish(&mut self) -> Result<Option<u32>> {
+            Ok(Some(self.0))
+        }
+    }
+
+    /// A source that never ends, counting the items it has produced.
+    fn endless(produced: &Arc<AtomicUsize>) -> impl Iterator<Item = Result<u32>> + Send {
+        let produced = produced.clone();
+        (0..).map(move |i| {
+            produced.fetch_add(1, Ordering::Relaxed);
+            Ok(i)
+        })
+    }
+
+    #[test]
+    fn passes_items_through_every_stage_in_order() {
+        let output: Vec<u32> = Pipeline::source((1..=5).map(Ok))
+            .then(map(|x: u32| x * 10))
+            .then(map(|x: u32| x + 1))
+            .spawn_iter()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(output, [11, 21, 31, 41, 51]);
+    }
+
+    #[test]
+    fn flushes_blocks_when_the_input_ends() {
+        let mut output = Pipeline::source((1..=4).map(Ok))
+            .then(Sum(0))
+            .spawn_iter()
+            .unwrap();
+        assert_eq!(output.next().unwrap().unwrap(), 10);
+        assert!(output.next().is_none());
+    }
+
+    #[test]
+    fn bounded_queues_hold_back_the_source() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let output = Pipeline::source(endless(&produced))
+            .with_queue(1)
+            .then(map(|x: u32| x))
+            .spawn_iter()
+            .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        // The source queue, the block and its output queue each hold one
+        // or a few items, and the source one more it cannot send.
+        let held = produced.load(Ordering::Relaxed);
+        assert!(held <= DEFAULT_QUEUE + 3, "{} items produced", held);
+
+        // Dropping the output stops the pipeline although the source
+        // never ends.
+        drop(output);
+        let stopped = produced.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(produced.load(Ordering::Relaxed), stopped);
+    }
+
+    #[test]
+    fn stop_ends_an_endless_source() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+        let handle = Pipeline::source(endless(&produced))
+            .sink(move |x| {
+                let _ = tx.send(x);
+                Ok(())
+            })
+            .spawn()
+            .unwrap();
+        let received: Vec<u32> = rx.iter().take(100).collect();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+        assert!(handle.is_running());
+        handle.stop().unwrap();
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let result = Pipeline::source(endless(&produced))
+            .sink(|x| if x == 3 { Err(Error::Timeout) } else { Ok(()) })
+            .run();
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let source = vec![Ok(1), Err(Error::Overrun), Ok(2)];
+        let mut output = Pipeline::source(source)
+            .then(map(|x: u32| x))
+            .spawn_iter()
+            .unwrap();
+        assert_eq!(output.next().unwrap().unwrap(), 1);
+        assert!(matches!(output.next(), Some(Err(Error::Overrun))));
+        assert!(output.next().is_none());
+    }
+
+    #[test]
+    fn runs_one_thread_per_stage() {
+        let pipeline = Pipeline::source((0..3).map(Ok))
+            .then(map(|x: u32| x))
+            .then(map(|x: u32| x))
+            .sink(|_| Ok(()));
+        assert_eq!(pipeline.stages(), 4);
+        pipeline.run().unwrap();
+    }
+}
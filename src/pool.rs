@@ -0,0 +1,311 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Usage counters of a `BufferPool`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of buffers in the pool.
+    pub capacity: usize,
+    /// Length of each buffer in bytes.
+    pub buf_len: usize,
+    /// Buffers currently held by consumers.
+    pub in_use: usize,
+    /// Most buffers ever held at once.
+    pub peak_in_use: usize,
+    /// Buffers handed out so far.
+    pub acquired: u64,
+    /// Times no buffer was free, so the data was dropped.
+    pub starved: u64,
+}
+
+struct PoolSlot {
+    /// Number of `PooledBuffer`s sharing the slot, 0 while it is free.
+    refs: AtomicUsize,
+    len: UnsafeCell<usize>,
+    data: UnsafeCell<Box<[u8]>>,
+}
+
+struct Shared {
+    slots: Box<[PoolSlot]>,
+    buf_len: usize,
+    /// Where the next search for a free slot starts.
+    next: AtomicUsize,
+    in_use: AtomicUsize,
+    peak_in_use: AtomicUsize,
+    acquired: AtomicU64,
+    starved: AtomicU64,
+}
+
+// Slot data is only written while its reference count is taken from 0 to 1
+// by the writer, and only read while it is positive.
+unsafe impl Sync for Shared {}
+unsafe impl Send for Shared {}
+
+/// Fixed set of reusable sample buffers.
+///
+/// Buffers are allocated once, filled from the asynchronous read path and
+/// handed to consumers as `PooledBuffer`s, which go back to the pool when the
+/// last clone is dropped. Taking and returning a buffer is lock-free and
+/// never allocates; when every buffer is in use the data is dropped and
+/// counted as starvation in `stats`. Cloning the pool gives another handle
+/// to the same buffers.
+#[derive(Clone)]
+pub struct BufferPool {
+    shared: Arc<Shared>,
+}
+
+impl BufferPool {
+    /// Allocate a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of buffers, at least 1. This bounds how many
+    ///   buffers consumers can hold at once.
+    /// * `buf_len` - The length of each buffer in bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new `BufferPool`.
+    pub fn new(count: usize, buf_len: usize) -> Self {
+        BufferPool {
+            shared: Arc::new(Shared {
+                slots: (0..count.max(1))
+                    .map(|_| PoolSlot {
+                        refs: AtomicUsize::new(0),
+                        len: UnsafeCell::new(0),
+                        data: UnsafeCell::new(vec![0; buf_len].into_boxed_slice()),
+                    })
+                    .collect(),
+                buf_len,
+                next: AtomicUsize::new(0),
+                in_use: AtomicUsize::new(0),
+                peak_in_use: AtomicUsize::new(0),
+                acquired: AtomicU64::new(0),
+                starved: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Get the number of buffers in the pool.
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    /// Get the length of each buffer in bytes.
+    pub fn buf_len(&self) -> usize {
+        self.shared.buf_len
+    }
+
+    /// Get the usage counters of the pool.
+    pub fn stats(&self) -> PoolStats {
+        let shared = &self.shared;
+        PoolStats {
+            capacity: shared.slots.len(),
+            buf_len: shared.buf_len,
+            in_use: shared.in_use.load(Ordering::Relaxed),
+            peak_in_use: shared.peak_in_use.load(Ordering::Relaxed),
+            acquired: shared.acquired.load(Ordering::Relaxed),
+            starved: shared.starved.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Copy data into a free buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to copy. At most `buf_len` bytes are copied.
+    ///
+    /// # Returns
+    ///
+    /// The filled buffer, or `None` if every buffer is in use.
+    pub fn copy_from(&self, data: &[u8]) -> Option<PooledBuffer> {
+        let shared = &self.shared;
+        let count = shared.slots.len();
+        let start = shared.next.fetch_add(1, Ordering::Relaxed);
+        let index = (0..count).map(|i| (start + i) % count).find(|&i| {
+            shared.slots[i]
+                .refs
+                .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        });
+        let Some(index) = index else {
+            shared.starved.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        let slot = &shared.slots[index];
+        // SAFETY: taking the count from 0 to 1 gave us the only reference.
+        unsafe {
+            let buf = &mut *slot.data.get();
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            *slot.len.get() = len;
+        }
+        shared.acquired.fetch_add(1, Ordering::Relaxed);
+        // Briefly overcounts while a buffer is being returned elsewhere.
+        let in_use = shared.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        shared
+            .peak_in_use
+            .fetch_max(in_use.min(count), Ordering::Relaxed);
+        Some(PooledBuffer {
+            shared: shared.clone(),
+            index,
+        })
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Shared, read-only sample buffer from a `BufferPool`.
+///
+/// Dereferences to the samples. Clones share the same buffer like an
+/// `Arc<[u8]>`, and the buffer is recycled once the last one is dropped.
+pub struct PooledBuffer {
+    shared: Arc<Shared>,
+    index: usize,
+}
+
+impl PooledBuffer {
+    fn slot(&self) -> &PoolSlot {
+        &self.shared.slots[self.index]
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        let slot = self.slot();
+        // SAFETY: the slot is not written while any reference is held.
+        unsafe {
+            let data = &*slot.data.get();
+            &data[..*slot.len.get()]
+        }
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Clone for PooledBuffer {
+    fn clone(&self) -> Self {
+        self.slot().refs.fetch_add(1, Ordering::Relaxed);
+        PooledBuffer {
+            shared: self.shared.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if self.slot().refs.fetch_sub(1, Ordering::Release) == 1 {
+            self.shared.in_use.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl fmt::Debug for PooledBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledBuffer")
+            .field("index", &self.index)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_at_most_buf_len_bytes() {
+        let pool = BufferPool::new(1, 3);
+        assert_eq!(&*pool.copy_from(&[1, 2]).unwrap(), [1, 2]);
+        assert_eq!(&*pool.copy_from(&[1, 2, 3, 4]).unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn recycles_a_slot_once_the_last_clone_is_dropped() {
+        let pool = BufferPool::new(2, 4);
+        let a = pool.copy_from(&[1]).unwrap();
+        let shared = a.clone();
+        let b = pool.copy_from(&[2]).unwrap();
+        assert!(pool.copy_from(&[3]).is_none());
+
+        drop(a);
+        // The clone still holds the slot, so its data must survive.
+        assert!(pool.copy_from(&[4]).is_none());
+        assert_eq!(&*shared, [1]);
+
+        drop(shared);
+        let c = pool.copy_from(&[5]).unwrap();
+        assert_eq!(&*c, [5]);
+        assert_eq!(&*b, [2]);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                capacity: 2,
+                buf_len: 4,
+                in_use: 2,
+                peak_in_use: 2,
+                acquired: 3,
+                starved: 2,
+            }
+        );
+
+        drop((b, c));
+        assert_eq!(pool.stats().in_use, 0);
+    }
+
+    #[test]
+    fn buffers_outlive_the_pool() {
+        let pool = BufferPool::new(1, 2);
+        let buf = pool.copy_from(&[7, 8]).unwrap();
+        drop(pool);
+        assert_eq!(&*buf, [7, 8]);
+    }
+
+    #[test]
+    fn shares_buffers_across_threads() {
+        let pool = BufferPool::new(4, 8);
+        let (tx, rx) = std::sync::mpsc::sync_channel::<PooledBuffer>(2);
+        let consumer = std::thread::spawn(move || {
+            let mut sum = 0u64;
+            for buf in rx {
+                assert!(buf.iter().all(|&b| b == buf[0]));
+                sum += buf[0] as u64;
+            }
+            sum
+        });
+        let mut sent = 0u64;
+        for i in 0..1000u32 {
+            let byte = (i % 251) as u8;
+            // Wait for the consumer instead of starving.
+            let buf = loop {
+                match pool.copy_from(&[byte; 8]) {
+                    Some(buf) => break buf,
+                    None => std::thread::yield_now(),
+                }
+            };
+            sent += byte as u64;
+            tx.send(buf).unwrap();
+        }
+        drop(tx);
+        assert_eq!(consumer.join().unwrap(), sent);
+        assert_eq!(pool.stats().in_use, 0);
+        assert!(pool.stats().peak_in_use <= 4);
+    }
+}
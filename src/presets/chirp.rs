@@ -0,0 +1,350 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use super::{Mode, Preset};
+use crate::error::{Error, Result};
+use crate::squelch::{DcsCode, Tone};
+
+/// The columns CHIRP writes, in its order.
+const COLUMNS: [&str; 21] = [
+    "Location",
+    "Name",
+    "Frequency",
+    "Duplex",
+    "Offset",
+    "Tone",
+    "rToneFreq",
+    "cToneFreq",
+    "DtcsCode",
+    "DtcsPolarity",
+    "RxDtcsCode",
+    "CrossMode",
+    "Mode",
+    "TStep",
+    "Skip",
+    "Power",
+    "Comment",
+    "URCALL",
+    "RPT1CALL",
+    "RPT2CALL",
+    "DVCODE",
+];
+
+/// Read a channel list exported by CHIRP.
+///
+/// Columns are found by their names in the header, so exports from any
+/// CHIRP version and files edited in a spreadsheet both load; only
+/// `Frequency` is required. The tone is the one the receive squelch opens
+/// on: `TSQL` and `DTCS` channels get theirs, `Tone` channels only send one
+/// and get none, and `Cross` channels get the receive half of their
+/// `CrossMode`. Transmit offsets are ignored, as only the receive frequency
+/// matters here.
+///
+/// # Arguments
+///
+/// * `reader` - The CSV file.
+///
+/// # Returns
+///
+/// The channels in file order if successful, otherwise an `Error`.
+/// `Error::InvalidPreset` is returned with the line number if a line cannot
+/// be parsed or uses a mode or tone setting CHIRP does not define.
+pub fn read_chirp_csv<R: Read>(reader: R) -> Result<Vec<Preset>> {
+    let mut columns: Option<Vec<String>> = None;
+    let mut presets = Vec::new();
+    for (n, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split(&line).map_err(|e| invalid(n, e))?;
+        match &columns {
+            None => {
+                if !fields.iter().any(|f| f == "Frequency") {
+                    return Err(invalid(n, "no Frequency column"));
+                }
+                columns = Some(fields);
+            }
+            Some(columns) => {
+                let row = Row { columns, fields };
+                presets.push(parse(&row).map_err(|e| invalid(n, &e))?);
+            }
+        }
+    }
+    Ok(presets)
+}
+
+/// One line of the file with its header.
+struct Row<'a> {
+    columns: &'a [String],
+    fields: Vec<String>,
+}
+
+impl Row<'_> {
+    /// Get a field by column name, empty if the column or field is missing.
+    fn get(&self, column: &str) -> &str {
+        self.columns
+            .iter()
+            .position(|c| c == column)
+            .and_then(|i| self.fields.get(i))
+            .map_or("", |f| f.trim())
+    }
+}
+
+fn parse(row: &Row) -> std::result::Result<Preset, String> {
+    let mhz: f64 = row
+        .get("Frequency")
+        .parse()
+        .map_err(|_| format!("bad frequency {:?}", row.get("Frequency")))?;
+    let hz = (mhz * 1e6).round();
+    if !(1.0..=u32::MAX as f64).contains(&hz) {
+        return Err(format!("frequency {} MHz out of range", mhz));
+    }
+    let mode = match row.get("Mode") {
+        "AM" => Mode::Am,
+        "NAM" => Mode::NarrowAm,
+        "FM" | "Auto" | "" => Mode::Fm,
+        "NFM" => Mode::NarrowFm,
+        "WFM" => Mode::WideFm,
+        "USB" => Mode::Usb,
+        "LSB" => Mode::Lsb,
+        "CW" | "CWR" | "NCW" | "NCWR" => Mode::Cw,
+        "DV" | "DN" | "DMR" | "P25" | "DIG" | "PKT" | "RTTY" | "RTTYR" | "FSK" | "FSKR" => {
+            Mode::Digital
+        }
+        other => return Err(format!("unknown mode {:?}", other)),
+    };
+    let ctcss = || {
+        row.get("cToneFreq")
+            .parse::<f32>()
+            .ok()
+            .filter(|hz| *hz > 0.0)
+            .map(Tone::Ctcss)
+            .ok_or_else(|| format!("bad cToneFreq {:?}", row.get("cToneFreq")))
+    };
+    // The second polarity letter is the receive one.
+    let dcs = |column: &str| {
+        let code = u16::from_str_radix(row.get(column), 8)
+            .ok()
+            .filter(|code| *code <= 0o777)
+            .ok_or_else(|| format!("bad {} {:?}", column, row.get(column)))?;
+        let inverted = row.get("DtcsPolarity").ends_with('R');
+        Ok::<_, String>(Tone::Dcs(DcsCode::new(code, inverted)))
+    };
+    let tone = match row.get("Tone") {
+        "" | "Tone" => None,
+        "TSQL" => Some(ctcss()?),
+        "DTCS" => Some(dcs("DtcsCode")?),
+        "Cross" => match row.get("CrossMode").split_once("->") {
+            Some((_, "Tone")) => Some(ctcss()?),
+            Some((_, "DTCS")) => Some(dcs("RxDtcsCode")?),
+            Some((_, "")) => None,
+            _ => return Err(format!("unknown cross mode {:?}", row.get("CrossMode"))),
+        },
+        other => return Err(format!("unknown tone mode {:?}", other)),
+    };
+    let step_hz = row
+        .get("TStep")
+        .parse::<f64>()
+        .ok()
+        .filter(|khz| *khz > 0.0)
+        .map(|khz| (khz * 1e3).round() as u32);
+    Ok(Preset {
+        name: row.get("Name").to_string(),
+        freq_hz: hz as u32,
+        mode,
+        tone,
+        step_hz,
+        // `P` is a priority channel, not a skipped one.
+        skip: row.get("Skip").eq_ignore_ascii_case("S"),
+        comment: row.get("Comment").to_string(),
+        filters: None,
+    })
+}
+
+/// Split a CSV line into fields, undoing quoting.
+fn split(line: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote");
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+fn invalid(line: usize, reason: &str) -> Error {
+    Error::InvalidPreset(format!("line {}: {}", line + 1, reason))
+}
+
+/// Write channels in CHIRP's CSV format, with every column CHIRP writes.
+///
+/// # Arguments
+///
+/// * `writer` - Where to write the file.
+/// * `presets` - The channels, numbered from 0 in the `Location` column.
+///
+/// # Returns
+///
+/// An `Ok` result if successful, otherwise an `Error`.
+pub fn write_chirp_csv<W: Write>(mut writer: W, presets: &[Preset]) -> Result<()> {
+    writeln!(writer, "{}", COLUMNS.join(","))?;
+    for (location, p) in presets.iter().enumerate() {
+        let (tone, ctcss, dcs) = match p.tone {
+            None => ("", 88.5, DcsCode::new(0o023, false)),
+            Some(Tone::Ctcss(hz)) => ("TSQL", hz, DcsCode::new(0o023, false)),
+            Some(Tone::Dcs(code)) => ("DTCS", 88.5, code),
+        };
+        let polarity = if dcs.inverted { "RR" } else { "NN" };
+        let step = p.step_hz.unwrap_or(5_000) as f64 / 1e3;
+        let row = [
+            location.to_string(),
+            quote(&p.name),
+            format!("{:.6}", p.freq_hz as f64 / 1e6),
+            String::new(),
+            "0.000000".to_string(),
+            tone.to_string(),
+            format!("{:.1}", ctcss),
+            format!("{:.1}", ctcss),
+            format!("{:03o}", dcs.code),
+            polarity.to_string(),
+            format!("{:03o}", dcs.code),
+            "Tone->Tone".to_string(),
+            p.mode.to_string(),
+            format!("{:.2}", step),
+            if p.skip { "S" } else { "" }.to_string(),
+            String::new(),
+            quote(&p.comment),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        ];
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a field if it holds a separator, a quote or a line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The start of an export from a Baofeng UV-5R, as CHIRP writes it.
+    const EXPORT: &str = "\
+Location,Name,Frequency,Duplex,Offset,Tone,rToneFreq,cToneFreq,DtcsCode,DtcsPolarity,RxDtcsCode,CrossMode,Mode,TStep,Skip,Power,Comment,URCALL,RPT1CALL,RPT2CALL,DVCODE
+0,CALL,146.520000,,0.600000,,88.5,88.5,023,NN,023,Tone->Tone,FM,5.00,,High,,,,,
+1,W1AW,145.490000,-,0.600000,Tone,100.0,88.5,023,NN,023,Tone->Tone,NFM,12.50,,High,,,,,
+2,\"Club, North\",147.060000,+,0.600000,TSQL,88.5,141.3,023,NN,023,Tone->Tone,NFM,12.50,S,Low,\"says \"\"hi\"\"\",,,,
+3,PMR1,446.006250,,0.000000,DTCS,88.5,88.5,754,NR,023,Tone->Tone,NFM,6.25,,Low,,,,,
+
+4,XBAND,439.000000,,0.000000,Cross,88.5,88.5,023,NN,131,Tone->DTCS,NFM,5.00,,High,,,,,
+5,AIR,118.100000,,0.000000,,88.5,88.5,023,NN,023,Tone->Tone,AM,8.33,P,High,,,,,
+";
+
+    #[test]
+    fn reads_a_chirp_export() {
+        let presets = read_chirp_csv(EXPORT.as_bytes()).unwrap();
+        assert_eq!(presets.len(), 6);
+
+        assert_eq!(
+            presets[0],
+            Preset::new("CALL", 146_520_000, Mode::Fm).with_step(5_000)
+        );
+        // A Tone channel only sends its tone, so it receives on carrier.
+        assert_eq!(presets[1].tone, None);
+        assert_eq!(presets[1].mode, Mode::NarrowFm);
+        assert_eq!(presets[2].name, "Club, North");
+        assert_eq!(presets[2].tone, Some(Tone::Ctcss(141.3)));
+        assert!(presets[2].skip);
+        assert_eq!(presets[2].comment, "says \"hi\"");
+        assert_eq!(presets[3].freq_hz, 446_006_250);
+        assert_eq!(presets[3].step_hz, Some(6_250));
+        assert_eq!(
+            presets[3].tone,
+            Some(Tone::Dcs(DcsCode::new(0o754, true))),
+            "the second polarity letter is the receive one"
+        );
+        assert_eq!(presets[4].tone, Some(Tone::Dcs(DcsCode::new(0o131, false))));
+        assert_eq!(presets[5].mode, Mode::Am);
+        assert!(!presets[5].skip, "P marks a priority channel");
+        assert_eq!(presets[5].step_hz, Some(8_330));
+        assert_eq!(
+            presets[2].to_string(),
+            "Club, North 147.06 MHz NFM 141.3 Hz"
+        );
+    }
+
+    #[test]
+    fn finds_columns_by_name() {
+        let csv = "Mode,Frequency,Name\nWFM,100.1,Radio One\n";
+        let presets = read_chirp_csv(csv.as_bytes()).unwrap();
+        assert_eq!(
+            presets,
+            [Preset::new("Radio One", 100_100_000, Mode::WideFm)]
+        );
+        assert!(read_chirp_csv("".as_bytes()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_its_own_output() {
+        let mut presets = read_chirp_csv(EXPORT.as_bytes()).unwrap();
+        presets.push(Preset::new("Marine 16", 156_800_000, Mode::Fm));
+        let mut csv = Vec::new();
+        write_chirp_csv(&mut csv, &presets).unwrap();
+        let text = String::from_utf8(csv).unwrap();
+        assert!(text.starts_with("Location,Name,Frequency,"));
+        assert!(text.contains("\n2,\"Club, North\",147.060000,"));
+        // Missing steps are written as CHIRP's default.
+        let mut expected = presets.clone();
+        expected[6].step_hz = Some(5_000);
+        assert_eq!(read_chirp_csv(text.as_bytes()).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_receive() {
+        let header = "Name,Frequency,Tone,cToneFreq,DtcsCode,CrossMode,Mode\n";
+        for (row, reason) in [
+            ("A,abc,,,,,FM", "line 2: bad frequency \"abc\""),
+            ("A,0,,,,,FM", "line 2: frequency 0 MHz out of range"),
+            ("A,145.5,,,,,C4FM", "line 2: unknown mode \"C4FM\""),
+            ("A,145.5,TSQL,,,,FM", "line 2: bad cToneFreq \"\""),
+            ("A,145.5,DTCS,,089,,FM", "line 2: bad DtcsCode \"089\""),
+            (
+                "A,145.5,Cross,,,DTCS->Tone->,FM",
+                "line 2: unknown cross mode",
+            ),
+            ("A,145.5,Beep,,,,FM", "line 2: unknown tone mode \"Beep\""),
+            ("\"A,145.5,,,,,FM", "line 2: unterminated quote"),
+        ] {
+            match read_chirp_csv(format!("{}{}\n", header, row).as_bytes()) {
+                Err(Error::InvalidPreset(e)) => assert!(e.starts_with(reason), "{}", e),
+                other => panic!("{:?} for {}", other, row),
+            }
+        }
+        assert!(matches!(
+            read_chirp_csv("Name,Freq\nA,1\n".as_bytes()),
+            Err(Error::InvalidPreset(e)) if e == "line 1: no Frequency column"
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+//! Channel presets: named frequencies with the mode and tone to receive
+//! them with.
+//!
+//! Presets are kept as lists of `Preset`s and stored in the CSV format
+//! CHIRP exports, so a radio's memories can be scanned as they are and
+//! lists built here can be loaded into a radio. `read_chirp_csv` and
+//! `write_chirp_csv` convert between the two, and `Preset::channel` gives
+//! the channel an `ActivityScanner` watches.
+//!
+//! Channel steps and modes differ between ITU regions: FM broadcast
+//! stations sit 100 kHz apart in Europe and 200 kHz apart in the Americas,
+//! European airband uses 8.33 kHz channels and marine duplex channels are
+//! worked simplex in the US. A `Region` holds its band plan of `Band`s, and
+//! gives presets and scanner channels that follow it.
+//!
+//! ```no_run
+//! use std::fs::File;
+//!
+//! use radion::presets::read_chirp_csv;
+//!
+//! for preset in read_chirp_csv(File::open("uv5r.csv")?)? {
+//!     // e.g. "Repeater 145.5 MHz NFM 88.5 Hz"
+//!     println!("{}", preset);
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod chirp;
+mod region;
+
+pub use chirp::{read_chirp_csv, write_chirp_csv};
+pub use region::{Band, Region};
+
+use std::fmt;
+
+use crate::demod::{AudioBand, AudioFilterConfig};
+use crate::squelch::Tone;
+use crate::units::Frequency;
+
+/// How a channel is demodulated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// AM with a 10 kHz passband, as on airband and shortwave broadcast.
+    Am,
+    /// AM with a 6 kHz passband, for 8.33 kHz airband channels.
+    NarrowAm,
+    /// FM with 5 kHz deviation on a 25 kHz channel.
+    Fm,
+    /// FM with 2.5 kHz deviation on a 12.5 kHz channel.
+    NarrowFm,
+    /// Broadcast FM on a 200 kHz channel.
+    WideFm,
+    /// Upper sideband.
+    Usb,
+    /// Lower sideband.
+    Lsb,
+    /// Morse code.
+    Cw,
+    /// A digital voice or data mode carried on a narrowband FM channel,
+    /// such as D-STAR, DMR, P25 or packet.
+    Digital,
+}
+
+impl Mode {
+    /// Get the width of the channel the mode occupies.
+    ///
+    /// # Returns
+    ///
+    /// The bandwidth in Hz.
+    pub fn bandwidth_hz(&self) -> u32 {
+        match self {
+            Mode::Am => 10_000,
+            Mode::NarrowAm => 6_000,
+            Mode::Fm => 25_000,
+            Mode::NarrowFm | Mode::Digital => 12_500,
+            Mode::WideFm => 200_000,
+            Mode::Usb | Mode::Lsb => 3_000,
+            Mode::Cw => 500,
+        }
+    }
+}
+
+/// Formats the mode as CHIRP names it, e.g. `NFM`.
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Am => "AM",
+            Mode::NarrowAm => "NAM",
+            Mode::Fm => "FM",
+            Mode::NarrowFm => "NFM",
+            Mode::WideFm => "WFM",
+            Mode::Usb => "USB",
+            Mode::Lsb => "LSB",
+            Mode::Cw => "CW",
+            Mode::Digital => "DIG",
+        })
+    }
+}
+
+/// A named channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    /// The channel's name, e.g. a repeater's callsign.
+    pub name: String,
+    /// The receive frequency in Hz.
+    pub freq_hz: u32,
+    /// How the channel is demodulated.
+    pub mode: Mode,
+    /// The tone or code the channel's squelch opens on, or `None` for
+    /// carrier squelch.
+    pub tone: Option<Tone>,
+    /// The tuning step in Hz the channel lies on, if known.
+    pub step_hz: Option<u32>,
+    /// Whether a scan passes over the channel.
+    pub skip: bool,
+    /// Free text kept with the channel.
+    pub comment: String,
+    /// The audio filters to run, or `None` for those suiting the mode and
+    /// tone. CHIRP files don't keep them.
+    pub filters: Option<AudioFilterConfig>,
+}
+
+impl Preset {
+    /// Create a preset with carrier squelch.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The channel's name.
+    /// * `freq_hz` - The receive frequency in Hz.
+    /// * `mode` - How the channel is demodulated.
+    ///
+    /// # Returns
+    ///
+    /// A new `Preset` without tone, step or comment.
+    pub fn new(name: &str, freq_hz: u32, mode: Mode) -> Self {
+        Preset {
+            name: name.to_string(),
+            freq_hz,
+            mode,
+            tone: None,
+            step_hz: None,
+            skip: false,
+            comment: String::new(),
+            filters: None,
+        }
+    }
+
+    /// Set the tone or code the squelch opens on.
+    ///
+    /// # Arguments
+    ///
+    /// * `tone` - The CTCSS tone or DCS code.
+    ///
+    /// # Returns
+    ///
+    /// The `Preset` with the tone.
+    pub fn with_tone(mut self, tone: Tone) -> Self {
+        self.tone = Some(tone);
+        self
+    }
+
+    /// Set the tuning step the channel lies on.
+    ///
+    /// # Arguments
+    ///
+    /// * `step_hz` - The step in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The `Preset` with the step.
+    pub fn with_step(mut self, step_hz: u32) -> Self {
+        self.step_hz = Some(step_hz);
+        self
+    }
+
+    /// Set the audio filters to run.
+    ///
+    /// # Arguments
+    ///
+    /// * `filters` - The filters.
+    ///
+    /// # Returns
+    ///
+    /// The `Preset` with the filters.
+    pub fn with_filters(mut self, filters: AudioFilterConfig) -> Self {
+        self.filters = Some(filters);
+        self
+    }
+
+    /// Get the tuning step the channel lies on.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The region whose band plan gives the step when the
+    ///   preset does not.
+    ///
+    /// # Returns
+    ///
+    /// The step in Hz, or `None` if neither the preset nor the band plan
+    /// gives one.
+    pub fn step_in(&self, region: Region) -> Option<u32> {
+        self.step_hz
+            .or_else(|| region.band(self.freq_hz as f64).map(|b| b.step_hz))
+    }
+
+    /// Get the audio filters to run on the channel.
+    ///
+    /// # Returns
+    ///
+    /// The filters set with `with_filters`, otherwise the voice band for
+    /// voice modes or the CW band for CW, with the tone removed where the
+    /// squelch uses one.
+    pub fn audio_filters(&self) -> AudioFilterConfig {
+        self.filters.unwrap_or_else(|| AudioFilterConfig {
+            tone_filter: self.tone.is_some(),
+            band: match self.mode {
+                Mode::Cw => Some(AudioBand::Cw {
+                    pitch_hz: 700.0,
+                    width_hz: 500.0,
+                }),
+                Mode::WideFm | Mode::Digital => None,
+                _ => Some(AudioBand::Voice),
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Get the channel to watch for activity.
+    ///
+    /// # Returns
+    ///
+    /// The channel centred on the frequency, as wide as the mode, with
+    /// the mode set where a scanner's classifier knows it, so it is not
+    /// classified again.
+    #[cfg(feature = "scan")]
+    pub fn channel(&self) -> crate::scan::Channel {
+        use crate::scan::{Channel, ChannelMode};
+        let channel = Channel::new(self.freq_hz, self.mode.bandwidth_hz());
+        match self.mode {
+            Mode::Am | Mode::NarrowAm => channel.with_mode(ChannelMode::Am),
+            Mode::Fm | Mode::NarrowFm => channel.with_mode(ChannelMode::NarrowFm),
+            Mode::Digital => channel.with_mode(ChannelMode::Data),
+            _ => channel,
+        }
+    }
+}
+
+/// Formats the preset as its name, frequency, mode and tone, e.g.
+/// `Repeater 145.5 MHz NFM 88.5 Hz`.
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.name,
+            Frequency(self.freq_hz as f64),
+            self.mode
+        )?;
+        if let Some(tone) = self.tone {
+            write!(f, " {}", tone)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::squelch::DcsCode;
+
+    #[test]
+    fn formats_presets() {
+        let preset = Preset::new("PMR1", 446_006_250, Mode::NarrowFm);
+        assert_eq!(preset.to_string(), "PMR1 446.00625 MHz NFM");
+        let preset = preset.with_tone(Tone::Dcs(DcsCode::new(0o023, false)));
+        assert_eq!(preset.to_string(), "PMR1 446.00625 MHz NFM D023N");
+    }
+
+    #[test]
+    fn picks_audio_filters_for_the_mode() {
+        let preset = Preset::new("PMR1", 446_006_250, Mode::NarrowFm);
+        assert_eq!(preset.audio_filters().band, Some(AudioBand::Voice));
+        assert!(!preset.audio_filters().tone_filter);
+        let preset = preset.with_tone(Tone::Ctcss(88.5));
+        assert!(preset.audio_filters().tone_filter);
+        let packet = Preset::new("APRS", 144_800_000, Mode::Digital);
+        assert_eq!(packet.audio_filters(), AudioFilterConfig::default());
+        let gated = AudioFilterConfig {
+            gate_db: Some(-40.0),
+            ..Default::default()
+        };
+        assert_eq!(packet.with_filters(gated).audio_filters(), gated);
+    }
+
+    #[cfg(feature = "scan")]
+    #[test]
+    fn watches_a_channel_as_wide_as_the_mode() {
+        let channel = Preset::new("AIR", 118_100_000, Mode::NarrowAm).channel();
+        assert_eq!(channel.centre_hz(), 118_100_000.0);
+        assert_eq!(channel.bandwidth_hz(), 6_000.0);
+        assert_eq!(channel.mode, Some(crate::scan::ChannelMode::Am));
+        let usb = Preset::new("HF", 14_300_000, Mode::Usb).channel();
+        assert_eq!(usb.mode, None);
+    }
+
+    #[test]
+    fn takes_missing_steps_from_the_band_plan() {
+        let preset = Preset::new("FM", 98_100_000, Mode::WideFm);
+        assert_eq!(preset.step_in(Region::One), Some(100_000));
+        assert_eq!(preset.step_in(Region::Two), Some(200_000));
+        assert_eq!(preset.with_step(50_000).step_in(Region::Two), Some(50_000));
+    }
+}
@@ -0,0 +1,423 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Mode, Preset};
+use crate::error::{Error, Result};
+use crate::units::Frequency;
+
+/// An ITU radio region, whose band plan sets the channel steps and modes
+/// in common bands.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// Region 1: Europe, Africa, the Middle East and northern Asia.
+    #[default]
+    One,
+    /// Region 2: the Americas.
+    Two,
+    /// Region 3: the rest of Asia and Oceania. Japan's FM broadcast band
+    /// starts at 76 MHz and is not covered.
+    Three,
+}
+
+/// A band of evenly spaced channels in a region's band plan.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Band {
+    /// The band's name, e.g. `FM broadcast`.
+    pub name: &'static str,
+    /// Centre of the first channel in Hz.
+    pub first_hz: u32,
+    /// Centre of the last channel in Hz.
+    pub last_hz: u32,
+    /// How the channels are demodulated.
+    pub mode: Mode,
+    /// The tuning step in Hz, as a radio names it, e.g. 8330 for 8.33 kHz
+    /// airband channels.
+    pub step_hz: u32,
+    /// The exact spacing as a fraction of Hz, numerator and denominator.
+    spacing: (u32, u32),
+}
+
+const fn band(name: &'static str, first_hz: u32, last_hz: u32, step_hz: u32, mode: Mode) -> Band {
+    Band {
+        name,
+        first_hz,
+        last_hz,
+        mode,
+        step_hz,
+        spacing: (step_hz, 1),
+    }
+}
+
+/// 8.33 kHz airband, three channels to every 25 kHz.
+const AIRBAND_833: Band = Band {
+    spacing: (25_000, 3),
+    ..band("Airband", 118_000_000, 136_991_667, 8_330, Mode::NarrowAm)
+};
+
+const MARINE: Band = band("Marine VHF", 156_000_000, 162_025_000, 25_000, Mode::Fm);
+
+const REGION_1: [Band; 8] = [
+    band("LW broadcast", 153_000, 279_000, 9_000, Mode::Am),
+    band("MW broadcast", 531_000, 1_602_000, 9_000, Mode::Am),
+    band(
+        "FM broadcast",
+        87_500_000,
+        108_000_000,
+        100_000,
+        Mode::WideFm,
+    ),
+    AIRBAND_833,
+    band(
+        "2 m amateur",
+        144_000_000,
+        146_000_000,
+        12_500,
+        Mode::NarrowFm,
+    ),
+    MARINE,
+    band(
+        "70 cm amateur",
+        430_000_000,
+        440_000_000,
+        12_500,
+        Mode::NarrowFm,
+    ),
+    band("PMR446", 446_006_250, 446_193_750, 12_500, Mode::NarrowFm),
+];
+
+const REGION_2: [Band; 7] = [
+    band("MW broadcast", 530_000, 1_700_000, 10_000, Mode::Am),
+    band(
+        "FM broadcast",
+        87_900_000,
+        107_900_000,
+        200_000,
+        Mode::WideFm,
+    ),
+    band("Airband", 118_000_000, 136_975_000, 25_000, Mode::Am),
+    band("2 m amateur", 144_000_000, 148_000_000, 5_000, Mode::Fm),
+    MARINE,
+    band("NOAA weather", 162_400_000, 162_550_000, 25_000, Mode::Fm),
+    band("70 cm amateur", 420_000_000, 450_000_000, 25_000, Mode::Fm),
+];
+
+const REGION_3: [Band; 6] = [
+    band("MW broadcast", 531_000, 1_602_000, 9_000, Mode::Am),
+    band(
+        "FM broadcast",
+        87_500_000,
+        108_000_000,
+        100_000,
+        Mode::WideFm,
+    ),
+    band("Airband", 118_000_000, 136_975_000, 25_000, Mode::Am),
+    band(
+        "2 m amateur",
+        144_000_000,
+        148_000_000,
+        12_500,
+        Mode::NarrowFm,
+    ),
+    MARINE,
+    band(
+        "70 cm amateur",
+        430_000_000,
+        440_000_000,
+        12_500,
+        Mode::NarrowFm,
+    ),
+];
+
+/// Marine channels worked simplex on the ship frequency everywhere.
+const MARINE_SIMPLEX: [u8; 24] = [
+    6, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 87, 88,
+];
+
+/// Marine duplex channels worked simplex on the ship frequency in the
+/// Americas, the US "A" channels.
+const MARINE_SIMPLEX_REGION_2: [u8; 20] = [
+    1, 3, 5, 7, 18, 19, 21, 22, 23, 61, 63, 64, 65, 66, 78, 79, 80, 81, 82, 83,
+];
+
+impl Band {
+    /// Get the number of channels in the band.
+    pub fn len(&self) -> usize {
+        self.index(self.last_hz) + 1
+    }
+
+    /// Check whether the band has no channels, which no band plan's has.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a channel's frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The channel, counted from 0 at `first_hz`.
+    ///
+    /// # Returns
+    ///
+    /// The centre in Hz, or `None` past the last channel.
+    pub fn channel(&self, index: usize) -> Option<u32> {
+        let (num, den) = (self.spacing.0 as u64, self.spacing.1 as u64);
+        let hz = self.first_hz as u64 + (index as u64 * num + den / 2) / den;
+        (hz <= self.last_hz as u64).then_some(hz as u32)
+    }
+
+    /// The channel nearest a frequency, clamped to the band.
+    fn index(&self, freq_hz: u32) -> usize {
+        let (num, den) = (self.spacing.0 as u64, self.spacing.1 as u64);
+        let offset = freq_hz.clamp(self.first_hz, self.last_hz) - self.first_hz;
+        ((offset as u64 * den + num / 2) / num) as usize
+    }
+
+    /// Check whether a frequency lies in the band, within half a channel
+    /// of its first and last channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq_hz` - The frequency in Hz.
+    pub fn contains(&self, freq_hz: f64) -> bool {
+        let half = self.spacing.0 as f64 / self.spacing.1 as f64 / 2.0;
+        freq_hz >= self.first_hz as f64 - half && freq_hz < self.last_hz as f64 + half
+    }
+
+    /// Get the channel nearest a frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq_hz` - The frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The channel's centre in Hz, the first or last channel for
+    /// frequencies outside the band.
+    pub fn snap(&self, freq_hz: f64) -> u32 {
+        let hz = freq_hz.round().clamp(0.0, u32::MAX as f64) as u32;
+        self.channel(self.index(hz)).unwrap_or(self.last_hz)
+    }
+
+    /// Get a preset for every channel, named after its frequency.
+    ///
+    /// # Returns
+    ///
+    /// The presets in the band's mode and step, lowest frequency first.
+    pub fn presets(&self) -> Vec<Preset> {
+        (0..self.len())
+            .filter_map(|i| self.channel(i))
+            .map(|hz| {
+                Preset::new(&Frequency(hz as f64).to_string(), hz, self.mode)
+                    .with_step(self.step_hz)
+            })
+            .collect()
+    }
+
+    /// Get every channel for an `ActivityScanner` to watch.
+    ///
+    /// # Returns
+    ///
+    /// The channels, as wide as the band's mode, lowest frequency first.
+    #[cfg(feature = "scan")]
+    pub fn channels(&self) -> Vec<crate::scan::Channel> {
+        self.presets().iter().map(Preset::channel).collect()
+    }
+}
+
+impl Region {
+    /// Get the region's band plan.
+    ///
+    /// # Returns
+    ///
+    /// The bands, which do not overlap, lowest frequency first.
+    pub fn bands(self) -> &'static [Band] {
+        match self {
+            Region::One => &REGION_1,
+            Region::Two => &REGION_2,
+            Region::Three => &REGION_3,
+        }
+    }
+
+    /// Find the band a frequency lies in.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq_hz` - The frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The band, or `None` if the band plan does not cover the frequency.
+    pub fn band(self, freq_hz: f64) -> Option<&'static Band> {
+        self.bands().iter().find(|b| b.contains(freq_hz))
+    }
+
+    /// Find a band by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name, ignoring case, e.g. `airband`.
+    ///
+    /// # Returns
+    ///
+    /// The band, or `None` if the region has no band of that name.
+    pub fn band_named(self, name: &str) -> Option<&'static Band> {
+        self.bands()
+            .iter()
+            .find(|b| b.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get a preset for a frequency in the mode and step of its band.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The channel's name.
+    /// * `freq_hz` - The frequency in Hz, moved to the nearest channel.
+    ///
+    /// # Returns
+    ///
+    /// A new `Preset`, or `None` if the band plan does not cover the
+    /// frequency.
+    pub fn preset(self, name: &str, freq_hz: f64) -> Option<Preset> {
+        let band = self.band(freq_hz)?;
+        Some(Preset::new(name, band.snap(freq_hz), band.mode).with_step(band.step_hz))
+    }
+
+    /// Get the frequency to listen to a marine VHF channel on.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel number, 1 to 28 or 60 to 88.
+    ///
+    /// # Returns
+    ///
+    /// The ship frequency for channels worked simplex in the region,
+    /// otherwise the coast station's frequency, in Hz, or `None` for
+    /// numbers that are not channels.
+    pub fn marine_channel(self, channel: u8) -> Option<u32> {
+        let ship_hz = match channel {
+            1..=28 => 156_000_000 + 50_000 * channel as u32,
+            60..=88 => 156_025_000 + 50_000 * (channel as u32 - 60),
+            _ => return None,
+        };
+        let simplex = MARINE_SIMPLEX.contains(&channel)
+            || self == Region::Two && MARINE_SIMPLEX_REGION_2.contains(&channel);
+        Some(if simplex {
+            ship_hz
+        } else {
+            ship_hz + 4_600_000
+        })
+    }
+
+    /// Get a preset for every marine VHF channel.
+    ///
+    /// # Returns
+    ///
+    /// The channels as `marine_channel` gives them, named e.g. `CH16`, in
+    /// channel order.
+    pub fn marine_presets(self) -> Vec<Preset> {
+        (1..=28)
+            .chain(60..=88)
+            .filter_map(|ch| {
+                let hz = self.marine_channel(ch)?;
+                Some(Preset::new(&format!("CH{:02}", ch), hz, Mode::Fm).with_step(25_000))
+            })
+            .collect()
+    }
+}
+
+/// Formats the region as e.g. `ITU Region 1`.
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = match self {
+            Region::One => 1,
+            Region::Two => 2,
+            Region::Three => 3,
+        };
+        write!(f, "ITU Region {}", n)
+    }
+}
+
+/// Parses a region from its number, with or without an `R` or `ITU Region`
+/// before it, e.g. `2`, `R2` or `ITU Region 2`.
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_ascii_lowercase();
+        let n = lower
+            .strip_prefix("itu region")
+            .or_else(|| lower.strip_prefix("region"))
+            .or_else(|| lower.strip_prefix('r'))
+            .unwrap_or(&lower);
+        match n.trim() {
+            "1" => Ok(Region::One),
+            "2" => Ok(Region::Two),
+            "3" => Ok(Region::Three),
+            _ => Err(Error::InvalidPreset(format!("unknown ITU region {:?}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_follow_the_region() {
+        let fm = |region: Region| region.band(98_000_000.0).unwrap();
+        assert_eq!(fm(Region::One).step_hz, 100_000);
+        assert_eq!(fm(Region::Two).step_hz, 200_000);
+        assert_eq!(fm(Region::Two).snap(97_980_000.0), 97_900_000);
+        assert_eq!(fm(Region::One).len(), 206);
+        assert_eq!(fm(Region::Two).len(), 101);
+        assert_eq!(Region::One.band(1_000_000.0).unwrap().step_hz, 9_000);
+        assert_eq!(Region::Two.band(1_000_000.0).unwrap().step_hz, 10_000);
+        assert!(Region::One.band(50_000_000.0).is_none());
+        for region in [Region::One, Region::Two, Region::Three] {
+            for pair in region.bands().windows(2) {
+                assert!(pair[0].last_hz < pair[1].first_hz);
+            }
+        }
+    }
+
+    #[test]
+    fn lays_out_833_khz_airband() {
+        let air = Region::One.band_named("airband").unwrap();
+        assert_eq!(air.len(), 2280);
+        assert_eq!(air.channel(1), Some(118_008_333));
+        assert_eq!(air.channel(2), Some(118_016_667));
+        assert_eq!(air.channel(3), Some(118_025_000));
+        assert_eq!(air.channel(2279), Some(136_991_667));
+        assert_eq!(air.channel(2280), None);
+        assert_eq!(air.snap(118_010_000.0), 118_008_333);
+        let preset = Region::One.preset("Tower", 118_101_000.0).unwrap();
+        assert_eq!(
+            (preset.freq_hz, preset.mode, preset.step_hz),
+            (118_100_000, Mode::NarrowAm, Some(8_330))
+        );
+        let preset = Region::Two.preset("Tower", 118_101_000.0).unwrap();
+        assert_eq!((preset.mode, preset.step_hz), (Mode::Am, Some(25_000)));
+    }
+
+    #[test]
+    fn tables_marine_channels() {
+        assert_eq!(Region::One.marine_channel(16), Some(156_800_000));
+        assert_eq!(Region::One.marine_channel(1), Some(160_650_000));
+        assert_eq!(Region::Two.marine_channel(1), Some(156_050_000));
+        assert_eq!(Region::One.marine_channel(60), Some(160_625_000));
+        assert_eq!(Region::Two.marine_channel(70), Some(156_525_000));
+        assert_eq!(Region::One.marine_channel(29), None);
+        let presets = Region::Two.marine_presets();
+        assert_eq!(presets.len(), 57);
+        assert_eq!(presets[15].name, "CH16");
+    }
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!("2".parse::<Region>().unwrap(), Region::Two);
+        assert_eq!("r3".parse::<Region>().unwrap(), Region::Three);
+        let region: Region = Region::One.to_string().parse().unwrap();
+        assert_eq!(region, Region::One);
+        assert!("4".parse::<Region>().is_err());
+    }
+}
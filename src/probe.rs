@@ -0,0 +1,106 @@
+use crate::device::Device;
+use crate::error::Result;
+use crate::sym;
+use crate::tuner::{RTLSDRTuner, SamplingMode};
+use std::time::{Duration, Instant};
+
+/// Sample rates tried by `Device::probe`, highest first.
+const PROBE_RATES: [u32; 6] = [
+    3_200_000, 2_880_000, 2_560_000, 2_400_000, 2_048_000, 1_024_000,
+];
+
+/// How long to stream at each rate while probing.
+const PROBE_DURATION: Duration = Duration::from_millis(250);
+
+/// Fraction of the nominal throughput a rate has to reach to count as stable.
+const PROBE_MIN_THROUGHPUT: f64 = 0.95;
+
+/// Capabilities found by `Device::probe`.
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    /// The tuner type, if librtlsdr recognises it.
+    pub tuner: Option<RTLSDRTuner>,
+    /// The tuner's gain steps in tenths of a dB.
+    pub gains: Vec<i32>,
+    /// The crystal frequencies as `(rtl_freq_hz, tuner_freq_hz)`.
+    pub xtal_freq: Option<(u32, u32)>,
+    /// Whether the tuner's filter bandwidth can be set. This comes from the
+    /// tuner type: librtlsdr accepts `set_tuner_bandwidth` for every tuner
+    /// but only programs the filter of the E4000, R820T and R828D.
+    pub bandwidth_control: bool,
+    /// Whether the loaded librtlsdr exports `rtlsdr_set_bias_tee`.
+    pub bias_tee: bool,
+    /// Whether direct sampling can be enabled, or `None` if it was off and
+    /// so not tried: turning it off again re-initialises the tuner, which
+    /// loses the gain and gain mode.
+    pub direct_sampling: Option<bool>,
+    /// Whether the sample rate test ran. It needs a rate to go back to, so
+    /// it is skipped if no sample rate was set.
+    pub rate_tested: bool,
+    /// The highest sample rate that streamed without losing data, if the
+    /// test ran and any did.
+    pub max_stable_sample_rate: Option<u32>,
+}
+
+impl Device {
+    /// Probe the capabilities of the device.
+    ///
+    /// The checks are non-destructive: the only setting changed is the
+    /// sample rate, which is restored afterwards, and checks that could not
+    /// be undone are skipped. The sample rate test streams briefly at every
+    /// candidate rate, so the whole probe takes a couple of seconds; set a
+    /// sample rate first for it to run.
+    ///
+    /// # Returns
+    ///
+    /// A `ProbeReport` describing the device, or an `Error` if the device
+    /// could not be restored to its previous state.
+    pub fn probe(&self) -> Result<ProbeReport> {
+        let tuner = self.get_tuner_type().ok();
+        let gains = self.get_tuner_gains().unwrap_or_default();
+        let xtal_freq = self.get_xtal_freq().ok();
+        let bandwidth_control = matches!(
+            tuner,
+            Some(RTLSDRTuner::E4000 | RTLSDRTuner::R820T | RTLSDRTuner::R828D)
+        );
+        let bias_tee = sym::lookup(c"rtlsdr_set_bias_tee").is_some();
+        let direct_sampling = (self.get_direct_sampling()? != SamplingMode::None).then_some(true);
+
+        let rate = self.get_sample_rate()?;
+        let rate_tested = rate > 0;
+        let mut max_stable_sample_rate = None;
+        if rate_tested {
+            max_stable_sample_rate = PROBE_RATES
+                .iter()
+                .copied()
+                .find(|&r| self.sample_rate_is_stable(r));
+            self.set_sample_rate(rate)?;
+            self.reset_buffer()?;
+        }
+
+        Ok(ProbeReport {
+            tuner,
+            gains,
+            xtal_freq,
+            bandwidth_control,
+            bias_tee,
+            direct_sampling,
+            rate_tested,
+            max_stable_sample_rate,
+        })
+    }
+
+    fn sample_rate_is_stable(&self, rate_hz: u32) -> bool {
+        if self.set_sample_rate(rate_hz).is_err() || self.reset_buffer().is_err() {
+            return false;
+        }
+        // Two bytes per sample, rounded down to whole USB transfers.
+        let bytes = (rate_hz as f64 * 2.0 * PROBE_DURATION.as_secs_f64()) as usize / 512 * 512;
+        let start = Instant::now();
+        if self.read_sync_exact(bytes).is_err() {
+            return false;
+        }
+        let throughput = bytes as f64 / 2.0 / start.elapsed().as_secs_f64();
+        throughput >= rate_hz as f64 * PROBE_MIN_THROUGHPUT
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What produced a recording or an event: the radion version, the version
+/// of every decoder built in, and optionally a hash of the pipeline's
+/// configuration.
+///
+/// Kept with SigMF recordings and written at the start of event logs, so
+/// datasets collected over months can be traced back to the code and
+/// settings that made them, and differences between versions told apart
+/// from differences in the signal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "events", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Provenance {
+    /// The radion version, e.g. `0.5.0`.
+    pub radion: String,
+    /// The version of each decoder, by name, e.g. `adsb`. A decoder's
+    /// version goes up whenever a change could make it decode the same
+    /// input differently.
+    pub decoders: BTreeMap<String, u32>,
+    /// The hash of the pipeline configuration given to `with_config`, as 16
+    /// hex digits.
+    #[cfg_attr(
+        feature = "events",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub config_hash: Option<String>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance::current()
+    }
+}
+
+impl Provenance {
+    /// Get the provenance of this build.
+    ///
+    /// # Returns
+    ///
+    /// The radion version and the decoders built in, without a
+    /// configuration hash.
+    pub fn current() -> Self {
+        #[allow(unused_mut)]
+        let mut decoders = BTreeMap::new();
+        #[cfg(feature = "adsb")]
+        decoders.insert("adsb".to_string(), crate::decoders::adsb::VERSION);
+        #[cfg(feature = "ais")]
+        decoders.insert("ais".to_string(), crate::decoders::ais::VERSION);
+        #[cfg(feature = "aprs")]
+        decoders.insert("aprs".to_string(), crate::decoders::aprs::VERSION);
+        #[cfg(feature = "apt")]
+        decoders.insert("apt".to_string(), crate::decoders::apt::VERSION);
+        #[cfg(feature = "ism")]
+        decoders.insert("ism".to_string(), crate::decoders::ism::VERSION);
+        #[cfg(feature = "rds")]
+        decoders.insert("rds".to_string(), crate::rds::VERSION);
+        #[cfg(feature = "timecode")]
+        decoders.insert("timecode".to_string(), crate::decoders::timecode::VERSION);
+        Provenance {
+            radion: env!("CARGO_PKG_VERSION").to_string(),
+            decoders,
+            config_hash: None,
+        }
+    }
+
+    /// Record the pipeline configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration in any stable form, such as a
+    ///   configuration file's contents or the command line.
+    ///
+    /// # Returns
+    ///
+    /// The `Provenance` with the configuration's 64-bit FNV-1a hash, which
+    /// stays the same across platforms and Rust versions.
+    pub fn with_config(mut self, config: impl AsRef<[u8]>) -> Self {
+        self.config_hash = Some(format!("{:016x}", fnv1a(config.as_ref())));
+        self
+    }
+}
+
+/// Formats the provenance on one line, e.g.
+/// `radion 0.5.0 (adsb 1, ais 1) config 9f1c0a55e2b4d7c3`.
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "radion {}", self.radion)?;
+        if !self.decoders.is_empty() {
+            let decoders: Vec<String> = self
+                .decoders
+                .iter()
+                .map(|(name, version)| format!("{} {}", name, version))
+                .collect();
+            write!(f, " ({})", decoders.join(", "))?;
+        }
+        if let Some(hash) = &self.config_hash {
+            write!(f, " config {}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_this_build() {
+        let p = Provenance::current();
+        assert_eq!(p.radion, env!("CARGO_PKG_VERSION"));
+        #[cfg(feature = "adsb")]
+        assert_eq!(p.decoders["adsb"], crate::decoders::adsb::VERSION);
+        assert_eq!(p.config_hash, None);
+        assert!(p.to_string().starts_with(&format!("radion {}", p.radion)));
+    }
+
+    #[test]
+    fn hashes_the_configuration() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let mut p = Provenance::current().with_config("--gain 40 --ppm 1");
+        assert_eq!(p.config_hash.as_deref().map(str::len), Some(16));
+        assert_ne!(p, Provenance::current().with_config("--gain 40 --ppm 2"));
+        p.decoders = [("ais".to_string(), 2)].into_iter().collect();
+        p.radion = "0.5.0".into();
+        p.config_hash = Some("0123456789abcdef".into());
+        assert_eq!(
+            p.to_string(),
+            "radion 0.5.0 (ais 2) config 0123456789abcdef"
+        );
+    }
+}
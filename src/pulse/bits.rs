@@ -0,0 +1,208 @@
+use std::fmt;
+
+/// A row of bits sliced from a pulse train, first received first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bits {
+    bits: Vec<bool>,
+}
+
+impl Bits {
+    /// Create an empty row.
+    pub fn new() -> Self {
+        Bits::default()
+    }
+
+    /// Get the number of bits.
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Check whether the row has no bits.
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Append a bit.
+    ///
+    /// # Arguments
+    ///
+    /// * `bit` - The bit to append.
+    pub fn push(&mut self, bit: bool) {
+        self.bits.push(bit);
+    }
+
+    /// Get one bit.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The bit's position from the start of the row.
+    ///
+    /// # Returns
+    ///
+    /// The bit, or `None` past the end of the row.
+    pub fn get(&self, index: usize) -> Option<bool> {
+        self.bits.get(index).copied()
+    }
+
+    /// Read a field as an unsigned integer, most significant bit first.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The position of the field's first bit.
+    /// * `len` - The field's width in bits, at most 64.
+    ///
+    /// # Returns
+    ///
+    /// The field's value, or `None` if it runs past the end of the row.
+    pub fn read(&self, start: usize, len: usize) -> Option<u64> {
+        assert!(len <= 64, "fields are at most 64 bits wide");
+        let field = self.bits.get(start..start.checked_add(len)?)?;
+        Some(field.iter().fold(0, |acc, &b| (acc << 1) | b as u64))
+    }
+
+    /// Find the first occurrence of a pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The bits to look for, e.g. a preamble or sync word.
+    /// * `from` - The position to start searching at.
+    ///
+    /// # Returns
+    ///
+    /// The position of the pattern's first bit, or `None` if not found.
+    pub fn find(&self, pattern: &[bool], from: usize) -> Option<usize> {
+        if pattern.is_empty() {
+            return (from <= self.len()).then_some(from);
+        }
+        self.bits
+            .get(from..)?
+            .windows(pattern.len())
+            .position(|w| w == pattern)
+            .map(|i| i + from)
+    }
+
+    /// Pack the row into bytes, most significant bit first. The last byte
+    /// is padded with zeros.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bits
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (i, &b)| acc | (b as u8) << (7 - i))
+            })
+            .collect()
+    }
+
+    /// Get the row with every bit flipped, for devices sending active-low
+    /// data.
+    pub fn inverted(&self) -> Bits {
+        Bits {
+            bits: self.bits.iter().map(|&b| !b).collect(),
+        }
+    }
+
+    /// Get the row without its first bits.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The number of bits to skip.
+    ///
+    /// # Returns
+    ///
+    /// The remaining bits, empty if `start` is past the end.
+    pub fn skip(&self, start: usize) -> Bits {
+        Bits {
+            bits: self.bits.get(start..).unwrap_or_default().to_vec(),
+        }
+    }
+
+    /// Get the bits as a slice.
+    pub fn as_slice(&self) -> &[bool] {
+        &self.bits
+    }
+}
+
+impl From<Vec<bool>> for Bits {
+    fn from(bits: Vec<bool>) -> Self {
+        Bits { bits }
+    }
+}
+
+impl Extend<bool> for Bits {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        self.bits.extend(iter);
+    }
+}
+
+impl FromIterator<bool> for Bits {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        Bits {
+            bits: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Formats the row as hex, followed by its length in bits when that is not
+/// a whole number of bytes, e.g. `a5f0 {13}`.
+impl fmt::Display for Bits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        if !self.len().is_multiple_of(8) {
+            write!(f, " {{{}}}", self.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(s: &str) -> Bits {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn reads_fields_msb_first() {
+        let row = bits("1010010111");
+        assert_eq!(row.read(0, 8), Some(0xa5));
+        assert_eq!(row.read(6, 4), Some(0b0111));
+        assert_eq!(row.read(7, 4), None);
+        assert_eq!(row.read(10, 0), Some(0));
+        assert_eq!(row.get(2), Some(true));
+        assert_eq!(row.get(10), None);
+    }
+
+    #[test]
+    fn finds_patterns() {
+        let row = bits("0010110110");
+        assert_eq!(row.find(&[true, true, false], 0), Some(4));
+        assert_eq!(row.find(&[true, true, false], 5), Some(7));
+        assert_eq!(row.find(&[true, true, true], 0), None);
+        assert_eq!(row.find(&[], 3), Some(3));
+        assert_eq!(row.find(&[], 11), None);
+        assert_eq!(row.find(&[true], 11), None);
+    }
+
+    #[test]
+    fn packs_and_formats_bytes() {
+        let row = bits("1010010111110000111");
+        assert_eq!(row.to_bytes(), [0xa5, 0xf0, 0xe0]);
+        assert_eq!(row.to_string(), "a5f0e0 {19}");
+        assert_eq!(bits("1010010111110000").to_string(), "a5f0");
+        assert_eq!(Bits::new().to_string(), "");
+    }
+
+    #[test]
+    fn inverts_and_skips() {
+        let row = bits("110100");
+        assert_eq!(row.inverted(), bits("001011"));
+        assert_eq!(row.skip(2), bits("0100"));
+        assert!(row.skip(7).is_empty());
+        assert_eq!(row.skip(2).as_slice(), [false, true, false, false]);
+    }
+}
@@ -0,0 +1,572 @@
+use crate::dsp::Complex;
+
+/// Carrier samples to wait before seeding the FSK tones.
+const SETTLE: u32 = 4;
+
+/// Samples a change between pulse and gap must hold to count.
+const DEBOUNCE: u64 = 2;
+
+/// How a burst carries its data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Modulation {
+    /// On-off keying: a pulse is the carrier being on.
+    Ook,
+    /// Two-level frequency shift keying: a pulse is the carrier sitting on
+    /// the higher of its two frequencies.
+    Fsk,
+}
+
+/// One pulse and the gap after it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pulse {
+    /// How long the carrier was on, or on the higher frequency for FSK, in
+    /// microseconds.
+    pub width: f64,
+    /// How long until the next pulse, in microseconds. For the last pulse
+    /// of a train this is the silence that ended the burst.
+    pub gap: f64,
+}
+
+/// The pulses of one burst.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PulseTrain {
+    /// How the pulses were detected.
+    pub modulation: Modulation,
+    /// The pulses, in order.
+    pub pulses: Vec<Pulse>,
+    /// The index of the burst's first sample since the detector was
+    /// created or reset.
+    pub start: u64,
+    /// The sample rate the burst was detected at, in Hz.
+    pub sample_rate: f64,
+    /// The carrier level during the burst, in dB relative to a full-scale
+    /// sample.
+    pub level: f32,
+    /// The noise floor before the burst, in the same dB.
+    pub noise: f32,
+}
+
+impl PulseTrain {
+    /// Get the signal-to-noise ratio of the burst in dB.
+    pub fn snr(&self) -> f32 {
+        self.level - self.noise
+    }
+
+    /// Get the length of the burst in microseconds, without the silence
+    /// after the last pulse.
+    pub fn duration(&self) -> f64 {
+        let total: f64 = self.pulses.iter().map(|p| p.width + p.gap).sum();
+        total - self.pulses.last().map_or(0.0, |p| p.gap)
+    }
+}
+
+/// Finds bursts of pulses in baseband.
+///
+/// The envelope is compared against a threshold halfway between the noise
+/// floor, tracked while the band is quiet, and the level of the current
+/// burst, tracked while the carrier is on, with some hysteresis. This
+/// follows signals from the noise floor to full scale without setting a
+/// level by hand. A burst starts when the envelope rises the minimum SNR
+/// above the noise floor and ends after the reset gap passes without a
+/// pulse.
+///
+/// For FSK the envelope only marks where the burst is; within it, the
+/// instantaneous frequency is compared against a threshold halfway between
+/// the two tones, tracked the same way.
+pub struct PulseDetector {
+    modulation: Modulation,
+    sample_rate: f64,
+    /// Amplitude ratio over the noise floor that starts a burst.
+    trigger: f32,
+    /// Silence in samples that ends a burst.
+    reset_gap: u64,
+    max_pulses: usize,
+    /// Envelope and frequency smoothing factor.
+    alpha: f32,
+    /// Noise floor smoothing factor.
+    noise_alpha: f32,
+    /// Burst level and FSK tone smoothing factor.
+    level_alpha: f32,
+    envelope: f32,
+    freq: f32,
+    last: Complex<f32>,
+    noise: f32,
+    level: f32,
+    /// The two FSK tones in radians per sample.
+    mark: f32,
+    space: f32,
+    /// Carrier samples seen before the tones were seeded.
+    settle: u32,
+    /// Whether a burst is in progress, the carrier is on and a pulse is
+    /// being measured.
+    in_burst: bool,
+    carrier: bool,
+    high: bool,
+    /// Samples in the current state.
+    count: u64,
+    /// Samples the new state has held before the change counts.
+    pending: u64,
+    width: u64,
+    /// Samples seen since creation or reset.
+    position: u64,
+    train: PulseTrain,
+}
+
+impl PulseDetector {
+    /// Create a detector with a minimum SNR of 9 dB and a reset gap of
+    /// 10 ms.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The input sample rate in Hz. 250 kHz is common for
+    ///   ISM-band work, though lower rates do for slow devices.
+    /// * `modulation` - How the bursts carry data.
+    ///
+    /// # Returns
+    ///
+    /// A new `PulseDetector`.
+    pub fn new(sample_rate: f64, modulation: Modulation) -> Self {
+        PulseDetector {
+            modulation,
+            sample_rate,
+            trigger: 0.0,
+            reset_gap: 0,
+            max_pulses: 1024,
+            // Smooth over a few samples; the rise and fall are delayed
+            // alike, so widths are preserved.
+            alpha: 0.5,
+            // Noise floor over about 10 ms, burst level over about 100 µs.
+            noise_alpha: (1.0 / (0.01 * sample_rate)).min(1.0) as f32,
+            level_alpha: (1.0 / (1e-4 * sample_rate)).min(1.0) as f32,
+            envelope: 0.0,
+            freq: 0.0,
+            last: Complex::new(0.0, 0.0),
+            noise: 0.0,
+            level: 0.0,
+            mark: 0.0,
+            space: 0.0,
+            settle: 0,
+            in_burst: false,
+            carrier: false,
+            high: false,
+            count: 0,
+            pending: 0,
+            width: 0,
+            position: 0,
+            train: PulseTrain {
+                modulation,
+                pulses: Vec::new(),
+                start: 0,
+                sample_rate,
+                level: 0.0,
+                noise: 0.0,
+            },
+        }
+        .with_min_snr(9.0)
+        .with_reset_gap(10_000.0)
+    }
+
+    /// Set how far above the noise floor a burst must rise.
+    ///
+    /// # Arguments
+    ///
+    /// * `snr` - The minimum SNR in dB.
+    ///
+    /// # Returns
+    ///
+    /// The `PulseDetector` with the new threshold.
+    pub fn with_min_snr(mut self, snr: f32) -> Self {
+        self.trigger = 10f32.powf(snr / 20.0);
+        self
+    }
+
+    /// Set how long a silence ends a burst.
+    ///
+    /// # Arguments
+    ///
+    /// * `gap` - The silence in microseconds. It must be longer than any
+    ///   gap within the device's transmissions.
+    ///
+    /// # Returns
+    ///
+    /// The `PulseDetector` with the new gap.
+    pub fn with_reset_gap(mut self, gap: f64) -> Self {
+        self.reset_gap = ((gap * 1e-6 * self.sample_rate) as u64).max(1);
+        self
+    }
+
+    /// Set the most pulses a train may hold. Longer bursts are split.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum number of pulses, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `PulseDetector` with the new limit.
+    pub fn with_max_pulses(mut self, max: usize) -> Self {
+        self.max_pulses = max.max(1);
+        self
+    }
+
+    /// Get the modulation the detector looks for.
+    pub fn modulation(&self) -> Modulation {
+        self.modulation
+    }
+
+    /// Get the tracked noise floor in dB relative to a full-scale sample.
+    pub fn noise_floor(&self) -> f32 {
+        to_db(self.noise)
+    }
+
+    /// Check whether a burst is in progress.
+    pub fn in_burst(&self) -> bool {
+        self.in_burst
+    }
+
+    /// Detect pulses in a chunk of baseband.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The next chunk of the stream, of any length.
+    ///
+    /// # Returns
+    ///
+    /// Every burst completed in this chunk. A burst still in progress is
+    /// returned by a later call.
+    pub fn process(&mut self, input: &[Complex<f32>]) -> Vec<PulseTrain> {
+        let mut trains = Vec::new();
+        for &x in input {
+            if let Some(train) = self.step(x) {
+                trains.push(train);
+            }
+        }
+        trains
+    }
+
+    /// Forget the noise floor and any burst in progress.
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.freq = 0.0;
+        self.last = Complex::new(0.0, 0.0);
+        self.noise = 0.0;
+        self.in_burst = false;
+        self.carrier = false;
+        self.high = false;
+        self.count = 0;
+        self.pending = 0;
+        self.position = 0;
+        self.train.pulses.clear();
+    }
+
+    fn step(&mut self, x: Complex<f32>) -> Option<PulseTrain> {
+        self.envelope += self.alpha * (x.norm() - self.envelope);
+        let turn = (x * self.last.conj()).arg();
+        self.freq += self.alpha * (turn - self.freq);
+        self.last = x;
+        self.position += 1;
+
+        if !self.in_burst {
+            // Let the noise floor settle before triggering on anything.
+            let floor = self.noise.max(f32::MIN_POSITIVE);
+            if self.envelope > floor * self.trigger && self.position > self.reset_gap {
+                self.start_burst();
+            } else {
+                let alpha = if self.position <= self.reset_gap {
+                    1.0 / self.position as f32
+                } else {
+                    self.noise_alpha
+                };
+                self.noise += alpha * (self.envelope - self.noise);
+                return None;
+            }
+        }
+
+        // Carrier detection with hysteresis around the midpoint.
+        let mid = 0.5 * (self.noise + self.level);
+        let margin = 0.1 * (self.level - self.noise);
+        if self.carrier && self.envelope < mid - margin {
+            self.carrier = false;
+        } else if !self.carrier && self.envelope > mid + margin {
+            self.carrier = true;
+        }
+        if self.carrier {
+            self.level += self.level_alpha * (self.envelope - self.level);
+        }
+
+        let high = match self.modulation {
+            Modulation::Ook => self.carrier,
+            Modulation::Fsk => self.carrier && self.fsk_high(),
+        };
+
+        // A change of state counts once it has held for a few samples, so
+        // glitches at the edges of a burst do not split pulses.
+        self.count += 1;
+        if high == self.high {
+            self.pending = 0;
+        } else {
+            self.pending += 1;
+        }
+        if self.pending >= DEBOUNCE {
+            let run = self.count - self.pending;
+            if self.high {
+                self.width = run;
+            } else if self.width > 0 {
+                self.push_pulse(run);
+            }
+            self.high = high;
+            self.count = self.pending;
+            self.pending = 0;
+        }
+
+        if !self.high && self.count >= self.reset_gap {
+            if self.width > 0 {
+                self.push_pulse(self.count);
+            }
+            return self.end_burst();
+        }
+        if self.train.pulses.len() >= self.max_pulses {
+            let train = self.end_burst();
+            self.start_burst();
+            return train;
+        }
+        None
+    }
+
+    /// Compare the frequency against the midpoint of the two tones. Each
+    /// tone follows the frequency quickly past it and slowly back, so they
+    /// settle on the extremes and survive long runs of one tone.
+    fn fsk_high(&mut self) -> bool {
+        if self.settle < SETTLE {
+            // Seed the tones once the frequency estimate has caught up.
+            self.settle += 1;
+            self.mark = self.freq;
+            self.space = self.freq;
+            return false;
+        }
+        let mid = 0.5 * (self.mark + self.space);
+        let margin = 0.1 * (self.mark - self.space);
+        let high = if self.high {
+            self.freq > mid - margin
+        } else {
+            self.freq > mid + margin
+        };
+        let (fast, slow) = (self.level_alpha, self.level_alpha / 64.0);
+        self.mark += if self.freq > self.mark { fast } else { slow } * (self.freq - self.mark);
+        self.space += if self.freq < self.space { fast } else { slow } * (self.freq - self.space);
+        high
+    }
+
+    fn start_burst(&mut self) {
+        self.in_burst = true;
+        self.level = self.envelope;
+        self.carrier = false;
+        self.high = false;
+        self.count = 0;
+        self.pending = 0;
+        self.width = 0;
+        self.settle = 0;
+        self.train.start = self.position - 1;
+        self.train.noise = to_db(self.noise);
+    }
+
+    fn push_pulse(&mut self, gap: u64) {
+        let us = 1e6 / self.sample_rate;
+        self.train.pulses.push(Pulse {
+            width: self.width as f64 * us,
+            gap: gap as f64 * us,
+        });
+        self.width = 0;
+    }
+
+    fn end_burst(&mut self) -> Option<PulseTrain> {
+        self.in_burst = false;
+        self.carrier = false;
+        self.high = false;
+        self.count = 0;
+        self.pending = 0;
+        self.width = 0;
+        if self.train.pulses.is_empty() {
+            return None;
+        }
+        self.train.level = to_db(self.level);
+        let pulses = std::mem::take(&mut self.train.pulses);
+        Some(PulseTrain {
+            pulses,
+            ..self.train.clone()
+        })
+    }
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-9).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulse::{Bits, Slicer};
+    use std::f32::consts::TAU;
+
+    const RATE: f64 = 250_000.0;
+
+    /// Baseband of a transmitter at `level` over noise `noise`, from runs
+    /// of (tone in Hz or `None` for off, duration in µs).
+    fn baseband(runs: &[(Option<f32>, f64)], level: f32, noise: f32) -> Vec<Complex<f32>> {
+        let mut rng = 0x9e37_79b9_7f4a_7c15u64;
+        let mut uniform = move || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        let mut phase = 0.0f32;
+        let mut out = Vec::new();
+        for &(tone, us) in runs {
+            for _ in 0..(us * 1e-6 * RATE).round() as usize {
+                let mut x = Complex::new(noise * uniform(), noise * uniform());
+                if let Some(hz) = tone {
+                    phase = (phase + TAU * hz / RATE as f32) % TAU;
+                    x += Complex::from_polar(level, phase);
+                }
+                out.push(x);
+            }
+        }
+        out
+    }
+
+    /// On-off keying of PWM bits, 1 short and 0 long, with a 600 µs gap.
+    fn ook_pwm(bits: &str) -> Vec<(Option<f32>, f64)> {
+        let mut runs = vec![(None, 20_000.0)];
+        for b in bits.chars() {
+            let width = if b == '1' { 400.0 } else { 1_000.0 };
+            runs.push((Some(5_000.0), width));
+            runs.push((None, 600.0));
+        }
+        runs.push((None, 20_000.0));
+        runs
+    }
+
+    #[test]
+    fn measures_ook_pulse_widths() {
+        let input = baseband(&ook_pwm("1011"), 0.3, 0.02);
+        let mut detector = PulseDetector::new(RATE, Modulation::Ook);
+        let trains = detector.process(&input);
+        assert_eq!(trains.len(), 1);
+        let train = &trains[0];
+        assert_eq!(train.modulation, Modulation::Ook);
+        let widths: Vec<f64> = train.pulses.iter().map(|p| p.width).collect();
+        for (width, nominal) in widths.iter().zip([400.0, 1_000.0, 400.0, 400.0]) {
+            assert!(
+                (width - nominal).abs() <= 20.0,
+                "{} µs pulse for {} µs",
+                width,
+                nominal
+            );
+        }
+        assert_eq!(widths.len(), 4);
+        for pulse in &train.pulses[..3] {
+            assert!((pulse.gap - 600.0).abs() <= 20.0, "{} µs gap", pulse.gap);
+        }
+        // The burst starts after the 20 ms of silence.
+        assert!(
+            (train.start as f64 - 5_000.0).abs() < 5.0,
+            "{}",
+            train.start
+        );
+        assert!(
+            (train.duration() - 4_000.0).abs() < 40.0,
+            "{} µs",
+            train.duration()
+        );
+        assert!(train.snr() > 20.0, "{} dB", train.snr());
+        assert!(!detector.in_burst());
+        let rows = Slicer::pwm(400.0, 1_000.0).slice(train);
+        assert_eq!(rows, [Bits::from(vec![true, false, true, true])]);
+    }
+
+    #[test]
+    fn ook_works_at_any_level() {
+        for level in [0.003, 0.03, 0.9] {
+            let input = baseband(&ook_pwm("1100"), level, level / 30.0);
+            let trains = PulseDetector::new(RATE, Modulation::Ook).process(&input);
+            assert_eq!(trains.len(), 1, "at {}", level);
+            assert_eq!(trains[0].pulses.len(), 4, "at {}", level);
+        }
+    }
+
+    #[test]
+    fn ignores_signals_below_the_minimum_snr() {
+        let input = baseband(&ook_pwm("1100"), 0.03, 0.1);
+        let mut detector = PulseDetector::new(RATE, Modulation::Ook);
+        assert!(detector.process(&input).is_empty());
+        assert!(
+            detector.noise_floor() < -20.0,
+            "{} dB",
+            detector.noise_floor()
+        );
+    }
+
+    #[test]
+    fn finds_bursts_across_chunks() {
+        let mut runs = ook_pwm("10");
+        runs.extend(ook_pwm("0110"));
+        let input = baseband(&runs, 0.3, 0.02);
+        let whole = PulseDetector::new(RATE, Modulation::Ook).process(&input);
+        let mut detector = PulseDetector::new(RATE, Modulation::Ook);
+        let chunked: Vec<PulseTrain> = input
+            .chunks(1_000)
+            .flat_map(|chunk| detector.process(chunk))
+            .collect();
+        assert_eq!(whole.len(), 2);
+        assert_eq!(whole, chunked);
+        assert_eq!(whole[1].pulses.len(), 4);
+    }
+
+    #[test]
+    fn splits_long_bursts() {
+        let input = baseband(&ook_pwm("11111"), 0.3, 0.02);
+        let trains = PulseDetector::new(RATE, Modulation::Ook)
+            .with_max_pulses(2)
+            .process(&input);
+        let lengths: Vec<usize> = trains.iter().map(|t| t.pulses.len()).collect();
+        assert_eq!(lengths, [2, 2, 1]);
+    }
+
+    #[test]
+    fn measures_fsk_runs_of_the_higher_tone() {
+        // 100 µs bits, mark at +40 kHz and space at -40 kHz, led in by
+        // 400 µs of space.
+        let bits = "1010101100101101";
+        let mut runs = vec![(None, 20_000.0), (Some(-40_000.0), 400.0)];
+        for b in bits.chars() {
+            runs.push((Some(if b == '1' { 40_000.0 } else { -40_000.0 }), 100.0));
+        }
+        runs.push((Some(-40_000.0), 400.0));
+        runs.push((None, 20_000.0));
+        let input = baseband(&runs, 0.3, 0.02);
+        let trains = PulseDetector::new(RATE, Modulation::Fsk).process(&input);
+        assert_eq!(trains.len(), 1);
+        assert_eq!(trains[0].modulation, Modulation::Fsk);
+        let widths: Vec<f64> = trains[0].pulses.iter().map(|p| p.width).collect();
+        let expected = [100.0, 100.0, 100.0, 200.0, 100.0, 200.0, 100.0];
+        assert_eq!(widths.len(), expected.len(), "{:?}", widths);
+        for (width, nominal) in widths.iter().zip(expected) {
+            assert!((width - nominal).abs() <= 12.0, "{:?}", widths);
+        }
+        // The space tone after the last mark is part of the final gap.
+        let rows = Slicer::pcm(100.0).slice(&trains[0]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].to_string(), "ab2d");
+    }
+
+    #[test]
+    fn reset_forgets_the_noise_floor() {
+        let mut detector = PulseDetector::new(RATE, Modulation::Ook);
+        detector.process(&baseband(&[(None, 20_000.0)], 0.0, 0.1));
+        assert!(detector.noise_floor() > -40.0);
+        detector.reset();
+        assert_eq!(detector.noise_floor(), -180.0);
+        assert_eq!(detector.modulation(), Modulation::Ook);
+    }
+}
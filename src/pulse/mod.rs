@@ -0,0 +1,17 @@
+//! Pulse detection and bit slicing for OOK and FSK bursts.
+//!
+//! The building blocks for decoding ISM-band devices such as weather
+//! stations, remote controls and tyre pressure sensors on 315, 433 and 868
+//! MHz. A `PulseDetector` turns baseband into `PulseTrain`s, one per burst,
+//! with an adaptive threshold so it works at any signal level. A `Slicer`
+//! then turns a train into rows of `Bits` according to the line coding the
+//! device uses. Timings are in microseconds, as protocol documentation and
+//! rtl_433's decoders give them.
+
+mod bits;
+mod detect;
+mod slicer;
+
+pub use bits::Bits;
+pub use detect::{Modulation, Pulse, PulseDetector, PulseTrain};
+pub use slicer::{Coding, Slicer};
@@ -0,0 +1,395 @@
+use std::iter::repeat_n;
+
+use super::bits::Bits;
+use super::detect::PulseTrain;
+
+/// Line coding of a device's bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Coding {
+    /// Pulse code modulation, also called NRZ: each bit period is high for
+    /// 1 and low for 0, so runs of equal bits merge into longer pulses and
+    /// gaps.
+    Pcm,
+    /// Pulse width modulation: every pulse is a bit, a short pulse for 1
+    /// and a long one for 0.
+    Pwm,
+    /// Pulse position modulation: every gap between pulses is a bit, a
+    /// short gap for 0 and a long one for 1.
+    Ppm,
+    /// Manchester coding: every bit is two half-bit periods, high then low
+    /// for 1 and low then high for 0, as in G.E. Thomas' convention. Use
+    /// `Bits::inverted` for IEEE 802.3 devices.
+    Manchester,
+}
+
+/// Turns pulse trains into bits.
+///
+/// A train is split into rows wherever a gap is longer than the row gap,
+/// since most devices repeat their message several times per burst with a
+/// pause in between. Widths are matched to the nearest nominal timing, so
+/// a slicer tolerates the loose clocks of cheap transmitters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Slicer {
+    coding: Coding,
+    short: f64,
+    long: f64,
+    row_gap: f64,
+}
+
+impl Slicer {
+    /// Create a PCM slicer with a row gap of 8 bit periods.
+    ///
+    /// # Arguments
+    ///
+    /// * `bit` - The bit period in microseconds.
+    ///
+    /// # Returns
+    ///
+    /// A new `Slicer`.
+    pub fn pcm(bit: f64) -> Self {
+        Self::new(Coding::Pcm, bit, bit, 8.0 * bit)
+    }
+
+    /// Create a PWM slicer with a row gap of 3 long pulses.
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - The width of a 1 pulse in microseconds.
+    /// * `long` - The width of a 0 pulse in microseconds.
+    ///
+    /// # Returns
+    ///
+    /// A new `Slicer`.
+    pub fn pwm(short: f64, long: f64) -> Self {
+        Self::new(Coding::Pwm, short, long, 3.0 * long)
+    }
+
+    /// Create a PPM slicer with a row gap of 3 long gaps.
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - The gap for a 0 in microseconds.
+    /// * `long` - The gap for a 1 in microseconds.
+    ///
+    /// # Returns
+    ///
+    /// A new `Slicer`.
+    pub fn ppm(short: f64, long: f64) -> Self {
+        Self::new(Coding::Ppm, short, long, 3.0 * long)
+    }
+
+    /// Create a Manchester slicer with a row gap of 4 bit periods.
+    ///
+    /// # Arguments
+    ///
+    /// * `half_bit` - Half the bit period in microseconds, the width of
+    ///   the shortest pulse.
+    ///
+    /// # Returns
+    ///
+    /// A new `Slicer`.
+    pub fn manchester(half_bit: f64) -> Self {
+        Self::new(Coding::Manchester, half_bit, 2.0 * half_bit, 8.0 * half_bit)
+    }
+
+    fn new(coding: Coding, short: f64, long: f64, row_gap: f64) -> Self {
+        assert!(short > 0.0, "pulse timings must be positive");
+        assert!(long >= short, "long timing must not be shorter than short");
+        Slicer {
+            coding,
+            short,
+            long,
+            row_gap,
+        }
+    }
+
+    /// Set the gap that separates rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `gap` - The shortest gap between rows in microseconds.
+    ///
+    /// # Returns
+    ///
+    /// The `Slicer` with the new row gap.
+    pub fn with_row_gap(mut self, gap: f64) -> Self {
+        self.row_gap = gap;
+        self
+    }
+
+    /// Get the line coding.
+    pub fn coding(&self) -> Coding {
+        self.coding
+    }
+
+    /// Slice a pulse train into rows of bits.
+    ///
+    /// # Arguments
+    ///
+    /// * `train` - A burst from `PulseDetector`.
+    ///
+    /// # Returns
+    ///
+    /// The rows in order, without empty ones.
+    pub fn slice(&self, train: &PulseTrain) -> Vec<Bits> {
+        let mut rows = Vec::new();
+        let mut row = Bits::new();
+        let mid = 0.5 * (self.short + self.long);
+        match self.coding {
+            Coding::Pcm => {
+                for p in &train.pulses {
+                    row.extend(repeat_n(true, periods(p.width, self.short)));
+                    if p.gap > self.row_gap {
+                        rows.push(std::mem::take(&mut row));
+                    } else {
+                        row.extend(repeat_n(false, periods(p.gap, self.short)));
+                    }
+                }
+            }
+            Coding::Pwm => {
+                for p in &train.pulses {
+                    row.push(p.width < mid);
+                    if p.gap > self.row_gap {
+                        rows.push(std::mem::take(&mut row));
+                    }
+                }
+            }
+            Coding::Ppm => {
+                for p in &train.pulses {
+                    if p.gap > self.row_gap {
+                        rows.push(std::mem::take(&mut row));
+                    } else {
+                        row.push(p.gap >= mid);
+                    }
+                }
+            }
+            Coding::Manchester => {
+                let mut halves = Vec::new();
+                for p in &train.pulses {
+                    halves.extend(repeat_n(true, periods(p.width, self.short)));
+                    if p.gap > self.row_gap {
+                        manchester(&halves, &mut rows);
+                        halves.clear();
+                    } else {
+                        halves.extend(repeat_n(false, periods(p.gap, self.short)));
+                    }
+                }
+                manchester(&halves, &mut rows);
+            }
+        }
+        rows.push(row);
+        rows.retain(|r| !r.is_empty());
+        rows
+    }
+
+    /// Measure how clearly each timing in a pulse train was sliced.
+    ///
+    /// # Arguments
+    ///
+    /// * `train` - A burst from `PulseDetector`.
+    ///
+    /// # Returns
+    ///
+    /// One margin per width or gap `slice` read bits from, in order, from
+    /// 0 for a timing on the boundary between two decisions to 1 for one
+    /// right on a nominal timing.
+    pub fn margins(&self, train: &PulseTrain) -> Vec<f32> {
+        let mid = 0.5 * (self.short + self.long);
+        let half_spread = 0.5 * (self.long - self.short);
+        let between = |d: f64| ((d - mid).abs() / half_spread).min(1.0) as f32;
+        let mut margins = Vec::new();
+        for p in &train.pulses {
+            let in_row = p.gap <= self.row_gap;
+            match self.coding {
+                Coding::Pcm | Coding::Manchester => {
+                    margins.push(on_period(p.width, self.short));
+                    if in_row {
+                        margins.push(on_period(p.gap, self.short));
+                    }
+                }
+                Coding::Pwm => margins.push(between(p.width)),
+                Coding::Ppm if in_row => margins.push(between(p.gap)),
+                Coding::Ppm => {}
+            }
+        }
+        margins
+    }
+}
+
+/// Round a duration to a whole number of periods, at least one.
+fn periods(duration: f64, period: f64) -> usize {
+    ((duration / period).round() as usize).max(1)
+}
+
+/// How close a duration is to the whole number of periods it rounds to,
+/// from 0 half a period away to 1 on it.
+fn on_period(duration: f64, period: f64) -> f32 {
+    let off = (duration / period - periods(duration, period) as f64).abs();
+    (1.0 - 2.0 * off).max(0.0) as f32
+}
+
+/// Decode Manchester half-bits into rows, starting a new row after each
+/// coding violation.
+fn manchester(halves: &[bool], rows: &mut Vec<Bits>) {
+    // A row starting with a 0 bit starts low, and that half-bit is lost in
+    // the silence before it. Try both alignments and keep the one decoding
+    // further before the first violation.
+    let mut restored = vec![false];
+    restored.extend_from_slice(halves);
+    let plain = manchester_rows(halves);
+    let shifted = manchester_rows(&restored);
+    let first = |rows: &[Bits]| rows.first().map_or(0, Bits::len);
+    rows.extend(if first(&shifted) > first(&plain) {
+        shifted
+    } else {
+        plain
+    });
+}
+
+fn manchester_rows(halves: &[bool]) -> Vec<Bits> {
+    let mut rows = Vec::new();
+    let mut row = Bits::new();
+    let mut start = 0;
+    while start < halves.len() {
+        // A last half-bit that is low merges into the gap after the row.
+        let second = halves.get(start + 1).copied().unwrap_or(false);
+        if halves[start] != second {
+            row.push(halves[start]);
+            start += 2;
+        } else {
+            if !row.is_empty() {
+                rows.push(std::mem::take(&mut row));
+            }
+            start += 1;
+        }
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulse::{Modulation, Pulse};
+
+    fn train(pulses: &[(f64, f64)]) -> PulseTrain {
+        PulseTrain {
+            modulation: Modulation::Ook,
+            pulses: pulses
+                .iter()
+                .map(|&(width, gap)| Pulse { width, gap })
+                .collect(),
+            start: 0,
+            sample_rate: 250_000.0,
+            level: -10.0,
+            noise: -40.0,
+        }
+    }
+
+    fn rows(rows: &[Bits]) -> Vec<String> {
+        rows.iter()
+            .map(|r| {
+                r.as_slice()
+                    .iter()
+                    .map(|&b| if b { '1' } else { '0' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pcm_expands_runs_into_bit_periods() {
+        // 1 0 11 000 1, a row gap, then 1 1 with loose timing.
+        let t = train(&[
+            (100.0, 100.0),
+            (210.0, 290.0),
+            (95.0, 2_000.0),
+            (190.0, 20_000.0),
+        ]);
+        assert_eq!(rows(&Slicer::pcm(100.0).slice(&t)), ["10110001", "11"]);
+    }
+
+    #[test]
+    fn pwm_reads_a_bit_from_each_pulse() {
+        let t = train(&[
+            (400.0, 600.0),
+            (1_100.0, 600.0),
+            (450.0, 600.0),
+            (950.0, 5_000.0),
+            (420.0, 20_000.0),
+        ]);
+        assert_eq!(rows(&Slicer::pwm(400.0, 1_000.0).slice(&t)), ["1010", "1"]);
+    }
+
+    #[test]
+    fn ppm_reads_a_bit_from_each_gap() {
+        let t = train(&[
+            (500.0, 1_000.0),
+            (500.0, 2_100.0),
+            (500.0, 900.0),
+            (500.0, 8_000.0),
+            (500.0, 1_900.0),
+            (500.0, 20_000.0),
+        ]);
+        assert_eq!(rows(&Slicer::ppm(1_000.0, 2_000.0).slice(&t)), ["010", "1"]);
+    }
+
+    #[test]
+    fn manchester_decodes_half_bit_pairs() {
+        // 1 1 0 1 is high-low high-low low-high high-low: pulses of one,
+        // one and two half-bits.
+        let t = train(&[(250.0, 250.0), (250.0, 500.0), (500.0, 20_000.0)]);
+        assert_eq!(rows(&Slicer::manchester(250.0).slice(&t)), ["1101"]);
+    }
+
+    #[test]
+    fn manchester_restores_a_leading_zero() {
+        // 0 1 1 0 starts low, so the first pulse is the second half of the
+        // 0 and the first half of the next 1.
+        let t = train(&[(500.0, 250.0), (250.0, 500.0), (250.0, 20_000.0)]);
+        assert_eq!(rows(&Slicer::manchester(250.0).slice(&t)), ["0110"]);
+    }
+
+    #[test]
+    fn manchester_splits_rows_at_violations_and_gaps() {
+        // 1 0, a violation of three high half-bits, then 1 after a row gap.
+        let t = train(&[
+            (250.0, 500.0),
+            (750.0, 250.0),
+            (250.0, 5_000.0),
+            (250.0, 20_000.0),
+        ]);
+        let sliced = Slicer::manchester(250.0).slice(&t);
+        assert_eq!(rows(&sliced)[0], "10");
+        assert_eq!(rows(&sliced).last().unwrap(), "1");
+    }
+
+    #[test]
+    fn a_custom_row_gap_keeps_repeats_together() {
+        let t = train(&[(400.0, 600.0), (400.0, 5_000.0), (1_000.0, 20_000.0)]);
+        let slicer = Slicer::pwm(400.0, 1_000.0);
+        assert_eq!(rows(&slicer.slice(&t)), ["11", "0"]);
+        assert_eq!(rows(&slicer.with_row_gap(6_000.0).slice(&t)), ["110"]);
+        assert_eq!(slicer.coding(), Coding::Pwm);
+    }
+
+    #[test]
+    fn margins_shrink_towards_the_decision_boundary() {
+        let t = train(&[(400.0, 600.0), (850.0, 600.0), (700.0, 20_000.0)]);
+        let pwm = Slicer::pwm(400.0, 1_000.0).margins(&t);
+        assert_eq!(pwm.len(), 3);
+        assert!((pwm[0] - 1.0).abs() < 1e-6);
+        assert!((pwm[1] - 0.5).abs() < 1e-6, "{:?}", pwm);
+        assert!(pwm[2].abs() < 1e-6, "{:?}", pwm);
+
+        // Every width and gap but the trailing silence, 1.25 periods being
+        // halfway to the boundary.
+        let t = train(&[(100.0, 125.0), (200.0, 20_000.0)]);
+        let pcm = Slicer::pcm(100.0).margins(&t);
+        assert_eq!(pcm.len(), 3);
+        assert!((pcm[1] - 0.5).abs() < 1e-6, "{:?}", pcm);
+        assert!(pcm[0] > 0.99 && pcm[2] > 0.99, "{:?}", pcm);
+    }
+}
@@ -0,0 +1,44 @@
+//! Unsafe, low-level access to librtlsdr.
+//!
+//! Everything here maps directly onto the C API and comes with none of the
+//! guarantees of the rest of the crate. It exists for the rare cases the safe
+//! API does not cover and may change between releases.
+//!
+//! All librtlsdr bindings are re-exported here. Use `Device::as_raw` to call
+//! functions that are not wrapped yet on a device opened through radion.
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+use std::os::raw::c_void;
+
+pub use crate::ffi::*;
+
+/// Read from the device asynchronously with the default buffer settings.
+///
+/// This is `rtlsdr_wait_async`, which librtlsdr deprecates in favour of
+/// `rtlsdr_read_async`. Prefer `Device::read_async_with` or
+/// `Device::start_async`.
+///
+/// # Arguments
+///
+/// * `device` - The device to read from.
+/// * `callback` - The callback function to call when data is read.
+/// * `ctx` - The context to pass to the callback function.
+///
+/// # Returns
+///
+/// An `Ok` result once the read is cancelled, otherwise an `Error`.
+///
+/// # Safety
+///
+/// `ctx` is handed to `callback` as-is, so it must stay valid for whatever
+/// `callback` does with it until this call returns. The device must not be
+/// read from by any other means while the call is running.
+pub unsafe fn wait_async(device: &Device, callback: ReadAsyncCbT, ctx: *mut c_void) -> Result<()> {
+    let ret = rtlsdr_wait_async(device.as_raw(), callback, ctx);
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(Error::from(ret))
+    }
+}
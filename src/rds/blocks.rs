@@ -0,0 +1,354 @@
+/// Generator polynomial of the RDS block code,
+/// x^10 + x^8 + x^7 + x^5 + x^4 + x^3 + 1.
+const POLY: u32 = 0x5B9;
+
+/// Offset words added to the checkword of each block type.
+const OFFSETS: [(BlockKind, u16); 5] = [
+    (BlockKind::A, 0x0FC),
+    (BlockKind::B, 0x198),
+    (BlockKind::C, 0x168),
+    (BlockKind::CPrime, 0x350),
+    (BlockKind::D, 0x1B4),
+];
+
+/// Longest error burst corrected. The code can correct bursts of up to five
+/// bits, but longer errors are then often miscorrected into valid-looking
+/// data, so only short bursts are fixed.
+const MAX_BURST: u32 = 2;
+
+/// Number of recent blocks considered when deciding to drop sync.
+const SYNC_WINDOW: u32 = 50;
+
+/// Uncorrectable blocks within `SYNC_WINDOW` that drop sync.
+const SYNC_MAX_ERRORS: u32 = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum BlockKind {
+    A,
+    B,
+    C,
+    CPrime,
+    D,
+}
+
+impl BlockKind {
+    fn position(self) -> usize {
+        match self {
+            BlockKind::A => 0,
+            BlockKind::B => 1,
+            BlockKind::C | BlockKind::CPrime => 2,
+            BlockKind::D => 3,
+        }
+    }
+}
+
+/// The four blocks of a group; `None` where a block could not be decoded.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct RawGroup {
+    pub(crate) blocks: [Option<u16>; 4],
+    /// Whether the third block carried offset C' rather than C.
+    pub(crate) c_prime: bool,
+    /// The number of bits repaired across the blocks.
+    pub(crate) corrected: u32,
+}
+
+/// Compute the checkword of 16 information bits, without offset.
+pub(crate) fn checkword(info: u16) -> u16 {
+    let mut reg = (info as u32) << 10;
+    for bit in (10..26).rev() {
+        if reg & (1 << bit) != 0 {
+            reg ^= POLY << (bit - 10);
+        }
+    }
+    (reg & 0x3FF) as u16
+}
+
+/// Build the 26-bit block a transmitter sends for 16 information bits.
+#[cfg(test)]
+pub(crate) fn encode(info: u16, kind: BlockKind) -> u32 {
+    let (_, offset) = OFFSETS.iter().find(|(k, _)| *k == kind).unwrap();
+    ((info as u32) << 10) | (checkword(info) ^ offset) as u32
+}
+
+/// Get the offset a 26-bit block was sent with, if it has no errors.
+fn offset_of(block: u32) -> Option<BlockKind> {
+    let info = (block >> 10) as u16;
+    let offset = (block & 0x3FF) as u16 ^ checkword(info);
+    OFFSETS
+        .iter()
+        .find(|(_, word)| *word == offset)
+        .map(|(kind, _)| *kind)
+}
+
+/// Try to decode a block expected at `position`, correcting a burst error of
+/// up to `MAX_BURST` bits, and count the bits repaired.
+fn decode(block: u32, position: usize) -> Option<(u16, BlockKind, u32)> {
+    let accept = |b: u32| {
+        offset_of(b)
+            .filter(|k| k.position() == position)
+            .map(|k| ((b >> 10) as u16, k, (b ^ block).count_ones()))
+    };
+    if let Some(found) = accept(block) {
+        return Some(found);
+    }
+    for len in 1..=MAX_BURST {
+        let ends = if len == 1 { 1 } else { 1 | (1 << (len - 1)) };
+        for middle in 0..(1u32 << len.saturating_sub(2)) {
+            let pattern = ends | (middle << 1);
+            for shift in 0..=(26 - len) {
+                if let Some(found) = accept(block ^ (pattern << shift)) {
+                    return Some(found);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Finds block boundaries in the bit stream and assembles groups.
+pub(crate) struct BlockSync {
+    reg: u32,
+    bits: u64,
+    synced: bool,
+    /// Position in the group of the block being received.
+    position: usize,
+    /// Bits received of the block being received.
+    filled: u32,
+    /// Bit count and position of the blocks seen in the last group's worth
+    /// of bits while searching. Offsets also turn up by chance, so the
+    /// search must not forget a real block for a chance one.
+    candidates: Vec<(u64, usize)>,
+    /// One bit per recent block, set if it was uncorrectable.
+    errors: u64,
+    group: RawGroup,
+}
+
+impl BlockSync {
+    pub(crate) fn new() -> Self {
+        BlockSync {
+            reg: 0,
+            bits: 0,
+            synced: false,
+            position: 0,
+            filled: 0,
+            candidates: Vec::new(),
+            errors: 0,
+            group: RawGroup::default(),
+        }
+    }
+
+    pub(crate) fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Feed one bit, returning a group when its last block has arrived.
+    pub(crate) fn push(&mut self, bit: bool) -> Option<RawGroup> {
+        self.reg = ((self.reg << 1) | bit as u32) & 0x3FF_FFFF;
+        self.bits += 1;
+
+        if !self.synced {
+            return self.search();
+        }
+
+        self.filled += 1;
+        if self.filled < 26 {
+            return None;
+        }
+        self.filled = 0;
+        let decoded = decode(self.reg, self.position);
+        self.errors = ((self.errors << 1) | decoded.is_none() as u64) & ((1 << SYNC_WINDOW) - 1);
+        if self.errors.count_ones() > SYNC_MAX_ERRORS {
+            self.synced = false;
+            self.candidates.clear();
+            self.group = RawGroup::default();
+            return None;
+        }
+        self.accept(decoded)
+    }
+
+    fn search(&mut self) -> Option<RawGroup> {
+        let kind = offset_of(self.reg)?;
+        let position = kind.position();
+        let bits = self.bits;
+        self.candidates.retain(|&(at, _)| bits - at <= 4 * 26);
+        let confirmed = self.candidates.iter().any(|&(at, prev)| {
+            let distance = bits - at;
+            distance.is_multiple_of(26) && (distance / 26) % 4 == ((position + 4 - prev) % 4) as u64
+        });
+        if confirmed {
+            self.synced = true;
+            self.errors = 0;
+            self.filled = 0;
+            self.candidates.clear();
+            self.group = RawGroup::default();
+            self.position = position;
+            return self.accept(Some(((self.reg >> 10) as u16, kind, 0)));
+        }
+        self.candidates.push((bits, position));
+        None
+    }
+
+    fn accept(&mut self, decoded: Option<(u16, BlockKind, u32)>) -> Option<RawGroup> {
+        if let Some((info, kind, corrected)) = decoded {
+            self.group.blocks[self.position] = Some(info);
+            self.group.corrected += corrected;
+            if kind == BlockKind::CPrime {
+                self.group.c_prime = true;
+            }
+        }
+        self.position = (self.position + 1) % 4;
+        if self.position == 0 {
+            Some(std::mem::take(&mut self.group))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkwords_match_the_generator_matrix() {
+        // First and last rows of the generator matrix in IEC 62106.
+        assert_eq!(checkword(0x8000), 0x077);
+        assert_eq!(checkword(0x0001), 0x1B9);
+        assert_eq!(checkword(0), 0);
+        // The code is linear.
+        for (a, b) in [(0x1234, 0xABCD), (0xFFFF, 0x0F0F), (0x8001, 0x7FFE)] {
+            assert_eq!(checkword(a ^ b), checkword(a) ^ checkword(b));
+        }
+    }
+
+    #[test]
+    fn offsets_identify_the_block() {
+        for kind in [
+            BlockKind::A,
+            BlockKind::B,
+            BlockKind::C,
+            BlockKind::CPrime,
+            BlockKind::D,
+        ] {
+            assert_eq!(offset_of(encode(0xC0DE, kind)), Some(kind));
+            assert_eq!(
+                decode(encode(0xC0DE, kind), kind.position()),
+                Some((0xC0DE, kind, 0))
+            );
+        }
+        assert_eq!(offset_of(encode(0xC0DE, BlockKind::A) ^ 1), None);
+        // A valid block in the wrong place is never taken for itself.
+        for position in 1..4 {
+            assert_ne!(
+                decode(encode(0xC0DE, BlockKind::A), position).map(|(info, kind, _)| (info, kind)),
+                Some((0xC0DE, BlockKind::A))
+            );
+        }
+    }
+
+    #[test]
+    fn corrects_short_bursts_only() {
+        let block = encode(0x2A5B, BlockKind::B);
+        for shift in 0..26 {
+            assert_eq!(
+                decode(block ^ (1 << shift), 1),
+                Some((0x2A5B, BlockKind::B, 1))
+            );
+        }
+        for shift in 0..25 {
+            assert_eq!(
+                decode(block ^ (0b11 << shift), 1),
+                Some((0x2A5B, BlockKind::B, 2))
+            );
+        }
+        assert_ne!(
+            decode(block ^ 0b10101, 1).map(|(info, kind, _)| (info, kind)),
+            Some((0x2A5B, BlockKind::B))
+        );
+    }
+
+    fn bits(block: u32) -> impl Iterator<Item = bool> {
+        (0..26).rev().map(move |i| block >> i & 1 != 0)
+    }
+
+    fn group(pi: u16, b: u16, c: u16, d: u16) -> [u32; 4] {
+        [
+            encode(pi, BlockKind::A),
+            encode(b, BlockKind::B),
+            encode(c, BlockKind::C),
+            encode(d, BlockKind::D),
+        ]
+    }
+
+    #[test]
+    fn syncs_on_two_blocks_at_the_right_distance() {
+        let mut sync = BlockSync::new();
+        // Start mid-group, after some noise, so the first block is C. Block
+        // C happens to be followed by a chance match of offset B.
+        let noise = [true, false, false, true, true, false, true];
+        let mut stream: Vec<bool> = noise.to_vec();
+        let first = group(0x1234, 0x0408, 0xE0CD, 0x5241);
+        let second = group(0x1234, 0x0409, 0xE0CD, 0x4449);
+        for block in first[2..].iter().chain(&second) {
+            stream.extend(bits(*block));
+        }
+
+        let mut groups = Vec::new();
+        for (i, bit) in stream.into_iter().enumerate() {
+            if let Some(raw) = sync.push(bit) {
+                groups.push(raw);
+            }
+            // Synced once block D completes, two blocks after C.
+            assert_eq!(sync.is_synced(), i + 1 >= noise.len() + 52, "bit {}", i);
+        }
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].blocks, [None, None, None, Some(0x5241)]);
+        assert_eq!(
+            groups[1].blocks,
+            [Some(0x1234), Some(0x0409), Some(0xE0CD), Some(0x4449)]
+        );
+        assert!(!groups[1].c_prime);
+    }
+
+    #[test]
+    fn marks_c_prime_and_missing_blocks() {
+        let mut sync = BlockSync::new();
+        let mut blocks = group(0x1234, 0x0408, 0, 0).to_vec();
+        blocks.extend([
+            encode(0x1234, BlockKind::A) ^ 0b10101,
+            encode(0x0C00, BlockKind::B),
+            encode(0x1234, BlockKind::CPrime),
+            encode(0x2020, BlockKind::D),
+        ]);
+        let groups: Vec<RawGroup> = blocks
+            .into_iter()
+            .flat_map(bits)
+            .filter_map(|bit| sync.push(bit))
+            .collect();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[1].blocks,
+            [None, Some(0x0C00), Some(0x1234), Some(0x2020)]
+        );
+        assert!(groups[1].c_prime);
+        assert_eq!(groups[1].corrected, 0);
+    }
+
+    #[test]
+    fn drops_sync_after_too_many_errors() {
+        let mut sync = BlockSync::new();
+        for bit in group(0x1234, 0x0408, 0, 0).into_iter().flat_map(bits) {
+            sync.push(bit);
+        }
+        assert!(sync.is_synced());
+        // Alternating bits never form a valid block.
+        for i in 0..26 * SYNC_MAX_ERRORS {
+            sync.push(i % 2 == 0);
+        }
+        assert!(sync.is_synced());
+        for i in 0..26 {
+            sync.push(i % 2 == 0);
+        }
+        assert!(!sync.is_synced());
+    }
+}
@@ -0,0 +1,170 @@
+use std::f64::consts::TAU;
+
+use crate::dsp::fir::{low_pass, Decimator, Window};
+use crate::dsp::Complex;
+
+/// RDS subcarrier frequency, three times the pilot.
+const SUBCARRIER_HZ: f64 = 57_000.0;
+
+/// RDS bit rate, the subcarrier divided by 48.
+const BIT_RATE: f64 = 1187.5;
+
+/// Rate the subcarrier is decimated to before symbol recovery.
+const TARGET_RATE: f64 = 19_000.0;
+
+/// Costas loop bandwidth in Hz.
+const COSTAS_BANDWIDTH_HZ: f64 = 30.0;
+
+/// Bits averaged before checking for a half-bit timing slip.
+const SLIP_CHECK_BITS: u32 = 32;
+
+/// Recovers the differentially decoded RDS bit stream from an FM multiplex.
+///
+/// The 57 kHz subcarrier is mixed to DC and decimated, a Costas loop removes
+/// the residual carrier phase, and a biphase matched filter with early-late
+/// timing recovery makes one decision per bit. Biphase symbols have a second,
+/// data-dependent peak half a bit away, so the filter output there is tracked
+/// as well and the timing jumps by half a bit if it is consistently larger.
+pub(crate) struct RdsDemod {
+    mpx_rate: f64,
+    nco_phase: f64,
+    nco_step: f64,
+    decimator: Decimator<Complex<f32>>,
+    mixed: Vec<Complex<f32>>,
+    baseband: Vec<Complex<f32>>,
+    costas_phase: f64,
+    costas_freq: f64,
+    kp: f64,
+    ki: f64,
+    level: f32,
+    /// Costas loop output, one real sample per baseband sample.
+    symbols: Vec<f32>,
+    /// Index into `symbols` of the next decision.
+    next: f64,
+    samples_per_bit: f64,
+    on_time: f32,
+    off_time: f32,
+    checked: u32,
+    prev: bool,
+    /// Matched filter output of the previous decision, relative to a
+    /// clean symbol.
+    prev_margin: f32,
+}
+
+impl RdsDemod {
+    pub(crate) fn new(mpx_rate: f64) -> Self {
+        let factor = (mpx_rate / TARGET_RATE).floor().max(1.0) as usize;
+        let rate = mpx_rate / factor as f64;
+        let taps = low_pass(2_800.0 / mpx_rate, 8 * factor + 1, Window::Blackman);
+        let wn = TAU * COSTAS_BANDWIDTH_HZ / rate;
+        let samples_per_bit = rate / BIT_RATE;
+        RdsDemod {
+            mpx_rate,
+            nco_phase: 0.0,
+            nco_step: SUBCARRIER_HZ / mpx_rate,
+            decimator: Decimator::new(factor, &taps),
+            mixed: Vec::new(),
+            baseband: Vec::new(),
+            costas_phase: 0.0,
+            costas_freq: 0.0,
+            kp: 2.0 * 0.707 * wn,
+            ki: wn * wn,
+            level: 0.0,
+            symbols: Vec::new(),
+            next: 2.0 * samples_per_bit,
+            samples_per_bit,
+            on_time: 0.0,
+            off_time: 0.0,
+            checked: 0,
+            prev: false,
+            prev_margin: 0.0,
+        }
+    }
+
+    /// Demodulate a chunk of the multiplex, adding each bit and its
+    /// decision margin, from 0 for a guess to about 1 for a clean symbol,
+    /// to `bits`.
+    pub(crate) fn process(&mut self, mpx: &[f32], bits: &mut Vec<(bool, f32)>) {
+        self.mixed.clear();
+        for &x in mpx {
+            let (sin, cos) = (TAU * self.nco_phase).sin_cos();
+            self.mixed
+                .push(Complex::new(x * cos as f32, -x * sin as f32));
+            self.nco_phase = (self.nco_phase + self.nco_step).fract();
+        }
+        self.baseband.clear();
+        self.decimator
+            .decimate_into(&self.mixed, &mut self.baseband);
+
+        for &z in &self.baseband {
+            self.level += 0.001 * (z.norm() - self.level);
+            let z = z / self.level.max(f32::MIN_POSITIVE);
+            let y = z * Complex::from_polar(1.0, -self.costas_phase as f32);
+            let err = (y.re.signum() * y.im).clamp(-1.0, 1.0) as f64;
+            self.costas_freq += self.ki * err;
+            self.costas_phase = (self.costas_phase + self.costas_freq + self.kp * err) % TAU;
+            self.symbols.push(y.re);
+        }
+
+        self.decide(bits);
+    }
+
+    /// Biphase matched filter for the bit ending at `end`.
+    fn matched(&self, end: usize) -> f32 {
+        let len = self.samples_per_bit.round() as usize;
+        let half = len / 2;
+        let start = end + 1 - len;
+        let first: f32 = self.symbols[start..start + half].iter().sum();
+        let second: f32 = self.symbols[end + 1 - half..=end].iter().sum();
+        first - second
+    }
+
+    fn decide(&mut self, bits: &mut Vec<(bool, f32)>) {
+        let len = self.samples_per_bit.round() as usize;
+        let delta = (len / 4).max(1);
+        loop {
+            let at = self.next.round() as usize;
+            if at + delta >= self.symbols.len() {
+                break;
+            }
+            let on = self.matched(at);
+            let early = self.matched(at - delta).abs();
+            let late = self.matched(at + delta).abs();
+            let off = self.matched(at - len / 2).abs();
+
+            // A differential bit is only as sure as the weaker of the two
+            // symbols it compares.
+            let symbol = on > 0.0;
+            let margin = on.abs() / len as f32;
+            bits.push((symbol != self.prev, margin.min(self.prev_margin)));
+            self.prev = symbol;
+            self.prev_margin = margin;
+
+            let err = (late - early) / (late + early + f32::EPSILON);
+            self.next += self.samples_per_bit + 0.2 * (err as f64) * delta as f64;
+
+            self.on_time += 0.05 * (on.abs() - self.on_time);
+            self.off_time += 0.05 * (off - self.off_time);
+            self.checked += 1;
+            if self.checked >= SLIP_CHECK_BITS {
+                self.checked = 0;
+                if self.off_time > 1.3 * self.on_time {
+                    self.next += self.samples_per_bit / 2.0;
+                    self.on_time = 0.0;
+                    self.off_time = 0.0;
+                }
+            }
+        }
+
+        // Keep enough history for the earliest window of the next decision.
+        let keep_from = (self.next as usize).saturating_sub(2 * len + delta);
+        if keep_from > 4096 {
+            self.symbols.drain(..keep_from);
+            self.next -= keep_from as f64;
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = RdsDemod::new(self.mpx_rate);
+    }
+}
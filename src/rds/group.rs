@@ -0,0 +1,434 @@
+use super::blocks::RawGroup;
+use crate::decoders::Quality;
+
+/// Whether a group is the A or B version of its type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GroupVersion {
+    A,
+    B,
+}
+
+/// Decoded content of a group.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GroupContent {
+    /// Type 0: basic tuning and switching information.
+    BasicTuning {
+        /// Traffic announcement in progress.
+        traffic_announcement: bool,
+        /// Music rather than speech.
+        music: bool,
+        /// Which pair of the eight PS characters this group carries, 0-3.
+        segment: u8,
+        /// Two characters of the programme service name.
+        ps: [char; 2],
+        /// Alternative frequency codes, version A only.
+        alternative_frequencies: Vec<AltFrequency>,
+    },
+    /// Type 2: RadioText.
+    RadioText {
+        /// Toggles when the broadcaster starts a new text.
+        text_ab: bool,
+        /// Segment address, 0-15.
+        segment: u8,
+        /// Four characters for version A, two for version B.
+        text: Vec<char>,
+    },
+    /// Any other group type, or a group with missing blocks.
+    Other,
+}
+
+/// One entry of an alternative frequency list.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AltFrequency {
+    /// An FM frequency in Hz.
+    Frequency(u32),
+    /// The number of frequencies in the list that follows.
+    Count(u8),
+    /// The next code is an LF/MF frequency, not decoded.
+    LfMfFollows,
+}
+
+impl AltFrequency {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1..=204 => Some(AltFrequency::Frequency(87_500_000 + code as u32 * 100_000)),
+            224..=249 => Some(AltFrequency::Count(code - 224)),
+            250 => Some(AltFrequency::LfMfFollows),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded RDS group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    /// Programme identification code, from block A or, in version B groups,
+    /// block C'.
+    pub pi: Option<u16>,
+    /// Group type, 0-15.
+    pub group_type: u8,
+    pub version: GroupVersion,
+    /// The station carries traffic information.
+    pub traffic_program: bool,
+    /// Programme type code, 0-31.
+    pub program_type: u8,
+    pub content: GroupContent,
+    /// The raw blocks, `None` where a block could not be decoded.
+    pub blocks: [Option<u16>; 4],
+    /// How confidently the group was received. `crc_ok` is set when all
+    /// four blocks passed their checkwords.
+    pub quality: Quality,
+}
+
+/// Map an RDS character code to a `char`.
+///
+/// The printable ASCII range is shared with the RDS character set except for
+/// a handful of symbols; everything outside it comes out as U+FFFD, apart
+/// from the RadioText end marker which is kept as `'\r'`.
+fn rds_char(code: u8) -> char {
+    match code {
+        0x0D => '\r',
+        0x24 => '¤',
+        0x5E => '―',
+        0x60 => '║',
+        0x7E => '¯',
+        0x20..=0x7D => code as char,
+        _ => char::REPLACEMENT_CHARACTER,
+    }
+}
+
+fn chars(word: u16) -> [char; 2] {
+    [rds_char((word >> 8) as u8), rds_char(word as u8)]
+}
+
+impl Group {
+    /// Parse a group, grading it from the soft decisions of its bits.
+    pub(crate) fn parse(raw: &RawGroup, soft: &[i8]) -> Option<Group> {
+        let [a, b, c, d] = raw.blocks;
+        let b = b?;
+        let group_type = (b >> 12) as u8;
+        let version = if b & 0x0800 == 0 {
+            GroupVersion::A
+        } else {
+            GroupVersion::B
+        };
+        let pi = match (a, version, raw.c_prime) {
+            (Some(a), _, _) => Some(a),
+            (None, GroupVersion::B, true) => c,
+            _ => None,
+        };
+
+        let content = match (group_type, version, c, d) {
+            (0, _, _, Some(d)) => {
+                let alternative_frequencies = match (version, c) {
+                    (GroupVersion::A, Some(c)) => [(c >> 8) as u8, c as u8]
+                        .into_iter()
+                        .filter_map(AltFrequency::from_code)
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                GroupContent::BasicTuning {
+                    traffic_announcement: b & 0x10 != 0,
+                    music: b & 0x08 != 0,
+                    segment: (b & 0x3) as u8,
+                    ps: chars(d),
+                    alternative_frequencies,
+                }
+            }
+            (2, GroupVersion::A, Some(c), Some(d)) => GroupContent::RadioText {
+                text_ab: b & 0x10 != 0,
+                segment: (b & 0xF) as u8,
+                text: chars(c).into_iter().chain(chars(d)).collect(),
+            },
+            (2, GroupVersion::B, _, Some(d)) => GroupContent::RadioText {
+                text_ab: b & 0x10 != 0,
+                segment: (b & 0xF) as u8,
+                text: chars(d).to_vec(),
+            },
+            _ => GroupContent::Other,
+        };
+
+        Some(Group {
+            pi,
+            group_type,
+            version,
+            traffic_program: b & 0x0400 != 0,
+            program_type: ((b >> 5) & 0x1F) as u8,
+            content,
+            blocks: raw.blocks,
+            quality: Quality::from_soft(
+                soft,
+                raw.blocks.iter().all(Option::is_some),
+                raw.corrected,
+            ),
+        })
+    }
+}
+
+/// Station information accumulated from the groups received so far.
+#[derive(Clone, Debug, Default)]
+pub struct Station {
+    pub pi: Option<u16>,
+    pub program_type: Option<u8>,
+    pub traffic_program: bool,
+    pub traffic_announcement: bool,
+    pub music: bool,
+    ps: [Option<[char; 2]>; 4],
+    radiotext: Vec<Option<char>>,
+    text_ab: Option<bool>,
+    /// Alternative FM frequencies in Hz, in the order first received.
+    pub alternative_frequencies: Vec<u32>,
+}
+
+impl Station {
+    /// Get the programme service name.
+    ///
+    /// # Returns
+    ///
+    /// The eight-character name once every segment has been received.
+    pub fn ps(&self) -> Option<String> {
+        let mut name = String::with_capacity(8);
+        for segment in &self.ps {
+            name.extend((*segment)?);
+        }
+        Some(name)
+    }
+
+    /// Get the RadioText received so far.
+    ///
+    /// # Returns
+    ///
+    /// The text up to the end marker or the last received segment, with
+    /// characters not yet received shown as spaces.
+    pub fn radiotext(&self) -> String {
+        let text: String = self
+            .radiotext
+            .iter()
+            .take_while(|c| **c != Some('\r'))
+            .map(|c| c.unwrap_or(' '))
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    pub(crate) fn update(&mut self, group: &Group) {
+        if group.pi.is_some() && group.pi != self.pi {
+            *self = Station {
+                pi: group.pi,
+                ..Station::default()
+            };
+        }
+        self.program_type = Some(group.program_type);
+        self.traffic_program = group.traffic_program;
+
+        match &group.content {
+            GroupContent::BasicTuning {
+                traffic_announcement,
+                music,
+                segment,
+                ps,
+                alternative_frequencies,
+            } => {
+                self.traffic_announcement = *traffic_announcement;
+                self.music = *music;
+                self.ps[*segment as usize] = Some(*ps);
+                for af in alternative_frequencies {
+                    if let AltFrequency::Frequency(f) = af {
+                        if !self.alternative_frequencies.contains(f) {
+                            self.alternative_frequencies.push(*f);
+                        }
+                    }
+                }
+            }
+            GroupContent::RadioText {
+                text_ab,
+                segment,
+                text,
+            } => {
+                if self.text_ab != Some(*text_ab) {
+                    self.radiotext.clear();
+                    self.text_ab = Some(*text_ab);
+                }
+                let start = *segment as usize * text.len();
+                if self.radiotext.len() < start + text.len() {
+                    self.radiotext.resize(start + text.len(), None);
+                }
+                for (slot, &c) in self.radiotext[start..].iter_mut().zip(text) {
+                    *slot = Some(c);
+                }
+            }
+            GroupContent::Other => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(blocks: [Option<u16>; 4], c_prime: bool) -> Group {
+        let raw = RawGroup {
+            blocks,
+            c_prime,
+            corrected: 0,
+        };
+        Group::parse(&raw, &[]).unwrap()
+    }
+
+    /// Block B of a group with PTY 10 and TP set.
+    fn block_b(group_type: u16, version_b: bool, low: u16) -> u16 {
+        group_type << 12 | (version_b as u16) << 11 | 1 << 10 | 10 << 5 | low
+    }
+
+    #[test]
+    fn parses_basic_tuning() {
+        // Segment 1, TA and music, AFs 89.1 MHz and 107.9 MHz.
+        let group = parse(
+            [
+                Some(0xD3C2),
+                Some(block_b(0, false, 0x10 | 0x08 | 1)),
+                Some(16 << 8 | 204),
+                Some(u16::from_be_bytes(*b"DI")),
+            ],
+            false,
+        );
+        assert_eq!(group.pi, Some(0xD3C2));
+        assert_eq!((group.group_type, group.version), (0, GroupVersion::A));
+        assert!(group.traffic_program);
+        assert_eq!(group.program_type, 10);
+        assert_eq!(
+            group.content,
+            GroupContent::BasicTuning {
+                traffic_announcement: true,
+                music: true,
+                segment: 1,
+                ps: ['D', 'I'],
+                alternative_frequencies: vec![
+                    AltFrequency::Frequency(89_100_000),
+                    AltFrequency::Frequency(107_900_000),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn parses_radiotext_of_both_versions() {
+        let a = parse(
+            [
+                Some(0xD3C2),
+                Some(block_b(2, false, 0x10 | 3)),
+                Some(u16::from_be_bytes(*b"Hi")),
+                Some(u16::from_be_bytes([b'!', 0x0D])),
+            ],
+            false,
+        );
+        assert_eq!(
+            a.content,
+            GroupContent::RadioText {
+                text_ab: true,
+                segment: 3,
+                text: vec!['H', 'i', '!', '\r'],
+            }
+        );
+        let b = parse([None, Some(block_b(2, true, 5)), None, Some(0x2424)], false);
+        assert_eq!(
+            b.content,
+            GroupContent::RadioText {
+                text_ab: false,
+                segment: 5,
+                text: vec!['¤', '¤'],
+            }
+        );
+    }
+
+    #[test]
+    fn version_b_groups_carry_pi_in_c_prime() {
+        let blocks = [None, Some(block_b(15, true, 0)), Some(0xD3C2), None];
+        assert_eq!(parse(blocks, true).pi, Some(0xD3C2));
+        assert_eq!(parse(blocks, false).pi, None);
+        assert_eq!(parse(blocks, true).content, GroupContent::Other);
+    }
+
+    #[test]
+    fn groups_without_block_b_are_dropped() {
+        let raw = RawGroup {
+            blocks: [Some(0xD3C2), None, Some(0), Some(0)],
+            c_prime: false,
+            corrected: 0,
+        };
+        assert!(Group::parse(&raw, &[]).is_none());
+    }
+
+    fn ps_group(pi: u16, segment: u16, text: &[u8; 2]) -> Group {
+        parse(
+            [
+                Some(pi),
+                Some(block_b(0, true, segment)),
+                Some(pi),
+                Some(u16::from_be_bytes(*text)),
+            ],
+            true,
+        )
+    }
+
+    fn text_group(ab: bool, segment: u16, text: &[u8; 4]) -> Group {
+        parse(
+            [
+                Some(0xD3C2),
+                Some(block_b(2, false, (ab as u16) << 4 | segment)),
+                Some(u16::from_be_bytes([text[0], text[1]])),
+                Some(u16::from_be_bytes([text[2], text[3]])),
+            ],
+            false,
+        )
+    }
+
+    #[test]
+    fn station_collects_the_ps_name() {
+        let mut station = Station::default();
+        for (segment, text) in [(0, b"RA"), (2, b"ON"), (1, b"DI")] {
+            station.update(&ps_group(0xD3C2, segment, text));
+            assert_eq!(station.ps(), None);
+        }
+        station.update(&ps_group(0xD3C2, 3, b"  "));
+        assert_eq!(station.ps().as_deref(), Some("RADION  "));
+        assert_eq!(station.pi, Some(0xD3C2));
+        assert_eq!(station.program_type, Some(10));
+
+        // A new station starts from scratch.
+        station.update(&ps_group(0xC201, 0, b"XX"));
+        assert_eq!(station.pi, Some(0xC201));
+        assert_eq!(station.ps(), None);
+    }
+
+    #[test]
+    fn station_assembles_radiotext() {
+        let mut station = Station::default();
+        station.update(&text_group(false, 1, b"o wo"));
+        assert_eq!(station.radiotext(), "    o wo");
+        station.update(&text_group(false, 0, b"Hell"));
+        station.update(&text_group(false, 2, b"rld\r"));
+        station.update(&text_group(false, 3, b"junk"));
+        assert_eq!(station.radiotext(), "Hello world");
+
+        // Toggling A/B starts a new text.
+        station.update(&text_group(true, 0, b"Next"));
+        assert_eq!(station.radiotext(), "Next");
+    }
+
+    #[test]
+    fn station_keeps_each_alternative_frequency_once() {
+        let mut station = Station::default();
+        for af in [(225u16 << 8) | 16, 204 << 8 | 16] {
+            station.update(&parse(
+                [
+                    Some(0xD3C2),
+                    Some(block_b(0, false, 0)),
+                    Some(af),
+                    Some(0x2020),
+                ],
+                false,
+            ));
+        }
+        assert_eq!(station.alternative_frequencies, [89_100_000, 107_900_000]);
+    }
+}
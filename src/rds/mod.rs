@@ -0,0 +1,190 @@
+//! RDS (Radio Data System) decoding from the FM multiplex.
+//!
+//! Feed the output of `demod::FmDemod` for a broadcast station to a
+//! `RdsDecoder` to get typed groups and the accumulated station information:
+//! programme identification, programme service name, RadioText and
+//! alternative frequencies. Each group carries a `decoders::Quality` from
+//! its checkwords and symbol margins.
+
+mod blocks;
+mod demod;
+mod group;
+
+pub use group::{AltFrequency, Group, GroupContent, GroupVersion, Station};
+
+use std::collections::VecDeque;
+
+use blocks::BlockSync;
+use demod::RdsDemod;
+
+use crate::decoders::quality::soft_bit;
+
+/// Bits in a group of four 26-bit blocks.
+const GROUP_BITS: usize = 104;
+
+/// The decoder's version, raised whenever a change could make it decode the
+/// same input differently. Recorded in `Provenance`.
+pub const VERSION: u32 = 1;
+
+/// Decodes RDS groups from an FM multiplex.
+pub struct RdsDecoder {
+    demod: RdsDemod,
+    sync: BlockSync,
+    bits: Vec<(bool, f32)>,
+    /// Soft decisions of the last group's worth of bits.
+    soft: VecDeque<i8>,
+    station: Station,
+}
+
+impl RdsDecoder {
+    /// Create a decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `mpx_rate` - Sample rate of the multiplex in Hz; at least 128 kHz so
+    ///   the 57 kHz subcarrier fits.
+    ///
+    /// # Returns
+    ///
+    /// A new `RdsDecoder`.
+    pub fn new(mpx_rate: f64) -> Self {
+        RdsDecoder {
+            demod: RdsDemod::new(mpx_rate),
+            sync: BlockSync::new(),
+            bits: Vec::new(),
+            soft: VecDeque::with_capacity(GROUP_BITS),
+            station: Station::default(),
+        }
+    }
+
+    /// Decode a chunk of the multiplex.
+    ///
+    /// # Arguments
+    ///
+    /// * `mpx` - Output of `FmDemod`, normalised to the 75 kHz deviation.
+    ///
+    /// # Returns
+    ///
+    /// Every group completed in this chunk. Groups without a decodable block
+    /// B are dropped since their type is unknown.
+    pub fn process(&mut self, mpx: &[f32]) -> Vec<Group> {
+        self.bits.clear();
+        self.demod.process(mpx, &mut self.bits);
+
+        let mut groups = Vec::new();
+        for &(bit, margin) in &self.bits {
+            if self.soft.len() == GROUP_BITS {
+                self.soft.pop_front();
+            }
+            self.soft
+                .push_back(soft_bit(if bit { margin } else { -margin }));
+            let Some(raw) = self.sync.push(bit) else {
+                continue;
+            };
+            if let Some(group) = Group::parse(&raw, self.soft.make_contiguous()) {
+                self.station.update(&group);
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// Check whether the decoder has found the block boundaries.
+    ///
+    /// # Returns
+    ///
+    /// `true` while groups are being received.
+    pub fn is_synced(&self) -> bool {
+        self.sync.is_synced()
+    }
+
+    /// Get the station information received so far.
+    ///
+    /// # Returns
+    ///
+    /// The accumulated `Station`, cleared whenever the PI code changes.
+    pub fn station(&self) -> &Station {
+        &self.station
+    }
+
+    /// Clear all state, e.g. after retuning.
+    pub fn reset(&mut self) {
+        self.demod.reset();
+        self.sync = BlockSync::new();
+        self.soft.clear();
+        self.station = Station::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::blocks::{encode, BlockKind};
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const MPX_RATE: f64 = 228_000.0;
+
+    /// Modulate groups onto the 57 kHz subcarrier the way a transmitter
+    /// does: differential coding, then biphase symbols on suppressed
+    /// carrier DSB.
+    fn modulate(groups: &[[u32; 4]], phase: f64) -> Vec<f32> {
+        let samples_per_bit = (MPX_RATE / 1187.5) as usize;
+        let mut level = false;
+        let mut mpx = Vec::new();
+        for block in groups.iter().flatten() {
+            for i in (0..26).rev() {
+                level ^= block >> i & 1 != 0;
+                for n in 0..samples_per_bit {
+                    let first_half = n < samples_per_bit / 2;
+                    let symbol = if level == first_half { 1.0 } else { -1.0 };
+                    let t = mpx.len() as f64 / MPX_RATE;
+                    let carrier = (TAU * 57_000.0 * t + phase).cos();
+                    mpx.push((0.05 * symbol * carrier) as f32);
+                }
+            }
+        }
+        mpx
+    }
+
+    fn ps_group(segment: u16, text: &[u8; 2]) -> [u32; 4] {
+        [
+            encode(0xD3C2, BlockKind::A),
+            encode(0x0540 | segment, BlockKind::B),
+            encode(0xE0CD, BlockKind::C),
+            encode(u16::from_be_bytes(*text), BlockKind::D),
+        ]
+    }
+
+    #[test]
+    fn decodes_a_station_from_the_multiplex() {
+        let segments = [b"RA", b"DI", b"ON", b"  "];
+        let groups: Vec<[u32; 4]> = (0..40)
+            .map(|i| ps_group(i % 4, segments[i as usize % 4]))
+            .collect();
+        let mpx = modulate(&groups, 1.0);
+
+        let mut decoder = RdsDecoder::new(MPX_RATE);
+        let mut decoded = Vec::new();
+        for chunk in mpx.chunks(4096) {
+            decoded.extend(decoder.process(chunk));
+        }
+        assert!(decoder.is_synced());
+        assert!(decoded.len() >= 30, "{} groups", decoded.len());
+        assert!(decoded.iter().all(|g| g.pi == Some(0xD3C2)));
+        let quality = decoded.last().unwrap().quality;
+        assert!(quality.crc_ok);
+        assert_eq!(quality.corrected_bits, 0);
+        assert!(quality.evm < 0.2, "{} evm", quality.evm);
+        assert!(
+            quality.bit_error_rate < 1e-6,
+            "{} ber",
+            quality.bit_error_rate
+        );
+        assert_eq!(decoder.station().ps().as_deref(), Some("RADION  "));
+        assert_eq!(decoder.station().program_type, Some(10));
+
+        decoder.reset();
+        assert!(!decoder.is_synced());
+        assert_eq!(decoder.station().pi, None);
+    }
+}
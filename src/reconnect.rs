@@ -0,0 +1,634 @@
+use std::fmt;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::builder::DeviceBuilder;
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use crate::tuner::SamplingMode;
+
+/// How far the wall clock must run ahead of the monotonic clock before
+/// `SleepDetector` reports a sleep, when not set with `SleepDetector::new`.
+pub const DEFAULT_SLEEP_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// Notices when the system has slept.
+///
+/// The monotonic clock stops while macOS and Linux sleep but the wall clock
+/// does not, so after a wake the wall clock has run ahead by about as long
+/// as the system slept. Stepping the wall clock forward, as a large NTP
+/// correction does, looks the same and is reported too.
+#[derive(Clone, Debug)]
+pub struct SleepDetector {
+    wall: SystemTime,
+    monotonic: Instant,
+    threshold: Duration,
+}
+
+impl Default for SleepDetector {
+    fn default() -> Self {
+        SleepDetector::new(DEFAULT_SLEEP_THRESHOLD)
+    }
+}
+
+impl SleepDetector {
+    /// Start watching for sleeps.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The shortest sleep reported.
+    pub fn new(threshold: Duration) -> Self {
+        SleepDetector {
+            wall: SystemTime::now(),
+            monotonic: Instant::now(),
+            threshold,
+        }
+    }
+
+    /// Check whether the system has slept since the last check.
+    ///
+    /// # Returns
+    ///
+    /// About how long it slept, or `None` if it has not.
+    pub fn check(&mut self) -> Option<Duration> {
+        self.observe(SystemTime::now(), Instant::now())
+    }
+
+    fn observe(&mut self, wall: SystemTime, monotonic: Instant) -> Option<Duration> {
+        let wall_elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        let elapsed = monotonic.saturating_duration_since(self.monotonic);
+        self.wall = wall;
+        self.monotonic = monotonic;
+        let slept = wall_elapsed.saturating_sub(elapsed);
+        (slept >= self.threshold).then_some(slept)
+    }
+}
+
+/// Something a `Reconnecting` source did to stay connected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReconnectEvent {
+    /// The system slept, so the source was closed to be reopened.
+    Woke {
+        /// About how long the system slept.
+        slept: Duration,
+    },
+    /// The source failed and was closed.
+    Lost {
+        /// What the failure was.
+        reason: String,
+    },
+    /// The source was reopened and its settings restored.
+    Reopened {
+        /// The number of attempts it took.
+        attempts: usize,
+    },
+}
+
+/// Formats the event, e.g. `reopened after 3 attempts`.
+impl fmt::Display for ReconnectEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconnectEvent::Woke { slept } => {
+                write!(f, "woke after sleeping {:.0} s", slept.as_secs_f64())
+            }
+            ReconnectEvent::Lost { reason } => write!(f, "lost: {}", reason),
+            ReconnectEvent::Reopened { attempts } => {
+                write!(f, "reopened after {} attempts", attempts)
+            }
+        }
+    }
+}
+
+type Opener<S> = Box<dyn FnMut() -> Result<S> + Send>;
+type Listener = Box<dyn FnMut(&ReconnectEvent) + Send>;
+
+/// A source that reopens itself when it fails or the system wakes from
+/// sleep, and restores its frequency, sample rate and gain.
+///
+/// After macOS sleeps, the dongle is re-enumerated on wake and the open
+/// handle silently stops delivering samples, so by default on macOS the
+/// source is also reopened whenever a `SleepDetector` notices a wake; see
+/// `with_wake_detection`. Reopening a `Device` goes by serial number or
+/// USB port rather than index, since indices can change when devices
+/// re-enumerate, and applies the `DeviceBuilder` settings it was opened
+/// with before the settings changed through `SdrSource` since.
+///
+/// A read that fails closes the source and reopens it, waiting the retry
+/// interval between attempts. With a limit set by `with_retry`, a read
+/// gives up with the last error once that many reads in a row have failed,
+/// or that many opens. A read of no samples is the end of the stream and
+/// is returned as it is, unless `with_empty_reads_lost` makes it a failure
+/// too, as the `Device` constructors do.
+///
+/// ```no_run
+/// use radion::{DeviceBuilder, Reconnecting, SdrSource};
+///
+/// let settings = DeviceBuilder::new(0).sample_rate(2_048_000).tuner_gain(296);
+/// let mut source = Reconnecting::open_by_serial("00000042", settings)?
+///     .with_listener(|event| eprintln!("dongle {}", event));
+/// source.tune(162_400_000)?;
+/// let mut buf = vec![0u8; 262_144];
+/// loop {
+///     // Keeps delivering samples across unplugging and sleep.
+///     let n = source.read(&mut buf)?;
+///     # let _ = n;
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct Reconnecting<S> {
+    open: Opener<S>,
+    source: Option<S>,
+    /// Settings made through `SdrSource`, restored after reopening.
+    freq_hz: Option<u32>,
+    rate_hz: Option<u32>,
+    gain: Option<Option<i32>>,
+    retry: Duration,
+    max_attempts: Option<usize>,
+    sleep: Option<SleepDetector>,
+    listener: Option<Listener>,
+    empty_reads_lost: bool,
+    reconnects: usize,
+}
+
+impl Reconnecting<Device> {
+    /// Open and supervise the only device with a serial number.
+    ///
+    /// Reads of no samples count as failures, as a dongle's stream has no
+    /// end.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - The serial number, see `Device::open_by_serial`.
+    /// * `settings` - The settings applied each time the device is opened;
+    ///   its index is ignored.
+    ///
+    /// # Returns
+    ///
+    /// The supervised device, or the `Error` of opening it the first time.
+    pub fn open_by_serial(serial: &str, settings: DeviceBuilder) -> Result<Self> {
+        let serial = serial.to_string();
+        Ok(
+            Reconnecting::new(move || open_device(Device::open_by_serial(&serial)?, &settings))?
+                .with_empty_reads_lost(true),
+        )
+    }
+
+    /// Open and supervise the device plugged into a USB port.
+    ///
+    /// Reads of no samples count as failures, as a dongle's stream has no
+    /// end.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The port, see `Device::open_by_usb_path`.
+    /// * `settings` - The settings applied each time the device is opened;
+    ///   its index is ignored.
+    ///
+    /// # Returns
+    ///
+    /// The supervised device, or the `Error` of opening it the first time.
+    pub fn open_by_usb_path(path: &str, settings: DeviceBuilder) -> Result<Self> {
+        let path = path.to_string();
+        Ok(
+            Reconnecting::new(move || open_device(Device::open_by_usb_path(&path)?, &settings))?
+                .with_empty_reads_lost(true),
+        )
+    }
+}
+
+fn open_device(device: Device, settings: &DeviceBuilder) -> Result<Device> {
+    settings.apply(&device)?;
+    device.reset_buffer()?;
+    Ok(device)
+}
+
+impl<S: SdrSource> Reconnecting<S> {
+    /// Open a source and supervise it.
+    ///
+    /// Defaults to retrying every second without limit, and to wake
+    /// detection on macOS only.
+    ///
+    /// # Arguments
+    ///
+    /// * `open` - Opens the source, ready to read; called again each time
+    ///   it is reopened.
+    ///
+    /// # Returns
+    ///
+    /// The supervised source, or the `Error` of opening it the first time.
+    pub fn new(mut open: impl FnMut() -> Result<S> + Send + 'static) -> Result<Self> {
+        let source = open()?;
+        Ok(Reconnecting {
+            open: Box::new(open),
+            source: Some(source),
+            freq_hz: None,
+            rate_hz: None,
+            gain: None,
+            retry: Duration::from_secs(1),
+            max_attempts: None,
+            sleep: cfg!(target_os = "macos").then(SleepDetector::default),
+            listener: None,
+            empty_reads_lost: false,
+            reconnects: 0,
+        })
+    }
+
+    /// Set how often reopening is tried and how many times.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The wait between attempts.
+    /// * `max_attempts` - The failed opens, or failed reads in a row,
+    ///   before a read gives up, or `None` to keep trying.
+    ///
+    /// # Returns
+    ///
+    /// The `Reconnecting` with the new retry policy.
+    pub fn with_retry(mut self, interval: Duration, max_attempts: Option<usize>) -> Self {
+        self.retry = interval;
+        self.max_attempts = max_attempts.map(|n| n.max(1));
+        self
+    }
+
+    /// Turn reopening after the system wakes from sleep on or off.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - The detector to check before each read, or `None`.
+    ///
+    /// # Returns
+    ///
+    /// The `Reconnecting` with or without wake detection.
+    pub fn with_wake_detection(mut self, detector: Option<SleepDetector>) -> Self {
+        self.sleep = detector;
+        self
+    }
+
+    /// Treat a read of no samples as a failure rather than the end of the
+    /// stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `lost` - Whether a read of no samples reopens the source.
+    ///
+    /// # Returns
+    ///
+    /// The `Reconnecting` with the new treatment of empty reads.
+    pub fn with_empty_reads_lost(mut self, lost: bool) -> Self {
+        self.empty_reads_lost = lost;
+        self
+    }
+
+    /// Call a function whenever the source is lost or reopened.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The function, called on the reading thread.
+    ///
+    /// # Returns
+    ///
+    /// The `Reconnecting` with the listener.
+    pub fn with_listener(mut self, listener: impl FnMut(&ReconnectEvent) + Send + 'static) -> Self {
+        self.listener = Some(Box::new(listener));
+        self
+    }
+
+    /// Get the number of times the source has been reopened.
+    pub fn reconnects(&self) -> usize {
+        self.reconnects
+    }
+
+    /// Check whether the source is open.
+    pub fn is_connected(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Get the source, while it is open.
+    pub fn get_ref(&self) -> Option<&S> {
+        self.source.as_ref()
+    }
+
+    fn emit(&mut self, event: ReconnectEvent) {
+        if let Some(listener) = &mut self.listener {
+            listener(&event);
+        }
+    }
+
+    /// Close the source, to be reopened on the next read.
+    fn lose(&mut self, event: ReconnectEvent) {
+        self.source = None;
+        self.emit(event);
+    }
+
+    /// Open the source again and restore its settings.
+    fn reopen(&mut self) -> Result<()> {
+        let mut attempts = 0;
+        loop {
+            if attempts > 0 {
+                thread::sleep(self.retry);
+            }
+            attempts += 1;
+            match (self.open)().and_then(|source| self.restore(source)) {
+                Ok(source) => {
+                    self.source = Some(source);
+                    self.reconnects += 1;
+                    if let Some(sleep) = &mut self.sleep {
+                        sleep.check();
+                    }
+                    self.emit(ReconnectEvent::Reopened { attempts });
+                    return Ok(());
+                }
+                Err(e) if self.max_attempts.is_some_and(|max| attempts >= max) => return Err(e),
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn restore(&self, mut source: S) -> Result<S> {
+        if let Some(rate) = self.rate_hz {
+            source.set_sample_rate(rate)?;
+        }
+        if let Some(freq) = self.freq_hz {
+            source.tune(freq)?;
+        }
+        if let Some(gain) = self.gain {
+            source.set_gain(gain)?;
+        }
+        Ok(source)
+    }
+
+    /// Run a call on the source, opening it first if it was lost.
+    fn with_source<T>(&mut self, call: impl FnOnce(&mut S) -> Result<T>) -> Result<T> {
+        if self.source.is_none() {
+            self.reopen()?;
+        }
+        call(self.source.as_mut().expect("reopened"))
+    }
+}
+
+impl<S: SdrSource> SdrSource for Reconnecting<S> {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        self.freq_hz = Some(freq_hz);
+        self.with_source(|s| s.tune(freq_hz))
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        match (&self.source, self.freq_hz) {
+            (Some(source), _) => source.center_freq(),
+            (None, Some(freq)) => Ok(freq),
+            (None, None) => Err(Error::NotFound),
+        }
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        self.rate_hz = Some(rate_hz);
+        self.with_source(|s| s.set_sample_rate(rate_hz))
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        match (&self.source, self.rate_hz) {
+            (Some(source), _) => source.sample_rate(),
+            (None, Some(rate)) => Ok(rate),
+            (None, None) => Err(Error::NotFound),
+        }
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.gain = Some(gain);
+        self.with_source(|s| s.set_gain(gain))
+    }
+
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        match &self.source {
+            Some(source) => source.sampling_mode(),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(slept) = self.sleep.as_mut().and_then(SleepDetector::check) {
+            self.lose(ReconnectEvent::Woke { slept });
+        }
+        let mut failures = 0;
+        loop {
+            if self.source.is_none() {
+                if failures > 0 {
+                    thread::sleep(self.retry);
+                }
+                self.reopen()?;
+            }
+            let source = self.source.as_mut().expect("reopened");
+            let error = match source.read(buf) {
+                Ok(0) if self.empty_reads_lost => {
+                    Error::System(io::Error::new(io::ErrorKind::UnexpectedEof, "no samples"))
+                }
+                Ok(n) => return Ok(n),
+                Err(e) => e,
+            };
+            failures += 1;
+            self.lose(ReconnectEvent::Lost {
+                reason: error.to_string(),
+            });
+            if self.max_attempts.is_some_and(|max| failures >= max) {
+                return Err(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A source that fails, or ends, after a number of reads, recording
+    /// what it was set to.
+    struct Flaky {
+        generation: u8,
+        reads_left: usize,
+        ends: bool,
+        freq_hz: u32,
+        rate_hz: u32,
+        gain: Option<i32>,
+    }
+
+    impl SdrSource for Flaky {
+        fn tune(&mut self, freq_hz: u32) -> Result<()> {
+            self.freq_hz = freq_hz;
+            Ok(())
+        }
+
+        fn center_freq(&self) -> Result<u32> {
+            Ok(self.freq_hz)
+        }
+
+        fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+            self.rate_hz = rate_hz;
+            Ok(())
+        }
+
+        fn sample_rate(&self) -> Result<u32> {
+            Ok(self.rate_hz)
+        }
+
+        fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+            self.gain = gain;
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.reads_left == 0 && self.ends {
+                return Ok(0);
+            }
+            if self.reads_left == 0 {
+                return Err(Error::System(std::io::ErrorKind::BrokenPipe.into()));
+            }
+            self.reads_left -= 1;
+            buf.fill(self.generation);
+            Ok(buf.len())
+        }
+    }
+
+    /// Opens `Flaky`s that each last a number of reads, failing the opens
+    /// listed.
+    fn opener(failing: &'static [u8], reads: usize) -> impl FnMut() -> Result<Flaky> + Send {
+        let mut generation = 0;
+        move || {
+            generation += 1;
+            if failing.contains(&generation) {
+                return Err(Error::NotFound);
+            }
+            Ok(Flaky {
+                generation,
+                reads_left: reads,
+                ends: false,
+                freq_hz: 0,
+                rate_hz: 0,
+                gain: None,
+            })
+        }
+    }
+
+    #[test]
+    fn reopens_and_restores_settings() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let mut source = Reconnecting::new(opener(&[2, 3], 2))
+            .unwrap()
+            .with_retry(Duration::ZERO, None)
+            .with_wake_detection(None)
+            .with_listener(move |e| log.lock().unwrap().push(e.clone()));
+        source.set_sample_rate(1_024_000).unwrap();
+        source.tune(162_400_000).unwrap();
+        source.set_gain(Some(296)).unwrap();
+
+        let mut buf = [0u8; 4];
+        let generations: Vec<u8> = (0..5)
+            .map(|_| {
+                source.read(&mut buf).unwrap();
+                buf[0]
+            })
+            .collect();
+        // The second and third opens fail, so the fourth takes over.
+        assert_eq!(generations, [1, 1, 4, 4, 5]);
+        assert_eq!(source.reconnects(), 2);
+        let restored = source.get_ref().unwrap();
+        assert_eq!(
+            (restored.freq_hz, restored.rate_hz, restored.gain),
+            (162_400_000, 1_024_000, Some(296))
+        );
+        let events = events.lock().unwrap();
+        assert!(matches!(events[0], ReconnectEvent::Lost { .. }));
+        assert_eq!(events[1], ReconnectEvent::Reopened { attempts: 3 });
+        assert_eq!(events[3], ReconnectEvent::Reopened { attempts: 1 });
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let mut source = Reconnecting::new(opener(&[2, 3, 4], 2))
+            .unwrap()
+            .with_retry(Duration::ZERO, Some(2))
+            .with_wake_detection(None);
+        let mut buf = [0u8; 4];
+        source.read(&mut buf).unwrap();
+        source.read(&mut buf).unwrap();
+        assert!(matches!(source.read(&mut buf), Err(Error::NotFound)));
+        assert!(!source.is_connected());
+        // Settings made while lost are kept for the next open.
+        source.tune(100_000_000).unwrap();
+        assert_eq!(source.get_ref().unwrap().freq_hz, 100_000_000);
+        assert_eq!(source.center_freq().unwrap(), 100_000_000);
+    }
+
+    #[test]
+    fn gives_up_on_a_source_that_opens_but_cannot_read() {
+        let mut source = Reconnecting::new(opener(&[], 0))
+            .unwrap()
+            .with_retry(Duration::ZERO, Some(3))
+            .with_wake_detection(None);
+        let mut buf = [0u8; 4];
+        assert!(matches!(source.read(&mut buf), Err(Error::System(_))));
+        assert_eq!(source.reconnects(), 2);
+    }
+
+    #[test]
+    fn passes_the_end_of_the_stream_through() {
+        let ending = || {
+            let mut flaky = opener(&[], 1);
+            move || flaky().map(|f| Flaky { ends: true, ..f })
+        };
+        let mut source = Reconnecting::new(ending())
+            .unwrap()
+            .with_wake_detection(None);
+        let mut buf = [0u8; 4];
+        assert_eq!(source.read(&mut buf).unwrap(), 4);
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+        assert_eq!(source.reconnects(), 0);
+
+        let mut source = Reconnecting::new(ending())
+            .unwrap()
+            .with_wake_detection(None)
+            .with_empty_reads_lost(true);
+        source.read(&mut buf).unwrap();
+        assert_eq!(source.read(&mut buf).unwrap(), 4);
+        assert_eq!((source.reconnects(), buf[0]), (1, 2));
+    }
+
+    #[test]
+    fn detects_sleep_from_the_clocks_drifting_apart() {
+        let mut detector = SleepDetector::new(Duration::from_secs(10));
+        let (wall, monotonic) = (detector.wall, detector.monotonic);
+        let awake = Duration::from_secs(30);
+        assert_eq!(detector.observe(wall + awake, monotonic + awake), None);
+        // The monotonic clock stood still for an hour.
+        let slept = Duration::from_secs(3600);
+        assert_eq!(
+            detector.observe(wall + awake + slept + awake, monotonic + awake + awake),
+            Some(slept)
+        );
+        assert_eq!(
+            detector.observe(wall + slept + awake * 3, monotonic + awake * 3),
+            None
+        );
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let mut asleep = SleepDetector::new(Duration::from_secs(10));
+        asleep.wall -= slept;
+        let mut source = Reconnecting::new(opener(&[], 2))
+            .unwrap()
+            .with_wake_detection(Some(asleep))
+            .with_listener(move |e| log.lock().unwrap().push(e.clone()));
+        let mut buf = [0u8; 4];
+        source.read(&mut buf).unwrap();
+        // Reopened before the dead handle was read.
+        assert_eq!(buf[0], 2);
+        assert!(
+            matches!(events.lock().unwrap()[0], ReconnectEvent::Woke { slept } if slept >= Duration::from_secs(3599))
+        );
+    }
+}
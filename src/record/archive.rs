@@ -0,0 +1,422 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::sigmf::{SigmfAnnotation, SigmfWriter};
+use crate::error::{Error, Result};
+use crate::file_source::IqFormat;
+use crate::Provenance;
+
+/// The disk space a `SliceArchive` may use unless told otherwise: 1 GiB.
+const DEFAULT_QUOTA: u64 = 1 << 30;
+
+/// Keeps the IQ a decoder was fed just before each of its decodes, so
+/// contested decodes can be looked at again, or run through a later
+/// version of the decoder.
+///
+/// Feed the archive the same narrowband IQ as the decoder. It keeps the
+/// most recent stretch of it, and `save` or `save_event` writes that
+/// stretch out as a SigMF recording whose annotation labels the decode.
+/// Recordings are numbered, and once they take up more than the quota the
+/// oldest are deleted, including any left by earlier runs.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use radion::decoders::ais::AisReceiver;
+/// use radion::events::Event;
+/// use radion::IqFormat;
+/// use radion::record::SliceArchive;
+///
+/// let mut archive =
+///     SliceArchive::new("slices", IqFormat::Cf32, 250_000, 162_000_000, Duration::from_millis(50))?
+///         .with_quota(100 << 20);
+/// let mut ais = AisReceiver::new(250_000.0);
+/// # let iq: Vec<radion::dsp::Complex<f32>> = Vec::new();
+/// archive.push_complex(&iq);
+/// for packet in ais.process(&iq) {
+///     archive.save_event(&Event::now(&packet))?;
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct SliceArchive {
+    dir: PathBuf,
+    format: IqFormat,
+    sample_rate: u32,
+    center_freq: u32,
+    /// The most recent samples, as raw bytes.
+    ring: VecDeque<u8>,
+    capacity: usize,
+    quota: u64,
+    /// Every recording in the directory, oldest first, with its size.
+    slices: VecDeque<(PathBuf, u64)>,
+    used: u64,
+    next: u64,
+    provenance: Provenance,
+}
+
+impl SliceArchive {
+    /// Open an archive, creating the directory if needed and counting any
+    /// recordings already in it against the quota.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory for the recordings.
+    /// * `format` - The format of the samples passed to `push`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    /// * `length` - How much IQ before each decode is kept.
+    ///
+    /// # Returns
+    ///
+    /// A new `SliceArchive` with a 1 GiB quota if successful, otherwise an
+    /// `Error`.
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+        length: Duration,
+    ) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut slices = Vec::new();
+        let mut next = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("sigmf-meta") {
+                continue;
+            }
+            let Some(seq) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.split('-').next())
+                .and_then(|s| s.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let base = path.with_extension("");
+            let size = slice_size(&base);
+            next = next.max(seq + 1);
+            slices.push((seq, base, size));
+        }
+        slices.sort();
+        let samples = (length.as_secs_f64() * sample_rate as f64).ceil() as usize;
+        Ok(SliceArchive {
+            dir,
+            format,
+            sample_rate,
+            center_freq,
+            ring: VecDeque::new(),
+            capacity: samples.max(1) * format.sample_size(),
+            quota: DEFAULT_QUOTA,
+            used: slices.iter().map(|(_, _, size)| size).sum(),
+            slices: slices
+                .into_iter()
+                .map(|(_, base, size)| (base, size))
+                .collect(),
+            next,
+            provenance: Provenance::current(),
+        })
+    }
+
+    /// Set the disk space the recordings may use.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The quota in bytes, metadata included.
+    ///
+    /// # Returns
+    ///
+    /// The `SliceArchive` with the new quota.
+    pub fn with_quota(mut self, bytes: u64) -> Self {
+        self.quota = bytes;
+        self
+    }
+
+    /// Set the provenance kept with each recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `provenance` - The provenance, by default `Provenance::current()`.
+    ///
+    /// # Returns
+    ///
+    /// The `SliceArchive` with the new provenance.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Get the directory recordings are written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the number of recordings in the archive.
+    pub fn len(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// Check whether the archive holds no recordings.
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// Get the disk space the recordings use.
+    ///
+    /// # Returns
+    ///
+    /// The size of every recording in bytes.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Add samples, dropping the oldest beyond the kept length.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Raw samples in the format given to `new`.
+    pub fn push(&mut self, samples: &[u8]) {
+        let samples = &samples[samples.len().saturating_sub(self.capacity)..];
+        let excess = (self.ring.len() + samples.len()).saturating_sub(self.capacity);
+        self.ring.drain(..excess);
+        self.ring.extend(samples);
+    }
+
+    /// Add complex samples to an archive of `IqFormat::Cf32`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - The samples, such as a channel from a `Channelizer`.
+    #[cfg(feature = "dsp")]
+    pub fn push_complex(&mut self, iq: &[crate::dsp::Complex<f32>]) {
+        debug_assert_eq!(self.format, IqFormat::Cf32);
+        let bytes: Vec<u8> = iq
+            .iter()
+            .flat_map(|s| s.re.to_le_bytes().into_iter().chain(s.im.to_le_bytes()))
+            .collect();
+        self.push(&bytes);
+    }
+
+    /// Forget the kept samples and record at a new frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The new center frequency in Hz.
+    pub fn retune(&mut self, center_freq: u32) {
+        self.center_freq = center_freq;
+        self.ring.clear();
+    }
+
+    /// Write the kept samples out as a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - What was decoded, e.g. `ais`, used in the file name and
+    ///   as the annotation's label.
+    /// * `comment` - Anything else to keep with it, such as the decode.
+    ///
+    /// # Returns
+    ///
+    /// The base name of the recording if successful, otherwise an `Error`.
+    /// `Error::InvalidParam` means a recording is larger than the quota.
+    pub fn save(&mut self, label: &str, comment: Option<&str>) -> Result<PathBuf> {
+        let held = Duration::from_secs_f64(
+            (self.ring.len() / self.format.sample_size()) as f64 / self.sample_rate as f64,
+        );
+        let start = SystemTime::now()
+            .checked_sub(held)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.write(label, comment, start)
+    }
+
+    /// Write the kept samples out as a recording of an event, labelled with
+    /// the event's type and with the event as JSON in the comment.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event, whose time is taken as the last sample's.
+    ///
+    /// # Returns
+    ///
+    /// The base name of the recording if successful, otherwise an `Error`.
+    #[cfg(feature = "events")]
+    pub fn save_event(&mut self, event: &crate::events::Event) -> Result<PathBuf> {
+        let json = serde_json::to_value(event).map_err(std::io::Error::other)?;
+        let label = json["type"].as_str().unwrap_or("event").to_string();
+        let held = (self.ring.len() / self.format.sample_size()) as f64 / self.sample_rate as f64;
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs_f64((event.time - held).max(0.0));
+        self.write(&label, Some(&json.to_string()), start)
+    }
+
+    fn write(&mut self, label: &str, comment: Option<&str>, start: SystemTime) -> Result<PathBuf> {
+        let base = self.dir.join(format!("{:06}-{}", self.next, label));
+        self.next += 1;
+        let (first, second) = self.ring.as_slices();
+        let mut writer =
+            SigmfWriter::create(&base, self.format, self.sample_rate, self.center_freq)?
+                .with_start_time(start)
+                .with_provenance(self.provenance.clone());
+        writer.write(first)?;
+        writer.write(second)?;
+        let count = writer.samples_written();
+        writer.annotate(SigmfAnnotation {
+            sample_start: 0,
+            sample_count: Some(count),
+            label: Some(label.to_string()),
+            comment: comment.map(str::to_string),
+            ..Default::default()
+        });
+        writer.finish()?;
+
+        let size = slice_size(&base);
+        if size > self.quota {
+            remove_slice(&base)?;
+            return Err(Error::InvalidParam);
+        }
+        self.slices.push_back((base.clone(), size));
+        self.used += size;
+        while self.used > self.quota {
+            let Some((oldest, size)) = self.slices.pop_front() else {
+                break;
+            };
+            remove_slice(&oldest)?;
+            self.used -= size;
+        }
+        Ok(base)
+    }
+}
+
+fn with_ext(base: &Path, ext: &str) -> PathBuf {
+    let mut name = base.as_os_str().to_owned();
+    name.push(ext);
+    PathBuf::from(name)
+}
+
+/// The size of both files of a recording, or 0 where one is missing.
+fn slice_size(base: &Path) -> u64 {
+    [".sigmf-meta", ".sigmf-data"]
+        .iter()
+        .filter_map(|ext| fs::metadata(with_ext(base, ext)).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn remove_slice(base: &Path) -> Result<()> {
+    for ext in [".sigmf-meta", ".sigmf-data"] {
+        match fs::remove_file(with_ext(base, ext)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::scratch;
+    use super::*;
+    use crate::record::SigmfReader;
+
+    fn open(dir: &Path) -> SliceArchive {
+        // 100 cu8 samples kept.
+        SliceArchive::new(
+            dir,
+            IqFormat::Cu8,
+            1_000,
+            162_000_000,
+            Duration::from_millis(100),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_the_latest_samples() {
+        let dir = scratch("slice-latest");
+        let mut archive = open(&dir);
+        let samples: Vec<u8> = (0..=255).collect();
+        archive.push(&samples[..150]);
+        archive.push(&samples[150..]);
+        let base = archive.save("ais", Some("packet")).unwrap();
+        assert_eq!(base, dir.join("000000-ais"));
+        let reader = SigmfReader::open(&base).unwrap();
+        assert_eq!(reader.len().unwrap(), 100);
+        assert_eq!(reader.center_freq(), Some(162_000_000.0));
+        let annotation = &reader.annotations()[0];
+        assert_eq!(annotation.sample_count, Some(100));
+        assert_eq!(annotation.label.as_deref(), Some("ais"));
+        assert_eq!(annotation.comment.as_deref(), Some("packet"));
+        let data = fs::read(reader.data_path()).unwrap();
+        assert_eq!(data, &samples[56..]);
+    }
+
+    #[test]
+    fn deletes_the_oldest_beyond_the_quota() {
+        let dir = scratch("slice-quota");
+        let mut archive = open(&dir);
+        archive.push(&[127; 200]);
+        let size = {
+            archive.save("adsb", None).unwrap();
+            archive.used()
+        };
+        let mut archive = archive.with_quota(2 * size + size / 2);
+        for _ in 0..3 {
+            archive.save("adsb", None).unwrap();
+        }
+        assert_eq!(archive.len(), 2);
+        assert!(archive.used() <= 2 * size + size / 2);
+        assert!(!with_ext(&dir.join("000001-adsb"), ".sigmf-data").exists());
+        assert!(with_ext(&dir.join("000003-adsb"), ".sigmf-meta").exists());
+
+        // A new run counts what's there and numbers on from it.
+        let mut reopened = open(&dir).with_quota(2 * size + size / 2);
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.used(), archive.used());
+        reopened.push(&[127; 200]);
+        assert_eq!(
+            reopened.save("adsb", None).unwrap(),
+            dir.join("000004-adsb")
+        );
+        assert_eq!(reopened.len(), 2);
+
+        let mut tiny = reopened.with_quota(10);
+        assert!(matches!(tiny.save("adsb", None), Err(Error::InvalidParam)));
+        assert!(!with_ext(&dir.join("000005-adsb"), ".sigmf-meta").exists());
+    }
+
+    #[cfg(feature = "events")]
+    #[test]
+    fn labels_recordings_with_their_event() {
+        use crate::events::{ActivityChange, Event, EventKind};
+
+        let dir = scratch("slice-event");
+        let mut archive = open(&dir);
+        archive.push(&[127; 200]);
+        let event = Event {
+            time: 1_700_000_000.5,
+            kind: EventKind::Activity(ActivityChange {
+                started: true,
+                freq_hz: 162_025_000.0,
+                bandwidth_hz: 25_000.0,
+                duration_s: 0.0,
+                peak_db: -50.0,
+                floor_db: -90.0,
+            }),
+        };
+        let base = archive.save_event(&event).unwrap();
+        assert_eq!(base, dir.join("000000-activity"));
+        let reader = SigmfReader::open(&base).unwrap();
+        // The 100 ms kept ends at the event.
+        let start = reader.captures()[0].datetime.clone().unwrap();
+        assert!(
+            ["2023-11-14T22:13:20.399Z", "2023-11-14T22:13:20.400Z"].contains(&start.as_str()),
+            "{}",
+            start
+        );
+        let comment = reader.annotations()[0].comment.clone().unwrap();
+        let back: Event = serde_json::from_str(&comment).unwrap();
+        assert_eq!(back.time, event.time);
+    }
+}
@@ -0,0 +1,447 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::flac::FlacWriter;
+use super::recorder::unique;
+use crate::error::Result;
+use crate::utils::UtcTime;
+
+/// File name template used by `AudioRecorder::new`.
+pub const DEFAULT_AUDIO_TEMPLATE: &str = "{date}_{time}_{freq}Hz.{ext}";
+
+/// File format of an `AudioRecorder`'s output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// 16-bit PCM WAV.
+    Wav,
+    /// 16-bit FLAC, typically half the size of WAV or less.
+    Flac,
+}
+
+/// A transmission saved by `AudioRecorder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transmission {
+    /// The file it was saved to.
+    pub path: PathBuf,
+    /// The frequency it was heard on in Hz.
+    pub frequency: u32,
+    /// When the squelch opened.
+    pub start: SystemTime,
+    /// The length of the audio saved, without the hang time.
+    pub duration: Duration,
+}
+
+/// Writer for 16-bit PCM WAV files.
+struct PcmWav {
+    writer: BufWriter<File>,
+    bytes: u64,
+}
+
+impl PcmWav {
+    fn create(path: &Path, sample_rate: u32, channels: u16) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let block_align = 2 * channels;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&36u32.to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(PcmWav { writer, bytes: 0 })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.bytes += 2 * samples.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<File> {
+        let bytes = self.bytes.min(u32::MAX as u64 - 36) as u32;
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(36 + bytes).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&bytes.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.into_inner().map_err(|e| e.into_error().into())
+    }
+}
+
+enum Sink {
+    Wav(PcmWav),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+struct Segment {
+    sink: Sink,
+    path: PathBuf,
+    start: SystemTime,
+    /// Frames written.
+    frames: u64,
+}
+
+/// Writer for demodulated audio that saves each transmission to a file of
+/// its own.
+///
+/// `write` is given the squelch state with every chunk of audio. A file is
+/// started when the squelch opens and closed once it has stayed shut for
+/// the hang time; audio heard while it is shut is held back, and written
+/// only if the squelch opens again within the hang time, so a fade doesn't
+/// split a transmission and no squelch tail is saved. Transmissions shorter
+/// than the minimum length, such as kerchunks, are deleted, and ones longer
+/// than the maximum are split.
+///
+/// Files are named from a template, in which these placeholders are
+/// replaced:
+///
+/// * `{date}` - the UTC date the squelch opened, as `YYYYMMDD`.
+/// * `{time}` - the UTC time the squelch opened, as `HHMMSS`.
+/// * `{millis}` - the milliseconds of that time, as `mmm`.
+/// * `{freq}` - the frequency in Hz.
+/// * `{seq}` - the number of the transmission, from 0.
+/// * `{ext}` - `wav` or `flac`.
+///
+/// An existing file is never overwritten; `-1`, `-2` and so on are added to
+/// the name instead.
+///
+/// ```no_run
+/// use radion::demod::FmDemod;
+/// use radion::dsp::fir::ChannelDecimator;
+/// use radion::record::{AudioFormat, AudioRecorder};
+/// use radion::samples::{to_complex_f32, Scaling};
+/// use radion::Device;
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(156_800_000)?;
+/// device.set_sample_rate(1_024_000)?;
+/// device.reset_buffer()?;
+///
+/// let mut channel = ChannelDecimator::new(64, 63);
+/// let mut fm = FmDemod::new(16_000.0, 5_000.0);
+/// let mut recorder =
+///     AudioRecorder::new("calls", 16_000, 1, 156_800_000).with_format(AudioFormat::Flac);
+/// loop {
+///     let iq = channel.decimate(&to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized));
+///     // A carrier squelch on the channel power.
+///     let power = iq.iter().map(|s| s.norm_sqr()).sum::<f32>() / iq.len() as f32;
+///     for call in recorder.write(&fm.process(&iq), power > 1e-3)? {
+///         println!("{} ({:?})", call.path.display(), call.duration);
+///     }
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct AudioRecorder {
+    dir: PathBuf,
+    template: String,
+    format: AudioFormat,
+    sample_rate: u32,
+    channels: u16,
+    frequency: u32,
+    hang: u64,
+    min_frames: u64,
+    max_frames: u64,
+    current: Option<Segment>,
+    /// Samples heard since the squelch shut, while the hang time runs.
+    held: Vec<f32>,
+    /// Transmissions saved, for `{seq}`.
+    saved: u64,
+    pcm: Vec<i16>,
+}
+
+impl AudioRecorder {
+    /// Create a recorder writing WAV files, with a 1 s hang time, no
+    /// minimum length and at most 10 minutes per file.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to write to, which must exist.
+    /// * `sample_rate` - The audio sample rate in Hz.
+    /// * `channels` - Interleaved channels in the audio, 1 or 2 for
+    ///   `FmStereo` output flattened with `as_flattened`.
+    /// * `frequency` - The frequency being recorded in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `AudioRecorder`.
+    pub fn new<P: AsRef<Path>>(dir: P, sample_rate: u32, channels: u16, frequency: u32) -> Self {
+        assert!(sample_rate > 0, "sample rate must be positive");
+        assert!((1..=8).contains(&channels), "channels must be 1 to 8");
+        let frames = |seconds: u64| seconds * sample_rate as u64;
+        AudioRecorder {
+            dir: dir.as_ref().to_path_buf(),
+            template: DEFAULT_AUDIO_TEMPLATE.to_owned(),
+            format: AudioFormat::Wav,
+            sample_rate,
+            channels,
+            frequency,
+            hang: frames(1),
+            min_frames: 0,
+            max_frames: frames(600),
+            current: None,
+            held: Vec::new(),
+            saved: 0,
+            pcm: Vec::new(),
+        }
+    }
+
+    /// Set the file format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format of the files.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioRecorder` with the new format.
+    pub fn with_format(mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the file name template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template, see `AudioRecorder` for the
+    ///   placeholders.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioRecorder` with the new template.
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Set how long the squelch may stay shut within one transmission.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time. Longer keeps the two sides of a quick
+    ///   exchange in one file.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioRecorder` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.hang = self.frames(hang);
+        self
+    }
+
+    /// Set the shortest transmission kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum length of audio; shorter transmissions are
+    ///   deleted when they end.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioRecorder` with the new minimum.
+    pub fn with_min_duration(mut self, min: Duration) -> Self {
+        self.min_frames = self.frames(min);
+        self
+    }
+
+    /// Set the longest file.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum length of audio per file; a longer
+    ///   transmission goes on in a new file.
+    ///
+    /// # Returns
+    ///
+    /// The `AudioRecorder` with the new maximum.
+    pub fn with_max_duration(mut self, max: Duration) -> Self {
+        self.max_frames = self.frames(max).max(1);
+        self
+    }
+
+    /// Get whether a transmission is being recorded, including during the
+    /// hang time.
+    pub fn is_recording(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Get the file being written, if any.
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|s| s.path.as_path())
+    }
+
+    /// Record audio.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of interleaved audio, from -1 to 1.
+    /// * `open` - Whether the squelch is open for the chunk.
+    ///
+    /// # Returns
+    ///
+    /// The transmissions that ended in the chunk if successful, otherwise
+    /// an `Error`.
+    pub fn write(&mut self, audio: &[f32], open: bool) -> Result<Vec<Transmission>> {
+        let mut ended = Vec::new();
+        if open {
+            if self.current.is_none() {
+                self.open()?;
+            }
+            // Audio from a dip in the hang time belongs to the transmission.
+            let held = std::mem::take(&mut self.held);
+            self.append(&held, &mut ended)?;
+            self.append(audio, &mut ended)?;
+        } else if self.current.is_some() {
+            self.held.extend_from_slice(audio);
+            if (self.held.len() / self.channels as usize) as u64 > self.hang {
+                ended.extend(self.close()?);
+            }
+        }
+        Ok(ended)
+    }
+
+    /// Record a change of frequency. Any transmission is ended, so the next
+    /// one is named with the new frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The new frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The transmission that was ended, if any and long enough to keep, if
+    /// successful, otherwise an `Error`.
+    pub fn retune(&mut self, frequency: u32) -> Result<Option<Transmission>> {
+        let ended = self.close()?;
+        self.frequency = frequency;
+        Ok(ended)
+    }
+
+    /// End the transmission being recorded, without waiting for the hang
+    /// time.
+    ///
+    /// # Returns
+    ///
+    /// The transmission, if any and long enough to keep, if successful,
+    /// otherwise an `Error`.
+    pub fn close(&mut self) -> Result<Option<Transmission>> {
+        self.held.clear();
+        let Some(segment) = self.current.take() else {
+            return Ok(None);
+        };
+        let file = match segment.sink {
+            Sink::Wav(w) => w.finish()?,
+            Sink::Flac(w) => w.finish()?.into_inner().map_err(|e| e.into_error())?,
+        };
+        file.sync_all()?;
+        if segment.frames < self.min_frames {
+            fs::remove_file(&segment.path)?;
+            return Ok(None);
+        }
+        self.saved += 1;
+        Ok(Some(Transmission {
+            path: segment.path,
+            frequency: self.frequency,
+            start: segment.start,
+            duration: Duration::from_secs_f64(segment.frames as f64 / self.sample_rate as f64),
+        }))
+    }
+
+    /// End the transmission being recorded and stop.
+    ///
+    /// # Returns
+    ///
+    /// The transmission, if any and long enough to keep, if successful,
+    /// otherwise an `Error`.
+    pub fn finish(mut self) -> Result<Option<Transmission>> {
+        self.close()
+    }
+
+    fn frames(&self, time: Duration) -> u64 {
+        (time.as_secs_f64() * self.sample_rate as f64) as u64
+    }
+
+    /// Write audio to the current file, splitting it at the maximum length.
+    fn append(&mut self, mut audio: &[f32], ended: &mut Vec<Transmission>) -> Result<()> {
+        let channels = self.channels as usize;
+        while audio.len() >= channels {
+            if self.current.is_none() {
+                self.open()?;
+            }
+            let segment = self.current.as_mut().expect("a file was just opened");
+            let room = (self.max_frames - segment.frames).min((audio.len() / channels) as u64);
+            let (now, rest) = audio.split_at(room as usize * channels);
+            self.pcm.clear();
+            self.pcm.extend(
+                now.iter()
+                    .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0).round() as i16),
+            );
+            match &mut segment.sink {
+                Sink::Wav(w) => w.write(&self.pcm)?,
+                Sink::Flac(w) => w.write(&self.pcm)?,
+            }
+            segment.frames += room;
+            audio = rest;
+            if segment.frames >= self.max_frames {
+                ended.extend(self.close()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let start = SystemTime::now();
+        let path = unique(self.dir.join(self.file_name(start)));
+        let sink = match self.format {
+            AudioFormat::Wav => Sink::Wav(PcmWav::create(&path, self.sample_rate, self.channels)?),
+            AudioFormat::Flac => Sink::Flac(FlacWriter::new(
+                BufWriter::new(File::create(&path)?),
+                self.sample_rate,
+                self.channels,
+            )?),
+        };
+        self.current = Some(Segment {
+            sink,
+            path,
+            start,
+            frames: 0,
+        });
+        Ok(())
+    }
+
+    fn file_name(&self, start: SystemTime) -> String {
+        let t = UtcTime::from_system_time(start);
+        let ext = match self.format {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        };
+        self.template
+            .replace(
+                "{date}",
+                &format!("{:04}{:02}{:02}", t.year, t.month, t.day),
+            )
+            .replace(
+                "{time}",
+                &format!("{:02}{:02}{:02}", t.hour, t.minute, t.second),
+            )
+            .replace("{millis}", &format!("{:03}", t.nanos / 1_000_000))
+            .replace("{freq}", &self.frequency.to_string())
+            .replace("{seq}", &self.saved.to_string())
+            .replace("{ext}", ext)
+    }
+}
+
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::error::Result;
+
+/// Inter-channel samples per frame.
+const BLOCK_SIZE: usize = 4096;
+
+/// Offset of the STREAMINFO frame sizes in the file.
+const FRAME_SIZES_OFFSET: u64 = 4 + 4 + 2 + 2;
+
+/// Largest Rice parameter of the 4-bit coding method.
+const MAX_RICE: u32 = 14;
+
+/// Writes bits most significant first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    fn put(&mut self, value: u64, bits: u32) {
+        for bit in (0..bits).rev() {
+            self.acc = (self.acc << 1) | ((value >> bit) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn put_signed(&mut self, value: i64, bits: u32) {
+        self.put(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn put_unary(&mut self, zeros: u64) {
+        for _ in 0..zeros {
+            self.put(0, 1);
+        }
+        self.put(1, 1);
+    }
+
+    /// Pad to a byte boundary with zeros.
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.put(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// The residual of a fixed predictor of `order`.
+fn residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let s = |n: usize| samples[n] as i64;
+    (order..samples.len())
+        .map(|n| {
+            (match order {
+                0 => s(n),
+                1 => s(n) - s(n - 1),
+                2 => s(n) - 2 * s(n - 1) + s(n - 2),
+                3 => s(n) - 3 * s(n - 1) + 3 * s(n - 2) - s(n - 3),
+                _ => s(n) - 4 * s(n - 1) + 6 * s(n - 2) - 4 * s(n - 3) + s(n - 4),
+            }) as i32
+        })
+        .collect()
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+/// The best Rice parameter for a residual and the bits it takes.
+fn rice_parameter(residual: &[i32]) -> (u32, u64) {
+    (0..=MAX_RICE)
+        .map(|k| {
+            let bits = residual
+                .iter()
+                .map(|&r| (zigzag(r) >> k) + 1 + k as u64)
+                .sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+/// Encodes 16-bit audio as FLAC, using a fixed predictor per channel and
+/// frame.
+pub(crate) struct FlacWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    channels: usize,
+    /// Interleaved samples not yet framed.
+    pending: Vec<i32>,
+    frames: u64,
+    samples: u64,
+    min_frame: u32,
+    max_frame: u32,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub(crate) fn new(mut writer: W, sample_rate: u32, channels: u16) -> Result<Self> {
+        assert!((1..=8).contains(&channels), "FLAC takes 1 to 8 channels");
+        let mut info = BitWriter::new();
+        info.put(BLOCK_SIZE as u64, 16);
+        info.put(BLOCK_SIZE as u64, 16);
+        info.put(0, 24);
+        info.put(0, 24);
+        info.put(sample_rate as u64, 20);
+        info.put(channels as u64 - 1, 3);
+        info.put(15, 5);
+        info.put(0, 36);
+        // The MD5 signature, left unset.
+        info.put(0, 64);
+        info.put(0, 64);
+
+        writer.write_all(b"fLaC")?;
+        // The last metadata block, STREAMINFO.
+        writer.write_all(&[0x80, 0, 0, info.bytes.len() as u8])?;
+        writer.write_all(&info.bytes)?;
+        Ok(FlacWriter {
+            writer,
+            sample_rate,
+            channels: channels as usize,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels as usize),
+            frames: 0,
+            samples: 0,
+            min_frame: u32::MAX,
+            max_frame: 0,
+        })
+    }
+
+    /// Append interleaved samples.
+    pub(crate) fn write(&mut self, samples: &[i16]) -> Result<()> {
+        for &sample in samples {
+            self.pending.push(sample as i32);
+            if self.pending.len() == BLOCK_SIZE * self.channels {
+                self.write_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the last frame and fill in the stream length.
+    pub(crate) fn finish(mut self) -> Result<W> {
+        if self.pending.len() >= self.channels {
+            let whole = self.pending.len() / self.channels * self.channels;
+            self.pending.truncate(whole);
+            self.write_frame()?;
+        }
+        // The frame sizes and the sample count, which shares bytes with
+        // the rate, channels and bit depth.
+        let mut info = BitWriter::new();
+        info.put(if self.frames > 0 { self.min_frame } else { 0 } as u64, 24);
+        info.put(self.max_frame as u64, 24);
+        info.put(self.sample_rate as u64, 20);
+        info.put(self.channels as u64 - 1, 3);
+        info.put(15, 5);
+        info.put(self.samples, 36);
+        self.writer.seek(SeekFrom::Start(FRAME_SIZES_OFFSET))?;
+        self.writer.write_all(&info.bytes)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_frame(&mut self) -> Result<()> {
+        let block = self.pending.len() / self.channels;
+        let mut frame = BitWriter::new();
+        frame.put(0xFFF8, 16);
+        // Block size from the end of the header, rate from STREAMINFO.
+        frame.put(0b0111, 4);
+        frame.put(0b0000, 4);
+        frame.put(self.channels as u64 - 1, 4);
+        frame.put(0b100, 3);
+        frame.put(0, 1);
+        put_utf8(&mut frame, self.frames);
+        frame.put(block as u64 - 1, 16);
+        let crc = crc8(&frame.bytes);
+        frame.put(crc as u64, 8);
+
+        for c in 0..self.channels {
+            let channel: Vec<i32> = self
+                .pending
+                .iter()
+                .skip(c)
+                .step_by(self.channels)
+                .copied()
+                .collect();
+            put_subframe(&mut frame, &channel);
+        }
+        frame.align();
+        let crc = crc16(&frame.bytes);
+        frame.put(crc as u64, 16);
+
+        self.writer.write_all(&frame.bytes)?;
+        let size = frame.bytes.len() as u32;
+        self.min_frame = self.min_frame.min(size);
+        self.max_frame = self.max_frame.max(size);
+        self.frames += 1;
+        self.samples += block as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// A frame number in FLAC's extended UTF-8 coding.
+fn put_utf8(bits: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        bits.put(value, 8);
+        return;
+    }
+    let mut continuation = 1;
+    while value >> (6 * continuation) >= 1 << (6 - continuation) {
+        continuation += 1;
+    }
+    let lead = (0xFF00u64 >> (continuation + 1)) & 0xFF;
+    bits.put(lead | (value >> (6 * continuation)), 8);
+    for n in (0..continuation).rev() {
+        bits.put(0x80 | ((value >> (6 * n)) & 0x3F), 8);
+    }
+}
+
+/// One channel of a frame: constant if it is, otherwise the cheaper of
+/// the best fixed predictor and verbatim.
+fn put_subframe(bits: &mut BitWriter, samples: &[i32]) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        bits.put(0, 8);
+        bits.put_signed(samples[0] as i64, 16);
+        return;
+    }
+    let verbatim = 16 * samples.len() as u64;
+    let best = (0..=4.min(samples.len() - 1))
+        .map(|order| {
+            let residual = residual(samples, order);
+            let (k, rice) = rice_parameter(&residual);
+            (order, residual, k, 16 * order as u64 + 6 + 4 + rice)
+        })
+        .min_by_key(|&(_, _, _, cost)| cost);
+    match best {
+        Some((order, residual, k, cost)) if cost < verbatim => {
+            bits.put(0b0001000 | order as u64, 7);
+            bits.put(0, 1);
+            for &s in &samples[..order] {
+                bits.put_signed(s as i64, 16);
+            }
+            // 4-bit Rice parameters, one partition.
+            bits.put(0b00, 2);
+            bits.put(0, 4);
+            bits.put(k as u64, 4);
+            for &r in &residual {
+                let u = zigzag(r);
+                bits.put_unary(u >> k);
+                bits.put(u & ((1 << k) - 1), k);
+            }
+        }
+        _ => {
+            bits.put(0b0000001, 7);
+            bits.put(0, 1);
+            for &s in samples {
+                bits.put_signed(s as i64, 16);
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Writing captures and demodulated audio to disk.
+
+#[cfg(feature = "sigmf")]
+mod archive;
+mod audio;
+mod flac;
+mod recorder;
+#[cfg(feature = "sigmf")]
+mod sigmf;
+mod triggered;
+mod wav;
+
+#[cfg(feature = "sigmf")]
+pub use archive::SliceArchive;
+pub use audio::{AudioFormat, AudioRecorder, Transmission, DEFAULT_AUDIO_TEMPLATE};
+pub use recorder::{Container, Recorder, RecordingHandle, SyncPolicy, DEFAULT_TEMPLATE};
+#[cfg(feature = "sigmf")]
+pub use sigmf::{SigmfAnnotation, SigmfCapture, SigmfReader, SigmfWriter};
+pub use triggered::{Burst, TriggeredCapture};
+pub use wav::WavWriter;
+
+/// An empty directory of its own for a test, under the system's temporary
+/// directory.
+#[cfg(test)]
+fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("radion-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,686 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "sigmf")]
+use super::SigmfWriter;
+use super::WavWriter;
+use crate::error::{Error, Result};
+use crate::file_source::IqFormat;
+use crate::source::SdrSource;
+use crate::utils::UtcTime;
+
+/// File name template used by `Recorder::new`.
+pub const DEFAULT_TEMPLATE: &str = "{date}_{time}_{freq}Hz_{rate}sps.{ext}";
+
+/// Bytes read from the source at a time by `Recorder::spawn`.
+const READ_CHUNK: usize = 256 * 1024;
+
+/// File format of a `Recorder`'s output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Container {
+    /// Bare samples, as written by `rtl_sdr`.
+    Raw,
+    /// 2-channel WAV, see `WavWriter`.
+    Wav,
+    /// A SigMF recording, see `SigmfWriter`.
+    #[cfg(feature = "sigmf")]
+    Sigmf,
+}
+
+/// When a `Recorder` forces data to disk.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Leave it to the operating system.
+    Never,
+    /// When a file is closed.
+    OnClose,
+    /// Periodically while writing, and when a file is closed.
+    Every(Duration),
+}
+
+enum Sink {
+    Raw(BufWriter<File>),
+    Wav(WavWriter),
+    #[cfg(feature = "sigmf")]
+    Sigmf(SigmfWriter),
+}
+
+impl Sink {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            Sink::Raw(w) => w.write_all(data)?,
+            Sink::Wav(w) => w.write(data)?,
+            #[cfg(feature = "sigmf")]
+            Sink::Sigmf(w) => w.write(data)?,
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        match self {
+            Sink::Raw(w) => {
+                w.flush()?;
+                w.get_ref().sync_data()?;
+            }
+            Sink::Wav(w) => {
+                w.get_mut().flush()?;
+                w.get_ref().get_ref().sync_data()?;
+            }
+            #[cfg(feature = "sigmf")]
+            Sink::Sigmf(w) => w.sync_data()?,
+        }
+        Ok(())
+    }
+
+    fn finish(self, sync: bool) -> Result<()> {
+        let file = match self {
+            Sink::Raw(w) => w.into_inner().map_err(|e| e.into_error())?,
+            Sink::Wav(w) => w.finish()?.into_inner().map_err(|e| e.into_error())?,
+            // Always synced, before its metadata is written.
+            #[cfg(feature = "sigmf")]
+            Sink::Sigmf(w) => return w.finish(),
+        };
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+struct OpenFile {
+    sink: Sink,
+    path: PathBuf,
+    bytes: u64,
+    last_sync: Instant,
+}
+
+/// Writer for long recordings split over several files.
+///
+/// A new file is started when the current one reaches the size or duration
+/// limit, or when the center frequency changes. Files are named from a
+/// template, in which these placeholders are replaced:
+///
+/// * `{date}` - the UTC date the file was started, as `YYYYMMDD`.
+/// * `{time}` - the UTC time the file was started, as `HHMMSS`.
+/// * `{freq}` - the center frequency in Hz.
+/// * `{rate}` - the sample rate in Hz.
+/// * `{seq}` - the number of the file in this recording, from 0.
+/// * `{ext}` - the usual extension of the container and sample format.
+///
+/// An existing file is never overwritten; `-1`, `-2` and so on are added to
+/// the name instead. Every file is finalized when it is closed, so its
+/// header and metadata are complete even if the recording is cut short.
+pub struct Recorder {
+    dir: PathBuf,
+    template: String,
+    format: IqFormat,
+    sample_rate: u32,
+    center_freq: u32,
+    container: Container,
+    max_size: Option<u64>,
+    max_duration: Option<Duration>,
+    sync: SyncPolicy,
+    #[cfg(feature = "sigmf")]
+    provenance: crate::Provenance,
+    current: Option<OpenFile>,
+    files: Vec<PathBuf>,
+}
+
+impl Recorder {
+    /// Create a recorder with no limits, the default template, raw files and
+    /// syncing on close.
+    ///
+    /// No file is created until the first samples are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to write to.
+    /// * `format` - The sample format of the data passed to `write`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `Recorder`.
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+    ) -> Self {
+        Recorder {
+            dir: dir.as_ref().to_path_buf(),
+            template: DEFAULT_TEMPLATE.to_owned(),
+            format,
+            sample_rate,
+            center_freq,
+            container: Container::Raw,
+            max_size: None,
+            max_duration: None,
+            sync: SyncPolicy::OnClose,
+            #[cfg(feature = "sigmf")]
+            provenance: crate::Provenance::current(),
+            current: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Set the file name template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template, see `Recorder` for the placeholders.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new template.
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Set the file format.
+    ///
+    /// # Arguments
+    ///
+    /// * `container` - The format of the files.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new format.
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Limit the size of each file.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The most sample data per file, rounded down to whole
+    ///   samples.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new limit.
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Limit the duration of each file.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The most recording time per file.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new limit.
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Set when data is forced to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `sync` - The sync policy.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new policy.
+    pub fn with_sync(mut self, sync: SyncPolicy) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Set the provenance kept in SigMF metadata.
+    ///
+    /// # Arguments
+    ///
+    /// * `provenance` - The provenance, by default `Provenance::current()`.
+    ///
+    /// # Returns
+    ///
+    /// The `Recorder` with the new provenance.
+    #[cfg(feature = "sigmf")]
+    pub fn with_provenance(mut self, provenance: crate::Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// The most bytes per file under both limits, in whole samples.
+    fn max_bytes(&self) -> u64 {
+        let size = self.format.sample_size() as u64;
+        let by_duration = self.max_duration.map(|d| {
+            let samples = (d.as_secs_f64() * self.sample_rate as f64) as u64;
+            samples.saturating_mul(size)
+        });
+        match (self.max_size, by_duration) {
+            (None, None) => u64::MAX,
+            (a, b) => (a.unwrap_or(u64::MAX).min(b.unwrap_or(u64::MAX)) / size * size).max(size),
+        }
+    }
+
+    /// Get the files closed so far.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Get the file being written, if any.
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|f| f.path.as_path())
+    }
+
+    /// Append samples, starting new files as the limits are reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Raw samples in the format given to `new`.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn write(&mut self, mut samples: &[u8]) -> Result<()> {
+        while !samples.is_empty() {
+            if self.current.is_none() {
+                self.open()?;
+            }
+            let max_bytes = self.max_bytes();
+            let file = self.current.as_mut().expect("a file was just opened");
+            let room = (max_bytes - file.bytes).min(samples.len() as u64) as usize;
+            file.sink.write(&samples[..room])?;
+            file.bytes += room as u64;
+            samples = &samples[room..];
+
+            if let SyncPolicy::Every(interval) = self.sync {
+                if file.last_sync.elapsed() >= interval {
+                    file.sink.sync()?;
+                    file.last_sync = Instant::now();
+                }
+            }
+            if file.bytes >= max_bytes {
+                self.close()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a change of center frequency. The current file is closed, so
+    /// the next samples start a file named and tagged with the new
+    /// frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The new center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn retune(&mut self, center_freq: u32) -> Result<()> {
+        if center_freq != self.center_freq {
+            self.close()?;
+            self.center_freq = center_freq;
+        }
+        Ok(())
+    }
+
+    /// Close and finalize the current file.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn close(&mut self) -> Result<()> {
+        if let Some(file) = self.current.take() {
+            file.sink.finish(self.sync != SyncPolicy::Never)?;
+            self.files.push(file.path);
+        }
+        Ok(())
+    }
+
+    /// Close the current file and end the recording.
+    ///
+    /// # Returns
+    ///
+    /// All files of the recording if successful, otherwise an `Error`.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.close()?;
+        Ok(self.files)
+    }
+
+    /// Record from a source on a background thread.
+    ///
+    /// The source's sample rate and center frequency replace the ones given
+    /// to `new`, and samples are read as `cu8` whatever format was given.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to record.
+    ///
+    /// # Returns
+    ///
+    /// A handle that stops the recording, or an `Error` if the source could
+    /// not be queried.
+    pub fn spawn<S>(mut self, mut source: S) -> Result<RecordingHandle<S>>
+    where
+        S: SdrSource + Send + 'static,
+    {
+        self.format = IqFormat::Cu8;
+        self.sample_rate = source.sample_rate()?;
+        self.center_freq = source.center_freq()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let worker = thread::spawn(move || {
+            let mut buf = vec![0; READ_CHUNK];
+            let mut ret = Ok(());
+            while !flag.load(Ordering::Acquire) {
+                let n = match source.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) => {
+                        ret = Err(e);
+                        break;
+                    }
+                };
+                if let Err(e) = self.write(&buf[..n]) {
+                    ret = Err(e);
+                    break;
+                }
+            }
+            let files = self.finish();
+            (source, ret.and(files))
+        });
+        Ok(RecordingHandle {
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let started = SystemTime::now();
+        let path = unique(self.dir.join(self.file_name(started)));
+        let sink = match self.container {
+            Container::Raw => Sink::Raw(BufWriter::new(File::create(&path)?)),
+            Container::Wav => Sink::Wav(WavWriter::create(
+                &path,
+                self.format,
+                self.sample_rate,
+                self.center_freq,
+            )?),
+            #[cfg(feature = "sigmf")]
+            Container::Sigmf => Sink::Sigmf(
+                SigmfWriter::create(&path, self.format, self.sample_rate, self.center_freq)?
+                    .with_provenance(self.provenance.clone()),
+            ),
+        };
+        self.current = Some(OpenFile {
+            sink,
+            path,
+            bytes: 0,
+            last_sync: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn file_name(&self, started: SystemTime) -> String {
+        let t = UtcTime::from_system_time(started);
+        let ext = match (self.container, self.format) {
+            (Container::Wav, _) => "wav",
+            #[cfg(feature = "sigmf")]
+            (Container::Sigmf, _) => "sigmf-data",
+            (Container::Raw, IqFormat::Cu8) => "cu8",
+            (Container::Raw, IqFormat::Cs16) => "cs16",
+            (Container::Raw, IqFormat::Cf32) => "cf32",
+        };
+        self.template
+            .replace(
+                "{date}",
+                &format!("{:04}{:02}{:02}", t.year, t.month, t.day),
+            )
+            .replace(
+                "{time}",
+                &format!("{:02}{:02}{:02}", t.hour, t.minute, t.second),
+            )
+            .replace("{freq}", &self.center_freq.to_string())
+            .replace("{rate}", &self.sample_rate.to_string())
+            .replace("{seq}", &self.files.len().to_string())
+            .replace("{ext}", ext)
+    }
+}
+
+/// `path`, or the first of `stem-1.ext`, `stem-2.ext` and so on that does
+/// not exist yet.
+pub(super) fn unique(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    // Keep compound extensions like `.sigmf-data` intact.
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (stem, ext) = match name.find('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name.as_str(), ""),
+    };
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .expect("some file name is free")
+}
+
+/// Guard for a recording running on a background thread.
+///
+/// Created by `Recorder::spawn`. Dropping the handle stops the recording
+/// and finalizes the current file, like `stop`.
+pub struct RecordingHandle<S> {
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<(S, Result<Vec<PathBuf>>)>>,
+}
+
+impl<S> RecordingHandle<S> {
+    /// Check whether the recording is still running.
+    ///
+    /// # Returns
+    ///
+    /// `false` once the recording has stopped, either because the source
+    /// ran out or because of an error.
+    pub fn is_running(&self) -> bool {
+        self.worker.as_ref().is_some_and(|w| !w.is_finished())
+    }
+
+    /// Stop the recording after the read in progress and finalize the
+    /// current file.
+    ///
+    /// If the recording thread panicked, the panic is resumed on the calling
+    /// thread.
+    ///
+    /// # Returns
+    ///
+    /// The source and the files of the recording if it ended cleanly,
+    /// otherwise the `Error` it failed with. Files are finalized either way.
+    pub fn stop(mut self) -> Result<(S, Vec<PathBuf>)> {
+        match self.finish() {
+            Some(Ok((source, Ok(files)))) => Ok((source, files)),
+            Some(Ok((_, Err(e)))) => Err(e),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Err(Error::Unknown),
+        }
+    }
+
+    fn finish(&mut self) -> Option<thread::Result<(S, Result<Vec<PathBuf>>)>> {
+        let worker = self.worker.take()?;
+        self.stop.store(true, Ordering::Release);
+        Some(worker.join())
+    }
+}
+
+impl<S> Drop for RecordingHandle<S> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(feature = "ctrlc")]
+impl<S> crate::Capture for RecordingHandle<S> {
+    type Output = (S, Vec<PathBuf>);
+
+    fn is_running(&self) -> bool {
+        RecordingHandle::is_running(self)
+    }
+
+    fn stop(self) -> Result<Self::Output> {
+        RecordingHandle::stop(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::scratch;
+    use super::*;
+    use crate::file_source::{FileSource, Pacing};
+    use std::fs;
+    use std::io::Cursor;
+
+    fn names(files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn rotates_at_the_size_limit() {
+        let dir = scratch("rotate-size");
+        let mut recorder = Recorder::new(&dir, IqFormat::Cu8, 2_048_000, 100_000_000)
+            .with_template("{seq}_{freq}.{ext}")
+            .with_max_size(10);
+        recorder.write(&[1; 7]).unwrap();
+        assert_eq!(
+            recorder.current_file(),
+            Some(dir.join("0_100000000.cu8").as_path())
+        );
+        recorder.write(&[2; 18]).unwrap();
+        assert_eq!(recorder.files().len(), 2);
+        let files = recorder.finish().unwrap();
+        assert_eq!(
+            names(&files),
+            ["0_100000000.cu8", "1_100000000.cu8", "2_100000000.cu8"]
+        );
+        let sizes: Vec<u64> = files
+            .iter()
+            .map(|f| fs::metadata(f).unwrap().len())
+            .collect();
+        assert_eq!(sizes, [10, 10, 5]);
+        assert_eq!(fs::read(&files[0]).unwrap(), [1, 1, 1, 1, 1, 1, 1, 2, 2, 2]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn limits_are_whole_samples() {
+        let dir = scratch("rotate-samples");
+        // 10 bytes is two and a half cs16 samples; 1.5 s at 4 S/s is six.
+        let mut by_size = Recorder::new(&dir, IqFormat::Cs16, 4, 0).with_max_size(10);
+        let by_duration = Recorder::new(&dir, IqFormat::Cu8, 4, 0)
+            .with_max_duration(Duration::from_millis(1_500))
+            .with_max_size(100);
+        assert_eq!(by_size.max_bytes(), 8);
+        assert_eq!(by_duration.max_bytes(), 12);
+        assert_eq!(
+            Recorder::new(&dir, IqFormat::Cf32, 4, 0)
+                .with_max_size(1)
+                .max_bytes(),
+            8
+        );
+        by_size.write(&[0; 20]).unwrap();
+        assert_eq!(by_size.files().len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn retuning_starts_a_new_file() {
+        let dir = scratch("retune");
+        let mut recorder =
+            Recorder::new(&dir, IqFormat::Cu8, 1_000, 100_000_000).with_template("{freq}.{ext}");
+        recorder.write(&[0; 4]).unwrap();
+        recorder.retune(100_000_000).unwrap();
+        assert!(recorder.current_file().is_some());
+        recorder.retune(101_000_000).unwrap();
+        assert!(recorder.current_file().is_none());
+        recorder.write(&[0; 4]).unwrap();
+        let files = recorder.finish().unwrap();
+        assert_eq!(names(&files), ["100000000.cu8", "101000000.cu8"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn never_overwrites_a_file() {
+        let dir = scratch("unique");
+        fs::write(dir.join("take.cu8"), b"keep").unwrap();
+        fs::write(dir.join("take-1.cu8"), b"keep").unwrap();
+        let mut recorder = Recorder::new(&dir, IqFormat::Cu8, 1_000, 0).with_template("take.{ext}");
+        recorder.write(&[0; 2]).unwrap();
+        let files = recorder.finish().unwrap();
+        assert_eq!(names(&files), ["take-2.cu8"]);
+        assert_eq!(fs::read(dir.join("take.cu8")).unwrap(), b"keep");
+
+        fs::write(dir.join("x.sigmf-data"), b"").unwrap();
+        assert_eq!(unique(dir.join("x.sigmf-data")), dir.join("x-1.sigmf-data"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn names_files_from_the_default_template() {
+        let recorder = Recorder::new("", IqFormat::Cs16, 2_048_000, 433_920_000);
+        // 2024-03-05 06:07:08 UTC.
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_618_828);
+        assert_eq!(
+            recorder.file_name(started),
+            "20240305_060708_433920000Hz_2048000sps.cs16"
+        );
+        let wav = Recorder::new("", IqFormat::Cu8, 1, 1).with_container(Container::Wav);
+        assert!(wav.file_name(started).ends_with(".wav"));
+    }
+
+    #[test]
+    fn writes_nothing_until_samples_arrive() {
+        let dir = scratch("empty");
+        let recorder = Recorder::new(&dir, IqFormat::Cu8, 1_000, 0);
+        assert!(recorder.finish().unwrap().is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn records_a_source_in_the_background() {
+        let dir = scratch("spawn");
+        let data: Vec<u8> = (0..=255).cycle().take(3_000).collect();
+        let source = FileSource::from_reader(Cursor::new(data.clone()), IqFormat::Cu8, 1_000)
+            .with_pacing(Pacing::Unpaced);
+        let handle = Recorder::new(&dir, IqFormat::Cs16, 1, 1)
+            .with_template("{rate}_{seq}.{ext}")
+            .with_container(Container::Wav)
+            .with_max_size(2_000)
+            .with_sync(SyncPolicy::Every(Duration::ZERO))
+            .spawn(source)
+            .unwrap();
+        while handle.is_running() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (_, files) = handle.stop().unwrap();
+        assert_eq!(names(&files), ["1000_0.wav", "1000_1.wav"]);
+        let first = fs::read(&files[0]).unwrap();
+        let header = first.len() - 2_000;
+        assert_eq!(first[header..], data[..2_000]);
+        assert_eq!(fs::read(&files[1]).unwrap()[header..], data[2_000..]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,716 @@
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::{Error, Result};
+use crate::file_source::{FileSource, IqFormat};
+use crate::utils::UtcTime;
+use crate::Provenance;
+
+/// SigMF specification version written by `SigmfWriter`.
+const SIGMF_VERSION: &str = "1.0.0";
+
+/// Version of the `radion` extension namespace holding the provenance.
+const EXTENSION_VERSION: &str = "1.0.0";
+
+/// A capture segment of a SigMF recording.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SigmfCapture {
+    /// Index of the first sample of the segment.
+    pub sample_start: u64,
+    /// Center frequency in Hz, if recorded.
+    pub frequency: Option<f64>,
+    /// Start of the segment as an ISO 8601 UTC timestamp, if recorded.
+    pub datetime: Option<String>,
+}
+
+/// An annotation on a range of samples of a SigMF recording.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SigmfAnnotation {
+    /// Index of the first annotated sample.
+    pub sample_start: u64,
+    /// Number of annotated samples, or `None` for the rest of the recording.
+    pub sample_count: Option<u64>,
+    /// Lower edge of the annotated signal in Hz.
+    pub freq_lower_edge: Option<f64>,
+    /// Upper edge of the annotated signal in Hz.
+    pub freq_upper_edge: Option<f64>,
+    /// Short label, e.g. the protocol.
+    pub label: Option<String>,
+    /// Free-form comment.
+    pub comment: Option<String>,
+}
+
+/// Paths of the metadata and data files of a recording, given either of them
+/// or their common base name.
+fn paths(path: &Path) -> (PathBuf, PathBuf) {
+    let base = match path.extension().and_then(|e| e.to_str()) {
+        Some("sigmf-meta") | Some("sigmf-data") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    let with = |ext: &str| {
+        let mut name = base.clone().into_os_string();
+        name.push(ext);
+        PathBuf::from(name)
+    };
+    (with(".sigmf-meta"), with(".sigmf-data"))
+}
+
+fn datatype(format: IqFormat) -> &'static str {
+    match format {
+        IqFormat::Cu8 => "cu8",
+        IqFormat::Cs16 => "ci16_le",
+        IqFormat::Cf32 => "cf32_le",
+    }
+}
+
+fn format_datetime(time: SystemTime) -> String {
+    let t = UtcTime::from_system_time(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        t.year,
+        t.month,
+        t.day,
+        t.hour,
+        t.minute,
+        t.second,
+        t.nanos / 1_000_000
+    )
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidMetadata(reason.into())
+}
+
+fn get_str(obj: &Value, key: &str) -> Option<String> {
+    obj.get(key).and_then(Value::as_str).map(str::to_owned)
+}
+
+fn get_u64(obj: &Value, key: &str) -> Result<Option<u64>> {
+    match obj.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| invalid(format!("{} is not a sample index", key))),
+    }
+}
+
+fn get_f64(obj: &Value, key: &str) -> Result<Option<f64>> {
+    match obj.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| invalid(format!("{} is not a number", key))),
+    }
+}
+
+/// Insert `value` under `key` unless it is `None`.
+fn put<T: Into<Value>>(obj: &mut Map<String, Value>, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        obj.insert(key.to_owned(), value.into());
+    }
+}
+
+/// Reader for SigMF recordings.
+///
+/// Parses and validates the `.sigmf-meta` file and plays the `.sigmf-data`
+/// file back through `FileSource`. Only single-channel complex recordings in
+/// the formats of `IqFormat` (`cu8`, `ci16_le` and `cf32_le`) are accepted.
+#[derive(Clone, Debug)]
+pub struct SigmfReader {
+    data_path: PathBuf,
+    format: IqFormat,
+    sample_rate: u32,
+    version: String,
+    description: Option<String>,
+    hardware: Option<String>,
+    recorder: Option<String>,
+    provenance: Option<Provenance>,
+    captures: Vec<SigmfCapture>,
+    annotations: Vec<SigmfAnnotation>,
+}
+
+impl SigmfReader {
+    /// Open a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The `.sigmf-meta` file, the `.sigmf-data` file, or their
+    ///   common base name.
+    ///
+    /// # Returns
+    ///
+    /// A new `SigmfReader` if successful, otherwise an `Error`.
+    /// `Error::InvalidMetadata` is returned if the metadata is malformed or
+    /// describes a recording that cannot be played back.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let (meta_path, data_path) = paths(path.as_ref());
+        let text = fs::read_to_string(&meta_path)?;
+        let meta: Value =
+            serde_json::from_str(&text).map_err(|e| invalid(format!("not valid JSON: {}", e)))?;
+
+        let global = meta
+            .get("global")
+            .filter(|g| g.is_object())
+            .ok_or_else(|| invalid("missing global object"))?;
+        let version =
+            get_str(global, "core:version").ok_or_else(|| invalid("missing core:version"))?;
+
+        let datatype =
+            get_str(global, "core:datatype").ok_or_else(|| invalid("missing core:datatype"))?;
+        let format = match datatype.as_str() {
+            "cu8" => IqFormat::Cu8,
+            "ci16_le" => IqFormat::Cs16,
+            "cf32_le" => IqFormat::Cf32,
+            other => return Err(invalid(format!("unsupported datatype {}", other))),
+        };
+        if get_u64(global, "core:num_channels")?.unwrap_or(1) != 1 {
+            return Err(invalid("only single-channel recordings are supported"));
+        }
+
+        let sample_rate = get_f64(global, "core:sample_rate")?
+            .ok_or_else(|| invalid("missing core:sample_rate"))?;
+        if !(sample_rate >= 1.0 && sample_rate <= u32::MAX as f64) {
+            return Err(invalid(format!(
+                "sample rate {} is out of range",
+                sample_rate
+            )));
+        }
+
+        let mut captures = Vec::new();
+        for c in meta
+            .get("captures")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            captures.push(SigmfCapture {
+                sample_start: get_u64(c, "core:sample_start")?
+                    .ok_or_else(|| invalid("capture without core:sample_start"))?,
+                frequency: get_f64(c, "core:frequency")?,
+                datetime: get_str(c, "core:datetime"),
+            });
+        }
+        captures.sort_by_key(|c| c.sample_start);
+
+        let mut annotations = Vec::new();
+        for a in meta
+            .get("annotations")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            annotations.push(SigmfAnnotation {
+                sample_start: get_u64(a, "core:sample_start")?
+                    .ok_or_else(|| invalid("annotation without core:sample_start"))?,
+                sample_count: get_u64(a, "core:sample_count")?,
+                freq_lower_edge: get_f64(a, "core:freq_lower_edge")?,
+                freq_upper_edge: get_f64(a, "core:freq_upper_edge")?,
+                label: get_str(a, "core:label"),
+                comment: get_str(a, "core:comment"),
+            });
+        }
+        annotations.sort_by_key(|a| a.sample_start);
+
+        Ok(SigmfReader {
+            data_path,
+            format,
+            sample_rate: sample_rate.round() as u32,
+            version,
+            description: get_str(global, "core:description"),
+            hardware: get_str(global, "core:hw"),
+            recorder: get_str(global, "core:recorder"),
+            provenance: get_str(global, "radion:version").map(|radion| Provenance {
+                radion,
+                decoders: global
+                    .get("radion:decoders")
+                    .and_then(Value::as_object)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(name, v)| Some((name.clone(), v.as_u64()? as u32)))
+                    .collect(),
+                config_hash: get_str(global, "radion:config_hash"),
+            }),
+            captures,
+            annotations,
+        })
+    }
+
+    /// Get the path of the data file.
+    pub fn data_path(&self) -> &Path {
+        &self.data_path
+    }
+
+    /// Get the sample format.
+    pub fn format(&self) -> IqFormat {
+        self.format
+    }
+
+    /// Get the sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Get the SigMF version the recording was written with.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Get the description of the recording.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Get the description of the hardware used.
+    pub fn hardware(&self) -> Option<&str> {
+        self.hardware.as_deref()
+    }
+
+    /// Get the name of the software that made the recording.
+    pub fn recorder(&self) -> Option<&str> {
+        self.recorder.as_deref()
+    }
+
+    /// Get what made the recording, if radion did.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    /// Get the capture segments, ordered by their first sample.
+    pub fn captures(&self) -> &[SigmfCapture] {
+        &self.captures
+    }
+
+    /// Get the annotations, ordered by their first sample.
+    pub fn annotations(&self) -> &[SigmfAnnotation] {
+        &self.annotations
+    }
+
+    /// Get the center frequency the recording starts at.
+    ///
+    /// # Returns
+    ///
+    /// The frequency of the first capture segment in Hz, or `None` if it is
+    /// not recorded.
+    pub fn center_freq(&self) -> Option<f64> {
+        self.captures.first().and_then(|c| c.frequency)
+    }
+
+    /// Get the number of samples in the data file.
+    ///
+    /// # Returns
+    ///
+    /// The number of whole samples if the file could be read, otherwise an
+    /// `Error`.
+    pub fn len(&self) -> Result<u64> {
+        Ok(fs::metadata(&self.data_path)?.len() / self.format.sample_size() as u64)
+    }
+
+    /// Check whether the data file holds no samples.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the data file is empty, or an `Error` if it could not be
+    /// read.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Play the recording back as an `SdrSource`.
+    ///
+    /// # Returns
+    ///
+    /// A real-time paced `FileSource` at the recording's sample rate and
+    /// starting center frequency if successful, otherwise an `Error`.
+    pub fn into_source(self) -> Result<FileSource> {
+        let source = FileSource::open(&self.data_path, self.format, self.sample_rate)?;
+        let freq = self.center_freq().unwrap_or(0.0);
+        Ok(source.with_center_freq(freq.clamp(0.0, u32::MAX as f64).round() as u32))
+    }
+}
+
+/// Writer for SigMF recordings.
+///
+/// Samples are written to the `.sigmf-data` file as they arrive; the
+/// `.sigmf-meta` file is written by `finish`. A recording that is never
+/// finished has its data but no metadata.
+pub struct SigmfWriter {
+    data: BufWriter<File>,
+    meta_path: PathBuf,
+    format: IqFormat,
+    sample_rate: u32,
+    description: Option<String>,
+    hardware: Option<String>,
+    provenance: Provenance,
+    captures: Vec<SigmfCapture>,
+    annotations: Vec<SigmfAnnotation>,
+    bytes: u64,
+}
+
+impl SigmfWriter {
+    /// Create a recording, replacing any existing one.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The base name of the recording; `.sigmf-meta` and
+    ///   `.sigmf-data` are appended, or replace either of those extensions.
+    /// * `format` - The sample format of the data passed to `write`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `SigmfWriter` if the data file could be created, otherwise an
+    /// `Error`.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+    ) -> Result<Self> {
+        let (meta_path, data_path) = paths(path.as_ref());
+        let data = BufWriter::new(File::create(data_path)?);
+        Ok(SigmfWriter {
+            data,
+            meta_path,
+            format,
+            sample_rate,
+            description: None,
+            hardware: None,
+            provenance: Provenance::current(),
+            captures: vec![SigmfCapture {
+                sample_start: 0,
+                frequency: Some(center_freq as f64),
+                datetime: Some(format_datetime(SystemTime::now())),
+            }],
+            annotations: Vec::new(),
+            bytes: 0,
+        })
+    }
+
+    /// Set the description of the recording.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the description of the hardware used.
+    pub fn with_hardware(mut self, hardware: impl Into<String>) -> Self {
+        self.hardware = Some(hardware.into());
+        self
+    }
+
+    /// Set the provenance kept in the metadata, e.g. to add the pipeline's
+    /// configuration. By default it's `Provenance::current()`.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Set when the first sample was received, for recordings written
+    /// after the fact. By default it's when the recording was created.
+    pub fn with_start_time(mut self, time: SystemTime) -> Self {
+        self.captures[0].datetime = Some(format_datetime(time));
+        self
+    }
+
+    /// Append samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Raw samples in the format given to `create`.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn write(&mut self, samples: &[u8]) -> Result<()> {
+        self.data.write_all(samples)?;
+        self.bytes += samples.len() as u64;
+        Ok(())
+    }
+
+    /// Get the number of whole samples written so far.
+    pub fn samples_written(&self) -> u64 {
+        self.bytes / self.format.sample_size() as u64
+    }
+
+    /// Flush the data written so far to disk.
+    pub(crate) fn sync_data(&mut self) -> Result<()> {
+        self.data.flush()?;
+        self.data.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    /// Start a new capture segment at the next sample, after a retune.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The new center frequency in Hz.
+    pub fn retune(&mut self, center_freq: u32) {
+        let sample_start = self.samples_written();
+        self.captures.retain(|c| c.sample_start != sample_start);
+        self.captures.push(SigmfCapture {
+            sample_start,
+            frequency: Some(center_freq as f64),
+            datetime: Some(format_datetime(SystemTime::now())),
+        });
+    }
+
+    /// Add an annotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotation` - The annotation, in sample indices of the recording.
+    pub fn annotate(&mut self, annotation: SigmfAnnotation) {
+        self.annotations.push(annotation);
+    }
+
+    /// Flush the data and write the metadata.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn finish(mut self) -> Result<()> {
+        self.data.flush()?;
+        self.data.get_ref().sync_all()?;
+
+        let mut global = Map::new();
+        global.insert("core:datatype".into(), datatype(self.format).into());
+        global.insert("core:sample_rate".into(), self.sample_rate.into());
+        global.insert("core:version".into(), SIGMF_VERSION.into());
+        global.insert(
+            "core:recorder".into(),
+            concat!("radion ", env!("CARGO_PKG_VERSION")).into(),
+        );
+        put(&mut global, "core:description", self.description);
+        put(&mut global, "core:hw", self.hardware);
+        global.insert(
+            "core:extensions".into(),
+            json!([{ "name": "radion", "version": EXTENSION_VERSION, "optional": true }]),
+        );
+        global.insert("radion:version".into(), self.provenance.radion.into());
+        global.insert(
+            "radion:decoders".into(),
+            Value::Object(
+                self.provenance
+                    .decoders
+                    .into_iter()
+                    .map(|(name, version)| (name, version.into()))
+                    .collect(),
+            ),
+        );
+        put(
+            &mut global,
+            "radion:config_hash",
+            self.provenance.config_hash,
+        );
+
+        let captures: Vec<Value> = self
+            .captures
+            .into_iter()
+            .map(|c| {
+                let mut obj = Map::new();
+                obj.insert("core:sample_start".into(), c.sample_start.into());
+                put(&mut obj, "core:frequency", c.frequency);
+                put(&mut obj, "core:datetime", c.datetime);
+                Value::Object(obj)
+            })
+            .collect();
+
+        self.annotations.sort_by_key(|a| a.sample_start);
+        let annotations: Vec<Value> = self
+            .annotations
+            .into_iter()
+            .map(|a| {
+                let mut obj = Map::new();
+                obj.insert("core:sample_start".into(), a.sample_start.into());
+                put(&mut obj, "core:sample_count", a.sample_count);
+                put(&mut obj, "core:freq_lower_edge", a.freq_lower_edge);
+                put(&mut obj, "core:freq_upper_edge", a.freq_upper_edge);
+                put(&mut obj, "core:label", a.label);
+                put(&mut obj, "core:comment", a.comment);
+                Value::Object(obj)
+            })
+            .collect();
+
+        let meta = json!({
+            "global": global,
+            "captures": captures,
+            "annotations": annotations,
+        });
+        let text = serde_json::to_string_pretty(&meta).map_err(std::io::Error::other)?;
+        fs::write(&self.meta_path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::scratch;
+    use super::*;
+    use crate::source::SdrSource;
+
+    #[test]
+    fn round_trips_metadata_and_samples() {
+        let dir = scratch("sigmf-round-trip");
+        let base = dir.join("capture");
+        let mut writer = SigmfWriter::create(&base, IqFormat::Cs16, 2_048_000, 100_000_000)
+            .unwrap()
+            .with_description("test capture")
+            .with_hardware("RTL-SDR Blog V4")
+            .with_provenance(Provenance::current().with_config("rtl_fm -f 100M"));
+        let samples: Vec<u8> = (0..40).collect();
+        writer.write(&samples[..20]).unwrap();
+        writer.retune(101_000_000);
+        writer.write(&samples[20..]).unwrap();
+        assert_eq!(writer.samples_written(), 10);
+        let burst = SigmfAnnotation {
+            sample_start: 6,
+            sample_count: Some(3),
+            freq_lower_edge: Some(100_990_000.0),
+            freq_upper_edge: Some(101_010_000.0),
+            label: Some("POCSAG".into()),
+            comment: Some("page".into()),
+        };
+        writer.annotate(burst.clone());
+        writer.annotate(SigmfAnnotation {
+            sample_start: 1,
+            ..Default::default()
+        });
+        writer.finish().unwrap();
+
+        let reader = SigmfReader::open(dir.join("capture.sigmf-meta")).unwrap();
+        assert_eq!(reader.format(), IqFormat::Cs16);
+        assert_eq!(reader.sample_rate(), 2_048_000);
+        assert_eq!(reader.version(), SIGMF_VERSION);
+        assert_eq!(reader.description(), Some("test capture"));
+        assert_eq!(reader.hardware(), Some("RTL-SDR Blog V4"));
+        assert!(reader.recorder().unwrap().starts_with("radion "));
+        assert_eq!(
+            reader.provenance(),
+            Some(&Provenance::current().with_config("rtl_fm -f 100M"))
+        );
+        assert_eq!(reader.center_freq(), Some(100_000_000.0));
+        let captures: Vec<_> = reader
+            .captures()
+            .iter()
+            .map(|c| (c.sample_start, c.frequency))
+            .collect();
+        assert_eq!(
+            captures,
+            [(0, Some(100_000_000.0)), (5, Some(101_000_000.0))]
+        );
+        assert!(reader.captures()[0]
+            .datetime
+            .as_ref()
+            .unwrap()
+            .ends_with('Z'));
+        assert_eq!(reader.annotations().len(), 2);
+        assert_eq!(reader.annotations()[0].sample_start, 1);
+        assert_eq!(reader.annotations()[1], burst);
+        assert_eq!(reader.len().unwrap(), 10);
+
+        let mut source = reader.into_source().unwrap();
+        assert_eq!(source.center_freq().unwrap(), 100_000_000);
+        assert_eq!(source.sample_rate().unwrap(), 2_048_000);
+        assert_eq!(fs::read(dir.join("capture.sigmf-data")).unwrap(), samples);
+        // The source converts to cu8 from the high byte of each component.
+        let mut buf = [0; 8];
+        source.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [129, 131, 133, 135, 137, 139, 141, 143]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn opens_by_either_file_or_base_name() {
+        let base = Path::new("/data/pass");
+        let both = (
+            PathBuf::from("/data/pass.sigmf-meta"),
+            PathBuf::from("/data/pass.sigmf-data"),
+        );
+        assert_eq!(paths(base), both);
+        assert_eq!(paths(&base.with_extension("sigmf-meta")), both);
+        assert_eq!(paths(&base.with_extension("sigmf-data")), both);
+        assert_eq!(
+            paths(Path::new("noaa.19")).0,
+            PathBuf::from("noaa.19.sigmf-meta")
+        );
+    }
+
+    #[test]
+    fn a_retune_before_any_sample_replaces_the_first_capture() {
+        let dir = scratch("sigmf-retune");
+        let mut writer = SigmfWriter::create(dir.join("x"), IqFormat::Cu8, 1_000, 1).unwrap();
+        writer.retune(2);
+        writer.finish().unwrap();
+        let reader = SigmfReader::open(dir.join("x")).unwrap();
+        assert_eq!(reader.captures().len(), 1);
+        assert_eq!(reader.center_freq(), Some(2.0));
+        assert!(reader.is_empty().unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_metadata_it_cannot_play() {
+        let dir = scratch("sigmf-invalid");
+        let global = |extra: &str| {
+            format!(
+                r#"{{"global": {{"core:version": "1.0.0", {}}}, "captures": []}}"#,
+                extra
+            )
+        };
+        for (meta, reason) in [
+            ("not json".to_string(), "not valid JSON"),
+            (r#"{"captures": []}"#.to_string(), "missing global"),
+            (
+                global(r#""core:datatype": "cu8""#),
+                "missing core:sample_rate",
+            ),
+            (
+                global(r#""core:datatype": "ri16_le", "core:sample_rate": 1000"#),
+                "unsupported datatype ri16_le",
+            ),
+            (
+                global(r#""core:datatype": "cu8", "core:sample_rate": 0.5"#),
+                "out of range",
+            ),
+            (
+                global(r#""core:datatype": "cu8", "core:sample_rate": 1e3, "core:num_channels": 2"#),
+                "single-channel",
+            ),
+            (
+                r#"{"global": {"core:version": "1.0.0", "core:datatype": "cu8", "core:sample_rate": 1000}, "captures": [{"core:sample_start": -1}]}"#
+                    .to_string(),
+                "core:sample_start is not a sample index",
+            ),
+        ] {
+            fs::write(dir.join("bad.sigmf-meta"), &meta).unwrap();
+            match SigmfReader::open(dir.join("bad")) {
+                Err(Error::InvalidMetadata(e)) => {
+                    assert!(e.contains(reason), "{:?} for {}", e, meta)
+                }
+                other => panic!("{:?} for {}", other.map(|r| r.format()), meta),
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_writes_sigmf() {
+        let dir = scratch("sigmf-recorder");
+        let mut recorder = crate::record::Recorder::new(&dir, IqFormat::Cu8, 1_000, 433_920_000)
+            .with_template("{freq}")
+            .with_container(crate::record::Container::Sigmf);
+        recorder.write(&[1, 2, 3, 4]).unwrap();
+        let files = recorder.finish().unwrap();
+        assert_eq!(files, [dir.join("433920000")]);
+        let reader = SigmfReader::open(&files[0]).unwrap();
+        assert_eq!(reader.center_freq(), Some(433_920_000.0));
+        assert_eq!(reader.len().unwrap(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
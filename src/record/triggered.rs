@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+use crate::utils::UtcTime;
+
+/// A burst saved by `TriggeredCapture`.
+#[derive(Clone, Debug)]
+pub struct Burst {
+    /// When the trigger fired.
+    pub time: SystemTime,
+    /// Center frequency of the source in Hz.
+    pub center_freq: u32,
+    /// Sample rate of the source in Hz.
+    pub sample_rate: u32,
+    /// Unsigned 8-bit I/Q pairs, from the pre-trigger history to the end of
+    /// the post-trigger period.
+    pub data: Vec<u8>,
+    /// Byte offset in `data` of the block that fired the trigger.
+    pub trigger_offset: usize,
+}
+
+impl Burst {
+    /// Get the length of the burst.
+    ///
+    /// # Returns
+    ///
+    /// The time covered by `data`.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.data.len() as f64 / 2.0 / self.sample_rate.max(1) as f64)
+    }
+
+    /// Get a file name describing the burst, in the style of `rtl_433`
+    /// dumps.
+    ///
+    /// # Returns
+    ///
+    /// A name like `burst_20240131T120000.123Z_433920000Hz_1024000sps.cu8`.
+    pub fn file_name(&self) -> String {
+        let t = UtcTime::from_system_time(self.time);
+        format!(
+            "burst_{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z_{}Hz_{}sps.cu8",
+            t.year,
+            t.month,
+            t.day,
+            t.hour,
+            t.minute,
+            t.second,
+            t.nanos / 1_000_000,
+            self.center_freq,
+            self.sample_rate
+        )
+    }
+
+    /// Save the burst as a raw cu8 file, readable by `FileSource`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&self.data)?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    }
+}
+
+type Condition = Box<dyn FnMut(&[u8]) -> bool + Send>;
+
+/// Capture that waits for a trigger and keeps the samples around it.
+///
+/// Samples are read from the source in short blocks. While idle, the most
+/// recent blocks are kept in a ring covering the pre-trigger time. When a
+/// block meets the trigger condition, the ring and every following block is
+/// collected until the condition has been false for the post-trigger time,
+/// so a burst of any length is kept whole, up to a maximum length.
+///
+/// The default condition is the mean power of a block reaching a threshold
+/// in dBFS; `with_trigger` replaces it with any test on the raw cu8 block.
+pub struct TriggeredCapture<S> {
+    source: S,
+    pre: usize,
+    post: usize,
+    max: usize,
+    ring: VecDeque<u8>,
+    condition: Condition,
+    buf: Vec<u8>,
+}
+
+/// Number of bytes covering `time` at `rate`, rounded up to a multiple of 512
+/// as librtlsdr requires.
+fn bytes_for(rate: u32, time: Duration) -> usize {
+    let bytes = (time.as_secs_f64() * rate as f64 * 2.0).ceil() as usize;
+    bytes.div_ceil(512) * 512
+}
+
+/// Mean power of a cu8 block in dBFS, where a full-scale complex tone reads
+/// 0 dBFS.
+fn block_dbfs(block: &[u8]) -> f32 {
+    let sum: f64 = block
+        .iter()
+        .map(|&x| {
+            let v = (x as f64 - 127.5) / 127.5;
+            v * v
+        })
+        .sum();
+    let power = sum / (block.len() / 2).max(1) as f64;
+    (10.0 * power.max(1e-20).log10()) as f32
+}
+
+impl<S: SdrSource> TriggeredCapture<S> {
+    /// Create a capture at the source's current sample rate.
+    ///
+    /// Defaults to a power trigger at -20 dBFS, 1 ms blocks and bursts of at
+    /// most 10 s.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to capture from.
+    /// * `pre` - Time kept before the block that fired the trigger.
+    /// * `post` - Time kept after the last block that met the condition.
+    ///
+    /// # Returns
+    ///
+    /// A new `TriggeredCapture` if the sample rate could be read, otherwise
+    /// an `Error`.
+    pub fn new(source: S, pre: Duration, post: Duration) -> Result<Self> {
+        let rate = source.sample_rate()?;
+        let block = bytes_for(rate, Duration::from_millis(1));
+        let capture = TriggeredCapture {
+            source,
+            pre: bytes_for(rate, pre),
+            post: bytes_for(rate, post),
+            max: bytes_for(rate, Duration::from_secs(10)),
+            ring: VecDeque::new(),
+            condition: Box::new(|_| false),
+            buf: vec![0; block],
+        };
+        Ok(capture.with_power_trigger(-20.0))
+    }
+
+    /// Trigger when the mean power of a block reaches a level.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_dbfs` - The level in dBFS. The dongle's noise floor is
+    ///   typically 30 to 40 dB below full scale.
+    ///
+    /// # Returns
+    ///
+    /// The `TriggeredCapture` with the new trigger.
+    pub fn with_power_trigger(mut self, threshold_dbfs: f32) -> Self {
+        self.condition = Box::new(move |block| block_dbfs(block) >= threshold_dbfs);
+        self
+    }
+
+    /// Trigger on a custom condition.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - Called with every block of raw cu8 samples; returns
+    ///   `true` when the block should trigger or extend a capture.
+    ///
+    /// # Returns
+    ///
+    /// The `TriggeredCapture` with the new trigger.
+    pub fn with_trigger<F>(mut self, condition: F) -> Self
+    where
+        F: FnMut(&[u8]) -> bool + Send + 'static,
+    {
+        self.condition = Box::new(condition);
+        self
+    }
+
+    /// Set how long a block is.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The time covered by each block the trigger sees. Shorter
+    ///   blocks react faster; longer ones average out noise.
+    ///
+    /// # Returns
+    ///
+    /// The `TriggeredCapture` with the new block length if the sample rate
+    /// could be read, otherwise an `Error`.
+    pub fn with_block(mut self, block: Duration) -> Result<Self> {
+        self.buf = vec![0; bytes_for(self.source.sample_rate()?, block)];
+        Ok(self)
+    }
+
+    /// Set the longest burst kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum time from the start of the pre-trigger history.
+    ///   A burst that reaches it is returned and a new one can start with the
+    ///   next block.
+    ///
+    /// # Returns
+    ///
+    /// The `TriggeredCapture` with the new maximum if the sample rate could
+    /// be read, otherwise an `Error`.
+    pub fn with_max_duration(mut self, max: Duration) -> Result<Self> {
+        self.max = bytes_for(self.source.sample_rate()?, max);
+        Ok(self)
+    }
+
+    /// Wait for the next burst.
+    ///
+    /// # Returns
+    ///
+    /// The burst if successful, otherwise an `Error`. Reaching the end of a
+    /// finite source while a burst is being collected returns what was
+    /// collected; reaching it while idle returns `Error::PartialRead`.
+    pub fn next_burst(&mut self) -> Result<Burst> {
+        loop {
+            self.source.read_exact(&mut self.buf)?;
+            if (self.condition)(&self.buf) {
+                break;
+            }
+            self.ring.extend(&self.buf);
+            let excess = self.ring.len().saturating_sub(self.pre);
+            self.ring.drain(..excess);
+        }
+
+        let time = SystemTime::now();
+        let mut data: Vec<u8> = self.ring.drain(..).collect();
+        let trigger_offset = data.len();
+        data.extend_from_slice(&self.buf);
+
+        let mut quiet = 0;
+        while quiet < self.post && data.len() < self.max {
+            match self.source.read_exact(&mut self.buf) {
+                Ok(()) => {}
+                Err(Error::PartialRead { .. }) => break,
+                Err(e) => return Err(e),
+            }
+            data.extend_from_slice(&self.buf);
+            if (self.condition)(&self.buf) {
+                quiet = 0;
+            } else {
+                quiet += self.buf.len();
+            }
+        }
+
+        Ok(Burst {
+            time,
+            center_freq: self.source.center_freq()?,
+            sample_rate: self.source.sample_rate()?,
+            data,
+            trigger_offset,
+        })
+    }
+
+    /// Wait for the next burst and save it in a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to write to; the file is named by
+    ///   `Burst::file_name`.
+    ///
+    /// # Returns
+    ///
+    /// The path of the new file if successful, otherwise an `Error`.
+    pub fn save_next<P: AsRef<Path>>(&mut self, dir: P) -> Result<PathBuf> {
+        let burst = self.next_burst()?;
+        let path = dir.as_ref().join(burst.file_name());
+        burst.save(&path)?;
+        Ok(path)
+    }
+
+    /// Give back the source.
+    ///
+    /// # Returns
+    ///
+    /// The source the capture was created with.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+use crate::file_source::IqFormat;
+use crate::utils::UtcTime;
+
+/// Size of the `ds64` chunk body reserved by a `JUNK` chunk, so a file can
+/// be turned into RF64 in place.
+const DS64_LEN: u32 = 28;
+
+/// Size of the `auxi` chunk body: two SYSTEMTIMEs, nine DWORDs and a
+/// 96-byte next file name.
+const AUXI_LEN: u32 = 164;
+
+/// Offset of the `auxi` stop time in the file.
+const AUXI_STOP_OFFSET: u64 = 12 + 8 + DS64_LEN as u64 + 8 + 16 + 8 + 16;
+
+/// Offset of the first sample in the file.
+const DATA_OFFSET: u64 = 12 + 8 + DS64_LEN as u64 + 8 + 16 + 8 + AUXI_LEN as u64 + 8;
+
+/// Windows SYSTEMTIME for `time`, in UTC.
+fn system_time(time: SystemTime) -> [u8; 16] {
+    let t = UtcTime::from_system_time(time);
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86_400;
+    // 1970-01-01 was a Thursday; SYSTEMTIME counts from Sunday.
+    let weekday = (days + 4) % 7;
+    let fields = [
+        t.year as u16,
+        t.month as u16,
+        weekday as u16,
+        t.day as u16,
+        t.hour as u16,
+        t.minute as u16,
+        t.second as u16,
+        (t.nanos / 1_000_000) as u16,
+    ];
+    let mut out = [0; 16];
+    for (chunk, field) in out.chunks_exact_mut(2).zip(fields) {
+        chunk.copy_from_slice(&field.to_le_bytes());
+    }
+    out
+}
+
+/// Writer for IQ recordings as 2-channel WAV files.
+///
+/// I goes in the left channel and Q in the right. `cu8` samples are stored
+/// as 8-bit PCM, which is unsigned like the dongle's output, `cs16` as 16-bit
+/// PCM and `cf32` as 32-bit float. An `auxi` chunk carries the center
+/// frequency, sample rate and start and stop times the way SDR# and HDSDR
+/// write it, so recordings open in those tools with the right frequency
+/// scale.
+///
+/// Files that grow past 4 GB are turned into RF64 by `finish`, using space
+/// reserved in the header, so there is no size limit and no rewrite.
+pub struct WavWriter<W: Write + Seek = BufWriter<File>> {
+    writer: W,
+    format: IqFormat,
+    bytes: u64,
+}
+
+impl WavWriter {
+    /// Create a recording, replacing any existing file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    /// * `format` - The sample format of the data passed to `write`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `WavWriter` if successful, otherwise an `Error`.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+    ) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Self::new(file, format, sample_rate, center_freq)
+    }
+
+    /// Get the file name SDR# gives IQ recordings, from which it and other
+    /// tools read the center frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The center frequency in Hz.
+    /// * `time` - The start of the recording.
+    ///
+    /// # Returns
+    ///
+    /// A name like `SDRSharp_20240131_120000Z_100000000Hz_IQ.wav`.
+    pub fn file_name(center_freq: u32, time: SystemTime) -> String {
+        let t = UtcTime::from_system_time(time);
+        format!(
+            "SDRSharp_{:04}{:02}{:02}_{:02}{:02}{:02}Z_{}Hz_IQ.wav",
+            t.year, t.month, t.day, t.hour, t.minute, t.second, center_freq
+        )
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Start a recording on any seekable writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the file, positioned at its start.
+    /// * `format` - The sample format of the data passed to `write`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `WavWriter` if the header could be written, otherwise an
+    /// `Error`.
+    pub fn new(
+        mut writer: W,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+    ) -> Result<Self> {
+        let (tag, bits): (u16, u16) = match format {
+            IqFormat::Cu8 => (1, 8),
+            IqFormat::Cs16 => (1, 16),
+            IqFormat::Cf32 => (3, 32),
+        };
+        let block_align = format.sample_size() as u16;
+        let now = system_time(SystemTime::now());
+
+        let mut header = Vec::with_capacity(DATA_OFFSET as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+
+        header.extend_from_slice(b"JUNK");
+        header.extend_from_slice(&DS64_LEN.to_le_bytes());
+        header.extend_from_slice(&[0; DS64_LEN as usize]);
+
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&tag.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&bits.to_le_bytes());
+
+        header.extend_from_slice(b"auxi");
+        header.extend_from_slice(&AUXI_LEN.to_le_bytes());
+        header.extend_from_slice(&now);
+        header.extend_from_slice(&now);
+        // CenterFreq, ADFrequency, IFFrequency, Bandwidth, IQOffset and four
+        // unused DWORDs.
+        for dword in [center_freq, sample_rate, 0, sample_rate, 0, 0, 0, 0, 0] {
+            header.extend_from_slice(&dword.to_le_bytes());
+        }
+        header.extend_from_slice(&[0; 96]);
+
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        debug_assert_eq!(header.len() as u64, DATA_OFFSET);
+
+        writer.write_all(&header)?;
+        Ok(WavWriter {
+            writer,
+            format,
+            bytes: 0,
+        })
+    }
+
+    /// Append samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Raw samples in the format given to `new`.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn write(&mut self, samples: &[u8]) -> Result<()> {
+        self.writer.write_all(samples)?;
+        self.bytes += samples.len() as u64;
+        Ok(())
+    }
+
+    /// Get the number of whole samples written so far.
+    pub fn samples_written(&self) -> u64 {
+        self.bytes / self.format.sample_size() as u64
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying writer. Writing to it
+    /// directly corrupts the file.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Fill in the sizes and stop time, switching to RF64 if needed.
+    ///
+    /// # Returns
+    ///
+    /// The underlying writer if successful, otherwise an `Error`.
+    pub fn finish(mut self) -> Result<W> {
+        if self.bytes % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        let riff_len = DATA_OFFSET - 8 + self.bytes.next_multiple_of(2);
+
+        self.writer.seek(SeekFrom::Start(AUXI_STOP_OFFSET))?;
+        self.writer.write_all(&system_time(SystemTime::now()))?;
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        if riff_len <= u32::MAX as u64 {
+            self.writer.write_all(b"RIFF")?;
+            self.writer.write_all(&(riff_len as u32).to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(DATA_OFFSET - 4))?;
+            self.writer.write_all(&(self.bytes as u32).to_le_bytes())?;
+        } else {
+            self.writer.write_all(b"RF64")?;
+            self.writer.write_all(&u32::MAX.to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(12))?;
+            self.writer.write_all(b"ds64")?;
+            self.writer.seek(SeekFrom::Current(4))?;
+            self.writer.write_all(&riff_len.to_le_bytes())?;
+            self.writer.write_all(&self.bytes.to_le_bytes())?;
+            self.writer
+                .write_all(&self.samples_written().to_le_bytes())?;
+            self.writer.write_all(&0u32.to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(DATA_OFFSET - 4))?;
+            self.writer.write_all(&u32::MAX.to_le_bytes())?;
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,402 @@
+//! Remote monitoring over links too slow for raw IQ.
+//!
+//! A `RemoteNode` runs at the receiver and sends upstream only what a
+//! monitoring station needs to watch the band: spectra decimated to a few
+//! hundred bins of one byte each, at most once per interval, and decoded
+//! `Event`s. It keeps the last few seconds of raw IQ in memory, so the
+//! station can ask for a short raw capture around an event with
+//! `RemoteStation::request_capture` and get it once the capture's window
+//! has passed, or be told it is unavailable when the window lies further
+//! ahead than the history is long. A spectrum of 512 bins every 5 seconds
+//! costs under 1 kbit/s, where raw IQ at 1 Msps costs 16 Mbit/s, which
+//! makes monitoring stations on cellular connections practical. The first
+//! frame a node sends is a `provenance` event, as `JsonlSink` writes
+//! first.
+//!
+//! The node connects out to the station, since cellular connections rarely
+//! accept incoming ones, and never blocks the loop that reads samples:
+//! what the link does not take yet is queued, and spectra are dropped
+//! while the queue is over its limit. Both directions use frames with a
+//! 12-byte little-endian header:
+//!
+//! | Offset | Size | Field                              |
+//! |--------|------|------------------------------------|
+//! | 0      | 4    | `RDRM`                             |
+//! | 4      | 1    | Version, 1                         |
+//! | 5      | 1    | Kind, see below                    |
+//! | 6      | 2    | Reserved, 0                        |
+//! | 8      | 4    | Payload length in bytes            |
+//!
+//! | Kind | Direction | Payload                                                                 |
+//! |------|-----------|-------------------------------------------------------------------------|
+//! | 1    | up        | Spectrum: time f64, first bin f64 Hz, bin width f64 Hz, floor f32 dB, one byte per bin in 0.5 dB steps above the floor |
+//! | 2    | up        | Event in its JSON form                                                  |
+//! | 3    | up        | Capture: id u32, first sample time f64, centre u32 Hz, rate u32 Hz, `cu8` samples |
+//! | 4    | up        | Capture unavailable: id u32                                             |
+//! | 5    | down      | Capture request: id u32, time f64, before u32 ms, after u32 ms          |
+//!
+//! Times are seconds since the Unix epoch.
+//!
+//! ```no_run
+//! use std::time::{Duration, SystemTime};
+//!
+//! use radion::remote::RemoteNode;
+//! use radion::scan::PowerSweep;
+//! use radion::Device;
+//!
+//! let mut node = RemoteNode::connect("monitor.example.org:7355")?
+//!     .with_bins(512)
+//!     .with_spectrum_interval(Duration::from_secs(5))
+//!     .with_history(Duration::from_secs(10));
+//! let mut sweep = PowerSweep::new(Device::new(0)?, 430_000_000, 431_000_000, 1_000.0)?;
+//! loop {
+//!     let table = sweep.sweep()?;
+//!     node.send_spectrum(SystemTime::now(), &table)?;
+//!     // Samples read for decoding go to `node.push_samples` too, and
+//!     // decoded frames to `node.send_event`.
+//!     node.poll()?;
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod node;
+mod station;
+
+pub use node::{RemoteNode, DEFAULT_BACKLOG};
+pub use station::{RemoteStation, Upstream};
+
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+/// Bytes of header at the start of every frame.
+pub const FRAME_HEADER_LEN: usize = 12;
+
+/// Identifies radion remote monitoring frames.
+const MAGIC: &[u8; 4] = b"RDRM";
+
+/// Frame layout version.
+const VERSION: u8 = 1;
+
+/// The largest payload accepted, well above any capture's.
+const MAX_PAYLOAD: usize = 64 << 20;
+
+/// The level step of a spectrum bin in dB.
+const DB_STEP: f32 = 0.5;
+
+const SPECTRUM: u8 = 1;
+const EVENT: u8 = 2;
+const CAPTURE: u8 = 3;
+const UNAVAILABLE: u8 = 4;
+const REQUEST: u8 = 5;
+
+/// A short raw recording a station asked a node for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    /// The id `RemoteStation::request_capture` returned.
+    pub id: u32,
+    /// When the first sample was taken.
+    pub time: SystemTime,
+    /// The centre frequency in Hz.
+    pub centre_hz: u32,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// Interleaved `cu8` IQ samples.
+    pub samples: Vec<u8>,
+}
+
+impl Capture {
+    /// Get how long the capture lasts.
+    ///
+    /// # Returns
+    ///
+    /// The time the samples span.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / 2.0 / self.sample_rate.max(1) as f64)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+fn from_unix_seconds(secs: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::try_from_secs_f64(secs).unwrap_or_default()
+}
+
+/// Append a frame to a buffer.
+fn write_frame(out: &mut Vec<u8>, kind: u8, payload: &[u8]) {
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[VERSION, kind, 0, 0]);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Take a whole frame off the front of a buffer.
+///
+/// # Returns
+///
+/// The kind and payload of the first frame if it has arrived in full,
+/// `None` if more bytes are needed, or `Error::InvalidMetadata` if the
+/// bytes are not a frame.
+fn take_frame(input: &mut Vec<u8>) -> Result<Option<(u8, Vec<u8>)>> {
+    if input.len() < FRAME_HEADER_LEN {
+        return Ok(None);
+    }
+    if &input[0..4] != MAGIC || input[4] != VERSION {
+        return Err(Error::InvalidMetadata(
+            "not a remote monitoring frame".to_string(),
+        ));
+    }
+    let len = u32::from_le_bytes(input[8..12].try_into().unwrap()) as usize;
+    if len > MAX_PAYLOAD {
+        return Err(Error::InvalidMetadata(format!("frame of {} bytes", len)));
+    }
+    if input.len() < FRAME_HEADER_LEN + len {
+        return Ok(None);
+    }
+    let kind = input[5];
+    let payload = input[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len].to_vec();
+    input.drain(..FRAME_HEADER_LEN + len);
+    Ok(Some((kind, payload)))
+}
+
+/// Reads little-endian fields from a payload.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(Error::InvalidMetadata("frame too short".to_string()));
+        }
+        let (field, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(field.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+}
+
+/// Read from a non-blocking stream until it has nothing more.
+///
+/// # Returns
+///
+/// `false` once the stream has closed, otherwise `true`.
+fn read_available<R: Read>(stream: &mut R, input: &mut Vec<u8>) -> Result<bool> {
+    let mut scratch = [0u8; 4096];
+    loop {
+        match stream.read(&mut scratch) {
+            Ok(0) => return Ok(false),
+            Ok(n) => input.extend_from_slice(&scratch[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{ActivityChange, Event, EventKind};
+    use crate::scan::PowerTable;
+    use std::net::{TcpListener, TcpStream};
+
+    fn link() -> (RemoteNode<TcpStream>, RemoteStation<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let node = RemoteNode::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        (node, RemoteStation::new(stream))
+    }
+
+    /// Take the `provenance` event a node sends first.
+    fn provenance(station: &mut RemoteStation<TcpStream>) -> crate::Provenance {
+        match station.recv().unwrap().unwrap() {
+            Upstream::Event(Event {
+                kind: EventKind::Provenance(provenance),
+                ..
+            }) => provenance,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn at(secs: f64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs_f64(1_760_000_000.0 + secs)
+    }
+
+    #[test]
+    fn sends_decimated_spectra_and_events() {
+        let (node, mut station) = link();
+        let mut node = node
+            .with_bins(100)
+            .with_spectrum_interval(Duration::from_secs(5))
+            .with_provenance(crate::Provenance::current().with_config("remote test"));
+        let mut db: Vec<f32> = (0..1000).map(|i| -90.0 + (i % 3) as f32 * 0.25).collect();
+        db[437] = -40.2;
+        let table = PowerTable {
+            start_hz: 430_000_000.0,
+            step_hz: 1_000.0,
+            db,
+        };
+        assert!(node.send_spectrum(at(0.0), &table).unwrap());
+        assert!(!node.send_spectrum(at(2.0), &table).unwrap());
+        let event = Event::new(
+            at(3.0),
+            EventKind::Activity(ActivityChange {
+                started: true,
+                freq_hz: 430_437_500.0,
+                bandwidth_hz: 12_500.0,
+                duration_s: 0.0,
+                peak_db: -40.2,
+                floor_db: -90.0,
+            }),
+        );
+        node.send_event(&event).unwrap();
+        assert!(node.send_spectrum(at(5.0), &table).unwrap());
+
+        assert_eq!(
+            provenance(&mut station),
+            crate::Provenance::current().with_config("remote test")
+        );
+        let Upstream::Spectrum { time, table: sent } = station.recv().unwrap().unwrap() else {
+            panic!("expected a spectrum");
+        };
+        assert_eq!(time, at(0.0));
+        assert_eq!((sent.start_hz, sent.step_hz), (430_000_000.0, 10_000.0));
+        assert_eq!(sent.db.len(), 100);
+        // The carrier survives decimation, to the nearest half dB.
+        assert_eq!(sent.db[43], -40.0);
+        assert_eq!(sent.db[0], -89.5);
+        match station.recv().unwrap().unwrap() {
+            Upstream::Event(received) => assert_eq!(received, event),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            station.recv().unwrap(),
+            Some(Upstream::Spectrum { .. })
+        ));
+        // 12 + 28 + 100 bytes per spectrum, and a few hundred for the
+        // provenance and the event.
+        assert_eq!(node.bytes_sent(), station.bytes_received());
+        assert!(node.bytes_sent() < 2 * 140 + 1000);
+    }
+
+    #[test]
+    fn captures_around_an_event_once_its_window_has_passed() {
+        let (node, mut station) = link();
+        let mut node = node.with_history(Duration::from_secs(2));
+        const RATE: u32 = 1_000;
+        let chunk = |n: u8| vec![n; 2 * RATE as usize / 10];
+        // 0.1 s chunks from 0 to 1 s, at 100 MHz.
+        for n in 0..10 {
+            node.push_samples(at(n as f64 / 10.0), 100_000_000, RATE, &chunk(n));
+        }
+        let id = station
+            .request_capture(
+                at(0.55),
+                Duration::from_millis(250),
+                Duration::from_millis(250),
+            )
+            .unwrap();
+        let gone = station
+            .request_capture(at(-10.0), Duration::from_secs(1), Duration::from_secs(1))
+            .unwrap();
+        let later = station
+            .request_capture(at(1.5), Duration::ZERO, Duration::from_millis(100))
+            .unwrap();
+        // The first two are served as they arrive, the last waits.
+        while node.pending_captures() == 0 {
+            node.poll().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        provenance(&mut station);
+        let mut capture = None;
+        let mut unavailable = None;
+        for _ in 0..2 {
+            match station.recv().unwrap().unwrap() {
+                Upstream::Capture(c) => capture = Some(c),
+                Upstream::CaptureUnavailable(id) => unavailable = Some(id),
+                other => panic!("{:?}", other),
+            }
+        }
+        assert_eq!(unavailable, Some(gone));
+        let capture = capture.unwrap();
+        assert_eq!(capture.id, id);
+        assert_eq!(
+            (capture.centre_hz, capture.sample_rate),
+            (100_000_000, RATE)
+        );
+        assert!((unix_seconds(capture.time) - unix_seconds(at(0.3))).abs() < 1e-3);
+        assert_eq!(capture.samples.len(), 2 * 500);
+        assert_eq!(capture.duration(), Duration::from_millis(500));
+        assert_eq!((capture.samples[0], capture.samples[999]), (3, 7));
+
+        // The later request is served once samples reach past it, up to
+        // the retune.
+        node.push_samples(at(1.0), 100_000_000, RATE, &vec![10; 2 * RATE as usize]);
+        node.push_samples(at(2.0), 101_000_000, RATE, &chunk(20));
+        node.poll().unwrap();
+        match station.recv().unwrap().unwrap() {
+            Upstream::Capture(c) => {
+                assert_eq!(c.id, later);
+                assert_eq!(c.samples.len(), 2 * 100);
+                assert!(c.samples.iter().all(|s| *s == 10));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(node.pending_captures(), 0);
+    }
+
+    #[test]
+    fn gives_up_on_windows_the_history_cannot_reach() {
+        let (node, mut station) = link();
+        let mut node = node.with_history(Duration::from_secs(2));
+        const RATE: u32 = 1_000;
+        node.push_samples(at(0.0), 100_000_000, RATE, &[0; 200]);
+        let far = station
+            .request_capture(at(60.0), Duration::ZERO, Duration::from_secs(1))
+            .unwrap();
+        let near = station
+            .request_capture(at(1.0), Duration::ZERO, Duration::from_secs(1))
+            .unwrap();
+        // The far one is refused as it arrives, the near one waits.
+        while node.pending_captures() == 0 {
+            node.poll().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        provenance(&mut station);
+        assert!(matches!(
+            station.recv().unwrap(),
+            Some(Upstream::CaptureUnavailable(id)) if id == far
+        ));
+
+        node.push_samples(at(0.1), 100_000_000, RATE, &vec![1; 2 * 2_100]);
+        node.poll().unwrap();
+        match station.recv().unwrap().unwrap() {
+            Upstream::Capture(c) => {
+                assert_eq!(c.id, near);
+                assert_eq!(c.samples.len(), 2 * RATE as usize);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(node.pending_captures(), 0);
+    }
+}
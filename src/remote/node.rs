@@ -0,0 +1,426 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime};
+
+use super::{
+    read_available, take_frame, unix_seconds, write_frame, Fields, CAPTURE, DB_STEP, EVENT,
+    REQUEST, SPECTRUM, UNAVAILABLE,
+};
+use crate::error::{Error, Result};
+use crate::events::{Event, EventKind};
+use crate::scan::PowerTable;
+use crate::Provenance;
+
+/// The most bytes a `RemoteNode` queues before dropping spectra, when not
+/// set with `with_max_backlog`.
+pub const DEFAULT_BACKLOG: usize = 1 << 20;
+
+/// Raw samples kept for captures, as pushed.
+struct Chunk {
+    /// When the first sample was taken, in seconds since the Unix epoch.
+    time: f64,
+    centre_hz: u32,
+    rate: u32,
+    samples: Vec<u8>,
+}
+
+impl Chunk {
+    fn end(&self) -> f64 {
+        self.time + self.samples.len() as f64 / 2.0 / self.rate.max(1) as f64
+    }
+}
+
+/// Frame a `provenance` event stamped now.
+fn provenance_frame(provenance: Provenance) -> Vec<u8> {
+    let event = Event::now(EventKind::Provenance(provenance));
+    let json = serde_json::to_vec(&event).expect("an event always serializes");
+    let mut frame = Vec::new();
+    write_frame(&mut frame, EVENT, &json);
+    frame
+}
+
+/// A capture the station asked for, by the window of time it covers.
+struct Request {
+    id: u32,
+    start: f64,
+    end: f64,
+}
+
+/// The receiver end of a remote monitoring link, see the module
+/// documentation.
+///
+/// The stream must not block; `connect` sets that up for TCP.
+pub struct RemoteNode<T> {
+    stream: T,
+    /// Bytes read but not yet a whole frame.
+    input: Vec<u8>,
+    /// Frames not yet taken by the stream.
+    output: Vec<u8>,
+    bins: usize,
+    interval: Duration,
+    last_spectrum: Option<SystemTime>,
+    history: VecDeque<Chunk>,
+    history_len: Duration,
+    requests: Vec<Request>,
+    max_backlog: usize,
+    dropped: u64,
+    sent: u64,
+}
+
+impl RemoteNode<TcpStream> {
+    /// Connect to a monitoring station.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The station's address.
+    ///
+    /// # Returns
+    ///
+    /// The node, or `Error::System` if the station could not be reached.
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+        Ok(RemoteNode::new(stream))
+    }
+}
+
+impl<T: Read + Write> RemoteNode<T> {
+    /// Create a node on a connected, non-blocking stream.
+    ///
+    /// Defaults to spectra of at most 512 bins every 5 seconds and 10
+    /// seconds of history. The first frame the node sends is a
+    /// `provenance` event naming the radion and decoder versions behind
+    /// the rest, as `JsonlSink` writes.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The link to the station.
+    ///
+    /// # Returns
+    ///
+    /// A new `RemoteNode`.
+    pub fn new(stream: T) -> Self {
+        RemoteNode {
+            stream,
+            input: Vec::new(),
+            output: provenance_frame(Provenance::current()),
+            bins: 512,
+            interval: Duration::from_secs(5),
+            last_spectrum: None,
+            history: VecDeque::new(),
+            history_len: Duration::from_secs(10),
+            requests: Vec::new(),
+            max_backlog: DEFAULT_BACKLOG,
+            dropped: 0,
+            sent: 0,
+        }
+    }
+
+    /// Set the provenance sent ahead of the first frame, e.g. to add the
+    /// pipeline's configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `provenance` - The provenance, by default `Provenance::current()`.
+    ///
+    /// # Returns
+    ///
+    /// The `RemoteNode` with the new provenance.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        // Nothing else is queued before the node is first used.
+        self.output = provenance_frame(provenance);
+        self
+    }
+
+    /// Set how many bins spectra are decimated to.
+    ///
+    /// # Arguments
+    ///
+    /// * `bins` - The most bins sent; each keeps the highest level of the
+    ///   bins it stands for, so narrow carriers are not averaged away.
+    ///
+    /// # Returns
+    ///
+    /// The `RemoteNode` with the new size.
+    pub fn with_bins(mut self, bins: usize) -> Self {
+        self.bins = bins.max(1);
+        self
+    }
+
+    /// Set the least time between spectra sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The interval.
+    ///
+    /// # Returns
+    ///
+    /// The `RemoteNode` with the new interval.
+    pub fn with_spectrum_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set how much raw IQ is kept for captures.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The time kept, which bounds how far before an event
+    ///   a capture can start.
+    ///
+    /// # Returns
+    ///
+    /// The `RemoteNode` with the new history.
+    pub fn with_history(mut self, history: Duration) -> Self {
+        self.history_len = history;
+        self
+    }
+
+    /// Set how many bytes may wait for the link before spectra are
+    /// dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The limit. Events and captures are always queued.
+    ///
+    /// # Returns
+    ///
+    /// The `RemoteNode` with the new limit.
+    pub fn with_max_backlog(mut self, bytes: usize) -> Self {
+        self.max_backlog = bytes;
+        self
+    }
+
+    /// Send a spectrum, decimated, unless one was sent too recently or
+    /// the link is backed up.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the spectrum was taken.
+    /// * `table` - The spectrum.
+    ///
+    /// # Returns
+    ///
+    /// Whether the spectrum was queued, or an `Error` if the link failed.
+    pub fn send_spectrum(&mut self, time: SystemTime, table: &PowerTable) -> Result<bool> {
+        let due = self
+            .last_spectrum
+            .is_none_or(|last| time.duration_since(last).unwrap_or_default() >= self.interval);
+        if !due || table.db.is_empty() {
+            return Ok(false);
+        }
+        if self.output.len() > self.max_backlog {
+            self.dropped += 1;
+            return Ok(false);
+        }
+        self.last_spectrum = Some(time);
+
+        let group = table.db.len().div_ceil(self.bins);
+        let peaks: Vec<f32> = table
+            .db
+            .chunks(group)
+            .map(|c| c.iter().copied().fold(f32::MIN, f32::max))
+            .collect();
+        let floor = peaks.iter().copied().fold(f32::MAX, f32::min);
+        let mut payload = Vec::with_capacity(28 + peaks.len());
+        payload.extend_from_slice(&unix_seconds(time).to_le_bytes());
+        payload.extend_from_slice(&table.start_hz.to_le_bytes());
+        payload.extend_from_slice(&(table.step_hz * group as f64).to_le_bytes());
+        payload.extend_from_slice(&floor.to_le_bytes());
+        payload.extend(
+            peaks
+                .iter()
+                .map(|db| ((db - floor) / DB_STEP).round().clamp(0.0, 255.0) as u8),
+        );
+        write_frame(&mut self.output, SPECTRUM, &payload);
+        self.flush()?;
+        Ok(true)
+    }
+
+    /// Send an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if the event was queued, otherwise an `Error`.
+    pub fn send_event(&mut self, event: &Event) -> Result<()> {
+        let json = serde_json::to_vec(event).map_err(|e| Error::InvalidMetadata(e.to_string()))?;
+        write_frame(&mut self.output, EVENT, &json);
+        self.flush()
+    }
+
+    /// Keep samples for captures.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the first sample was taken.
+    /// * `centre_hz` - The centre frequency in Hz.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `samples` - Interleaved `cu8` IQ samples.
+    pub fn push_samples(
+        &mut self,
+        time: SystemTime,
+        centre_hz: u32,
+        sample_rate: u32,
+        samples: &[u8],
+    ) {
+        self.history.push_back(Chunk {
+            time: unix_seconds(time),
+            centre_hz,
+            rate: sample_rate,
+            samples: samples.to_vec(),
+        });
+        let end = self.history.back().map_or(0.0, Chunk::end);
+        while self
+            .history
+            .front()
+            .is_some_and(|c| end - c.end() > self.history_len.as_secs_f64())
+        {
+            self.history.pop_front();
+        }
+    }
+
+    /// Read the station's requests, send the captures whose window has
+    /// passed, and write what the link takes.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, or an `Error` if the link failed or the station
+    /// closed it.
+    pub fn poll(&mut self) -> Result<()> {
+        let open = read_available(&mut self.stream, &mut self.input)?;
+        while let Some((kind, payload)) = take_frame(&mut self.input)? {
+            if kind != REQUEST {
+                continue;
+            }
+            let mut fields = Fields(&payload);
+            let id = fields.u32()?;
+            let time = fields.f64()?;
+            let before = fields.u32()? as f64 / 1e3;
+            let after = fields.u32()? as f64 / 1e3;
+            self.requests.push(Request {
+                id,
+                start: time - before,
+                end: time + after,
+            });
+        }
+        self.serve_requests();
+        self.flush()?;
+        if !open {
+            return Err(Error::System(ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(())
+    }
+
+    /// Send the captures whose window the history has passed, and give up
+    /// on those it is too far from to reach while still holding their
+    /// start.
+    fn serve_requests(&mut self) {
+        let Some(latest) = self.history.back().map(Chunk::end) else {
+            return;
+        };
+        let horizon = latest + self.history_len.as_secs_f64();
+        let mut i = 0;
+        while i < self.requests.len() {
+            let end = self.requests[i].end;
+            if end > latest && end <= horizon {
+                i += 1;
+                continue;
+            }
+            let request = self.requests.swap_remove(i);
+            let found = if end > horizon {
+                None
+            } else {
+                self.extract(&request)
+            };
+            match found {
+                Some((time, centre_hz, rate, samples)) => {
+                    let mut payload = Vec::with_capacity(20 + samples.len());
+                    payload.extend_from_slice(&request.id.to_le_bytes());
+                    payload.extend_from_slice(&time.to_le_bytes());
+                    payload.extend_from_slice(&centre_hz.to_le_bytes());
+                    payload.extend_from_slice(&rate.to_le_bytes());
+                    payload.extend_from_slice(&samples);
+                    write_frame(&mut self.output, CAPTURE, &payload);
+                }
+                None => write_frame(&mut self.output, UNAVAILABLE, &request.id.to_le_bytes()),
+            }
+        }
+    }
+
+    /// Cut a request's window out of the history, up to the first retune
+    /// in it.
+    fn extract(&self, request: &Request) -> Option<(f64, u32, u32, Vec<u8>)> {
+        let mut tuning = None;
+        let mut start = 0.0;
+        let mut samples = Vec::new();
+        for chunk in &self.history {
+            if chunk.end() <= request.start || chunk.time >= request.end {
+                continue;
+            }
+            match tuning {
+                None => tuning = Some((chunk.centre_hz, chunk.rate)),
+                Some(t) if t != (chunk.centre_hz, chunk.rate) => break,
+                _ => {}
+            }
+            let rate = chunk.rate as f64;
+            let n = chunk.samples.len() / 2;
+            // Rounded, as times this far from the epoch are only good to a
+            // fraction of a microsecond.
+            let first = (((request.start - chunk.time) * rate).round().max(0.0) as usize).min(n);
+            let last = (((request.end - chunk.time) * rate).round().max(0.0) as usize).min(n);
+            if samples.is_empty() {
+                start = chunk.time + first as f64 / rate;
+            }
+            samples.extend_from_slice(&chunk.samples[2 * first..2 * last]);
+        }
+        let (centre_hz, rate) = tuning?;
+        (!samples.is_empty()).then_some((start, centre_hz, rate, samples))
+    }
+
+    /// Write what the stream takes without blocking.
+    fn flush(&mut self) -> Result<()> {
+        let mut written = 0;
+        while written < self.output.len() {
+            match self.stream.write(&self.output[written..]) {
+                Ok(0) => return Err(Error::System(ErrorKind::WriteZero.into())),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.output.drain(..written);
+        self.sent += written as u64;
+        Ok(())
+    }
+
+    /// Get the number of bytes the link has taken.
+    pub fn bytes_sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Get the number of bytes waiting for the link.
+    pub fn backlog(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Get the number of spectra dropped because the link was backed up.
+    pub fn spectra_dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Get the number of captures asked for and not yet sent.
+    pub fn pending_captures(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Give back the stream.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
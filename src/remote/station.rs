@@ -0,0 +1,183 @@
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use super::{
+    from_unix_seconds, take_frame, unix_seconds, write_frame, Capture, Fields, CAPTURE, DB_STEP,
+    EVENT, REQUEST, SPECTRUM, UNAVAILABLE,
+};
+use crate::error::{Error, Result};
+use crate::events::Event;
+use crate::scan::PowerTable;
+
+/// Something a `RemoteNode` sent.
+#[derive(Clone, Debug)]
+pub enum Upstream {
+    /// A decimated spectrum, with levels to the nearest 0.5 dB.
+    Spectrum {
+        /// When the spectrum was taken.
+        time: SystemTime,
+        /// The spectrum.
+        table: PowerTable,
+    },
+    /// A decoded event.
+    Event(Event),
+    /// A capture that was asked for.
+    Capture(Capture),
+    /// A capture that was asked for, by id, whose window the node no
+    /// longer had or could not reach.
+    CaptureUnavailable(u32),
+}
+
+/// The monitoring station end of a remote monitoring link, see the module
+/// documentation.
+///
+/// Reads block, so a station serving several nodes gives each its own
+/// thread, or sets a read timeout on the stream.
+pub struct RemoteStation<T> {
+    stream: T,
+    /// Bytes read but not yet a whole frame.
+    input: Vec<u8>,
+    next_id: u32,
+    received: u64,
+}
+
+impl<T: Read + Write> RemoteStation<T> {
+    /// Create a station on a node's connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The link to the node, e.g. a stream a `TcpListener`
+    ///   accepted.
+    ///
+    /// # Returns
+    ///
+    /// A new `RemoteStation`.
+    pub fn new(stream: T) -> Self {
+        RemoteStation {
+            stream,
+            input: Vec::new(),
+            next_id: 1,
+            received: 0,
+        }
+    }
+
+    /// Wait for what the node sends next.
+    ///
+    /// # Returns
+    ///
+    /// The next message, `None` once the node has closed the link, or an
+    /// `Error` if the link failed or sent something malformed.
+    pub fn recv(&mut self) -> Result<Option<Upstream>> {
+        let mut scratch = [0u8; 4096];
+        loop {
+            if let Some((kind, payload)) = take_frame(&mut self.input)? {
+                match parse(kind, &payload)? {
+                    Some(message) => return Ok(Some(message)),
+                    // A kind added by a later version.
+                    None => continue,
+                }
+            }
+            let n = self.stream.read(&mut scratch)?;
+            if n == 0 {
+                return if self.input.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(Error::InvalidMetadata("link closed mid-frame".to_string()))
+                };
+            }
+            self.received += n as u64;
+            self.input.extend_from_slice(&scratch[..n]);
+        }
+    }
+
+    /// Ask the node for the raw samples around a time.
+    ///
+    /// The node sends the capture once the window has passed, as an
+    /// `Upstream::Capture`, or `Upstream::CaptureUnavailable` if it no
+    /// longer has any of it or the window ends further ahead of its
+    /// newest samples than its history is long. A retune during the
+    /// window cuts the capture short.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of interest, e.g. an event's.
+    /// * `before` - How long before it the capture starts.
+    /// * `after` - How long after it the capture ends.
+    ///
+    /// # Returns
+    ///
+    /// The id the capture will come back with, or an `Error` if the
+    /// request could not be sent.
+    pub fn request_capture(
+        &mut self,
+        time: SystemTime,
+        before: Duration,
+        after: Duration,
+    ) -> Result<u32> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut payload = Vec::with_capacity(20);
+        payload.extend_from_slice(&id.to_le_bytes());
+        payload.extend_from_slice(&unix_seconds(time).to_le_bytes());
+        payload.extend_from_slice(&(before.as_millis() as u32).to_le_bytes());
+        payload.extend_from_slice(&(after.as_millis() as u32).to_le_bytes());
+        let mut frame = Vec::new();
+        write_frame(&mut frame, REQUEST, &payload);
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        Ok(id)
+    }
+
+    /// Get the number of bytes received from the node.
+    pub fn bytes_received(&self) -> u64 {
+        self.received
+    }
+
+    /// Give back the stream.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+/// Decode a frame from a node.
+///
+/// # Returns
+///
+/// The message, `None` for kinds this version does not know, or
+/// `Error::InvalidMetadata` if the payload is malformed.
+fn parse(kind: u8, payload: &[u8]) -> Result<Option<Upstream>> {
+    let mut fields = Fields(payload);
+    Ok(Some(match kind {
+        SPECTRUM => {
+            let time = from_unix_seconds(fields.f64()?);
+            let start_hz = fields.f64()?;
+            let step_hz = fields.f64()?;
+            let floor = fields.f32()?;
+            let db = fields
+                .0
+                .iter()
+                .map(|b| floor + *b as f32 * DB_STEP)
+                .collect();
+            Upstream::Spectrum {
+                time,
+                table: PowerTable {
+                    start_hz,
+                    step_hz,
+                    db,
+                },
+            }
+        }
+        EVENT => Upstream::Event(
+            serde_json::from_slice(payload).map_err(|e| Error::InvalidMetadata(e.to_string()))?,
+        ),
+        CAPTURE => Upstream::Capture(Capture {
+            id: fields.u32()?,
+            time: from_unix_seconds(fields.f64()?),
+            centre_hz: fields.u32()?,
+            sample_rate: fields.u32()?,
+            samples: fields.0.to_vec(),
+        }),
+        UNAVAILABLE => Upstream::CaptureUnavailable(fields.u32()?),
+        _ => return Ok(None),
+    }))
+}
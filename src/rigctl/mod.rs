@@ -0,0 +1,195 @@
+//! Hamlib `rigctld` compatible control server.
+//!
+//! `RigctlServer` speaks the subset of the rigctld TCP protocol that
+//! logging programs, satellite trackers and other CAT-aware software use to
+//! follow and tune a receiver: frequency, mode and passband, and squelch
+//! level, plus the handshake Hamlib's NET rigctl backend (model 2) needs.
+//! Commands are carried out on a `Rig`, implemented by the application for
+//! whatever drives the dongle, from the thread that calls
+//! `RigctlServer::poll`, so no locking is needed.
+//!
+//! ```no_run
+//! use radion::rigctl::{Mode, Rig, RigctlServer};
+//! use radion::{Device, Result};
+//!
+//! struct Receiver {
+//!     device: Device,
+//!     mode: Mode,
+//!     squelch: f32,
+//! }
+//!
+//! impl Rig for Receiver {
+//!     fn frequency(&self) -> Result<u32> {
+//!         self.device.get_center_freq()
+//!     }
+//!
+//!     fn set_frequency(&mut self, hz: u32) -> Result<()> {
+//!         self.device.set_center_freq(hz)
+//!     }
+//!
+//!     fn mode(&self) -> Result<(Mode, u32)> {
+//!         Ok((self.mode, self.mode.default_passband()))
+//!     }
+//!
+//!     fn set_mode(&mut self, mode: Mode, _passband: Option<u32>) -> Result<()> {
+//!         self.mode = mode;
+//!         Ok(())
+//!     }
+//!
+//!     fn squelch(&self) -> Result<f32> {
+//!         Ok(self.squelch)
+//!     }
+//!
+//!     fn set_squelch(&mut self, level: f32) -> Result<()> {
+//!         self.squelch = level;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let device = Device::new(0)?;
+//! device.set_sample_rate(240_000)?;
+//! device.reset_buffer()?;
+//! let mut rx = Receiver { device, mode: Mode::Fm, squelch: 0.0 };
+//!
+//! // rigctl -m 2 -r localhost:4532 F 145500000
+//! let mut server = RigctlServer::bind("127.0.0.1:4532")?;
+//! loop {
+//!     let iq = rx.device.read_sync(16_384)?;
+//!     // ... demodulate `iq` according to rx.mode and rx.squelch ...
+//!     server.poll(&mut rx)?;
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod protocol;
+mod server;
+
+pub use server::{RigctlServer, DEFAULT_PORT};
+
+use std::fmt;
+
+use crate::error::Result;
+
+/// A Hamlib operating mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Amplitude modulation.
+    Am,
+    /// Morse, received as USB with an offset.
+    Cw,
+    /// Upper sideband.
+    Usb,
+    /// Lower sideband.
+    Lsb,
+    /// Narrowband FM.
+    Fm,
+    /// Wideband (broadcast) FM.
+    Wfm,
+    /// Double sideband, suppressed carrier.
+    Dsb,
+}
+
+impl Mode {
+    /// Every mode, in the order of Hamlib's mode bits.
+    pub const ALL: [Mode; 7] = [
+        Mode::Am,
+        Mode::Cw,
+        Mode::Usb,
+        Mode::Lsb,
+        Mode::Fm,
+        Mode::Wfm,
+        Mode::Dsb,
+    ];
+
+    /// Get the mode's Hamlib name, e.g. `USB`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Am => "AM",
+            Mode::Cw => "CW",
+            Mode::Usb => "USB",
+            Mode::Lsb => "LSB",
+            Mode::Fm => "FM",
+            Mode::Wfm => "WFM",
+            Mode::Dsb => "DSB",
+        }
+    }
+
+    /// Get a mode from its Hamlib name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name, in any case.
+    ///
+    /// # Returns
+    ///
+    /// The mode, or `None` if it isn't one of these.
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL
+            .into_iter()
+            .find(|m| m.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Get the usual passband of the mode in Hz, which Hamlib asks for with
+    /// a passband of 0.
+    pub fn default_passband(self) -> u32 {
+        match self {
+            Mode::Am => 8_000,
+            Mode::Cw => 500,
+            Mode::Usb | Mode::Lsb => 2_400,
+            Mode::Fm => 12_500,
+            Mode::Wfm => 200_000,
+            Mode::Dsb => 6_000,
+        }
+    }
+
+    /// The mode's bit in Hamlib's `rmode_t`.
+    fn bit(self) -> u64 {
+        match self {
+            Mode::Am => 1 << 0,
+            Mode::Cw => 1 << 1,
+            Mode::Usb => 1 << 2,
+            Mode::Lsb => 1 << 3,
+            Mode::Fm => 1 << 5,
+            Mode::Wfm => 1 << 6,
+            Mode::Dsb => 1 << 19,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A receiver that `RigctlServer` can control.
+///
+/// Errors are reported to the client as Hamlib status codes:
+/// `Error::NotSupported` as "not available", `Error::InvalidParam` as
+/// "invalid parameter" and anything else as an I/O error.
+pub trait Rig {
+    /// Get the tuned frequency in Hz.
+    fn frequency(&self) -> Result<u32>;
+
+    /// Tune to a frequency in Hz.
+    fn set_frequency(&mut self, hz: u32) -> Result<()>;
+
+    /// Get the mode and its passband in Hz.
+    fn mode(&self) -> Result<(Mode, u32)>;
+
+    /// Set the mode.
+    ///
+    /// `passband` is the wanted width in Hz, 0 for the mode's usual width,
+    /// or `None` to keep the current one.
+    fn set_mode(&mut self, mode: Mode, passband: Option<u32>) -> Result<()>;
+
+    /// Get the squelch level, from 0 (open) to 1.
+    fn squelch(&self) -> Result<f32> {
+        Err(crate::Error::NotSupported)
+    }
+
+    /// Set the squelch level, from 0 (open) to 1.
+    fn set_squelch(&mut self, _level: f32) -> Result<()> {
+        Err(crate::Error::NotSupported)
+    }
+}
@@ -0,0 +1,512 @@
+use std::fmt::Write as _;
+
+use super::{Mode, Rig};
+use crate::error::Error;
+
+/// Hamlib status codes.
+const RIG_OK: i32 = 0;
+const RIG_EINVAL: i32 = -1;
+const RIG_EIO: i32 = -6;
+const RIG_ENAVAIL: i32 = -11;
+
+/// The rigctld protocol version `\dump_state` reports.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Hamlib's model number for a NET rigctl rig.
+const RIG_MODEL_NETRIGCTL: u32 = 2;
+
+/// `RIG_LEVEL_SQL`.
+const LEVEL_SQL: u64 = 1 << 5;
+
+/// `RIG_VFO_A`.
+const VFO_A: u32 = 1 << 0;
+
+/// The tuning range reported, that of an R820T dongle.
+const RANGE_HZ: (u32, u32) = (24_000_000, 1_766_000_000);
+
+/// What a command returned.
+enum Reply {
+    /// Labelled values, one per line.
+    Values(Vec<(&'static str, String)>),
+    /// A status code alone, as set commands reply.
+    Status(i32),
+    /// Preformatted text, sent as is.
+    Raw(String),
+}
+
+/// The result of a line sent by a client.
+pub(crate) enum Outcome {
+    Reply(String),
+    Quit,
+}
+
+fn status(result: crate::Result<()>) -> Reply {
+    Reply::Status(match result {
+        Ok(()) => RIG_OK,
+        Err(e) => code(&e),
+    })
+}
+
+fn code(error: &Error) -> i32 {
+    match error {
+        Error::NotSupported => RIG_ENAVAIL,
+        Error::InvalidParam => RIG_EINVAL,
+        _ => RIG_EIO,
+    }
+}
+
+fn values<T>(result: crate::Result<T>, f: impl FnOnce(T) -> Vec<(&'static str, String)>) -> Reply {
+    match result {
+        Ok(v) => Reply::Values(f(v)),
+        Err(e) => Reply::Status(code(&e)),
+    }
+}
+
+/// Carry out one line of the protocol.
+///
+/// A line starting with `+`, `;`, `|` or `,` asks for the extended reply
+/// format, with records separated by a newline or that character.
+pub(crate) fn execute<R: Rig + ?Sized>(rig: &mut R, line: &str) -> Outcome {
+    let line = line.trim();
+    let (separator, line) = match line.chars().next() {
+        Some('+') => (Some('\n'), &line[1..]),
+        Some(c @ (';' | '|' | ',')) => (Some(c), &line[1..]),
+        _ => (None, line),
+    };
+    let (command, args) = if let Some(long) = line.strip_prefix('\\') {
+        let end = long.find(char::is_whitespace).unwrap_or(long.len());
+        (long_name(&long[..end]), long[end..].trim())
+    } else {
+        match line.chars().next() {
+            Some(c) => (short_name(c), line[c.len_utf8()..].trim()),
+            None => return Outcome::Reply(String::new()),
+        }
+    };
+    let args: Vec<&str> = args.split_whitespace().collect();
+
+    let reply = match command {
+        Some("quit") => return Outcome::Quit,
+        Some(name) => run(rig, name, &args),
+        None => Reply::Status(RIG_EINVAL),
+    };
+    Outcome::Reply(format_reply(command.unwrap_or(""), &args, reply, separator))
+}
+
+fn short_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        'f' => "get_freq",
+        'F' => "set_freq",
+        'm' => "get_mode",
+        'M' => "set_mode",
+        'l' => "get_level",
+        'L' => "set_level",
+        'v' => "get_vfo",
+        'V' => "set_vfo",
+        't' => "get_ptt",
+        's' => "get_split_vfo",
+        '_' => "get_info",
+        'q' | 'Q' => "quit",
+        _ => return None,
+    })
+}
+
+fn long_name(name: &str) -> Option<&'static str> {
+    [
+        "get_freq",
+        "set_freq",
+        "get_mode",
+        "set_mode",
+        "get_level",
+        "set_level",
+        "get_vfo",
+        "set_vfo",
+        "get_ptt",
+        "get_split_vfo",
+        "get_info",
+        "get_powerstat",
+        "chk_vfo",
+        "dump_state",
+        "quit",
+    ]
+    .into_iter()
+    .find(|&n| n == name)
+}
+
+fn run<R: Rig + ?Sized>(rig: &mut R, command: &str, args: &[&str]) -> Reply {
+    match (command, args) {
+        ("get_freq", _) => values(rig.frequency(), |hz| vec![("Frequency", hz.to_string())]),
+        ("set_freq", [hz, ..]) => match hz.parse::<f64>() {
+            Ok(hz) if (0.0..=u32::MAX as f64).contains(&hz) => {
+                status(rig.set_frequency(hz.round() as u32))
+            }
+            _ => Reply::Status(RIG_EINVAL),
+        },
+        ("get_mode", _) => values(rig.mode(), |(mode, passband)| {
+            vec![
+                ("Mode", mode.name().to_string()),
+                ("Passband", passband.to_string()),
+            ]
+        }),
+        ("set_mode", [mode, rest @ ..]) => {
+            let passband = match rest.first().map(|p| p.parse::<i64>()) {
+                None | Some(Ok(-1)) => Ok(None),
+                Some(Ok(p)) if p >= 0 => Ok(Some(p.min(u32::MAX as i64) as u32)),
+                _ => Err(()),
+            };
+            match (Mode::from_name(mode), passband) {
+                (Some(mode), Ok(passband)) => status(rig.set_mode(mode, passband)),
+                (None, _) if mode.starts_with('?') => Reply::Raw(mode_list()),
+                _ => Reply::Status(RIG_EINVAL),
+            }
+        }
+        ("get_level", [level, ..]) if level.eq_ignore_ascii_case("SQL") => {
+            values(rig.squelch(), |l| {
+                vec![("Level Value", format!("{:.6}", l))]
+            })
+        }
+        ("get_level", [level, ..]) if level.starts_with('?') => Reply::Raw("SQL\n".to_string()),
+        ("set_level", [level, value, ..]) if level.eq_ignore_ascii_case("SQL") => {
+            match value.parse::<f32>() {
+                Ok(v) if (0.0..=1.0).contains(&v) => status(rig.set_squelch(v)),
+                _ => Reply::Status(RIG_EINVAL),
+            }
+        }
+        ("set_level", [level, ..]) if level.starts_with('?') => Reply::Raw("SQL\n".to_string()),
+        ("get_level", [_, ..]) | ("set_level", [_, _, ..]) => Reply::Status(RIG_ENAVAIL),
+        ("get_vfo", _) => Reply::Values(vec![("VFO", "VFOA".to_string())]),
+        ("set_vfo", [vfo, ..]) => {
+            if ["VFOA", "currVFO", "Main", "VFO"].contains(vfo) {
+                Reply::Status(RIG_OK)
+            } else {
+                Reply::Status(RIG_EINVAL)
+            }
+        }
+        ("get_ptt", _) => Reply::Values(vec![("PTT", "0".to_string())]),
+        ("get_split_vfo", _) => Reply::Values(vec![
+            ("Split", "0".to_string()),
+            ("TX VFO", "VFOA".to_string()),
+        ]),
+        ("get_info", _) => Reply::Values(vec![("Info", "radion".to_string())]),
+        ("get_powerstat", _) => Reply::Values(vec![("Power Status", "1".to_string())]),
+        ("chk_vfo", _) => Reply::Values(vec![("ChkVFO", "0".to_string())]),
+        ("dump_state", _) => Reply::Raw(dump_state()),
+        _ => Reply::Status(RIG_EINVAL),
+    }
+}
+
+fn format_reply(command: &str, args: &[&str], reply: Reply, separator: Option<char>) -> String {
+    let mut out = String::new();
+    match separator {
+        None => match reply {
+            Reply::Values(values) => {
+                for (_, value) in values {
+                    out.push_str(&value);
+                    out.push('\n');
+                }
+            }
+            Reply::Status(code) => {
+                let _ = writeln!(out, "RPRT {}", code);
+            }
+            Reply::Raw(text) => out = text,
+        },
+        Some(sep) => {
+            out.push_str(command);
+            out.push(':');
+            for arg in args {
+                out.push(' ');
+                out.push_str(arg);
+            }
+            out.push(sep);
+            let code = match reply {
+                Reply::Values(values) => {
+                    for (label, value) in values {
+                        let _ = write!(out, "{}: {}{}", label, value, sep);
+                    }
+                    RIG_OK
+                }
+                Reply::Status(code) => code,
+                Reply::Raw(text) => {
+                    out.push_str(&text);
+                    RIG_OK
+                }
+            };
+            let _ = writeln!(out, "RPRT {}", code);
+        }
+    }
+    out
+}
+
+fn mode_list() -> String {
+    let names: Vec<&str> = Mode::ALL.iter().map(|m| m.name()).collect();
+    format!("{}\n", names.join(" "))
+}
+
+/// The rig description Hamlib's NET rigctl backend reads when it connects.
+fn dump_state() -> String {
+    let modes = Mode::ALL.iter().fold(0, |bits, m| bits | m.bit());
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", PROTOCOL_VERSION);
+    let _ = writeln!(out, "{}", RIG_MODEL_NETRIGCTL);
+    // ITU region.
+    let _ = writeln!(out, "1");
+    // Receive ranges, then no transmit ranges.
+    let _ = writeln!(
+        out,
+        "{}.000000 {}.000000 0x{:x} -1 -1 0x{:x} 0x1",
+        RANGE_HZ.0, RANGE_HZ.1, modes, VFO_A
+    );
+    let _ = writeln!(out, "0 0 0 0 0 0 0");
+    let _ = writeln!(out, "0 0 0 0 0 0 0");
+    // Tuning steps, then filters.
+    let _ = writeln!(out, "0x{:x} 1", modes);
+    let _ = writeln!(out, "0 0");
+    for mode in Mode::ALL {
+        let _ = writeln!(out, "0x{:x} {}", mode.bit(), mode.default_passband());
+    }
+    let _ = writeln!(out, "0 0");
+    // Max RIT, XIT and IF shift, announces, preamps and attenuators.
+    let _ = writeln!(out, "0\n0\n0\n0\n\n");
+    // Functions, then levels and parameters, got and set.
+    let _ = writeln!(out, "0x0\n0x0");
+    let _ = writeln!(out, "0x{:x}\n0x{:x}", LEVEL_SQL, LEVEL_SQL);
+    let _ = writeln!(out, "0x0\n0x0");
+    let _ = write!(
+        out,
+        "vfo_ops=0x0\nptt_type=0x0\ntargetable_vfo=0x0\nhas_set_vfo=1\nhas_get_vfo=1\n\
+         has_set_freq=1\nhas_get_freq=1\nhas_set_conf=0\nhas_get_conf=0\n\
+         has_power2mW=0\nhas_mW2power=0\ntimeout=0\ndone\n"
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+
+    struct FakeRig {
+        hz: u32,
+        mode: Mode,
+        passband: u32,
+        squelch: f32,
+    }
+
+    impl Rig for FakeRig {
+        fn frequency(&self) -> Result<u32> {
+            Ok(self.hz)
+        }
+
+        fn set_frequency(&mut self, hz: u32) -> Result<()> {
+            match hz {
+                0 => Err(Error::Timeout),
+                2_000_000_000.. => Err(Error::InvalidParam),
+                _ => {
+                    self.hz = hz;
+                    Ok(())
+                }
+            }
+        }
+
+        fn mode(&self) -> Result<(Mode, u32)> {
+            Ok((self.mode, self.passband))
+        }
+
+        fn set_mode(&mut self, mode: Mode, passband: Option<u32>) -> Result<()> {
+            self.mode = mode;
+            self.passband = match passband {
+                Some(0) => mode.default_passband(),
+                Some(p) => p,
+                None => self.passband,
+            };
+            Ok(())
+        }
+
+        fn squelch(&self) -> Result<f32> {
+            Ok(self.squelch)
+        }
+
+        fn set_squelch(&mut self, level: f32) -> Result<()> {
+            self.squelch = level;
+            Ok(())
+        }
+    }
+
+    /// A rig implementing only what `Rig` requires.
+    struct NoSquelch;
+
+    impl Rig for NoSquelch {
+        fn frequency(&self) -> Result<u32> {
+            Err(Error::NotSupported)
+        }
+
+        fn set_frequency(&mut self, _hz: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn mode(&self) -> Result<(Mode, u32)> {
+            Ok((Mode::Am, 8_000))
+        }
+
+        fn set_mode(&mut self, _mode: Mode, _passband: Option<u32>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn rig() -> FakeRig {
+        FakeRig {
+            hz: 145_500_000,
+            mode: Mode::Fm,
+            passband: 12_500,
+            squelch: 0.25,
+        }
+    }
+
+    fn reply<R: Rig + ?Sized>(rig: &mut R, line: &str) -> String {
+        match execute(rig, line) {
+            Outcome::Reply(reply) => reply,
+            Outcome::Quit => panic!("{:?} quit", line),
+        }
+    }
+
+    #[test]
+    fn short_commands() {
+        let mut rig = rig();
+        assert_eq!(reply(&mut rig, "f"), "145500000\n");
+        assert_eq!(reply(&mut rig, "F 7074000"), "RPRT 0\n");
+        assert_eq!(rig.hz, 7_074_000);
+        assert_eq!(reply(&mut rig, "F 7074000.6"), "RPRT 0\n");
+        assert_eq!(rig.hz, 7_074_001);
+        assert_eq!(reply(&mut rig, "m"), "FM\n12500\n");
+        assert_eq!(reply(&mut rig, "M USB 2700"), "RPRT 0\n");
+        assert_eq!((rig.mode, rig.passband), (Mode::Usb, 2_700));
+        assert_eq!(reply(&mut rig, "M lsb -1"), "RPRT 0\n");
+        assert_eq!((rig.mode, rig.passband), (Mode::Lsb, 2_700));
+        assert_eq!(reply(&mut rig, "M AM 0"), "RPRT 0\n");
+        assert_eq!((rig.mode, rig.passband), (Mode::Am, 8_000));
+        assert_eq!(reply(&mut rig, "M ?"), "AM CW USB LSB FM WFM DSB\n");
+        assert_eq!(reply(&mut rig, "l SQL"), "0.250000\n");
+        assert_eq!(reply(&mut rig, "l ?"), "SQL\n");
+        assert_eq!(reply(&mut rig, "L sql 0.5"), "RPRT 0\n");
+        assert_eq!(rig.squelch, 0.5);
+        assert_eq!(reply(&mut rig, "L ?"), "SQL\n");
+        assert_eq!(reply(&mut rig, "v"), "VFOA\n");
+        assert_eq!(reply(&mut rig, "V currVFO"), "RPRT 0\n");
+        assert_eq!(reply(&mut rig, "t"), "0\n");
+        assert_eq!(reply(&mut rig, "s"), "0\nVFOA\n");
+        assert_eq!(reply(&mut rig, "_"), "radion\n");
+        assert!(matches!(execute(&mut rig, "q"), Outcome::Quit));
+        assert!(matches!(execute(&mut rig, "Q"), Outcome::Quit));
+    }
+
+    #[test]
+    fn long_commands() {
+        let mut rig = rig();
+        assert_eq!(reply(&mut rig, "\\get_freq"), "145500000\n");
+        assert_eq!(reply(&mut rig, "\\set_freq 433920000"), "RPRT 0\n");
+        assert_eq!(rig.hz, 433_920_000);
+        assert_eq!(reply(&mut rig, "\\get_mode"), "FM\n12500\n");
+        assert_eq!(reply(&mut rig, "\\set_mode WFM 0"), "RPRT 0\n");
+        assert_eq!((rig.mode, rig.passband), (Mode::Wfm, 200_000));
+        assert_eq!(reply(&mut rig, "\\get_level SQL"), "0.250000\n");
+        assert_eq!(reply(&mut rig, "\\set_level SQL 1"), "RPRT 0\n");
+        assert_eq!(reply(&mut rig, "\\get_vfo"), "VFOA\n");
+        assert_eq!(reply(&mut rig, "\\set_vfo VFOA"), "RPRT 0\n");
+        assert_eq!(reply(&mut rig, "\\get_ptt"), "0\n");
+        assert_eq!(reply(&mut rig, "\\get_split_vfo"), "0\nVFOA\n");
+        assert_eq!(reply(&mut rig, "\\get_info"), "radion\n");
+        assert_eq!(reply(&mut rig, "\\get_powerstat"), "1\n");
+        assert_eq!(reply(&mut rig, "\\chk_vfo"), "0\n");
+        assert!(matches!(execute(&mut rig, "\\quit"), Outcome::Quit));
+    }
+
+    #[test]
+    fn dump_state_ends_with_done() {
+        let state = reply(&mut rig(), "\\dump_state");
+        let mut lines = state.lines();
+        assert_eq!(lines.next(), Some("1"));
+        assert_eq!(lines.next(), Some("2"));
+        assert!(lines.any(|l| l.starts_with("24000000.000000 1766000000.000000 ")));
+        assert!(state.ends_with("\ndone\n"));
+    }
+
+    #[test]
+    fn extended_replies() {
+        let mut rig = rig();
+        assert_eq!(
+            reply(&mut rig, "+f"),
+            "get_freq:\nFrequency: 145500000\nRPRT 0\n"
+        );
+        assert_eq!(
+            reply(&mut rig, "+\\get_mode"),
+            "get_mode:\nMode: FM\nPassband: 12500\nRPRT 0\n"
+        );
+        assert_eq!(reply(&mut rig, ";F 7000000"), "set_freq: 7000000;RPRT 0\n");
+        assert_eq!(
+            reply(&mut rig, "|s"),
+            "get_split_vfo:|Split: 0|TX VFO: VFOA|RPRT 0\n"
+        );
+        assert_eq!(reply(&mut rig, ",L SQL 2"), "set_level: SQL 2,RPRT -1\n");
+        assert_eq!(reply(&mut rig, "+x"), ":\nRPRT -1\n");
+        assert_eq!(reply(&mut rig, "+\\"), ":\nRPRT -1\n");
+        assert!(reply(&mut rig, "+\\dump_state").ends_with("done\nRPRT 0\n"));
+        assert!(matches!(execute(&mut rig, "+q"), Outcome::Quit));
+    }
+
+    #[test]
+    fn malformed_lines_are_invalid() {
+        let mut rig = rig();
+        for line in [
+            "F",
+            "F abc",
+            "F -5",
+            "F 1e12",
+            "F NaN",
+            "M",
+            "M XYZ",
+            "M FM -5",
+            "M FM wide",
+            "L SQL",
+            "L SQL 1.5",
+            "L SQL loud",
+            "L RF",
+            "V VFOB",
+            "x",
+            "é",
+            "\\",
+            "\\get_freqs",
+            "\\set_freq",
+            "\\GET_FREQ",
+        ] {
+            assert_eq!(reply(&mut rig, line), "RPRT -1\n", "{:?}", line);
+        }
+        assert_eq!(rig.hz, 145_500_000);
+        assert_eq!(rig.squelch, 0.25);
+    }
+
+    #[test]
+    fn long_names_are_not_overrun() {
+        let line = format!("\\{}", "a".repeat(100_000));
+        assert_eq!(reply(&mut rig(), &line), "RPRT -1\n");
+    }
+
+    #[test]
+    fn blank_lines_get_no_reply() {
+        assert_eq!(reply(&mut rig(), ""), "");
+        assert_eq!(reply(&mut rig(), "  \r\n"), "");
+        assert_eq!(reply(&mut rig(), "+"), "");
+    }
+
+    #[test]
+    fn rig_errors_become_status_codes() {
+        let mut rig = rig();
+        assert_eq!(reply(&mut rig, "F 0"), "RPRT -6\n");
+        assert_eq!(reply(&mut rig, "F 2100000000"), "RPRT -1\n");
+        assert_eq!(reply(&mut rig, "l RF"), "RPRT -11\n");
+        assert_eq!(reply(&mut rig, "L RF 0.5"), "RPRT -11\n");
+
+        assert_eq!(reply(&mut NoSquelch, "f"), "RPRT -11\n");
+        assert_eq!(reply(&mut NoSquelch, "l SQL"), "RPRT -11\n");
+        assert_eq!(reply(&mut NoSquelch, "L SQL 0.5"), "RPRT -11\n");
+        assert_eq!(reply(&mut NoSquelch, "+f"), "get_freq:\nRPRT -11\n");
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use super::protocol::{self, Outcome};
+use super::Rig;
+use crate::error::Result;
+
+/// The port rigctld listens on by default.
+pub const DEFAULT_PORT: u16 = 4532;
+
+/// The longest command line accepted; a client sending more is dropped.
+const MAX_LINE: usize = 1024;
+
+struct Client {
+    stream: TcpStream,
+    /// Bytes read but not yet ending in a newline.
+    input: Vec<u8>,
+    /// Replies not yet taken by the socket.
+    output: Vec<u8>,
+    /// Set once the client has quit, so it goes after its replies are sent.
+    closing: bool,
+}
+
+impl Client {
+    /// Read and carry out whatever commands the client has sent, then write
+    /// what the socket takes.
+    ///
+    /// Returns the number of commands carried out, or `None` once the
+    /// client has gone.
+    fn serve<R: Rig + ?Sized>(&mut self, rig: &mut R) -> Option<usize> {
+        let mut scratch = [0u8; 512];
+        let mut executed = 0;
+        while !self.closing {
+            match self.stream.read(&mut scratch) {
+                Ok(0) => return None,
+                Ok(n) => self.input.extend_from_slice(&scratch[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+            while let Some(end) = self.input.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.input.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                executed += 1;
+                match protocol::execute(rig, line.trim_end_matches(['\r', '\n'])) {
+                    Outcome::Reply(reply) => self.output.extend_from_slice(reply.as_bytes()),
+                    Outcome::Quit => {
+                        self.closing = true;
+                        break;
+                    }
+                }
+            }
+            if self.input.len() > MAX_LINE {
+                return None;
+            }
+        }
+
+        let mut written = 0;
+        while written < self.output.len() {
+            match self.stream.write(&self.output[written..]) {
+                Ok(0) => return None,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+        self.output.drain(..written);
+        if self.closing && self.output.is_empty() {
+            return None;
+        }
+        Some(executed)
+    }
+}
+
+/// A TCP server speaking the rigctld protocol, for any number of clients.
+///
+/// The server never blocks: connections are accepted, and commands read,
+/// carried out and answered, whenever `poll` is called, so it fits in the
+/// loop that reads samples. Commands from different clients are carried
+/// out in turn, each seeing the effect of the ones before.
+pub struct RigctlServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl RigctlServer {
+    /// Start listening.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `"127.0.0.1:4532"`; port 0
+    ///   picks a free one.
+    ///
+    /// # Returns
+    ///
+    /// The server, or `Error::System` if the address can't be bound.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(RigctlServer {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Get the address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Get the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accept new clients and carry out the commands they have sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `rig` - The receiver the commands act on.
+    ///
+    /// # Returns
+    ///
+    /// The number of commands carried out.
+    pub fn poll<R: Rig + ?Sized>(&mut self, rig: &mut R) -> Result<usize> {
+        self.accept();
+        let mut executed = 0;
+        self.clients.retain_mut(|c| match c.serve(rig) {
+            Some(n) => {
+                executed += n;
+                true
+            }
+            None => false,
+        });
+        Ok(executed)
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+                    // Replies are small and clients wait on each one.
+                    let _ = stream.set_nodelay(true);
+                    self.clients.push(Client {
+                        stream,
+                        input: Vec::new(),
+                        output: Vec::new(),
+                        closing: false,
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                // Nothing waiting, or out of descriptors: try again later.
+                Err(_) => break,
+            }
+        }
+    }
+}
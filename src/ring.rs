@@ -0,0 +1,516 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::stats::{StreamCounters, StreamStats, DEFAULT_BUF_LEN};
+use crate::stream::StreamHandle;
+use std::cell::UnsafeCell;
+use std::ops::Deref;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Sequence of a slot the consumer is reading.
+const READING: u64 = u64::MAX;
+
+/// Sequence of a slot the producer is overwriting.
+const WRITING: u64 = u64::MAX - 1;
+
+/// Longest a waiting consumer sleeps before looking at the ring again, in
+/// case a wakeup was missed.
+const PARK_LIMIT: Duration = Duration::from_millis(100);
+
+/// What a `RingReader` does with a buffer that arrives while the ring is
+/// full.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    /// Overwrite the oldest buffer not yet read, so the consumer always
+    /// sees the most recent data. While the consumer holds on to the oldest
+    /// buffer, new ones are dropped instead.
+    #[default]
+    DropOldest,
+    /// Stop accepting buffers. The consumer reads what was queued before the
+    /// overrun, then gets `Error::Overrun` and the read ends.
+    Error,
+}
+
+struct Slot {
+    /// `pos` while free for the buffer at position `pos`, `pos + 1` once it
+    /// holds it, or `READING` or `WRITING`.
+    seq: AtomicU64,
+    len: UnsafeCell<usize>,
+    data: UnsafeCell<Box<[u8]>>,
+}
+
+/// Pre-allocated single-producer single-consumer ring of buffers.
+///
+/// Each slot is owned by whoever last moved its sequence number, so its
+/// data is never touched by both sides at once and no locks are taken.
+struct Ring {
+    slots: Box<[Slot]>,
+    policy: OverrunPolicy,
+    /// Buffers written so far.
+    head: AtomicU64,
+    /// Position of the first buffer dropped under `OverrunPolicy::Error`.
+    end: AtomicU64,
+    closed: AtomicBool,
+    waiting: AtomicBool,
+    waiter: Mutex<Option<Thread>>,
+}
+
+// Slot data is only accessed by the side that owns the slot, see `Slot::seq`.
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn new(capacity: usize, slot_len: usize, policy: OverrunPolicy) -> Self {
+        Ring {
+            slots: (0..capacity as u64)
+                .map(|pos| Slot {
+                    seq: AtomicU64::new(pos),
+                    len: UnsafeCell::new(0),
+                    data: UnsafeCell::new(vec![0; slot_len].into_boxed_slice()),
+                })
+                .collect(),
+            policy,
+            head: AtomicU64::new(0),
+            end: AtomicU64::new(u64::MAX),
+            closed: AtomicBool::new(false),
+            waiting: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+        }
+    }
+
+    fn slot(&self, pos: u64) -> &Slot {
+        &self.slots[(pos % self.slots.len() as u64) as usize]
+    }
+
+    /// Wake the consumer if it is waiting. Never blocks.
+    fn wake(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) {
+            // The consumer only holds the lock before it starts waiting.
+            if let Ok(waiter) = self.waiter.try_lock() {
+                if let Some(thread) = waiter.as_ref() {
+                    thread.unpark();
+                }
+            }
+        }
+    }
+}
+
+/// The USB callback's end of a ring.
+struct Producer {
+    ring: Arc<Ring>,
+    stats: Arc<StreamCounters>,
+    head: u64,
+    failed: bool,
+}
+
+impl Producer {
+    fn push(&mut self, buf: &[u8]) {
+        if self.failed {
+            self.stats.record_drop();
+            return;
+        }
+        let ring = &*self.ring;
+        let slot = ring.slot(self.head);
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq != self.head {
+            // The slot still holds the oldest buffer, or the consumer is
+            // reading it.
+            self.stats.record_drop();
+            if ring.policy == OverrunPolicy::Error {
+                self.failed = true;
+                ring.end.store(self.head, Ordering::Release);
+                ring.wake();
+                return;
+            }
+            if seq == READING
+                || slot
+                    .seq
+                    .compare_exchange(seq, WRITING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+            {
+                return;
+            }
+        }
+        // SAFETY: the sequence number gives the slot to the producer until
+        // it is stored below.
+        unsafe {
+            let data = &mut *slot.data.get();
+            let len = buf.len().min(data.len());
+            data[..len].copy_from_slice(&buf[..len]);
+            *slot.len.get() = len;
+        }
+        slot.seq.store(self.head + 1, Ordering::Release);
+        self.head += 1;
+        ring.head.store(self.head, Ordering::Release);
+        ring.wake();
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+        self.ring.wake();
+    }
+}
+
+enum Next {
+    Ready(u64),
+    Empty,
+    Overrun,
+    Closed,
+}
+
+/// Asynchronous read delivering buffers through a pre-allocated lock-free
+/// ring.
+///
+/// Created by `Device::start_ring`. The USB callback copies every buffer
+/// into a free slot of the ring and never allocates, locks or waits on the
+/// consumer; what happens when the consumer falls behind is set by the
+/// `OverrunPolicy`, and dropped buffers are counted in `stats`. Dropping the
+/// reader cancels the read and closes the device.
+pub struct RingReader {
+    handle: Option<StreamHandle>,
+    device: Option<Device>,
+    ring: Arc<Ring>,
+    stats: Arc<StreamCounters>,
+    tail: u64,
+    done: bool,
+}
+
+impl RingReader {
+    pub(crate) fn spawn(
+        device: Device,
+        capacity: usize,
+        policy: OverrunPolicy,
+        buf_num: u32,
+        buf_len: u32,
+    ) -> Result<Self> {
+        let slot_len = if buf_len == 0 {
+            DEFAULT_BUF_LEN
+        } else {
+            buf_len
+        };
+        let ring = Arc::new(Ring::new(capacity.max(1), slot_len as usize, policy));
+        let stats = device.stream_counters();
+        let mut producer = Producer {
+            ring: ring.clone(),
+            stats: stats.clone(),
+            head: 0,
+            failed: false,
+        };
+        let handle = device.start_async(move |buf| producer.push(buf), buf_num, buf_len)?;
+        Ok(RingReader {
+            handle: Some(handle),
+            device: None,
+            ring,
+            stats,
+            tail: 0,
+            done: false,
+        })
+    }
+
+    /// Wait for the next buffer.
+    ///
+    /// # Returns
+    ///
+    /// The oldest buffer not yet read, `None` once the read has ended, or
+    /// the `Error` it failed with. `Error::Overrun` ends the read under
+    /// `OverrunPolicy::Error`.
+    pub fn recv(&mut self) -> Result<Option<RingSlot<'_>>> {
+        self.recv_until(None)
+    }
+
+    /// Wait for the next buffer for at most `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait. Zero only takes a buffer that is
+    ///   already queued.
+    ///
+    /// # Returns
+    ///
+    /// Like `recv`, or `Error::Timeout` if no buffer arrived in time.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<RingSlot<'_>>> {
+        self.recv_until(Some(Instant::now() + timeout))
+    }
+
+    /// Get the number of buffers the ring holds.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// Get the number of buffers queued and not yet read.
+    pub fn len(&self) -> usize {
+        let head = self.ring.head.load(Ordering::Acquire);
+        (head.saturating_sub(self.tail) as usize).min(self.capacity())
+    }
+
+    /// Check whether no buffers are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check whether the read is still running.
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(StreamHandle::is_running)
+    }
+
+    /// Get the counters of the asynchronous read path, including buffers
+    /// dropped because the ring was full. See `Device::stream_stats`.
+    pub fn stats(&self) -> StreamStats {
+        self.stats.snapshot()
+    }
+
+    /// Cancel the read and wait for the reader thread to exit.
+    ///
+    /// # Returns
+    ///
+    /// The device if the read ended cleanly, otherwise the `Error` it failed
+    /// with.
+    pub fn stop(mut self) -> Result<Device> {
+        if let Some(handle) = self.handle.take() {
+            return handle.stop();
+        }
+        self.device.take().ok_or(Error::Unknown)
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<Option<RingSlot<'_>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut waiting = false;
+        let next = loop {
+            match self.poll() {
+                Next::Empty => {}
+                next => break next,
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|d| now >= d) {
+                break Next::Empty;
+            }
+            if !waiting {
+                *self.ring.waiter.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(thread::current());
+                self.ring.waiting.store(true, Ordering::Relaxed);
+                atomic::fence(Ordering::SeqCst);
+                waiting = true;
+                // Look again, in case a buffer arrived before the producer
+                // could see that we are waiting.
+                continue;
+            }
+            let limit = deadline.map_or(PARK_LIMIT, |d| PARK_LIMIT.min(d - now));
+            thread::park_timeout(limit);
+        };
+        if waiting {
+            self.ring.waiting.store(false, Ordering::Relaxed);
+        }
+
+        match next {
+            Next::Ready(pos) => Ok(Some(RingSlot {
+                slot: self.ring.slot(pos),
+                pos,
+                capacity: self.ring.slots.len() as u64,
+            })),
+            Next::Empty => Err(Error::Timeout),
+            Next::Overrun => {
+                self.end_read()?;
+                Err(Error::Overrun)
+            }
+            Next::Closed => self.end_read().map(|_| None),
+        }
+    }
+
+    /// Take the next buffer if there is one, skipping any overwritten.
+    fn poll(&mut self) -> Next {
+        loop {
+            // Read before the slot, so a closed ring has no buffers left.
+            let closed = self.ring.closed.load(Ordering::Acquire);
+            let slot = self.ring.slot(self.tail);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let pos = self.tail;
+            if seq == pos + 1 {
+                if slot
+                    .seq
+                    .compare_exchange(seq, READING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    self.tail += 1;
+                    return Next::Ready(pos);
+                }
+                // The producer took the slot for a newer buffer.
+                continue;
+            }
+            if seq == WRITING || (seq != READING && seq > pos + 1) {
+                self.tail += 1;
+                continue;
+            }
+            if self.ring.end.load(Ordering::Acquire) == pos {
+                return Next::Overrun;
+            }
+            return if closed { Next::Closed } else { Next::Empty };
+        }
+    }
+
+    fn end_read(&mut self) -> Result<()> {
+        self.done = true;
+        if let Some(handle) = self.handle.take() {
+            self.device = Some(handle.stop()?);
+        }
+        Ok(())
+    }
+}
+
+/// A buffer borrowed from a `RingReader`'s ring.
+///
+/// Dereferences to the raw cu8 samples. The slot is handed back to the
+/// producer when this is dropped, so hold on to it no longer than needed.
+pub struct RingSlot<'a> {
+    slot: &'a Slot,
+    pos: u64,
+    capacity: u64,
+}
+
+impl Deref for RingSlot<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the slot is marked `READING`, which the producer leaves
+        // alone.
+        unsafe {
+            let data = &*self.slot.data.get();
+            &data[..*self.slot.len.get()]
+        }
+    }
+}
+
+impl Drop for RingSlot<'_> {
+    fn drop(&mut self) {
+        self.slot
+            .seq
+            .store(self.pos + self.capacity, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Both ends of a ring without a device behind it.
+    fn ring(capacity: usize, slot_len: usize, policy: OverrunPolicy) -> (Producer, RingReader) {
+        let ring = Arc::new(Ring::new(capacity, slot_len, policy));
+        let stats = Arc::new(StreamCounters::default());
+        let producer = Producer {
+            ring: ring.clone(),
+            stats: stats.clone(),
+            head: 0,
+            failed: false,
+        };
+        let reader = RingReader {
+            handle: None,
+            device: None,
+            ring,
+            stats,
+            tail: 0,
+            done: false,
+        };
+        (producer, reader)
+    }
+
+    fn next(reader: &mut RingReader) -> Vec<u8> {
+        reader
+            .recv_timeout(Duration::ZERO)
+            .unwrap()
+            .expect("buffer")
+            .to_vec()
+    }
+
+    #[test]
+    fn wraps_around_the_slots() {
+        let (mut producer, mut reader) = ring(4, 2, OverrunPolicy::DropOldest);
+        for i in 0..11u8 {
+            producer.push(&[i, i, i]);
+            assert_eq!(reader.len(), 1);
+            assert_eq!(next(&mut reader), [i, i]);
+        }
+        assert!(matches!(
+            reader.recv_timeout(Duration::ZERO),
+            Err(Error::Timeout)
+        ));
+        drop(producer);
+        assert!(reader.recv().unwrap().is_none());
+        assert!(!reader.stats().has_losses());
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_newest_buffers() {
+        let (mut producer, mut reader) = ring(3, 1, OverrunPolicy::DropOldest);
+        for i in 0..5 {
+            producer.push(&[i]);
+        }
+        assert_eq!(reader.len(), 3);
+        assert_eq!(next(&mut reader), [2]);
+        assert_eq!(next(&mut reader), [3]);
+        assert_eq!(next(&mut reader), [4]);
+        assert!(reader.is_empty());
+        assert_eq!(reader.stats().dropped_frames, 2);
+    }
+
+    #[test]
+    fn drop_oldest_never_overwrites_the_buffer_being_read() {
+        let (mut producer, mut reader) = ring(2, 1, OverrunPolicy::DropOldest);
+        producer.push(&[0]);
+        producer.push(&[1]);
+        {
+            let held = reader.recv().unwrap().unwrap();
+            producer.push(&[2]);
+            producer.push(&[3]);
+            assert_eq!(*held, [0]);
+        }
+        producer.push(&[4]);
+        assert_eq!(next(&mut reader), [1]);
+        assert_eq!(next(&mut reader), [4]);
+        assert_eq!(reader.stats().dropped_frames, 2);
+    }
+
+    #[test]
+    fn error_policy_ends_the_read_after_the_queued_buffers() {
+        let (mut producer, mut reader) = ring(2, 1, OverrunPolicy::Error);
+        for i in 0..4 {
+            producer.push(&[i]);
+        }
+        assert_eq!(next(&mut reader), [0]);
+        assert_eq!(next(&mut reader), [1]);
+        assert!(matches!(reader.recv(), Err(Error::Overrun)));
+        assert!(reader.recv().unwrap().is_none());
+        // Once failed, the producer keeps dropping even with room free.
+        producer.push(&[4]);
+        assert_eq!(reader.stats().dropped_frames, 3);
+    }
+
+    #[test]
+    fn delivers_in_order_across_threads() {
+        const COUNT: u32 = 20_000;
+        let (mut producer, mut reader) = ring(8, 4, OverrunPolicy::DropOldest);
+        let feeder = thread::spawn(move || {
+            for i in 0..COUNT {
+                producer.push(&i.to_le_bytes());
+                if i % 64 == 0 {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut received = 0;
+        let mut last = None;
+        while let Some(buf) = reader.recv().unwrap() {
+            let i = u32::from_le_bytes(buf[..].try_into().unwrap());
+            assert!(last < Some(i), "{} after {:?}", i, last);
+            last = Some(i);
+            received += 1;
+        }
+        feeder.join().unwrap();
+        assert_eq!(received + reader.stats().dropped_frames, COUNT as u64);
+    }
+}
@@ -0,0 +1,364 @@
+use num_complex::Complex;
+
+/// Scaling applied when converting 8-bit samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Scaling {
+    /// Map the full 8-bit range onto -1.0..1.0 (or the full `i16` range).
+    #[default]
+    Normalized,
+    /// Only remove the DC offset, keeping the original step size.
+    Raw,
+}
+
+/// Convert interleaved unsigned 8-bit IQ into complex floats.
+///
+/// The 127.5 offset of the dongle's ADC is removed, so a zero input signal
+/// maps to exactly 0.0. A trailing odd byte is ignored.
+///
+/// # Arguments
+///
+/// * `buf` - Interleaved I/Q samples as returned by the device.
+/// * `scaling` - `Normalized` gives -1.0..1.0, `Raw` gives -127.5..127.5.
+///
+/// # Returns
+///
+/// One complex sample per I/Q pair.
+pub fn to_complex_f32(buf: &[u8], scaling: Scaling) -> Vec<Complex<f32>> {
+    let mut out = vec![Complex::new(0.0, 0.0); buf.len() / 2];
+    to_complex_f32_into(buf, &mut out, scaling);
+    out
+}
+
+/// Convert interleaved unsigned 8-bit IQ into complex floats in place.
+///
+/// Converts `min(buf.len() / 2, out.len())` samples, so a buffer can be
+/// reused across reads without reallocating.
+///
+/// # Arguments
+///
+/// * `buf` - Interleaved I/Q samples as returned by the device.
+/// * `out` - Where to write the converted samples.
+/// * `scaling` - `Normalized` gives -1.0..1.0, `Raw` gives -127.5..127.5.
+///
+/// # Returns
+///
+/// The number of samples written.
+pub fn to_complex_f32_into(buf: &[u8], out: &mut [Complex<f32>], scaling: Scaling) -> usize {
+    let n = out.len().min(buf.len() / 2);
+    let (buf, out) = (&buf[..n * 2], &mut out[..n]);
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512f") {
+            // Safety: AVX-512F support was just checked.
+            unsafe { simd::convert_avx512(buf, out, scaling) };
+            return n;
+        }
+        if is_x86_feature_detected!("avx2") {
+            // Safety: AVX2 support was just checked.
+            unsafe { simd::convert_avx2(buf, out, scaling) };
+            return n;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // Safety: NEON is part of the aarch64 baseline.
+        unsafe { simd::convert_neon(buf, out, scaling) };
+        return n;
+    }
+
+    #[allow(unreachable_code)]
+    {
+        convert_lut(buf, out, scaling);
+        n
+    }
+}
+
+/// Get the conversion path `to_complex_f32_into` uses on this machine.
+///
+/// # Returns
+///
+/// `"avx512"`, `"avx2"`, `"neon"` or `"lut"`.
+pub fn conversion_path() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512f") {
+            return "avx512";
+        }
+        if is_x86_feature_detected!("avx2") {
+            return "avx2";
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        return "neon";
+    }
+    #[allow(unreachable_code)]
+    "lut"
+}
+
+const fn build_lut(scale: f32) -> [f32; 256] {
+    let mut lut = [0.0; 256];
+    let mut i = 0;
+    while i < 256 {
+        lut[i] = (i as f32 - 127.5) * scale;
+        i += 1;
+    }
+    lut
+}
+
+static LUT_NORMALIZED: [f32; 256] = build_lut(1.0 / 127.5);
+static LUT_RAW: [f32; 256] = build_lut(1.0);
+
+fn lut(scaling: Scaling) -> &'static [f32; 256] {
+    match scaling {
+        Scaling::Normalized => &LUT_NORMALIZED,
+        Scaling::Raw => &LUT_RAW,
+    }
+}
+
+/// Portable conversion through a 256-entry lookup table.
+fn convert_lut(buf: &[u8], out: &mut [Complex<f32>], scaling: Scaling) {
+    let lut = lut(scaling);
+    for (o, pair) in out.iter_mut().zip(buf.chunks_exact(2)) {
+        *o = Complex::new(lut[pair[0] as usize], lut[pair[1] as usize]);
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+mod simd {
+    use super::{convert_lut, Scaling};
+    use num_complex::Complex;
+
+    fn scale(scaling: Scaling) -> f32 {
+        match scaling {
+            Scaling::Normalized => 1.0 / 127.5,
+            Scaling::Raw => 1.0,
+        }
+    }
+
+    // Every path computes (v - 127.5) * scale like the lookup table: the
+    // subtraction is exact, so the results match it bit for bit.
+
+    /// Convert 64 components (32 samples) per step with AVX-512.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn convert_avx512(buf: &[u8], out: &mut [Complex<f32>], scaling: Scaling) {
+        use std::arch::x86_64::*;
+
+        let offset = _mm512_set1_ps(127.5);
+        let scale = _mm512_set1_ps(scale(scaling));
+        let chunks = buf.len() / 64;
+        // Complex<f32> is repr(C), so the output is a plain run of f32s.
+        let dst = out.as_mut_ptr() as *mut f32;
+        for i in 0..chunks {
+            let src = buf.as_ptr().add(i * 64);
+            for j in 0..4 {
+                let bytes = _mm_loadu_si128(src.add(j * 16) as *const __m128i);
+                let floats = _mm512_cvtepi32_ps(_mm512_cvtepu8_epi32(bytes));
+                let floats = _mm512_mul_ps(_mm512_sub_ps(floats, offset), scale);
+                _mm512_storeu_ps(dst.add(i * 64 + j * 16), floats);
+            }
+        }
+        convert_lut(&buf[chunks * 64..], &mut out[chunks * 32..], scaling);
+    }
+
+    /// Convert 32 components (16 samples) per step with AVX2.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn convert_avx2(buf: &[u8], out: &mut [Complex<f32>], scaling: Scaling) {
+        use std::arch::x86_64::*;
+
+        let offset = _mm256_set1_ps(127.5);
+        let scale = _mm256_set1_ps(scale(scaling));
+        let chunks = buf.len() / 32;
+        let dst = out.as_mut_ptr() as *mut f32;
+        for i in 0..chunks {
+            let src = buf.as_ptr().add(i * 32);
+            for j in 0..4 {
+                let bytes = _mm_loadl_epi64(src.add(j * 8) as *const __m128i);
+                let floats = _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(bytes));
+                let floats = _mm256_mul_ps(_mm256_sub_ps(floats, offset), scale);
+                _mm256_storeu_ps(dst.add(i * 32 + j * 8), floats);
+            }
+        }
+        convert_lut(&buf[chunks * 32..], &mut out[chunks * 16..], scaling);
+    }
+
+    /// Convert 16 components (8 samples) per step with NEON.
+    #[cfg(target_arch = "aarch64")]
+    pub unsafe fn convert_neon(buf: &[u8], out: &mut [Complex<f32>], scaling: Scaling) {
+        use std::arch::aarch64::*;
+
+        let offset = vdupq_n_f32(127.5);
+        let scale = vdupq_n_f32(scale(scaling));
+        let chunks = buf.len() / 16;
+        let dst = out.as_mut_ptr() as *mut f32;
+        for i in 0..chunks {
+            let bytes = vld1q_u8(buf.as_ptr().add(i * 16));
+            let lo = vmovl_u8(vget_low_u8(bytes));
+            let hi = vmovl_u8(vget_high_u8(bytes));
+            let words = [
+                vmovl_u16(vget_low_u16(lo)),
+                vmovl_u16(vget_high_u16(lo)),
+                vmovl_u16(vget_low_u16(hi)),
+                vmovl_u16(vget_high_u16(hi)),
+            ];
+            for (j, w) in words.iter().enumerate() {
+                let floats = vmulq_f32(vsubq_f32(vcvtq_f32_u32(*w), offset), scale);
+                vst1q_f32(dst.add(i * 16 + j * 4), floats);
+            }
+        }
+        convert_lut(&buf[chunks * 16..], &mut out[chunks * 8..], scaling);
+    }
+}
+
+/// Convert interleaved unsigned 8-bit IQ into complex 16-bit integers.
+///
+/// To remove the 127.5 offset without rounding, each component is mapped to
+/// `2 * v - 255`, i.e. odd values in -255..=255. `Normalized` additionally
+/// shifts them up to use the full `i16` range.
+///
+/// # Arguments
+///
+/// * `buf` - Interleaved I/Q samples as returned by the device.
+/// * `scaling` - `Normalized` gives -32640..=32640, `Raw` gives -255..=255.
+///
+/// # Returns
+///
+/// One complex sample per I/Q pair.
+pub fn to_complex_i16(buf: &[u8], scaling: Scaling) -> Vec<Complex<i16>> {
+    let shift = match scaling {
+        Scaling::Normalized => 7,
+        Scaling::Raw => 0,
+    };
+    buf.chunks_exact(2)
+        .map(|pair| {
+            Complex::new(
+                (2 * pair[0] as i16 - 255) << shift,
+                (2 * pair[1] as i16 - 255) << shift,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every byte value, for I and Q, followed by an odd-length tail that
+    /// the SIMD paths hand to the lookup table.
+    fn all_bytes() -> Vec<u8> {
+        let mut buf: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+        buf.extend((0..75).map(|i| (i * 37 % 256) as u8));
+        buf
+    }
+
+    fn bits(samples: &[Complex<f32>]) -> Vec<(u32, u32)> {
+        samples
+            .iter()
+            .map(|c| (c.re.to_bits(), c.im.to_bits()))
+            .collect()
+    }
+
+    #[test]
+    fn removes_offset() {
+        let raw = to_complex_f32(&[0, 127, 128, 255], Scaling::Raw);
+        assert_eq!(raw, [Complex::new(-127.5, -0.5), Complex::new(0.5, 127.5)]);
+
+        let norm = to_complex_f32(&[0, 127, 128, 255], Scaling::Normalized);
+        assert_eq!(norm[0], Complex::new(-1.0, -0.5 / 127.5));
+        assert_eq!(norm[1], Complex::new(0.5 / 127.5, 1.0));
+    }
+
+    #[test]
+    fn normalized_is_raw_over_127_5() {
+        let buf = all_bytes();
+        let raw = to_complex_f32(&buf, Scaling::Raw);
+        let norm = to_complex_f32(&buf, Scaling::Normalized);
+        for (r, n) in raw.iter().zip(&norm) {
+            assert!((r.re / 127.5 - n.re).abs() <= f32::EPSILON);
+            assert!((r.im / 127.5 - n.im).abs() <= f32::EPSILON);
+            assert!(n.re.abs() <= 1.0 && n.im.abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn i16_maps_to_2v_minus_255() {
+        let raw = to_complex_i16(&[0, 127, 128, 255], Scaling::Raw);
+        assert_eq!(raw, [Complex::new(-255, -1), Complex::new(1, 255)]);
+
+        let norm = to_complex_i16(&[0, 127, 128, 255], Scaling::Normalized);
+        assert_eq!(norm, [Complex::new(-32640, -128), Complex::new(128, 32640)]);
+    }
+
+    #[test]
+    fn drops_trailing_odd_byte() {
+        assert_eq!(to_complex_f32(&[255, 0, 42], Scaling::Raw).len(), 1);
+        assert_eq!(to_complex_i16(&[255, 0, 42], Scaling::Raw).len(), 1);
+
+        let mut out = [Complex::new(9.0, 9.0); 2];
+        assert_eq!(
+            to_complex_f32_into(&[255, 0, 42], &mut out, Scaling::Raw),
+            1
+        );
+        assert_eq!(out[0], Complex::new(127.5, -127.5));
+        assert_eq!(out[1], Complex::new(9.0, 9.0));
+    }
+
+    #[test]
+    fn into_stops_at_output_len() {
+        let mut out = [Complex::new(0.0, 0.0); 1];
+        assert_eq!(
+            to_complex_f32_into(&[0, 0, 255, 255], &mut out, Scaling::Raw),
+            1
+        );
+        assert_eq!(out[0], Complex::new(-127.5, -127.5));
+    }
+
+    #[test]
+    fn dispatched_path_matches_lut() {
+        let buf = all_bytes();
+        for scaling in [Scaling::Normalized, Scaling::Raw] {
+            let mut expected = vec![Complex::new(0.0, 0.0); buf.len() / 2];
+            convert_lut(&buf, &mut expected, scaling);
+            let got = to_complex_f32(&buf, scaling);
+            assert_eq!(bits(&got), bits(&expected), "{}", conversion_path());
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn x86_paths_match_lut() {
+        let buf = all_bytes();
+        let n = buf.len() / 2;
+        for scaling in [Scaling::Normalized, Scaling::Raw] {
+            let mut expected = vec![Complex::new(0.0, 0.0); n];
+            convert_lut(&buf[..n * 2], &mut expected, scaling);
+            if is_x86_feature_detected!("avx2") {
+                let mut got = vec![Complex::new(0.0, 0.0); n];
+                unsafe { simd::convert_avx2(&buf[..n * 2], &mut got, scaling) };
+                assert_eq!(bits(&got), bits(&expected), "avx2");
+            }
+            if is_x86_feature_detected!("avx512f") {
+                let mut got = vec![Complex::new(0.0, 0.0); n];
+                unsafe { simd::convert_avx512(&buf[..n * 2], &mut got, scaling) };
+                assert_eq!(bits(&got), bits(&expected), "avx512");
+            }
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn neon_matches_lut() {
+        let buf = all_bytes();
+        let n = buf.len() / 2;
+        for scaling in [Scaling::Normalized, Scaling::Raw] {
+            let mut expected = vec![Complex::new(0.0, 0.0); n];
+            convert_lut(&buf[..n * 2], &mut expected, scaling);
+            let mut got = vec![Complex::new(0.0, 0.0); n];
+            unsafe { simd::convert_neon(&buf[..n * 2], &mut got, scaling) };
+            assert_eq!(bits(&got), bits(&expected));
+        }
+    }
+}
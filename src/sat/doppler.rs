@@ -0,0 +1,181 @@
+use std::time::{Duration, SystemTime};
+
+use super::sgp4::Sgp4;
+use super::station::GroundStation;
+use crate::error::Result;
+use crate::source::SdrSource;
+
+/// Keeps a source tuned to a satellite's downlink through a pass.
+///
+/// Each `update` works out the Doppler-shifted downlink for the given time
+/// and retunes when it has moved far enough from the frequency last tuned,
+/// but no more often than the minimum interval, so the dongle's PLL isn't
+/// reprogrammed for every few Hz. The shift reaches about ±3.5 kHz at
+/// 137 MHz and ±10 kHz at 435 MHz, changing fastest at culmination.
+///
+/// ```no_run
+/// use std::time::SystemTime;
+///
+/// use radion::sat::{DopplerTracker, GroundStation, Sgp4, Tle};
+/// use radion::{Device, Frequency};
+///
+/// let tle = Tle::parse(
+///     "1 25544U 98067A   24001.50000000  .00016717  00000-0  10270-3 0  9005",
+///     "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391428600",
+/// )?;
+/// let iss = Sgp4::new(&tle)?;
+/// let station = GroundStation::new(51.5, -0.1, 30.0);
+///
+/// let mut device = Device::new(0)?;
+/// let mut tracker = DopplerTracker::new(iss, station, 145_800_000);
+/// loop {
+///     if let Some(freq) = tracker.update(&mut device, SystemTime::now())? {
+///         println!("retuned to {}", Frequency::from(freq));
+///     }
+///     let _samples = device.read_sync(65_536)?;
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct DopplerTracker {
+    sat: Sgp4,
+    station: GroundStation,
+    downlink: f64,
+    offset: f64,
+    threshold: f64,
+    min_interval: Duration,
+    /// When the source was last tuned and to what.
+    tuned: Option<(SystemTime, u32)>,
+    retunes: u64,
+}
+
+impl DopplerTracker {
+    /// Create a tracker.
+    ///
+    /// # Arguments
+    ///
+    /// * `sat` - The satellite's propagator.
+    /// * `station` - Where the receiver is.
+    /// * `downlink` - The transmitted frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `DopplerTracker` tuning onto the downlink, retuning after it
+    /// moves 100 Hz and at most once a second.
+    pub fn new(sat: Sgp4, station: GroundStation, downlink: u32) -> Self {
+        DopplerTracker {
+            sat,
+            station,
+            downlink: downlink as f64,
+            offset: 0.0,
+            threshold: 100.0,
+            min_interval: Duration::from_secs(1),
+            tuned: None,
+            retunes: 0,
+        }
+    }
+
+    /// Tune a fixed distance from the downlink, to keep it clear of the
+    /// DC spike.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - How far the tuned frequency is from the corrected
+    ///   downlink in Hz. The downlink then sits at minus this offset in
+    ///   the samples.
+    ///
+    /// # Returns
+    ///
+    /// The `DopplerTracker` with the offset set.
+    pub fn with_tune_offset(mut self, offset: i32) -> Self {
+        self.offset = offset as f64;
+        self
+    }
+
+    /// Set how far the downlink must move before retuning.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The change in Hz, well inside the receiver's
+    ///   channel filter.
+    ///
+    /// # Returns
+    ///
+    /// The `DopplerTracker` with the new threshold.
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold as f64;
+        self
+    }
+
+    /// Set the shortest time between retunes.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The interval.
+    ///
+    /// # Returns
+    ///
+    /// The `DopplerTracker` with the new interval.
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Get the received downlink frequency at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time.
+    ///
+    /// # Returns
+    ///
+    /// The frequency in Hz if the orbit is still valid, otherwise
+    /// `Error::OrbitDecayed`.
+    pub fn frequency(&self, time: SystemTime) -> Result<f64> {
+        let look = self.station.look(&self.sat, time)?;
+        Ok(self.downlink + look.doppler(self.downlink))
+    }
+
+    /// Get the frequency the source was last tuned to, if any.
+    pub fn tuned(&self) -> Option<u32> {
+        self.tuned.map(|(_, freq)| freq)
+    }
+
+    /// Get how many times the source has been tuned.
+    pub fn retunes(&self) -> u64 {
+        self.retunes
+    }
+
+    /// Retune the source if the downlink has moved far enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to tune.
+    /// * `time` - The current time, or the time of the samples about to be
+    ///   read when working from a schedule.
+    ///
+    /// # Returns
+    ///
+    /// The new frequency if the source was retuned, `None` if it was left
+    /// alone, otherwise an `Error` from the source or
+    /// `Error::OrbitDecayed`.
+    pub fn update<S: SdrSource>(
+        &mut self,
+        source: &mut S,
+        time: SystemTime,
+    ) -> Result<Option<u32>> {
+        let target = (self.frequency(time)? + self.offset).round() as u32;
+        if let Some((at, freq)) = self.tuned {
+            // A clock stepping backwards counts as time passed.
+            let waited = time
+                .duration_since(at)
+                .map_or(true, |elapsed| elapsed >= self.min_interval);
+            if !waited || (target as f64 - freq as f64).abs() < self.threshold {
+                return Ok(None);
+            }
+        }
+        source.tune(target)?;
+        self.tuned = Some((time, target));
+        self.retunes += 1;
+        Ok(Some(target))
+    }
+}
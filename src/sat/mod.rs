@@ -0,0 +1,56 @@
+//! Satellite pass prediction and Doppler correction.
+//!
+//! Parse a satellite's two-line elements with `Tle`, propagate its orbit
+//! with `Sgp4`, and find where it appears and when it passes over a
+//! `GroundStation`. `DopplerTracker` keeps any `SdrSource` tuned to the
+//! satellite's shifted downlink during a pass.
+//!
+//! ```no_run
+//! use std::time::{Duration, SystemTime};
+//!
+//! use radion::sat::{GroundStation, Sgp4, Tle};
+//!
+//! let text = std::fs::read_to_string("weather.txt")?;
+//! let station = GroundStation::new(52.2, 0.12, 20.0);
+//! let now = SystemTime::now();
+//! for tle in Tle::parse_all(&text)? {
+//!     let sat = Sgp4::new(&tle)?;
+//!     for pass in station.passes(&sat, now, now + Duration::from_secs(86_400), 10.0)? {
+//!         println!(
+//!             "{}: {:?} for {:?}, up to {:.0}°",
+//!             tle.name.as_deref().unwrap_or("?"),
+//!             pass.aos,
+//!             pass.duration(),
+//!             pass.max_elevation
+//!         );
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod doppler;
+mod sgp4;
+mod station;
+mod tle;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use doppler::DopplerTracker;
+pub use sgp4::{Sgp4, State};
+pub use station::{GroundStation, Look, Pass};
+pub use tle::Tle;
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+fn from_unix_seconds(seconds: f64) -> SystemTime {
+    if seconds >= 0.0 {
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    } else {
+        UNIX_EPOCH - Duration::from_secs_f64(-seconds)
+    }
+}
@@ -0,0 +1,499 @@
+use std::f64::consts::{PI, TAU};
+use std::time::SystemTime;
+
+use super::tle::Tle;
+use super::unix_seconds;
+use crate::error::{Error, Result};
+
+/// WGS 72 constants, which element sets are fitted with.
+const EARTH_RADIUS: f64 = 6378.135;
+const MU: f64 = 398_600.8;
+const J2: f64 = 0.001_082_616;
+const J3: f64 = -0.000_002_538_81;
+const J4: f64 = -0.000_001_655_97;
+
+/// Orbits longer than this need the deep-space SDP4 terms.
+const DEEP_SPACE_MINUTES: f64 = 225.0;
+
+/// A satellite's position and velocity in the TEME frame, the
+/// true-equator mean-equinox frame SGP4 works in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct State {
+    /// Position in km.
+    pub position: [f64; 3],
+    /// Velocity in km/s.
+    pub velocity: [f64; 3],
+}
+
+/// SGP4 orbit propagator for near-Earth satellites.
+///
+/// Implements the near-Earth model of Spacetrack Report #3 as revised by
+/// Vallado et al. (2006), which covers orbits shorter than 225 minutes:
+/// weather, amateur, Earth observation and crewed satellites. Element sets
+/// for higher orbits, which need the deep-space SDP4 terms, are rejected.
+#[derive(Clone, Debug)]
+pub struct Sgp4 {
+    epoch: f64,
+    bstar: f64,
+    ecco: f64,
+    inclo: f64,
+    nodeo: f64,
+    argpo: f64,
+    mo: f64,
+    no: f64,
+    simple: bool,
+    aycof: f64,
+    con41: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    argpdot: f64,
+    omgcof: f64,
+    sinmao: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    mdot: f64,
+    nodedot: f64,
+    xlcof: f64,
+    xmcof: f64,
+    nodecf: f64,
+}
+
+fn xke() -> f64 {
+    60.0 / (EARTH_RADIUS.powi(3) / MU).sqrt()
+}
+
+impl Sgp4 {
+    /// Initialise the propagator.
+    ///
+    /// # Arguments
+    ///
+    /// * `tle` - The satellite's element set.
+    ///
+    /// # Returns
+    ///
+    /// A new `Sgp4`, otherwise `Error::InvalidTle` for deep-space orbits
+    /// and elements SGP4 cannot use.
+    pub fn new(tle: &Tle) -> Result<Self> {
+        let xke = xke();
+        let j3oj2 = J3 / J2;
+        let ecco = tle.eccentricity;
+        let inclo = tle.inclination.to_radians();
+        let no_kozai = tle.mean_motion * TAU / 1440.0;
+        if !(0.0..1.0).contains(&ecco) || no_kozai <= 0.0 {
+            return Err(Error::InvalidTle("elements describe no orbit".into()));
+        }
+
+        // Recover the original mean motion from the Kozai mean motion.
+        let eccsq = ecco * ecco;
+        let omeosq = 1.0 - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let ak = (xke / no_kozai).powf(2.0 / 3.0);
+        let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+        let del = d1 / (adel * adel);
+        let no = no_kozai / (1.0 + del);
+        if TAU / no >= DEEP_SPACE_MINUTES {
+            return Err(Error::InvalidTle(
+                "deep-space orbits of 225 minutes or more are not supported".into(),
+            ));
+        }
+
+        let ao = (xke / no).powf(2.0 / 3.0);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1.0 - 5.0 * cosio2;
+        let con41 = -con42 - 2.0 * cosio2;
+        let posq = po * po;
+        let rp = ao * (1.0 - ecco);
+
+        // Perigees below 220 km use a truncated drag model.
+        let simple = rp < 220.0 / EARTH_RADIUS + 1.0;
+        let mut sfour = 78.0 / EARTH_RADIUS + 1.0;
+        let mut qzms24 = ((120.0 - 78.0) / EARTH_RADIUS).powi(4);
+        let perigee = (rp - 1.0) * EARTH_RADIUS;
+        if perigee < 156.0 {
+            let s = if perigee < 98.0 { 20.0 } else { perigee - 78.0 };
+            qzms24 = ((120.0 - s) / EARTH_RADIUS).powi(4);
+            sfour = s / EARTH_RADIUS + 1.0;
+        }
+        let pinvsq = 1.0 / posq;
+        let tsi = 1.0 / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1.0 - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * no
+            * (ao * (1.0 + 1.5 * etasq + eeta * (4.0 + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8.0 + 3.0 * etasq * (8.0 + etasq)));
+        let cc1 = tle.bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2.0 * coef * tsi * j3oj2 * no * sinio / ecco
+        } else {
+            0.0
+        };
+        let x1mth2 = 1.0 - cosio2;
+        let argpo = tle.arg_perigee.to_radians();
+        let cc4 = 2.0
+            * no
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2.0 + 0.5 * etasq) + ecco * (0.5 + 2.0 * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3.0 * con41 * (1.0 - 2.0 * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75
+                            * x1mth2
+                            * (2.0 * etasq - eeta * (1.0 + etasq))
+                            * (2.0 * argpo).cos()));
+        let cc5 = 2.0 * coef1 * ao * omeosq * (1.0 + 2.75 * (etasq + eeta) + eeta * etasq);
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no;
+        let mdot = no
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13.0 - 78.0 * cosio2 + 137.0 * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7.0 - 114.0 * cosio2 + 395.0 * cosio4)
+            + temp3 * (3.0 - 36.0 * cosio2 + 49.0 * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1
+            + (0.5 * temp2 * (4.0 - 19.0 * cosio2) + 2.0 * temp3 * (3.0 - 7.0 * cosio2)) * cosio;
+        let mo = tle.mean_anomaly.to_radians();
+        let xlcof_den = if (cosio + 1.0).abs() > 1.5e-12 {
+            1.0 + cosio
+        } else {
+            1.5e-12
+        };
+
+        let mut sgp4 = Sgp4 {
+            epoch: unix_seconds(tle.epoch),
+            bstar: tle.bstar,
+            ecco,
+            inclo,
+            nodeo: tle.raan.to_radians(),
+            argpo,
+            mo,
+            no,
+            simple,
+            aycof: -0.5 * j3oj2 * sinio,
+            con41,
+            cc1,
+            cc4,
+            cc5,
+            d2: 0.0,
+            d3: 0.0,
+            d4: 0.0,
+            delmo: (1.0 + eta * mo.cos()).powi(3),
+            eta,
+            argpdot,
+            omgcof: tle.bstar * cc3 * argpo.cos(),
+            sinmao: mo.sin(),
+            t2cof: 1.5 * cc1,
+            t3cof: 0.0,
+            t4cof: 0.0,
+            t5cof: 0.0,
+            x1mth2,
+            x7thm1: 7.0 * cosio2 - 1.0,
+            mdot,
+            nodedot,
+            xlcof: -0.25 * j3oj2 * sinio * (3.0 + 5.0 * cosio) / xlcof_den,
+            xmcof: if ecco > 1.0e-4 {
+                -2.0 / 3.0 * coef * tle.bstar / eeta
+            } else {
+                0.0
+            },
+            nodecf: 3.5 * omeosq * xhdot1 * cc1,
+        };
+        if !simple {
+            let cc1sq = cc1 * cc1;
+            let d2 = 4.0 * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.0;
+            let d3 = (17.0 * ao + sfour) * temp;
+            let d4 = 0.5 * temp * ao * tsi * (221.0 * ao + 31.0 * sfour) * cc1;
+            sgp4.d2 = d2;
+            sgp4.d3 = d3;
+            sgp4.d4 = d4;
+            sgp4.t3cof = d2 + 2.0 * cc1sq;
+            sgp4.t4cof = 0.25 * (3.0 * d3 + cc1 * (12.0 * d2 + 10.0 * cc1sq));
+            sgp4.t5cof = 0.2
+                * (3.0 * d4 + 12.0 * cc1 * d3 + 6.0 * d2 * d2 + 15.0 * cc1sq * (2.0 * d2 + cc1sq));
+        }
+        Ok(sgp4)
+    }
+
+    /// Get the satellite's state at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time, ideally within a few days of the epoch.
+    ///
+    /// # Returns
+    ///
+    /// The `State` if the orbit is still valid, otherwise
+    /// `Error::OrbitDecayed`.
+    pub fn propagate(&self, time: SystemTime) -> Result<State> {
+        self.propagate_minutes((unix_seconds(time) - self.epoch) / 60.0)
+    }
+
+    /// Get the satellite's state a number of minutes from the epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `minutes` - Minutes since the element set's epoch, negative
+    ///   before it.
+    ///
+    /// # Returns
+    ///
+    /// The `State` if the orbit is still valid, otherwise
+    /// `Error::OrbitDecayed`.
+    pub fn propagate_minutes(&self, minutes: f64) -> Result<State> {
+        let xke = xke();
+        let t = minutes;
+        let t2 = t * t;
+
+        // Secular gravity and atmospheric drag.
+        let xmdf = self.mo + self.mdot * t;
+        let argpdf = self.argpo + self.argpdot * t;
+        let nodedf = self.nodeo + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1.0 - self.cc1 * t;
+        let mut tempe = self.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+        if !self.simple {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1.0 + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            mm = xmdf + delomg + delm;
+            argpm = argpdf - delomg - delm;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa -= self.d2 * t2 + self.d3 * t3 + self.d4 * t4;
+            tempe += self.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let am = (xke / self.no).powf(2.0 / 3.0) * tempa * tempa;
+        if am <= 0.0 {
+            return Err(Error::OrbitDecayed);
+        }
+        let nm = xke / am.powf(1.5);
+        let mut em = self.ecco - tempe;
+        if !(-0.001..1.0).contains(&em) {
+            return Err(Error::OrbitDecayed);
+        }
+        em = em.max(1.0e-6);
+        mm += self.no * templ;
+        let xlm = mm + argpm + nodem;
+        let nodem = nodem.rem_euclid(TAU);
+        let argpm = argpm.rem_euclid(TAU);
+        let xlm = xlm.rem_euclid(TAU);
+        let mm = (xlm - argpm - nodem).rem_euclid(TAU);
+        let (sinip, cosip) = self.inclo.sin_cos();
+
+        // Long-period periodics.
+        let axnl = em * argpm.cos();
+        let temp = 1.0 / (am * (1.0 - em * em));
+        let aynl = em * argpm.sin() + temp * self.aycof;
+        let xl = mm + argpm + nodem + temp * self.xlcof * axnl;
+
+        // Kepler's equation.
+        let u = (xl - nodem).rem_euclid(TAU);
+        let mut eo1 = u;
+        let (mut sineo1, mut coseo1) = (0.0, 0.0);
+        for _ in 0..10 {
+            (sineo1, coseo1) = eo1.sin_cos();
+            let step =
+                (u - aynl * coseo1 + axnl * sineo1 - eo1) / (1.0 - coseo1 * axnl - sineo1 * aynl);
+            eo1 += step.clamp(-0.95, 0.95);
+            if step.abs() < 1.0e-12 {
+                break;
+            }
+        }
+
+        // Short-period periodics.
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1.0 - el2);
+        if pl < 0.0 {
+            return Err(Error::OrbitDecayed);
+        }
+        let rl = am * (1.0 - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1.0 - el2).sqrt();
+        let temp = esine / (1.0 + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1.0 - 2.0 * sinu * sinu;
+        let temp = 1.0 / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let mrt = rl * (1.0 - 1.5 * temp2 * betal * self.con41) + 0.5 * temp1 * self.x1mth2 * cos2u;
+        if mrt < 1.0 {
+            return Err(Error::OrbitDecayed);
+        }
+        let su = su - 0.25 * temp2 * self.x7thm1 * sin2u;
+        let xnode = nodem + 1.5 * temp2 * cosip * sin2u;
+        let xinc = self.inclo + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * self.x1mth2 * sin2u / xke;
+        let rvdot = rvdotl + nm * temp1 * (self.x1mth2 * cos2u + 1.5 * self.con41) / xke;
+
+        // Orientation vectors.
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let u = [
+            xmx * sinsu + cnod * cossu,
+            xmy * sinsu + snod * cossu,
+            sini * sinsu,
+        ];
+        let v = [
+            xmx * cossu - cnod * sinsu,
+            xmy * cossu - snod * sinsu,
+            sini * cossu,
+        ];
+        let km_per_s = EARTH_RADIUS * xke / 60.0;
+        Ok(State {
+            position: u.map(|c| mrt * c * EARTH_RADIUS),
+            velocity: [0, 1, 2].map(|i| (mvt * u[i] + rvdot * v[i]) * km_per_s),
+        })
+    }
+}
+
+/// Greenwich mean sidereal time in radians, IAU 1982.
+pub(crate) fn gmst(unix: f64) -> f64 {
+    let tut1 = (unix / 86_400.0 + 2_440_587.5 - 2_451_545.0) / 36_525.0;
+    let seconds = -6.2e-6 * tut1.powi(3)
+        + 0.093_104 * tut1 * tut1
+        + (876_600.0 * 3600.0 + 8_640_184.812_866) * tut1
+        + 67_310.548_41;
+    (seconds * PI / 180.0 / 240.0).rem_euclid(TAU)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expected states from Vallado's `tcppver.out`: minutes since epoch,
+    /// position in km and velocity in km/s.
+    type Vector = (f64, [f64; 3], [f64; 3]);
+
+    fn check(line1: &str, line2: &str, vectors: &[Vector]) {
+        let sgp4 = Sgp4::new(&Tle::parse(line1, line2).unwrap()).unwrap();
+        for &(minutes, position, velocity) in vectors {
+            let state = sgp4.propagate_minutes(minutes).unwrap();
+            for i in 0..3 {
+                assert!(
+                    (state.position[i] - position[i]).abs() < 1e-6,
+                    "position[{}] at {} min: {} != {}",
+                    i,
+                    minutes,
+                    state.position[i],
+                    position[i]
+                );
+                assert!(
+                    (state.velocity[i] - velocity[i]).abs() < 1e-9,
+                    "velocity[{}] at {} min: {} != {}",
+                    i,
+                    minutes,
+                    state.velocity[i],
+                    velocity[i]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn catalog_00005() {
+        check(
+            "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753",
+            "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667",
+            &[
+                (
+                    0.0,
+                    [7022.46529266, -1400.08296755, 0.03995155],
+                    [1.893841015, 6.405893759, 4.534807250],
+                ),
+                (
+                    360.0,
+                    [-7154.03120202, -3783.17682504, -3536.19412294],
+                    [4.741887409, -4.151817765, -2.093935425],
+                ),
+                (
+                    720.0,
+                    [-7134.59340119, 6531.68641334, 3260.27186483],
+                    [-4.113793027, -2.911922039, -2.557327851],
+                ),
+                (
+                    1080.0,
+                    [5568.53901181, 4492.06992591, 3863.87641983],
+                    [-4.209106476, 5.159719888, 2.744852980],
+                ),
+                (
+                    1440.0,
+                    [-938.55923943, -6268.18748831, -4294.02924751],
+                    [7.536105209, -0.427127707, 0.989878080],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn catalog_06251() {
+        check(
+            "1 06251U 62025E   06176.82412014  .00008885  00000-0  12808-3 0  3985",
+            "2 06251  58.0579  54.0425 0030035 139.1568 221.1854 15.56387291  6774",
+            &[
+                (
+                    0.0,
+                    [3988.31022699, 5498.96657235, 0.90055879],
+                    [-3.290032738, 2.357652820, 6.496623475],
+                ),
+                (
+                    120.0,
+                    [-3935.69800083, 409.10980837, 5471.33577327],
+                    [-3.374784183, -6.635211043, -1.942056221],
+                ),
+                (
+                    240.0,
+                    [-1675.12766915, -5683.30432352, -3286.21510937],
+                    [5.282496925, 1.508674259, -5.354872978],
+                ),
+                (
+                    360.0,
+                    [4993.62642836, 2890.54969900, -3600.40145627],
+                    [0.347333429, 5.707031557, 5.070699638],
+                ),
+                (
+                    720.0,
+                    [3692.60030028, -976.24265255, -5623.36447493],
+                    [3.897257243, 6.415554948, 1.429112190],
+                ),
+            ],
+        );
+    }
+}
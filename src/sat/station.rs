@@ -0,0 +1,287 @@
+use std::time::{Duration, SystemTime};
+
+use super::sgp4::{gmst, Sgp4};
+use super::{from_unix_seconds, unix_seconds};
+use crate::error::Result;
+
+/// WGS 84 ellipsoid.
+const WGS84_A: f64 = 6378.137;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// The Earth's rotation rate in radians per second.
+const EARTH_ROTATION: f64 = 7.292_115e-5;
+
+/// The speed of light in km/s.
+const SPEED_OF_LIGHT: f64 = 299_792.458;
+
+/// How far apart elevations are sampled when searching for passes.
+const SEARCH_STEP: f64 = 20.0;
+
+/// How far past the end of the search a pass in progress is followed.
+const MAX_PASS: f64 = 3.0 * 3600.0;
+
+/// Where a satellite appears from a ground station.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Look {
+    /// Azimuth in degrees clockwise from true north.
+    pub azimuth: f64,
+    /// Elevation in degrees above the horizon, negative below it.
+    pub elevation: f64,
+    /// Distance in km.
+    pub range: f64,
+    /// How fast the distance is growing in km/s, negative while the
+    /// satellite approaches.
+    pub range_rate: f64,
+}
+
+impl Look {
+    /// Get the Doppler shift of a transmission.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The transmitted frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// How far the received frequency is above the transmitted one in Hz.
+    pub fn doppler(&self, frequency: f64) -> f64 {
+        -self.range_rate / SPEED_OF_LIGHT * frequency
+    }
+}
+
+/// A satellite pass over a ground station.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pass {
+    /// When the satellite rises above the minimum elevation, or the start
+    /// of the search for a pass already in progress.
+    pub aos: SystemTime,
+    /// When it sets below the minimum elevation again.
+    pub los: SystemTime,
+    /// When it is highest.
+    pub culmination: SystemTime,
+    /// The highest elevation in degrees.
+    pub max_elevation: f64,
+    /// The azimuth at AOS in degrees.
+    pub aos_azimuth: f64,
+    /// The azimuth at LOS in degrees.
+    pub los_azimuth: f64,
+}
+
+impl Pass {
+    /// Get how long the pass lasts.
+    pub fn duration(&self) -> Duration {
+        self.los.duration_since(self.aos).unwrap_or_default()
+    }
+}
+
+/// A receiving location on the ground.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GroundStation {
+    /// Latitude in degrees, positive north.
+    pub lat: f64,
+    /// Longitude in degrees, positive east.
+    pub lon: f64,
+    /// Height above the WGS 84 ellipsoid in metres.
+    pub altitude: f64,
+}
+
+impl GroundStation {
+    /// Create a ground station.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude in degrees, positive north.
+    /// * `lon` - Longitude in degrees, positive east.
+    /// * `altitude` - Height above the WGS 84 ellipsoid in metres.
+    ///
+    /// # Returns
+    ///
+    /// A new `GroundStation`.
+    pub fn new(lat: f64, lon: f64, altitude: f64) -> Self {
+        assert!(
+            (-90.0..=90.0).contains(&lat),
+            "latitude must be within ±90°"
+        );
+        assert!(
+            (-180.0..=360.0).contains(&lon),
+            "longitude must be within -180° to 360°"
+        );
+        GroundStation { lat, lon, altitude }
+    }
+
+    /// Get where a satellite appears at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `sat` - The satellite's propagator.
+    /// * `time` - The time.
+    ///
+    /// # Returns
+    ///
+    /// The `Look` angles if the orbit is still valid, otherwise
+    /// `Error::OrbitDecayed`.
+    pub fn look(&self, sat: &Sgp4, time: SystemTime) -> Result<Look> {
+        self.look_at(sat, unix_seconds(time))
+    }
+
+    /// Find the passes over the station in a time span.
+    ///
+    /// # Arguments
+    ///
+    /// * `sat` - The satellite's propagator.
+    /// * `from` - The start of the span. A pass in progress starts here.
+    /// * `until` - The end of the span. A pass rising before it is
+    ///   followed to its end.
+    /// * `min_elevation` - The elevation in degrees a pass must rise above
+    ///   and that marks AOS and LOS, e.g. 0 or a local horizon mask.
+    ///
+    /// # Returns
+    ///
+    /// The passes in order if the orbit stays valid, otherwise
+    /// `Error::OrbitDecayed`. Passes that rise above the minimum for less
+    /// than about 20 seconds may be missed.
+    pub fn passes(
+        &self,
+        sat: &Sgp4,
+        from: SystemTime,
+        until: SystemTime,
+        min_elevation: f64,
+    ) -> Result<Vec<Pass>> {
+        let (from, until) = (unix_seconds(from), unix_seconds(until));
+        let above =
+            |t: f64| -> Result<bool> { Ok(self.look_at(sat, t)?.elevation >= min_elevation) };
+        let mut passes = Vec::new();
+        let mut t = from;
+        let mut aos = above(t)?.then_some(t);
+        while t < until || (aos.is_some() && t < until + MAX_PASS) {
+            let next = t + SEARCH_STEP;
+            match (aos, above(next)?) {
+                (None, true) if t < until => {
+                    aos = Some(self.crossing(sat, t, next, min_elevation)?)
+                }
+                (Some(start), false) => {
+                    let end = self.crossing(sat, t, next, min_elevation)?;
+                    passes.push(self.pass(sat, start, end)?);
+                    aos = None;
+                }
+                _ => {}
+            }
+            t = next;
+        }
+        Ok(passes)
+    }
+
+    /// Find the next pass over the station within two days.
+    ///
+    /// # Arguments
+    ///
+    /// * `sat` - The satellite's propagator.
+    /// * `from` - When to start looking. A pass in progress starts here.
+    /// * `min_elevation` - The elevation in degrees a pass must rise above.
+    ///
+    /// # Returns
+    ///
+    /// The `Pass` if there is one, otherwise `None`, or
+    /// `Error::OrbitDecayed` if the orbit stops being valid.
+    pub fn next_pass(
+        &self,
+        sat: &Sgp4,
+        from: SystemTime,
+        min_elevation: f64,
+    ) -> Result<Option<Pass>> {
+        let mut start = from;
+        // Search in steps so a pass soon doesn't cost two days of steps.
+        for _ in 0..8 {
+            let end = start + Duration::from_secs(6 * 3600);
+            if let Some(pass) = self
+                .passes(sat, start, end, min_elevation)?
+                .into_iter()
+                .next()
+            {
+                return Ok(Some(pass));
+            }
+            start = end;
+        }
+        Ok(None)
+    }
+
+    fn look_at(&self, sat: &Sgp4, unix: f64) -> Result<Look> {
+        let state = sat.propagate(from_unix_seconds(unix))?;
+        // TEME to Earth-fixed, ignoring polar motion.
+        let (sin_g, cos_g) = gmst(unix).sin_cos();
+        let rotate = |v: [f64; 3]| {
+            [
+                cos_g * v[0] + sin_g * v[1],
+                -sin_g * v[0] + cos_g * v[1],
+                v[2],
+            ]
+        };
+        let r = rotate(state.position);
+        let v = rotate(state.velocity);
+        let v = [
+            v[0] + EARTH_ROTATION * r[1],
+            v[1] - EARTH_ROTATION * r[0],
+            v[2],
+        ];
+
+        let (sin_lat, cos_lat) = self.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.lon.to_radians().sin_cos();
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        let h = self.altitude / 1000.0;
+        let site = [
+            (n + h) * cos_lat * cos_lon,
+            (n + h) * cos_lat * sin_lon,
+            (n * (1.0 - e2) + h) * sin_lat,
+        ];
+        let rho = [r[0] - site[0], r[1] - site[1], r[2] - site[2]];
+        let east = -sin_lon * rho[0] + cos_lon * rho[1];
+        let north = -sin_lat * cos_lon * rho[0] - sin_lat * sin_lon * rho[1] + cos_lat * rho[2];
+        let up = cos_lat * cos_lon * rho[0] + cos_lat * sin_lon * rho[1] + sin_lat * rho[2];
+        let range = (rho[0] * rho[0] + rho[1] * rho[1] + rho[2] * rho[2]).sqrt();
+        Ok(Look {
+            azimuth: east.atan2(north).to_degrees().rem_euclid(360.0),
+            elevation: (up / range).asin().to_degrees(),
+            range,
+            range_rate: (rho[0] * v[0] + rho[1] * v[1] + rho[2] * v[2]) / range,
+        })
+    }
+
+    /// Bisect to the time within `a..b` the elevation crosses the minimum.
+    fn crossing(&self, sat: &Sgp4, mut a: f64, mut b: f64, min_elevation: f64) -> Result<f64> {
+        let rising = self.look_at(sat, a)?.elevation < min_elevation;
+        while b - a > 0.5 {
+            let mid = 0.5 * (a + b);
+            if (self.look_at(sat, mid)?.elevation < min_elevation) == rising {
+                a = mid;
+            } else {
+                b = mid;
+            }
+        }
+        Ok(0.5 * (a + b))
+    }
+
+    fn pass(&self, sat: &Sgp4, aos: f64, los: f64) -> Result<Pass> {
+        // Golden-section search for the culmination.
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut a, mut b) = (aos, los);
+        while b - a > 0.5 {
+            let c = b - ratio * (b - a);
+            let d = a + ratio * (b - a);
+            if self.look_at(sat, c)?.elevation > self.look_at(sat, d)?.elevation {
+                b = d;
+            } else {
+                a = c;
+            }
+        }
+        let culmination = 0.5 * (a + b);
+        Ok(Pass {
+            aos: from_unix_seconds(aos),
+            los: from_unix_seconds(los),
+            culmination: from_unix_seconds(culmination),
+            max_elevation: self.look_at(sat, culmination)?.elevation,
+            aos_azimuth: self.look_at(sat, aos)?.azimuth,
+            los_azimuth: self.look_at(sat, los)?.azimuth,
+        })
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+/// A NORAD two-line element set, the mean orbital elements SGP4 works
+/// from, as published by CelesTrak and Space-Track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tle {
+    /// The satellite's name from the line before the elements, if given.
+    pub name: Option<String>,
+    /// The NORAD catalogue number.
+    pub norad_id: u32,
+    /// When the elements are valid. Accuracy falls off over the days either
+    /// side, so fetch fresh elements at least weekly.
+    pub epoch: SystemTime,
+    /// Inclination in degrees.
+    pub inclination: f64,
+    /// Right ascension of the ascending node in degrees.
+    pub raan: f64,
+    /// Eccentricity.
+    pub eccentricity: f64,
+    /// Argument of perigee in degrees.
+    pub arg_perigee: f64,
+    /// Mean anomaly in degrees.
+    pub mean_anomaly: f64,
+    /// Mean motion in revolutions per day.
+    pub mean_motion: f64,
+    /// The drag term B* in inverse Earth radii.
+    pub bstar: f64,
+}
+
+impl Tle {
+    /// Parse one element set.
+    ///
+    /// # Arguments
+    ///
+    /// * `line1` - The first line of elements, starting `1 `.
+    /// * `line2` - The second line, starting `2 `.
+    ///
+    /// # Returns
+    ///
+    /// The `Tle` without a name if the lines are well formed and their
+    /// checksums match, otherwise `Error::InvalidTle`.
+    pub fn parse(line1: &str, line2: &str) -> Result<Self> {
+        let (line1, line2) = (line1.trim_end(), line2.trim_end());
+        check_line(line1, '1')?;
+        check_line(line2, '2')?;
+        let norad_id = field(line1, 2..7)?;
+        if field::<u32>(line2, 2..7)? != norad_id {
+            return Err(invalid("lines belong to different satellites"));
+        }
+
+        let year: i64 = field(line1, 18..20)?;
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let day: f64 = field(line1, 20..32)?;
+        let seconds = days_to_year(year) as f64 * 86_400.0 + (day - 1.0) * 86_400.0;
+
+        Ok(Tle {
+            name: None,
+            norad_id,
+            epoch: UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0)),
+            inclination: field(line2, 8..16)?,
+            raan: field(line2, 17..25)?,
+            eccentricity: format!("0.{}", slice(line2, 26..33)?.trim())
+                .parse()
+                .map_err(|_| invalid("bad eccentricity"))?,
+            arg_perigee: field(line2, 34..42)?,
+            mean_anomaly: field(line2, 43..51)?,
+            mean_motion: field(line2, 52..63)?,
+            bstar: exponent_field(slice(line1, 53..61)?)?,
+        })
+    }
+
+    /// Parse every element set in a file, with or without name lines.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The file's contents, in two- or three-line format.
+    ///
+    /// # Returns
+    ///
+    /// The element sets in order if all are well formed, otherwise
+    /// `Error::InvalidTle`.
+    pub fn parse_all(text: &str) -> Result<Vec<Self>> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|l| !l.is_empty())
+            .collect();
+        let mut tles = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let name = if lines[i].starts_with("1 ") {
+                None
+            } else {
+                i += 1;
+                // CelesTrak's three-line format may put a 0 before the name.
+                Some(lines[i - 1].trim_start_matches("0 ").trim().to_string())
+            };
+            let (Some(line1), Some(line2)) = (lines.get(i), lines.get(i + 1)) else {
+                return Err(invalid("incomplete element set"));
+            };
+            let mut tle = Tle::parse(line1, line2)?;
+            tle.name = name;
+            tles.push(tle);
+            i += 2;
+        }
+        Ok(tles)
+    }
+
+    /// Get the orbital period.
+    pub fn period(&self) -> Duration {
+        Duration::from_secs_f64(86_400.0 / self.mean_motion)
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidTle(reason.to_string())
+}
+
+fn check_line(line: &str, number: char) -> Result<()> {
+    if line.len() != 69 || !line.is_ascii() || !line.starts_with(number) {
+        return Err(invalid(&format!("line {} is not 69 characters", number)));
+    }
+    // The last digit is the sum of the others, counting minus signs as 1.
+    let sum: u32 = line[..68]
+        .chars()
+        .map(|c| match c {
+            '-' => 1,
+            c => c.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+    if line[68..].parse::<u32>().ok() != Some(sum % 10) {
+        return Err(invalid(&format!("line {} checksum mismatch", number)));
+    }
+    Ok(())
+}
+
+fn slice(line: &str, range: std::ops::Range<usize>) -> Result<&str> {
+    line.get(range).ok_or_else(|| invalid("line too short"))
+}
+
+fn field<T: std::str::FromStr>(line: &str, range: std::ops::Range<usize>) -> Result<T> {
+    let text = slice(line, range)?.trim();
+    text.parse()
+        .map_err(|_| invalid(&format!("bad field {:?}", text)))
+}
+
+/// Parse the `±ddddd±e` form with an implied leading decimal point.
+fn exponent_field(text: &str) -> Result<f64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(0.0);
+    }
+    let split = text
+        .rfind(['-', '+'])
+        .filter(|&i| i > 0)
+        .ok_or_else(|| invalid(&format!("bad exponent field {:?}", text)))?;
+    let (mantissa, exponent) = text.split_at(split);
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, mantissa.trim_start_matches('+')),
+    };
+    let mantissa: f64 = format!("0.{}", digits)
+        .parse()
+        .map_err(|_| invalid(&format!("bad exponent field {:?}", text)))?;
+    let exponent: i32 = exponent
+        .parse()
+        .map_err(|_| invalid(&format!("bad exponent field {:?}", text)))?;
+    Ok(sign * mantissa * 10f64.powi(exponent))
+}
+
+/// Days from 1970-01-01 to January 1st of `year`.
+fn days_to_year(year: i64) -> i64 {
+    let leaps = |y: i64| y.div_euclid(4) - y.div_euclid(100) + y.div_euclid(400);
+    365 * (year - 1970) + leaps(year - 1) - leaps(1969)
+}
@@ -0,0 +1,807 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use super::mode::{classify_mode, ChannelMode};
+use super::power::{bytes_for, integrate, READ_CHUNK};
+use crate::dsp::fir::Window;
+use crate::dsp::{FreqXlator, Spectrum};
+use crate::error::{Error, Result};
+use crate::samples::{to_complex_f32, Scaling};
+use crate::source::SdrSource;
+use crate::units::Frequency;
+
+/// A channel watched by an `ActivityScanner`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// Lower edge in Hz.
+    pub low_hz: f64,
+    /// Upper edge in Hz.
+    pub high_hz: f64,
+    /// How the channel is modulated, if known. An `ActivityScanner` fills
+    /// it in the first time the channel becomes active.
+    pub mode: Option<ChannelMode>,
+}
+
+impl Channel {
+    /// Create a channel from its centre and width.
+    ///
+    /// # Arguments
+    ///
+    /// * `centre_hz` - The channel frequency in Hz.
+    /// * `bandwidth_hz` - The occupied bandwidth in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `Channel`.
+    pub fn new(centre_hz: u32, bandwidth_hz: u32) -> Self {
+        let half = bandwidth_hz as f64 / 2.0;
+        Channel {
+            low_hz: centre_hz as f64 - half,
+            high_hz: centre_hz as f64 + half,
+            mode: None,
+        }
+    }
+
+    /// Create a channel covering a frequency range.
+    ///
+    /// # Arguments
+    ///
+    /// * `low_hz` - Lower edge in Hz.
+    /// * `high_hz` - Upper edge in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `Channel`.
+    pub fn range(low_hz: u32, high_hz: u32) -> Self {
+        Channel {
+            low_hz: low_hz.min(high_hz) as f64,
+            high_hz: low_hz.max(high_hz) as f64,
+            mode: None,
+        }
+    }
+
+    /// Set the mode of the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - How the channel is modulated.
+    ///
+    /// # Returns
+    ///
+    /// The `Channel` with the mode set, so a scanner does not classify it.
+    pub fn with_mode(mut self, mode: ChannelMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Cut a band into evenly spaced channels, as found in a band plan.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_hz` - Centre of the first channel in Hz.
+    /// * `last_hz` - Centre of the last channel in Hz.
+    /// * `spacing_hz` - Distance between channel centres, also used as the
+    ///   channel width.
+    ///
+    /// # Returns
+    ///
+    /// The channels from `first_hz` up to and including `last_hz`.
+    pub fn grid(first_hz: u32, last_hz: u32, spacing_hz: u32) -> Vec<Self> {
+        (first_hz..=last_hz)
+            .step_by(spacing_hz.max(1) as usize)
+            .map(|centre| Channel::new(centre, spacing_hz))
+            .collect()
+    }
+
+    /// Get the channel frequency.
+    ///
+    /// # Returns
+    ///
+    /// The centre of the channel in Hz.
+    pub fn centre_hz(&self) -> f64 {
+        (self.low_hz + self.high_hz) / 2.0
+    }
+
+    /// Get the channel width.
+    ///
+    /// # Returns
+    ///
+    /// The width of the channel in Hz.
+    pub fn bandwidth_hz(&self) -> f64 {
+        self.high_hz - self.low_hz
+    }
+}
+
+/// A period of activity on one channel.
+#[derive(Clone, Debug)]
+pub struct Activity {
+    /// Index of the channel in the list given to `ActivityScanner::new`.
+    pub index: usize,
+    /// The channel.
+    pub channel: Channel,
+    /// When the channel was first measured above the threshold.
+    pub start: SystemTime,
+    /// Time from `start` to the last measurement above the threshold.
+    pub duration: Duration,
+    /// Highest channel power seen so far in dB.
+    pub peak_db: f32,
+    /// The channel's noise floor in dB when the activity started.
+    pub floor_db: f32,
+}
+
+/// Change reported by `ActivityScanner::next_pass`.
+#[derive(Clone, Debug)]
+pub enum ActivityEvent {
+    /// A channel rose above its threshold. The duration is zero and the peak
+    /// is the first measurement.
+    Started(Activity),
+    /// A channel stayed below its threshold for the hang time.
+    Ended(Activity),
+}
+
+/// Formats the event as one log line, e.g. `146.52 MHz active, peak
+/// -41.2 dB` or `146.52 MHz ended after 3.5 s, peak -38.0 dB`.
+impl fmt::Display for ActivityEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let a = self.activity();
+        let centre = Frequency(a.channel.centre_hz());
+        match self {
+            ActivityEvent::Started(_) => write!(f, "{} active", centre)?,
+            ActivityEvent::Ended(_) => write!(
+                f,
+                "{} ended after {:.1} s",
+                centre,
+                a.duration.as_secs_f64()
+            )?,
+        }
+        write!(f, ", peak {:.1} dB", a.peak_db)
+    }
+}
+
+impl ActivityEvent {
+    /// Get the activity the event is about.
+    ///
+    /// # Returns
+    ///
+    /// The activity, as it stood when the event was raised.
+    pub fn activity(&self) -> &Activity {
+        match self {
+            ActivityEvent::Started(a) | ActivityEvent::Ended(a) => a,
+        }
+    }
+}
+
+/// Channels measured from one tuning.
+struct Group {
+    centre_hz: u32,
+    /// Indices into the channel list.
+    channels: Vec<usize>,
+}
+
+struct ChannelState {
+    channel: Channel,
+    /// First and one past the last spectrum bin of the channel.
+    bins: (usize, usize),
+    floor_db: Option<f32>,
+    active: Option<Activity>,
+    last_above: SystemTime,
+}
+
+/// Scanner that watches a list of channels for activity.
+///
+/// Channels that fit in the usable part of one tuning are grouped, so a
+/// dense band plan costs one hop per group rather than one per channel. At
+/// each hop the source is retuned, left to settle, and a Welch spectrum is
+/// integrated; the power of a channel is the total over its bins, in dBFS.
+///
+/// Each channel learns its own noise floor, first from the minimum over a
+/// number of initial passes and then as a slow average of measurements taken
+/// while it is idle. A channel becomes active when it rises `threshold` dB
+/// above its floor, and ends once it has stayed 3 dB below that for the hang
+/// time, so brief fades in a transmission do not split it into several.
+///
+/// When a channel without a mode becomes active, the scanner stays on it a
+/// little longer and runs `classify_mode` on what it receives, storing the
+/// result in the channel entry, so the right demodulator can be picked
+/// from `Activity::channel` and the mode is known from then on.
+pub struct ActivityScanner<S> {
+    source: S,
+    rate: f64,
+    spectrum: Spectrum,
+    groups: Vec<Group>,
+    channels: Vec<ChannelState>,
+    settle: Duration,
+    integration: Duration,
+    threshold_db: f32,
+    hang: Duration,
+    learning: usize,
+    passes: usize,
+    classify: Duration,
+    buf: Vec<u8>,
+}
+
+/// Fraction of the sample rate usable around the tuned frequency.
+pub(super) const USABLE: f64 = 0.75;
+
+/// Hysteresis below the threshold before a channel counts as idle.
+pub(super) const HYSTERESIS_DB: f32 = 3.0;
+
+/// Equivalent noise bandwidth of the periodic Hann window in bins. Dividing a
+/// sum of bins by it turns the spectrum's coherent-gain scaling into power.
+const HANN_ENBW: f64 = 1.5;
+
+/// Weight of a new idle measurement in the noise floor average.
+pub(super) const FLOOR_ALPHA: f32 = 1.0 / 16.0;
+
+impl<S: SdrSource> ActivityScanner<S> {
+    /// Plan a scanner at the source's current sample rate.
+    ///
+    /// Defaults to a 10 dB threshold, 2 s hang time, 3 learning passes,
+    /// 20 ms of settling and 20 ms of integration per hop, and 100 ms of
+    /// mode detection.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to scan with.
+    /// * `channels` - The channels to watch.
+    /// * `bin_hz` - Requested bin width in Hz; it should be well below the
+    ///   narrowest channel. The actual width is the sample rate divided by
+    ///   the next power of two.
+    ///
+    /// # Returns
+    ///
+    /// A new `ActivityScanner` if successful, otherwise an `Error`.
+    /// `Error::InvalidParam` is returned if a channel is wider than 75% of
+    /// the sample rate or narrower than one bin.
+    pub fn new(source: S, channels: &[Channel], bin_hz: f64) -> Result<Self> {
+        let rate = source.sample_rate()? as f64;
+        let fft_len = ((rate / bin_hz).ceil() as usize).next_power_of_two().max(2);
+        let step = rate / fft_len as f64;
+        let usable = rate * USABLE;
+
+        if channels
+            .iter()
+            .any(|c| c.bandwidth_hz() > usable || c.bandwidth_hz() < step)
+        {
+            return Err(Error::InvalidParam);
+        }
+
+        let mut order: Vec<usize> = (0..channels.len()).collect();
+        order.sort_by(|&a, &b| channels[a].low_hz.total_cmp(&channels[b].low_hz));
+
+        let mut groups: Vec<Group> = Vec::new();
+        let mut group_low = f64::NAN;
+        let mut group_high = f64::NAN;
+        for i in order {
+            let c = channels[i];
+            match groups.last_mut() {
+                Some(group) if c.high_hz.max(group_high) - group_low <= usable => {
+                    group.channels.push(i);
+                    group_high = group_high.max(c.high_hz);
+                }
+                _ => {
+                    if let Some(group) = groups.last_mut() {
+                        group.centre_hz = ((group_low + group_high) / 2.0).round() as u32;
+                    }
+                    groups.push(Group {
+                        centre_hz: 0,
+                        channels: vec![i],
+                    });
+                    group_low = c.low_hz;
+                    group_high = c.high_hz;
+                }
+            }
+        }
+        if let Some(group) = groups.last_mut() {
+            group.centre_hz = ((group_low + group_high) / 2.0).round() as u32;
+        }
+
+        let mut states: Vec<ChannelState> = channels
+            .iter()
+            .map(|&channel| ChannelState {
+                channel,
+                bins: (0, 0),
+                floor_db: None,
+                active: None,
+                last_above: SystemTime::UNIX_EPOCH,
+            })
+            .collect();
+        for group in &groups {
+            for &i in &group.channels {
+                let c = states[i].channel;
+                let bin = |f: f64| {
+                    let offset = (f - group.centre_hz as f64) / step;
+                    ((fft_len / 2) as f64 + offset)
+                        .round()
+                        .clamp(0.0, fft_len as f64) as usize
+                };
+                states[i].bins = (bin(c.low_hz), bin(c.high_hz).max(bin(c.low_hz) + 1));
+            }
+        }
+
+        Ok(ActivityScanner {
+            source,
+            rate,
+            spectrum: Spectrum::new(fft_len, Window::Hann),
+            groups,
+            channels: states,
+            settle: Duration::from_millis(20),
+            integration: Duration::from_millis(20),
+            threshold_db: 10.0,
+            hang: Duration::from_secs(2),
+            learning: 3,
+            passes: 0,
+            classify: Duration::from_millis(100),
+            buf: vec![0; READ_CHUNK],
+        })
+    }
+
+    /// Set how far above the noise floor a channel counts as active.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_db` - The threshold in dB above the floor.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new threshold.
+    pub fn with_threshold(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set how long a channel has to stay idle before its activity ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.hang = hang;
+        self
+    }
+
+    /// Set how many passes are used to learn the initial noise floors.
+    ///
+    /// # Arguments
+    ///
+    /// * `passes` - Passes before events are reported, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new learning period.
+    pub fn with_learning(mut self, passes: usize) -> Self {
+        self.learning = passes.max(1);
+        self
+    }
+
+    /// Set how long samples are discarded after each retune.
+    ///
+    /// # Arguments
+    ///
+    /// * `settle` - The settling time.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new settling time.
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// Set how long each hop is integrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `integration` - The integration time per hop.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new integration time.
+    pub fn with_integration(mut self, integration: Duration) -> Self {
+        self.integration = integration;
+        self
+    }
+
+    /// Set how long a newly active channel of unknown mode is received to
+    /// classify it.
+    ///
+    /// # Arguments
+    ///
+    /// * `classify` - The time to receive for, or zero to not classify.
+    ///
+    /// # Returns
+    ///
+    /// The `ActivityScanner` with the new detection time.
+    pub fn with_mode_detection(mut self, classify: Duration) -> Self {
+        self.classify = classify;
+        self
+    }
+
+    /// Get the number of tunings in one pass.
+    ///
+    /// # Returns
+    ///
+    /// The number of hops per pass.
+    pub fn hops(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Check whether the noise floors have been learned.
+    ///
+    /// # Returns
+    ///
+    /// `true` once the learning passes are complete.
+    pub fn is_ready(&self) -> bool {
+        self.passes >= self.learning
+    }
+
+    /// Get the learned noise floor of a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the channel.
+    ///
+    /// # Returns
+    ///
+    /// The floor in dB, or `None` before the channel was first measured.
+    pub fn noise_floor(&self, index: usize) -> Option<f32> {
+        self.channels.get(index).and_then(|c| c.floor_db)
+    }
+
+    /// Get a channel entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the channel.
+    ///
+    /// # Returns
+    ///
+    /// The channel with the mode learned so far, or `None` if there is no
+    /// such channel.
+    pub fn channel(&self, index: usize) -> Option<Channel> {
+        self.channels.get(index).map(|c| c.channel)
+    }
+
+    /// Get the activity in progress on a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the channel.
+    ///
+    /// # Returns
+    ///
+    /// The activity, or `None` if the channel is idle.
+    pub fn activity(&self, index: usize) -> Option<&Activity> {
+        self.channels.get(index).and_then(|c| c.active.as_ref())
+    }
+
+    /// Measure every channel once.
+    ///
+    /// # Returns
+    ///
+    /// The channels that started or ended activity during the pass if
+    /// successful, otherwise an `Error`. Nothing is reported during the
+    /// learning passes.
+    pub fn next_pass(&mut self) -> Result<Vec<ActivityEvent>> {
+        let mut events = Vec::new();
+        for g in 0..self.groups.len() {
+            integrate(
+                &mut self.source,
+                self.groups[g].centre_hz,
+                bytes_for(self.rate, self.settle),
+                bytes_for(self.rate, self.integration),
+                &mut self.spectrum,
+                &mut self.buf,
+            )?;
+            let power = self.spectrum.take_power().unwrap_or_default();
+            let now = SystemTime::now();
+            let first_event = events.len();
+            for k in 0..self.groups[g].channels.len() {
+                let i = self.groups[g].channels[k];
+                let (first, last) = self.channels[i].bins;
+                let sum = power[first..last].iter().sum::<f64>() / HANN_ENBW;
+                let db = (10.0 * sum.max(1e-20).log10()) as f32;
+                self.update(i, db, now, &mut events);
+            }
+            self.classify(g, &mut events[first_event..])?;
+        }
+        self.passes += 1;
+        Ok(events)
+    }
+
+    /// Classify the channels of a group that have just become active and
+    /// have no mode yet, while the source is still tuned to the group.
+    fn classify(&mut self, group: usize, events: &mut [ActivityEvent]) -> Result<()> {
+        let unknown = |e: &&mut ActivityEvent| matches!(e, ActivityEvent::Started(a) if a.channel.mode.is_none());
+        if self.classify.is_zero() || !events.iter_mut().any(|e| unknown(&e)) {
+            return Ok(());
+        }
+        let mut buf = vec![0; bytes_for(self.rate, self.classify)];
+        self.source.read_exact(&mut buf)?;
+        let input = to_complex_f32(&buf, Scaling::Normalized);
+        let centre_hz = self.groups[group].centre_hz as f64;
+
+        for event in events.iter_mut().filter(unknown) {
+            let ActivityEvent::Started(activity) = event else {
+                continue;
+            };
+            let channel = activity.channel;
+            let factor = ((self.rate / channel.bandwidth_hz()) as usize).max(1);
+            let mut xlator = FreqXlator::with_low_pass(
+                self.rate,
+                channel.centre_hz() - centre_hz,
+                factor,
+                8 * factor + 1,
+            );
+            let mut samples = Vec::with_capacity(input.len() / factor + 1);
+            xlator.process_into(&input, &mut samples);
+            let mode = classify_mode(&samples, self.rate / factor as f64);
+            activity.channel.mode = mode;
+            let state = &mut self.channels[activity.index];
+            state.channel.mode = mode;
+            if let Some(active) = &mut state.active {
+                active.channel.mode = mode;
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, index: usize, db: f32, now: SystemTime, events: &mut Vec<ActivityEvent>) {
+        let learning = self.passes < self.learning;
+        let threshold = self.threshold_db;
+        let hang = self.hang;
+        let state = &mut self.channels[index];
+
+        let floor = match state.floor_db {
+            Some(floor) if learning => floor.min(db),
+            Some(floor) => floor,
+            None => db,
+        };
+        state.floor_db = Some(floor);
+        if learning {
+            return;
+        }
+
+        if db >= floor + threshold {
+            state.last_above = now;
+            match &mut state.active {
+                Some(activity) => {
+                    activity.peak_db = activity.peak_db.max(db);
+                    activity.duration = now.duration_since(activity.start).unwrap_or_default();
+                }
+                None => {
+                    let activity = Activity {
+                        index,
+                        channel: state.channel,
+                        start: now,
+                        duration: Duration::ZERO,
+                        peak_db: db,
+                        floor_db: floor,
+                    };
+                    events.push(ActivityEvent::Started(activity.clone()));
+                    state.active = Some(activity);
+                }
+            }
+            return;
+        }
+
+        if db < floor + threshold - HYSTERESIS_DB {
+            if state.active.is_none() {
+                state.floor_db = Some(floor + FLOOR_ALPHA * (db - floor));
+            } else if now.duration_since(state.last_above).unwrap_or_default() >= hang {
+                if let Some(activity) = state.active.take() {
+                    events.push(ActivityEvent::Ended(activity));
+                }
+            }
+        }
+    }
+
+    /// Give back the source.
+    ///
+    /// # Returns
+    ///
+    /// The source the scanner was created with.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: SdrSource> Iterator for ActivityScanner<S> {
+    type Item = Result<Vec<ActivityEvent>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_pass())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockDevice, MockSignal, MockSignals};
+    use crate::scan::testing::{carrier, clear_carriers, mock_band};
+
+    /// Switch the band's carrier to another one, or off.
+    fn switch(signals: &MockSignals, on: Option<MockSignal>) {
+        clear_carriers(signals);
+        if let Some(signal) = on {
+            signals.push(signal);
+        }
+    }
+
+    /// A carrier keyed with 1200 baud FSK of alternating bits.
+    fn fsk(freq_hz: u32) -> MockSignal {
+        MockSignal::Fsk {
+            freq_hz,
+            deviation_hz: 2_400.0,
+            baud: 1_200.0,
+            amplitude: 20.0 / 127.5,
+        }
+    }
+
+    fn scanner(band: MockDevice, channels: &[Channel]) -> ActivityScanner<MockDevice> {
+        ActivityScanner::new(band, channels, 1_000.0)
+            .unwrap()
+            .with_settle(Duration::from_millis(1))
+            .with_integration(Duration::from_millis(10))
+            .with_hang(Duration::ZERO)
+            .with_learning(2)
+    }
+
+    fn started(events: &[ActivityEvent]) -> Vec<usize> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                ActivityEvent::Started(a) => Some(a.index),
+                ActivityEvent::Ended(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_channels_that_fit_one_tuning() {
+        let channels = Channel::grid(146_000_000, 148_000_000, 25_000);
+        let (band, _) = mock_band(1);
+        // 768 kHz of each tuning is usable, so 81 channels need three hops.
+        assert_eq!(scanner(band, &channels).hops(), 3);
+
+        let (band, _) = mock_band(1);
+        assert!(matches!(
+            ActivityScanner::new(band, &[Channel::new(100_000_000, 800_000)], 1_000.0),
+            Err(Error::InvalidParam)
+        ));
+        let (band, _) = mock_band(1);
+        assert!(matches!(
+            ActivityScanner::new(band, &[Channel::new(100_000_000, 500)], 1_000.0),
+            Err(Error::InvalidParam)
+        ));
+    }
+
+    #[test]
+    fn reports_nothing_while_learning_the_floor() {
+        let (band, signals) = mock_band(1);
+        switch(&signals, Some(carrier(146_540_000, 40.0)));
+        let mut scanner = scanner(band, &Channel::grid(146_500_000, 146_540_000, 20_000));
+        assert!(scanner.noise_floor(0).is_none());
+        for _ in 0..2 {
+            assert!(!scanner.is_ready());
+            assert!(scanner.next_pass().unwrap().is_empty());
+        }
+        assert!(scanner.is_ready());
+        // A carrier present from the start is learned as that channel's floor.
+        assert!(scanner.noise_floor(2).unwrap() > scanner.noise_floor(0).unwrap() + 20.0);
+        assert!(scanner.next_pass().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_carrier_starts_and_ends_activity_on_its_channel_only() {
+        let (band, signals) = mock_band(1);
+        let mut scanner = scanner(band, &Channel::grid(146_500_000, 146_540_000, 20_000));
+        for _ in 0..3 {
+            assert!(scanner.next_pass().unwrap().is_empty());
+        }
+
+        switch(&signals, Some(carrier(146_540_000, 20.0)));
+        let events = scanner.next_pass().unwrap();
+        assert_eq!(started(&events), [2]);
+        let activity = events[0].activity();
+        assert!(
+            activity.peak_db > activity.floor_db + 20.0,
+            "{} dB over a {} dB floor",
+            activity.peak_db,
+            activity.floor_db
+        );
+        assert!(scanner.activity(2).is_some());
+        assert!(scanner.activity(0).is_none() && scanner.activity(1).is_none());
+        // Still active: no new event.
+        assert!(scanner.next_pass().unwrap().is_empty());
+
+        switch(&signals, None);
+        let events = scanner.next_pass().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], ActivityEvent::Ended(a) if a.index == 2));
+        assert!(scanner.activity(2).is_none());
+    }
+
+    #[test]
+    fn learns_the_mode_of_a_channel_once_active() {
+        let (band, signals) = mock_band(1);
+        let channels = [
+            Channel::new(146_520_000, 12_500),
+            Channel::new(146_540_000, 12_500).with_mode(ChannelMode::Am),
+        ];
+        let mut scanner = scanner(band, &channels);
+        for _ in 0..3 {
+            scanner.next_pass().unwrap();
+        }
+        assert_eq!(scanner.channel(0).unwrap().mode, None);
+
+        switch(&signals, Some(fsk(146_520_000)));
+        let events = scanner.next_pass().unwrap();
+        assert_eq!(events[0].activity().channel.mode, Some(ChannelMode::Data));
+        assert_eq!(scanner.channel(0).unwrap().mode, Some(ChannelMode::Data));
+
+        // A known mode is kept.
+        switch(&signals, Some(fsk(146_540_000)));
+        let events = scanner.next_pass().unwrap();
+        assert_eq!(started(&events), [1]);
+        assert_eq!(events[1].activity().channel.mode, Some(ChannelMode::Am));
+        assert_eq!(scanner.channel(0).unwrap().mode, Some(ChannelMode::Data));
+    }
+
+    #[test]
+    fn a_carrier_below_the_threshold_is_ignored() {
+        let (band, signals) = mock_band(1);
+        let mut scanner = scanner(band, &[Channel::new(146_520_000, 12_500)]);
+        for _ in 0..2 {
+            scanner.next_pass().unwrap();
+        }
+
+        // About 4 dB over the noise in the channel.
+        switch(&signals, Some(carrier(146_522_000, 0.45)));
+        let mut scanner = scanner.with_threshold(12.0);
+        assert!(scanner.next_pass().unwrap().is_empty());
+        let mut scanner = scanner.with_threshold(3.0);
+        assert_eq!(started(&scanner.next_pass().unwrap()), [0]);
+    }
+
+    #[test]
+    fn hangs_on_through_a_short_fade() {
+        let (band, signals) = mock_band(1);
+        let mut scanner = scanner(band, &[Channel::new(146_520_000, 12_500)])
+            .with_hang(Duration::from_secs(3600));
+        for _ in 0..2 {
+            scanner.next_pass().unwrap();
+        }
+        switch(&signals, Some(carrier(146_522_000, 20.0)));
+        assert_eq!(started(&scanner.next_pass().unwrap()), [0]);
+        switch(&signals, None);
+        assert!(scanner.next_pass().unwrap().is_empty());
+        switch(&signals, Some(carrier(146_522_000, 20.0)));
+        assert!(scanner.next_pass().unwrap().is_empty());
+        assert!(scanner.activity(0).unwrap().duration > Duration::ZERO);
+    }
+
+    #[test]
+    fn formats_events_in_engineering_units() {
+        let activity = Activity {
+            index: 0,
+            channel: Channel::new(146_520_000, 12_500),
+            start: SystemTime::UNIX_EPOCH,
+            duration: Duration::from_millis(3_500),
+            peak_db: -38.04,
+            floor_db: -80.0,
+        };
+        assert_eq!(
+            ActivityEvent::Started(activity.clone()).to_string(),
+            "146.52 MHz active, peak -38.0 dB"
+        );
+        assert_eq!(
+            ActivityEvent::Ended(activity).to_string(),
+            "146.52 MHz ended after 3.5 s, peak -38.0 dB"
+        );
+    }
+}
@@ -0,0 +1,735 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::power::{PowerTable, SweepRow};
+use crate::error::{Error, Result};
+use crate::units::Frequency;
+use crate::utils::UtcTime;
+
+/// The period of a `BaselineSchedule` when not set with `with_period`.
+pub const DEFAULT_PERIOD: Duration = Duration::from_secs(86_400);
+
+/// The averaged spectrum of a site over a number of sweeps, to compare
+/// later sweeps against.
+///
+/// Sweeps are averaged in linear power, so brief carriers raise the
+/// average far less than they raise their own bins. A baseline is saved as
+/// a single `rtl_power` CSV line whose time is the last sweep's and whose
+/// sample count is the number of sweeps averaged, so `Heatmap` and
+/// `rtl_power`'s own tools can read it.
+#[derive(Clone, Debug)]
+pub struct Baseline {
+    start_hz: f64,
+    step_hz: f64,
+    /// Summed linear power per bin.
+    power: Vec<f64>,
+    sweeps: usize,
+    updated: SystemTime,
+}
+
+impl Default for Baseline {
+    fn default() -> Self {
+        Baseline::new()
+    }
+}
+
+impl Baseline {
+    /// Create a baseline of no sweeps.
+    pub fn new() -> Self {
+        Baseline {
+            start_hz: 0.0,
+            step_hz: 0.0,
+            power: Vec::new(),
+            sweeps: 0,
+            updated: UNIX_EPOCH,
+        }
+    }
+
+    /// Check whether a table lies on the baseline's bins.
+    ///
+    /// The step is compared loosely, as `write_csv` rounds it to 0.01 Hz.
+    fn same_grid(&self, start_hz: f64, step_hz: f64, bins: usize) -> bool {
+        bins == self.power.len()
+            && (step_hz - self.step_hz).abs() <= self.step_hz * 1e-4
+            && (start_hz - self.start_hz).abs() <= self.step_hz / 2.0
+    }
+
+    /// Add sweeps averaging to a table.
+    fn add_weighted(&mut self, time: SystemTime, table: &PowerTable, sweeps: usize) -> Result<()> {
+        if self.sweeps == 0 {
+            self.start_hz = table.start_hz;
+            self.step_hz = table.step_hz;
+            self.power = vec![0.0; table.db.len()];
+        } else if !self.same_grid(table.start_hz, table.step_hz, table.db.len()) {
+            return Err(Error::InvalidParam);
+        }
+        for (sum, db) in self.power.iter_mut().zip(&table.db) {
+            *sum += 10f64.powf(*db as f64 / 10.0) * sweeps as f64;
+        }
+        self.sweeps += sweeps;
+        self.updated = self.updated.max(time);
+        Ok(())
+    }
+
+    /// Add a sweep.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the sweep finished.
+    /// * `table` - The sweep.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, or `Error::InvalidParam` if the sweep isn't on the
+    /// same bins as the first.
+    pub fn add(&mut self, time: SystemTime, table: &PowerTable) -> Result<()> {
+        self.add_weighted(time, table, 1)
+    }
+
+    /// Add every sweep of another baseline.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The baseline to add.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, or `Error::InvalidParam` if the baselines aren't on
+    /// the same bins.
+    pub fn merge(&mut self, other: &Baseline) -> Result<()> {
+        if other.sweeps == 0 {
+            return Ok(());
+        }
+        if self.sweeps == 0 {
+            *self = other.clone();
+            return Ok(());
+        }
+        if !self.same_grid(other.start_hz, other.step_hz, other.power.len()) {
+            return Err(Error::InvalidParam);
+        }
+        for (sum, other) in self.power.iter_mut().zip(&other.power) {
+            *sum += other;
+        }
+        self.sweeps += other.sweeps;
+        self.updated = self.updated.max(other.updated);
+        Ok(())
+    }
+
+    /// Get the number of sweeps averaged.
+    pub fn sweeps(&self) -> usize {
+        self.sweeps
+    }
+
+    /// Check whether no sweeps have been added.
+    pub fn is_empty(&self) -> bool {
+        self.sweeps == 0
+    }
+
+    /// Get when the last sweep finished.
+    pub fn updated(&self) -> SystemTime {
+        self.updated
+    }
+
+    /// Get the average spectrum.
+    ///
+    /// # Returns
+    ///
+    /// The mean power per bin in dB, or `None` if no sweeps have been
+    /// added.
+    pub fn table(&self) -> Option<PowerTable> {
+        if self.sweeps == 0 {
+            return None;
+        }
+        let n = self.sweeps as f64;
+        Some(PowerTable {
+            start_hz: self.start_hz,
+            step_hz: self.step_hz,
+            db: self
+                .power
+                .iter()
+                .map(|p| (10.0 * (p / n).log10()) as f32)
+                .collect(),
+        })
+    }
+
+    /// Compare a sweep with the baseline.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The sweep, or the table of a later baseline.
+    /// * `threshold_db` - How far a bin must rise above or fall below the
+    ///   baseline to count as changed.
+    ///
+    /// # Returns
+    ///
+    /// The differences, or `Error::InvalidParam` if the baseline is empty
+    /// or the sweep isn't on its bins.
+    pub fn compare(&self, table: &PowerTable, threshold_db: f32) -> Result<Comparison> {
+        let reference = self.table().ok_or(Error::InvalidParam)?;
+        if !self.same_grid(table.start_hz, table.step_hz, table.db.len()) {
+            return Err(Error::InvalidParam);
+        }
+        let delta_db: Vec<f32> = table
+            .db
+            .iter()
+            .zip(&reference.db)
+            .map(|(now, then)| now - then)
+            .collect();
+        let n = delta_db.len().max(1) as f32;
+        let mean_db = delta_db.iter().sum::<f32>() / n;
+        let rms_db = (delta_db.iter().map(|d| d * d).sum::<f32>() / n).sqrt();
+        let mut sorted = delta_db.clone();
+        sorted.sort_by(f32::total_cmp);
+        let floor_db = sorted.get(sorted.len() / 2).copied().unwrap_or(0.0);
+        let raised = delta_db.iter().filter(|d| **d >= threshold_db).count() as f32 / n;
+        let lowered = delta_db.iter().filter(|d| **d <= -threshold_db).count() as f32 / n;
+
+        // Runs of bins changed the same way.
+        let mut changes: Vec<SpectrumChange> = Vec::new();
+        let mut run: Option<usize> = None;
+        let sign = |d: f32| {
+            if d >= threshold_db {
+                1
+            } else if d <= -threshold_db {
+                -1
+            } else {
+                0
+            }
+        };
+        for bin in 0..=delta_db.len() {
+            let s = delta_db.get(bin).map_or(0, |d| sign(*d));
+            if let Some(first) = run {
+                if s == sign(delta_db[first]) {
+                    continue;
+                }
+                let worst = (first..bin)
+                    .max_by(|a, b| delta_db[*a].abs().total_cmp(&delta_db[*b].abs()))
+                    .unwrap_or(first);
+                changes.push(SpectrumChange {
+                    low_hz: table.start_hz + first as f64 * table.step_hz,
+                    high_hz: table.start_hz + bin as f64 * table.step_hz,
+                    peak_hz: table.frequency(worst),
+                    delta_db: delta_db[worst],
+                    level_db: table.db[worst],
+                });
+                run = None;
+            }
+            if s != 0 {
+                run = Some(bin);
+            }
+        }
+        Ok(Comparison {
+            start_hz: table.start_hz,
+            step_hz: table.step_hz,
+            delta_db,
+            mean_db,
+            rms_db,
+            floor_db,
+            raised,
+            lowered,
+            changes,
+        })
+    }
+
+    /// Write the baseline as an `rtl_power` CSV line.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the line.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, `Error::InvalidParam` if the baseline is empty, or
+    /// an `Error` if writing failed.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        let table = self.table().ok_or(Error::InvalidParam)?;
+        let row = SweepRow {
+            time: self.updated,
+            low_hz: table.start_hz,
+            high_hz: table.start_hz + table.db.len() as f64 * table.step_hz,
+            step_hz: table.step_hz,
+            samples: self.sweeps,
+            db: table.db,
+        };
+        writeln!(writer, "{}", row)?;
+        Ok(())
+    }
+
+    /// Read a baseline written by `write_csv`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The CSV.
+    ///
+    /// # Returns
+    ///
+    /// The baseline, or an `Error` if the CSV isn't one `rtl_power` line.
+    pub fn read_csv<R: BufRead>(reader: R) -> Result<Self> {
+        let mut baseline = Baseline::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !baseline.is_empty() {
+                return Err(Error::InvalidMetadata(
+                    "a baseline is a single line".to_string(),
+                ));
+            }
+            let row: SweepRow = line.parse()?;
+            let table =
+                PowerTable::from_rows(std::slice::from_ref(&row)).expect("one row makes a table");
+            baseline.add_weighted(row.time, &table, row.samples.max(1))?;
+        }
+        if baseline.is_empty() {
+            return Err(Error::InvalidMetadata("empty baseline".to_string()));
+        }
+        Ok(baseline)
+    }
+
+    /// Save the baseline to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file, created or replaced.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a baseline saved with `save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file.
+    ///
+    /// # Returns
+    ///
+    /// The baseline if successful, otherwise an `Error`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Baseline::read_csv(BufReader::new(File::open(path)?))
+    }
+}
+
+/// A run of bins that rose above or fell below a baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrumChange {
+    /// Lower edge of the run in Hz.
+    pub low_hz: f64,
+    /// Upper edge of the run in Hz.
+    pub high_hz: f64,
+    /// Centre of the most changed bin in Hz.
+    pub peak_hz: f64,
+    /// The change of the most changed bin in dB, positive for a rise.
+    pub delta_db: f32,
+    /// The level of the most changed bin now, in dB.
+    pub level_db: f32,
+}
+
+/// Formats the change as its range and change, e.g.
+/// `144.2 MHz - 144.21 MHz +18.5 dB`.
+impl fmt::Display for SpectrumChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} {:+.1} dB",
+            Frequency(self.low_hz),
+            Frequency(self.high_hz),
+            self.delta_db
+        )
+    }
+}
+
+/// How a sweep differs from a `Baseline`.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// Frequency of the first bin in Hz.
+    pub start_hz: f64,
+    /// Bin width in Hz.
+    pub step_hz: f64,
+    /// The change per bin in dB, positive where the sweep is louder.
+    pub delta_db: Vec<f32>,
+    /// The mean change over all bins in dB.
+    pub mean_db: f32,
+    /// The root mean square change over all bins in dB.
+    pub rms_db: f32,
+    /// The median change in dB, how far the noise floor moved; broadband
+    /// interference raises it while new carriers hardly do.
+    pub floor_db: f32,
+    /// The fraction of bins that rose by the threshold or more.
+    pub raised: f32,
+    /// The fraction of bins that fell by the threshold or more.
+    pub lowered: f32,
+    /// The runs of bins that changed by the threshold or more, lowest
+    /// frequency first.
+    pub changes: Vec<SpectrumChange>,
+}
+
+impl Comparison {
+    /// Get the runs of bins that rose above the baseline.
+    ///
+    /// # Returns
+    ///
+    /// The rises, largest first.
+    pub fn rises(&self) -> Vec<&SpectrumChange> {
+        let mut rises: Vec<&SpectrumChange> =
+            self.changes.iter().filter(|c| c.delta_db > 0.0).collect();
+        rises.sort_by(|a, b| b.delta_db.total_cmp(&a.delta_db));
+        rises
+    }
+}
+
+/// Formats the comparison as a summary, e.g.
+/// `floor +0.4 dB, mean +1.2 dB, rms 3.1 dB, 2.0% raised, 0.1% lowered, 3 changes`.
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "floor {:+.1} dB, mean {:+.1} dB, rms {:.1} dB, {:.1}% raised, {:.1}% lowered, {} changes",
+            self.floor_db,
+            self.mean_db,
+            self.rms_db,
+            self.raised * 100.0,
+            self.lowered * 100.0,
+            self.changes.len()
+        )
+    }
+}
+
+/// Takes a `Baseline` snapshot per period, daily by default, and keeps a
+/// rolling reference of the most recent ones.
+///
+/// Periods are aligned to the Unix epoch, so daily snapshots run from
+/// midnight to midnight UTC. When a sweep falls in a new period, the
+/// previous period's snapshot is finished, saved to the snapshot directory
+/// if one is set, as `baseline-YYYY-MM-DD-HHMM.csv` after the period's
+/// start, and kept. The reference is the kept snapshots averaged together.
+///
+/// ```no_run
+/// use std::time::SystemTime;
+///
+/// use radion::scan::{BaselineSchedule, PowerSweep};
+/// use radion::Device;
+///
+/// let mut sweep = PowerSweep::new(Device::new(0)?, 400_000_000, 450_000_000, 10_000.0)?;
+/// let mut schedule = BaselineSchedule::new().with_dir("baselines")?.with_keep(7);
+/// loop {
+///     let table = sweep.sweep()?;
+///     if let Some(reference) = schedule.reference() {
+///         // e.g. "floor +0.4 dB, mean +1.2 dB, ..."
+///         println!("{}", reference.compare(&table, 6.0)?);
+///     }
+///     if let Some(snapshot) = schedule.observe(SystemTime::now(), &table)? {
+///         println!("snapshot of {} sweeps saved", snapshot.sweeps());
+///     }
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct BaselineSchedule {
+    period: Duration,
+    dir: Option<PathBuf>,
+    keep: usize,
+    /// The period being averaged and its start.
+    current: Baseline,
+    current_start: SystemTime,
+    snapshots: VecDeque<Baseline>,
+}
+
+impl Default for BaselineSchedule {
+    fn default() -> Self {
+        BaselineSchedule::new()
+    }
+}
+
+impl BaselineSchedule {
+    /// Create a schedule of daily snapshots kept in memory, with a
+    /// reference of the last 7.
+    pub fn new() -> Self {
+        BaselineSchedule {
+            period: DEFAULT_PERIOD,
+            dir: None,
+            keep: 7,
+            current: Baseline::new(),
+            current_start: UNIX_EPOCH,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Set how long each snapshot averages.
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - The period, at least a second.
+    ///
+    /// # Returns
+    ///
+    /// The `BaselineSchedule` with the new period.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period.max(Duration::from_secs(1));
+        self
+    }
+
+    /// Save snapshots to a directory, and load the most recent ones
+    /// already saved there.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory, created if missing.
+    ///
+    /// # Returns
+    ///
+    /// The `BaselineSchedule` with the earlier snapshots kept if
+    /// successful, otherwise an `Error`.
+    pub fn with_dir<P: AsRef<Path>>(mut self, dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut names: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("baseline-") && n.ends_with(".csv"))
+            })
+            .collect();
+        // The names sort in time order.
+        names.sort();
+        self.snapshots.clear();
+        for path in names.iter().rev().take(self.keep).rev() {
+            self.snapshots.push_back(Baseline::load(path)?);
+        }
+        self.dir = Some(dir);
+        Ok(self)
+    }
+
+    /// Set how many snapshots the reference averages.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - The number of snapshots, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `BaselineSchedule` with the new count.
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        while self.snapshots.len() > self.keep {
+            self.snapshots.pop_front();
+        }
+        self
+    }
+
+    /// Get the start of the period a time falls in.
+    fn period_start(&self, time: SystemTime) -> SystemTime {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let period = self.period.as_secs();
+        UNIX_EPOCH + Duration::from_secs(secs / period * period)
+    }
+
+    /// Add a sweep to the current period's snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the sweep finished.
+    /// * `table` - The sweep.
+    ///
+    /// # Returns
+    ///
+    /// The previous period's snapshot if this sweep started a new period,
+    /// otherwise `None`, or an `Error` if the sweep isn't on the bins of
+    /// the period's earlier sweeps or the snapshot could not be saved.
+    pub fn observe(&mut self, time: SystemTime, table: &PowerTable) -> Result<Option<Baseline>> {
+        let start = self.period_start(time);
+        let mut finished = None;
+        if !self.current.is_empty() && start != self.current_start {
+            let snapshot = std::mem::take(&mut self.current);
+            if let Some(dir) = &self.dir {
+                let t = UtcTime::from_system_time(self.current_start);
+                let name = format!(
+                    "baseline-{:04}-{:02}-{:02}-{:02}{:02}.csv",
+                    t.year, t.month, t.day, t.hour, t.minute
+                );
+                snapshot.save(dir.join(name))?;
+            }
+            self.snapshots.push_back(snapshot.clone());
+            if self.snapshots.len() > self.keep {
+                self.snapshots.pop_front();
+            }
+            finished = Some(snapshot);
+        }
+        if self.current.is_empty() {
+            self.current_start = start;
+        }
+        self.current.add(time, table)?;
+        Ok(finished)
+    }
+
+    /// Get the snapshot of the current period so far.
+    pub fn current(&self) -> &Baseline {
+        &self.current
+    }
+
+    /// Get the kept snapshots.
+    ///
+    /// # Returns
+    ///
+    /// The snapshots, oldest first.
+    pub fn snapshots(&self) -> impl Iterator<Item = &Baseline> {
+        self.snapshots.iter()
+    }
+
+    /// Get the rolling reference to compare sweeps against.
+    ///
+    /// # Returns
+    ///
+    /// The kept snapshots averaged together, or `None` before the first
+    /// snapshot is finished or if the snapshots aren't on the same bins.
+    pub fn reference(&self) -> Option<Baseline> {
+        let mut reference = Baseline::new();
+        for snapshot in &self.snapshots {
+            reference.merge(snapshot).ok()?;
+        }
+        (!reference.is_empty()).then_some(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(floor_db: f32, carrier: Option<(usize, f32)>) -> PowerTable {
+        let mut db: Vec<f32> = (0..200).map(|i| floor_db + (i % 5) as f32 * 0.2).collect();
+        if let Some((bin, level)) = carrier {
+            db[bin] = level;
+            db[bin + 1] = level - 3.0;
+        }
+        PowerTable {
+            start_hz: 430_000_000.0,
+            step_hz: 10_000.0,
+            db,
+        }
+    }
+
+    fn day(n: u64, hour: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_760_000_000 / 86_400 * 86_400 + n * 86_400 + hour * 3600)
+    }
+
+    #[test]
+    fn reloads_onto_the_bins_of_live_sweeps() {
+        // 2.4 MS/s over 2048 bins, a step the CSV rounds.
+        let live = PowerTable {
+            start_hz: 100_000_000.0,
+            step_hz: 2_400_000.0 / 2048.0,
+            db: vec![-80.0; 4096],
+        };
+        let mut fresh = Baseline::new();
+        fresh.add(day(0, 1), &live).unwrap();
+        let mut csv = Vec::new();
+        fresh.write_csv(&mut csv).unwrap();
+        let mut reloaded = Baseline::read_csv(csv.as_slice()).unwrap();
+        assert!(reloaded.compare(&live, 6.0).unwrap().rises().is_empty());
+        reloaded.merge(&fresh).unwrap();
+        assert_eq!(reloaded.sweeps(), 2);
+    }
+
+    #[test]
+    fn averages_in_linear_power_and_compares() {
+        let mut baseline = Baseline::new();
+        baseline.add(day(0, 1), &table(-90.0, None)).unwrap();
+        baseline
+            .add(day(0, 2), &table(-90.0, Some((50, -60.0))))
+            .unwrap();
+        assert_eq!(baseline.sweeps(), 2);
+        let mean = baseline.table().unwrap();
+        // Half the time 30 dB up averages to about 27 dB up.
+        assert!((mean.db[50] - -63.0).abs() < 0.1, "{}", mean.db[50]);
+        assert!((mean.db[0] - -90.0).abs() < 1e-3);
+
+        let now = table(-88.0, Some((120, -50.0)));
+        let comparison = baseline.compare(&now, 6.0).unwrap();
+        assert!((comparison.floor_db - 2.0).abs() < 1e-3);
+        let rises = comparison.rises();
+        assert_eq!(rises.len(), 1);
+        assert_eq!(rises[0].peak_hz, now.frequency(120));
+        assert!((rises[0].delta_db - 40.0).abs() < 0.01);
+        assert_eq!(rises[0].low_hz, 431_200_000.0);
+        assert_eq!(rises[0].high_hz, 431_220_000.0);
+        // The carrier that left is a fall.
+        let fall = comparison
+            .changes
+            .iter()
+            .find(|c| c.delta_db < 0.0)
+            .unwrap();
+        assert_eq!(fall.peak_hz, now.frequency(50));
+        assert!(comparison.to_string().starts_with("floor +2.0 dB"));
+
+        let narrower = PowerTable {
+            db: vec![-90.0; 10],
+            ..table(-90.0, None)
+        };
+        assert!(baseline.compare(&narrower, 6.0).is_err());
+        assert!(Baseline::new().compare(&now, 6.0).is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_baselines() {
+        let mut baseline = Baseline::new();
+        for n in 0..3 {
+            baseline
+                .add(day(0, n), &table(-90.0, Some((10, -70.0))))
+                .unwrap();
+        }
+        let mut csv = Vec::new();
+        baseline.write_csv(&mut csv).unwrap();
+        let loaded = Baseline::read_csv(&csv[..]).unwrap();
+        assert_eq!(loaded.sweeps(), 3);
+        assert_eq!(loaded.updated(), day(0, 2));
+        let (a, b) = (baseline.table().unwrap(), loaded.table().unwrap());
+        assert_eq!(a.start_hz, b.start_hz);
+        assert!(a.db.iter().zip(&b.db).all(|(x, y)| (x - y).abs() < 0.01));
+        let mut two = csv.clone();
+        two.extend_from_slice(&csv);
+        assert!(Baseline::read_csv(&two[..]).is_err());
+    }
+
+    #[test]
+    fn snapshots_each_day_and_keeps_a_rolling_reference() {
+        let dir = std::env::temp_dir().join(format!("radion-baseline-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut schedule = BaselineSchedule::new().with_dir(&dir).unwrap().with_keep(2);
+        assert!(schedule.reference().is_none());
+        for n in 0..4 {
+            for hour in [1, 13] {
+                let finished = schedule
+                    .observe(day(n, hour), &table(-90.0 + n as f32, None))
+                    .unwrap();
+                assert_eq!(finished.is_some(), n > 0 && hour == 1);
+            }
+        }
+        assert_eq!(schedule.current().sweeps(), 2);
+        // Days 1 and 2 are kept, at -89 and -88 dB.
+        let reference = schedule.reference().unwrap();
+        assert_eq!(reference.sweeps(), 4);
+        let floor = reference.table().unwrap().db[0];
+        assert!((floor - -88.46).abs() < 0.05, "{}", floor);
+
+        let saved = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(saved, 3);
+        let reloaded = BaselineSchedule::new().with_dir(&dir).unwrap().with_keep(2);
+        assert_eq!(reloaded.snapshots().count(), 2);
+        assert_eq!(reloaded.reference().unwrap().sweeps(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
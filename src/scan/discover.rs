@@ -0,0 +1,457 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use super::power::PowerTable;
+use crate::units::Frequency;
+
+/// A carrier found by a `SignalFinder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredSignal {
+    /// The centre frequency in Hz, averaged over every sighting.
+    pub freq_hz: f64,
+    /// The width 10 dB below the peak in Hz, averaged over every sighting.
+    pub bandwidth_hz: f64,
+    /// The strongest level seen in dB.
+    pub peak_db: f32,
+    /// When the carrier was first seen.
+    pub first_seen: SystemTime,
+    /// When the carrier was last seen.
+    pub last_seen: SystemTime,
+    /// The number of sweeps or lines the carrier was seen in.
+    pub sightings: usize,
+}
+
+impl DiscoveredSignal {
+    /// Get a preset for the carrier, in whichever of CW, NFM, FM and WFM is
+    /// the narrowest its bandwidth fits, with when it was seen in the
+    /// comment.
+    ///
+    /// # Returns
+    ///
+    /// A new `Preset` named after the frequency.
+    #[cfg(feature = "presets")]
+    pub fn to_preset(&self) -> crate::presets::Preset {
+        use crate::presets::{Mode, Preset};
+        let mode = [Mode::Cw, Mode::NarrowFm, Mode::Fm, Mode::WideFm]
+            .into_iter()
+            .find(|m| m.bandwidth_hz() as f64 >= self.bandwidth_hz)
+            .unwrap_or(Mode::WideFm);
+        let freq_hz = self.freq_hz.round() as u32;
+        let mut preset = Preset::new(&Frequency(freq_hz as f64).to_string(), freq_hz, mode);
+        let utc = |time| {
+            let t = crate::utils::UtcTime::from_system_time(time);
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}",
+                t.year, t.month, t.day, t.hour, t.minute
+            )
+        };
+        preset.comment = format!(
+            "found {} seen {} times until {}",
+            utc(self.first_seen),
+            self.sightings,
+            utc(self.last_seen)
+        );
+        preset
+    }
+
+    /// Get a preset for the carrier on the nearest channel of its band in
+    /// a region's band plan, in the band's mode and step.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The region whose band plan to follow.
+    ///
+    /// # Returns
+    ///
+    /// A new `Preset` named after the channel's frequency, or the one
+    /// `to_preset` gives if the band plan does not cover the carrier.
+    #[cfg(feature = "presets")]
+    pub fn to_preset_in(&self, region: crate::presets::Region) -> crate::presets::Preset {
+        let mut preset = self.to_preset();
+        if let Some(band) = region.band(self.freq_hz) {
+            preset.freq_hz = band.snap(self.freq_hz);
+            preset.name = Frequency(preset.freq_hz as f64).to_string();
+            preset.mode = band.mode;
+            preset.step_hz = Some(band.step_hz);
+        }
+        preset
+    }
+}
+
+/// Formats the signal as its frequency, bandwidth and peak, e.g.
+/// `146.52 MHz 2 kHz -41.0 dB`.
+impl fmt::Display for DiscoveredSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:.1} dB",
+            Frequency(self.freq_hz),
+            Frequency(self.bandwidth_hz),
+            self.peak_db
+        )
+    }
+}
+
+/// Bookmarks persistent narrowband carriers in sweeps or waterfall lines.
+///
+/// Each spectrum's noise floor is taken as its median bin. Runs of bins
+/// standing `threshold_db` above it that are no wider than the largest
+/// bandwidth are carriers, centred on their power-weighted mean. A carrier
+/// seen again within its own width of an earlier one is the same signal,
+/// and once seen `min_sightings` times it is listed as discovered, ready
+/// to be saved with `presets::write_chirp_csv`.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::time::SystemTime;
+///
+/// use radion::presets::write_chirp_csv;
+/// use radion::scan::{PowerSweep, SignalFinder};
+/// use radion::Device;
+///
+/// let mut sweep = PowerSweep::new(Device::new(0)?, 144_000_000, 148_000_000, 1_000.0)?;
+/// let mut finder = SignalFinder::new();
+/// for _ in 0..60 {
+///     let table = sweep.sweep()?;
+///     for signal in finder.observe_table(SystemTime::now(), &table) {
+///         println!("found {}", signal);
+///     }
+/// }
+/// write_chirp_csv(File::create("found.csv")?, &finder.to_presets())?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct SignalFinder {
+    threshold_db: f32,
+    max_bandwidth_hz: f64,
+    min_sightings: usize,
+    candidates: Vec<DiscoveredSignal>,
+}
+
+impl Default for SignalFinder {
+    fn default() -> Self {
+        SignalFinder::new()
+    }
+}
+
+impl SignalFinder {
+    /// Create a finder for carriers 10 dB above the floor, up to 50 kHz
+    /// wide, seen in at least 3 spectra.
+    pub fn new() -> Self {
+        SignalFinder {
+            threshold_db: 10.0,
+            max_bandwidth_hz: 50_000.0,
+            min_sightings: 3,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Set how far above the noise floor a carrier must stand.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_db` - The threshold in dB above the floor.
+    ///
+    /// # Returns
+    ///
+    /// The `SignalFinder` with the new threshold.
+    pub fn with_threshold(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set the widest signal counted as a narrowband carrier.
+    ///
+    /// # Arguments
+    ///
+    /// * `bandwidth_hz` - The largest bandwidth in Hz.
+    ///
+    /// # Returns
+    ///
+    /// The `SignalFinder` with the new bandwidth.
+    pub fn with_max_bandwidth(mut self, bandwidth_hz: f64) -> Self {
+        self.max_bandwidth_hz = bandwidth_hz;
+        self
+    }
+
+    /// Set how many spectra a carrier must be seen in to be discovered.
+    ///
+    /// # Arguments
+    ///
+    /// * `sightings` - The number of spectra, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `SignalFinder` with the new count.
+    pub fn with_min_sightings(mut self, sightings: usize) -> Self {
+        self.min_sightings = sightings.max(1);
+        self
+    }
+
+    /// Look for carriers in a stitched sweep.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the sweep finished.
+    /// * `table` - The sweep.
+    ///
+    /// # Returns
+    ///
+    /// The signals discovered by this sweep.
+    pub fn observe_table(&mut self, time: SystemTime, table: &PowerTable) -> Vec<DiscoveredSignal> {
+        self.observe(time, table.start_hz, table.step_hz, &table.db)
+    }
+
+    /// Look for carriers in a spectrum, such as a waterfall line.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the spectrum was measured.
+    /// * `start_hz` - The frequency of the first bin in Hz: the tuned
+    ///   frequency less half the sample rate for a waterfall line.
+    /// * `step_hz` - The bin width in Hz.
+    /// * `db` - The power per bin in dB.
+    ///
+    /// # Returns
+    ///
+    /// The signals discovered by this spectrum.
+    pub fn observe(
+        &mut self,
+        time: SystemTime,
+        start_hz: f64,
+        step_hz: f64,
+        db: &[f32],
+    ) -> Vec<DiscoveredSignal> {
+        let mut finite: Vec<f32> = db.iter().copied().filter(|v| v.is_finite()).collect();
+        if finite.is_empty() {
+            return Vec::new();
+        }
+        let mid = finite.len() / 2;
+        let floor = *finite.select_nth_unstable_by(mid, f32::total_cmp).1;
+        let threshold = floor + self.threshold_db;
+
+        let mut seen = vec![false; self.candidates.len()];
+        let mut found = Vec::new();
+        let mut i = 0;
+        while i < db.len() {
+            let run = db[i..].iter().take_while(|&&v| v >= threshold).count();
+            if run == 0 {
+                i += 1;
+                continue;
+            }
+            let bins = &db[i..i + run];
+            let first = i;
+            i += run;
+            if run as f64 * step_hz > self.max_bandwidth_hz {
+                continue;
+            }
+            let peak = bins.iter().copied().fold(f32::MIN, f32::max);
+            let (mut sum, mut weighted) = (0.0, 0.0);
+            for (k, &v) in bins.iter().enumerate() {
+                let power = 10f64.powf(v as f64 / 10.0);
+                sum += power;
+                weighted += power * (first + k) as f64;
+            }
+            let freq_hz = start_hz + weighted / sum * step_hz;
+            let width = bins.iter().filter(|&&v| v >= peak - 10.0).count();
+            let bandwidth_hz = width as f64 * step_hz;
+
+            let matched = self.candidates.iter().position(|c| {
+                (c.freq_hz - freq_hz).abs() <= c.bandwidth_hz.max(bandwidth_hz) / 2.0 + step_hz
+            });
+            match matched {
+                Some(index) if !seen[index] => {
+                    seen[index] = true;
+                    let c = &mut self.candidates[index];
+                    let n = c.sightings as f64;
+                    c.freq_hz = (c.freq_hz * n + freq_hz) / (n + 1.0);
+                    c.bandwidth_hz = (c.bandwidth_hz * n + bandwidth_hz) / (n + 1.0);
+                    c.peak_db = c.peak_db.max(peak);
+                    c.last_seen = time;
+                    c.sightings += 1;
+                    if c.sightings == self.min_sightings {
+                        found.push(c.clone());
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    let signal = DiscoveredSignal {
+                        freq_hz,
+                        bandwidth_hz,
+                        peak_db: peak,
+                        first_seen: time,
+                        last_seen: time,
+                        sightings: 1,
+                    };
+                    if self.min_sightings == 1 {
+                        found.push(signal.clone());
+                    }
+                    self.candidates.push(signal);
+                }
+            }
+        }
+        found
+    }
+
+    /// Get the signals discovered so far.
+    ///
+    /// # Returns
+    ///
+    /// The signals seen often enough, lowest frequency first.
+    pub fn discovered(&self) -> Vec<&DiscoveredSignal> {
+        let mut signals: Vec<&DiscoveredSignal> = self
+            .candidates
+            .iter()
+            .filter(|c| c.sightings >= self.min_sightings)
+            .collect();
+        signals.sort_by(|a, b| a.freq_hz.total_cmp(&b.freq_hz));
+        signals
+    }
+
+    /// Get presets for the signals discovered so far.
+    ///
+    /// # Returns
+    ///
+    /// One `Preset` per signal, lowest frequency first.
+    #[cfg(feature = "presets")]
+    pub fn to_presets(&self) -> Vec<crate::presets::Preset> {
+        self.discovered()
+            .into_iter()
+            .map(DiscoveredSignal::to_preset)
+            .collect()
+    }
+
+    /// Get presets for the signals discovered so far, on the channels of
+    /// a region's band plan.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The region whose band plan to follow.
+    ///
+    /// # Returns
+    ///
+    /// One `Preset` per signal as `DiscoveredSignal::to_preset_in` gives
+    /// it, lowest frequency first.
+    #[cfg(feature = "presets")]
+    pub fn to_presets_in(&self, region: crate::presets::Region) -> Vec<crate::presets::Preset> {
+        self.discovered()
+            .into_iter()
+            .map(|signal| signal.to_preset_in(region))
+            .collect()
+    }
+
+    /// Forget every carrier seen.
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// 1 kHz bins from 144 MHz over a ragged -90 dB floor, with a carrier
+    /// 3 bins wide centred on bin `carrier` and a 100 kHz wide signal.
+    fn table(carrier: usize) -> PowerTable {
+        let mut db: Vec<f32> = (0..1000).map(|i| -90.0 + (i % 7) as f32 * 0.5).collect();
+        db[carrier - 1] = -50.0;
+        db[carrier] = -40.0;
+        db[carrier + 1] = -50.0;
+        for v in &mut db[600..700] {
+            *v = -60.0;
+        }
+        PowerTable {
+            start_hz: 144_000_000.0,
+            step_hz: 1_000.0,
+            db,
+        }
+    }
+
+    #[test]
+    fn discovers_persistent_carriers() {
+        let mut finder = SignalFinder::new();
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for n in 0..2 {
+            let time = start + Duration::from_secs(n * 10);
+            assert!(finder.observe_table(time, &table(200)).is_empty());
+        }
+        assert!(finder.discovered().is_empty());
+        let found = finder.observe_table(start + Duration::from_secs(20), &table(201));
+        assert_eq!(found.len(), 1);
+        let signal = &found[0];
+        assert!(
+            (signal.freq_hz - 144_200_333.3).abs() < 1.0,
+            "{} hz",
+            signal.freq_hz
+        );
+        assert_eq!(signal.bandwidth_hz, 3_000.0);
+        assert_eq!(signal.peak_db, -40.0);
+        assert_eq!(signal.first_seen, start);
+        assert_eq!(signal.last_seen, start + Duration::from_secs(20));
+        assert_eq!(signal.sightings, 3);
+        // Already discovered: not reported again, but kept up to date.
+        assert!(finder
+            .observe_table(start + Duration::from_secs(30), &table(200))
+            .is_empty());
+        assert_eq!(finder.discovered().len(), 1);
+        assert_eq!(finder.discovered()[0].sightings, 4);
+        assert_eq!(
+            finder.discovered()[0].to_string(),
+            "144.20025 MHz 3 kHz -40.0 dB"
+        );
+    }
+
+    #[test]
+    fn ignores_wide_signals_and_one_offs() {
+        let mut finder = SignalFinder::new().with_min_sightings(2);
+        let now = UNIX_EPOCH;
+        finder.observe_table(now, &table(100));
+        finder.observe_table(now, &table(900));
+        assert!(finder.discovered().is_empty());
+        let found = finder.observe_table(now, &table(900));
+        assert_eq!(found.len(), 1);
+        assert!((found[0].freq_hz - 144_900_000.0).abs() < 1.0);
+        finder.clear();
+        assert!(finder.discovered().is_empty());
+    }
+
+    #[cfg(feature = "presets")]
+    #[test]
+    fn exports_presets() {
+        use crate::presets::{write_chirp_csv, Mode};
+
+        let mut finder = SignalFinder::new().with_min_sightings(1);
+        finder.observe_table(UNIX_EPOCH + Duration::from_secs(60), &table(200));
+        let presets = finder.to_presets();
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].freq_hz, 144_200_000);
+        assert_eq!(presets[0].name, "144.2 MHz");
+        assert_eq!(presets[0].mode, Mode::NarrowFm);
+        assert!(presets[0].comment.starts_with("found 1970-01-01"));
+        let mut csv = Vec::new();
+        write_chirp_csv(&mut csv, &presets).unwrap();
+        assert!(String::from_utf8(csv).unwrap().contains("144.200000"));
+    }
+
+    #[cfg(feature = "presets")]
+    #[test]
+    fn exports_presets_on_the_region_channels() {
+        use crate::presets::{Mode, Region};
+
+        let mut finder = SignalFinder::new().with_min_sightings(1);
+        finder.observe_table(UNIX_EPOCH, &table(207));
+        let europe = finder.to_presets_in(Region::One);
+        assert_eq!(europe[0].freq_hz, 144_212_500);
+        assert_eq!(europe[0].name, "144.2125 MHz");
+        assert_eq!(
+            (europe[0].mode, europe[0].step_hz),
+            (Mode::NarrowFm, Some(12_500))
+        );
+        let americas = finder.to_presets_in(Region::Two);
+        assert_eq!(americas[0].freq_hz, 144_205_000);
+        assert_eq!(
+            (americas[0].mode, americas[0].step_hz),
+            (Mode::Fm, Some(5_000))
+        );
+    }
+}
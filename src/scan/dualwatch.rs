@@ -0,0 +1,688 @@
+use std::f64::consts::TAU;
+use std::fmt;
+use std::time::Duration;
+
+use super::activity::{Channel, FLOOR_ALPHA, HYSTERESIS_DB, USABLE};
+use crate::dsp::{Channelizer, Complex, FreqXlator};
+use crate::error::{Error, Result};
+use crate::samples::{to_complex_f32, Scaling};
+use crate::source::SdrSource;
+
+/// Polyphase branch length of the shared-span channelizer.
+const TAPS_PER_CHANNEL: usize = 16;
+
+/// Length of the time-sliced channel filter, in multiples of the
+/// decimation factor.
+pub(super) const TAPS_PER_FACTOR: usize = 8;
+
+/// One of the two channels of a `DualWatch`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Watched {
+    /// The channel that takes over whenever it is active.
+    Priority,
+    /// The channel listened to while the priority channel is quiet.
+    Secondary,
+}
+
+impl Watched {
+    fn index(self) -> usize {
+        match self {
+            Watched::Priority => 0,
+            Watched::Secondary => 1,
+        }
+    }
+}
+
+/// How a `DualWatch` covers its two channels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WatchMode {
+    /// Both channels fit in one tuning and are received at once through a
+    /// `Channelizer`, so the priority channel is never missed.
+    Shared,
+    /// The channels are too far apart for one tuning, so the source is
+    /// retuned between them and the priority channel is only sampled every
+    /// priority interval while the secondary one is active.
+    TimeSliced,
+}
+
+/// A block of samples from the channel a `DualWatch` is listening to.
+#[derive(Clone, Debug)]
+pub struct WatchBlock {
+    /// The channel the samples are from.
+    pub channel: Watched,
+    /// The channel at baseband, at `DualWatch::output_rate`.
+    pub samples: Vec<Complex<f32>>,
+    /// The channel power in dBFS.
+    pub power_db: f32,
+    /// Whether the priority channel has just taken over from an active
+    /// secondary channel.
+    pub preempted: bool,
+}
+
+/// Formats the block as one log line, e.g. `priority -32.5 dB` or
+/// `priority -32.5 dB, preempted`.
+impl fmt::Display for WatchBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.channel {
+            Watched::Priority => "priority",
+            Watched::Secondary => "secondary",
+        };
+        write!(f, "{} {:.1} dB", name, self.power_db)?;
+        if self.preempted {
+            write!(f, ", preempted")?;
+        }
+        Ok(())
+    }
+}
+
+/// Squelch state of one channel, timed in input samples.
+#[derive(Default)]
+pub(super) struct Squelch {
+    pub(super) floor_db: Option<f32>,
+    measured: usize,
+    open: bool,
+    last_above: u64,
+}
+
+impl Squelch {
+    /// Update the squelch with a measurement taken at input sample `now`.
+    ///
+    /// The first `learning` measurements only learn the floor. Returns
+    /// whether the channel is open, counting a hang of `hang` samples.
+    pub(super) fn update(
+        &mut self,
+        db: f32,
+        now: u64,
+        hang: u64,
+        threshold_db: f32,
+        learning: usize,
+    ) -> bool {
+        self.measured += 1;
+        let floor = match self.floor_db {
+            Some(floor) if self.measured <= learning => floor.min(db),
+            Some(floor) => floor,
+            None => db,
+        };
+        self.floor_db = Some(floor);
+        if self.measured <= learning {
+            return false;
+        }
+
+        if db >= floor + threshold_db {
+            self.open = true;
+            self.last_above = now;
+        } else if db < floor + threshold_db - HYSTERESIS_DB {
+            if !self.open {
+                self.floor_db = Some(floor + FLOOR_ALPHA * (db - floor));
+            } else if now - self.last_above >= hang {
+                self.open = false;
+            }
+        }
+        self.open
+    }
+
+    /// Check whether the floor has been learned.
+    pub(super) fn learned(&self, learning: usize) -> bool {
+        self.measured > learning
+    }
+}
+
+enum Tuning {
+    Shared {
+        channelizer: Channelizer,
+        /// The channelizer output of each channel.
+        bins: [usize; 2],
+        /// How far each channel sits from its output's centre, in Hz.
+        residual: [f64; 2],
+        /// Phase of the mixer removing the residual, in radians.
+        phase: [f64; 2],
+        out: Vec<Vec<Complex<f32>>>,
+    },
+    TimeSliced {
+        xlator: FreqXlator,
+        tuned: Option<Watched>,
+    },
+}
+
+/// Dual-watch receiver: listens to a secondary channel while checking a
+/// priority channel, and switches to the priority channel as soon as it
+/// becomes active, as hardware scanners do.
+///
+/// When both channels fit in the usable 75% of one tuning they are
+/// received together through a `Channelizer` (`WatchMode::Shared`).
+/// Otherwise the source is retuned between them (`WatchMode::TimeSliced`):
+/// while idle both channels are checked in turn, and while the secondary
+/// channel is active the receiver leaves it for a short look at the
+/// priority channel every priority interval.
+///
+/// Each channel comes out at baseband at the same rate, the source's
+/// sample rate divided down to just above the wider channel, ready for a
+/// demodulator. The squelch learns each channel's noise floor the way
+/// `ActivityScanner` does and opens `threshold` dB above it; a channel
+/// stays selected until it has been quiet for the hang time.
+pub struct DualWatch<S> {
+    source: S,
+    rate: f64,
+    channels: [Channel; 2],
+    tuning: Tuning,
+    factor: usize,
+    squelch: [Squelch; 2],
+    listening: Option<Watched>,
+    /// Input samples read so far, the clock for all timing.
+    clock: u64,
+    last_check: u64,
+    block: Duration,
+    dwell: Duration,
+    settle: Duration,
+    interval: Duration,
+    hang: Duration,
+    threshold_db: f32,
+    learning: usize,
+    buf: Vec<u8>,
+}
+
+impl<S: SdrSource> DualWatch<S> {
+    /// Plan a dual watch at the source's current sample rate.
+    ///
+    /// Defaults to a 10 dB threshold, 2 s hang time, 3 learning
+    /// measurements per channel and 50 ms blocks. Time slicing looks at the
+    /// priority channel every 2 s for 40 ms, after 10 ms of settling.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to receive with.
+    /// * `priority` - The channel to prefer.
+    /// * `secondary` - The channel to listen to otherwise.
+    ///
+    /// # Returns
+    ///
+    /// A new `DualWatch` if successful, otherwise an `Error`.
+    /// `Error::InvalidParam` is returned if a channel is wider than 75% of
+    /// the sample rate.
+    pub fn new(mut source: S, priority: Channel, secondary: Channel) -> Result<Self> {
+        let rate = source.sample_rate()? as f64;
+        let usable = rate * USABLE;
+        let widest = priority.bandwidth_hz().max(secondary.bandwidth_hz());
+        if widest > usable || widest <= 0.0 {
+            return Err(Error::InvalidParam);
+        }
+        let factor = ((rate / widest) as usize).max(2);
+        let spacing = rate / factor as f64;
+
+        let (p, s) = (priority.centre_hz(), secondary.centre_hz());
+        let (low, high) = (p.min(s), p.max(s));
+        // Keep both channels clear of the DC spike.
+        let centre = if (high - low) / 2.0 < spacing {
+            low - spacing
+        } else {
+            (low + high) / 2.0
+        };
+        let fits = [priority, secondary]
+            .iter()
+            .all(|c| c.low_hz >= centre - usable / 2.0 && c.high_hz <= centre + usable / 2.0);
+
+        let tuning = if fits {
+            source.tune(centre.round() as u32)?;
+            let centre = centre.round();
+            let channelizer = Channelizer::new(factor, TAPS_PER_CHANNEL);
+            let mut bins = [0; 2];
+            let mut residual = [0.0; 2];
+            for (i, f) in [p, s].into_iter().enumerate() {
+                let offset = f - centre;
+                bins[i] = channelizer
+                    .channel_index(offset, rate)
+                    .ok_or(Error::InvalidParam)?;
+                residual[i] = offset - channelizer.channel_frequency(bins[i], rate);
+            }
+            Tuning::Shared {
+                out: vec![Vec::new(); channelizer.channels()],
+                channelizer,
+                bins,
+                residual,
+                phase: [0.0; 2],
+            }
+        } else {
+            Tuning::TimeSliced {
+                xlator: FreqXlator::with_low_pass(
+                    rate,
+                    rate / 4.0,
+                    factor,
+                    factor * TAPS_PER_FACTOR,
+                ),
+                tuned: None,
+            }
+        };
+
+        Ok(DualWatch {
+            source,
+            rate,
+            channels: [priority, secondary],
+            tuning,
+            factor,
+            squelch: Default::default(),
+            listening: None,
+            clock: 0,
+            last_check: 0,
+            block: Duration::from_millis(50),
+            dwell: Duration::from_millis(40),
+            settle: Duration::from_millis(10),
+            interval: Duration::from_secs(2),
+            hang: Duration::from_secs(2),
+            threshold_db: 10.0,
+            learning: 3,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Set how far above the noise floor a channel counts as active.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_db` - The threshold in dB above the floor.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new threshold.
+    pub fn with_threshold(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set how long a channel has to stay quiet before it is let go.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.hang = hang;
+        self
+    }
+
+    /// Set how many measurements of each channel are used to learn its
+    /// initial noise floor.
+    ///
+    /// # Arguments
+    ///
+    /// * `measurements` - Measurements before the squelch opens, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new learning period.
+    pub fn with_learning(mut self, measurements: usize) -> Self {
+        self.learning = measurements.max(1);
+        self
+    }
+
+    /// Set how much is read for each block delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The length of a block.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new block length.
+    pub fn with_block(mut self, block: Duration) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Set how often a time-sliced watch looks at the priority channel
+    /// while the secondary one is active.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The priority interval.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new priority interval.
+    pub fn with_priority_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set how long a time-sliced watch measures a channel it hops to, and
+    /// how long it discards samples after each retune first.
+    ///
+    /// # Arguments
+    ///
+    /// * `dwell` - The measuring time.
+    /// * `settle` - The settling time.
+    ///
+    /// # Returns
+    ///
+    /// The `DualWatch` with the new timing.
+    pub fn with_dwell(mut self, dwell: Duration, settle: Duration) -> Self {
+        self.dwell = dwell;
+        self.settle = settle;
+        self
+    }
+
+    /// Get how the two channels are covered.
+    ///
+    /// # Returns
+    ///
+    /// `WatchMode::Shared` if both are received from one tuning.
+    pub fn mode(&self) -> WatchMode {
+        match self.tuning {
+            Tuning::Shared { .. } => WatchMode::Shared,
+            Tuning::TimeSliced { .. } => WatchMode::TimeSliced,
+        }
+    }
+
+    /// Get the rate of the samples in each block.
+    ///
+    /// # Returns
+    ///
+    /// The output sample rate in Hz.
+    pub fn output_rate(&self) -> f64 {
+        self.rate / self.factor as f64
+    }
+
+    /// Get a watched channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `which` - The channel.
+    ///
+    /// # Returns
+    ///
+    /// The channel as given to `new`.
+    pub fn channel(&self, which: Watched) -> Channel {
+        self.channels[which.index()]
+    }
+
+    /// Get the channel being listened to.
+    ///
+    /// # Returns
+    ///
+    /// The selected channel, or `None` while both are quiet.
+    pub fn listening(&self) -> Option<Watched> {
+        self.listening
+    }
+
+    /// Get the learned noise floor of a channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `which` - The channel.
+    ///
+    /// # Returns
+    ///
+    /// The floor in dBFS, or `None` before the channel was first measured.
+    pub fn noise_floor(&self, which: Watched) -> Option<f32> {
+        self.squelch[which.index()].floor_db
+    }
+
+    /// Receive the next block.
+    ///
+    /// # Returns
+    ///
+    /// The block of the channel being listened to, `None` while both are
+    /// quiet, or an `Error` if the source failed.
+    pub fn next_block(&mut self) -> Result<Option<WatchBlock>> {
+        match self.tuning {
+            Tuning::Shared { .. } => self.next_shared(),
+            Tuning::TimeSliced { .. } => self.next_sliced(),
+        }
+    }
+
+    fn next_shared(&mut self) -> Result<Option<WatchBlock>> {
+        let input = self.read(self.block)?;
+        let Tuning::Shared {
+            channelizer,
+            bins,
+            residual,
+            phase,
+            out,
+        } = &mut self.tuning
+        else {
+            unreachable!();
+        };
+        out.iter_mut().for_each(Vec::clear);
+        channelizer.process_into(&input, out);
+
+        let out_rate = self.rate / self.factor as f64;
+        let mut channels: [Vec<Complex<f32>>; 2] = Default::default();
+        for i in 0..2 {
+            let step = TAU * residual[i] / out_rate;
+            channels[i] = out[bins[i]]
+                .iter()
+                .map(|&x| {
+                    let (sin, cos) = (-phase[i]).sin_cos();
+                    phase[i] = (phase[i] + step).rem_euclid(TAU);
+                    x * Complex::new(cos as f32, sin as f32)
+                })
+                .collect();
+        }
+        let power = channels.each_ref().map(|c| power_db(c));
+        let open = [
+            self.update(Watched::Priority, power[0]),
+            self.update(Watched::Secondary, power[1]),
+        ];
+
+        let previous = self.listening;
+        self.listening = if open[0] {
+            Some(Watched::Priority)
+        } else if open[1] {
+            Some(Watched::Secondary)
+        } else {
+            None
+        };
+        Ok(self.listening.map(|which| {
+            let i = which.index();
+            WatchBlock {
+                channel: which,
+                samples: std::mem::take(&mut channels[i]),
+                power_db: power[i],
+                preempted: which == Watched::Priority && previous == Some(Watched::Secondary),
+            }
+        }))
+    }
+
+    fn next_sliced(&mut self) -> Result<Option<WatchBlock>> {
+        if self.listening == Some(Watched::Secondary)
+            && self.elapsed(self.last_check) >= self.interval
+        {
+            self.last_check = self.clock;
+            let (samples, db) = self.measure(Watched::Priority, self.dwell)?;
+            if self.update(Watched::Priority, db) {
+                self.listening = Some(Watched::Priority);
+                return Ok(Some(WatchBlock {
+                    channel: Watched::Priority,
+                    samples,
+                    power_db: db,
+                    preempted: true,
+                }));
+            }
+        }
+
+        if let Some(which) = self.listening {
+            let (samples, db) = self.measure(which, self.block)?;
+            if !self.update(which, db) {
+                self.listening = None;
+                return Ok(None);
+            }
+            return Ok(Some(WatchBlock {
+                channel: which,
+                samples,
+                power_db: db,
+                preempted: false,
+            }));
+        }
+
+        for which in [Watched::Priority, Watched::Secondary] {
+            let (samples, db) = self.measure(which, self.dwell)?;
+            if self.update(which, db) {
+                self.listening = Some(which);
+                self.last_check = self.clock;
+                return Ok(Some(WatchBlock {
+                    channel: which,
+                    samples,
+                    power_db: db,
+                    preempted: false,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tune a time-sliced watch to a channel if needed, then receive it.
+    fn measure(&mut self, which: Watched, len: Duration) -> Result<(Vec<Complex<f32>>, f32)> {
+        let Tuning::TimeSliced { tuned, .. } = &self.tuning else {
+            unreachable!();
+        };
+        let retune = *tuned != Some(which);
+        if retune {
+            let freq = self.channels[which.index()].centre_hz() - self.rate / 4.0;
+            self.source.tune(freq.round() as u32)?;
+            let settling = self.read(self.settle)?;
+            let Tuning::TimeSliced { xlator, tuned } = &mut self.tuning else {
+                unreachable!();
+            };
+            *tuned = Some(which);
+            // Flushes the previous channel out of the filter.
+            xlator.process_into(&settling, &mut Vec::new());
+        }
+        let input = self.read(len)?;
+        let Tuning::TimeSliced { xlator, .. } = &mut self.tuning else {
+            unreachable!();
+        };
+        let mut samples = Vec::with_capacity(input.len() / self.factor + 1);
+        xlator.process_into(&input, &mut samples);
+        let db = power_db(&samples);
+        Ok((samples, db))
+    }
+
+    /// Update the squelch of a channel with a measurement.
+    ///
+    /// Returns whether the channel is open, counting the hang time.
+    fn update(&mut self, which: Watched, db: f32) -> bool {
+        let hang = (self.hang.as_secs_f64() * self.rate) as u64;
+        self.squelch[which.index()].update(db, self.clock, hang, self.threshold_db, self.learning)
+    }
+
+    fn elapsed(&self, since: u64) -> Duration {
+        Duration::from_secs_f64((self.clock - since) as f64 / self.rate)
+    }
+
+    fn read(&mut self, len: Duration) -> Result<Vec<Complex<f32>>> {
+        let samples = (len.as_secs_f64() * self.rate).round() as usize;
+        self.buf.resize(samples * 2, 0);
+        self.source.read_exact(&mut self.buf)?;
+        self.clock += samples as u64;
+        Ok(to_complex_f32(&self.buf, Scaling::Normalized))
+    }
+
+    /// Give back the source.
+    ///
+    /// # Returns
+    ///
+    /// The source the watch was created with.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: SdrSource> Iterator for DualWatch<S> {
+    type Item = Result<Option<WatchBlock>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_block())
+    }
+}
+
+pub(super) fn power_db(samples: &[Complex<f32>]) -> f32 {
+    let power =
+        samples.iter().map(|x| x.norm_sqr() as f64).sum::<f64>() / samples.len().max(1) as f64;
+    (10.0 * power.max(1e-20).log10()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockDevice;
+    use crate::scan::testing::{carrier, clear_carriers, mock_band, tunes};
+
+    fn run(watch: &mut DualWatch<MockDevice>, blocks: usize) -> Vec<Option<WatchBlock>> {
+        (0..blocks).map(|_| watch.next_block().unwrap()).collect()
+    }
+
+    fn listening(blocks: &[Option<WatchBlock>]) -> Option<Watched> {
+        blocks.last().unwrap().as_ref().map(|b| b.channel)
+    }
+
+    #[test]
+    fn shares_one_tuning_when_both_fit() {
+        let (band, carriers) = mock_band(1);
+        let priority = Channel::new(145_500_000, 25_000);
+        let secondary = Channel::new(145_800_000, 25_000);
+        let mut watch = DualWatch::new(band, priority, secondary)
+            .unwrap()
+            .with_hang(Duration::from_millis(100));
+        assert_eq!(watch.mode(), WatchMode::Shared);
+        assert_eq!(watch.output_rate(), 25_600.0);
+        assert!(run(&mut watch, 5).iter().all(Option::is_none));
+
+        carriers.push(carrier(145_800_000, 20.0));
+        let blocks = run(&mut watch, 2);
+        assert_eq!(listening(&blocks), Some(Watched::Secondary));
+
+        carriers.push(carrier(145_500_000, 20.0));
+        let block = watch.next_block().unwrap().unwrap();
+        assert_eq!(block.channel, Watched::Priority);
+        assert!(block.preempted);
+        assert_eq!(block.samples.len(), 1280);
+
+        carriers.remove(2);
+        let blocks = run(&mut watch, 5);
+        assert_eq!(listening(&blocks), Some(Watched::Secondary));
+        clear_carriers(&carriers);
+        assert_eq!(listening(&run(&mut watch, 5)), None);
+        assert_eq!(tunes(&watch.into_inner()).len(), 1);
+    }
+
+    #[test]
+    fn time_slices_when_apart() {
+        let (band, carriers) = mock_band(1);
+        let priority = Channel::new(145_500_000, 25_000);
+        let secondary = Channel::new(433_500_000, 25_000);
+        let mut watch = DualWatch::new(band, priority, secondary)
+            .unwrap()
+            .with_hang(Duration::from_millis(100))
+            .with_priority_interval(Duration::from_millis(500));
+        assert_eq!(watch.mode(), WatchMode::TimeSliced);
+        assert!(run(&mut watch, 4).iter().all(Option::is_none));
+        assert!(watch.noise_floor(Watched::Priority).is_some());
+
+        carriers.push(carrier(433_500_000, 20.0));
+        let blocks = run(&mut watch, 3);
+        assert_eq!(listening(&blocks), Some(Watched::Secondary));
+        assert!(blocks.iter().flatten().all(|b| !b.preempted));
+
+        // The priority channel is picked up at the next look.
+        carriers.push(carrier(145_500_000, 20.0));
+        let blocks = run(&mut watch, 12);
+        let taken = blocks.iter().flatten().position(|b| b.preempted).unwrap();
+        assert!(blocks[taken..]
+            .iter()
+            .flatten()
+            .all(|b| b.channel == Watched::Priority));
+
+        clear_carriers(&carriers);
+        assert_eq!(listening(&run(&mut watch, 4)), None);
+        assert_eq!(watch.listening(), None);
+    }
+
+    #[test]
+    fn rejects_channels_wider_than_a_tuning() {
+        let (band, _) = mock_band(1);
+        let wide = Channel::new(100_000_000, 900_000);
+        assert!(DualWatch::new(band, wide, Channel::new(101_000_000, 200_000)).is_err());
+    }
+}
@@ -0,0 +1,632 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::power::{PowerTable, SweepRow};
+use crate::dsp::spectrum::Colormap;
+use crate::error::{Error, Result};
+use crate::units::Frequency;
+use crate::utils::UtcTime;
+
+/// The width of the time labels' margin, room for `HH:MM:SS`.
+const LEFT: usize = 54;
+
+/// The height of the frequency labels' margin.
+const TOP: usize = 14;
+
+/// The least spacing of frequency labels, in pixels.
+const FREQ_SPACING: usize = 110;
+
+/// The spacing of time labels, in sweeps.
+const TIME_SPACING: usize = 12;
+
+/// The size of a label character with the space after it.
+const CHAR_WIDTH: usize = 6;
+const CHAR_HEIGHT: usize = 7;
+
+/// 5 by 7 pixel glyphs for the characters labels use, one row per byte
+/// with the leftmost pixel in bit 4.
+const GLYPHS: [(char, [u8; 7]); 19] = [
+    ('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+    ('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+    ('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+    ('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+    ('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+    ('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+    ('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+    ('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c]),
+    (':', [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00]),
+    ('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+    ('k', [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12]),
+    ('M', [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('G', [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f]),
+    ('H', [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11]),
+    ('z', [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f]),
+    (' ', [0x00; 7]),
+];
+
+/// A label's text and top left corner.
+struct Label {
+    x: usize,
+    y: usize,
+    text: String,
+}
+
+/// Time-versus-frequency image of a sweep history, as `rtl_power`'s
+/// `heatmap.py` draws.
+///
+/// Each sweep is one row of pixels, oldest at the top, with one pixel per
+/// bin. Frequencies are labelled along the top and times in UTC down the
+/// left unless labels are turned off. Levels map onto the colour scale
+/// between the quietest and loudest bins of the whole history, or a range
+/// set with `with_range`.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use radion::dsp::spectrum::Colormap;
+/// use radion::scan::Heatmap;
+///
+/// // Draw a file written by rtl_power or `PowerSweep`.
+/// let csv = BufReader::new(File::open("sweep.csv")?);
+/// Heatmap::read_csv(csv)?
+///     .with_colormap(Colormap::Viridis)
+///     .save_png("sweep.png")?;
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct Heatmap {
+    start_hz: f64,
+    step_hz: f64,
+    rows: Vec<(SystemTime, Vec<f32>)>,
+    colormap: Colormap,
+    range: Option<(f32, f32)>,
+    labels: bool,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap::new()
+    }
+}
+
+impl Heatmap {
+    /// Create an empty heatmap with the `Heat` colour scale and labels.
+    pub fn new() -> Self {
+        Heatmap {
+            start_hz: 0.0,
+            step_hz: 0.0,
+            rows: Vec::new(),
+            colormap: Colormap::Heat,
+            range: None,
+            labels: true,
+        }
+    }
+
+    /// Read the sweeps of an `rtl_power` CSV file.
+    ///
+    /// A sweep ends where the next line starts at or below the previous
+    /// line's frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The CSV, from `rtl_power` or `SweepRow`'s `Display`.
+    ///
+    /// # Returns
+    ///
+    /// A `Heatmap` of every sweep if successful, otherwise an `Error`.
+    pub fn read_csv<R: BufRead>(reader: R) -> Result<Self> {
+        let mut heatmap = Heatmap::new();
+        let mut sweep: Vec<SweepRow> = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row: SweepRow = line.parse()?;
+            if sweep.last().is_some_and(|last| row.low_hz <= last.low_hz) {
+                heatmap.push_rows(&sweep)?;
+                sweep.clear();
+            }
+            sweep.push(row);
+        }
+        if !sweep.is_empty() {
+            heatmap.push_rows(&sweep)?;
+        }
+        Ok(heatmap)
+    }
+
+    /// Set the colour scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `colormap` - The colour scale.
+    ///
+    /// # Returns
+    ///
+    /// The `Heatmap` with the new scale.
+    pub fn with_colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    /// Fix the levels at the ends of the colour scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_db` - The level drawn in the scale's first colour.
+    /// * `max_db` - The level drawn in its last colour.
+    ///
+    /// # Returns
+    ///
+    /// The `Heatmap` with the new range, or `Error::InvalidParam` if
+    /// `max_db` is not above `min_db`.
+    pub fn with_range(mut self, min_db: f32, max_db: f32) -> Result<Self> {
+        if !(min_db.is_finite() && max_db.is_finite()) || max_db <= min_db {
+            return Err(Error::InvalidParam);
+        }
+        self.range = Some((min_db, max_db));
+        Ok(self)
+    }
+
+    /// Turn the axis labels on or off.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - Whether to label the axes.
+    ///
+    /// # Returns
+    ///
+    /// The `Heatmap` with or without labels.
+    pub fn with_labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Add a sweep.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - When the sweep finished.
+    /// * `table` - The sweep.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, or `Error::InvalidParam` if the sweep isn't on the
+    /// same bins as the first.
+    pub fn push(&mut self, time: SystemTime, table: &PowerTable) -> Result<()> {
+        if let Some((_, first)) = self.rows.first() {
+            if table.db.len() != first.len()
+                || table.step_hz != self.step_hz
+                || (table.start_hz - self.start_hz).abs() > self.step_hz / 2.0
+            {
+                return Err(Error::InvalidParam);
+            }
+        } else {
+            self.start_hz = table.start_hz;
+            self.step_hz = table.step_hz;
+        }
+        self.rows.push((time, table.db.clone()));
+        Ok(())
+    }
+
+    /// Add a sweep from its rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Every row of one sweep, in any order.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result, or `Error::InvalidParam` if there are no rows or the
+    /// sweep isn't on the same bins as the first.
+    pub fn push_rows(&mut self, rows: &[SweepRow]) -> Result<()> {
+        let table = PowerTable::from_rows(rows).ok_or(Error::InvalidParam)?;
+        let time = rows
+            .iter()
+            .map(|r| r.time)
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.push(time, &table)
+    }
+
+    /// Get the number of sweeps.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Check whether no sweep has been added.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Get the size of the image, labels included.
+    ///
+    /// # Returns
+    ///
+    /// The width and height in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        let bins = self.rows.first().map_or(0, |(_, db)| db.len());
+        if self.labels {
+            (LEFT + bins.max(1), TOP + self.rows.len().max(CHAR_HEIGHT))
+        } else {
+            (bins.max(1), self.rows.len().max(1))
+        }
+    }
+
+    /// Render the image as RGB pixels.
+    ///
+    /// # Returns
+    ///
+    /// `width * height * 3` bytes of `size`, row-major.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        let (width, height) = self.size();
+        let (left, top) = self.origin();
+        let range = self.range();
+        let mut rgb = vec![0; width * height * 3];
+        for (y, (_, db)) in self.rows.iter().enumerate() {
+            for (x, &v) in db.iter().enumerate() {
+                let at = ((top + y) * width + left + x) * 3;
+                rgb[at..at + 3].copy_from_slice(&self.colormap.rgb(level(v, range)));
+            }
+        }
+        for x in self.ticks() {
+            for y in TOP - 3..TOP {
+                let at = (y * width + x) * 3;
+                rgb[at..at + 3].fill(255);
+            }
+        }
+        for label in self.labels() {
+            for (i, c) in label.text.chars().enumerate() {
+                let Some((_, glyph)) = GLYPHS.iter().find(|(g, _)| *g == c) else {
+                    continue;
+                };
+                for (dy, bits) in glyph.iter().enumerate() {
+                    for dx in 0..5 {
+                        let x = label.x + i * CHAR_WIDTH + dx;
+                        if bits >> (4 - dx) & 1 == 1 && x < width {
+                            let at = ((label.y + dy) * width + x) * 3;
+                            rgb[at..at + 3].fill(255);
+                        }
+                    }
+                }
+            }
+        }
+        rgb
+    }
+
+    /// Write the image as a PNG.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the image.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let (width, height) = self.size();
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let to_error = |e: png::EncodingError| Error::System(std::io::Error::other(e));
+        let mut png = encoder.write_header().map_err(to_error)?;
+        png.write_image_data(&self.to_rgb()).map_err(to_error)?;
+        png.finish().map_err(to_error)
+    }
+
+    /// Save the image as a PNG file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+
+    /// Write the image as an SVG, one rectangle per run of equal colour in
+    /// each sweep and the labels as text.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where to write the image.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn write_svg<W: Write>(&self, mut writer: W) -> Result<()> {
+        let (width, height) = self.size();
+        let (left, top) = self.origin();
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\" shape-rendering=\"crispEdges\">",
+            w = width,
+            h = height
+        );
+        let _ = writeln!(svg, "<rect width=\"{}\" height=\"{}\"/>", width, height);
+        let range = self.range();
+        for (y, (_, db)) in self.rows.iter().enumerate() {
+            let mut x = 0;
+            while x < db.len() {
+                let colour = self.colormap.rgb(level(db[x], range));
+                let run = db[x..]
+                    .iter()
+                    .take_while(|&&v| self.colormap.rgb(level(v, range)) == colour)
+                    .count();
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\" fill=\"#{:02x}{:02x}{:02x}\"/>",
+                    left + x,
+                    top + y,
+                    run,
+                    colour[0],
+                    colour[1],
+                    colour[2]
+                );
+                x += run;
+            }
+        }
+        for x in self.ticks() {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{x}.5\" y1=\"{}\" x2=\"{x}.5\" y2=\"{}\" stroke=\"#fff\"/>",
+                TOP - 3,
+                TOP,
+                x = x
+            );
+        }
+        for label in self.labels() {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" fill=\"#fff\" font-family=\"monospace\" \
+                 font-size=\"9\">{}</text>",
+                label.x,
+                label.y + CHAR_HEIGHT,
+                label.text
+            );
+        }
+        svg.push_str("</svg>\n");
+        writer.write_all(svg.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save the image as an SVG file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if successful, otherwise an `Error`.
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_svg(std::fs::File::create(path)?)
+    }
+
+    /// Where the first bin of the oldest sweep is drawn.
+    fn origin(&self) -> (usize, usize) {
+        if self.labels {
+            (LEFT, TOP)
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// The levels at the ends of the colour scale: the range set, or else
+    /// the lowest and highest level in the history.
+    fn range(&self) -> (f32, f32) {
+        self.range.unwrap_or_else(|| {
+            let finite = self
+                .rows
+                .iter()
+                .flat_map(|(_, db)| db)
+                .filter(|v| v.is_finite());
+            let (min, max) =
+                finite.fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            if max > min {
+                (min, max)
+            } else {
+                (min, min + 1.0)
+            }
+        })
+    }
+
+    /// The frequency labels' spacing: the first of 1, 2 or 5 times a power
+    /// of ten that keeps them `FREQ_SPACING` pixels apart.
+    fn tick_hz(&self) -> f64 {
+        let least = self.step_hz * FREQ_SPACING as f64;
+        let decade = 10f64.powf(least.log10().floor());
+        [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|m| m * decade)
+            .find(|&step| step >= least)
+            .unwrap_or(10.0 * decade)
+    }
+
+    /// The columns of the frequency ticks.
+    fn ticks(&self) -> Vec<usize> {
+        self.tick_freqs().into_iter().map(|(x, _)| x).collect()
+    }
+
+    fn tick_freqs(&self) -> Vec<(usize, f64)> {
+        let bins = self.rows.first().map_or(0, |(_, db)| db.len());
+        if !self.labels || bins == 0 {
+            return Vec::new();
+        }
+        let tick = self.tick_hz();
+        let end = self.start_hz + bins as f64 * self.step_hz;
+        let mut f = (self.start_hz / tick).ceil() * tick;
+        let mut ticks = Vec::new();
+        while f < end {
+            ticks.push((LEFT + ((f - self.start_hz) / self.step_hz) as usize, f));
+            f += tick;
+        }
+        ticks
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        if !self.labels {
+            return Vec::new();
+        }
+        let (width, _) = self.size();
+        let mut labels: Vec<Label> = self
+            .tick_freqs()
+            .into_iter()
+            .map(|(x, f)| {
+                let text = Frequency(f).to_string();
+                let len = text.len() * CHAR_WIDTH;
+                Label {
+                    x: x.saturating_sub(len / 2).min(width.saturating_sub(len)),
+                    y: 2,
+                    text,
+                }
+            })
+            .collect();
+        for (y, (time, _)) in self.rows.iter().enumerate().step_by(TIME_SPACING) {
+            let t = UtcTime::from_system_time(*time);
+            labels.push(Label {
+                x: 2,
+                y: TOP + y.min(self.rows.len().saturating_sub(CHAR_HEIGHT)),
+                text: format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second),
+            });
+        }
+        labels
+    }
+}
+
+/// Place a level on the colour scale between `min` and `max`.
+fn level(db: f32, (min, max): (f32, f32)) -> u8 {
+    ((db - min) / (max - min) * 255.0).clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn sweep(n: u64, bins: usize) -> Vec<SweepRow> {
+        // Two hops, with a carrier in the upper one.
+        (0..2)
+            .map(|hop| SweepRow {
+                time: UNIX_EPOCH + Duration::from_secs(3600 + 10 * n),
+                low_hz: 100_000_000.0 + (hop * bins) as f64 * 1_000.0,
+                high_hz: 100_000_000.0 + ((hop + 1) * bins) as f64 * 1_000.0,
+                step_hz: 1_000.0,
+                samples: 1024,
+                db: (0..bins)
+                    .map(|i| if hop == 1 && i == 3 { -20.0 } else { -80.0 })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draws_sweeps_oldest_first() {
+        let mut heatmap = Heatmap::new()
+            .with_labels(false)
+            .with_colormap(Colormap::Grayscale);
+        for n in 0..3 {
+            heatmap.push_rows(&sweep(n, 100)).unwrap();
+        }
+        assert_eq!(heatmap.len(), 3);
+        assert_eq!(heatmap.size(), (200, 3));
+        let rgb = heatmap.to_rgb();
+        for y in 0..3 {
+            let pixel = |x: usize| rgb[(y * 200 + x) * 3];
+            assert_eq!(pixel(0), 0);
+            assert_eq!(pixel(103), 255);
+        }
+        let fixed = heatmap.with_range(-100.0, 0.0).unwrap();
+        assert_eq!(fixed.to_rgb()[0], 51);
+        assert!(matches!(
+            Heatmap::new().with_range(0.0, 0.0),
+            Err(Error::InvalidParam)
+        ));
+    }
+
+    #[test]
+    fn rejects_sweeps_on_other_bins() {
+        let mut heatmap = Heatmap::new();
+        heatmap.push_rows(&sweep(0, 100)).unwrap();
+        assert!(matches!(
+            heatmap.push_rows(&sweep(1, 90)),
+            Err(Error::InvalidParam)
+        ));
+        assert!(matches!(heatmap.push_rows(&[]), Err(Error::InvalidParam)));
+    }
+
+    #[test]
+    fn labels_the_axes() {
+        let mut heatmap = Heatmap::new();
+        for n in 0..20 {
+            heatmap.push_rows(&sweep(n, 200)).unwrap();
+        }
+        // 110 kHz at 1 kHz bins rounds up to labels every 200 kHz.
+        assert_eq!(heatmap.tick_hz(), 200_000.0);
+        assert_eq!(heatmap.ticks(), [LEFT, LEFT + 200]);
+        let labels = heatmap.labels();
+        let texts: Vec<&str> = labels.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["100 MHz", "100.2 MHz", "01:00:00", "01:02:00"]);
+        assert_eq!(labels[3].y, TOP + 12);
+        // The labels are drawn in white over the black margin.
+        let (width, height) = heatmap.size();
+        assert_eq!((width, height), (LEFT + 400, TOP + 20));
+        let rgb = heatmap.to_rgb();
+        assert!(rgb[..TOP * width * 3].contains(&255));
+        assert_eq!(rgb[(TOP - 1) * width * 3 + LEFT * 3], 255);
+    }
+
+    #[test]
+    fn reads_rtl_power_csv() {
+        let mut csv = String::new();
+        for n in 0..4 {
+            for row in sweep(n, 10) {
+                csv.push_str(&row.to_string());
+                csv.push('\n');
+            }
+        }
+        let heatmap = Heatmap::read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(heatmap.len(), 4);
+        assert_eq!(heatmap.rows[3].0, UNIX_EPOCH + Duration::from_secs(3630));
+        assert_eq!(heatmap.rows[0].1.len(), 20);
+        assert!(Heatmap::read_csv("garbage\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn writes_svg() {
+        let mut heatmap = Heatmap::new().with_colormap(Colormap::Viridis);
+        heatmap.push_rows(&sweep(0, 10)).unwrap();
+        let mut svg = Vec::new();
+        heatmap.write_svg(&mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"74\""));
+        // The quiet bins either side of the carrier merge into runs.
+        assert_eq!(svg.matches("height=\"1\"").count(), 3);
+        assert!(svg.contains("fill=\"#fde725\""));
+        assert!(svg.contains(">00:00:00</text>") || svg.contains(">01:00:00</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn writes_png() {
+        let mut heatmap = Heatmap::new();
+        heatmap.push_rows(&sweep(0, 10)).unwrap();
+        let mut png = Vec::new();
+        heatmap.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,524 @@
+use std::time::Duration;
+
+use super::activity::{Channel, USABLE};
+use super::dualwatch::{power_db, Squelch, TAPS_PER_FACTOR};
+use super::power::{PowerSweep, PowerTable, SweepRow};
+use crate::dsp::{Complex, FreqXlator};
+use crate::error::{Error, Result};
+use crate::samples::{to_complex_f32, Scaling};
+use crate::source::SdrSource;
+
+/// A block delivered by `SweepWhileDecoding`.
+#[derive(Clone, Debug)]
+pub enum HybridBlock {
+    /// Samples of the decoded channel.
+    Channel {
+        /// The channel at baseband, at `SweepWhileDecoding::output_rate`.
+        samples: Vec<Complex<f32>>,
+        /// The channel power in dBFS.
+        power_db: f32,
+        /// Whether the squelch is open.
+        open: bool,
+    },
+    /// A hop of the background sweep, measured while the channel was quiet.
+    Sweep(SweepRow),
+}
+
+/// Decoder front end that sweeps a range in the background on the same
+/// source.
+///
+/// The source stays on one channel, which comes out at baseband for a
+/// decoder, with a squelch learning its noise floor the way
+/// `ActivityScanner` does. Once the squelch has been closed for the quiet
+/// time, the tuner is borrowed for a few hops of a `PowerSweep` and then
+/// retuned to the channel. Two limits keep the decoder from missing much:
+/// one interruption, including the settling after retuning back, never
+/// lasts longer than the maximum interruption, and the share of all
+/// samples spent away from the channel stays under the duty cycle. While
+/// the squelch is open the tuner is never borrowed.
+///
+/// Completed sweeps are stitched into a table available from `table`.
+pub struct SweepWhileDecoding<S> {
+    sweep: PowerSweep<S>,
+    channel: Channel,
+    rate: f64,
+    factor: usize,
+    xlator: FreqXlator,
+    squelch: Squelch,
+    open: bool,
+    /// Whether the source is tuned to the channel.
+    on_channel: bool,
+    /// Hops left in the current interruption.
+    borrowed: usize,
+    /// Input samples read or skipped so far, the clock for all timing.
+    clock: u64,
+    /// Input samples spent away from the channel.
+    away: u64,
+    last_open: u64,
+    interruptions: u64,
+    rows: Vec<SweepRow>,
+    table: Option<PowerTable>,
+    block: Duration,
+    settle: Duration,
+    hang: Duration,
+    quiet: Duration,
+    max_interruption: Duration,
+    duty_cycle: f64,
+    threshold_db: f32,
+    learning: usize,
+    buf: Vec<u8>,
+}
+
+impl<S: SdrSource> SweepWhileDecoding<S> {
+    /// Plan decoding a channel and sweeping a range at the source's
+    /// current sample rate.
+    ///
+    /// Defaults to a 10 dB threshold, 2 s hang time, 3 learning
+    /// measurements and 50 ms blocks with 10 ms of settling after retuning
+    /// to the channel. The tuner is borrowed after 2 s of quiet for at most
+    /// 200 ms and 10% of the time. The sweep uses the `PowerSweep`
+    /// defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to receive with.
+    /// * `channel` - The channel to decode.
+    /// * `start_hz` - Lower edge of the swept range in Hz.
+    /// * `stop_hz` - Upper edge of the swept range in Hz.
+    /// * `bin_hz` - Requested bin width of the sweep in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `SweepWhileDecoding` if successful, otherwise an `Error`.
+    /// `Error::InvalidParam` is returned if the channel is wider than a
+    /// quarter of the sample rate.
+    pub fn new(
+        source: S,
+        channel: Channel,
+        start_hz: u32,
+        stop_hz: u32,
+        bin_hz: f64,
+    ) -> Result<Self> {
+        let rate = source.sample_rate()? as f64;
+        let width = channel.bandwidth_hz();
+        // The channel sits a quarter of the rate off centre, clear of the
+        // DC spike, and has to stay in the usable part of the passband.
+        if width <= 0.0 || width > rate * (USABLE - 0.5) {
+            return Err(Error::InvalidParam);
+        }
+        let factor = ((rate / width) as usize).max(2);
+        Ok(SweepWhileDecoding {
+            sweep: PowerSweep::new(source, start_hz, stop_hz, bin_hz)?,
+            channel,
+            rate,
+            factor,
+            xlator: FreqXlator::with_low_pass(rate, rate / 4.0, factor, factor * TAPS_PER_FACTOR),
+            squelch: Squelch::default(),
+            open: false,
+            on_channel: false,
+            borrowed: 0,
+            clock: 0,
+            away: 0,
+            last_open: 0,
+            interruptions: 0,
+            rows: Vec::new(),
+            table: None,
+            block: Duration::from_millis(50),
+            settle: Duration::from_millis(10),
+            hang: Duration::from_secs(2),
+            quiet: Duration::from_secs(2),
+            max_interruption: Duration::from_millis(200),
+            duty_cycle: 0.1,
+            threshold_db: 10.0,
+            learning: 3,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Set how far above the noise floor the channel counts as active.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_db` - The threshold in dB above the floor.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new threshold.
+    pub fn with_threshold(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    /// Set how long the squelch stays open after the channel goes quiet.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.hang = hang;
+        self
+    }
+
+    /// Set how many measurements are used to learn the initial noise
+    /// floor. Nothing is swept before it is learned.
+    ///
+    /// # Arguments
+    ///
+    /// * `measurements` - Measurements before the squelch opens, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new learning period.
+    pub fn with_learning(mut self, measurements: usize) -> Self {
+        self.learning = measurements.max(1);
+        self
+    }
+
+    /// Set how much of the channel is read for each block, and how long
+    /// samples are discarded after retuning to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The length of a block.
+    /// * `settle` - The settling time.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new timing.
+    pub fn with_block(mut self, block: Duration, settle: Duration) -> Self {
+        self.block = block;
+        self.settle = settle;
+        self
+    }
+
+    /// Set the settling and integration time of each sweep hop.
+    ///
+    /// # Arguments
+    ///
+    /// * `settle` - The settling time after retuning to a hop.
+    /// * `integration` - The integration time per hop.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new hop timing.
+    pub fn with_sweep_timing(mut self, settle: Duration, integration: Duration) -> Self {
+        self.sweep = self.sweep.with_settle(settle).with_integration(integration);
+        self
+    }
+
+    /// Set how long the squelch has to be closed before the tuner is
+    /// borrowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `quiet` - The quiet time.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new quiet time.
+    pub fn with_quiet(mut self, quiet: Duration) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Set the longest the channel may go unheard at a time.
+    ///
+    /// As many hops are swept per interruption as fit in it together with
+    /// the settling back on the channel. If not even one hop fits, nothing
+    /// is swept.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_interruption` - The longest interruption.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new limit.
+    pub fn with_max_interruption(mut self, max_interruption: Duration) -> Self {
+        self.max_interruption = max_interruption;
+        self
+    }
+
+    /// Set the largest share of time spent away from the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty_cycle` - The share from 0.0, never sweeping, to 1.0.
+    ///
+    /// # Returns
+    ///
+    /// The `SweepWhileDecoding` with the new duty cycle.
+    pub fn with_duty_cycle(mut self, duty_cycle: f64) -> Self {
+        self.duty_cycle = duty_cycle.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Get the rate of the channel samples.
+    ///
+    /// # Returns
+    ///
+    /// The output sample rate in Hz.
+    pub fn output_rate(&self) -> f64 {
+        self.rate / self.factor as f64
+    }
+
+    /// Get the decoded channel.
+    ///
+    /// # Returns
+    ///
+    /// The channel as given to `new`.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Check whether the squelch is open.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Get the learned noise floor of the channel.
+    ///
+    /// # Returns
+    ///
+    /// The floor in dBFS, or `None` before the channel was first measured.
+    pub fn noise_floor(&self) -> Option<f32> {
+        self.squelch.floor_db
+    }
+
+    /// Get the number of times the tuner was borrowed.
+    pub fn interruptions(&self) -> u64 {
+        self.interruptions
+    }
+
+    /// Get the share of time spent away from the channel so far.
+    ///
+    /// # Returns
+    ///
+    /// The share from 0.0 to 1.0.
+    pub fn duty(&self) -> f64 {
+        self.away as f64 / self.clock.max(1) as f64
+    }
+
+    /// Get the number of hops in one sweep.
+    pub fn hops(&self) -> usize {
+        self.sweep.hops()
+    }
+
+    /// Get the last complete sweep.
+    ///
+    /// # Returns
+    ///
+    /// The stitched table, or `None` before the first sweep completed.
+    pub fn table(&self) -> Option<&PowerTable> {
+        self.table.as_ref()
+    }
+
+    /// Receive the next block.
+    ///
+    /// # Returns
+    ///
+    /// A block of the channel or a hop of the sweep, or an `Error` if the
+    /// source failed.
+    pub fn next_block(&mut self) -> Result<HybridBlock> {
+        if self.borrowed == 0 && self.may_borrow() {
+            self.borrowed = self.hops_per_interruption();
+            self.interruptions += 1;
+        }
+        if self.borrowed > 0 {
+            self.borrowed -= 1;
+            return self.next_hop().map(HybridBlock::Sweep);
+        }
+
+        if !self.on_channel {
+            let freq = self.channel.centre_hz() - self.rate / 4.0;
+            self.sweep.source_mut().tune(freq.round() as u32)?;
+            let settling = self.read(self.settle)?;
+            self.away += settling.len() as u64;
+            // Flushes the last hop out of the filter.
+            self.xlator.process_into(&settling, &mut Vec::new());
+            self.on_channel = true;
+        }
+        let input = self.read(self.block)?;
+        let mut samples = Vec::with_capacity(input.len() / self.factor + 1);
+        self.xlator.process_into(&input, &mut samples);
+        let db = power_db(&samples);
+        let hang = (self.hang.as_secs_f64() * self.rate) as u64;
+        self.open = self
+            .squelch
+            .update(db, self.clock, hang, self.threshold_db, self.learning);
+        if self.open {
+            self.last_open = self.clock;
+        }
+        Ok(HybridBlock::Channel {
+            samples,
+            power_db: db,
+            open: self.open,
+        })
+    }
+
+    fn may_borrow(&self) -> bool {
+        let quiet = (self.quiet.as_secs_f64() * self.rate) as u64;
+        let hops = self.hops_per_interruption();
+        // Samples the interruption will take, settling back included.
+        let cost =
+            (self.sweep.hop_duration() * hops as u32 + self.settle).as_secs_f64() * self.rate;
+        self.squelch.learned(self.learning)
+            && !self.open
+            && self.clock - self.last_open >= quiet
+            && hops > 0
+            && self.away as f64 + cost <= self.duty_cycle * (self.clock as f64 + cost)
+    }
+
+    fn hops_per_interruption(&self) -> usize {
+        let budget = self.max_interruption.saturating_sub(self.settle);
+        (budget.as_secs_f64() / self.sweep.hop_duration().as_secs_f64()).floor() as usize
+    }
+
+    fn next_hop(&mut self) -> Result<SweepRow> {
+        self.on_channel = false;
+        let row = self.sweep.next_row()?;
+        let busy = (self.sweep.hop_duration().as_secs_f64() * self.rate) as u64;
+        self.clock += busy;
+        self.away += busy;
+        self.rows.push(row.clone());
+        if self.rows.len() == self.sweep.hops() {
+            self.table = PowerTable::from_rows(&self.rows);
+            self.rows.clear();
+        }
+        Ok(row)
+    }
+
+    fn read(&mut self, len: Duration) -> Result<Vec<Complex<f32>>> {
+        let samples = (len.as_secs_f64() * self.rate).round() as usize;
+        self.buf.resize(samples * 2, 0);
+        self.sweep.source_mut().read_exact(&mut self.buf)?;
+        self.clock += samples as u64;
+        Ok(to_complex_f32(&self.buf, Scaling::Normalized))
+    }
+
+    /// Give back the source.
+    ///
+    /// # Returns
+    ///
+    /// The source the receiver was created with.
+    pub fn into_inner(self) -> S {
+        self.sweep.into_inner()
+    }
+}
+
+impl<S: SdrSource> Iterator for SweepWhileDecoding<S> {
+    type Item = Result<HybridBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_block())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockDevice;
+    use crate::scan::testing::{carrier, mock_band};
+
+    fn run(hybrid: &mut SweepWhileDecoding<MockDevice>, blocks: usize) -> Vec<HybridBlock> {
+        (0..blocks).map(|_| hybrid.next_block().unwrap()).collect()
+    }
+
+    fn is_sweep(block: &HybridBlock) -> bool {
+        matches!(block, HybridBlock::Sweep(_))
+    }
+
+    /// The longest run of sweep hops in a row.
+    fn longest_interruption(blocks: &[HybridBlock]) -> usize {
+        blocks
+            .split(|b| !is_sweep(b))
+            .map(<[_]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn sweeps_only_while_the_channel_is_quiet() {
+        let (band, carriers) = mock_band(1);
+        carriers.push(carrier(102_345_000, 20.0));
+        let channel = Channel::new(100_000_000, 12_500);
+        let mut hybrid = SweepWhileDecoding::new(band, channel, 101_000_000, 103_000_000, 4_000.0)
+            .unwrap()
+            .with_block(Duration::from_millis(20), Duration::from_millis(2))
+            .with_sweep_timing(Duration::from_millis(2), Duration::from_millis(8))
+            .with_hang(Duration::from_millis(40))
+            .with_quiet(Duration::from_millis(100))
+            .with_max_interruption(Duration::from_millis(25))
+            .with_duty_cycle(0.2);
+        assert_eq!(hybrid.hops(), 3);
+
+        let quiet = run(&mut hybrid, 40);
+        assert!(quiet[..5].iter().all(|b| !is_sweep(b)));
+        assert!(quiet.iter().any(is_sweep));
+        assert_eq!(longest_interruption(&quiet), 2);
+        assert!(hybrid.duty() < 0.3, "duty {}", hybrid.duty());
+        let table = hybrid.table().unwrap();
+        let (bin, _) = table
+            .db
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        assert!((table.frequency(bin) - 102_345_000.0).abs() <= table.step_hz);
+
+        // While the channel is active, it keeps the tuner.
+        carriers.push(carrier(100_000_000, 20.0));
+        let active = run(&mut hybrid, 40);
+        let opened = active
+            .iter()
+            .position(|b| matches!(b, HybridBlock::Channel { open: true, .. }))
+            .unwrap();
+        assert!(opened <= 2);
+        assert!(active[opened..].iter().all(|b| !is_sweep(b)));
+        assert!(hybrid.is_open());
+
+        let interruptions = hybrid.interruptions();
+        carriers.remove(2);
+        let after = run(&mut hybrid, 40);
+        assert!(!hybrid.is_open());
+        assert!(after[..4].iter().all(|b| !is_sweep(b)));
+        assert!(hybrid.interruptions() > interruptions);
+        if let HybridBlock::Channel { samples, .. } = &after[0] {
+            assert_eq!(samples.len(), 20_480 / 81);
+        }
+    }
+
+    #[test]
+    fn respects_the_limits() {
+        let (band, _) = mock_band(1);
+        let channel = Channel::new(100_000_000, 12_500);
+        let mut hybrid = SweepWhileDecoding::new(band, channel, 101_000_000, 103_000_000, 4_000.0)
+            .unwrap()
+            .with_block(Duration::from_millis(20), Duration::from_millis(2))
+            .with_sweep_timing(Duration::from_millis(2), Duration::from_millis(8))
+            .with_quiet(Duration::ZERO)
+            .with_max_interruption(Duration::from_millis(5));
+        // No hop fits in the interruption.
+        assert!(run(&mut hybrid, 20).iter().all(|b| !is_sweep(b)));
+
+        let (band, _) = mock_band(1);
+        let mut hybrid = SweepWhileDecoding::new(band, channel, 101_000_000, 103_000_000, 4_000.0)
+            .unwrap()
+            .with_block(Duration::from_millis(20), Duration::from_millis(2))
+            .with_sweep_timing(Duration::from_millis(2), Duration::from_millis(8))
+            .with_quiet(Duration::ZERO)
+            .with_max_interruption(Duration::from_millis(25))
+            .with_duty_cycle(0.05);
+        let blocks = run(&mut hybrid, 200);
+        assert!(blocks.iter().any(is_sweep));
+        assert!(hybrid.duty() <= 0.05, "duty {}", hybrid.duty());
+
+        let wide = Channel::new(100_000_000, 300_000);
+        let (band, _) = mock_band(1);
+        assert!(SweepWhileDecoding::new(band, wide, 101_000_000, 103_000_000, 4_000.0).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+//! Frequency scanning built on any `SdrSource`.
+
+mod activity;
+mod baseline;
+mod discover;
+mod dualwatch;
+mod heatmap;
+mod hybrid;
+mod mode;
+mod parallel;
+mod power;
+#[cfg(test)]
+mod testing;
+
+pub use activity::{Activity, ActivityEvent, ActivityScanner, Channel};
+pub use baseline::{Baseline, BaselineSchedule, Comparison, SpectrumChange};
+pub use discover::{DiscoveredSignal, SignalFinder};
+pub use dualwatch::{DualWatch, WatchBlock, WatchMode, Watched};
+pub use heatmap::Heatmap;
+pub use hybrid::{HybridBlock, SweepWhileDecoding};
+#[cfg(feature = "demod")]
+pub use mode::ModeDemod;
+pub use mode::{classify_mode, ChannelMode};
+pub use parallel::ParallelSweep;
+pub use power::{PowerSweep, PowerTable, SweepRow};
@@ -0,0 +1,303 @@
+use std::f64::consts::TAU;
+use std::fmt;
+
+#[cfg(feature = "demod")]
+use crate::demod::{AmDemod, FmDemod};
+#[cfg(feature = "demod")]
+use crate::dsp::fir::Decimator;
+use crate::dsp::Complex;
+
+/// Length of the moving average applied before measuring, in seconds. It
+/// keeps the lower voice frequencies and most of the noise out.
+const SMOOTHING_S: f64 = 0.5e-3;
+
+/// Smoothed envelope deviation, relative to its mean, above which a signal
+/// counts as amplitude modulated.
+const AM_DEPTH: f64 = 0.15;
+
+/// Smoothed frequency deviation in Hz above which a signal counts as
+/// frequency modulated.
+const FM_SPREAD_HZ: f64 = 400.0;
+
+/// Kurtosis below which a modulating signal counts as data. Keyed signals
+/// sit on two or a few levels, with a kurtosis between 1 and 1.5, while
+/// speech is peaky, well above 2.
+const DATA_KURTOSIS: f64 = 1.8;
+
+/// Smoothing windows needed before a mode is guessed.
+const MIN_WINDOWS: usize = 8;
+
+/// Modulation found by `classify_mode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelMode {
+    /// Amplitude modulated voice, as on airband.
+    Am,
+    /// Frequency modulated voice, as on land mobile and amateur channels.
+    NarrowFm,
+    /// Keyed data: FSK, AFSK on an FM carrier or on-off keying.
+    Data,
+}
+
+/// Formats the mode as a short name, e.g. `NFM`.
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChannelMode::Am => "AM",
+            ChannelMode::NarrowFm => "NFM",
+            ChannelMode::Data => "data",
+        })
+    }
+}
+
+impl ChannelMode {
+    /// Create the demodulator for the mode.
+    ///
+    /// AM is demodulated from the envelope; FM voice and data are
+    /// discriminated with 5 kHz full scale, data decoders taking the
+    /// discriminator output as their input.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The rate of the channel's baseband samples in Hz.
+    /// * `audio_rate` - The output rate in Hz. `sample_rate` should be an
+    ///   integer multiple of it.
+    ///
+    /// # Returns
+    ///
+    /// A `ModeDemod` for the mode.
+    #[cfg(feature = "demod")]
+    pub fn demodulator(self, sample_rate: f64, audio_rate: f64) -> ModeDemod {
+        match self {
+            ChannelMode::Am => ModeDemod::Am(AmDemod::new(sample_rate, audio_rate)),
+            ChannelMode::NarrowFm | ChannelMode::Data => {
+                let factor = (sample_rate / audio_rate).round().max(1.0) as usize;
+                ModeDemod::Fm(
+                    FmDemod::new(sample_rate, 5_000.0),
+                    Decimator::with_low_pass(factor, 8 * factor + 1),
+                )
+            }
+        }
+    }
+}
+
+/// Demodulator picked for a `ChannelMode` by `ChannelMode::demodulator`.
+#[cfg(feature = "demod")]
+pub enum ModeDemod {
+    /// Envelope detection.
+    Am(AmDemod),
+    /// A discriminator followed by decimation to the audio rate.
+    Fm(FmDemod, Decimator<f32>),
+}
+
+#[cfg(feature = "demod")]
+impl ModeDemod {
+    /// Demodulate quadrature samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `iq` - Baseband samples with the carrier at DC.
+    ///
+    /// # Returns
+    ///
+    /// Audio at the audio rate.
+    pub fn process(&mut self, iq: &[Complex<f32>]) -> Vec<f32> {
+        match self {
+            ModeDemod::Am(am) => am.process(iq),
+            ModeDemod::Fm(fm, audio) => {
+                let mut out = Vec::with_capacity(iq.len() / audio.factor() + 1);
+                audio.decimate_into(&fm.process(iq), &mut out);
+                out
+            }
+        }
+    }
+}
+
+/// Guess how an active channel is modulated.
+///
+/// The envelope and instantaneous frequency are smoothed over half a
+/// millisecond. A varying envelope on a steady frequency is AM, a steady
+/// envelope with a varying frequency is FM, and either varying between a
+/// few fixed levels rather than like speech is data. The signal should be
+/// well above the noise, at least 10 dB, and a few tens of milliseconds
+/// long.
+///
+/// # Arguments
+///
+/// * `samples` - The channel at baseband, with the carrier near DC and
+///   little else in the band.
+/// * `sample_rate` - The sample rate in Hz.
+///
+/// # Returns
+///
+/// The mode, or `None` if there were too few samples or the carrier was
+/// not modulated.
+pub fn classify_mode(samples: &[Complex<f32>], sample_rate: f64) -> Option<ChannelMode> {
+    let window = ((sample_rate * SMOOTHING_S).round() as usize).max(1);
+    if samples.len() < window * MIN_WINDOWS + 1 {
+        return None;
+    }
+    let envelope: Vec<f64> = samples.iter().map(|x| x.norm() as f64).collect();
+    let freq: Vec<f64> = samples
+        .windows(2)
+        .map(|w| (w[1] * w[0].conj()).arg() as f64 * sample_rate / TAU)
+        .collect();
+    let envelope = moving_average(&envelope, window);
+    let freq = moving_average(&freq, window);
+
+    let (envelope_mean, envelope_sd, envelope_kurtosis) = moments(&envelope);
+    let (_, freq_sd, freq_kurtosis) = moments(&freq);
+    let depth = envelope_sd / envelope_mean.max(f64::MIN_POSITIVE);
+
+    if freq_sd >= FM_SPREAD_HZ && depth < AM_DEPTH {
+        Some(if freq_kurtosis < DATA_KURTOSIS {
+            ChannelMode::Data
+        } else {
+            ChannelMode::NarrowFm
+        })
+    } else if depth >= AM_DEPTH {
+        Some(if envelope_kurtosis < DATA_KURTOSIS {
+            ChannelMode::Data
+        } else {
+            ChannelMode::Am
+        })
+    } else {
+        None
+    }
+}
+
+fn moving_average(x: &[f64], window: usize) -> Vec<f64> {
+    let mut sum: f64 = x[..window].iter().sum();
+    let mut out = Vec::with_capacity(x.len() - window + 1);
+    out.push(sum / window as f64);
+    for i in window..x.len() {
+        sum += x[i] - x[i - window];
+        out.push(sum / window as f64);
+    }
+    out
+}
+
+/// Mean, standard deviation and kurtosis.
+fn moments(x: &[f64]) -> (f64, f64, f64) {
+    let n = x.len() as f64;
+    let mean = x.iter().sum::<f64>() / n;
+    let (mut m2, mut m4) = (0.0, 0.0);
+    for &v in x {
+        let d = (v - mean) * (v - mean);
+        m2 += d;
+        m4 += d * d;
+    }
+    let (m2, m4) = (m2 / n, m4 / n);
+    let kurtosis = if m2 > 0.0 { m4 / (m2 * m2) } else { 0.0 };
+    (mean, m2.sqrt(), kurtosis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 25_000.0;
+
+    struct Noise(u64);
+
+    impl Noise {
+        fn next(&mut self) -> f64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+        }
+    }
+
+    /// Speech-like modulation: low-passed noise, peaky like a voice.
+    fn voice(len: usize) -> Vec<f64> {
+        let mut noise = Noise(7);
+        let mut level = 0.0;
+        let mut syllable = 0.0;
+        (0..len)
+            .map(|i| {
+                level += 0.1 * (noise.next() - level);
+                if i % 2000 == 0 {
+                    syllable = noise.next().abs() * 2.0;
+                }
+                level * syllable * 6.0
+            })
+            .collect()
+    }
+
+    /// Random bits at 1200 baud, as +-1.
+    fn bits(len: usize) -> Vec<f64> {
+        let mut noise = Noise(11);
+        let mut bit = 1.0;
+        (0..len)
+            .map(|i| {
+                if i % (RATE as usize / 1200) == 0 {
+                    bit = if noise.next() > 0.0 { 1.0 } else { -1.0 };
+                }
+                bit
+            })
+            .collect()
+    }
+
+    fn with_noise(signal: impl Iterator<Item = Complex<f64>>) -> Vec<Complex<f32>> {
+        let mut noise = Noise(3);
+        signal
+            .map(|x| {
+                Complex::new(
+                    (x.re + 0.1 * noise.next()) as f32,
+                    (x.im + 0.1 * noise.next()) as f32,
+                )
+            })
+            .collect()
+    }
+
+    fn am(modulation: &[f64]) -> Vec<Complex<f32>> {
+        with_noise(
+            modulation
+                .iter()
+                .map(|&m| Complex::new(1.0 + 0.8 * m.clamp(-1.0, 1.0), 0.0)),
+        )
+    }
+
+    fn fm(modulation: &[f64], deviation: f64) -> Vec<Complex<f32>> {
+        let mut phase = 0.0;
+        with_noise(modulation.iter().map(|&m| {
+            phase += TAU * deviation * m / RATE;
+            Complex::from_polar(1.0, phase)
+        }))
+    }
+
+    #[test]
+    fn tells_modes_apart() {
+        let len = RATE as usize / 5;
+        assert_eq!(classify_mode(&am(&voice(len)), RATE), Some(ChannelMode::Am));
+        assert_eq!(
+            classify_mode(&fm(&voice(len), 2_500.0), RATE),
+            Some(ChannelMode::NarrowFm)
+        );
+        assert_eq!(
+            classify_mode(&fm(&bits(len), 2_400.0), RATE),
+            Some(ChannelMode::Data)
+        );
+        let ook: Vec<f64> = bits(len).iter().map(|&b| b.max(-0.9)).collect();
+        assert_eq!(classify_mode(&am(&ook), RATE), Some(ChannelMode::Data));
+        assert_eq!(classify_mode(&fm(&vec![0.0; len], 0.0), RATE), None);
+        assert_eq!(classify_mode(&am(&voice(100)), RATE), None);
+    }
+
+    #[cfg(feature = "demod")]
+    #[test]
+    fn picks_a_demodulator() {
+        let mut demod = ChannelMode::NarrowFm.demodulator(RATE, 12_500.0);
+        let tone: Vec<f64> = (0..5000)
+            .map(|i| (TAU * 1000.0 * i as f64 / RATE).sin())
+            .collect();
+        let audio = demod.process(&fm(&tone, 5_000.0));
+        assert_eq!(audio.len(), 2500);
+        let peak = audio[100..].iter().fold(0.0f32, |a, &x| a.max(x.abs()));
+        assert!((0.8..1.2).contains(&peak), "peak {}", peak);
+        assert!(matches!(
+            ChannelMode::Am.demodulator(RATE, 12_500.0),
+            ModeDemod::Am(_)
+        ));
+    }
+}
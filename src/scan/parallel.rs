@@ -0,0 +1,290 @@
+use std::thread;
+use std::time::Duration;
+
+use super::power::{PowerSweep, PowerTable, SweepRow};
+use crate::device_pool::DevicePool;
+use crate::error::{Error, Result};
+use crate::source::SdrSource;
+
+/// `PowerSweep` spread over every source in a `DevicePool`.
+///
+/// The hops of the range are cut into one contiguous segment per source,
+/// and the segments are swept at the same time on their own threads, so a
+/// full sweep takes about as long as one segment: roughly the single-dongle
+/// time divided by the number of dongles. The rows lie on the frequency
+/// grid a single `PowerSweep` would use and are merged into one table. All
+/// sources must run at the same sample rate; with fewer hops than sources,
+/// the extra sources are left idle.
+pub struct ParallelSweep<S> {
+    sweeps: Vec<PowerSweep<S>>,
+    /// Hops in the whole range.
+    hops: usize,
+}
+
+impl<S: SdrSource + Send> ParallelSweep<S> {
+    /// Plan a sweep at the sources' current sample rate.
+    ///
+    /// Defaults to the same cropping, settling and integration as
+    /// `PowerSweep`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The sources to sweep with.
+    /// * `start_hz` - Lower edge of the range in Hz.
+    /// * `stop_hz` - Upper edge of the range in Hz.
+    /// * `bin_hz` - Requested bin width in Hz; the actual width is the
+    ///   sample rate divided by the next power of two.
+    ///
+    /// # Returns
+    ///
+    /// A new `ParallelSweep`, `Error::InvalidParam` if the pool is empty or
+    /// its sources run at different sample rates, or the `Error` of a
+    /// source whose sample rate could not be read.
+    pub fn new(pool: DevicePool<S>, start_hz: u32, stop_hz: u32, bin_hz: f64) -> Result<Self> {
+        let mut rate = None;
+        for device in pool.iter() {
+            let r = device.sample_rate()?;
+            if *rate.get_or_insert(r) != r {
+                return Err(Error::InvalidParam);
+            }
+        }
+        if rate.is_none() {
+            return Err(Error::InvalidParam);
+        }
+        let sweeps = pool
+            .into_iter()
+            .map(|device| PowerSweep::new(device, start_hz, stop_hz, bin_hz))
+            .collect::<Result<Vec<_>>>()?;
+        let mut sweep = ParallelSweep { sweeps, hops: 0 };
+        sweep.plan();
+        Ok(sweep)
+    }
+
+    /// Cut the range into one segment per source, on hop boundaries.
+    fn plan(&mut self) {
+        self.hops = self.sweeps[0].hops();
+        let used = self.devices();
+        for (k, sweep) in self.sweeps.iter_mut().enumerate().take(used) {
+            sweep.segment(k * self.hops / used, (k + 1) * self.hops / used);
+        }
+    }
+
+    /// Set the fraction of each hop's bandwidth that is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `crop` - Fraction discarded, split between both edges, from 0.0 to
+    ///   0.9.
+    ///
+    /// # Returns
+    ///
+    /// The re-planned `ParallelSweep`.
+    pub fn with_crop(mut self, crop: f64) -> Self {
+        self.sweeps = self.sweeps.into_iter().map(|s| s.with_crop(crop)).collect();
+        self.plan();
+        self
+    }
+
+    /// Set how long samples are discarded after each retune.
+    ///
+    /// # Arguments
+    ///
+    /// * `settle` - The settling time.
+    ///
+    /// # Returns
+    ///
+    /// The `ParallelSweep` with the new settling time.
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.sweeps = self
+            .sweeps
+            .into_iter()
+            .map(|s| s.with_settle(settle))
+            .collect();
+        self
+    }
+
+    /// Set how long each hop is integrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `integration` - The integration time per hop.
+    ///
+    /// # Returns
+    ///
+    /// The `ParallelSweep` with the new integration time.
+    pub fn with_integration(mut self, integration: Duration) -> Self {
+        self.sweeps = self
+            .sweeps
+            .into_iter()
+            .map(|s| s.with_integration(integration))
+            .collect();
+        self
+    }
+
+    /// Set the gain of every source and normalise levels to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `gain` - The gain in tenths of a dB, or `None` for automatic gain.
+    ///
+    /// # Returns
+    ///
+    /// The `ParallelSweep` if every source took the gain, otherwise an
+    /// `Error`.
+    pub fn with_gain(mut self, gain: Option<i32>) -> Result<Self> {
+        self.sweeps = self
+            .sweeps
+            .into_iter()
+            .map(|s| s.with_gain(gain))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self)
+    }
+
+    /// Get the number of sources sweeping.
+    ///
+    /// # Returns
+    ///
+    /// The number of segments, at most the number of hops.
+    pub fn devices(&self) -> usize {
+        self.sweeps.len().min(self.hops)
+    }
+
+    /// Get the number of hops in one sweep, over all sources.
+    ///
+    /// # Returns
+    ///
+    /// The number of rows per sweep.
+    pub fn hops(&self) -> usize {
+        self.hops
+    }
+
+    /// Run one full sweep, every source sweeping its segment at once.
+    ///
+    /// # Returns
+    ///
+    /// The rows of every hop in frequency order if successful, otherwise
+    /// the `Error` of the first source that failed.
+    pub fn sweep_rows(&mut self) -> Result<Vec<SweepRow>> {
+        let used = self.devices();
+        let segments = thread::scope(|scope| {
+            let running: Vec<_> = self.sweeps[..used]
+                .iter_mut()
+                .map(|sweep| scope.spawn(move || sweep.sweep_rows()))
+                .collect();
+            running
+                .into_iter()
+                .map(|t| t.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+                .collect::<Vec<_>>()
+        });
+        let mut rows = Vec::with_capacity(self.hops);
+        for segment in segments {
+            rows.extend(segment?);
+        }
+        Ok(rows)
+    }
+
+    /// Run one full sweep, every source sweeping its segment at once.
+    ///
+    /// # Returns
+    ///
+    /// The merged table if successful, otherwise an `Error`.
+    pub fn sweep(&mut self) -> Result<PowerTable> {
+        let rows = self.sweep_rows()?;
+        Ok(PowerTable::from_rows(&rows).expect("a sweep has at least one hop"))
+    }
+
+    /// Give back the sources.
+    ///
+    /// # Returns
+    ///
+    /// The pool the sweep was created with, in the same order.
+    pub fn into_pool(self) -> DevicePool<S> {
+        self.sweeps
+            .into_iter()
+            .map(PowerSweep::into_inner)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockDevice;
+    use crate::scan::testing::{carrier, mock_band, tunes};
+
+    /// A band with a carrier on it.
+    fn band_with(carrier_hz: u32, seed: u64) -> MockDevice {
+        let (band, signals) = mock_band(seed);
+        signals.push(carrier(carrier_hz, 20.0));
+        band
+    }
+
+    fn fast<S: SdrSource>(sweep: PowerSweep<S>) -> PowerSweep<S> {
+        sweep
+            .with_settle(Duration::from_millis(1))
+            .with_integration(Duration::from_millis(4))
+    }
+
+    fn peak(table: &PowerTable) -> f64 {
+        let (bin, _) = table
+            .db
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        table.frequency(bin)
+    }
+
+    #[test]
+    fn merges_segments_onto_the_single_sweep_grid() {
+        let (start, stop, carrier_hz) = (100_000_000, 106_000_000, 104_321_000);
+        let single = fast(PowerSweep::new(band_with(carrier_hz, 1), start, stop, 4_000.0).unwrap())
+            .sweep()
+            .unwrap();
+
+        let pool: DevicePool<MockDevice> =
+            (1..=3).map(|seed| band_with(carrier_hz, seed)).collect();
+        let mut sweep = ParallelSweep::new(pool, start, stop, 4_000.0)
+            .unwrap()
+            .with_settle(Duration::from_millis(1))
+            .with_integration(Duration::from_millis(4));
+        assert_eq!(sweep.devices(), 3);
+        let table = sweep.sweep().unwrap();
+        assert_eq!(table.start_hz, single.start_hz);
+        assert_eq!(table.step_hz, single.step_hz);
+        assert_eq!(table.db.len(), single.db.len());
+        assert_eq!(peak(&table), peak(&single));
+        assert!((peak(&table) - carrier_hz as f64).abs() <= table.step_hz);
+
+        // Each source only visited its own share of the range.
+        let hops = sweep.hops();
+        let bands = sweep.into_pool().into_inner();
+        let tunes: Vec<Vec<u32>> = bands.iter().map(tunes).collect();
+        let counts: Vec<usize> = tunes.iter().map(Vec::len).collect();
+        assert_eq!(counts.iter().sum::<usize>(), hops);
+        assert!(counts.iter().all(|&n| n + 1 >= hops / 3));
+        for pair in tunes.windows(2) {
+            assert!(pair[0].iter().max() < pair[1].iter().min());
+        }
+    }
+
+    #[test]
+    fn leaves_extra_sources_idle() {
+        let pool: DevicePool<MockDevice> = (1..=4).map(|seed| mock_band(seed).0).collect();
+        let mut sweep = ParallelSweep::new(pool, 100_000_000, 101_000_000, 4_000.0)
+            .unwrap()
+            .with_settle(Duration::from_millis(1))
+            .with_integration(Duration::from_millis(4));
+        assert_eq!(sweep.hops(), 2);
+        assert_eq!(sweep.devices(), 2);
+        assert_eq!(sweep.sweep_rows().unwrap().len(), 2);
+        assert!(tunes(sweep.into_pool().get(3).unwrap()).is_empty());
+        assert!(ParallelSweep::new(
+            DevicePool::<MockDevice>::from_devices(Vec::new()),
+            1,
+            2,
+            1.0
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::dsp::fir::Window;
+use crate::dsp::Spectrum;
+use crate::error::{Error, Result};
+use crate::samples::{to_complex_f32, Scaling};
+use crate::source::SdrSource;
+use crate::utils::UtcTime;
+
+/// Bytes read from the source at a time.
+pub(super) const READ_CHUNK: usize = 16 * 1024;
+
+/// One hop of a sweep, in the layout of an `rtl_power` CSV line.
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    /// When the hop finished integrating.
+    pub time: SystemTime,
+    /// Frequency of the first bin in Hz.
+    pub low_hz: f64,
+    /// Frequency just past the last bin in Hz.
+    pub high_hz: f64,
+    /// Bin width in Hz.
+    pub step_hz: f64,
+    /// Number of IQ samples integrated.
+    pub samples: usize,
+    /// Power per bin in dB, see `PowerSweep` for the reference level.
+    pub db: Vec<f32>,
+}
+
+/// Formats the row as an `rtl_power` CSV line, without the newline. The date
+/// and time are in UTC.
+impl fmt::Display for SweepRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let t = UtcTime::from_system_time(self.time);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}, {:02}:{:02}:{:02}, {:.0}, {:.0}, {:.2}, {}",
+            t.year,
+            t.month,
+            t.day,
+            t.hour,
+            t.minute,
+            t.second,
+            self.low_hz,
+            self.high_hz,
+            self.step_hz,
+            self.samples
+        )?;
+        for db in &self.db {
+            write!(f, ", {:.2}", db)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses an `rtl_power` CSV line, as written by this crate or by
+/// `rtl_power` itself, with the date and time in UTC.
+impl FromStr for SweepRow {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let invalid = || Error::InvalidMetadata(format!("not an rtl_power line: {:?}", line));
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() < 7 {
+            return Err(invalid());
+        }
+        let parts = |field: &str, sep: char| -> Option<Vec<u32>> {
+            field.split(sep).map(|p| p.parse().ok()).collect()
+        };
+        let (date, time) = (
+            parts(fields[0], '-').ok_or_else(invalid)?,
+            parts(fields[1], ':').ok_or_else(invalid)?,
+        );
+        if date.len() != 3 || time.len() != 3 {
+            return Err(invalid());
+        }
+        let number = |field: &str| field.parse::<f64>().map_err(|_| invalid());
+        Ok(SweepRow {
+            time: UtcTime {
+                year: date[0] as i64,
+                month: date[1],
+                day: date[2],
+                hour: time[0],
+                minute: time[1],
+                second: time[2],
+                nanos: 0,
+            }
+            .to_system_time(),
+            low_hz: number(fields[2])?,
+            high_hz: number(fields[3])?,
+            step_hz: number(fields[4])?,
+            samples: fields[5].parse().map_err(|_| invalid())?,
+            db: fields[6..]
+                .iter()
+                .map(|f| f.parse::<f32>().map_err(|_| invalid()))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// Power over a whole sweep, stitched from its rows.
+#[derive(Clone, Debug)]
+pub struct PowerTable {
+    /// Frequency of the first bin in Hz.
+    pub start_hz: f64,
+    /// Bin width in Hz.
+    pub step_hz: f64,
+    /// Power per bin in dB.
+    pub db: Vec<f32>,
+}
+
+impl PowerTable {
+    /// Stitch the rows of one sweep into a single table.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The rows of a sweep, in any order.
+    ///
+    /// # Returns
+    ///
+    /// The table, or `None` if `rows` is empty.
+    pub fn from_rows(rows: &[SweepRow]) -> Option<Self> {
+        let mut rows: Vec<&SweepRow> = rows.iter().collect();
+        rows.sort_by(|a, b| a.low_hz.total_cmp(&b.low_hz));
+        let first = rows.first()?;
+        Some(PowerTable {
+            start_hz: first.low_hz,
+            step_hz: first.step_hz,
+            db: rows.iter().flat_map(|r| r.db.iter().copied()).collect(),
+        })
+    }
+
+    /// Get the frequency of a bin.
+    ///
+    /// # Arguments
+    ///
+    /// * `bin` - The bin index.
+    ///
+    /// # Returns
+    ///
+    /// The bin's centre frequency in Hz.
+    pub fn frequency(&self, bin: usize) -> f64 {
+        self.start_hz + (bin as f64 + 0.5) * self.step_hz
+    }
+}
+
+/// `rtl_power`-style sweep across a frequency range wider than the sample
+/// rate.
+///
+/// The range is cut into hops that each use the centre of the passband,
+/// discarding a fraction at the edges where the dongle's anti-aliasing filter
+/// rolls off. At every hop the source is retuned, samples are discarded for a
+/// settling period, and a Welch spectrum is integrated and cropped to the
+/// hop's share of the range. Hop widths are a whole, even number of bins, so
+/// all rows lie on one frequency grid and stitch without gaps or overlap.
+///
+/// Levels are dBFS. With a fixed gain set through `with_gain` the gain is
+/// subtracted, so sweeps taken at different gains are comparable; with
+/// automatic gain they are not.
+///
+/// The sweep is an endless iterator over rows, starting again at the bottom
+/// of the range after the last hop.
+pub struct PowerSweep<S> {
+    source: S,
+    start_hz: f64,
+    stop_hz: f64,
+    rate: f64,
+    bins_per_hop: usize,
+    /// The first hop swept, when restricted to a segment of the range.
+    first_hop: usize,
+    hops: usize,
+    next_hop: usize,
+    spectrum: Spectrum,
+    settle: Duration,
+    integration: Duration,
+    gain_db: Option<f64>,
+    buf: Vec<u8>,
+}
+
+impl<S: SdrSource> PowerSweep<S> {
+    /// Plan a sweep at the source's current sample rate.
+    ///
+    /// Defaults to cropping 25% of each hop, 50 ms of settling and 100 ms of
+    /// integration per hop.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to sweep with.
+    /// * `start_hz` - Lower edge of the range in Hz.
+    /// * `stop_hz` - Upper edge of the range in Hz.
+    /// * `bin_hz` - Requested bin width in Hz; the actual width is the
+    ///   sample rate divided by the next power of two.
+    ///
+    /// # Returns
+    ///
+    /// A new `PowerSweep` if the sample rate could be read, otherwise an
+    /// `Error`.
+    pub fn new(source: S, start_hz: u32, stop_hz: u32, bin_hz: f64) -> Result<Self> {
+        let rate = source.sample_rate()? as f64;
+        let fft_len = ((rate / bin_hz).ceil() as usize).next_power_of_two().max(2);
+        let mut sweep = PowerSweep {
+            source,
+            start_hz: start_hz.min(stop_hz) as f64,
+            stop_hz: start_hz.max(stop_hz) as f64,
+            rate,
+            bins_per_hop: 0,
+            first_hop: 0,
+            hops: 0,
+            next_hop: 0,
+            spectrum: Spectrum::new(fft_len, Window::Hann),
+            settle: Duration::from_millis(50),
+            integration: Duration::from_millis(100),
+            gain_db: None,
+            buf: vec![0; READ_CHUNK],
+        };
+        sweep.plan(0.25);
+        Ok(sweep)
+    }
+
+    fn plan(&mut self, crop: f64) {
+        let fft_len = self.spectrum.len();
+        let usable = ((fft_len as f64 * (1.0 - crop.clamp(0.0, 0.9))) as usize / 2 * 2).max(2);
+        self.bins_per_hop = usable;
+        let hop_hz = usable as f64 * self.step_hz();
+        self.hops = (((self.stop_hz - self.start_hz) / hop_hz).ceil() as usize).max(1);
+        self.first_hop = 0;
+        self.next_hop = 0;
+    }
+
+    /// Restrict the sweep to the hops `first..last` of its range, keeping
+    /// the frequency grid of the whole range.
+    pub(super) fn segment(&mut self, first: usize, last: usize) {
+        self.first_hop = first;
+        self.hops = last.saturating_sub(first).max(1);
+        self.next_hop = 0;
+    }
+
+    /// Set the fraction of each hop's bandwidth that is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `crop` - Fraction discarded, split between both edges, from 0.0 to
+    ///   0.9. More cropping gives flatter results but needs more hops.
+    ///
+    /// # Returns
+    ///
+    /// The re-planned `PowerSweep`.
+    pub fn with_crop(mut self, crop: f64) -> Self {
+        self.plan(crop);
+        self
+    }
+
+    /// Set how long samples are discarded after each retune.
+    ///
+    /// # Arguments
+    ///
+    /// * `settle` - The settling time.
+    ///
+    /// # Returns
+    ///
+    /// The `PowerSweep` with the new settling time.
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// Set how long each hop is integrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `integration` - The integration time per hop.
+    ///
+    /// # Returns
+    ///
+    /// The `PowerSweep` with the new integration time.
+    pub fn with_integration(mut self, integration: Duration) -> Self {
+        self.integration = integration;
+        self
+    }
+
+    /// Set the gain used for the sweep and normalise levels to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `gain` - The gain in tenths of a dB, or `None` for automatic gain.
+    ///
+    /// # Returns
+    ///
+    /// The `PowerSweep` if the gain was set, otherwise an `Error`.
+    pub fn with_gain(mut self, gain: Option<i32>) -> Result<Self> {
+        self.source.set_gain(gain)?;
+        self.gain_db = gain.map(|g| g as f64 / 10.0);
+        Ok(self)
+    }
+
+    /// Get the bin width.
+    ///
+    /// # Returns
+    ///
+    /// The width of every bin in Hz.
+    pub fn step_hz(&self) -> f64 {
+        self.rate / self.spectrum.len() as f64
+    }
+
+    /// Get the number of hops in one sweep.
+    ///
+    /// # Returns
+    ///
+    /// The number of rows per sweep.
+    pub fn hops(&self) -> usize {
+        self.hops
+    }
+
+    /// Get how long the source is busy with one hop.
+    pub(super) fn hop_duration(&self) -> Duration {
+        self.settle + self.integration
+    }
+
+    /// Get the source, for reading between hops.
+    pub(super) fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Measure the next hop.
+    ///
+    /// # Returns
+    ///
+    /// The row for the hop if successful, otherwise an `Error`.
+    pub fn next_row(&mut self) -> Result<SweepRow> {
+        let hop = self.first_hop + self.next_hop;
+        self.next_hop = (self.next_hop + 1) % self.hops;
+
+        let step = self.step_hz();
+        let low = self.start_hz + (hop * self.bins_per_hop) as f64 * step;
+        let centre = low + (self.bins_per_hop / 2) as f64 * step;
+        let samples = integrate(
+            &mut self.source,
+            centre.round() as u32,
+            bytes_for(self.rate, self.settle),
+            bytes_for(self.rate, self.integration),
+            &mut self.spectrum,
+            &mut self.buf,
+        )?;
+        let spectrum = self.spectrum.take_dbfs().unwrap_or_default();
+
+        // Bins of this hop, centred on the tuned frequency, clipped to the
+        // requested range.
+        let first = spectrum.len() / 2 - self.bins_per_hop / 2;
+        let in_range = (((self.stop_hz - low) / step).ceil() as usize).min(self.bins_per_hop);
+        let offset = self.gain_db.unwrap_or(0.0) as f32;
+        let db = spectrum[first..first + in_range]
+            .iter()
+            .map(|db| db - offset)
+            .collect();
+
+        Ok(SweepRow {
+            time: SystemTime::now(),
+            low_hz: low,
+            high_hz: low + in_range as f64 * step,
+            step_hz: step,
+            samples,
+            db,
+        })
+    }
+
+    /// Run one full sweep from the bottom of the range.
+    ///
+    /// # Returns
+    ///
+    /// The stitched table if successful, otherwise an `Error`.
+    pub fn sweep(&mut self) -> Result<PowerTable> {
+        let rows = self.sweep_rows()?;
+        Ok(PowerTable::from_rows(&rows).expect("a sweep has at least one hop"))
+    }
+
+    /// Run one full sweep from the bottom of the range.
+    ///
+    /// # Returns
+    ///
+    /// The rows of every hop if successful, otherwise an `Error`.
+    pub fn sweep_rows(&mut self) -> Result<Vec<SweepRow>> {
+        self.next_hop = 0;
+        (0..self.hops).map(|_| self.next_row()).collect()
+    }
+
+    /// Give back the source.
+    ///
+    /// # Returns
+    ///
+    /// The source the sweep was created with.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: SdrSource> Iterator for PowerSweep<S> {
+    type Item = Result<SweepRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_row())
+    }
+}
+
+/// Number of bytes covering `time` at `rate`, rounded up to a multiple of 512
+/// as librtlsdr requires.
+pub(super) fn bytes_for(rate: f64, time: Duration) -> usize {
+    let bytes = (time.as_secs_f64() * rate * 2.0).ceil() as usize;
+    bytes.div_ceil(512) * 512
+}
+
+/// Tune `source`, discard `settle` bytes and integrate at least `integration`
+/// bytes into a cleared `spectrum`.
+///
+/// Returns the number of IQ samples integrated.
+pub(super) fn integrate<S: SdrSource>(
+    source: &mut S,
+    centre_hz: u32,
+    mut settle: usize,
+    integration: usize,
+    spectrum: &mut Spectrum,
+    buf: &mut [u8],
+) -> Result<usize> {
+    source.tune(centre_hz)?;
+    while settle > 0 {
+        let len = settle.min(buf.len());
+        source.read_exact(&mut buf[..len])?;
+        settle -= len;
+    }
+
+    spectrum.clear();
+    let mut remaining = integration.max(2 * spectrum.len());
+    let mut samples = 0;
+    while remaining > 0 {
+        let len = remaining.min(buf.len());
+        source.read_exact(&mut buf[..len])?;
+        spectrum.push(&to_complex_f32(&buf[..len], Scaling::Normalized));
+        samples += len / 2;
+        remaining -= len;
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn parses_rtl_power_lines() {
+        let row = SweepRow {
+            time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            low_hz: 88_000_000.0,
+            high_hz: 88_004_000.0,
+            step_hz: 1_000.0,
+            samples: 2048,
+            db: vec![-40.5, -41.25, -12.0, -39.75],
+        };
+        let line = row.to_string();
+        assert_eq!(
+            line,
+            "2023-11-14, 22:13:20, 88000000, 88004000, 1000.00, 2048, -40.50, -41.25, -12.00, -39.75"
+        );
+        let parsed: SweepRow = line.parse().unwrap();
+        assert_eq!(parsed.time, row.time);
+        assert_eq!(parsed.low_hz, row.low_hz);
+        assert_eq!(parsed.step_hz, row.step_hz);
+        assert_eq!(parsed.samples, row.samples);
+        assert_eq!(parsed.db, row.db);
+        // rtl_power leaves the date unpadded and may end with a space.
+        let parsed: SweepRow = "2024-3-1, 0:00:05, 1, 3, 1, 10, -1, -2 ".parse().unwrap();
+        assert_eq!(
+            parsed.time,
+            UtcTime {
+                year: 2024,
+                month: 3,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 5,
+                nanos: 0
+            }
+            .to_system_time()
+        );
+        assert_eq!(parsed.db, [-1.0, -2.0]);
+        assert!("2024-03-01, 00:00:05, 1, 3".parse::<SweepRow>().is_err());
+        assert!("yesterday, 00:00:05, 1, 3, 1, 10, -1"
+            .parse::<SweepRow>()
+            .is_err());
+    }
+}
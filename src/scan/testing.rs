@@ -0,0 +1,55 @@
+//! A simulated band for the scanners' tests, on `MockDevice`.
+
+use crate::mock::{Call, MockDevice, MockSignal, MockSignals};
+
+/// The sample rate of `mock_band`.
+pub const RATE: u32 = 1_024_000;
+
+/// Create a band with a noise floor, on which carriers can be switched.
+///
+/// # Arguments
+///
+/// * `seed` - The seed of the noise.
+///
+/// # Returns
+///
+/// The source and a handle to add carriers to it.
+pub fn mock_band(seed: u64) -> (MockDevice, MockSignals) {
+    let band = MockDevice::new().with_signal(MockSignal::Noise {
+        amplitude: 4.0 / 127.5,
+        seed,
+    });
+    band.set_sample_rate(RATE).unwrap();
+    band.clear_calls();
+    let signals = band.signals();
+    (band, signals)
+}
+
+/// Create a carrier.
+///
+/// # Arguments
+///
+/// * `freq_hz` - Its frequency.
+/// * `amplitude` - Its amplitude in cu8 steps, against noise of 4.
+pub fn carrier(freq_hz: u32, amplitude: f64) -> MockSignal {
+    MockSignal::Carrier {
+        freq_hz,
+        amplitude: amplitude / 127.5,
+    }
+}
+
+/// Switch off every carrier, leaving the noise.
+pub fn clear_carriers(signals: &MockSignals) {
+    signals.retain(|s| matches!(s, MockSignal::Noise { .. }));
+}
+
+/// Get where a band was tuned, in order.
+pub fn tunes(band: &MockDevice) -> Vec<u32> {
+    band.calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            Call::SetCenterFreq(freq_hz) => Some(freq_hz),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,133 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::stream::StreamHandle;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::Duration;
+
+/// How often `run_until_ctrlc` checks whether the capture ended by itself.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Something running in the background that can be shut down cleanly.
+///
+/// Implementors cancel their reads, flush their sinks and finalize any files
+/// in `stop`, so that interrupting a capture never leaves a truncated
+/// recording behind.
+pub trait Capture {
+    /// What is handed back once the capture has stopped.
+    type Output;
+
+    /// Check whether the capture is still running.
+    ///
+    /// # Returns
+    ///
+    /// `false` once it has ended on its own, e.g. after an error.
+    fn is_running(&self) -> bool;
+
+    /// Stop the capture and release its resources.
+    ///
+    /// # Returns
+    ///
+    /// The output of the capture if successful, otherwise an `Error`.
+    fn stop(self) -> Result<Self::Output>;
+}
+
+impl Capture for StreamHandle {
+    type Output = Device;
+
+    fn is_running(&self) -> bool {
+        StreamHandle::is_running(self)
+    }
+
+    fn stop(self) -> Result<Device> {
+        StreamHandle::stop(self)
+    }
+}
+
+struct Signal {
+    /// Why the handler could not be installed, if it failed.
+    error: Option<String>,
+    raised: AtomicBool,
+    lock: Mutex<()>,
+    wake: Condvar,
+}
+
+static SIGNAL: OnceLock<Signal> = OnceLock::new();
+
+/// Install the process-wide handler on first use.
+fn signal() -> Result<&'static Signal> {
+    let signal = SIGNAL.get_or_init(|| {
+        let installed = ctrlc::set_handler(|| {
+            if let Some(signal) = SIGNAL.get() {
+                signal.raised.store(true, Ordering::Release);
+                let _guard = signal.lock.lock();
+                signal.wake.notify_all();
+            }
+        });
+        Signal {
+            error: installed.err().map(|e| e.to_string()),
+            raised: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            wake: Condvar::new(),
+        }
+    });
+    match &signal.error {
+        Some(e) => Err(Error::System(std::io::Error::other(e.clone()))),
+        None => Ok(signal),
+    }
+}
+
+/// Check whether Ctrl-C (SIGINT) or SIGTERM has been received.
+///
+/// The first call installs the signal handler, so call it once before
+/// starting work, then use it as the condition of a read loop:
+///
+/// ```no_run
+/// # fn main() -> radion::Result<()> {
+/// let device = radion::Device::new(0)?;
+/// radion::stop_requested()?;
+/// for chunk in device.samples(16 * 1024) {
+///     if radion::stop_requested()? {
+///         break;
+///     }
+///     let _ = chunk?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Returns
+///
+/// `true` once a signal has arrived, or an `Error` if the handler could not
+/// be installed, e.g. because another one was installed by other code.
+pub fn stop_requested() -> Result<bool> {
+    Ok(signal()?.raised.load(Ordering::Acquire))
+}
+
+/// Run a capture until Ctrl-C or SIGTERM, then stop it cleanly.
+///
+/// Returns early if the capture ends on its own. Either way `Capture::stop`
+/// is called, which cancels the read and flushes and finalizes any sinks.
+///
+/// # Arguments
+///
+/// * `capture` - The running capture, e.g. the `StreamHandle` returned by
+///   `Device::start_async`.
+///
+/// # Returns
+///
+/// The output of `Capture::stop`, e.g. the device back from a
+/// `StreamHandle`.
+pub fn run_until_ctrlc<C: Capture>(capture: C) -> Result<C::Output> {
+    let signal = signal()?;
+    let mut guard = signal.lock.lock().unwrap_or_else(|e| e.into_inner());
+    while !signal.raised.load(Ordering::Acquire) && capture.is_running() {
+        guard = signal
+            .wake
+            .wait_timeout(guard, POLL_INTERVAL)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    drop(guard);
+    capture.stop()
+}
@@ -0,0 +1,444 @@
+//! Captures that run for weeks with a fixed memory footprint.
+//!
+//! A `Monitor` reads a source into buffers that are all allocated before
+//! the capture starts: one scratch buffer for the read and a `BufferPool`
+//! handing the data to the consumer. Once it has warmed up, nothing on the
+//! capture thread should allocate again. Installing `CountingAlloc` as the
+//! global allocator lets it check this: debug builds panic on the first
+//! allocation in the steady state, and release builds count them in the
+//! `MemoryReport` the monitor hands out at a fixed interval, next to the
+//! process's resident memory, the live heap and the pool's usage.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use radion::soak::{CountingAlloc, Monitor};
+//! use radion::{BufferPool, Device};
+//!
+//! #[global_allocator]
+//! static ALLOC: CountingAlloc = CountingAlloc;
+//!
+//! let device = Device::new(0)?;
+//! device.reset_buffer()?;
+//! let mut peak = 0u8;
+//! Monitor::new(device, BufferPool::new(8, 262_144))
+//!     .with_report(Duration::from_secs(3600), |report| eprintln!("{}", report))
+//!     .run(|buf| {
+//!         peak = buf.iter().fold(peak, |a, &b| a.max(b));
+//!         Ok(true)
+//!     })?;
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::pool::{BufferPool, PoolStats, PooledBuffer};
+use crate::source::SdrSource;
+
+/// Reads a `Monitor` makes before it expects no more allocations, when not
+/// set with `with_warmup`.
+pub const DEFAULT_WARMUP: u64 = 16;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Global allocator counting allocations and live heap bytes.
+///
+/// Forwards to the system allocator. Install it in the application with
+/// `#[global_allocator]` to enable `heap_stats`, `thread_allocations` and
+/// the steady-state checks of `SteadyState` and `Monitor`; without it they
+/// report nothing.
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn allocated(size: usize) {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            INSTALLED.store(true, Ordering::Relaxed);
+        }
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+        // The counter has no destructor, so it is there until the thread
+        // is gone.
+        let _ = THREAD_ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    }
+
+    fn freed(size: usize) {
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            CountingAlloc::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            CountingAlloc::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CountingAlloc::freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            CountingAlloc::freed(layout.size());
+            CountingAlloc::allocated(new_size);
+        }
+        new
+    }
+}
+
+/// Process-wide heap counters kept by `CountingAlloc`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Allocations so far, counting each reallocation as one.
+    pub allocations: u64,
+    /// Deallocations so far.
+    pub deallocations: u64,
+    /// Bytes currently allocated.
+    pub live_bytes: usize,
+    /// Most bytes ever allocated at once.
+    pub peak_bytes: usize,
+}
+
+/// Get the heap counters of the process.
+///
+/// # Returns
+///
+/// The counters, or `None` if `CountingAlloc` is not the global allocator.
+pub fn heap_stats() -> Option<HeapStats> {
+    INSTALLED.load(Ordering::Relaxed).then(|| HeapStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+    })
+}
+
+/// Get the number of allocations made by the calling thread.
+///
+/// # Returns
+///
+/// The count, or `None` if `CountingAlloc` is not the global allocator.
+pub fn thread_allocations() -> Option<u64> {
+    INSTALLED
+        .load(Ordering::Relaxed)
+        .then(|| THREAD_ALLOCATIONS.with(Cell::get))
+}
+
+/// Get the resident memory of the process.
+///
+/// # Returns
+///
+/// The resident set size in bytes, or `None` where it cannot be read. Only
+/// Linux reports it.
+pub fn resident_bytes() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+        // SAFETY: sysconf has no preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        Some(pages * u64::try_from(page).ok()?)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Check that a thread stops allocating.
+///
+/// Created once a loop has allocated everything it needs; every `check`
+/// afterwards expects the calling thread to have made no allocation since
+/// the last one. Debug builds panic when it has, release builds only count
+/// it in `violations`. Allocations the loop is allowed, such as building a
+/// report, are forgiven with `rearm`. Does nothing unless `CountingAlloc`
+/// is the global allocator.
+pub struct SteadyState {
+    baseline: u64,
+    violations: u64,
+}
+
+impl SteadyState {
+    /// Start expecting no allocations on the calling thread.
+    ///
+    /// # Returns
+    ///
+    /// A new `SteadyState`.
+    pub fn enter() -> Self {
+        SteadyState {
+            baseline: thread_allocations().unwrap_or(0),
+            violations: 0,
+        }
+    }
+
+    /// Check that the calling thread has not allocated since the last check.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, if it has.
+    pub fn check(&mut self) {
+        let Some(now) = thread_allocations() else {
+            return;
+        };
+        let new = now.saturating_sub(self.baseline);
+        self.baseline = now;
+        self.violations += new;
+        debug_assert!(new == 0, "{} allocations in the steady state", new);
+    }
+
+    /// Forgive the allocations made since the last check.
+    pub fn rearm(&mut self) {
+        self.baseline = thread_allocations().unwrap_or(0);
+    }
+
+    /// Get the number of allocations found by `check` so far.
+    pub fn violations(&self) -> u64 {
+        self.violations
+    }
+}
+
+/// Memory use of a long-running capture, created by `MemoryReport::now` or
+/// handed out by `Monitor`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// How long the capture has been running.
+    pub uptime: Duration,
+    /// Resident memory of the process in bytes, if the platform reports it.
+    pub resident_bytes: Option<u64>,
+    /// Heap counters, if `CountingAlloc` is the global allocator.
+    pub heap: Option<HeapStats>,
+    /// Usage of the capture's buffer pool.
+    pub pool: Option<PoolStats>,
+    /// Allocations found on the capture thread in the steady state.
+    pub steady_allocations: u64,
+}
+
+impl MemoryReport {
+    /// Take a report of the process as it is now.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - The buffer pool to include, if any.
+    ///
+    /// # Returns
+    ///
+    /// A new `MemoryReport` with no uptime or steady-state allocations.
+    pub fn now(pool: Option<&BufferPool>) -> Self {
+        MemoryReport {
+            uptime: Duration::ZERO,
+            resident_bytes: resident_bytes(),
+            heap: heap_stats(),
+            pool: pool.map(BufferPool::stats),
+            steady_allocations: 0,
+        }
+    }
+}
+
+fn mib(bytes: f64) -> f64 {
+    bytes / (1024.0 * 1024.0)
+}
+
+/// Formats the report on one line, e.g. `up 3600 s, rss 14.2 MiB, heap 6.1
+/// MiB (peak 6.3 MiB), pool 1/8 buffers (peak 3, 0 starved), 0 steady-state
+/// allocations`.
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "up {} s", self.uptime.as_secs())?;
+        if let Some(rss) = self.resident_bytes {
+            write!(f, ", rss {:.1} MiB", mib(rss as f64))?;
+        }
+        if let Some(heap) = &self.heap {
+            write!(
+                f,
+                ", heap {:.1} MiB (peak {:.1} MiB)",
+                mib(heap.live_bytes as f64),
+                mib(heap.peak_bytes as f64)
+            )?;
+        }
+        if let Some(pool) = &self.pool {
+            write!(
+                f,
+                ", pool {}/{} buffers (peak {}, {} starved)",
+                pool.in_use, pool.capacity, pool.peak_in_use, pool.starved
+            )?;
+        }
+        write!(f, ", {} steady-state allocations", self.steady_allocations)
+    }
+}
+
+type Reporter<'a> = Box<dyn FnMut(&MemoryReport) + 'a>;
+
+/// Capture loop that allocates nothing once it is running.
+///
+/// Reads the source into a preallocated buffer and hands each read to the
+/// consumer as a `PooledBuffer`. Reads arriving while the consumer still
+/// holds every buffer of the pool are dropped and counted as starvation.
+/// After the warm-up reads the loop checks with a `SteadyState` that
+/// neither it nor the consumer allocates.
+pub struct Monitor<'a, S> {
+    source: S,
+    pool: BufferPool,
+    scratch: Box<[u8]>,
+    warmup: u64,
+    report: Option<(Duration, Reporter<'a>)>,
+}
+
+impl<'a, S: SdrSource> Monitor<'a, S> {
+    /// Set up a capture.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to read until it is exhausted.
+    /// * `pool` - The buffers to hand out; each read is `buf_len` bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new `Monitor` with its read buffer allocated.
+    pub fn new(source: S, pool: BufferPool) -> Self {
+        let scratch = vec![0; pool.buf_len()].into_boxed_slice();
+        Monitor {
+            source,
+            pool,
+            scratch,
+            warmup: DEFAULT_WARMUP,
+            report: None,
+        }
+    }
+
+    /// Set how many reads may allocate before the steady state.
+    ///
+    /// # Arguments
+    ///
+    /// * `reads` - The number of warm-up reads. Consumers that size their
+    ///   own buffers on first use need at least one.
+    ///
+    /// # Returns
+    ///
+    /// The `Monitor` with the new warm-up.
+    pub fn with_warmup(mut self, reads: u64) -> Self {
+        self.warmup = reads;
+        self
+    }
+
+    /// Report memory use at a fixed interval.
+    ///
+    /// The report may allocate; that is not counted against the steady
+    /// state.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The time between reports.
+    /// * `report` - The closure to call with each report.
+    ///
+    /// # Returns
+    ///
+    /// The `Monitor` with reporting enabled.
+    pub fn with_report<F>(mut self, interval: Duration, report: F) -> Self
+    where
+        F: FnMut(&MemoryReport) + 'a,
+    {
+        self.report = Some((interval, Box::new(report)));
+        self
+    }
+
+    /// Get the pool the data is handed out in.
+    pub fn pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// Run the capture.
+    ///
+    /// # Arguments
+    ///
+    /// * `consumer` - The closure to call with every read. It returns
+    ///   whether to go on.
+    ///
+    /// # Returns
+    ///
+    /// A last `MemoryReport` once the source is exhausted or the consumer
+    /// stopped, otherwise the first `Error` of the source or the consumer.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds with `CountingAlloc` installed, if the capture thread
+    /// allocates in the steady state.
+    pub fn run<F>(mut self, mut consumer: F) -> Result<MemoryReport>
+    where
+        F: FnMut(PooledBuffer) -> Result<bool>,
+    {
+        let start = Instant::now();
+        let mut next_report = self.report.as_ref().map(|(interval, _)| start + *interval);
+        let mut steady: Option<SteadyState> = None;
+        let mut reads = 0u64;
+        loop {
+            let n = self.source.read(&mut self.scratch)?;
+            if n == 0 {
+                break;
+            }
+            let more = match self.pool.copy_from(&self.scratch[..n]) {
+                Some(buf) => consumer(buf)?,
+                None => true,
+            };
+            reads += 1;
+            match steady.as_mut() {
+                Some(steady) => steady.check(),
+                None if reads >= self.warmup => steady = Some(SteadyState::enter()),
+                None => {}
+            }
+            if let (Some(due), Some((interval, report))) = (next_report.as_mut(), &mut self.report)
+            {
+                let now = Instant::now();
+                if now >= *due {
+                    report(&report_of(&self.pool, &steady, now - start));
+                    *due = now + *interval;
+                    if let Some(steady) = steady.as_mut() {
+                        steady.rearm();
+                    }
+                }
+            }
+            if !more {
+                break;
+            }
+        }
+        Ok(report_of(&self.pool, &steady, start.elapsed()))
+    }
+
+    /// Give back the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+fn report_of(pool: &BufferPool, steady: &Option<SteadyState>, uptime: Duration) -> MemoryReport {
+    MemoryReport {
+        uptime,
+        steady_allocations: steady.as_ref().map_or(0, SteadyState::violations),
+        ..MemoryReport::now(Some(pool))
+    }
+}
@@ -0,0 +1,141 @@
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::tuner::SamplingMode;
+
+/// A source of interleaved 8-bit IQ samples that can be tuned.
+///
+/// Implemented by `Device` for live hardware, so processing code written
+/// against this trait also runs on recordings or other sources. Samples are
+/// always delivered in the dongle's native format: unsigned 8-bit I/Q pairs
+/// centered on 127.5.
+pub trait SdrSource {
+    /// Set the center frequency in Hz.
+    fn tune(&mut self, freq_hz: u32) -> Result<()>;
+
+    /// Get the center frequency in Hz.
+    fn center_freq(&self) -> Result<u32>;
+
+    /// Set the sample rate in Hz.
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()>;
+
+    /// Get the sample rate in Hz.
+    fn sample_rate(&self) -> Result<u32>;
+
+    /// Set the gain in tenths of a dB, or `None` for automatic gain.
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()>;
+
+    /// Get the direct sampling mode. Sources without one, such as
+    /// recordings, report `SamplingMode::None`.
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        Ok(SamplingMode::None)
+    }
+
+    /// Read samples into `buf`.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes read, 0 once the source is exhausted.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Fill `buf` completely.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if `buf` was filled, `Error::PartialRead` if the source
+    /// ran out first, otherwise an `Error`.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut read = 0;
+        while read < buf.len() {
+            let n = self.read(&mut buf[read..])?;
+            if n == 0 {
+                return Err(Error::PartialRead {
+                    read,
+                    requested: buf.len(),
+                });
+            }
+            read += n;
+        }
+        Ok(())
+    }
+
+    /// Iterate over the source in chunks of `chunk_len` bytes.
+    ///
+    /// The iterator ends once the source is exhausted or after yielding the
+    /// first error.
+    fn chunks(&mut self, chunk_len: usize) -> Chunks<'_, Self>
+    where
+        Self: Sized,
+    {
+        Chunks {
+            source: self,
+            chunk_len,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over chunks of an `SdrSource`, created by `SdrSource::chunks`.
+pub struct Chunks<'a, S> {
+    source: &'a mut S,
+    chunk_len: usize,
+    done: bool,
+}
+
+impl<S: SdrSource> Iterator for Chunks<'_, S> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut buf = vec![0u8; self.chunk_len];
+        match self.source.read(&mut buf) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(n) => {
+                buf.truncate(n);
+                Some(Ok(buf))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl SdrSource for Device {
+    fn tune(&mut self, freq_hz: u32) -> Result<()> {
+        self.set_center_freq(freq_hz)
+    }
+
+    fn center_freq(&self) -> Result<u32> {
+        self.get_center_freq()
+    }
+
+    fn set_sample_rate(&mut self, rate_hz: u32) -> Result<()> {
+        Device::set_sample_rate(self, rate_hz)
+    }
+
+    fn sample_rate(&self) -> Result<u32> {
+        self.get_sample_rate()
+    }
+
+    fn set_gain(&mut self, gain: Option<i32>) -> Result<()> {
+        self.set_tuner_gain_mode(gain.is_some())?;
+        match gain {
+            Some(gain) => self.set_tuner_gain(gain),
+            None => Ok(()),
+        }
+    }
+
+    fn sampling_mode(&self) -> Result<SamplingMode> {
+        self.get_direct_sampling()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.read_sync_into(buf)
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+
+/// The standard CTCSS tones in Hz.
+pub const CTCSS_TONES: [f32; 51] = [
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5, 94.8, 97.4, 100.0, 103.5, 107.2,
+    110.9, 114.8, 118.8, 123.0, 127.3, 131.8, 136.5, 141.3, 146.2, 150.0, 151.4, 156.7, 159.8,
+    162.2, 165.5, 167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5,
+    203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+];
+
+/// How far a measured tone may be from a standard one and still be taken
+/// for it, as a fraction of the tone.
+const TOLERANCE: f32 = 0.015;
+
+/// The range searched for a tone in Hz.
+const SEARCH: (f64, f64) = (60.0, 260.0);
+
+/// The search step in Hz, refined by interpolation.
+const STEP: f64 = 0.5;
+
+/// A tone measured over one window.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Measurement {
+    /// The nominal tone: the standard tone it is nearest if close enough,
+    /// otherwise the measurement rounded to 0.1 Hz.
+    pub(crate) nominal: f32,
+    /// The measured frequency in Hz.
+    pub(crate) frequency: f32,
+    /// The tone's share of the sub-audio power, from 0 to 1.
+    pub(crate) share: f32,
+}
+
+/// Finds the strongest sub-audio tone in a sliding window.
+pub(crate) struct CtcssDetector {
+    rate: f64,
+    hop: usize,
+    since: usize,
+    window: Vec<f32>,
+    samples: VecDeque<f32>,
+}
+
+impl CtcssDetector {
+    /// Measure over `window` samples every `hop` samples at `rate`.
+    pub(crate) fn new(rate: f64, window: usize, hop: usize) -> Self {
+        CtcssDetector {
+            rate,
+            hop,
+            since: 0,
+            window: (0..window)
+                .map(|n| (0.5 - 0.5 * (TAU * n as f64 / window as f64).cos()) as f32)
+                .collect(),
+            samples: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Add a sample, measuring the window once every hop.
+    pub(crate) fn push(&mut self, sample: f32) -> Option<Measurement> {
+        if self.samples.len() == self.window.len() {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        self.since += 1;
+        if self.samples.len() < self.window.len() || self.since < self.hop {
+            return None;
+        }
+        self.since = 0;
+        self.measure()
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.samples.clear();
+        self.since = 0;
+    }
+
+    fn measure(&self) -> Option<Measurement> {
+        let mean = self.samples.iter().sum::<f32>() / self.samples.len() as f32;
+        let x: Vec<f32> = self
+            .samples
+            .iter()
+            .zip(&self.window)
+            .map(|(&s, &w)| (s - mean) * w)
+            .collect();
+        let power = self
+            .samples
+            .iter()
+            .map(|&s| (s - mean).powi(2))
+            .sum::<f32>()
+            / self.samples.len() as f32;
+        if power <= 0.0 {
+            return None;
+        }
+
+        let steps = ((SEARCH.1 - SEARCH.0) / STEP) as usize;
+        let spectrum: Vec<f32> = (0..=steps)
+            .map(|k| self.magnitude(&x, SEARCH.0 + k as f64 * STEP))
+            .collect();
+        let peak = (1..steps)
+            .max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b]))
+            .unwrap_or(1);
+        // Parabolic interpolation on the log magnitudes.
+        let (a, b, c) = (
+            spectrum[peak - 1].max(f32::MIN_POSITIVE).ln(),
+            spectrum[peak].max(f32::MIN_POSITIVE).ln(),
+            spectrum[peak + 1].max(f32::MIN_POSITIVE).ln(),
+        );
+        let denominator = a - 2.0 * b + c;
+        let shift = if denominator < 0.0 {
+            (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let frequency = SEARCH.0 + (peak as f64 + shift as f64) * STEP;
+        let amplitude = 2.0 * self.magnitude(&x, frequency) / self.window.iter().sum::<f32>();
+        Some(Measurement {
+            nominal: nominal(frequency as f32),
+            frequency: frequency as f32,
+            share: (amplitude * amplitude / 2.0 / power).min(1.0),
+        })
+    }
+
+    /// The magnitude of the windowed samples at a frequency, by Goertzel.
+    fn magnitude(&self, x: &[f32], frequency: f64) -> f32 {
+        let coeff = (2.0 * (TAU * frequency / self.rate).cos()) as f32;
+        let (mut s1, mut s2) = (0.0f32, 0.0f32);
+        for &v in x {
+            let s = v + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s;
+        }
+        (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0).sqrt()
+    }
+}
+
+/// The standard tone nearest a frequency if close enough, otherwise the
+/// frequency rounded to 0.1 Hz.
+fn nominal(frequency: f32) -> f32 {
+    let nearest = CTCSS_TONES
+        .iter()
+        .copied()
+        .min_by(|a, b| (a - frequency).abs().total_cmp(&(b - frequency).abs()))
+        .unwrap_or(frequency);
+    if (nearest - frequency).abs() <= nearest * TOLERANCE {
+        nearest
+    } else {
+        (frequency * 10.0).round() / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 2_000.0;
+
+    #[test]
+    fn snaps_to_standard_tones() {
+        assert_eq!(nominal(88.6), 88.5);
+        assert_eq!(nominal(253.0), 254.1);
+        assert_eq!(nominal(64.04), 64.0);
+        assert_eq!(nominal(259.0), 259.0);
+    }
+
+    fn measure(freq: f64, noise: f32) -> Measurement {
+        let mut detector = CtcssDetector::new(RATE, 500, 100);
+        let mut seed = 1u32;
+        (0..500)
+            .filter_map(|n| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = noise * ((seed >> 8) as f32 / (1 << 24) as f32 - 0.5);
+                let tone = 0.2 * (TAU * freq * n as f64 / RATE).sin() as f32;
+                detector.push(tone + noise + 0.1)
+            })
+            .last()
+            .expect("a measurement per window")
+    }
+
+    #[test]
+    fn measures_tones_to_a_fraction_of_a_hertz() {
+        for tone in [67.0, 100.0, 131.8, 203.5, 254.1] {
+            let m = measure(tone as f64, 0.0);
+            assert_eq!(m.nominal, tone);
+            assert!(
+                (m.frequency - tone).abs() < 0.1,
+                "{} for {}",
+                m.frequency,
+                tone
+            );
+            assert!(m.share > 0.95, "share {}", m.share);
+        }
+    }
+
+    #[test]
+    fn noise_lowers_the_share() {
+        let m = measure(88.5, 1.0);
+        assert_eq!(m.nominal, 88.5);
+        assert!(m.share < 0.5, "share {}", m.share);
+    }
+
+    #[test]
+    fn silence_measures_nothing() {
+        let mut detector = CtcssDetector::new(RATE, 500, 100);
+        assert!((0..1000).all(|_| detector.push(0.5).is_none()));
+    }
+}
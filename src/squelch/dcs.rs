@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// The standard DCS codes, as the octal numbers they are known by.
+pub const DCS_CODES: [u16; 104] = [
+    0o023, 0o025, 0o026, 0o031, 0o032, 0o036, 0o043, 0o047, 0o051, 0o053, 0o054, 0o065, 0o071,
+    0o072, 0o073, 0o074, 0o114, 0o115, 0o116, 0o122, 0o125, 0o131, 0o132, 0o134, 0o143, 0o145,
+    0o152, 0o155, 0o156, 0o162, 0o165, 0o172, 0o174, 0o205, 0o212, 0o223, 0o225, 0o226, 0o243,
+    0o244, 0o245, 0o246, 0o251, 0o252, 0o255, 0o261, 0o263, 0o265, 0o266, 0o271, 0o274, 0o306,
+    0o311, 0o315, 0o325, 0o331, 0o332, 0o343, 0o346, 0o351, 0o356, 0o364, 0o365, 0o371, 0o411,
+    0o412, 0o413, 0o423, 0o431, 0o432, 0o445, 0o446, 0o452, 0o454, 0o455, 0o462, 0o464, 0o465,
+    0o466, 0o503, 0o506, 0o516, 0o523, 0o526, 0o532, 0o546, 0o565, 0o606, 0o612, 0o624, 0o627,
+    0o631, 0o632, 0o654, 0o662, 0o664, 0o703, 0o712, 0o723, 0o731, 0o732, 0o734, 0o743, 0o754,
+];
+
+/// The DCS bit rate in bits per second.
+const BIT_RATE: f64 = 134.4;
+
+/// The Golay (23,12) generator polynomial.
+const GOLAY: u32 = 0xC75;
+
+/// The fixed `100` bits after the code.
+const MARKER: u32 = 0b100 << 9;
+
+/// Bits in a code word.
+const WORD_BITS: u32 = 23;
+
+/// Weight of each sample in the slicer's level trackers.
+const LEVEL_ALPHA: f32 = 0.01;
+
+/// How far a transition pulls the bit clock towards it.
+const CLOCK_GAIN: f32 = 0.1;
+
+/// A DCS code and its polarity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DcsCode {
+    /// The code as its octal number, e.g. `0o023` for D023.
+    pub code: u16,
+    /// Whether the code is sent inverted, the `I` in D023I.
+    pub inverted: bool,
+}
+
+impl DcsCode {
+    /// Create a code.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code as its octal number, e.g. `0o023`, at most
+    ///   `0o777`.
+    /// * `inverted` - Whether it is sent inverted.
+    ///
+    /// # Returns
+    ///
+    /// A new `DcsCode`.
+    pub fn new(code: u16, inverted: bool) -> Self {
+        assert!(code <= 0o777, "DCS codes are three octal digits");
+        DcsCode { code, inverted }
+    }
+
+    /// Get the 23-bit word sent for the code, first bit in bit 0, before
+    /// any inversion.
+    pub fn word(&self) -> u32 {
+        let data = MARKER | self.code as u32;
+        // Systematic encoding: the data followed by its remainder.
+        let mut remainder = data;
+        for _ in 0..12 {
+            if remainder & 1 != 0 {
+                remainder ^= GOLAY;
+            }
+            remainder >>= 1;
+        }
+        (remainder << 12) | data
+    }
+}
+
+impl fmt::Display for DcsCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let polarity = if self.inverted { 'I' } else { 'N' };
+        write!(f, "D{:03o}{}", self.code, polarity)
+    }
+}
+
+/// Slices DCS bits from sub-audio and matches them against the standard
+/// codes.
+pub(crate) struct DcsDecoder {
+    /// Bit clock step per sample.
+    step: f32,
+    /// Bit clock phase, transitions falling at 0.
+    clock: f32,
+    high: f32,
+    low: f32,
+    last: bool,
+    shift: u32,
+    /// Bits sliced so far.
+    received: u64,
+    /// The preferred code matched in the current word and the bit the
+    /// word's first match ended at.
+    found: Option<(DcsCode, u64)>,
+    words: HashMap<u32, DcsCode>,
+}
+
+impl DcsDecoder {
+    pub(crate) fn new(rate: f64) -> Self {
+        let mask = (1 << WORD_BITS) - 1;
+        let mut words = HashMap::new();
+        for &code in &DCS_CODES {
+            let word = DcsCode::new(code, false).word();
+            words.insert(word, DcsCode::new(code, false));
+            words.insert(!word & mask, DcsCode::new(code, true));
+        }
+        DcsDecoder {
+            step: (BIT_RATE / rate) as f32,
+            clock: 0.0,
+            high: 0.0,
+            low: 0.0,
+            last: false,
+            shift: 0,
+            received: 0,
+            found: None,
+            words,
+        }
+    }
+
+    /// Add a sample, returning a code once a word's worth of bits has passed
+    /// since it first matched.
+    ///
+    /// The word repeats, so every rotation of it goes by, and some codes
+    /// are rotations of others, D023N of D047I for example. Of the codes
+    /// matched within one word, the normal polarity and then the lowest
+    /// code is returned, as scanners do.
+    pub(crate) fn push(&mut self, sample: f32) -> Option<DcsCode> {
+        // Track the two levels so a frequency offset doesn't move the
+        // slicing threshold.
+        if sample > self.high {
+            self.high = sample;
+        } else {
+            self.high += LEVEL_ALPHA * (sample - self.high);
+        }
+        if sample < self.low {
+            self.low = sample;
+        } else {
+            self.low += LEVEL_ALPHA * (sample - self.low);
+        }
+        let bit = sample > 0.5 * (self.high + self.low);
+
+        if bit != self.last {
+            let error = if self.clock < 0.5 {
+                self.clock
+            } else {
+                self.clock - 1.0
+            };
+            self.clock -= CLOCK_GAIN * error;
+            self.last = bit;
+        }
+        let before = self.clock;
+        self.clock += self.step;
+        if self.clock >= 1.0 {
+            self.clock -= 1.0;
+        }
+        if !(before < 0.5 && self.clock >= 0.5) {
+            return None;
+        }
+
+        self.shift = (self.shift >> 1) | ((bit as u32) << (WORD_BITS - 1));
+        self.received += 1;
+        let rank = |code: DcsCode| (code.inverted, code.code);
+        if let Some(&code) = self.words.get(&self.shift) {
+            match &mut self.found {
+                Some((best, _)) if rank(code) < rank(*best) => *best = code,
+                Some(_) => {}
+                None => self.found = Some((code, self.received)),
+            }
+        }
+        match self.found {
+            Some((code, first)) if self.received - first >= WORD_BITS as u64 - 1 => {
+                self.found = None;
+                Some(code)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.clock = 0.0;
+        self.high = 0.0;
+        self.low = 0.0;
+        self.last = false;
+        self.shift = 0;
+        self.received = 0;
+        self.found = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_golay_code_words() {
+        let words: Vec<u32> = (0..=0o777).map(|c| DcsCode::new(c, false).word()).collect();
+        for (i, &a) in words.iter().enumerate() {
+            assert_eq!(a & 0xFFF, MARKER | i as u32);
+            assert!(a < 1 << WORD_BITS);
+            // The Golay (23,12) code has a minimum distance of 7.
+            for &b in &words[i + 1..] {
+                assert!((a ^ b).count_ones() >= 7, "{:o} {:o}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_codes_like_radios() {
+        assert_eq!(DcsCode::new(0o023, false).to_string(), "D023N");
+        assert_eq!(DcsCode::new(0o754, true).to_string(), "D754I");
+    }
+}
@@ -0,0 +1,529 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::ctcss::CtcssDetector;
+use super::dcs::{DcsCode, DcsDecoder};
+use crate::dsp::fir::{low_pass, Decimator, Window};
+use crate::units::Frequency;
+
+/// The rate the sub-audio band is decimated to.
+const SUBAUDIO_RATE: f64 = 2_000.0;
+
+/// The top of the sub-audio band in Hz.
+const SUBAUDIO_CUTOFF: f64 = 300.0;
+
+/// The CTCSS measurement window.
+const CTCSS_WINDOW: f64 = 0.25;
+
+/// How often CTCSS is measured.
+const CTCSS_HOP: f64 = 0.05;
+
+/// The tone's share of the sub-audio power needed to start a detection.
+const START_SHARE: f32 = 0.5;
+
+/// The share needed to keep one going.
+const HOLD_SHARE: f32 = 0.3;
+
+/// Matching measurements or words needed to start a detection.
+const CONFIRM: u32 = 2;
+
+/// Weight of a new measurement in a detection's averaged frequency.
+const FREQUENCY_ALPHA: f32 = 0.2;
+
+/// A subaudible squelch tone or code.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tone {
+    /// A CTCSS tone by its nominal frequency in Hz, e.g. 100.0.
+    Ctcss(f32),
+    /// A DCS code.
+    Dcs(DcsCode),
+}
+
+/// Formats a CTCSS tone by its frequency, e.g. `88.5 Hz`, and a DCS code
+/// as `D023N`.
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tone::Ctcss(hz) => write!(f, "{:.1}", Frequency(*hz as f64)),
+            Tone::Dcs(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// A tone or code heard on a channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Detection {
+    /// The tone or code.
+    pub tone: Tone,
+    /// For CTCSS, the measured frequency in Hz, averaged over the
+    /// detection.
+    pub frequency: Option<f32>,
+    /// The audio sample the detection was confirmed at.
+    pub start: u64,
+    /// Audio samples from `start` to the last time the tone was heard.
+    pub duration: u64,
+}
+
+/// Change reported by `ToneDetector::process`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneEvent {
+    /// A tone or code was confirmed. Any other detection has ended first.
+    Started(Detection),
+    /// A tone or code has gone unheard for the hang time.
+    Ended(Detection),
+}
+
+/// Formats the event as one log line, e.g. `88.5 Hz started, measured
+/// 88.46 Hz`.
+impl fmt::Display for ToneEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToneEvent::Started(d) => {
+                write!(f, "{} started", d.tone)?;
+                if let Some(hz) = d.frequency {
+                    write!(f, ", measured {:.2}", Frequency(hz as f64))?;
+                }
+                Ok(())
+            }
+            ToneEvent::Ended(d) => {
+                write!(f, "{} ended after {} samples", d.tone, d.duration)
+            }
+        }
+    }
+}
+
+impl ToneEvent {
+    /// Get the detection the event is about.
+    ///
+    /// # Returns
+    ///
+    /// The detection, as it stood when the event was raised.
+    pub fn detection(&self) -> &Detection {
+        match self {
+            ToneEvent::Started(d) | ToneEvent::Ended(d) => d,
+        }
+    }
+}
+
+/// Detects CTCSS tones and DCS codes in narrowband FM audio.
+///
+/// The audio below 300 Hz is decimated to about 2 kHz. Every 50 ms the
+/// strongest tone between 60 and 260 Hz in the last 250 ms is measured, to
+/// a fraction of a hertz, and taken for the nearest standard tone; it
+/// counts when it carries at least half the sub-audio power. Alongside, the
+/// 134.4 bit/s DCS stream is sliced and each 23 bits compared against the
+/// Golay-coded words of the standard codes in both polarities; codes that
+/// send the same bits, such as D023N and D047I, are reported as the one in
+/// normal polarity. Two matching measurements or words in a row start a
+/// detection, which ends once the tone has gone unheard for the hang time.
+///
+/// Feed it the discriminator output before any de-emphasis or voice
+/// filtering.
+pub struct ToneDetector {
+    decimator: Decimator<f32>,
+    factor: u64,
+    /// The decimated rate.
+    rate: f64,
+    ctcss: CtcssDetector,
+    dcs: DcsDecoder,
+    hang: u64,
+    /// Decimated samples seen.
+    samples: u64,
+    current: Option<Detection>,
+    /// When the current detection was last heard, in decimated samples.
+    last_heard: u64,
+    /// A tone heard but not yet confirmed, and how many times in a row.
+    candidate: Option<(Tone, u32)>,
+}
+
+impl ToneDetector {
+    /// Create a detector with a 250 ms hang time.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz, at least 8 kHz.
+    ///
+    /// # Returns
+    ///
+    /// A new `ToneDetector`.
+    pub fn new(audio_rate: f64) -> Self {
+        assert!(
+            audio_rate >= 8_000.0,
+            "tone detection needs an audio rate of at least 8 kHz"
+        );
+        let factor = (audio_rate / SUBAUDIO_RATE).floor() as usize;
+        let taps = (5.5 * audio_rate / 600.0) as usize | 1;
+        let filter = low_pass(SUBAUDIO_CUTOFF / audio_rate, taps, Window::Blackman);
+        let rate = audio_rate / factor as f64;
+        ToneDetector {
+            decimator: Decimator::new(factor, &filter),
+            factor: factor as u64,
+            rate,
+            ctcss: CtcssDetector::new(
+                rate,
+                (CTCSS_WINDOW * rate) as usize,
+                (CTCSS_HOP * rate) as usize,
+            ),
+            dcs: DcsDecoder::new(rate),
+            hang: (0.25 * rate) as u64,
+            samples: 0,
+            current: None,
+            last_heard: 0,
+            candidate: None,
+        }
+    }
+
+    /// Set how long a tone may go unheard before its detection ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time. Shorter than about 200 ms lets a DCS
+    ///   detection end between words.
+    ///
+    /// # Returns
+    ///
+    /// The `ToneDetector` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.hang = (hang.as_secs_f64() * self.rate) as u64;
+        self
+    }
+
+    /// Get the tone or code being heard, if any.
+    pub fn current(&self) -> Option<&Detection> {
+        self.current.as_ref()
+    }
+
+    /// Look for tones in a chunk of audio.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of discriminator output, of any length.
+    ///
+    /// # Returns
+    ///
+    /// The detections started and ended in the chunk, in order.
+    pub fn process(&mut self, audio: &[f32]) -> Vec<ToneEvent> {
+        let mut events = Vec::new();
+        for sample in self.decimator.decimate(audio) {
+            self.samples += 1;
+            if let Some(code) = self.dcs.push(sample) {
+                self.heard(Tone::Dcs(code), None, &mut events);
+            }
+            if let Some(tone) = self.ctcss.push(sample) {
+                let holding = self.current.map(|d| d.tone) == Some(Tone::Ctcss(tone.nominal));
+                let needed = if holding { HOLD_SHARE } else { START_SHARE };
+                if tone.share >= needed {
+                    self.heard(Tone::Ctcss(tone.nominal), Some(tone.frequency), &mut events);
+                }
+            }
+            if let Some(detection) = self.current {
+                if self.samples - self.last_heard > self.hang {
+                    events.push(ToneEvent::Ended(detection));
+                    self.current = None;
+                }
+            }
+        }
+        events
+    }
+
+    /// Forget any detection, e.g. after retuning, without reporting it as
+    /// ended.
+    pub fn reset(&mut self) {
+        self.decimator.reset();
+        self.ctcss.reset();
+        self.dcs.reset();
+        self.current = None;
+        self.candidate = None;
+    }
+
+    fn heard(&mut self, tone: Tone, frequency: Option<f32>, events: &mut Vec<ToneEvent>) {
+        let audio_sample = self.samples * self.factor;
+        if let Some(current) = &mut self.current {
+            if current.tone == tone {
+                current.duration = audio_sample - current.start;
+                if let (Some(average), Some(measured)) = (&mut current.frequency, frequency) {
+                    *average += FREQUENCY_ALPHA * (measured - *average);
+                }
+                self.last_heard = self.samples;
+                return;
+            }
+        }
+
+        let count = match self.candidate {
+            Some((candidate, count)) if candidate == tone => count + 1,
+            _ => 1,
+        };
+        if count < CONFIRM {
+            self.candidate = Some((tone, count));
+            return;
+        }
+        self.candidate = None;
+        if let Some(previous) = self.current.take() {
+            events.push(ToneEvent::Ended(previous));
+        }
+        let detection = Detection {
+            tone,
+            frequency,
+            start: audio_sample,
+            duration: 0,
+        };
+        events.push(ToneEvent::Started(detection));
+        self.current = Some(detection);
+        self.last_heard = self.samples;
+    }
+}
+
+/// A squelch that opens only while a given tone or code is heard.
+///
+/// The audio is muted in place from the sample a matching detection ends
+/// until the sample one starts, so a scanner can stop on the users of one
+/// tone and skip the rest. The tone itself isn't filtered out of the
+/// audio.
+///
+/// ```no_run
+/// use radion::demod::FmDemod;
+/// use radion::squelch::{Tone, ToneEvent, ToneSquelch};
+///
+/// let mut fm = FmDemod::new(16_000.0, 2_500.0);
+/// let mut squelch = ToneSquelch::new(16_000.0, Tone::Ctcss(88.5));
+/// # let iq: Vec<radion::dsp::Complex<f32>> = Vec::new();
+/// let mut audio = fm.process(&iq);
+/// for event in squelch.process(&mut audio) {
+///     if let ToneEvent::Started(_) = event {
+///         println!("{}", event);
+///     }
+/// }
+/// ```
+pub struct ToneSquelch {
+    detector: ToneDetector,
+    tone: Tone,
+    /// Audio samples processed.
+    samples: u64,
+}
+
+impl ToneSquelch {
+    /// Create a squelch.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_rate` - The audio sample rate in Hz, at least 8 kHz.
+    /// * `tone` - The tone or code that opens it. A CTCSS tone is given by
+    ///   its nominal frequency from `CTCSS_TONES`.
+    ///
+    /// # Returns
+    ///
+    /// A new, closed `ToneSquelch`.
+    pub fn new(audio_rate: f64, tone: Tone) -> Self {
+        ToneSquelch {
+            detector: ToneDetector::new(audio_rate),
+            tone,
+            samples: 0,
+        }
+    }
+
+    /// Set how long the tone may go unheard before the squelch closes.
+    ///
+    /// # Arguments
+    ///
+    /// * `hang` - The hang time.
+    ///
+    /// # Returns
+    ///
+    /// The `ToneSquelch` with the new hang time.
+    pub fn with_hang(mut self, hang: Duration) -> Self {
+        self.detector = self.detector.with_hang(hang);
+        self
+    }
+
+    /// Get whether the squelch is open.
+    pub fn is_open(&self) -> bool {
+        self.detector.current().map(|d| d.tone) == Some(self.tone)
+    }
+
+    /// Get the detector, to see what else is being heard.
+    pub fn detector(&self) -> &ToneDetector {
+        &self.detector
+    }
+
+    /// Squelch a chunk of audio.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio` - The next chunk of discriminator output, muted in place
+    ///   where the squelch is closed.
+    ///
+    /// # Returns
+    ///
+    /// Every detection started and ended in the chunk, not only those of
+    /// the wanted tone.
+    pub fn process(&mut self, audio: &mut [f32]) -> Vec<ToneEvent> {
+        let mut open = self.is_open();
+        let events = self.detector.process(audio);
+        let first = self.samples;
+        self.samples += audio.len() as u64;
+
+        let mut from = 0;
+        for event in &events {
+            let detection = event.detection();
+            if detection.tone != self.tone {
+                continue;
+            }
+            let at = match event {
+                ToneEvent::Started(d) => d.start,
+                ToneEvent::Ended(d) => d.start + d.duration,
+            };
+            let at = (at.saturating_sub(first) as usize).min(audio.len());
+            if !open {
+                audio[from..at.max(from)].fill(0.0);
+            }
+            from = at.max(from);
+            open = matches!(event, ToneEvent::Started(_));
+        }
+        if !open {
+            audio[from..].fill(0.0);
+        }
+        events
+    }
+
+    /// Close the squelch and forget any detection, e.g. after retuning.
+    pub fn reset(&mut self) {
+        self.detector.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const RATE: f64 = 16_000.0;
+
+    /// Voice band audio, well above the sub-audio band.
+    fn voice(n: usize) -> f32 {
+        let t = n as f64 / RATE;
+        (0.4 * (TAU * 1_000.0 * t).sin() + 0.3 * (TAU * 2_300.0 * t).sin()) as f32
+    }
+
+    fn ctcss(tone: f64, seconds: f64) -> Vec<f32> {
+        (0..(seconds * RATE) as usize)
+            .map(|n| voice(n) + 0.15 * (TAU * tone * n as f64 / RATE).sin() as f32)
+            .collect()
+    }
+
+    /// Audio carrying a DCS code as NRZ at 134.4 bit/s, first bit first.
+    fn dcs(code: DcsCode, seconds: f64) -> Vec<f32> {
+        let word = code.word() ^ if code.inverted { (1 << 23) - 1 } else { 0 };
+        (0..(seconds * RATE) as usize)
+            .map(|n| {
+                let bit = (n as f64 * 134.4 / RATE) as u64 % 23;
+                let level = if word >> bit & 1 != 0 { 0.15 } else { -0.15 };
+                voice(n) + level
+            })
+            .collect()
+    }
+
+    fn started(events: &[ToneEvent]) -> Vec<Tone> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                ToneEvent::Started(d) => Some(d.tone),
+                ToneEvent::Ended(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_ctcss_tones() {
+        let mut detector = ToneDetector::new(RATE);
+        let events = detector.process(&ctcss(88.6, 1.0));
+        assert_eq!(started(&events), [Tone::Ctcss(88.5)]);
+        let ToneEvent::Started(detection) = events[0] else {
+            unreachable!()
+        };
+        assert!((detection.frequency.unwrap() - 88.6).abs() < 0.2);
+        // Two 50 ms hops past the 250 ms window.
+        assert!(detection.start < (0.4 * RATE) as u64, "{}", detection.start);
+        assert_eq!(detector.current().unwrap().tone, Tone::Ctcss(88.5));
+
+        let events = detector.process(&vec![0.0; RATE as usize]);
+        assert!(matches!(events[..], [ToneEvent::Ended(d)] if d.tone == Tone::Ctcss(88.5)));
+        assert!(detector.current().is_none());
+    }
+
+    #[test]
+    fn a_new_tone_ends_the_old_one() {
+        let mut detector = ToneDetector::new(RATE);
+        detector.process(&ctcss(100.0, 1.0));
+        let events = detector.process(&ctcss(173.8, 1.0));
+        assert!(matches!(
+            events[..],
+            [ToneEvent::Ended(old), ToneEvent::Started(new)]
+                if old.tone == Tone::Ctcss(100.0) && new.tone == Tone::Ctcss(173.8)
+        ));
+    }
+
+    #[test]
+    fn voice_alone_is_no_tone() {
+        let mut detector = ToneDetector::new(RATE);
+        let audio: Vec<f32> = (0..2 * RATE as usize).map(voice).collect();
+        assert!(detector.process(&audio).is_empty());
+    }
+
+    #[test]
+    fn detects_dcs_codes() {
+        for code in [DcsCode::new(0o023, false), DcsCode::new(0o631, false)] {
+            let mut detector = ToneDetector::new(RATE);
+            let events = detector.process(&dcs(code, 1.5));
+            assert_eq!(started(&events), [Tone::Dcs(code)], "{}", code);
+        }
+    }
+
+    #[test]
+    fn reports_codes_sharing_bits_in_normal_polarity() {
+        // Every inverted standard code sends the bits of a normal one.
+        for (sent, heard) in [(0o047, 0o023), (0o754, 0o116)] {
+            let mut detector = ToneDetector::new(RATE);
+            let events = detector.process(&dcs(DcsCode::new(sent, true), 1.5));
+            assert_eq!(started(&events), [Tone::Dcs(DcsCode::new(heard, false))]);
+        }
+    }
+
+    #[test]
+    fn squelch_opens_only_for_its_tone() {
+        let mut squelch = ToneSquelch::new(RATE, Tone::Ctcss(88.5));
+        let mut audio = ctcss(88.5, 1.0);
+        squelch.process(&mut audio);
+        assert!(squelch.is_open());
+        let opened = audio.iter().position(|&s| s != 0.0).unwrap();
+        assert!(opened > 0 && opened < (0.4 * RATE) as usize);
+        assert!(audio[opened..].iter().filter(|&&s| s == 0.0).count() < 10);
+
+        let mut squelch = ToneSquelch::new(RATE, Tone::Ctcss(88.5));
+        let mut audio = ctcss(123.0, 1.0);
+        squelch.process(&mut audio);
+        assert!(!squelch.is_open());
+        assert!(audio.iter().all(|&s| s == 0.0));
+        assert!(matches!(
+            squelch.detector().current(),
+            Some(d) if d.tone == Tone::Ctcss(123.0)
+        ));
+    }
+
+    #[test]
+    fn formats_events_in_engineering_units() {
+        let detection = Detection {
+            tone: Tone::Ctcss(88.5),
+            frequency: Some(88.46),
+            start: 0,
+            duration: 16_000,
+        };
+        assert_eq!(
+            ToneEvent::Started(detection).to_string(),
+            "88.5 Hz started, measured 88.46 Hz"
+        );
+        assert_eq!(
+            ToneEvent::Ended(detection).to_string(),
+            "88.5 Hz ended after 16000 samples"
+        );
+    }
+}
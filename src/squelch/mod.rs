@@ -0,0 +1,45 @@
+//! Subaudible tone squelch: CTCSS tones and DCS codes on narrowband FM.
+//!
+//! `ToneDetector` watches discriminator output for the standard CTCSS
+//! tones and DCS codes, reporting each as a `ToneEvent` when it starts and
+//! ends, with the measured frequency of CTCSS tones, so a scanner can log
+//! who is using a channel. `ToneSquelch` uses it as a squelch qualifier,
+//! muting the audio unless one tone or code is present.
+//!
+//! ```no_run
+//! use radion::demod::FmDemod;
+//! use radion::dsp::FreqXlator;
+//! use radion::dsp::fir::{low_pass, Window};
+//! use radion::samples::{to_complex_f32, Scaling};
+//! use radion::squelch::ToneDetector;
+//! use radion::Device;
+//!
+//! const SAMPLE_RATE: u32 = 240_000;
+//!
+//! let device = Device::new(0)?;
+//! device.set_center_freq(446_000_000)?;
+//! device.set_sample_rate(SAMPLE_RATE)?;
+//! device.reset_buffer()?;
+//!
+//! // PMR446 channel 1 at 446.00625 MHz, 12.5 kHz wide, down to 16 kHz.
+//! let taps = low_pass(6_250.0 / SAMPLE_RATE as f64, 129, Window::Blackman);
+//! let mut channel = FreqXlator::new(SAMPLE_RATE as f64, 6_250.0, 15, &taps);
+//! let mut fm = FmDemod::new(16_000.0, 2_500.0);
+//! let mut tones = ToneDetector::new(16_000.0);
+//! loop {
+//!     let iq = to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized);
+//!     for event in tones.process(&fm.process(&channel.process(&iq))) {
+//!         // e.g. "88.5 Hz started, measured 88.46 Hz"
+//!         println!("{}", event);
+//!     }
+//! }
+//! # Ok::<(), radion::Error>(())
+//! ```
+
+mod ctcss;
+mod dcs;
+mod detector;
+
+pub use ctcss::CTCSS_TONES;
+pub use dcs::{DcsCode, DCS_CODES};
+pub use detector::{Detection, Tone, ToneDetector, ToneEvent, ToneSquelch};
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// librtlsdr's buffer count when 0 is passed.
+pub(crate) const DEFAULT_BUF_NUM: u32 = 15;
+
+/// librtlsdr's buffer length when 0 is passed.
+pub(crate) const DEFAULT_BUF_LEN: u32 = 16 * 32 * 512;
+
+/// Counters of the asynchronous read path.
+///
+/// Returned by `Device::stream_stats`, `StreamHandle::stats` and
+/// `SampleStream::stats`. The counters cover every asynchronous read since
+/// the device was opened, except reads through the raw `read_async`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// USB transfers delivered.
+    pub frames: u64,
+    /// Bytes delivered.
+    pub bytes: u64,
+    /// Transfers shorter than the requested buffer length.
+    pub short_frames: u64,
+    /// Transfers dropped because the consumer was not keeping up, e.g.
+    /// when the queue of a `SampleStream` was full.
+    pub dropped_frames: u64,
+    /// Times a transfer arrived later than all of librtlsdr's buffers could
+    /// have covered, which means the dongle dropped samples before them.
+    pub discontinuities: u64,
+    /// When data was last dropped or lost, if ever.
+    pub last_overrun: Option<SystemTime>,
+}
+
+impl StreamStats {
+    /// Check whether any data was lost.
+    ///
+    /// # Returns
+    ///
+    /// `true` if frames were dropped or discontinuities detected.
+    pub fn has_losses(&self) -> bool {
+        self.dropped_frames > 0 || self.discontinuities > 0
+    }
+}
+
+/// Shared counters behind `StreamStats`, updated from the reader thread.
+#[derive(Default)]
+pub(crate) struct StreamCounters {
+    frames: AtomicU64,
+    bytes: AtomicU64,
+    short_frames: AtomicU64,
+    dropped_frames: AtomicU64,
+    discontinuities: AtomicU64,
+    last_overrun: Mutex<Option<SystemTime>>,
+}
+
+impl StreamCounters {
+    pub(crate) fn snapshot(&self) -> StreamStats {
+        StreamStats {
+            frames: self.frames.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            short_frames: self.short_frames.load(Ordering::Relaxed),
+            dropped_frames: self.dropped_frames.load(Ordering::Relaxed),
+            discontinuities: self.discontinuities.load(Ordering::Relaxed),
+            last_overrun: *self.last_overrun.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    pub(crate) fn record_frame(&self, len: usize, expected: usize) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if len < expected {
+            self.short_frames.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_drop(&self) {
+        self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+        self.mark_overrun();
+    }
+
+    pub(crate) fn record_discontinuity(&self) {
+        self.discontinuities.fetch_add(1, Ordering::Relaxed);
+        self.mark_overrun();
+    }
+
+    fn mark_overrun(&self) {
+        *self.last_overrun.lock().unwrap_or_else(|e| e.into_inner()) = Some(SystemTime::now());
+    }
+}
+
+/// Detects samples lost before they reached librtlsdr's buffers.
+///
+/// Transfers arrive in bursts, but never later than the time the samples
+/// they hold were taken plus the time all of librtlsdr's buffers last. A
+/// transfer that does means the buffers were all full and the dongle's FIFO
+/// overflowed.
+pub(crate) struct GapDetector {
+    rate: f64,
+    /// Expected transfer length in bytes.
+    pub(crate) expected: usize,
+    /// Time covered by all buffers, in seconds.
+    capacity: f64,
+    start: Option<Instant>,
+    samples: u64,
+    earliest: f64,
+}
+
+impl GapDetector {
+    pub(crate) fn new(sample_rate: u32, buf_num: u32, buf_len: u32) -> Self {
+        let buf_num = if buf_num == 0 {
+            DEFAULT_BUF_NUM
+        } else {
+            buf_num
+        };
+        let buf_len = if buf_len == 0 {
+            DEFAULT_BUF_LEN
+        } else {
+            buf_len
+        };
+        let rate = sample_rate as f64;
+        GapDetector {
+            rate,
+            expected: buf_len as usize,
+            capacity: if rate > 0.0 {
+                buf_num as f64 * buf_len as f64 / 2.0 / rate
+            } else {
+                f64::INFINITY
+            },
+            start: None,
+            samples: 0,
+            earliest: f64::INFINITY,
+        }
+    }
+
+    /// Account for a transfer that has just arrived.
+    ///
+    /// Returns `true` if samples were lost before it.
+    pub(crate) fn check(&mut self, len: usize) -> bool {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        self.samples += len as u64 / 2;
+        if self.rate <= 0.0 {
+            return false;
+        }
+        let offset = now.duration_since(start).as_secs_f64() - self.samples as f64 / self.rate;
+        self.earliest = self.earliest.min(offset);
+        if offset - self.earliest > self.capacity {
+            // Start over from this transfer, after the gap.
+            self.start = Some(now);
+            self.samples = 0;
+            self.earliest = f64::INFINITY;
+            self.check(0);
+            return true;
+        }
+        false
+    }
+}
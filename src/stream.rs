@@ -0,0 +1,219 @@
+use crate::device::{AsyncCanceller, Device};
+use crate::error::{Error, Result};
+use crate::stats::{StreamCounters, StreamStats};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Iterator over consecutive chunks of IQ samples read from a device.
+///
+/// Created by `Device::samples`. The device buffer is reset before the first
+/// chunk is read, and the iterator ends after yielding the first error.
+pub struct Samples<'a> {
+    device: &'a Device,
+    chunk_len: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> Samples<'a> {
+    pub(crate) fn new(device: &'a Device, chunk_len: usize) -> Self {
+        Samples {
+            device,
+            chunk_len,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Samples<'_> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.device.reset_buffer() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        let chunk = self.device.read_sync(self.chunk_len);
+        self.done = chunk.is_err();
+        Some(chunk)
+    }
+}
+
+/// Guard for an asynchronous read running on a dedicated thread.
+///
+/// Created by `Device::start_async`. Dropping the handle cancels the read,
+/// joins the reader thread and closes the device.
+pub struct StreamHandle {
+    canceller: AsyncCanceller,
+    stats: Arc<StreamCounters>,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<(Device, Result<()>)>>,
+}
+
+impl StreamHandle {
+    pub(crate) fn spawn<F>(device: Device, mut callback: F, buf_num: u32, buf_len: u32) -> Self
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let canceller = device.canceller();
+        let stats = device.stream_counters();
+        let flag = stop.clone();
+        let reader = thread::spawn(move || {
+            let ret = device.read_async_with(
+                |buf| {
+                    // Catches a stop requested before librtlsdr was running
+                    // and therefore not cancellable yet.
+                    if flag.load(Ordering::Acquire) {
+                        let _ = device.cancel_async();
+                    } else {
+                        callback(buf);
+                    }
+                },
+                buf_num,
+                buf_len,
+            );
+            (device, ret)
+        });
+        StreamHandle {
+            canceller,
+            stats,
+            stop,
+            reader: Some(reader),
+        }
+    }
+
+    /// Check whether the read is still running.
+    ///
+    /// # Returns
+    ///
+    /// `false` once the read has stopped, either because it was cancelled or
+    /// because librtlsdr reported an error.
+    pub fn is_running(&self) -> bool {
+        self.reader.as_ref().is_some_and(|r| !r.is_finished())
+    }
+
+    /// Get the counters of the asynchronous read path, see
+    /// `Device::stream_stats`.
+    pub fn stats(&self) -> StreamStats {
+        self.stats.snapshot()
+    }
+
+    /// Cancel the read and wait for the reader thread to exit.
+    ///
+    /// If the callback panicked, the panic is resumed on the calling thread.
+    ///
+    /// # Returns
+    ///
+    /// The device if the read ended cleanly, otherwise the `Error` it failed
+    /// with.
+    pub fn stop(mut self) -> Result<Device> {
+        match self.finish() {
+            Some(Ok((device, Ok(())))) => Ok(device),
+            Some(Ok((_, Err(e)))) => Err(e),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Err(Error::Unknown),
+        }
+    }
+
+    fn finish(&mut self) -> Option<thread::Result<(Device, Result<()>)>> {
+        let reader = self.reader.take()?;
+        self.stop.store(true, Ordering::Release);
+        let _ = self.canceller.cancel();
+        Some(reader.join())
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio_stream::SampleStream;
+
+#[cfg(feature = "tokio")]
+mod tokio_stream {
+    use super::StreamHandle;
+    use crate::device::Device;
+    use crate::error::Result;
+    use crate::stats::{StreamCounters, StreamStats};
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use tokio::sync::mpsc;
+
+    /// Asynchronous stream of sample buffers read from a device.
+    ///
+    /// Created by `Device::into_stream`. The device is read on a dedicated
+    /// thread; buffers that arrive while the channel is full are dropped so
+    /// the USB transfers never stall. Dropping the stream cancels the read and
+    /// closes the device.
+    pub struct SampleStream {
+        rx: mpsc::Receiver<Vec<u8>>,
+        handle: Option<StreamHandle>,
+        stats: Arc<StreamCounters>,
+    }
+
+    impl SampleStream {
+        pub(crate) fn spawn(
+            device: Device,
+            capacity: usize,
+            buf_num: u32,
+            buf_len: u32,
+        ) -> Result<Self> {
+            let (tx, rx) = mpsc::channel(capacity.max(1));
+            let stats = device.stream_counters();
+            let counters = stats.clone();
+            let handle = device.start_async(
+                move |buf| {
+                    if tx.try_send(buf.to_vec()).is_err() {
+                        counters.record_drop();
+                    }
+                },
+                buf_num,
+                buf_len,
+            )?;
+            Ok(SampleStream {
+                rx,
+                handle: Some(handle),
+                stats,
+            })
+        }
+
+        /// Get the counters of the asynchronous read path, including buffers
+        /// dropped because the stream was not polled fast enough. See
+        /// `Device::stream_stats`.
+        pub fn stats(&self) -> StreamStats {
+            self.stats.snapshot()
+        }
+    }
+
+    impl Stream for SampleStream {
+        type Item = Result<Vec<u8>>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            match self.rx.poll_recv(cx) {
+                Poll::Ready(Some(buf)) => Poll::Ready(Some(Ok(buf))),
+                // The sender lives in the reader's callback, so the channel
+                // only closes once the read has ended.
+                Poll::Ready(None) => match self.handle.take().map(StreamHandle::stop) {
+                    Some(Err(e)) => Poll::Ready(Some(Err(e))),
+                    _ => Poll::Ready(None),
+                },
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+}
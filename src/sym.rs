@@ -0,0 +1,58 @@
+use std::ffi::CStr;
+
+/// Check whether the loaded librtlsdr, or the libusb it was loaded with,
+/// exports a symbol.
+///
+/// Used for functions that only some librtlsdr builds provide, so that
+/// linking never depends on them.
+///
+/// # Arguments
+///
+/// * `name` - The name of the symbol.
+///
+/// # Returns
+///
+/// The address of the symbol, or `None` if it is not available.
+#[cfg(unix)]
+pub fn lookup(name: &CStr) -> Option<*mut libc::c_void> {
+    let addr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+    if addr.is_null() {
+        None
+    } else {
+        Some(addr)
+    }
+}
+
+#[cfg(windows)]
+pub fn lookup(name: &CStr) -> Option<*mut std::os::raw::c_void> {
+    use std::os::raw::{c_char, c_void};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(name: *const c_char) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    // The DLL names Windows builds of librtlsdr and its libusb ship under.
+    const MODULES: [&CStr; 4] = [
+        c"rtlsdr.dll",
+        c"librtlsdr.dll",
+        c"libusb-1.0.dll",
+        c"usb-1.0.dll",
+    ];
+    MODULES.iter().find_map(|module| {
+        let addr = unsafe {
+            let handle = GetModuleHandleA(module.as_ptr());
+            if handle.is_null() {
+                return None;
+            }
+            GetProcAddress(handle, name.as_ptr())
+        };
+        (!addr.is_null()).then_some(addr)
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn lookup(_name: &CStr) -> Option<*mut std::os::raw::c_void> {
+    None
+}
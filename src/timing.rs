@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Length of the windows whose fastest arrival feeds the drift estimate, in
+/// seconds of samples.
+const WINDOW_S: f64 = 1.0;
+
+/// Number of windows kept for the drift estimate.
+const MAX_WINDOWS: usize = 600;
+
+/// Windows needed before the drift is estimated.
+const MIN_WINDOWS: usize = 3;
+
+/// A point in time on both host clocks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    /// The monotonic clock, for measuring intervals.
+    pub monotonic: Instant,
+    /// The wall clock, for logging and aligning with other hosts.
+    pub wall: SystemTime,
+}
+
+/// A buffer from the asynchronous read path with the time of its first
+/// sample.
+///
+/// Passed to the callbacks of `Device::read_async_timed` and
+/// `Device::start_async_timed`. Buffers only carry their arrival time, which
+/// includes however long USB and the scheduler held them up, so `t0` is
+/// estimated from a model of the sample clock instead: a straight line
+/// through the earliest arrivals, whose slope is the drift of the dongle's
+/// clock against the host's. What remains is a constant transfer delay that
+/// cannot be observed from the host and is the same for every buffer.
+#[derive(Copy, Clone, Debug)]
+pub struct TimedSamples<'a> {
+    /// Estimated host time at which the first sample was taken, less the
+    /// constant transfer delay.
+    pub t0: Timestamp,
+    /// Index of the first sample since the read started.
+    pub sample_index: u64,
+    /// The raw cu8 samples.
+    pub samples: &'a [u8],
+    /// How much later than the clock model predicts the buffer arrived.
+    pub latency: Duration,
+    /// Drift of the dongle's sample clock against the host clock in ppm,
+    /// positive when the dongle is slow, once enough has been read to tell.
+    pub drift_ppm: Option<f64>,
+}
+
+/// Model of when samples arrive, fitted to buffer arrival times.
+pub(crate) struct ArrivalClock {
+    rate: f64,
+    start: Option<(Instant, SystemTime)>,
+    samples: u64,
+    /// Earliest arrival offset of the window being filled.
+    window_min: f64,
+    window_end: f64,
+    /// `(ideal time, earliest offset)` of completed windows.
+    windows: VecDeque<(f64, f64)>,
+}
+
+impl ArrivalClock {
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        ArrivalClock {
+            rate: sample_rate.max(1) as f64,
+            start: None,
+            samples: 0,
+            window_min: f64::INFINITY,
+            window_end: WINDOW_S,
+            windows: VecDeque::new(),
+        }
+    }
+
+    /// Time a buffer that has just arrived.
+    pub(crate) fn stamp<'a>(&mut self, samples: &'a [u8]) -> TimedSamples<'a> {
+        let now = Instant::now();
+        let (start, wall_start) = *self.start.get_or_insert((now, SystemTime::now()));
+        let first = self.samples;
+        self.samples += samples.len() as u64 / 2;
+
+        // Offset of the arrival of the last sample from where a perfect clock
+        // started at `start` would put it.
+        let ideal_end = self.samples as f64 / self.rate;
+        let offset = now.duration_since(start).as_secs_f64() - ideal_end;
+        self.window_min = self.window_min.min(offset);
+        if ideal_end >= self.window_end {
+            if self.windows.len() == MAX_WINDOWS {
+                self.windows.pop_front();
+            }
+            self.windows.push_back((ideal_end, self.window_min));
+            self.window_min = f64::INFINITY;
+            self.window_end = ideal_end + WINDOW_S;
+        }
+
+        let (intercept, slope) = self.fit().unwrap_or_else(|| {
+            let earliest = self
+                .windows
+                .iter()
+                .map(|w| w.1)
+                .fold(self.window_min, f64::min);
+            (earliest, 0.0)
+        });
+        let latency = (offset - (intercept + slope * ideal_end)).max(0.0);
+        let ideal_first = first as f64 / self.rate;
+        // Negative for the first buffers, whose samples were taken before
+        // the first one arrived.
+        let since_start = intercept + ideal_first * (1.0 + slope);
+        let shift = Duration::from_secs_f64(since_start.abs());
+        let t0 = if since_start >= 0.0 {
+            Timestamp {
+                monotonic: start + shift,
+                wall: wall_start + shift,
+            }
+        } else {
+            Timestamp {
+                monotonic: start.checked_sub(shift).unwrap_or(start),
+                wall: wall_start - shift,
+            }
+        };
+
+        TimedSamples {
+            t0,
+            sample_index: first,
+            samples,
+            latency: Duration::from_secs_f64(latency),
+            drift_ppm: (self.windows.len() >= MIN_WINDOWS).then_some(slope * 1e6),
+        }
+    }
+
+    /// Least-squares line through the earliest arrivals of the windows.
+    fn fit(&self) -> Option<(f64, f64)> {
+        if self.windows.len() < MIN_WINDOWS {
+            return None;
+        }
+        let n = self.windows.len() as f64;
+        let mean_x = self.windows.iter().map(|w| w.0).sum::<f64>() / n;
+        let mean_y = self.windows.iter().map(|w| w.1).sum::<f64>() / n;
+        let (mut sxx, mut sxy) = (0.0, 0.0);
+        for &(x, y) in &self.windows {
+            sxx += (x - mean_x) * (x - mean_x);
+            sxy += (x - mean_x) * (y - mean_y);
+        }
+        let slope = sxy / sxx;
+        // Shift the line down onto the earliest window, so the model sits on
+        // the lower envelope instead of the average delay.
+        let intercept = self
+            .windows
+            .iter()
+            .map(|&(x, y)| y - slope * x)
+            .fold(f64::INFINITY, f64::min);
+        Some((intercept, slope))
+    }
+}
@@ -14,6 +14,8 @@ pub enum RTLSDRTuner {
     R828D = 6,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(i32)]
 pub enum SamplingMode {
     None = 0,
     IADC = 1,
@@ -37,3 +39,234 @@ impl TryFrom<c_int> for RTLSDRTuner {
         }
     }
 }
+
+impl TryFrom<c_int> for SamplingMode {
+    type Error = Error;
+
+    fn try_from(value: c_int) -> Result<Self, Error> {
+        match value {
+            0 => Ok(SamplingMode::None),
+            1 => Ok(SamplingMode::IADC),
+            2 => Ok(SamplingMode::QADC),
+            _ => Err(Error::Unknown),
+        }
+    }
+}
+
+impl From<SamplingMode> for c_int {
+    fn from(mode: SamplingMode) -> Self {
+        mode as c_int
+    }
+}
+
+/// One gain step of a tuner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GainStep {
+    /// The gain librtlsdr reports and accepts, in tenths of a dB.
+    pub nominal: i32,
+    /// The independently measured gain in tenths of a dB, where documented.
+    pub measured: Option<i32>,
+}
+
+impl GainStep {
+    const fn nominal(nominal: i32) -> Self {
+        GainStep {
+            nominal,
+            measured: None,
+        }
+    }
+
+    /// Get the best known gain for this step.
+    ///
+    /// # Returns
+    ///
+    /// The measured gain if documented, otherwise the nominal one, in tenths
+    /// of a dB.
+    pub fn effective(&self) -> i32 {
+        self.measured.unwrap_or(self.nominal)
+    }
+}
+
+/// Static knowledge about a tuner: gain table, tuning range and notes on how
+/// far the reported gains can be trusted.
+#[derive(Copy, Clone, Debug)]
+pub struct TunerCapabilities {
+    pub tuner: RTLSDRTuner,
+    /// Manual gain steps in the order librtlsdr lists them.
+    pub gains: &'static [GainStep],
+    /// Approximate tuning range as `(low_hz, high_hz)`, if known.
+    pub frequency_range: Option<(u64, u64)>,
+    /// How the gain figures were obtained and how accurate they are.
+    pub gain_notes: &'static str,
+}
+
+impl TunerCapabilities {
+    /// Find the step librtlsdr will actually use for a requested gain.
+    ///
+    /// # Arguments
+    ///
+    /// * `requested` - The gain passed to `Device::set_tuner_gain`, in tenths
+    ///   of a dB.
+    ///
+    /// # Returns
+    ///
+    /// The closest step, or `None` if the tuner has no gain table.
+    pub fn nearest_gain(&self, requested: i32) -> Option<GainStep> {
+        self.gains
+            .iter()
+            .min_by_key(|g| (g.nominal - requested).abs())
+            .copied()
+    }
+
+    /// Get the correction to apply to a power measurement taken with a given
+    /// gain setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `requested` - The gain passed to `Device::set_tuner_gain`, in tenths
+    ///   of a dB.
+    ///
+    /// # Returns
+    ///
+    /// `requested - effective` in dB, i.e. the amount by which the requested
+    /// figure overstates the real gain, or `None` if the tuner has no gain
+    /// table.
+    pub fn gain_error_db(&self, requested: i32) -> Option<f64> {
+        self.nearest_gain(requested)
+            .map(|g| (requested - g.effective()) as f64 / 10.0)
+    }
+}
+
+const E4000_GAINS: [GainStep; 14] = [
+    GainStep::nominal(-10),
+    GainStep::nominal(15),
+    GainStep::nominal(40),
+    GainStep::nominal(65),
+    GainStep::nominal(90),
+    GainStep::nominal(115),
+    GainStep::nominal(140),
+    GainStep::nominal(165),
+    GainStep::nominal(190),
+    GainStep::nominal(215),
+    GainStep::nominal(240),
+    GainStep::nominal(290),
+    GainStep::nominal(340),
+    GainStep::nominal(420),
+];
+
+const FC0012_GAINS: [GainStep; 5] = [
+    GainStep::nominal(-99),
+    GainStep::nominal(-40),
+    GainStep::nominal(71),
+    GainStep::nominal(179),
+    GainStep::nominal(192),
+];
+
+const FC0013_GAINS: [GainStep; 23] = [
+    GainStep::nominal(-99),
+    GainStep::nominal(-73),
+    GainStep::nominal(-65),
+    GainStep::nominal(-63),
+    GainStep::nominal(-60),
+    GainStep::nominal(-58),
+    GainStep::nominal(-54),
+    GainStep::nominal(58),
+    GainStep::nominal(61),
+    GainStep::nominal(63),
+    GainStep::nominal(65),
+    GainStep::nominal(67),
+    GainStep::nominal(68),
+    GainStep::nominal(70),
+    GainStep::nominal(71),
+    GainStep::nominal(179),
+    GainStep::nominal(181),
+    GainStep::nominal(182),
+    GainStep::nominal(184),
+    GainStep::nominal(186),
+    GainStep::nominal(188),
+    GainStep::nominal(191),
+    GainStep::nominal(197),
+];
+
+const FC2580_GAINS: [GainStep; 1] = [GainStep::nominal(0)];
+
+const R82XX_GAINS: [GainStep; 29] = [
+    GainStep::nominal(0),
+    GainStep::nominal(9),
+    GainStep::nominal(14),
+    GainStep::nominal(27),
+    GainStep::nominal(37),
+    GainStep::nominal(77),
+    GainStep::nominal(87),
+    GainStep::nominal(125),
+    GainStep::nominal(144),
+    GainStep::nominal(157),
+    GainStep::nominal(166),
+    GainStep::nominal(197),
+    GainStep::nominal(207),
+    GainStep::nominal(229),
+    GainStep::nominal(254),
+    GainStep::nominal(280),
+    GainStep::nominal(297),
+    GainStep::nominal(328),
+    GainStep::nominal(338),
+    GainStep::nominal(364),
+    GainStep::nominal(372),
+    GainStep::nominal(386),
+    GainStep::nominal(402),
+    GainStep::nominal(421),
+    GainStep::nominal(434),
+    GainStep::nominal(439),
+    GainStep::nominal(445),
+    GainStep::nominal(480),
+    GainStep::nominal(496),
+];
+
+const R82XX_NOTES: &str = "Cumulative sums of the driver's LNA and mixer step \
+    tables with the VGA held fixed. Steps are not evenly spaced and the real gain \
+    varies with frequency, so treat the figures as relative, not absolute.";
+
+impl RTLSDRTuner {
+    /// Get the static capabilities of this tuner type.
+    ///
+    /// # Returns
+    ///
+    /// The gain table and tuning range librtlsdr uses for the tuner.
+    pub fn capabilities(self) -> TunerCapabilities {
+        let (gains, frequency_range, gain_notes): (&'static [GainStep], _, _) = match self {
+            RTLSDRTuner::Unknown => (&[], None, "Unknown tuner, no gain table."),
+            RTLSDRTuner::E4000 => (
+                &E4000_GAINS,
+                Some((52_000_000, 2_200_000_000)),
+                "LNA plus mixer gain from the E4000 datasheet. Tuning has a gap \
+                 around 1.1-1.25 GHz.",
+            ),
+            RTLSDRTuner::FC0012 => (
+                &FC0012_GAINS,
+                Some((22_000_000, 948_600_000)),
+                "LNA gain settings only; the rest of the chain is fixed, so the \
+                 figures are relative.",
+            ),
+            RTLSDRTuner::FC0013 => (
+                &FC0013_GAINS,
+                Some((22_000_000, 1_100_000_000)),
+                "LNA gain settings only; the rest of the chain is fixed, so the \
+                 figures are relative.",
+            ),
+            RTLSDRTuner::FC2580 => (
+                &FC2580_GAINS,
+                Some((146_000_000, 924_000_000)),
+                "librtlsdr has no manual gain control for this tuner. Tuning has \
+                 a gap around 308-438 MHz.",
+            ),
+            RTLSDRTuner::R820T => (&R82XX_GAINS, Some((24_000_000, 1_766_000_000)), R82XX_NOTES),
+            RTLSDRTuner::R828D => (&R82XX_GAINS, Some((24_000_000, 1_766_000_000)), R82XX_NOTES),
+        };
+        TunerCapabilities {
+            tuner: self,
+            gains,
+            frequency_range,
+            gain_notes,
+        }
+    }
+}
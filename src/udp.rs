@@ -0,0 +1,512 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::file_source::{convert, IqFormat};
+use crate::source::SdrSource;
+
+/// Bytes of header at the start of every datagram.
+pub const UDP_HEADER_LEN: usize = 24;
+
+/// Identifies radion IQ datagrams.
+const MAGIC: &[u8; 4] = b"RDIQ";
+
+/// Header layout version.
+const VERSION: u8 = 1;
+
+/// Largest datagram that fits an Ethernet frame unfragmented.
+const DEFAULT_DATAGRAM: usize = 1472;
+
+/// Largest UDP payload over IPv4.
+const MAX_DATAGRAM: usize = 65_507;
+
+fn format_code(format: IqFormat) -> u8 {
+    match format {
+        IqFormat::Cu8 => 0,
+        IqFormat::Cs16 => 1,
+        IqFormat::Cf32 => 2,
+    }
+}
+
+/// Header fields of a datagram.
+struct Header {
+    sequence: u64,
+    format: IqFormat,
+    center_freq: u32,
+    sample_rate: u32,
+}
+
+impl Header {
+    fn write(&self, out: &mut [u8]) {
+        out[0..4].copy_from_slice(MAGIC);
+        out[4] = VERSION;
+        out[5] = format_code(self.format);
+        out[6..8].fill(0);
+        out[8..16].copy_from_slice(&self.sequence.to_le_bytes());
+        out[16..20].copy_from_slice(&self.center_freq.to_le_bytes());
+        out[20..24].copy_from_slice(&self.sample_rate.to_le_bytes());
+    }
+
+    fn parse(datagram: &[u8]) -> Option<Header> {
+        if datagram.len() < UDP_HEADER_LEN || &datagram[0..4] != MAGIC || datagram[4] != VERSION {
+            return None;
+        }
+        let format = match datagram[5] {
+            0 => IqFormat::Cu8,
+            1 => IqFormat::Cs16,
+            2 => IqFormat::Cf32,
+            _ => return None,
+        };
+        let u32_at = |at: usize| u32::from_le_bytes(datagram[at..at + 4].try_into().unwrap());
+        Some(Header {
+            sequence: u64::from_le_bytes(datagram[8..16].try_into().unwrap()),
+            format,
+            center_freq: u32_at(16),
+            sample_rate: u32_at(20),
+        })
+    }
+}
+
+/// Streams IQ samples over UDP.
+///
+/// Samples are sent in datagrams of whole samples, by default small enough
+/// not to be fragmented on Ethernet, each starting with a 24-byte
+/// little-endian header:
+///
+/// | Offset | Size | Field                                           |
+/// |--------|------|-------------------------------------------------|
+/// | 0      | 4    | `RDIQ`                                          |
+/// | 4      | 1    | Version, 1                                      |
+/// | 5      | 1    | Format: 0 for `cu8`, 1 for `cs16`, 2 for `cf32` |
+/// | 6      | 2    | Reserved, 0                                     |
+/// | 8      | 8    | Sequence number, from 0                         |
+/// | 16     | 4    | Center frequency in Hz                          |
+/// | 20     | 4    | Sample rate in Hz                               |
+///
+/// so a receiver can spot lost datagrams and follow retuning. GNU Radio's
+/// UDP source can skip the header with a header size of 24, or it can be
+/// left out with `with_header`. Sending to a multicast group reaches every
+/// `UdpSource` that joined it.
+///
+/// ```no_run
+/// use radion::{Device, IqFormat, UdpSink};
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(100_000_000)?;
+/// device.set_sample_rate(1_024_000)?;
+/// device.reset_buffer()?;
+///
+/// let mut sink = UdpSink::connect("239.1.2.3:5000", IqFormat::Cu8, 1_024_000, 100_000_000)?;
+/// loop {
+///     sink.send(&device.read_sync(16_384)?)?;
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct UdpSink {
+    socket: UdpSocket,
+    header: Header,
+    with_header: bool,
+    max_datagram: usize,
+    /// Bytes of an incomplete sample held for the next `send`.
+    partial: Vec<u8>,
+    datagram: Vec<u8>,
+}
+
+impl UdpSink {
+    /// Create a sink sending to one address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to send to, unicast, broadcast or multicast.
+    /// * `format` - The sample format of the data passed to `send`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `UdpSink` if the socket could be set up, otherwise an `Error`.
+    pub fn connect(
+        addr: impl ToSocketAddrs,
+        format: IqFormat,
+        sample_rate: u32,
+        center_freq: u32,
+    ) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::System(ErrorKind::InvalidInput.into()))?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        if let IpAddr::V4(ip) = addr.ip() {
+            if ip.is_broadcast() {
+                socket.set_broadcast(true)?;
+            }
+        }
+        socket.connect(addr)?;
+        Ok(UdpSink {
+            socket,
+            header: Header {
+                sequence: 0,
+                format,
+                center_freq,
+                sample_rate,
+            },
+            with_header: true,
+            max_datagram: DEFAULT_DATAGRAM,
+            partial: Vec::new(),
+            datagram: Vec::new(),
+        })
+    }
+
+    /// Set whether datagrams start with the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - `false` to send bare samples, for receivers that
+    ///   expect nothing else.
+    ///
+    /// # Returns
+    ///
+    /// The `UdpSink` with the header on or off.
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.with_header = header;
+        self
+    }
+
+    /// Set the largest datagram sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The most bytes per datagram, header included. Larger
+    ///   datagrams cost less per sample but are fragmented beyond the
+    ///   path's MTU, and a lost fragment loses the whole datagram.
+    ///
+    /// # Returns
+    ///
+    /// The `UdpSink` with the new limit.
+    pub fn with_max_datagram(mut self, bytes: usize) -> Self {
+        assert!(
+            (UDP_HEADER_LEN + 8..=MAX_DATAGRAM).contains(&bytes),
+            "datagrams must be 32 to 65507 bytes"
+        );
+        self.max_datagram = bytes;
+        self
+    }
+
+    /// Set how many routers multicast datagrams may cross.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - The time to live, 1 by default, which keeps them on the
+    ///   local network.
+    ///
+    /// # Returns
+    ///
+    /// The `UdpSink` with the new TTL if it could be set, otherwise an
+    /// `Error`.
+    pub fn with_multicast_ttl(self, ttl: u32) -> Result<Self> {
+        match self.socket.peer_addr()? {
+            SocketAddr::V4(_) => self.socket.set_multicast_ttl_v4(ttl)?,
+            // IPv6 has no portable hop limit setter in std; the default
+            // of 1 applies.
+            SocketAddr::V6(_) => return Err(Error::NotSupported),
+        }
+        Ok(self)
+    }
+
+    /// Get the address datagrams are sent to.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.peer_addr()?)
+    }
+
+    /// Get the number of datagrams sent so far, the next sequence number.
+    pub fn datagrams_sent(&self) -> u64 {
+        self.header.sequence
+    }
+
+    /// Record a change of center frequency, sent in the following headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The new center frequency in Hz.
+    pub fn retune(&mut self, center_freq: u32) {
+        self.header.center_freq = center_freq;
+    }
+
+    /// Record a change of sample rate, sent in the following headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The new sample rate in Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.header.sample_rate = sample_rate;
+    }
+
+    /// Send samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Raw samples in the format given to `connect`. A
+    ///   trailing incomplete sample is held until the next call.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if every datagram was sent, otherwise an `Error`.
+    /// Datagrams nobody is listening for are not an error.
+    pub fn send(&mut self, samples: &[u8]) -> Result<()> {
+        let size = self.header.format.sample_size();
+        let header_len = if self.with_header { UDP_HEADER_LEN } else { 0 };
+        let per_datagram = (self.max_datagram - header_len) / size * size;
+
+        let mut data = std::mem::take(&mut self.partial);
+        data.extend_from_slice(samples);
+        let whole = data.len() / size * size;
+        for chunk in data[..whole].chunks(per_datagram) {
+            self.datagram.clear();
+            self.datagram.resize(header_len, 0);
+            if self.with_header {
+                self.header.write(&mut self.datagram);
+            }
+            self.datagram.extend_from_slice(chunk);
+            match self.socket.send(&self.datagram) {
+                Ok(_) => {}
+                // Reported for an earlier datagram when no one is bound to
+                // a unicast port.
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.header.sequence += 1;
+        }
+        data.drain(..whole);
+        self.partial = data;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "pipeline")]
+impl crate::pipeline::Block for UdpSink {
+    type In = Vec<u8>;
+    type Out = Vec<u8>;
+
+    /// Sends each chunk and passes it on unchanged.
+    fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+        self.send(&input)?;
+        Ok(Some(input))
+    }
+}
+
+/// Receives IQ samples streamed by `UdpSink`.
+///
+/// Datagrams are converted to the dongle's native unsigned 8-bit format
+/// whatever format they were sent in, and the center frequency and sample
+/// rate are taken from their headers. Datagrams without a valid header are
+/// ignored. A gap in the sequence numbers is filled with as many mid-scale
+/// samples as the lost datagrams would have carried, so the stream keeps
+/// its timing, and counted by `lost`; datagrams arriving late are dropped.
+///
+/// The sender can't be tuned from here: `tune`, `set_sample_rate` and
+/// `set_gain` return `Error::NotSupported`.
+///
+/// ```no_run
+/// use radion::{Frequency, SampleRate, SdrSource, UdpSource};
+///
+/// let mut source = UdpSource::bind_multicast("239.1.2.3".parse().unwrap(), 5000)?;
+/// let mut buf = vec![0; 16_384];
+/// source.read_exact(&mut buf)?;
+/// println!(
+///     "{} at {}, {} datagrams lost",
+///     Frequency::from(source.center_freq()?),
+///     SampleRate::from(source.sample_rate()?),
+///     source.lost()
+/// );
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct UdpSource {
+    socket: UdpSocket,
+    format: Option<IqFormat>,
+    center_freq: u32,
+    sample_rate: u32,
+    /// The sequence number expected next.
+    next: Option<u64>,
+    lost: u64,
+    /// Converted samples not yet read, from `start`.
+    pending: Vec<u8>,
+    start: usize,
+    datagram: Vec<u8>,
+}
+
+impl UdpSource {
+    /// Receive datagrams sent to a local address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to bind, e.g. `0.0.0.0:5000`.
+    ///
+    /// # Returns
+    ///
+    /// A new `UdpSource` if the socket could be bound, otherwise an `Error`.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(Self::from_socket(UdpSocket::bind(addr)?))
+    }
+
+    /// Receive datagrams sent to a multicast group, on the default
+    /// interface.
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - The multicast group to join.
+    /// * `port` - The port the sender sends to.
+    ///
+    /// # Returns
+    ///
+    /// A new `UdpSource` if the socket could be bound and the group joined,
+    /// otherwise an `Error`.
+    pub fn bind_multicast(group: IpAddr, port: u16) -> Result<Self> {
+        let socket = match group {
+            IpAddr::V4(group) => {
+                let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+                socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+                socket
+            }
+            IpAddr::V6(group) => {
+                let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, port))?;
+                socket.join_multicast_v6(&group, 0)?;
+                socket
+            }
+        };
+        Ok(Self::from_socket(socket))
+    }
+
+    fn from_socket(socket: UdpSocket) -> Self {
+        UdpSource {
+            socket,
+            format: None,
+            center_freq: 0,
+            sample_rate: 0,
+            next: None,
+            lost: 0,
+            pending: Vec::new(),
+            start: 0,
+            datagram: vec![0; MAX_DATAGRAM],
+        }
+    }
+
+    /// Give up on reads after a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long `read` waits for a datagram before returning
+    ///   `Error::Timeout`, or `None` to wait forever, the default.
+    ///
+    /// # Returns
+    ///
+    /// The `UdpSource` with the new timeout if it could be set, otherwise
+    /// an `Error`.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> Result<Self> {
+        self.socket.set_read_timeout(timeout)?;
+        Ok(self)
+    }
+
+    /// Get the local address the source is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Get the sample format the stream is sent in, once a datagram has
+    /// arrived.
+    pub fn format(&self) -> Option<IqFormat> {
+        self.format
+    }
+
+    /// Get the number of datagrams lost so far.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Wait for the next datagram and queue its samples.
+    fn receive(&mut self) -> Result<()> {
+        loop {
+            let n = match self.socket.recv(&mut self.datagram) {
+                Ok(n) => n,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Err(Error::Timeout)
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let Some(header) = Header::parse(&self.datagram[..n]) else {
+                continue;
+            };
+            let size = header.format.sample_size();
+            let samples = (n - UDP_HEADER_LEN) / size;
+            match self.next {
+                Some(next) if header.sequence < next => continue,
+                Some(next) if header.sequence > next => {
+                    let missing = header.sequence - next;
+                    self.lost += missing;
+                    let fill = (missing as usize).saturating_mul(samples * 2);
+                    self.pending.resize(self.pending.len() + fill, 128);
+                }
+                _ => {}
+            }
+            self.next = Some(header.sequence + 1);
+            self.format = Some(header.format);
+            self.center_freq = header.center_freq;
+            self.sample_rate = header.sample_rate;
+
+            let body = &self.datagram[UDP_HEADER_LEN..UDP_HEADER_LEN + samples * size];
+            let at = self.pending.len();
+            self.pending.resize(at + samples * 2, 0);
+            convert(header.format, body, &mut self.pending[at..]);
+            return Ok(());
+        }
+    }
+}
+
+impl SdrSource for UdpSource {
+    fn tune(&mut self, _freq_hz: u32) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Get the center frequency from the latest datagram, 0 before the
+    /// first.
+    fn center_freq(&self) -> Result<u32> {
+        Ok(self.center_freq)
+    }
+
+    fn set_sample_rate(&mut self, _rate_hz: u32) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Get the sample rate from the latest datagram, 0 before the first.
+    fn sample_rate(&self) -> Result<u32> {
+        Ok(self.sample_rate)
+    }
+
+    fn set_gain(&mut self, _gain: Option<i32>) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Read samples, waiting for a datagram only if none are queued.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let want = buf.len() / 2 * 2;
+        if want == 0 {
+            return Ok(0);
+        }
+        if self.start == self.pending.len() {
+            self.pending.clear();
+            self.start = 0;
+            while self.pending.is_empty() {
+                self.receive()?;
+            }
+        }
+        let n = want.min(self.pending.len() - self.start);
+        buf[..n].copy_from_slice(&self.pending[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+/// A frequency displayed in engineering notation, e.g. `162.4 MHz`.
+///
+/// The formatter precision sets the number of decimals (`{:.3}` gives
+/// `162.400 MHz`); without one, up to six decimals are shown with trailing
+/// zeros removed.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct Frequency(pub f64);
+
+/// A sample rate displayed in engineering notation, e.g. `2.4 Msps`.
+///
+/// Precision works as for `Frequency`.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct SampleRate(pub f64);
+
+impl From<u32> for Frequency {
+    fn from(hz: u32) -> Self {
+        Frequency(hz as f64)
+    }
+}
+
+impl From<u32> for SampleRate {
+    fn from(sps: u32) -> Self {
+        SampleRate(sps as f64)
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_engineering(f, self.0, "Hz")
+    }
+}
+
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_engineering(f, self.0, "sps")
+    }
+}
+
+fn write_engineering(f: &mut fmt::Formatter<'_>, value: f64, unit: &str) -> fmt::Result {
+    let (scaled, prefix) = match value.abs() {
+        v if v >= 1e9 => (value / 1e9, "G"),
+        v if v >= 1e6 => (value / 1e6, "M"),
+        v if v >= 1e3 => (value / 1e3, "k"),
+        _ => (value, ""),
+    };
+    let number = match f.precision() {
+        Some(precision) => format!("{:.*}", precision, scaled),
+        None => {
+            let s = format!("{:.6}", scaled);
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+    };
+    let s = format!("{} {}{}", number, prefix, unit);
+    // Formatter::pad would apply the precision again as a maximum width.
+    match (f.width(), f.align()) {
+        (Some(w), Some(fmt::Alignment::Right)) => write!(f, "{:>w$}", s),
+        (Some(w), Some(fmt::Alignment::Center)) => write!(f, "{:^w$}", s),
+        (Some(w), _) => write!(f, "{:<w$}", s),
+        (None, _) => f.write_str(&s),
+    }
+}
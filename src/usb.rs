@@ -0,0 +1,190 @@
+use crate::sym;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+/// The USB vendor and product IDs librtlsdr opens, in its own order.
+const KNOWN_DEVICES: [(u16, u16); 42] = [
+    (0x0bda, 0x2832),
+    (0x0bda, 0x2838),
+    (0x0413, 0x6680),
+    (0x0413, 0x6f0f),
+    (0x0458, 0x707f),
+    (0x0ccd, 0x00a9),
+    (0x0ccd, 0x00b3),
+    (0x0ccd, 0x00b4),
+    (0x0ccd, 0x00b5),
+    (0x0ccd, 0x00b7),
+    (0x0ccd, 0x00b8),
+    (0x0ccd, 0x00b9),
+    (0x0ccd, 0x00c0),
+    (0x0ccd, 0x00c6),
+    (0x0ccd, 0x00d3),
+    (0x0ccd, 0x00d7),
+    (0x0ccd, 0x00e0),
+    (0x1554, 0x5020),
+    (0x15f4, 0x0131),
+    (0x15f4, 0x0133),
+    (0x185b, 0x0620),
+    (0x185b, 0x0650),
+    (0x185b, 0x0680),
+    (0x1b80, 0xd393),
+    (0x1b80, 0xd394),
+    (0x1b80, 0xd395),
+    (0x1b80, 0xd397),
+    (0x1b80, 0xd398),
+    (0x1b80, 0xd39d),
+    (0x1b80, 0xd3a4),
+    (0x1b80, 0xd3a8),
+    (0x1b80, 0xd3af),
+    (0x1b80, 0xd3b0),
+    (0x1d19, 0x1101),
+    (0x1d19, 0x1102),
+    (0x1d19, 0x1103),
+    (0x1d19, 0x1104),
+    (0x1f4d, 0xa803),
+    (0x1f4d, 0xb803),
+    (0x1f4d, 0xc803),
+    (0x1f4d, 0xd286),
+    (0x1f4d, 0xd803),
+];
+
+/// `struct libusb_device_descriptor`.
+#[repr(C)]
+#[derive(Default)]
+struct DeviceDescriptor {
+    length: u8,
+    descriptor_type: u8,
+    bcd_usb: u16,
+    device_class: u8,
+    device_sub_class: u8,
+    device_protocol: u8,
+    max_packet_size: u8,
+    id_vendor: u16,
+    id_product: u16,
+    bcd_device: u16,
+    manufacturer: u8,
+    product: u8,
+    serial_number: u8,
+    num_configurations: u8,
+}
+
+type InitT = unsafe extern "system" fn(ctx: *mut *mut c_void) -> c_int;
+type ExitT = unsafe extern "system" fn(ctx: *mut c_void);
+type GetDeviceListT =
+    unsafe extern "system" fn(ctx: *mut c_void, list: *mut *mut *mut c_void) -> isize;
+type FreeDeviceListT = unsafe extern "system" fn(list: *mut *mut c_void, unref: c_int);
+type GetDeviceDescriptorT =
+    unsafe extern "system" fn(dev: *mut c_void, desc: *mut DeviceDescriptor) -> c_int;
+type GetBusNumberT = unsafe extern "system" fn(dev: *mut c_void) -> u8;
+type GetPortNumbersT =
+    unsafe extern "system" fn(dev: *mut c_void, ports: *mut u8, len: c_int) -> c_int;
+
+/// The libusb functions needed to walk the bus, taken from the libusb
+/// librtlsdr was loaded with so that linking never depends on it.
+struct Libusb {
+    init: InitT,
+    exit: ExitT,
+    get_device_list: GetDeviceListT,
+    free_device_list: FreeDeviceListT,
+    get_device_descriptor: GetDeviceDescriptorT,
+    get_bus_number: GetBusNumberT,
+    get_port_numbers: GetPortNumbersT,
+}
+
+impl Libusb {
+    fn load() -> Option<Self> {
+        unsafe {
+            Some(Libusb {
+                init: mem::transmute::<*mut c_void, InitT>(sym::lookup(c"libusb_init")?),
+                exit: mem::transmute::<*mut c_void, ExitT>(sym::lookup(c"libusb_exit")?),
+                get_device_list: mem::transmute::<*mut c_void, GetDeviceListT>(sym::lookup(
+                    c"libusb_get_device_list",
+                )?),
+                free_device_list: mem::transmute::<*mut c_void, FreeDeviceListT>(sym::lookup(
+                    c"libusb_free_device_list",
+                )?),
+                get_device_descriptor: mem::transmute::<*mut c_void, GetDeviceDescriptorT>(
+                    sym::lookup(c"libusb_get_device_descriptor")?,
+                ),
+                get_bus_number: mem::transmute::<*mut c_void, GetBusNumberT>(sym::lookup(
+                    c"libusb_get_bus_number",
+                )?),
+                get_port_numbers: mem::transmute::<*mut c_void, GetPortNumbersT>(sym::lookup(
+                    c"libusb_get_port_numbers",
+                )?),
+            })
+        }
+    }
+}
+
+/// Get the USB path of every device librtlsdr can see.
+///
+/// librtlsdr numbers the devices it recognises in libusb's enumeration
+/// order, so the paths come out in device index order.
+///
+/// # Returns
+///
+/// The paths, or `None` if libusb could not be found or enumerated.
+pub fn device_paths() -> Option<Vec<String>> {
+    let usb = Libusb::load()?;
+    let mut paths = Vec::new();
+    unsafe {
+        let mut ctx = ptr::null_mut();
+        if (usb.init)(&mut ctx) != 0 {
+            return None;
+        }
+        let mut list = ptr::null_mut();
+        let count = (usb.get_device_list)(ctx, &mut list);
+        if count < 0 {
+            (usb.exit)(ctx);
+            return None;
+        }
+        for i in 0..count as usize {
+            let dev = *list.add(i);
+            let mut desc = DeviceDescriptor::default();
+            if (usb.get_device_descriptor)(dev, &mut desc) != 0
+                || !is_known(desc.id_vendor, desc.id_product)
+            {
+                continue;
+            }
+            // USB 3 allows hubs seven deep.
+            let mut ports = [0u8; 7];
+            let depth = (usb.get_port_numbers)(dev, ports.as_mut_ptr(), ports.len() as c_int);
+            let ports = &ports[..depth.max(0) as usize];
+            paths.push(format_path((usb.get_bus_number)(dev), ports));
+        }
+        (usb.free_device_list)(list, 1);
+        (usb.exit)(ctx);
+    }
+    Some(paths)
+}
+
+fn is_known(vendor: u16, product: u16) -> bool {
+    KNOWN_DEVICES.contains(&(vendor, product))
+}
+
+/// Write a path the way Linux names USB devices in sysfs: the bus number, a
+/// dash and the port on each hub from the root down, e.g. `1-4.2`.
+fn format_path(bus: u8, ports: &[u8]) -> String {
+    let ports: Vec<String> = ports.iter().map(|port| port.to_string()).collect();
+    format!("{}-{}", bus, ports.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_paths_like_sysfs() {
+        assert_eq!(format_path(1, &[4]), "1-4");
+        assert_eq!(format_path(3, &[1, 2, 4]), "3-1.2.4");
+    }
+
+    #[test]
+    fn recognises_librtlsdr_devices() {
+        assert!(is_known(0x0bda, 0x2838));
+        assert!(is_known(0x1f4d, 0xd803));
+        assert!(!is_known(0x0bda, 0x2831));
+    }
+}
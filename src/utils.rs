@@ -1,50 +1,123 @@
 use crate::error::{Error, Result};
-use crate::hw_info::HwInfo;
+use crate::hw_info::{EepromWarning, HwInfo};
 
 pub const MAX_STR_SIZE: usize = 35;
 pub const STR_OFFSET_START: usize = 0x09;
 pub const EEPROM_SIZE: usize = 256;
+pub const EEPROM_PAGE_SIZE: usize = 8;
 
-/// Parse string descriptors from EEPROM data.
+/// Parse a single string descriptor from EEPROM data.
 ///
 /// # Arguments
 ///
 /// * `data` - EEPROM data.
+/// * `pos` - The offset of the descriptor.
 ///
 /// # Returns
 ///
-/// A tuple containing the manufacturer, product, and serial strings.
-pub fn parse_string_descriptors(data: &[u8]) -> Result<(String, String, String)> {
-    let mut pos = STR_OFFSET_START;
-    let mut strings = Vec::new();
+/// The decoded string and the length of the descriptor in bytes.
+pub fn parse_string_descriptor(data: &[u8], pos: usize) -> Result<(String, usize)> {
+    if pos + 2 > data.len() {
+        return Err(Error::StringDescriptorInvalid);
+    }
+    let length = data[pos] as usize;
+    if length < 2 || pos + length > data.len() {
+        return Err(Error::StringDescriptorInvalid);
+    }
+    if data[pos + 1] != 0x03 {
+        return Err(Error::StringDescriptorInvalid);
+    }
+    let s = String::from_utf16(
+        &data[pos + 2..pos + length]
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect::<Vec<_>>(),
+    )
+    .map_err(|_| Error::StringDescriptorInvalid)?;
+    Ok((s, length))
+}
 
-    for _ in 0..3 {
-        if pos + 2 > data.len() {
-            return Err(Error::StringDescriptorInvalid);
-        }
-        let length = data[pos] as usize;
-        if length < 2 || pos + length > data.len() {
-            return Err(Error::StringDescriptorInvalid);
-        }
-        if data[pos + 1] != 0x03 {
-            return Err(Error::StringDescriptorInvalid);
+/// Parse hardware information from EEPROM data.
+///
+/// Corrupted headers and string descriptors do not fail the parse; they are
+/// reported in `HwInfo::warnings` and the affected fields are recovered as far
+/// as possible.
+///
+/// # Arguments
+///
+/// * `data` - EEPROM data.
+///
+/// # Returns
+///
+/// The hardware information, or an `Error` if `data` is too short to hold a
+/// header.
+pub fn parse_hw_info(data: &[u8]) -> Result<HwInfo> {
+    if data.len() < STR_OFFSET_START {
+        return Err(Error::NoValidEEPROMHeader);
+    }
+
+    let mut info = HwInfo {
+        vendor_id: u16::from_le_bytes([data[2], data[3]]),
+        product_id: u16::from_le_bytes([data[4], data[5]]),
+        have_serial: data[6] == 0xA5,
+        remote_wakeup: (data[7] & 0x01) != 0,
+        enable_ir: (data[7] & 0x02) != 0,
+        ..Default::default()
+    };
+    if data[0] != 0x28 || data[1] != 0x32 {
+        info.warnings.push(EepromWarning::InvalidHeader);
+    }
+
+    let mut pos = STR_OFFSET_START;
+    for i in 0..3 {
+        match parse_string_descriptor(data, pos) {
+            Ok((s, length)) => {
+                match i {
+                    0 => info.manufact = s,
+                    1 => info.product = s,
+                    _ => info.serial = s,
+                }
+                pos += length;
+            }
+            Err(_) => {
+                info.warnings
+                    .push(EepromWarning::InvalidStringDescriptor(i));
+                break;
+            }
         }
-        let s = String::from_utf16(
-            &data[pos + 2..pos + length]
-                .chunks(2)
-                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
-                .collect::<Vec<_>>(),
-        )
-        .map_err(|_| Error::StringDescriptorInvalid)?;
-        strings.push(s);
-        pos += length;
     }
 
-    if strings.len() == 3 {
-        Ok((strings[0].clone(), strings[1].clone(), strings[2].clone()))
-    } else {
-        Err(Error::Unknown)
+    Ok(info)
+}
+
+/// Serialize hardware information to an EEPROM image.
+///
+/// # Arguments
+///
+/// * `info` - Hardware information.
+///
+/// # Returns
+///
+/// An `EEPROM_SIZE` byte image.
+pub fn serialize_hw_info(info: &HwInfo) -> Result<Vec<u8>> {
+    let mut data = vec![0u8; EEPROM_SIZE];
+
+    data[0] = 0x28;
+    data[1] = 0x32;
+    data[2..4].copy_from_slice(&info.vendor_id.to_le_bytes());
+    data[4..6].copy_from_slice(&info.product_id.to_le_bytes());
+    data[6] = if info.have_serial { 0xA5 } else { 0x00 };
+    data[7] = 0x00;
+    if info.remote_wakeup {
+        data[7] |= 0x01;
     }
+    if info.enable_ir {
+        data[7] |= 0x02;
+    }
+
+    serialize_string_descriptors(&mut data, info)?;
+
+    Ok(data)
 }
 
 /// Serialize string descriptors to EEPROM data.
@@ -53,7 +126,7 @@ pub fn parse_string_descriptors(data: &[u8]) -> Result<(String, String, String)>
 ///
 /// * `data` - EEPROM data.
 /// * `info` - Hardware information.
-pub fn serialize_string_descriptors(data: &mut Vec<u8>, info: &HwInfo) -> Result<()> {
+pub fn serialize_string_descriptors(data: &mut [u8], info: &HwInfo) -> Result<()> {
     let mut pos = STR_OFFSET_START;
     let strings = [&info.manufact, &info.product, &info.serial];
 
@@ -78,3 +151,79 @@ pub fn serialize_string_descriptors(data: &mut Vec<u8>, info: &HwInfo) -> Result
 
     Ok(())
 }
+
+/// A point in time broken down into UTC calendar fields.
+// Only used by some features.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct UtcTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanos: u32,
+}
+
+#[allow(dead_code)]
+impl UtcTime {
+    /// Break a `SystemTime` down into UTC calendar fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time to convert. Times before 1970 are clamped to the
+    ///   epoch.
+    ///
+    /// # Returns
+    ///
+    /// The calendar fields, using the proleptic Gregorian calendar.
+    pub fn from_system_time(time: std::time::SystemTime) -> Self {
+        let since = time
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let secs = since.as_secs() as i64;
+        let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+        // Days to civil date, after Howard Hinnant's `civil_from_days`.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        UtcTime {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+            nanos: since.subsec_nanos(),
+        }
+    }
+
+    /// Combine the calendar fields into a `SystemTime`.
+    ///
+    /// # Returns
+    ///
+    /// The time, clamped to the epoch for dates before 1970.
+    pub fn to_system_time(self) -> std::time::SystemTime {
+        // Civil date to days, after Howard Hinnant's `days_from_civil`.
+        let month = self.month as i64;
+        let y = self.year - (month <= 2) as i64;
+        let era = y.div_euclid(400);
+        let yoe = y.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        let secs =
+            days * 86_400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        std::time::UNIX_EPOCH + std::time::Duration::new(secs.max(0) as u64, self.nanos)
+    }
+}
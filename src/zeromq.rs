@@ -0,0 +1,651 @@
+use std::time::Duration;
+
+use num_complex::Complex;
+
+use crate::error::{Error, Result};
+use crate::file_source::{convert, IqFormat};
+use crate::source::SdrSource;
+
+/// Start of the tag header GNU Radio puts before the samples.
+const HEADER_MAGIC: u16 = 0x5FF0;
+
+/// Tag header version.
+const HEADER_VERSION: u8 = 0x01;
+
+/// Bytes in a `gr_complex`.
+const ITEM_SIZE: usize = 8;
+
+/// How long unsent messages may hold up dropping a sink.
+const LINGER_MS: i32 = 1000;
+
+/// Tag key GNU Radio sources use for the center frequency.
+const FREQ_KEY: &str = "rx_freq";
+
+/// Tag key GNU Radio sources use for the sample rate.
+const RATE_KEY: &str = "rx_rate";
+
+// PMT serialization type codes.
+const PST_TRUE: u8 = 0x00;
+const PST_FALSE: u8 = 0x01;
+const PST_SYMBOL: u8 = 0x02;
+const PST_INT32: u8 = 0x03;
+const PST_DOUBLE: u8 = 0x04;
+const PST_COMPLEX: u8 = 0x05;
+const PST_NULL: u8 = 0x06;
+const PST_PAIR: u8 = 0x07;
+const PST_VECTOR: u8 = 0x08;
+const PST_DICT: u8 = 0x09;
+const PST_UNIFORM_VECTOR: u8 = 0x0a;
+const PST_UINT64: u8 = 0x0b;
+const PST_TUPLE: u8 = 0x0c;
+const PST_INT64: u8 = 0x0d;
+
+/// The value of a stream tag.
+///
+/// Scalars map to their PMT types; pairs, vectors, dictionaries and the
+/// like are read past but not kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagValue {
+    /// The empty list, `PMT_NIL`.
+    Null,
+    /// `PMT_T` or `PMT_F`.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned 64-bit integer.
+    Uint(u64),
+    /// A double, as frequencies and rates are tagged.
+    Double(f64),
+    /// A complex double.
+    Complex(Complex<f64>),
+    /// A symbol, PMT's string.
+    Symbol(String),
+    /// A composite value, not decoded.
+    Other,
+}
+
+/// A GNU Radio stream tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZmqTag {
+    /// The absolute sample the tag is attached to.
+    pub offset: u64,
+    /// The tag's key, e.g. `rx_freq`.
+    pub key: String,
+    /// The tag's value.
+    pub value: TagValue,
+}
+
+/// A message received by `ZmqSource`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZmqChunk {
+    /// The absolute offset of the first sample, if the sender passes tags.
+    pub offset: Option<u64>,
+    /// The tags on the samples.
+    pub tags: Vec<ZmqTag>,
+    /// The samples.
+    pub samples: Vec<Complex<f32>>,
+}
+
+fn put_symbol(out: &mut Vec<u8>, symbol: &str) {
+    out.push(PST_SYMBOL);
+    out.extend_from_slice(&(symbol.len() as u16).to_be_bytes());
+    out.extend_from_slice(symbol.as_bytes());
+}
+
+fn put_value(out: &mut Vec<u8>, value: &TagValue) {
+    match value {
+        TagValue::Null | TagValue::Other => out.push(PST_NULL),
+        TagValue::Bool(true) => out.push(PST_TRUE),
+        TagValue::Bool(false) => out.push(PST_FALSE),
+        TagValue::Int(v) => match i32::try_from(*v) {
+            Ok(v) => {
+                out.push(PST_INT32);
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+            Err(_) => {
+                out.push(PST_INT64);
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+        },
+        TagValue::Uint(v) => {
+            out.push(PST_UINT64);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        TagValue::Double(v) => {
+            out.push(PST_DOUBLE);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        TagValue::Complex(v) => {
+            out.push(PST_COMPLEX);
+            out.extend_from_slice(&v.re.to_be_bytes());
+            out.extend_from_slice(&v.im.to_be_bytes());
+        }
+        TagValue::Symbol(s) => put_symbol(out, s),
+    }
+}
+
+/// Reads serialized PMTs.
+struct PmtReader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> PmtReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at.checked_add(n)?)?;
+        self.at += n;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn be<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn le_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn value(&mut self) -> Option<TagValue> {
+        Some(match self.u8()? {
+            PST_TRUE => TagValue::Bool(true),
+            PST_FALSE => TagValue::Bool(false),
+            PST_NULL => TagValue::Null,
+            PST_SYMBOL => {
+                let len = u16::from_be_bytes(self.be()?) as usize;
+                TagValue::Symbol(String::from_utf8_lossy(self.take(len)?).into_owned())
+            }
+            PST_INT32 => TagValue::Int(i32::from_be_bytes(self.be()?) as i64),
+            PST_INT64 => TagValue::Int(i64::from_be_bytes(self.be()?)),
+            PST_UINT64 => TagValue::Uint(u64::from_be_bytes(self.be()?)),
+            PST_DOUBLE => TagValue::Double(f64::from_be_bytes(self.be()?)),
+            PST_COMPLEX => TagValue::Complex(Complex::new(
+                f64::from_be_bytes(self.be()?),
+                f64::from_be_bytes(self.be()?),
+            )),
+            PST_PAIR | PST_DICT => {
+                self.value()?;
+                self.value()?;
+                TagValue::Other
+            }
+            PST_VECTOR | PST_TUPLE => {
+                let len = u32::from_be_bytes(self.be()?);
+                for _ in 0..len {
+                    self.value()?;
+                }
+                TagValue::Other
+            }
+            PST_UNIFORM_VECTOR => {
+                let size = match self.u8()? {
+                    0x00 | 0x01 => 1,
+                    0x02 | 0x03 => 2,
+                    0x04 | 0x05 | 0x08 => 4,
+                    0x06 | 0x07 | 0x09 | 0x0a => 8,
+                    0x0b => 16,
+                    _ => return None,
+                };
+                let len = u32::from_be_bytes(self.be()?) as usize;
+                let pad = self.u8()? as usize;
+                self.take(pad)?;
+                self.take(len.checked_mul(size)?)?;
+                TagValue::Other
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The tag header in front of the samples: the first sample's offset, the
+/// tags and the header's length.
+fn parse_header(message: &[u8]) -> Option<(u64, Vec<ZmqTag>, usize)> {
+    let mut reader = PmtReader {
+        data: message,
+        at: 0,
+    };
+    if u16::from_le_bytes(reader.take(2)?.try_into().ok()?) != HEADER_MAGIC
+        || reader.u8()? != HEADER_VERSION
+    {
+        return None;
+    }
+    let offset = reader.le_u64()?;
+    let count = reader.le_u64()?;
+    let mut tags = Vec::new();
+    for _ in 0..count {
+        let offset = reader.le_u64()?;
+        let key = match reader.value()? {
+            TagValue::Symbol(key) => key,
+            _ => return None,
+        };
+        let value = reader.value()?;
+        // The source id.
+        reader.value()?;
+        tags.push(ZmqTag { offset, key, value });
+    }
+    Some((offset, tags, reader.at))
+}
+
+/// Publishes IQ samples on a ZeroMQ PUB socket, the way GNU Radio's ZMQ PUB
+/// Sink does for a complex stream.
+///
+/// Each `send` is one message of `gr_complex` samples. With `pass_tags`
+/// on, the message starts with GNU Radio's tag header: the absolute offset
+/// of the first sample and any stream tags, with `rx_freq` and `rx_rate`
+/// tags at the start of the stream and after every `retune` or
+/// `set_sample_rate`, as hardware sources add them. The receiving ZMQ SUB
+/// Source must have Pass Tags set the same way, and its Key set to the
+/// sink's key, if any.
+///
+/// ```no_run
+/// use radion::samples::{to_complex_f32, Scaling};
+/// use radion::{Device, ZmqSink};
+///
+/// let device = Device::new(0)?;
+/// device.set_center_freq(100_000_000)?;
+/// device.set_sample_rate(2_048_000)?;
+/// device.reset_buffer()?;
+///
+/// // GNU Radio: ZMQ SUB Source, complex, tcp://host:5555, Pass Tags on.
+/// let mut sink = ZmqSink::bind("tcp://*:5555", 2_048_000, 100_000_000)?.with_pass_tags(true);
+/// loop {
+///     sink.send(&to_complex_f32(&device.read_sync(65_536)?, Scaling::Normalized))?;
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct ZmqSink {
+    // Declared before the context, which waits for it to close on drop.
+    socket: zmq::Socket,
+    _context: zmq::Context,
+    key: Option<Vec<u8>>,
+    pass_tags: bool,
+    /// Samples sent so far.
+    offset: u64,
+    pending: Vec<ZmqTag>,
+    message: Vec<u8>,
+}
+
+impl ZmqSink {
+    /// Bind a PUB socket.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The ZeroMQ endpoint to bind, e.g. `tcp://*:5555`.
+    /// * `sample_rate` - The sample rate in Hz.
+    /// * `center_freq` - The center frequency in Hz.
+    ///
+    /// # Returns
+    ///
+    /// A new `ZmqSink` without tags if the socket could be bound, otherwise
+    /// an `Error`.
+    pub fn bind(endpoint: &str, sample_rate: u32, center_freq: u32) -> Result<Self> {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::PUB).map_err(zmq_error)?;
+        socket.set_linger(LINGER_MS).map_err(zmq_error)?;
+        socket.bind(endpoint).map_err(zmq_error)?;
+        let mut sink = ZmqSink {
+            socket,
+            _context: context,
+            key: None,
+            pass_tags: false,
+            offset: 0,
+            pending: Vec::new(),
+            message: Vec::new(),
+        };
+        sink.retune(center_freq);
+        sink.set_sample_rate(sample_rate);
+        Ok(sink)
+    }
+
+    /// Set whether messages carry GNU Radio's tag header.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass_tags` - Whether to send the header, off by default as in
+    ///   GNU Radio.
+    ///
+    /// # Returns
+    ///
+    /// The `ZmqSink` with tags on or off.
+    pub fn with_pass_tags(mut self, pass_tags: bool) -> Self {
+        self.pass_tags = pass_tags;
+        self
+    }
+
+    /// Send every message after a key frame, for subscribers to filter on.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key, as set on GNU Radio 3.9 and later ZMQ blocks.
+    ///
+    /// # Returns
+    ///
+    /// The `ZmqSink` with the key.
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.as_bytes().to_vec());
+        self
+    }
+
+    /// Get the endpoint the socket is bound to, with any wildcard port
+    /// resolved.
+    pub fn endpoint(&self) -> Result<String> {
+        self.socket
+            .get_last_endpoint()
+            .map_err(zmq_error)?
+            .map_err(|_| Error::InvalidMetadata("endpoint is not UTF-8".to_string()))
+    }
+
+    /// Get the number of samples sent so far.
+    pub fn samples_sent(&self) -> u64 {
+        self.offset
+    }
+
+    /// Tag the next sample sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The tag's key.
+    /// * `value` - The tag's value.
+    pub fn tag(&mut self, key: &str, value: TagValue) {
+        self.pending.push(ZmqTag {
+            offset: self.offset,
+            key: key.to_string(),
+            value,
+        });
+    }
+
+    /// Record a change of center frequency, tagged as `rx_freq`.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - The new center frequency in Hz.
+    pub fn retune(&mut self, center_freq: u32) {
+        self.tag(FREQ_KEY, TagValue::Double(center_freq as f64));
+    }
+
+    /// Record a change of sample rate, tagged as `rx_rate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The new sample rate in Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.tag(RATE_KEY, TagValue::Double(sample_rate as f64));
+    }
+
+    /// Publish samples as one message.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The samples to send.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if the message was queued, otherwise an `Error`.
+    /// Messages nobody is subscribed to are dropped, as ever with PUB.
+    pub fn send(&mut self, samples: &[Complex<f32>]) -> Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        self.message.clear();
+        if self.pass_tags {
+            self.message.extend_from_slice(&HEADER_MAGIC.to_le_bytes());
+            self.message.push(HEADER_VERSION);
+            self.message.extend_from_slice(&self.offset.to_le_bytes());
+            self.message
+                .extend_from_slice(&(self.pending.len() as u64).to_le_bytes());
+            for tag in &self.pending {
+                self.message.extend_from_slice(&tag.offset.to_le_bytes());
+                put_symbol(&mut self.message, &tag.key);
+                put_value(&mut self.message, &tag.value);
+                self.message.push(PST_FALSE);
+            }
+        }
+        self.pending.clear();
+        for sample in samples {
+            self.message.extend_from_slice(&sample.re.to_le_bytes());
+            self.message.extend_from_slice(&sample.im.to_le_bytes());
+        }
+        if let Some(key) = &self.key {
+            self.socket
+                .send(&key[..], zmq::SNDMORE)
+                .map_err(zmq_error)?;
+        }
+        self.socket.send(&self.message[..], 0).map_err(zmq_error)?;
+        self.offset += samples.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "pipeline")]
+impl crate::pipeline::Block for ZmqSink {
+    type In = Vec<Complex<f32>>;
+    type Out = Vec<Complex<f32>>;
+
+    /// Publishes each chunk and passes it on unchanged.
+    fn process(&mut self, input: Self::In) -> Result<Option<Self::Out>> {
+        self.send(&input)?;
+        Ok(Some(input))
+    }
+}
+
+/// Receives IQ samples from a ZeroMQ PUB socket, such as GNU Radio's ZMQ PUB
+/// Sink for a complex stream or a `ZmqSink`.
+///
+/// `recv` returns each message with its tags. Through `SdrSource`, samples
+/// are converted to the dongle's unsigned 8-bit format and the center
+/// frequency and sample rate follow the `rx_freq` and `rx_rate` tags, so
+/// a GNU Radio flowgraph can feed radion's decoders; `tune`,
+/// `set_sample_rate` and `set_gain` return `Error::NotSupported`.
+///
+/// ```no_run
+/// use radion::ZmqSource;
+///
+/// let mut source = ZmqSource::connect("tcp://127.0.0.1:5555")?.with_pass_tags(true);
+/// loop {
+///     let chunk = source.recv()?;
+///     for tag in &chunk.tags {
+///         println!("{} = {:?} at {}", tag.key, tag.value, tag.offset);
+///     }
+/// }
+/// # Ok::<(), radion::Error>(())
+/// ```
+pub struct ZmqSource {
+    socket: zmq::Socket,
+    _context: zmq::Context,
+    keyed: bool,
+    pass_tags: bool,
+    center_freq: u32,
+    sample_rate: u32,
+    /// Converted samples not yet read, from `start`.
+    pending: Vec<u8>,
+    start: usize,
+}
+
+impl ZmqSource {
+    /// Connect a SUB socket, subscribed to everything.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The ZeroMQ endpoint of the publisher, e.g.
+    ///   `tcp://127.0.0.1:5555`.
+    ///
+    /// # Returns
+    ///
+    /// A new `ZmqSource` expecting no tags if the socket could be set up,
+    /// otherwise an `Error`.
+    pub fn connect(endpoint: &str) -> Result<Self> {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::SUB).map_err(zmq_error)?;
+        socket.set_linger(0).map_err(zmq_error)?;
+        socket.connect(endpoint).map_err(zmq_error)?;
+        socket.set_subscribe(b"").map_err(zmq_error)?;
+        Ok(ZmqSource {
+            socket,
+            _context: context,
+            keyed: false,
+            pass_tags: false,
+            center_freq: 0,
+            sample_rate: 0,
+            pending: Vec::new(),
+            start: 0,
+        })
+    }
+
+    /// Set whether messages carry GNU Radio's tag header.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass_tags` - Whether to expect the header, as set on the sender.
+    ///
+    /// # Returns
+    ///
+    /// The `ZmqSource` expecting tags or not.
+    pub fn with_pass_tags(mut self, pass_tags: bool) -> Self {
+        self.pass_tags = pass_tags;
+        self
+    }
+
+    /// Receive only messages sent after a key frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key the sender was given.
+    ///
+    /// # Returns
+    ///
+    /// The `ZmqSource` subscribed to the key if successful, otherwise an
+    /// `Error`.
+    pub fn with_key(mut self, key: &str) -> Result<Self> {
+        self.socket.set_unsubscribe(b"").map_err(zmq_error)?;
+        self.socket
+            .set_subscribe(key.as_bytes())
+            .map_err(zmq_error)?;
+        self.keyed = true;
+        Ok(self)
+    }
+
+    /// Give up on receiving after a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait for a message before returning
+    ///   `Error::Timeout`, or `None` to wait forever, the default.
+    ///
+    /// # Returns
+    ///
+    /// The `ZmqSource` with the new timeout if it could be set, otherwise
+    /// an `Error`.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> Result<Self> {
+        let ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        self.socket.set_rcvtimeo(ms).map_err(zmq_error)?;
+        Ok(self)
+    }
+
+    /// Wait for the next message.
+    ///
+    /// # Returns
+    ///
+    /// The samples and tags if successful, `Error::Timeout` if the timeout
+    /// passed, or `Error::InvalidMetadata` if tags were expected but the
+    /// header is missing or malformed.
+    pub fn recv(&mut self) -> Result<ZmqChunk> {
+        let (offset, tags, body) = self.recv_raw()?;
+        let samples = body
+            .chunks_exact(ITEM_SIZE)
+            .map(|b| {
+                Complex::new(
+                    f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                    f32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+                )
+            })
+            .collect();
+        Ok(ZmqChunk {
+            offset,
+            tags,
+            samples,
+        })
+    }
+
+    /// Receive a message, split into its header fields and samples.
+    fn recv_raw(&mut self) -> Result<(Option<u64>, Vec<ZmqTag>, Vec<u8>)> {
+        let mut parts = self.socket.recv_multipart(0).map_err(zmq_error)?;
+        // A keyed message is the key and then the payload.
+        let message = if self.keyed && parts.len() > 1 {
+            parts.swap_remove(1)
+        } else {
+            parts.swap_remove(0)
+        };
+        let (offset, tags, body) = if self.pass_tags {
+            let (offset, tags, len) = parse_header(&message)
+                .ok_or_else(|| Error::InvalidMetadata("bad ZMQ tag header".to_string()))?;
+            (Some(offset), tags, message[len..].to_vec())
+        } else {
+            (None, Vec::new(), message)
+        };
+        for tag in &tags {
+            match (tag.key.as_str(), &tag.value) {
+                (FREQ_KEY, TagValue::Double(f)) => self.center_freq = *f as u32,
+                (RATE_KEY, TagValue::Double(r)) => self.sample_rate = *r as u32,
+                _ => {}
+            }
+        }
+        Ok((offset, tags, body))
+    }
+}
+
+impl SdrSource for ZmqSource {
+    fn tune(&mut self, _freq_hz: u32) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Get the center frequency from the latest `rx_freq` tag, 0 before
+    /// the first.
+    fn center_freq(&self) -> Result<u32> {
+        Ok(self.center_freq)
+    }
+
+    fn set_sample_rate(&mut self, _rate_hz: u32) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Get the sample rate from the latest `rx_rate` tag, 0 before the
+    /// first.
+    fn sample_rate(&self) -> Result<u32> {
+        Ok(self.sample_rate)
+    }
+
+    fn set_gain(&mut self, _gain: Option<i32>) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Read samples, waiting for a message only if none are queued.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let want = buf.len() / 2 * 2;
+        if want == 0 {
+            return Ok(0);
+        }
+        while self.start == self.pending.len() {
+            let (_, _, body) = self.recv_raw()?;
+            let samples = body.len() / ITEM_SIZE;
+            self.pending.resize(samples * 2, 0);
+            convert(
+                IqFormat::Cf32,
+                &body[..samples * ITEM_SIZE],
+                &mut self.pending,
+            );
+            self.start = 0;
+        }
+        let n = want.min(self.pending.len() - self.start);
+        buf[..n].copy_from_slice(&self.pending[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
+
+fn zmq_error(e: zmq::Error) -> Error {
+    match e {
+        zmq::Error::EAGAIN => Error::Timeout,
+        e => Error::System(std::io::Error::other(e)),
+    }
+}
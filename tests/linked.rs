@@ -0,0 +1,46 @@
+//! Checks against whichever librtlsdr the build linked: the system one, or
+//! a bundled one from `RTLSDR_LIB_DIR`, static with `RTLSDR_STATIC=1`.
+//! They pass with or without dongles plugged in and drivers bound, so they
+//! can run on build machines.
+
+use radion::{Device, Error};
+
+#[test]
+fn lists_every_device() {
+    let count = Device::get_device_count();
+    let devices = Device::list();
+    assert_eq!(devices.len(), count as usize);
+    for (index, info) in devices.iter().enumerate() {
+        assert_eq!(info.index, index as u32);
+        if !info.usb_path.is_empty() {
+            let (bus, ports) = info.usb_path.split_once('-').unwrap();
+            assert!(bus.parse::<u8>().is_ok(), "{}", info.usb_path);
+            assert!(
+                ports.split('.').all(|port| port.parse::<u8>().is_ok()),
+                "{}",
+                info.usb_path
+            );
+        }
+    }
+    assert_eq!(radion::capabilities().devices, count);
+}
+
+#[test]
+fn opening_a_missing_device_is_not_a_driver_problem() {
+    let err = Device::new(Device::get_device_count()).err().unwrap();
+    assert!(!matches!(err, Error::DriverMissing), "{}", err);
+    assert!(Device::get_device_name(Device::get_device_count()).is_none());
+}
+
+#[test]
+fn selectors_report_missing_devices() {
+    assert!(matches!(
+        Device::open_by_serial("no such serial"),
+        Err(Error::NotFound)
+    ));
+    assert!(matches!(Device::open_by_usb_path(""), Err(Error::NotFound)));
+    assert!(matches!(
+        Device::open_by_usb_path("255-255"),
+        Err(Error::NotFound)
+    ));
+}
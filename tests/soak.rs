@@ -0,0 +1,93 @@
+//! Checks of the steady-state allocation tracking. They install
+//! `CountingAlloc` as the global allocator, so they get a test binary of
+//! their own rather than swapping the allocator under the unit tests.
+#![cfg(all(feature = "soak", feature = "mock"))]
+
+use radion::soak::{
+    heap_stats, resident_bytes, thread_allocations, CountingAlloc, Monitor, SteadyState,
+};
+use radion::{BufferPool, MockDevice, MockSignal};
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// A device playing back a ramp through every byte value.
+fn ramp() -> MockDevice {
+    MockDevice::new().with_signal(MockSignal::Data((0..=255).collect()))
+}
+
+#[test]
+fn counts_allocations_of_the_calling_thread() {
+    let before = thread_allocations().unwrap();
+    let buf = std::hint::black_box(vec![0u8; 4096]);
+    assert_eq!(thread_allocations().unwrap(), before + 1);
+    assert!(heap_stats().unwrap().peak_bytes >= buf.len());
+
+    let mut steady = SteadyState::enter();
+    steady.check();
+    drop(buf);
+    steady.check();
+    assert_eq!(steady.violations(), 0);
+
+    #[cfg(target_os = "linux")]
+    assert!(resident_bytes().unwrap() > 0);
+}
+
+#[test]
+fn runs_without_allocating_once_warm() {
+    let mut sum = 0u64;
+    let mut reads = 0;
+    let mut held = Vec::new();
+    let mut reported = 0;
+    let report = Monitor::new(ramp(), BufferPool::new(4, 512))
+        .with_warmup(1)
+        .with_report(std::time::Duration::ZERO, |_| reported += 1)
+        .run(|buf| {
+            // Sized on the first read, then reused.
+            if held.capacity() == 0 {
+                held.reserve(2);
+            }
+            sum += buf.iter().map(|&b| b as u64).sum::<u64>();
+            held.push(buf);
+            if held.len() == 2 {
+                held.clear();
+            }
+            reads += 1;
+            Ok(reads < 200)
+        })
+        .unwrap();
+    // Each read of 512 bytes plays the ramp twice.
+    assert_eq!(sum, 200 * 255 * 256);
+    assert_eq!(reported, 200);
+    assert_eq!(report.steady_allocations, 0);
+    let pool = report.pool.clone().unwrap();
+    assert_eq!((pool.acquired, pool.starved, pool.peak_in_use), (200, 0, 2));
+    assert!(report.heap.is_some());
+    assert!(report
+        .to_string()
+        .contains("pool 0/4 buffers (peak 2, 0 starved)"));
+}
+
+#[test]
+fn stops_when_the_consumer_says_so() {
+    let mut seen = 0;
+    Monitor::new(ramp(), BufferPool::new(2, 16))
+        .run(|_| {
+            seen += 1;
+            Ok(seen < 5)
+        })
+        .unwrap();
+    assert_eq!(seen, 5);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "allocations in the steady state")]
+fn panics_on_allocation_in_the_steady_state() {
+    let _ = Monitor::new(ramp(), BufferPool::new(2, 16))
+        .with_warmup(4)
+        .run(|buf| {
+            std::hint::black_box(buf.to_vec());
+            Ok(true)
+        });
+}